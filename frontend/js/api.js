@@ -0,0 +1,20 @@
+/**
+ * REST API helper - adds credentials for gateways with API_KEYS/JWT_SECRET set
+ */
+
+const Api = (function() {
+    // Key from the page's ?api_key= (remembered for later visits)
+    const pageKey = new URLSearchParams(window.location.search).get('api_key');
+    if (pageKey) {
+        localStorage.setItem('apiKey', pageKey);
+    }
+
+    // fetch() with the X-API-Key header when a key is known
+    function get(path) {
+        const key = localStorage.getItem('apiKey');
+        const headers = key ? { 'X-API-Key': key } : {};
+        return fetch(path, { headers });
+    }
+
+    return { get };
+})();
@@ -15,6 +15,9 @@
     const toggleListBtn = document.getElementById('toggle-list');
     const listPanel = document.getElementById('list-panel');
 
+    // Last full position_update per aircraft, the base for position_delta
+    let lastPositionUpdates = {};
+
     // Initialize map
     FlightMap.init('map');
 
@@ -80,7 +83,8 @@
             const hasPosition = ac.lat && ac.lon;
             const row = document.createElement('tr');
             row.className = (ac.icao === selected ? 'selected' : '') +
-                (!hasPosition ? ' no-position' : '');
+                (!hasPosition ? ' no-position' : '') +
+                (FlightMap.isEmergency(ac) ? ' emergency' : '');
 
             // Format position
             let positionText = '-';
@@ -107,10 +111,10 @@
                 speedText = Math.round(ac.speed) + ' kts';
             }
 
-            // Format heading
-            let headingText = '-';
-            if (ac.heading !== undefined && ac.heading !== null) {
-                headingText = Math.round(ac.heading) + '°';
+            // Format track
+            let trackText = '-';
+            if (ac.track !== undefined && ac.track !== null) {
+                trackText = Math.round(ac.track) + '°';
             }
 
             row.innerHTML = `
@@ -120,7 +124,7 @@
                 <td class="altitude-cell">${altitudeText}</td>
                 <td class="vrate-cell ${ac.vrate > 0 ? 'climbing' : ac.vrate < 0 ? 'descending' : ''}">${vrateText}</td>
                 <td class="speed-cell">${speedText}</td>
-                <td class="heading-cell">${headingText}</td>
+                <td class="heading-cell">${trackText}</td>
                 <td class="squawk-cell">${ac.squawk || '-'}</td>
                 <td class="device-cell">${ac.device_id || '-'}</td>
             `;
@@ -136,11 +140,34 @@
         });
     }
 
+    // Apply a full position_update message
+    function applyPositionUpdate(data) {
+        const update = {
+            icao: data.icao,
+            device_id: data.device_id,
+            emergency: data.emergency || null,
+            seen: data.time,
+        };
+        // Fields the gateway has never received are left out; keep what we have
+        ['lat', 'lon', 'altitude', 'speed', 'track', 'magnetic_heading', 'vrate'].forEach(key => {
+            if (data[key] !== undefined && data[key] !== null) update[key] = data[key];
+        });
+        if (data.squawk) update.squawk = data.squawk;
+        // Metadata and route (only sent when known)
+        ['registration', 'aircraft_type', 'model', 'operator', 'origin', 'destination'].forEach(key => {
+            if (data[key]) update[key] = data[key];
+        });
+        FlightMap.updateAircraft(update);
+        updateAircraftCount();
+        throttledListUpdate();
+    }
+
     // Handle WebSocket message
     function handleMessage(data) {
         switch (data.type) {
             case 'initial':
                 // Initial aircraft list
+                lastPositionUpdates = {};
                 if (data.aircraft && Array.isArray(data.aircraft)) {
                     data.aircraft.forEach(ac => {
                         FlightMap.updateAircraft(ac);
@@ -160,20 +187,25 @@
                 break;
 
             case 'position_update':
-                // Real-time position update from LISTEN/NOTIFY
-                FlightMap.updateAircraft({
-                    icao: data.icao,
-                    device_id: data.device_id,
-                    lat: data.lat,
-                    lon: data.lon,
-                    altitude: data.altitude,
-                    speed: data.speed,
-                    heading: data.heading,
-                    vrate: data.vrate,
-                    seen: data.time,
-                });
-                updateAircraftCount();
-                throttledListUpdate();
+                // Real-time position update (also a delta keyframe)
+                lastPositionUpdates[data.icao] = data;
+                applyPositionUpdate(data);
+                break;
+
+            case 'position_delta': {
+                // Only the fields changed since the last update for this aircraft
+                const base = lastPositionUpdates[data.icao];
+                if (!base) break;
+                const merged = { ...base, ...data, type: 'position_update' };
+                lastPositionUpdates[data.icao] = merged;
+                applyPositionUpdate(merged);
+                break;
+            }
+
+            case 'snapshot_diff':
+                // Position updates and removals batched over the last interval
+                (data.updates || []).forEach(update => handleMessage(update));
+                (data.removed || []).forEach(icao => handleMessage({ type: 'aircraft_removed', icao }));
                 break;
 
             case 'signal':
@@ -192,10 +224,38 @@
                 }
                 break;
 
+            case 'alert':
+                // Watchlist rule matched
+                showAlert(`${data.rule_name}: ${data.callsign || data.icao}` +
+                    (data.registration ? ` (${data.registration})` : ''), data.icao);
+                break;
+
+            case 'geofence':
+                // Geofence entry/exit
+                if (data.event === 'enter') {
+                    showAlert(`${data.callsign || data.icao} entered ${data.geofence_name}`, data.icao);
+                }
+                break;
+
+            case 'emergency':
+                // Emergency squawk started or ended
+                FlightMap.updateAircraft({
+                    icao: data.icao,
+                    squawk: data.squawk,
+                    emergency: data.active ? data.emergency : null,
+                });
+                if (data.active) {
+                    showAlert(`EMERGENCY ${data.squawk} (${data.description}): ` +
+                        `${data.callsign || data.icao}`, data.icao);
+                }
+                throttledListUpdate();
+                break;
+
             case 'remove':
             case 'aircraft_removed':
                 // Aircraft removed (timed out)
                 if (data.icao) {
+                    delete lastPositionUpdates[data.icao];
                     FlightMap.removeAircraft(data.icao);
                     updateAircraftCount();
                     updateAircraftList();
@@ -204,6 +264,19 @@
         }
     }
 
+    // Show an alert toast for a few seconds (click selects the aircraft)
+    function showAlert(text, icao) {
+        const toast = document.createElement('div');
+        toast.className = 'alert-toast';
+        toast.textContent = text;
+        toast.addEventListener('click', function() {
+            FlightMap.selectAircraft(icao);
+            toast.remove();
+        });
+        document.body.appendChild(toast);
+        setTimeout(() => toast.remove(), 8000);
+    }
+
     // Throttle list updates to prevent excessive DOM updates
     function throttledListUpdate() {
         if (!handleMessage.listUpdatePending) {
@@ -256,7 +329,7 @@
     }, 30000);
 
     // Fetch initial data via REST API as backup
-    fetch('/api/aircraft')
+    Api.get('/api/aircraft')
         .then(response => response.json())
         .then(aircraft => {
             aircraft.forEach(ac => {
@@ -28,13 +28,33 @@ const FlightMap = (function() {
         return '#ef4444';  // Red - very high
     }
 
+    // Emergency squawks: 7500 hijack, 7600 radio failure, 7700 general
+    const EMERGENCY_SQUAWKS = ['7500', '7600', '7700'];
+
+    function isEmergency(aircraft) {
+        if (!aircraft) return false;
+        if (aircraft.emergency !== undefined) return !!aircraft.emergency;
+        return EMERGENCY_SQUAWKS.includes(aircraft.squawk);
+    }
+
+    // Aircraft fields come from transponders and the aircraft database,
+    // so escape them before building HTML
+    function escapeHtml(value) {
+        return String(value)
+            .replace(/&/g, '&amp;')
+            .replace(/</g, '&lt;')
+            .replace(/>/g, '&gt;')
+            .replace(/"/g, '&quot;')
+            .replace(/'/g, '&#39;');
+    }
+
     // Create aircraft icon
-    function createAircraftIcon(heading, altitude) {
-        const color = getAltitudeColor(altitude);
+    function createAircraftIcon(heading, altitude, emergency) {
+        const color = emergency ? '#ff1744' : getAltitudeColor(altitude);
         const rotation = heading || 0;
 
         return L.divIcon({
-            className: 'aircraft-marker',
+            className: 'aircraft-marker' + (emergency ? ' emergency' : ''),
             html: `<div class="aircraft-icon" style="transform: rotate(${rotation}deg); color: ${color};">
                 ${aircraftSvg}
             </div>`,
@@ -53,12 +73,23 @@ const FlightMap = (function() {
             attributionControl: true,
         });
 
-        // Add tile layer (dark theme)
-        L.tileLayer('https://{s}.basemaps.cartocdn.com/dark_all/{z}/{x}/{y}{r}.png', {
-            attribution: '&copy; <a href="https://www.openstreetmap.org/copyright">OpenStreetMap</a> &copy; <a href="https://carto.com/attributions">CARTO</a>',
-            subdomains: 'abcd',
-            maxZoom: 19
-        }).addTo(map);
+        // Add tile layer: the gateway's tile proxy when it has one,
+        // otherwise the public dark theme
+        fetch('/tiles/tiles.json')
+            .then(response => response.ok ? response.json() : Promise.reject())
+            .then(tileJson => {
+                L.tileLayer(tileJson.tiles[0], {
+                    attribution: tileJson.attribution,
+                    maxZoom: tileJson.maxzoom
+                }).addTo(map);
+            })
+            .catch(() => {
+                L.tileLayer('https://{s}.basemaps.cartocdn.com/dark_all/{z}/{x}/{y}{r}.png', {
+                    attribution: '&copy; <a href="https://www.openstreetmap.org/copyright">OpenStreetMap</a> &copy; <a href="https://carto.com/attributions">CARTO</a>',
+                    subdomains: 'abcd',
+                    maxZoom: 19
+                }).addTo(map);
+            });
 
         // Map click handler - deselect aircraft
         map.on('click', function() {
@@ -86,7 +117,9 @@ const FlightMap = (function() {
         }
 
         const position = [aircraft.lat, aircraft.lon];
-        const icon = createAircraftIcon(aircraft.heading, aircraft.altitude);
+        // Point the icon along the track; the heading is the next best thing
+        const icon = createAircraftIcon(aircraft.track ?? aircraft.magnetic_heading, aircraft.altitude,
+            isEmergency(aircraftData[icao]));
 
         // Track recent positions for auto-trail (last 5 unique positions)
         if (!aircraftRecentPositions[icao]) {
@@ -146,7 +179,8 @@ const FlightMap = (function() {
             marker.bindTooltip(function() {
                 const a = marker.aircraft;
                 return `<div class="aircraft-label">
-                    <strong>${a.callsign || a.icao}</strong><br>
+                    <strong>${escapeHtml(a.callsign || a.icao)}</strong><br>
+                    ${a.aircraft_type ? escapeHtml(a.aircraft_type) + (a.registration ? ' / ' + escapeHtml(a.registration) : '') + '<br>' : ''}
                     ${a.altitude ? a.altitude.toLocaleString() + ' ft' : '-'}
                 </div>`;
             }, {
@@ -251,15 +285,26 @@ const FlightMap = (function() {
             aircraft.callsign || aircraft.icao;
         document.getElementById('info-icao').textContent = aircraft.icao;
         document.getElementById('info-callsign').textContent = aircraft.callsign || '-';
+        document.getElementById('info-registration').textContent = aircraft.registration || '-';
+        document.getElementById('info-type').textContent =
+            aircraft.model || aircraft.aircraft_type || '-';
+        document.getElementById('info-operator').textContent = aircraft.operator || '-';
+        document.getElementById('info-route').textContent =
+            aircraft.origin && aircraft.destination ?
+                `${aircraft.origin} → ${aircraft.destination}` : '-';
         document.getElementById('info-altitude').textContent =
             aircraft.altitude ? aircraft.altitude.toLocaleString() + ' ft' : '-';
         document.getElementById('info-speed').textContent =
             aircraft.speed ? Math.round(aircraft.speed) + ' kts' : '-';
-        document.getElementById('info-heading').textContent =
-            aircraft.heading ? Math.round(aircraft.heading) + '°' : '-';
+        document.getElementById('info-track').textContent =
+            aircraft.track != null ? Math.round(aircraft.track) + '°' : '-';
+        document.getElementById('info-mag-heading').textContent =
+            aircraft.magnetic_heading != null ? Math.round(aircraft.magnetic_heading) + '° M' : '-';
         document.getElementById('info-vrate').textContent =
             aircraft.vrate ? aircraft.vrate + ' fpm' : '-';
-        document.getElementById('info-squawk').textContent = aircraft.squawk || '-';
+        const squawkEl = document.getElementById('info-squawk');
+        squawkEl.textContent = aircraft.squawk || '-';
+        squawkEl.classList.toggle('emergency', isEmergency(aircraft));
         document.getElementById('info-position').textContent =
             aircraft.lat && aircraft.lon ?
                 `${aircraft.lat.toFixed(4)}, ${aircraft.lon.toFixed(4)}` : '-';
@@ -287,7 +332,7 @@ const FlightMap = (function() {
         }
 
         // Fetch trail from API
-        fetch(`/api/aircraft/${icao}/trail?minutes=30`)
+        Api.get(`/api/aircraft/${icao}/trail?minutes=30`)
             .then(response => response.json())
             .then(trail => {
                 if (trail.length > 0) {
@@ -388,5 +433,6 @@ const FlightMap = (function() {
         getAircraftCountByDevice,
         getSelected,
         cleanupStale,
+        isEmergency,
     };
 })();
@@ -15,6 +15,12 @@ const SDRStatus = (function() {
     let frequency;
     let sampleRate;
     let gain;
+    let sdrVersion;
+    let sdrUptime;
+    let sdrClockOffset;
+    let sdrErrorCorrection;
+    let sdrBlanker;
+    let sdrPositionFilter;
     // Signal section
     let sdrSignalDbfs;
     let sdrNoiseDbfs;
@@ -34,12 +40,31 @@ const SDRStatus = (function() {
     let sdrFrames;
     let sdrCrcErrors;
     let sdrCorrected;
+    let sdrDuplicates;
     let sdrSuccessRate;
+    let sdrDfRatio;
+    let messageTypeChart;
+    let messageTypeCtx;
 
     // Signal history for chart (per device)
     const signalHistory = {};  // device_id -> array of data points
     const maxHistoryLength = 60; // 60 seconds of data
 
+    // Cumulative downlink format counts reported over the last minute,
+    // per device, for the message type breakdown
+    const formatHistory = {};  // device_id -> [{time, formats, typeCodes}]
+    const formatWindowMs = 60000;
+
+    // Names of the downlink formats and type codes shown in the breakdown
+    const formatNames = {
+        0: 'DF0 ACAS', 4: 'DF4 Alt', 5: 'DF5 Ident', 11: 'DF11 All-call',
+        16: 'DF16 ACAS', 17: 'DF17 ADS-B', 18: 'DF18 TIS-B', 20: 'DF20 Comm-B',
+        21: 'DF21 Comm-B', 24: 'DF24 Comm-D'
+    };
+
+    // Host clocks further off the gateway's are highlighted (as the gateway flags them)
+    const maxClockSkewMs = 2000;
+
     // Real-time meter history (short buffer for waterfall effect)
     const meterHistory = [];
     const maxMeterHistory = 60;  // 60 data points for waterfall
@@ -76,6 +101,12 @@ const SDRStatus = (function() {
         frequency = document.getElementById('sdr-frequency');
         sampleRate = document.getElementById('sdr-sample-rate');
         gain = document.getElementById('sdr-gain');
+        sdrVersion = document.getElementById('sdr-version');
+        sdrUptime = document.getElementById('sdr-uptime');
+        sdrClockOffset = document.getElementById('sdr-clock-offset');
+        sdrErrorCorrection = document.getElementById('sdr-error-correction');
+        sdrBlanker = document.getElementById('sdr-blanker');
+        sdrPositionFilter = document.getElementById('sdr-position-filter');
         // Signal section
         sdrSignalDbfs = document.getElementById('sdr-signal-dbfs');
         sdrNoiseDbfs = document.getElementById('sdr-noise-dbfs');
@@ -93,12 +124,19 @@ const SDRStatus = (function() {
         sdrFrames = document.getElementById('sdr-frames');
         sdrCrcErrors = document.getElementById('sdr-crc-errors');
         sdrCorrected = document.getElementById('sdr-corrected');
+        sdrDuplicates = document.getElementById('sdr-duplicates');
         sdrSuccessRate = document.getElementById('sdr-success-rate');
+        sdrDfRatio = document.getElementById('sdr-df-ratio');
+        messageTypeChart = document.getElementById('message-type-chart');
 
         if (signalChart) {
             chartCtx = signalChart.getContext('2d');
         }
 
+        if (messageTypeChart) {
+            messageTypeCtx = messageTypeChart.getContext('2d');
+        }
+
         if (signalMeter) {
             meterCtx = signalMeter.getContext('2d');
             // Initialize meter display
@@ -115,6 +153,9 @@ const SDRStatus = (function() {
         // Start animation loop for smooth meter updates
         startAnimationLoop();
 
+        // Fill the chart with the last minute before live updates arrive
+        loadSignalHistory();
+
         // Start periodic status updates (fallback for when no live signal data)
         fetchSDRStatus();
         updateInterval = setInterval(fetchSDRStatus, 5000);
@@ -209,6 +250,67 @@ const SDRStatus = (function() {
         if (gain && data.gain_db !== undefined) {
             gain.textContent = data.gain_db.toFixed(1) + ' dB';
         }
+
+        updateDecoderConfig(data);
+    }
+
+    /**
+     * Show the capture host's version, uptime and decoder setup (from a
+     * status message or the status API; older hosts send none)
+     */
+    function updateDecoderConfig(data) {
+        if (sdrVersion) {
+            sdrVersion.textContent = data.software_version || '-';
+        }
+
+        if (sdrUptime) {
+            sdrUptime.textContent = data.uptime_secs != null ? formatUptime(data.uptime_secs) : '-';
+        }
+
+        // Gateway minus host clock; far off means the host's time isn't set
+        if (sdrClockOffset) {
+            const offset = data.clock_offset_ms;
+            sdrClockOffset.textContent = offset != null ? (offset > 0 ? '+' : '') + offset + ' ms' : '-';
+            sdrClockOffset.className = 'sdr-value' +
+                (offset != null && Math.abs(offset) > maxClockSkewMs ? ' status-stale' : '');
+        }
+
+        const decoder = data.decoder;
+        if (sdrErrorCorrection) {
+            if (!decoder) {
+                sdrErrorCorrection.textContent = '-';
+            } else {
+                let text = decoder.max_corrected_bits + ' bit' +
+                    (decoder.max_corrected_bits === 1 ? '' : 's');
+                if (decoder.max_false_positive_rate) {
+                    text += ' (FP limit ' + (decoder.max_false_positive_rate * 100).toFixed(0) + '%)';
+                }
+                sdrErrorCorrection.textContent = text;
+            }
+        }
+
+        if (sdrBlanker) {
+            sdrBlanker.textContent = !decoder ? '-' :
+                decoder.noise_blanker_factor ? decoder.noise_blanker_factor + '× noise' : 'Off';
+        }
+
+        if (sdrPositionFilter) {
+            sdrPositionFilter.textContent = !decoder ? '-' :
+                'NIC ≥ ' + decoder.min_position_nic + ', NACp ≥ ' + decoder.min_position_nac_p +
+                (decoder.track_smoothing ? ', smoothed' : '');
+        }
+    }
+
+    /**
+     * Format seconds as days, hours and minutes
+     */
+    function formatUptime(secs) {
+        const days = Math.floor(secs / 86400);
+        const hours = Math.floor((secs % 86400) / 3600);
+        const minutes = Math.floor((secs % 3600) / 60);
+        if (days > 0) return days + 'd ' + hours + 'h';
+        if (hours > 0) return hours + 'h ' + minutes + 'm';
+        return minutes + 'm';
     }
 
     /**
@@ -263,10 +365,16 @@ const SDRStatus = (function() {
         targetSignal = data.signal_dbfs || -60;
         targetNoise = data.noise_dbfs || -60;
 
+        // Keep the last minute of message type counts
+        if (data.downlink_formats) {
+            recordMessageTypes(devId, data);
+        }
+
         // Update display if this is the current device
         if (devId === currentDeviceId) {
             updateLiveSignalDisplay(data);
             drawSignalChart();
+            drawMessageTypes();
         }
 
         // Update status indicator to show live data (use "Connected" for active state)
@@ -327,6 +435,10 @@ const SDRStatus = (function() {
             sdrCorrected.textContent = formatNumber(data.corrected_frames || 0);
         }
 
+        if (sdrDuplicates) {
+            sdrDuplicates.textContent = formatNumber(data.duplicate_frames || 0);
+        }
+
         // Calculate and display success rate
         if (sdrSuccessRate) {
             const preambles = data.preambles_detected || 0;
@@ -343,6 +455,103 @@ const SDRStatus = (function() {
         updateSignalBars(data.signal_dbfs, data.noise_dbfs);
     }
 
+    /**
+     * Add a report's cumulative message type counts to the device's history
+     */
+    function recordMessageTypes(devId, data) {
+        const now = Date.now();
+        const history = formatHistory[devId] || (formatHistory[devId] = []);
+        const formats = data.downlink_formats;
+
+        // Counts going down means the capture host restarted
+        const last = history[history.length - 1];
+        if (last && Object.keys(last.formats).some(df =>
+                (formats[df] ? formats[df].frames : 0) < last.formats[df].frames)) {
+            history.length = 0;
+        }
+
+        history.push({ time: now, formats, typeCodes: data.type_codes || {} });
+        while (history.length > 2 && now - history[1].time >= formatWindowMs) {
+            history.shift();
+        }
+    }
+
+    /**
+     * Frames per downlink format (and type code) over the recorded window
+     */
+    function messageTypeBreakdown(devId) {
+        const history = formatHistory[devId];
+        if (!history || history.length < 2) return null;
+
+        const first = history[0];
+        const last = history[history.length - 1];
+        const formats = Object.entries(last.formats).map(([df, counts]) => {
+            const before = first.formats[df] || { frames: 0, corrected: 0 };
+            return {
+                df: Number(df),
+                frames: counts.frames - before.frames,
+                corrected: counts.corrected - before.corrected
+            };
+        }).filter(f => f.frames > 0);
+        const total = formats.reduce((sum, f) => sum + f.frames, 0);
+        return total > 0 ? { formats, total } : null;
+    }
+
+    /**
+     * Draw the share of each downlink format as horizontal bars, the
+     * error-corrected part shaded, with the DF17/DF11/corrected ratio
+     */
+    function drawMessageTypes() {
+        if (!messageTypeCtx) return;
+
+        const width = messageTypeChart.width;
+        const height = messageTypeChart.height;
+        messageTypeCtx.fillStyle = '#1a1a2e';
+        messageTypeCtx.fillRect(0, 0, width, height);
+
+        const breakdown = currentDeviceId ? messageTypeBreakdown(currentDeviceId) : null;
+        if (!breakdown) {
+            if (sdrDfRatio) {
+                sdrDfRatio.textContent = '-';
+            }
+            return;
+        }
+        const { formats, total } = breakdown;
+        const share = df => {
+            const format = formats.find(f => f.df === df);
+            return format ? format.frames / total : 0;
+        };
+        const corrected = formats.reduce((sum, f) => sum + f.corrected, 0) / total;
+        if (sdrDfRatio) {
+            sdrDfRatio.textContent = [share(17), share(11), corrected]
+                .map(r => (r * 100).toFixed(0) + '%')
+                .join(' / ');
+        }
+
+        // Busiest formats first, as many as fit
+        const rowHeight = 15;
+        const labelWidth = 90;
+        const rows = formats
+            .sort((a, b) => b.frames - a.frames)
+            .slice(0, Math.floor(height / rowHeight));
+        messageTypeCtx.font = '9px monospace';
+        rows.forEach((format, i) => {
+            const y = i * rowHeight + 2;
+            const barWidth = (format.frames / total) * (width - labelWidth - 35);
+            const fixedWidth = barWidth * (format.corrected / format.frames);
+
+            messageTypeCtx.fillStyle = '#888';
+            messageTypeCtx.fillText(formatNames[format.df] || 'DF' + format.df, 2, y + 9);
+            messageTypeCtx.fillStyle = 'rgba(74, 222, 128, 0.8)';
+            messageTypeCtx.fillRect(labelWidth, y, barWidth - fixedWidth, rowHeight - 4);
+            messageTypeCtx.fillStyle = 'rgba(251, 191, 36, 0.8)';
+            messageTypeCtx.fillRect(labelWidth + barWidth - fixedWidth, y, fixedWidth, rowHeight - 4);
+            messageTypeCtx.fillStyle = '#888';
+            messageTypeCtx.fillText(
+                (format.frames / total * 100).toFixed(0) + '%', labelWidth + barWidth + 4, y + 9);
+        });
+    }
+
     /**
      * Format large numbers with K/M suffixes
      */
@@ -355,13 +564,52 @@ const SDRStatus = (function() {
         return num.toString();
     }
 
+    async function loadSignalHistory() {
+        try {
+            const response = await Api.get('/api/signal/live?seconds=' + maxHistoryLength);
+            if (!response.ok) {
+                throw new Error('Failed to fetch signal history');
+            }
+            for (const sample of await response.json()) {
+                const devId = sample.device_id || 'unknown';
+                if (!currentDeviceId) {
+                    currentDeviceId = devId;
+                }
+                if (!signalHistory[devId]) {
+                    signalHistory[devId] = [];
+                }
+                // Live updates may have arrived while this was loading
+                if (signalHistory[devId].some(p => p.time === sample.timestamp_ms)) {
+                    continue;
+                }
+                signalHistory[devId].push({
+                    time: sample.timestamp_ms,
+                    signal: sample.signal_dbfs,
+                    noise: sample.noise_dbfs,
+                    snr: sample.snr_db,
+                    msgRate: sample.msg_rate
+                });
+            }
+            for (const history of Object.values(signalHistory)) {
+                history.sort((a, b) => a.time - b.time);
+                history.splice(0, Math.max(0, history.length - maxHistoryLength));
+            }
+            drawSignalChart();
+        } catch (err) {
+            console.warn('Signal history fetch error:', err);
+        }
+    }
+
     async function fetchSDRStatus() {
         try {
-            const response = await fetch('/api/sdr/status');
+            const response = await Api.get('/api/sdr/status');
             if (!response.ok) {
                 throw new Error('Failed to fetch SDR status');
             }
-            const data = await response.json();
+            // Keyed by device ID; show the first active device, else the first
+            const devices = Object.values(await response.json());
+            const data = devices.find(d => d.status === 'active') || devices[0] ||
+                { connected: false, status: 'disconnected' };
             updateSDRDisplay(data);
         } catch (err) {
             console.warn('SDR status fetch error:', err);
@@ -415,6 +663,8 @@ const SDRStatus = (function() {
             gain.textContent = data.gain_db.toFixed(1) + ' dB';
         }
 
+        updateDecoderConfig(data);
+
         // Only update msg rate from API if no live signal
         if (msgRate && !hasLiveSignal) {
             msgRate.textContent = (data.messages_per_second || 0).toFixed(1);
@@ -14,7 +14,14 @@ const WebSocketClient = (function() {
     function getWsUrl() {
         const protocol = window.location.protocol === 'https:' ? 'wss:' : 'ws:';
         const host = window.location.host;
-        return `${protocol}//${host}/ws`;
+        const params = new URLSearchParams({ v: '2', delta: 'true', coalesce_ms: '1000' });
+        // Forward the page's ?token= for gateways with WS_AUTH_TOKEN set
+        const token = new URLSearchParams(window.location.search).get('token');
+        if (token) params.set('token', token);
+        // An API key limits a tenant's stream to its receivers
+        const apiKey = localStorage.getItem('apiKey');
+        if (apiKey) params.set('api_key', apiKey);
+        return `${protocol}//${host}/ws?${params}`;
     }
 
     // Update connection status
@@ -0,0 +1,241 @@
+//! Local cache of aircraft callsigns and categories
+//!
+//! Identification squitters go out every 5 seconds at best, and far less
+//! often for aircraft at the edge of range, so a freshly restarted host
+//! shows bare addresses for a while. The host keeps the last callsign and
+//! emitter category of every ICAO address it has identified in a small
+//! SQLite file (`AIRCRAFT_CACHE_FILE`, default `adsb-capture.db`, empty to
+//! turn it off) and labels newly tracked aircraft from it until they
+//! identify themselves. Every `AIRCRAFT_CACHE_SYNC_SECS` (default 300, 0
+//! for never) it also asks the gateway for the labels of the aircraft it
+//! has in view, heard by this host or any other; a gateway that can't be
+//! reached is skipped until the next round. Entries not refreshed for 30
+//! days are dropped when the cache is opened.
+
+use crate::grpc::adsb::AircraftLabel;
+use crate::grpc::StreamingGatewayClient;
+use anyhow::Result;
+use rusqlite::{params, Connection};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+/// Entries older than this are dropped on open
+const RETENTION_DAYS: i64 = 30;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS aircraft_info (
+    icao_address INTEGER PRIMARY KEY,
+    callsign TEXT,
+    category TEXT,
+    last_seen INTEGER NOT NULL
+);
+";
+
+/// What is known about an aircraft before it identifies itself
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Label {
+    pub callsign: Option<String>,
+    pub category: Option<String>,
+}
+
+/// Labels by ICAO address, held in memory and written back in batches
+pub struct AircraftInfoCache {
+    conn: Connection,
+    labels: HashMap<u32, Label>,
+    /// Changed since the last flush, with when they were learned (unix ms)
+    dirty: HashMap<u32, i64>,
+}
+
+impl AircraftInfoCache {
+    /// Open or create the cache at `path`, dropping stale entries
+    pub fn open(path: &Path) -> Result<Self> {
+        let conn = Connection::open(path)?;
+        conn.execute_batch(SCHEMA)?;
+        let cutoff = chrono::Utc::now().timestamp_millis() - RETENTION_DAYS * 86_400_000;
+        conn.execute("DELETE FROM aircraft_info WHERE last_seen < ?1", params![cutoff])?;
+        let mut labels = HashMap::new();
+        {
+            let mut stmt = conn.prepare("SELECT icao_address, callsign, category FROM aircraft_info")?;
+            let rows = stmt.query_map([], |row| {
+                Ok((
+                    row.get::<_, u32>(0)?,
+                    Label {
+                        callsign: row.get(1)?,
+                        category: row.get(2)?,
+                    },
+                ))
+            })?;
+            for row in rows {
+                let (icao, label) = row?;
+                labels.insert(icao, label);
+            }
+        }
+        Ok(Self {
+            conn,
+            labels,
+            dirty: HashMap::new(),
+        })
+    }
+
+    pub fn get(&self, icao: u32) -> Option<&Label> {
+        self.labels.get(&icao)
+    }
+
+    /// Remember what an ICAO address reported at `now_ms`; fields it didn't
+    /// report keep their cached values. Returns whether anything changed
+    pub fn record(&mut self, icao: u32, callsign: Option<&str>, category: Option<&str>, now_ms: i64) -> bool {
+        if icao > 0xFF_FFFF || (callsign.is_none() && category.is_none()) {
+            return false;
+        }
+        let label = self.labels.entry(icao).or_default();
+        let mut changed = false;
+        for (field, value) in [(&mut label.callsign, callsign), (&mut label.category, category)] {
+            if let Some(value) = value.map(str::trim).filter(|v| !v.is_empty()) {
+                if field.as_deref() != Some(value) {
+                    *field = Some(value.to_string());
+                    changed = true;
+                }
+            }
+        }
+        if changed {
+            self.dirty.insert(icao, now_ms);
+        }
+        changed
+    }
+
+    /// Take in the gateway's labels; returns how many changed the cache
+    pub fn merge(&mut self, labels: &[AircraftLabel], now_ms: i64) -> usize {
+        labels
+            .iter()
+            .filter(|label| {
+                u32::from_str_radix(&label.icao, 16).is_ok_and(|icao| {
+                    self.record(icao, label.callsign.as_deref(), label.category.as_deref(), now_ms)
+                })
+            })
+            .count()
+    }
+
+    /// Write the entries changed since the last flush
+    pub fn flush(&mut self) -> Result<usize> {
+        if self.dirty.is_empty() {
+            return Ok(0);
+        }
+        let tx = self.conn.transaction()?;
+        {
+            let mut stmt = tx.prepare(
+                "INSERT INTO aircraft_info (icao_address, callsign, category, last_seen)
+                 VALUES (?1, ?2, ?3, ?4)
+                 ON CONFLICT (icao_address) DO UPDATE SET
+                    callsign = excluded.callsign,
+                    category = excluded.category,
+                    last_seen = excluded.last_seen",
+            )?;
+            for (icao, last_seen) in &self.dirty {
+                if let Some(label) = self.labels.get(icao) {
+                    stmt.execute(params![icao, label.callsign, label.category, last_seen])?;
+                }
+            }
+        }
+        tx.commit()?;
+        let written = self.dirty.len();
+        self.dirty.clear();
+        Ok(written)
+    }
+}
+
+/// Merge the gateway's labels into `cache` every `interval`
+pub fn spawn_sync(gateway_url: String, device_id: String, cache: Arc<Mutex<AircraftInfoCache>>, interval: Duration) {
+    tokio::spawn(async move {
+        let client = StreamingGatewayClient::new(&gateway_url);
+        let mut failing = false;
+        loop {
+            match client.aircraft_labels(&device_id).await {
+                Ok(labels) => {
+                    let now_ms = chrono::Utc::now().timestamp_millis();
+                    if let Ok(mut cache) = cache.lock() {
+                        let changed = cache.merge(&labels, now_ms);
+                        debug!("Aircraft labels from gateway: {} received, {} new", labels.len(), changed);
+                    }
+                    failing = false;
+                }
+                Err(e) => {
+                    // Logged once per outage; the next round tries again
+                    if !failing {
+                        warn!("Aircraft label sync with gateway failed: {}", e);
+                    }
+                    failing = true;
+                }
+            }
+            tokio::time::sleep(interval).await;
+        }
+    });
+}
+
+/// Open the cache, or run without one when it can't be opened
+pub fn open(path: &Path) -> Option<Arc<Mutex<AircraftInfoCache>>> {
+    match AircraftInfoCache::open(path) {
+        Ok(cache) => {
+            info!("Aircraft info cache {}: {} aircraft", path.display(), cache.labels.len());
+            Some(Arc::new(Mutex::new(cache)))
+        }
+        Err(e) => {
+            warn!("Aircraft info cache {} unavailable, continuing without: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+/// Write what the cache learned since the last flush
+pub fn flush(cache: &Mutex<AircraftInfoCache>) {
+    let Ok(mut cache) = cache.lock() else {
+        return;
+    };
+    match cache.flush() {
+        Ok(0) => {}
+        Ok(n) => debug!("Aircraft info cache: {} aircraft written", n),
+        Err(e) => warn!("Failed to write the aircraft info cache: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_aircraft_info_cache() {
+        let path = std::env::temp_dir().join(format!("aircraft-info-test-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let now = chrono::Utc::now().timestamp_millis();
+
+        let mut cache = AircraftInfoCache::open(&path).unwrap();
+        cache.record(0x71BE11, Some("KAL123  "), None, now);
+        cache.record(0x71BE11, None, Some("A3"), now);
+        // Non-ICAO tracker keys aren't cached
+        assert!(!cache.record(0x1_71BE11, Some("N123"), None, now));
+        let gateway = |icao: &str, callsign: &str| AircraftLabel {
+            icao: icao.into(),
+            callsign: Some(callsign.into()),
+            category: None,
+        };
+        assert_eq!(cache.merge(&[gateway("71BE11", "KAL123"), gateway("4840D6", "KLM605")], now), 1);
+        assert_eq!(cache.flush().unwrap(), 2);
+        assert_eq!(cache.flush().unwrap(), 0);
+        drop(cache);
+
+        let mut cache = AircraftInfoCache::open(&path).unwrap();
+        assert_eq!(cache.labels.len(), 2);
+        let label = cache.get(0x71BE11).unwrap();
+        assert_eq!((label.callsign.as_deref(), label.category.as_deref()), (Some("KAL123"), Some("A3")));
+
+        // Stale entries are dropped on open
+        cache.record(0x4840D6, Some("KLM606"), None, now - (RETENTION_DAYS + 1) * 86_400_000);
+        cache.flush().unwrap();
+        drop(cache);
+        let cache = AircraftInfoCache::open(&path).unwrap();
+        assert!(cache.get(0x4840D6).is_none());
+        let _ = std::fs::remove_file(&path);
+    }
+}
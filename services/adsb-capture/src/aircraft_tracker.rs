@@ -4,20 +4,50 @@
 //! This is essential for weak signal conditions where individual messages may be incomplete.
 
 use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
+
+use crate::adsb::{
+    format_address, AddressType, AdsbVersion, AircraftData, EmergencySquawk, IcaoTrust,
+    PositionIntegrity, QualityThreshold,
+};
+use crate::aircraft_info::AircraftInfoCache;
 
 use std::collections::VecDeque;
 
 /// Maximum age for aircraft state before removal
 const AIRCRAFT_TIMEOUT_SECS: u64 = 60;
 
+/// Maximum age for non-ICAO addresses (anonymous, TIS-B tracks), which can
+/// be handed to another target soon after they go quiet
+const NON_ICAO_TIMEOUT_SECS: u64 = 20;
+
 /// Position update threshold for logging
 const POSITION_LOG_INTERVAL_SECS: u64 = 5;
 
 /// Maximum recent messages to keep for deduplication
 const MAX_RECENT_MESSAGES: usize = 10;
 
+/// Share of the gap between the predicted and decoded position the
+/// smoothed track moves per fix
+const SMOOTHING_GAIN: f64 = 0.4;
+
+/// Restart the smoothed track from the decoded position after a gap this long
+const SMOOTHING_RESET_SECS: f64 = 10.0;
+
+/// Squawk transitions kept per aircraft
+const MAX_SQUAWK_CHANGES: usize = 16;
+
+/// A change of squawk code
+#[derive(Debug, Clone, PartialEq)]
+pub struct SquawkChange {
+    pub time: Instant,
+    /// Code before the change; `None` for the first one heard
+    pub from: Option<u16>,
+    pub to: u16,
+}
+
 /// Recent message for deduplication and voting
 #[derive(Debug, Clone)]
 struct RecentMessage {
@@ -34,24 +64,45 @@ struct RecentMessage {
 /// Aggregated aircraft state
 #[derive(Debug, Clone)]
 pub struct AircraftState {
-    /// ICAO 24-bit address
+    /// Tracker key: the ICAO 24-bit address, or a non-ICAO address with
+    /// bit 24 set
     pub icao: u32,
+    /// Whether the address is an ICAO one
+    pub address_type: AddressType,
     /// Flight callsign
     pub callsign: Option<String>,
+    /// Emitter category (e.g. "A3")
+    pub category: Option<String>,
+    /// Callsign taken from the aircraft info cache, not yet heard
+    pub callsign_cached: bool,
     /// Last known latitude
     pub latitude: Option<f64>,
     /// Last known longitude
     pub longitude: Option<f64>,
+    /// Smoothed track estimate (only with smoothing on)
+    pub filtered_latitude: Option<f64>,
+    pub filtered_longitude: Option<f64>,
+    /// When the smoothed track was last updated
+    last_fix: Option<Instant>,
     /// Barometric altitude in feet
     pub altitude_ft: Option<i32>,
     /// Ground speed in knots
     pub ground_speed_kts: Option<f32>,
-    /// True heading in degrees
-    pub heading_deg: Option<f32>,
-    /// Vertical rate in feet per minute
+    /// Ground track in degrees true
+    pub track_deg: Option<f32>,
+    /// Heading in degrees magnetic
+    pub magnetic_heading_deg: Option<f32>,
+    /// Vertical rate in feet per minute, from whichever source sent last
     pub vertical_rate_fpm: Option<i32>,
+    /// Vertical rate by source: barometric and GNSS (geometric)
+    pub baro_rate_fpm: Option<i32>,
+    pub geom_rate_fpm: Option<i32>,
     /// Squawk code
     pub squawk: Option<u16>,
+    /// Recent squawk transitions, oldest first
+    pub squawk_changes: VecDeque<SquawkChange>,
+    /// Emergency declared via squawk (7500/7600/7700)
+    pub emergency: Option<EmergencySquawk>,
     /// Last update time
     pub last_seen: Instant,
     /// Last position update time (for rate limiting logs)
@@ -62,6 +113,12 @@ pub struct AircraftState {
     pub position_messages: u64,
     /// Whether we have a valid position
     pub has_position: bool,
+    /// Last extended squitter position was a surface one
+    pub on_ground: bool,
+    /// ADS-B version from the last operational status message
+    pub adsb_version: Option<AdsbVersion>,
+    /// Integrity and accuracy of the current position
+    pub integrity: Option<PositionIntegrity>,
     /// Recent messages for deduplication
     recent_messages: VecDeque<RecentMessage>,
     /// Confidence score (higher = more reliable)
@@ -73,28 +130,43 @@ impl AircraftState {
         let now = Instant::now();
         Self {
             icao,
+            address_type: AddressType::Icao,
             callsign: None,
+            category: None,
+            callsign_cached: false,
             latitude: None,
             longitude: None,
+            filtered_latitude: None,
+            filtered_longitude: None,
+            last_fix: None,
             altitude_ft: None,
             ground_speed_kts: None,
-            heading_deg: None,
+            track_deg: None,
+            magnetic_heading_deg: None,
             vertical_rate_fpm: None,
+            baro_rate_fpm: None,
+            geom_rate_fpm: None,
             squawk: None,
+            squawk_changes: VecDeque::new(),
+            emergency: None,
             last_seen: now,
             last_position_log: now - Duration::from_secs(POSITION_LOG_INTERVAL_SECS),
             messages: 0,
             position_messages: 0,
             has_position: false,
+            on_ground: false,
+            adsb_version: None,
+            integrity: None,
             recent_messages: VecDeque::with_capacity(MAX_RECENT_MESSAGES),
             confidence: 0,
         }
     }
 
     /// Update state with new aircraft data
-    pub fn update(&mut self, data: &crate::adsb::AircraftData) {
+    pub fn update(&mut self, data: &AircraftData) {
         self.last_seen = Instant::now();
         self.messages += 1;
+        self.address_type = data.address_type;
 
         // Create message hash for deduplication
         let msg_hash = Self::compute_message_hash(data);
@@ -122,10 +194,34 @@ impl AircraftState {
             self.recent_messages.pop_front();
         }
 
-        // Update callsign if provided
+        // Update callsign if provided. A different callsign usually means a
+        // turnaround; the gateway starts a new flight for it
         if let Some(ref cs) = data.callsign {
             if !cs.trim().is_empty() && cs != "#######" {
+                if let Some(previous) = self.callsign.as_deref().filter(|p| p.trim() != cs.trim()) {
+                    if !self.callsign_cached {
+                        info!("Aircraft {} callsign {} -> {}", self.address(), previous.trim(), cs.trim());
+                    }
+                }
                 self.callsign = Some(cs.clone());
+                self.callsign_cached = false;
+            }
+        }
+
+        if data.category.is_some() {
+            self.category = data.category.clone();
+        }
+
+        if let Some(status) = data.operational_status {
+            self.adsb_version = Some(status.version);
+        }
+
+        // Surface (TC 5-8) or airborne (TC 9-18, 20-22) position squitter
+        if matches!(data.df, 17 | 18) {
+            match data.tc {
+                5..=8 => self.on_ground = true,
+                9..=18 | 20..=22 => self.on_ground = false,
+                _ => {}
             }
         }
 
@@ -158,6 +254,7 @@ impl AircraftState {
                 self.longitude = Some(new_lon);
                 self.position_messages += 1;
                 self.has_position = true;
+                self.integrity = data.integrity;
             }
         }
 
@@ -175,22 +272,118 @@ impl AircraftState {
             }
         }
 
-        if let Some(hdg) = data.heading_deg {
-            if hdg >= 0.0 && hdg < 360.0 {
-                self.heading_deg = Some(hdg);
+        if let Some(trk) = data.track_deg {
+            if (0.0..360.0).contains(&trk) {
+                self.track_deg = Some(trk);
+            }
+        }
+
+        if let Some(hdg) = data.magnetic_heading_deg {
+            if (0.0..360.0).contains(&hdg) {
+                self.magnetic_heading_deg = Some(hdg);
             }
         }
 
         if let Some(vr) = data.vertical_rate_fpm {
             if vr.abs() < 10000 {
                 self.vertical_rate_fpm = Some(vr);
+                if data.vertical_rate_gnss {
+                    self.geom_rate_fpm = Some(vr);
+                } else {
+                    self.baro_rate_fpm = Some(vr);
+                }
             }
         }
 
         // Update squawk if provided
         if let Some(sq) = data.squawk {
-            self.squawk = Some(sq);
+            self.update_squawk(sq, Instant::now());
+            self.update_emergency(sq);
+        }
+    }
+
+    /// Take a decoded squawk, recording and logging it when it changes
+    fn update_squawk(&mut self, squawk: u16, now: Instant) {
+        if self.squawk == Some(squawk) {
+            return;
+        }
+        if let Some(from) = self.squawk {
+            info!(
+                "Aircraft {} {} squawk {:04} -> {:04}",
+                self.address(),
+                self.callsign.as_deref().unwrap_or("-"),
+                from,
+                squawk
+            );
         }
+        self.squawk_changes.push_back(SquawkChange {
+            time: now,
+            from: self.squawk,
+            to: squawk,
+        });
+        while self.squawk_changes.len() > MAX_SQUAWK_CHANGES {
+            self.squawk_changes.pop_front();
+        }
+        self.squawk = Some(squawk);
+    }
+
+    /// Track emergency squawk changes, logging when one is set or cleared
+    fn update_emergency(&mut self, squawk: u16) {
+        let emergency = EmergencySquawk::from_squawk(squawk);
+        if emergency == self.emergency {
+            return;
+        }
+        match emergency {
+            Some(e) => warn!(
+                "Aircraft {} {} squawking {:04} ({})",
+                self.address(),
+                self.callsign.as_deref().unwrap_or("-"),
+                squawk,
+                e.description()
+            ),
+            None => info!(
+                "Aircraft {} emergency cleared (squawk {:04})",
+                self.address(), squawk
+            ),
+        }
+        self.emergency = emergency;
+    }
+
+    /// Fold the latest decoded position into the smoothed track: the last
+    /// estimate is dead-reckoned with the reported velocity, then moved part
+    /// of the way toward the decoded position
+    fn smooth_position(&mut self, now: Instant) {
+        let (Some(lat), Some(lon)) = (self.latitude, self.longitude) else {
+            return;
+        };
+        let previous = self.filtered_latitude.zip(self.filtered_longitude);
+        let elapsed = self.last_fix.map(|t| now.duration_since(t).as_secs_f64());
+        self.last_fix = Some(now);
+
+        let (Some((f_lat, f_lon)), Some(dt)) = (previous, elapsed) else {
+            (self.filtered_latitude, self.filtered_longitude) = (Some(lat), Some(lon));
+            return;
+        };
+        if dt > SMOOTHING_RESET_SECS {
+            (self.filtered_latitude, self.filtered_longitude) = (Some(lat), Some(lon));
+            return;
+        }
+
+        // Nautical miles travelled since the last fix, north and east
+        let (north_nm, east_nm) = match (self.ground_speed_kts, self.track_deg) {
+            (Some(kts), Some(hdg)) => {
+                let nm = kts as f64 * dt / 3600.0;
+                let hdg = (hdg as f64).to_radians();
+                (nm * hdg.cos(), nm * hdg.sin())
+            }
+            _ => (0.0, 0.0),
+        };
+        // One degree of latitude is 60 nm
+        let p_lat = f_lat + north_nm / 60.0;
+        let p_lon = f_lon + east_nm / (60.0 * f_lat.to_radians().cos().max(0.01));
+
+        self.filtered_latitude = Some(p_lat + SMOOTHING_GAIN * (lat - p_lat));
+        self.filtered_longitude = Some(p_lon + SMOOTHING_GAIN * (lon - p_lon));
     }
 
     /// Check if enough time has passed to log position again
@@ -203,9 +396,18 @@ impl AircraftState {
         self.last_position_log = Instant::now();
     }
 
+    /// Address as sent to the gateway (`~` prefix when not ICAO)
+    pub fn address(&self) -> String {
+        format_address(self.icao)
+    }
+
     /// Check if aircraft state is stale
     pub fn is_stale(&self) -> bool {
-        self.last_seen.elapsed() > Duration::from_secs(AIRCRAFT_TIMEOUT_SECS)
+        let timeout = match self.address_type {
+            AddressType::Icao => AIRCRAFT_TIMEOUT_SECS,
+            AddressType::Anonymous | AddressType::TisbTrack => NON_ICAO_TIMEOUT_SECS,
+        };
+        self.last_seen.elapsed() > Duration::from_secs(timeout)
     }
 
     /// Get age in seconds
@@ -214,7 +416,7 @@ impl AircraftState {
     }
 
     /// Compute a simple hash for message deduplication
-    fn compute_message_hash(data: &crate::adsb::AircraftData) -> u64 {
+    fn compute_message_hash(data: &AircraftData) -> u64 {
         use std::collections::hash_map::DefaultHasher;
         use std::hash::{Hash, Hasher};
 
@@ -241,7 +443,10 @@ impl AircraftState {
         if let Some(spd) = data.ground_speed_kts {
             ((spd * 10.0) as i32).hash(&mut hasher);
         }
-        if let Some(hdg) = data.heading_deg {
+        if let Some(trk) = data.track_deg {
+            ((trk * 10.0) as i32).hash(&mut hasher);
+        }
+        if let Some(hdg) = data.magnetic_heading_deg {
             ((hdg * 10.0) as i32).hash(&mut hasher);
         }
 
@@ -270,6 +475,19 @@ pub struct AircraftTracker {
     aircraft: HashMap<u32, AircraftState>,
     max_aircraft: usize,
     last_cleanup: Instant,
+    /// Aircraft timed out since the last `expire`
+    removed: Vec<u32>,
+    /// Keep a smoothed track alongside the decoded positions
+    smoothing: bool,
+    /// Positions below this quality are dropped
+    quality: QualityThreshold,
+    /// Positions dropped for low quality
+    pub low_quality_positions: u64,
+    /// Addresses corroborated by clean frames; without it every address
+    /// is reported from its first message
+    trust: Option<Arc<IcaoTrust>>,
+    /// Labels new aircraft and learns from identified ones
+    info: Option<Arc<Mutex<AircraftInfoCache>>>,
 }
 
 impl AircraftTracker {
@@ -278,12 +496,68 @@ impl AircraftTracker {
             aircraft: HashMap::with_capacity(max_aircraft),
             max_aircraft,
             last_cleanup: Instant::now(),
+            removed: Vec::new(),
+            smoothing: false,
+            quality: QualityThreshold::default(),
+            low_quality_positions: 0,
+            trust: None,
+            info: None,
         }
     }
 
+    /// Keep a smoothed track estimate for every aircraft
+    pub fn set_smoothing(&mut self, enabled: bool) {
+        self.smoothing = enabled;
+    }
+
+    /// Drop positions below a NIC/NACp threshold rather than tracking them
+    pub fn set_quality_threshold(&mut self, quality: QualityThreshold) {
+        self.quality = quality;
+    }
+
+    /// Hold back aircraft until `trust` has corroborated their address
+    pub fn set_trust(&mut self, trust: Arc<IcaoTrust>) {
+        self.trust = Some(trust);
+    }
+
+    /// Label new aircraft from `info` until they identify themselves, and
+    /// keep it up to date with the callsigns and categories heard
+    pub fn set_info_cache(&mut self, info: Arc<Mutex<AircraftInfoCache>>) {
+        self.info = Some(info);
+    }
+
+    /// Whether updates for `data`'s aircraft are reported. An address heard
+    /// in a single clean frame is tracked but not reported until another
+    /// corroborates it; DF18 rebroadcasts can't corroborate and are taken
+    /// as they come
+    fn is_corroborated(&self, data: &AircraftData) -> bool {
+        data.df == 18
+            || self
+                .trust
+                .as_ref()
+                .is_none_or(|trust| trust.is_trusted(data.icao_address, Instant::now()))
+    }
+
     /// Update aircraft state with new data, returns updated state if significant
-    pub fn update(&mut self, data: &crate::adsb::AircraftData) -> Option<&AircraftState> {
-        let icao = data.icao_address;
+    pub fn update(&mut self, data: &AircraftData) -> Option<&AircraftState> {
+        let icao = data.key();
+
+        // Low-integrity positions can wander hundreds of metres; keep the
+        // rest of the message
+        let filtered;
+        let data = if data.latitude.is_some() && !self.quality.accepts(data.integrity.as_ref()) {
+            self.low_quality_positions += 1;
+            debug!("Aircraft {}: dropped low-quality position {:?}", format_address(icao), data.integrity);
+            filtered = AircraftData {
+                latitude: None,
+                longitude: None,
+                integrity: None,
+                ..data.clone()
+            };
+            &filtered
+        } else {
+            data
+        };
 
         // Get or create aircraft state
         if !self.aircraft.contains_key(&icao) {
@@ -291,27 +565,43 @@ impl AircraftTracker {
             if self.aircraft.len() >= self.max_aircraft {
                 self.cleanup_stale();
             }
-            self.aircraft.insert(icao, AircraftState::new(icao));
-            debug!("New aircraft tracked: {:06X}", icao);
+            let mut state = AircraftState::new(icao);
+            if let Some(label) = self.info.as_ref().and_then(|i| i.lock().ok()?.get(icao).cloned()) {
+                state.callsign_cached = label.callsign.is_some();
+                state.callsign = label.callsign;
+                state.category = label.category;
+            }
+            self.aircraft.insert(icao, state);
+            debug!("New aircraft tracked: {}", format_address(icao));
         }
 
         let state = self.aircraft.get_mut(&icao)?;
         let had_position = state.has_position;
+        let fixes = state.position_messages;
 
         state.update(data);
+        if data.callsign.is_some() || data.category.is_some() {
+            if let Some(mut info) = self.info.as_ref().and_then(|i| i.lock().ok()) {
+                let callsign = state.callsign.as_deref().filter(|_| !state.callsign_cached);
+                info.record(icao, callsign, state.category.as_deref(), chrono::Utc::now().timestamp_millis());
+            }
+        }
+        if self.smoothing && state.position_messages > fixes {
+            state.smooth_position(Instant::now());
+        }
 
         // Log if we got a new position or it's time for an update
         if state.has_position && ((!had_position) || state.should_log_position()) {
             state.mark_position_logged();
             info!(
-                "Aircraft {:06X} {} at ({:.4}, {:.4}) alt={} spd={:.0} hdg={:.0} | msgs={}",
-                icao,
+                "Aircraft {} {} at ({:.4}, {:.4}) alt={} spd={:.0} trk={:.0} | msgs={}",
+                state.address(),
                 state.callsign.as_deref().unwrap_or("-"),
                 state.latitude.unwrap_or(0.0),
                 state.longitude.unwrap_or(0.0),
                 state.altitude_ft.unwrap_or(0),
                 state.ground_speed_kts.unwrap_or(0.0),
-                state.heading_deg.unwrap_or(0.0),
+                state.track_deg.unwrap_or(0.0),
                 state.messages
             );
         }
@@ -322,6 +612,9 @@ impl AircraftTracker {
             self.last_cleanup = Instant::now();
         }
 
+        if !self.is_corroborated(data) {
+            return None;
+        }
         self.aircraft.get(&icao)
     }
 
@@ -350,10 +643,27 @@ impl AircraftTracker {
         self.aircraft.values().filter(|a| a.has_position && !a.is_stale()).count()
     }
 
+    /// Remove stale aircraft and return every ICAO timed out since the last call
+    pub fn expire(&mut self) -> Vec<u32> {
+        self.cleanup_stale();
+        self.last_cleanup = Instant::now();
+        std::mem::take(&mut self.removed)
+    }
+
     /// Remove stale aircraft
     fn cleanup_stale(&mut self) {
         let before = self.aircraft.len();
-        self.aircraft.retain(|_, state| !state.is_stale());
+        let removed = &mut self.removed;
+        self.aircraft.retain(|icao, state| {
+            let stale = state.is_stale();
+            if stale {
+                removed.push(*icao);
+            }
+            !stale
+        });
+        if let Some(trust) = &self.trust {
+            trust.prune(Instant::now());
+        }
         let removed = before - self.aircraft.len();
         if removed > 0 {
             debug!("Cleaned up {} stale aircraft, {} remaining", removed, self.aircraft.len());
@@ -394,3 +704,165 @@ impl std::fmt::Display for TrackerStats {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expire_reports_timed_out_aircraft() {
+        let mut tracker = AircraftTracker::new(16);
+        for icao in [0x71BE11, 0x4840D6] {
+            tracker.update(&AircraftData {
+                icao_address: icao,
+                altitude_ft: Some(3000),
+                ..Default::default()
+            });
+        }
+        assert!(tracker.expire().is_empty());
+
+        let stale = Instant::now() - Duration::from_secs(AIRCRAFT_TIMEOUT_SECS + 1);
+        tracker.aircraft.get_mut(&0x71BE11).unwrap().last_seen = stale;
+        assert_eq!(tracker.expire(), vec![0x71BE11]);
+        assert!(tracker.expire().is_empty());
+        assert_eq!(tracker.stats_summary().total_aircraft, 1);
+    }
+
+    #[test]
+    fn test_low_quality_positions_dropped() {
+        let mut tracker = AircraftTracker::new(16);
+        tracker.set_quality_threshold(QualityThreshold { min_nic: 6, min_nac_p: 0 });
+        let position = |nuc_p, lat| AircraftData {
+            icao_address: 0x71BE11,
+            latitude: Some(lat),
+            longitude: Some(127.0),
+            altitude_ft: Some(3000),
+            integrity: Some(PositionIntegrity {
+                nuc_p: Some(nuc_p),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        // NUCp 5 (NIC 6 equivalent) is kept, NUCp 3 is not
+        tracker.update(&position(5, 37.0));
+        let state = tracker.update(&position(3, 37.01)).unwrap();
+        assert_eq!(state.latitude, Some(37.0));
+        assert_eq!(state.altitude_ft, Some(3000));
+        assert_eq!(tracker.low_quality_positions, 1);
+    }
+
+    #[test]
+    fn test_non_icao_addresses_kept_apart() {
+        let mut tracker = AircraftTracker::new(16);
+        let data = |address_type, callsign: &str| AircraftData {
+            icao_address: 0x71BE11,
+            address_type,
+            callsign: Some(callsign.into()),
+            ..Default::default()
+        };
+        tracker.update(&data(AddressType::Icao, "KAL123"));
+        let state = tracker.update(&data(AddressType::Anonymous, "N12345")).unwrap();
+        assert_eq!(state.address(), "~71BE11");
+        assert_eq!(state.address_type, AddressType::Anonymous);
+
+        // Same 24 bits, separate aircraft
+        assert_eq!(tracker.count(), 2);
+        assert_eq!(tracker.get(0x71BE11).unwrap().callsign.as_deref(), Some("KAL123"));
+        assert_eq!(tracker.get(0x71BE11).unwrap().address(), "71BE11");
+    }
+
+    #[test]
+    fn test_labelled_from_info_cache() {
+        let path = std::env::temp_dir().join(format!("tracker-info-test-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let info = Arc::new(Mutex::new(AircraftInfoCache::open(&path).unwrap()));
+        info.lock().unwrap().record(0x71BE11, Some("KAL123"), Some("A5"), 0);
+        let mut tracker = AircraftTracker::new(16);
+        tracker.set_info_cache(info.clone());
+
+        let heard = |callsign: Option<&str>| AircraftData {
+            icao_address: 0x71BE11,
+            altitude_ft: Some(35000),
+            callsign: callsign.map(str::to_string),
+            ..Default::default()
+        };
+        let state = tracker.update(&heard(None)).unwrap();
+        assert_eq!((state.callsign.as_deref(), state.category.as_deref()), (Some("KAL123"), Some("A5")));
+        assert!(state.callsign_cached);
+
+        // The aircraft's own identification replaces the label and is remembered
+        let state = tracker.update(&heard(Some("KAL124"))).unwrap();
+        assert!(!state.callsign_cached);
+        assert_eq!(info.lock().unwrap().get(0x71BE11).unwrap().callsign.as_deref(), Some("KAL124"));
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_uncorroborated_addresses_held_back() {
+        let trust = Arc::new(IcaoTrust::new());
+        let mut tracker = AircraftTracker::new(16);
+        tracker.set_trust(trust.clone());
+        let data = AircraftData {
+            icao_address: 0x71BE11,
+            df: 17,
+            callsign: Some("KAL123".into()),
+            ..Default::default()
+        };
+
+        // Tracked from the first clean frame, reported from the second
+        trust.corroborate(0x71BE11, Instant::now());
+        assert!(tracker.update(&data).is_none());
+        assert_eq!(tracker.count(), 1);
+        trust.corroborate(0x71BE11, Instant::now());
+        let state = tracker.update(&AircraftData { callsign: None, ..data.clone() }).unwrap();
+        assert_eq!(state.callsign.as_deref(), Some("KAL123"));
+
+        // Rebroadcasts aren't held back
+        let rebroadcast = AircraftData { icao_address: 0x4840D6, df: 18, ..data };
+        assert!(tracker.update(&rebroadcast).is_some());
+    }
+
+    #[test]
+    fn test_squawk_changes() {
+        let mut state = AircraftState::new(0x71BE11);
+        let start = Instant::now();
+        state.update_squawk(1234, start);
+        state.update_squawk(1234, start + Duration::from_secs(1));
+        state.update_squawk(7600, start + Duration::from_secs(2));
+
+        let changes: Vec<_> = state.squawk_changes.iter().map(|c| (c.from, c.to)).collect();
+        assert_eq!(changes, vec![(None, 1234), (Some(1234), 7600)]);
+        assert_eq!(state.squawk_changes[1].time, start + Duration::from_secs(2));
+        assert_eq!(state.squawk, Some(7600));
+    }
+
+    #[test]
+    fn test_smoothed_track() {
+        let mut state = AircraftState::new(0x71BE11);
+        let start = Instant::now();
+        let fix = |state: &mut AircraftState, lat, lon, secs| {
+            state.latitude = Some(lat);
+            state.longitude = Some(lon);
+            state.smooth_position(start + Duration::from_secs(secs));
+        };
+
+        // First fix starts the track
+        fix(&mut state, 37.0, 127.0, 0);
+        assert_eq!(state.filtered_latitude, Some(37.0));
+
+        // Flying north at 360 kts: 0.1 nm per second, so the prediction
+        // after 6 s is 0.01 degrees north. A fix 0.02 north pulls it partway.
+        state.ground_speed_kts = Some(360.0);
+        state.track_deg = Some(0.0);
+        fix(&mut state, 37.02, 127.0, 6);
+        let lat = state.filtered_latitude.unwrap();
+        assert!((lat - 37.014).abs() < 1e-9, "{}", lat);
+        assert!((state.filtered_longitude.unwrap() - 127.0).abs() < 1e-9);
+
+        // A long gap restarts from the decoded position
+        fix(&mut state, 37.5, 127.5, 60);
+        assert_eq!(state.filtered_latitude, Some(37.5));
+        assert_eq!(state.filtered_longitude, Some(127.5));
+    }
+}
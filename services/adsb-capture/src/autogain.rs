@@ -0,0 +1,130 @@
+//! Tuner gain auto-tune
+//!
+//! `adsb-capture --tune-gain [SECONDS]` captures for `SECONDS` (default 10)
+//! at every gain the R820T tuner supports, records decoded frames and the
+//! CRC error ratio at each, and writes the best gain to the settings file as
+//! `DEVICE_GAIN`, where later runs pick it up unless the environment
+//! overrides it. The best gain decodes the most frames; ties go to the
+//! lower CRC error ratio, then the lower gain.
+
+use std::path::Path;
+use tracing::warn;
+
+use crate::config::SettingsFile;
+use crate::sdr::SdrConfig;
+use crate::self_test::{capture_for, Measurement};
+
+/// Capture time per gain when none is given
+pub const DEFAULT_SECONDS: u64 = 10;
+
+/// Gains the R820T/R820T2 tuner supports, in tenths of dB
+const TUNER_GAINS: [i32; 29] = [
+    0, 9, 14, 27, 37, 77, 87, 125, 144, 157, 166, 197, 207, 229, 254, 280, 297, 328, 338, 364,
+    372, 386, 402, 421, 434, 439, 445, 480, 496,
+];
+
+/// `Some(seconds)` when the command line asks for a gain sweep
+pub fn requested(args: &[String]) -> Option<u64> {
+    let pos = args.iter().position(|a| a == "--tune-gain")?;
+    Some(
+        args.get(pos + 1)
+            .and_then(|s| s.parse().ok())
+            .filter(|&s| s > 0)
+            .unwrap_or(DEFAULT_SECONDS),
+    )
+}
+
+/// Share of detected messages that failed the CRC
+fn crc_ratio(m: &Measurement) -> f64 {
+    let total = m.frames + m.crc_errors;
+    if total == 0 {
+        return 0.0;
+    }
+    m.crc_errors as f64 / total as f64
+}
+
+/// The step with the most frames, if any step decoded anything
+pub fn best(steps: &[Measurement]) -> Option<&Measurement> {
+    steps
+        .iter()
+        .filter(|m| m.frames > 0)
+        .min_by(|a, b| {
+            b.frames
+                .cmp(&a.frames)
+                .then(crc_ratio(a).total_cmp(&crc_ratio(b)))
+                .then(a.gain_tenths_db.cmp(&b.gain_tenths_db))
+        })
+}
+
+/// Capture at every tuner gain
+pub fn sweep(config: &SdrConfig, seconds: u64) -> Vec<Measurement> {
+    let mut steps = Vec::new();
+    for gain in TUNER_GAINS {
+        let config = SdrConfig {
+            gain,
+            ..config.clone()
+        };
+        match capture_for(&config, seconds) {
+            Ok(m) => steps.push(m),
+            Err(e) => warn!("Capture at {:.1} dB failed: {}", gain as f32 / 10.0, e),
+        }
+    }
+    steps
+}
+
+/// Table of every step with the chosen one marked
+pub fn summary(steps: &[Measurement], seconds: u64) -> String {
+    let chosen = best(steps).map(|m| m.gain_tenths_db);
+    let mut out = format!("Gain sweep, {} s per step\n", seconds);
+    out.push_str("   gain   frames   frames/s   CRC errors\n");
+    for m in steps {
+        out.push_str(&format!(
+            "  {:>5.1}  {:>7}  {:>9.1}  {:>10} ({:.0}%){}\n",
+            m.gain_tenths_db as f32 / 10.0,
+            m.frames,
+            m.frames as f64 / seconds.max(1) as f64,
+            m.crc_errors,
+            crc_ratio(m) * 100.0,
+            if Some(m.gain_tenths_db) == chosen { "  <- best" } else { "" }
+        ));
+    }
+    out
+}
+
+/// Store `gain` (tenths of dB) as `DEVICE_GAIN` in the settings file
+pub fn save(path: &Path, gain: i32) -> std::io::Result<()> {
+    let mut settings = SettingsFile::load(path);
+    settings.set("DEVICE_GAIN", &format!("{:.1}", gain as f32 / 10.0));
+    settings.save(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn step(gain: i32, frames: u64, crc_errors: u64) -> Measurement {
+        Measurement {
+            gain_tenths_db: gain,
+            frames,
+            crc_errors,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_best_gain() {
+        let steps = vec![
+            step(297, 400, 1000),
+            step(386, 900, 2000),
+            step(421, 900, 1500),
+            step(496, 900, 1500),
+        ];
+        // Most frames, then fewest CRC errors, then lowest gain
+        assert_eq!(best(&steps).unwrap().gain_tenths_db, 421);
+        assert!(summary(&steps, 10).contains("42.1"));
+
+        assert!(best(&[step(496, 0, 50)]).is_none());
+        assert!(best(&[]).is_none());
+        assert_eq!(requested(&["adsb-capture".into(), "--tune-gain".into()]), Some(DEFAULT_SECONDS));
+    }
+}
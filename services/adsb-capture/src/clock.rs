@@ -0,0 +1,127 @@
+//! Clock offset to the gateway
+//!
+//! Outgoing `timestamp_ms` values come from this host's clock, and a Pi
+//! without a real-time clock or network time can be minutes or years off,
+//! which dates rows in the future and breaks anything comparing them with
+//! the gateway's time. Every `CLOCK_SYNC_INTERVAL_SECS` the host pings the
+//! gateway a few times and takes the round with the shortest round trip:
+//! the offset is the gateway's time minus the middle of that round trip.
+//! It goes out with every status update and, unless `CLOCK_CORRECTION` is
+//! off, is added to every outgoing timestamp. Offsets beyond
+//! `CLOCK_SKEW_WARN_MS` are logged as warnings.
+
+use crate::grpc::StreamingGatewayClient;
+use std::sync::atomic::{AtomicBool, AtomicI64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+/// Pings per sync; the fastest round trip gives the best estimate
+const PING_ROUNDS: usize = 4;
+
+/// Offset of this host's clock to the gateway's
+#[derive(Debug, Default)]
+pub struct GatewayClock {
+    offset_ms: AtomicI64,
+    synced: AtomicBool,
+    /// Add the offset to outgoing timestamps
+    correct: bool,
+}
+
+impl GatewayClock {
+    pub fn new(correct: bool) -> Arc<Self> {
+        Arc::new(Self {
+            correct,
+            ..Default::default()
+        })
+    }
+
+    /// Gateway time minus host time, once measured
+    pub fn offset_ms(&self) -> Option<i64> {
+        self.synced
+            .load(Ordering::Relaxed)
+            .then(|| self.offset_ms.load(Ordering::Relaxed))
+    }
+
+    fn set_offset(&self, offset_ms: i64) {
+        self.offset_ms.store(offset_ms, Ordering::Relaxed);
+        self.synced.store(true, Ordering::Relaxed);
+    }
+
+    /// Current time for outgoing messages, in gateway time when correcting
+    pub fn now_ms(&self) -> u64 {
+        let now = chrono::Utc::now().timestamp_millis();
+        let offset = if self.correct { self.offset_ms().unwrap_or(0) } else { 0 };
+        (now + offset).max(0) as u64
+    }
+}
+
+/// Offset and round trip of the fastest of `samples` (host send, gateway,
+/// host receive times)
+pub fn estimate(samples: &[(i64, i64, i64)]) -> Option<(i64, i64)> {
+    samples
+        .iter()
+        .map(|&(sent, gateway, received)| (gateway - (sent + received) / 2, received - sent))
+        .min_by_key(|&(_, round_trip)| round_trip)
+}
+
+/// Measure the offset now and every `interval`
+pub fn spawn_sync(
+    gateway_url: String,
+    device_id: String,
+    clock: Arc<GatewayClock>,
+    interval: Duration,
+    warn_ms: i64,
+) {
+    tokio::spawn(async move {
+        let client = StreamingGatewayClient::new(&gateway_url);
+        let mut warned = false;
+        loop {
+            match client.ping(&device_id, PING_ROUNDS).await {
+                Ok(samples) => {
+                    if let Some((offset, round_trip)) = estimate(&samples) {
+                        debug!("Clock offset to gateway: {} ms (round trip {} ms)", offset, round_trip);
+                        if offset.abs() > warn_ms {
+                            if !warned {
+                                warn!(
+                                    "System clock is {} ms {} the gateway's; check NTP{}",
+                                    offset.abs(),
+                                    if offset > 0 { "behind" } else { "ahead of" },
+                                    if clock.correct { ", timestamps are corrected meanwhile" } else { "" }
+                                );
+                            }
+                            warned = true;
+                        } else if warned {
+                            info!("System clock is back within {} ms of the gateway's", warn_ms);
+                            warned = false;
+                        }
+                        clock.set_offset(offset);
+                    }
+                }
+                Err(e) => warn!("Clock sync with gateway failed: {}", e),
+            }
+            tokio::time::sleep(interval).await;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimate() {
+        assert_eq!(estimate(&[]), None);
+        // The host is 5 s behind; the slow second round would say 5.1 s
+        let samples = [(1_000, 6_010, 1_020), (2_000, 7_200, 2_200), (3_000, 8_004, 3_008)];
+        assert_eq!(estimate(&samples), Some((5_000, 8)));
+
+        let clock = GatewayClock::new(true);
+        assert_eq!(clock.offset_ms(), None);
+        let before = chrono::Utc::now().timestamp_millis() as u64;
+        clock.set_offset(60_000);
+        assert_eq!(clock.offset_ms(), Some(60_000));
+        assert!(clock.now_ms() >= before + 60_000);
+        assert!(GatewayClock::new(false).now_ms() < before + 60_000);
+    }
+}
@@ -1,6 +1,20 @@
 //! Configuration loaded from environment variables
+//!
+//! Values found in neither the environment nor the settings file (by
+//! default `adsb-capture.conf` in the working directory, `KEY=VALUE` lines)
+//! take their defaults. `--tune-gain` writes its result to the settings file.
 
-use std::path::PathBuf;
+use crate::event_filter::{self, BoundingBox};
+use crate::send_policy::SendMode;
+use std::collections::HashSet;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+
+/// Settings file used when `SETTINGS_FILE` isn't set
+pub const DEFAULT_SETTINGS_FILE: &str = "adsb-capture.conf";
+
+/// Aircraft info cache used when `AIRCRAFT_CACHE_FILE` isn't set
+pub const DEFAULT_AIRCRAFT_CACHE_FILE: &str = "adsb-capture.db";
 
 /// Application configuration
 #[derive(Debug, Clone)]
@@ -25,41 +39,365 @@ pub struct Config {
 
     /// Signal metrics reporting interval in milliseconds
     pub signal_report_interval_ms: u64,
+
+    /// Forward every raw frame to the gateway for archival
+    pub forward_raw_frames: bool,
+
+    /// Attach the raw frame, error correction and detector confidence to
+    /// every aircraft event
+    pub event_provenance: bool,
+
+    /// Scan this far either side of 1090 MHz before capture starts (0 = no scan)
+    pub scan_span_mhz: f32,
+
+    /// Step between scanned frequencies
+    pub scan_step_mhz: f32,
+
+    /// Blank strong pulses too long for Mode S (radar, DME, ignition noise)
+    pub noise_blanker: bool,
+
+    /// Pulses at least this many times the noise floor are blanked
+    pub noise_blanker_factor: u32,
+
+    /// Send a smoothed track estimate alongside each raw position
+    pub track_smoothing: bool,
+
+    /// Positions below this integrity category are dropped (0 = no limit);
+    /// version 0 NUCp is compared as the equivalent NIC
+    pub min_position_nic: u8,
+
+    /// Positions below this accuracy category are dropped (0 = no limit)
+    pub min_position_nac_p: u8,
+
+    /// Limit error correction while more than this share of corrected
+    /// frames look like noise (0 = never)
+    pub max_false_positive_rate: f32,
+
+    /// Identical frames within this many milliseconds are dropped as
+    /// echoes (0 = keep all)
+    pub dedup_window_ms: u64,
+
+    /// Receiver latitude and longitude, to place surface positions
+    pub receiver_location: Option<(f64, f64)>,
+
+    /// Measure the clock offset to the gateway this often (0 = never)
+    pub clock_sync_interval_secs: u64,
+
+    /// Shift outgoing timestamps to the gateway's clock
+    pub clock_correction: bool,
+
+    /// Warn when the clock is this far off the gateway's
+    pub clock_skew_warn_ms: i64,
+
+    /// Only send aircraft at or above this altitude
+    pub filter_min_altitude_ft: Option<i32>,
+
+    /// Only send aircraft at or below this altitude
+    pub filter_max_altitude_ft: Option<i32>,
+
+    /// Only send aircraft inside this box
+    pub filter_bbox: Option<BoundingBox>,
+
+    /// Never send aircraft inside these boxes
+    pub filter_exclude: Vec<BoundingBox>,
+
+    /// Only send these addresses (empty = all)
+    pub filter_icao_allow: HashSet<u32>,
+
+    /// Never send these addresses
+    pub filter_icao_deny: HashSet<u32>,
+
+    /// Send aircraft reporting surface positions
+    pub filter_ground: bool,
+
+    /// Ping the gateway over the heartbeat stream this often (0 = never)
+    pub heartbeat_interval_secs: u64,
+
+    /// Reconnect when a heartbeat goes unanswered this long
+    pub heartbeat_timeout_secs: u64,
+
+    /// Send events for every frame or only on material changes
+    pub event_send_policy: SendMode,
+
+    /// Altitude change that makes an event worth sending
+    pub event_min_alt_change_ft: i32,
+
+    /// Send an event for each aircraft at least this often
+    pub event_keepalive_secs: u64,
+
+    /// Serve the tracked aircraft and a map page here (unset = off)
+    pub local_http_addr: Option<SocketAddr>,
+
+    /// Aggregators (`host:port`) to send every frame to in Beast format
+    pub beast_feeds: Vec<String>,
+
+    /// Callsign and category cache (unset = off)
+    pub aircraft_cache_file: Option<PathBuf>,
+
+    /// Fetch the gateway's aircraft labels this often (0 = never)
+    pub aircraft_cache_sync_secs: u64,
+
+    /// Where persisted settings are read from and written to
+    pub settings_file: PathBuf,
 }
 
 impl Config {
-    /// Load configuration from environment variables
+    /// Load configuration from environment variables, falling back to the
+    /// settings file for anything not set
     pub fn from_env() -> Self {
+        let settings_file = std::env::var("SETTINGS_FILE")
+            .map(PathBuf::from)
+            .unwrap_or_else(|_| PathBuf::from(DEFAULT_SETTINGS_FILE));
+        let settings = SettingsFile::load(&settings_file);
+        let var = |name: &str| {
+            std::env::var(name)
+                .ok()
+                .or_else(|| settings.get(name).map(str::to_string))
+        };
+
         Self {
-            gateway_url: std::env::var("GATEWAY_URL")
-                .unwrap_or_else(|_| "http://localhost:30051".to_string()),
+            gateway_url: var("GATEWAY_URL")
+                .unwrap_or_else(|| "http://localhost:30051".to_string()),
 
-            device_index: std::env::var("DEVICE_INDEX")
-                .ok()
+            device_index: var("DEVICE_INDEX")
                 .and_then(|s| s.parse().ok())
                 .unwrap_or(0),
 
-            device_id: std::env::var("DEVICE_ID")
-                .unwrap_or_else(|_| format!("RTL-SDR-{:08X}", 1)),
+            device_id: var("DEVICE_ID")
+                .unwrap_or_else(|| format!("RTL-SDR-{:08X}", 1)),
 
-            gain_db: std::env::var("DEVICE_GAIN")
-                .ok()
+            gain_db: var("DEVICE_GAIN")
                 .and_then(|s| s.parse().ok())
                 .unwrap_or(49.6),
 
-            ppm_error: std::env::var("PPM_ERROR")
-                .ok()
+            ppm_error: var("PPM_ERROR")
                 .and_then(|s| s.parse().ok())
                 .unwrap_or(0),
 
-            rtl_adsb_path: std::env::var("RTL_ADSB_PATH")
+            rtl_adsb_path: var("RTL_ADSB_PATH")
                 .map(PathBuf::from)
-                .unwrap_or_else(|_| PathBuf::from("rtl_adsb.exe")),
+                .unwrap_or_else(|| PathBuf::from("rtl_adsb.exe")),
 
-            signal_report_interval_ms: std::env::var("SIGNAL_REPORT_INTERVAL_MS")
-                .ok()
+            signal_report_interval_ms: var("SIGNAL_REPORT_INTERVAL_MS")
                 .and_then(|s| s.parse().ok())
                 .unwrap_or(500),  // 0.5 seconds for real-time signal updates
+
+            forward_raw_frames: var("FORWARD_RAW_FRAMES")
+                .map(|s| s == "1" || s.eq_ignore_ascii_case("true"))
+                .unwrap_or(false),
+
+            event_provenance: var("EVENT_PROVENANCE")
+                .map(|s| s == "1" || s.eq_ignore_ascii_case("true"))
+                .unwrap_or(false),
+
+            scan_span_mhz: var("SCAN_SPAN_MHZ")
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(0.0),
+
+            scan_step_mhz: var("SCAN_STEP_MHZ")
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(0.25),
+
+            noise_blanker: var("NOISE_BLANKER")
+                .map(|s| s == "1" || s.eq_ignore_ascii_case("true"))
+                .unwrap_or(false),
+
+            noise_blanker_factor: var("NOISE_BLANKER_FACTOR")
+                .and_then(|s| s.parse().ok())
+                .filter(|&f| f > 1)
+                .unwrap_or(8),
+
+            track_smoothing: var("TRACK_SMOOTHING")
+                .map(|s| s == "1" || s.eq_ignore_ascii_case("true"))
+                .unwrap_or(true),
+
+            min_position_nic: var("MIN_POSITION_NIC")
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(0),
+
+            min_position_nac_p: var("MIN_POSITION_NACP")
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(0),
+
+            max_false_positive_rate: var("MAX_FALSE_POSITIVE_RATE")
+                .and_then(|s| s.parse().ok())
+                .filter(|&r: &f32| (0.0..1.0).contains(&r))
+                .unwrap_or(0.0),
+
+            dedup_window_ms: var("DEDUP_WINDOW_MS")
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(2),
+
+            receiver_location: var("RECEIVER_LAT")
+                .and_then(|s| s.parse::<f64>().ok())
+                .zip(var("RECEIVER_LON").and_then(|s| s.parse::<f64>().ok()))
+                .filter(|(lat, lon)| (-90.0..=90.0).contains(lat) && (-180.0..=180.0).contains(lon)),
+
+            clock_sync_interval_secs: var("CLOCK_SYNC_INTERVAL_SECS")
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(60),
+
+            clock_correction: var("CLOCK_CORRECTION")
+                .map(|s| s == "1" || s.eq_ignore_ascii_case("true"))
+                .unwrap_or(true),
+
+            clock_skew_warn_ms: var("CLOCK_SKEW_WARN_MS")
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(2000),
+
+            filter_min_altitude_ft: var("FILTER_MIN_ALT_FT").and_then(|s| s.parse().ok()),
+
+            filter_max_altitude_ft: var("FILTER_MAX_ALT_FT").and_then(|s| s.parse().ok()),
+
+            filter_bbox: var("FILTER_BBOX").and_then(|s| BoundingBox::parse(&s)),
+
+            filter_exclude: var("FILTER_EXCLUDE_BBOX")
+                .map(|s| s.split(';').filter_map(BoundingBox::parse).collect())
+                .unwrap_or_default(),
+
+            filter_icao_allow: var("FILTER_ICAO_ALLOW")
+                .map(|s| event_filter::parse_addresses(&s))
+                .unwrap_or_default(),
+
+            filter_icao_deny: var("FILTER_ICAO_DENY")
+                .map(|s| event_filter::parse_addresses(&s))
+                .unwrap_or_default(),
+
+            filter_ground: var("FILTER_GROUND")
+                .map(|s| s == "1" || s.eq_ignore_ascii_case("true"))
+                .unwrap_or(true),
+
+            heartbeat_interval_secs: var("HEARTBEAT_INTERVAL_SECS")
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(5),
+
+            heartbeat_timeout_secs: var("HEARTBEAT_TIMEOUT_SECS")
+                .and_then(|s| s.parse().ok())
+                .filter(|&t| t > 0)
+                .unwrap_or(15),
+
+            event_send_policy: var("EVENT_SEND_POLICY")
+                .and_then(|s| SendMode::parse(&s))
+                .unwrap_or_default(),
+
+            event_min_alt_change_ft: var("EVENT_MIN_ALT_CHANGE_FT")
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(100),
+
+            event_keepalive_secs: var("EVENT_KEEPALIVE_SECS")
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(10),
+
+            local_http_addr: var("LOCAL_HTTP_ADDR").and_then(|s| s.parse().ok()),
+
+            beast_feeds: var("BEAST_FEEDS")
+                .map(|s| {
+                    s.split(',')
+                        .map(str::trim)
+                        .filter(|t| !t.is_empty())
+                        .map(str::to_string)
+                        .collect()
+                })
+                .unwrap_or_default(),
+
+            aircraft_cache_file: match var("AIRCRAFT_CACHE_FILE") {
+                Some(path) if path.trim().is_empty() => None,
+                Some(path) => Some(PathBuf::from(path)),
+                None => Some(PathBuf::from(DEFAULT_AIRCRAFT_CACHE_FILE)),
+            },
+
+            aircraft_cache_sync_secs: var("AIRCRAFT_CACHE_SYNC_SECS")
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(300),
+
+            settings_file,
+        }
+    }
+}
+
+/// `KEY=VALUE` settings persisted between runs; comments and blank lines
+/// are kept when the file is rewritten
+#[derive(Debug, Default, Clone)]
+pub struct SettingsFile {
+    lines: Vec<String>,
+}
+
+impl SettingsFile {
+    /// Read `path`; a missing or unreadable file has no settings
+    pub fn load(path: &Path) -> Self {
+        Self::parse(&std::fs::read_to_string(path).unwrap_or_default())
+    }
+
+    fn parse(text: &str) -> Self {
+        Self {
+            lines: text.lines().map(str::to_string).collect(),
+        }
+    }
+
+    fn key_value(line: &str) -> Option<(&str, &str)> {
+        let line = line.trim();
+        if line.starts_with('#') {
+            return None;
         }
+        line.split_once('=').map(|(k, v)| (k.trim(), v.trim()))
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.lines
+            .iter()
+            .rev()
+            .filter_map(|line| Self::key_value(line))
+            .find(|(k, _)| *k == key)
+            .map(|(_, v)| v)
+    }
+
+    /// Replace every `key` line with one holding `value`, or append one
+    pub fn set(&mut self, key: &str, value: &str) {
+        let line = format!("{}={}", key, value);
+        let mut replaced = false;
+        self.lines.retain_mut(|existing| {
+            if Self::key_value(existing).is_some_and(|(k, _)| k == key) {
+                if replaced {
+                    return false;
+                }
+                *existing = line.clone();
+                replaced = true;
+            }
+            true
+        });
+        if !replaced {
+            self.lines.push(line);
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        let mut text = self.lines.join("\n");
+        text.push('\n');
+        std::fs::write(path, text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_settings_file() {
+        let mut settings = SettingsFile::parse("# tuned 2024-01-15\nDEVICE_GAIN = 38.6\nPPM_ERROR=2\n");
+        assert_eq!(settings.get("DEVICE_GAIN"), Some("38.6"));
+        assert_eq!(settings.get("DEVICE_INDEX"), None);
+
+        settings.set("DEVICE_GAIN", "42.1");
+        settings.set("DEVICE_INDEX", "1");
+        assert_eq!(
+            settings.lines,
+            vec!["# tuned 2024-01-15", "DEVICE_GAIN=42.1", "PPM_ERROR=2", "DEVICE_INDEX=1"]
+        );
+
+        let path = std::env::temp_dir().join(format!("settings-test-{}.conf", std::process::id()));
+        settings.save(&path).unwrap();
+        assert_eq!(SettingsFile::load(&path).get("DEVICE_GAIN"), Some("42.1"));
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(SettingsFile::load(&path).get("DEVICE_GAIN"), None);
     }
 }
@@ -0,0 +1,86 @@
+//! Duplicate frame suppression
+//!
+//! Multipath echoes and overlapping preamble detections can hand the same
+//! frame to the decoder several times within a few microseconds to
+//! milliseconds. Frames identical to one received less than
+//! `DEDUP_WINDOW_MS` earlier (measured in samples, so independent of when
+//! the decode loop gets to them) are dropped before parsing and counted,
+//! instead of each copy reaching the tracker, the gateway stream and the
+//! message counts.
+
+use std::collections::VecDeque;
+
+/// Frames remembered; more than a window's worth even at peak traffic
+const MAX_RECENT: usize = 64;
+
+/// Recently accepted frames, by sample time
+#[derive(Debug)]
+pub struct FrameDedup {
+    window_samples: u64,
+    recent: VecDeque<(u64, Vec<u8>)>,
+    duplicates: u64,
+}
+
+impl FrameDedup {
+    /// Drop copies within `window_ms` at `sample_rate` (0 = keep everything)
+    pub fn new(window_ms: u64, sample_rate: u32) -> Self {
+        Self {
+            window_samples: window_ms * sample_rate as u64 / 1000,
+            recent: VecDeque::with_capacity(MAX_RECENT),
+            duplicates: 0,
+        }
+    }
+
+    /// `true` (and counted) when the frame `data` received at sample
+    /// `now` repeats one inside the window
+    pub fn is_duplicate(&mut self, data: &[u8], now: u64) -> bool {
+        if self.window_samples == 0 {
+            return false;
+        }
+        while self
+            .recent
+            .front()
+            .is_some_and(|(at, _)| now.saturating_sub(*at) > self.window_samples)
+        {
+            self.recent.pop_front();
+        }
+        if self.recent.iter().any(|(_, recent)| recent == data) {
+            self.duplicates += 1;
+            return true;
+        }
+        if self.recent.len() == MAX_RECENT {
+            self.recent.pop_front();
+        }
+        self.recent.push_back((now, data.to_vec()));
+        false
+    }
+
+    /// Duplicates dropped since start
+    pub fn duplicates(&self) -> u64 {
+        self.duplicates
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dedup() {
+        // 2 ms at 2 Msps = 4000 samples
+        let mut dedup = FrameDedup::new(2, 2_000_000);
+        let reply = [0x5D, 0x48, 0x40, 0xD6, 0xD6, 0xC7, 0xE8];
+        assert!(!dedup.is_duplicate(&reply, 10_000));
+        // Echo a few microseconds later
+        assert!(dedup.is_duplicate(&reply, 10_012));
+        // A different frame in between is kept
+        assert!(!dedup.is_duplicate(&[0x5D, 0x71, 0xBE, 0x11, 0, 0, 0], 10_500));
+        // The same reply after the window is a new reply
+        assert!(!dedup.is_duplicate(&reply, 20_000));
+        assert_eq!(dedup.duplicates(), 1);
+
+        let mut off = FrameDedup::new(0, 2_000_000);
+        assert!(!off.is_duplicate(&reply, 0));
+        assert!(!off.is_duplicate(&reply, 1));
+    }
+}
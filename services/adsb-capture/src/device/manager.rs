@@ -6,10 +6,13 @@ use anyhow::Result;
 use tokio::sync::mpsc;
 use tracing::{debug, error, info, warn};
 
-use crate::adsb::{parse_message, AircraftData, CprContext, ParseError};
+use crate::adsb::{
+    format_address, parse_message, AircraftData, CprContext, EmergencySquawk, IcaoTrust,
+    ParseError,
+};
 use crate::config::Config;
 use crate::decoder::DecoderRunner;
-use crate::grpc::adsb::{AircraftEvent, DeviceStatus, SignalMetrics};
+use crate::grpc::adsb::{AircraftEvent, DeviceStatus, Emergency, SignalMetrics};
 
 use super::state::DeviceState;
 
@@ -18,6 +21,7 @@ pub struct DeviceManager {
     config: Config,
     device_state: DeviceState,
     cpr_context: CprContext,
+    trust: IcaoTrust,
     aircraft_tx: mpsc::Sender<AircraftEvent>,
     signal_tx: mpsc::Sender<SignalMetrics>,
     status_tx: mpsc::Sender<DeviceStatus>,
@@ -35,11 +39,14 @@ impl DeviceManager {
             config.device_index,
             config.gain_db,
         );
+        let mut cpr_context = CprContext::new(256);
+        cpr_context.set_receiver(config.receiver_location);
 
         Self {
             config,
             device_state,
-            cpr_context: CprContext::new(256),
+            cpr_context,
+            trust: IcaoTrust::new(),
             aircraft_tx,
             signal_tx,
             status_tx,
@@ -86,7 +93,8 @@ impl DeviceManager {
         loop {
             tokio::select! {
                 Some(raw_msg) = raw_rx.recv() => {
-                    match parse_message(&raw_msg, &mut self.cpr_context) {
+                    self.trust.observe(&raw_msg, Instant::now());
+                    match parse_message(&raw_msg, &mut self.cpr_context, &self.trust) {
                         Ok(aircraft) => {
                             self.device_state.stats.record_decoded();
                             messages_since_report += 1;
@@ -205,17 +213,32 @@ impl DeviceManager {
         let event = AircraftEvent {
             device_id: self.device_state.device_id.clone(),
             timestamp_ms: chrono::Utc::now().timestamp_millis() as u64,
-            icao: format!("{:06X}", aircraft.icao_address),
-            callsign: aircraft.callsign.clone().unwrap_or_default(),
-            altitude_ft: aircraft.altitude_ft.unwrap_or(0),
-            latitude: aircraft.latitude.unwrap_or(0.0),
-            longitude: aircraft.longitude.unwrap_or(0.0),
-            speed_kts: aircraft.ground_speed_kts.unwrap_or(0.0),
-            heading_deg: aircraft.heading_deg.unwrap_or(0.0),
-            vertical_rate_fpm: aircraft.vertical_rate_fpm.unwrap_or(0),
-            squawk: aircraft.squawk.map(|s| format!("{:04}", s)).unwrap_or_default(),
+            icao: format_address(aircraft.key()),
+            callsign: aircraft.callsign.clone(),
+            altitude_ft: aircraft.altitude_ft,
+            latitude: aircraft.latitude,
+            longitude: aircraft.longitude,
+            speed_kts: aircraft.ground_speed_kts,
+            track_deg: aircraft.track_deg,
+            magnetic_heading_deg: aircraft.magnetic_heading_deg,
+            vertical_rate_fpm: aircraft.vertical_rate_fpm,
+            baro_rate_fpm: aircraft.vertical_rate_fpm.filter(|_| !aircraft.vertical_rate_gnss),
+            geom_rate_fpm: aircraft.vertical_rate_fpm.filter(|_| aircraft.vertical_rate_gnss),
+            squawk: aircraft.squawk.map(|s| format!("{:04}", s)),
             downlink_format: aircraft.df as u32,
             type_code: aircraft.tc as u32,
+            emergency: Emergency::from(aircraft.squawk.and_then(EmergencySquawk::from_squawk)) as i32,
+            removed: false,
+            category: aircraft.category.clone(),
+            filtered_latitude: None,
+            filtered_longitude: None,
+            adsb_version: aircraft.operational_status.map(|s| s.version.number() as u32),
+            nic: aircraft.integrity.and_then(|i| i.nic).map(u32::from),
+            nac_p: aircraft.integrity.and_then(|i| i.nac_p).map(u32::from),
+            nuc_p: aircraft.integrity.and_then(|i| i.nuc_p).map(u32::from),
+            containment_radius_m: aircraft.integrity.and_then(|i| i.rc_m),
+            provenance: None,
+            source: None,
         };
 
         self.aircraft_tx.send(event).await?;
@@ -239,6 +262,12 @@ impl DeviceManager {
             samples_processed: 0,
             noise_floor: 0,
             peak_signal: 0,
+            interrogators: Vec::new(),
+            downlink_formats: Vec::new(),
+            type_codes: Vec::new(),
+            false_positive_rate: None,
+            corrected_false_positive_rate: None,
+            duplicate_frames: 0,
         };
 
         if let Err(e) = self.signal_tx.send(metrics).await {
@@ -255,6 +284,8 @@ impl DeviceManager {
             center_freq: self.device_state.center_freq,
             gain_db: self.device_state.gain_db,
             timestamp_ms: chrono::Utc::now().timestamp_millis() as u64,
+            software_version: env!("CARGO_PKG_VERSION").to_string(),
+            ..Default::default()
         };
 
         if let Err(e) = self.status_tx.send(status).await {
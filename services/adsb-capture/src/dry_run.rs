@@ -0,0 +1,135 @@
+//! Dry run without a gateway
+//!
+//! `adsb-capture --dry-run` runs the full SDR and decoding pipeline but
+//! never connects to the gateway, e.g. to try antennas in the field without
+//! an uplink, where the streams would otherwise retry forever. What would
+//! have been streamed is logged instead: every aircraft event on one line,
+//! a signal summary every `SIGNAL_LOG_INTERVAL_SECS` and device status
+//! changes. Raw frames and scan reports are not sent anywhere.
+
+use crate::grpc::adsb::{AircraftEvent, DeviceStatus, SignalMetrics};
+use std::fmt::Write;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tracing::info;
+
+/// Seconds between signal summaries
+const SIGNAL_LOG_INTERVAL_SECS: u64 = 10;
+
+/// `true` when the command line asks for a dry run
+pub fn requested(args: &[String]) -> bool {
+    args.iter().any(|a| a == "--dry-run")
+}
+
+/// One log line for an aircraft event, with the fields it carries
+pub fn describe(event: &AircraftEvent) -> String {
+    if event.removed {
+        return format!("{} timed out", event.icao);
+    }
+    let mut line = format!("{} DF{}", event.icao, event.downlink_format);
+    if event.type_code > 0 {
+        let _ = write!(line, "/TC{}", event.type_code);
+    }
+    if let Some(callsign) = &event.callsign {
+        let _ = write!(line, " {}", callsign);
+    }
+    if let Some(squawk) = &event.squawk {
+        let _ = write!(line, " squawk {}", squawk);
+    }
+    if let Some(altitude) = event.altitude_ft {
+        let _ = write!(line, " {} ft", altitude);
+    }
+    if let (Some(lat), Some(lon)) = (event.latitude, event.longitude) {
+        let _ = write!(line, " {:.4},{:.4}", lat, lon);
+    }
+    if let Some(speed) = event.speed_kts {
+        let _ = write!(line, " {:.0} kt", speed);
+    }
+    if let Some(track) = event.track_deg {
+        let _ = write!(line, " {:.0}°", track);
+    }
+    if let Some(rate) = event.vertical_rate_fpm {
+        let _ = write!(line, " {:+} fpm", rate);
+    }
+    line
+}
+
+/// Log what the streams would send; the returned tasks run until the
+/// channels close or are aborted
+pub fn spawn_logging(
+    mut aircraft_rx: mpsc::Receiver<AircraftEvent>,
+    mut signal_rx: mpsc::Receiver<SignalMetrics>,
+    mut status_rx: mpsc::Receiver<DeviceStatus>,
+) -> Vec<JoinHandle<()>> {
+    let aircraft = tokio::spawn(async move {
+        while let Some(event) = aircraft_rx.recv().await {
+            info!("[Aircraft] {}", describe(&event));
+        }
+    });
+
+    let signal = tokio::spawn(async move {
+        let mut last_log = Instant::now();
+        while let Some(m) = signal_rx.recv().await {
+            if last_log.elapsed() < Duration::from_secs(SIGNAL_LOG_INTERVAL_SECS) {
+                continue;
+            }
+            last_log = Instant::now();
+            info!(
+                "[Signal] {:.1} dBFS, noise {:.1} dBFS, SNR {:.1} dB, {:.1} msg/s, {} frames, {} corrected, {} CRC errors",
+                m.signal_dbfs,
+                m.noise_dbfs,
+                m.snr_db,
+                m.msg_rate,
+                m.frames_decoded,
+                m.corrected_frames,
+                m.crc_errors
+            );
+        }
+    });
+
+    let status = tokio::spawn(async move {
+        let mut connected = None;
+        while let Some(status) = status_rx.recv().await {
+            if connected != Some(status.connected) {
+                info!(
+                    "[Status] {} {}",
+                    status.device_id,
+                    if status.connected { "connected" } else { "disconnected" }
+                );
+                connected = Some(status.connected);
+            }
+        }
+    });
+
+    vec![aircraft, signal, status]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_describe() {
+        let event = AircraftEvent {
+            icao: "71BE11".into(),
+            downlink_format: 17,
+            type_code: 11,
+            callsign: Some("KAL123".into()),
+            altitude_ft: Some(35000),
+            latitude: Some(37.4602),
+            longitude: Some(126.4407),
+            vertical_rate_fpm: Some(-640),
+            ..Default::default()
+        };
+        assert_eq!(describe(&event), "71BE11 DF17/TC11 KAL123 35000 ft 37.4602,126.4407 -640 fpm");
+
+        let removed = AircraftEvent {
+            icao: "71BE11".into(),
+            removed: true,
+            ..Default::default()
+        };
+        assert_eq!(describe(&removed), "71BE11 timed out");
+        assert!(requested(&["adsb-capture".into(), "--dry-run".into()]));
+    }
+}
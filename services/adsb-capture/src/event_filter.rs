@@ -0,0 +1,181 @@
+//! Aircraft event filters
+//!
+//! A chain of rules applied to each aircraft's state before its update is
+//! sent to the gateway, for operators who must keep certain areas out of
+//! their feed or only care about traffic near their field. Every rule must
+//! pass; a rule judges only what is known, so aircraft without a position
+//! pass the area rules and those without an altitude the altitude rules.
+//! Removals always go out. Raw frames (`FORWARD_RAW_FRAMES`) are not
+//! filtered. The chain is set up from the `FILTER_*` settings.
+
+use crate::aircraft_tracker::AircraftState;
+use crate::config::Config;
+use std::collections::HashSet;
+
+/// Latitude/longitude box; `min_lon > max_lon` crosses the antimeridian
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoundingBox {
+    pub min_lat: f64,
+    pub min_lon: f64,
+    pub max_lat: f64,
+    pub max_lon: f64,
+}
+
+impl BoundingBox {
+    /// `min_lat,min_lon,max_lat,max_lon`
+    pub fn parse(s: &str) -> Option<Self> {
+        let parts: Vec<f64> = s.split(',').map(|p| p.trim().parse().ok()).collect::<Option<_>>()?;
+        let [min_lat, min_lon, max_lat, max_lon] = parts[..] else {
+            return None;
+        };
+        let valid = (-90.0..=90.0).contains(&min_lat)
+            && (-90.0..=90.0).contains(&max_lat)
+            && (-180.0..=180.0).contains(&min_lon)
+            && (-180.0..=180.0).contains(&max_lon)
+            && min_lat <= max_lat;
+        valid.then_some(Self {
+            min_lat,
+            min_lon,
+            max_lat,
+            max_lon,
+        })
+    }
+
+    pub fn contains(&self, lat: f64, lon: f64) -> bool {
+        let lon_ok = if self.min_lon <= self.max_lon {
+            (self.min_lon..=self.max_lon).contains(&lon)
+        } else {
+            lon >= self.min_lon || lon <= self.max_lon
+        };
+        (self.min_lat..=self.max_lat).contains(&lat) && lon_ok
+    }
+}
+
+/// Comma-separated hex addresses; invalid entries are skipped
+pub fn parse_addresses(s: &str) -> HashSet<u32> {
+    s.split(',')
+        .filter_map(|a| u32::from_str_radix(a.trim(), 16).ok())
+        .filter(|&a| a <= 0xFFFFFF)
+        .collect()
+}
+
+/// One link of the chain
+#[derive(Debug, Clone, PartialEq)]
+pub enum Rule {
+    MinAltitude(i32),
+    MaxAltitude(i32),
+    Inside(BoundingBox),
+    Outside(BoundingBox),
+    Allow(HashSet<u32>),
+    Deny(HashSet<u32>),
+    Airborne,
+}
+
+impl Rule {
+    fn passes(&self, state: &AircraftState) -> bool {
+        let position = state.latitude.zip(state.longitude);
+        match self {
+            Rule::MinAltitude(min) => state.altitude_ft.is_none_or(|alt| alt >= *min),
+            Rule::MaxAltitude(max) => state.altitude_ft.is_none_or(|alt| alt <= *max),
+            Rule::Inside(bbox) => position.is_none_or(|(lat, lon)| bbox.contains(lat, lon)),
+            Rule::Outside(bbox) => position.is_none_or(|(lat, lon)| !bbox.contains(lat, lon)),
+            Rule::Allow(addresses) => addresses.contains(&state.icao),
+            Rule::Deny(addresses) => !addresses.contains(&state.icao),
+            Rule::Airborne => !state.on_ground,
+        }
+    }
+}
+
+/// Rules an aircraft must all pass to be sent
+#[derive(Debug, Clone, Default)]
+pub struct EventFilter {
+    rules: Vec<Rule>,
+}
+
+impl EventFilter {
+    pub fn new(rules: Vec<Rule>) -> Self {
+        Self { rules }
+    }
+
+    /// The chain configured by the `FILTER_*` settings
+    pub fn from_config(config: &Config) -> Self {
+        let mut rules = Vec::new();
+        rules.extend(config.filter_min_altitude_ft.map(Rule::MinAltitude));
+        rules.extend(config.filter_max_altitude_ft.map(Rule::MaxAltitude));
+        rules.extend(config.filter_bbox.map(Rule::Inside));
+        rules.extend(config.filter_exclude.iter().copied().map(Rule::Outside));
+        if !config.filter_icao_allow.is_empty() {
+            rules.push(Rule::Allow(config.filter_icao_allow.clone()));
+        }
+        if !config.filter_icao_deny.is_empty() {
+            rules.push(Rule::Deny(config.filter_icao_deny.clone()));
+        }
+        if !config.filter_ground {
+            rules.push(Rule::Airborne);
+        }
+        Self::new(rules)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    pub fn rules(&self) -> &[Rule] {
+        &self.rules
+    }
+
+    /// Whether `state` may be sent
+    pub fn matches(&self, state: &AircraftState) -> bool {
+        self.rules.iter().all(|rule| rule.passes(state))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filter_chain() {
+        let field = BoundingBox::parse("37.0,126.0,38.0,127.5").unwrap();
+        let restricted = BoundingBox::parse("37.5,126.9,37.7,127.1").unwrap();
+        assert_eq!(BoundingBox::parse("38,126,37,127"), None);
+        assert_eq!(parse_addresses("71be11, 06A0A5,zz,1000000"), HashSet::from([0x71BE11, 0x06A0A5]));
+
+        let filter = EventFilter::new(vec![
+            Rule::MinAltitude(1000),
+            Rule::Inside(field),
+            Rule::Outside(restricted),
+            Rule::Deny(HashSet::from([0x06A0A5])),
+            Rule::Airborne,
+        ]);
+        let mut state = AircraftState::new(0x71BE11);
+        // Nothing known yet
+        assert!(filter.matches(&state));
+
+        state.altitude_ft = Some(5000);
+        state.latitude = Some(37.2);
+        state.longitude = Some(126.5);
+        assert!(filter.matches(&state));
+
+        state.altitude_ft = Some(500);
+        assert!(!filter.matches(&state));
+        state.altitude_ft = Some(5000);
+
+        state.latitude = Some(37.6);
+        state.longitude = Some(127.0);
+        assert!(!filter.matches(&state));
+        state.latitude = Some(39.0);
+        assert!(!filter.matches(&state));
+        state.latitude = Some(37.2);
+        state.longitude = Some(126.5);
+
+        state.on_ground = true;
+        assert!(!filter.matches(&state));
+        state.on_ground = false;
+
+        state.icao = 0x06A0A5;
+        assert!(!filter.matches(&state));
+        assert!(!EventFilter::new(vec![Rule::Allow(HashSet::from([0x71BE11]))]).matches(&state));
+        assert!(EventFilter::default().matches(&state));
+    }
+}
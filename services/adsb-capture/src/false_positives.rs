@@ -0,0 +1,170 @@
+//! False positive estimate
+//!
+//! A frame that passes the CRC can still be noise: a DF11 reply whose 24
+//! parity bits happen to match, or an error correction that lands on the
+//! wrong codeword. Such frames decode to random addresses that are never
+//! heard again, while a real aircraft keeps transmitting. A frame whose
+//! address goes `WINDOW_SECS` without another frame is counted as a likely
+//! false positive, and the share of them among recent frames is reported
+//! with the signal metrics, separately for frames that needed error
+//! correction. The last frame of every real aircraft also counts, so the
+//! estimate is an upper bound, by roughly one frame per aircraft that flies
+//! out of range.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// How long an address must stay silent before its last frame is suspect
+const WINDOW_SECS: u64 = 60;
+
+/// Counts halve over this long, so the rates follow the last few minutes
+const HALF_LIFE_SECS: f64 = 300.0;
+
+/// Fewest settled frames before a rate is reported
+const MIN_FRAMES: f64 = 20.0;
+
+/// Settled frames of one kind
+#[derive(Debug, Default, Clone, Copy)]
+struct Counts {
+    /// Followed by another frame from the same address
+    confirmed: f64,
+    /// Never followed by one
+    orphaned: f64,
+}
+
+impl Counts {
+    fn rate(&self) -> Option<f32> {
+        let total = self.confirmed + self.orphaned;
+        (total >= MIN_FRAMES).then(|| (self.orphaned / total) as f32)
+    }
+
+    fn decay(&mut self, factor: f64) {
+        self.confirmed *= factor;
+        self.orphaned *= factor;
+    }
+}
+
+/// The last frame from an address
+#[derive(Debug, Clone, Copy)]
+struct LastFrame {
+    at: Instant,
+    corrected: bool,
+}
+
+/// Estimated share of decoded frames that were noise
+#[derive(Debug, Default)]
+pub struct FalsePositiveEstimator {
+    last: HashMap<u32, LastFrame>,
+    clean: Counts,
+    corrected: Counts,
+    decayed_at: Option<Instant>,
+}
+
+impl FalsePositiveEstimator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn counts(&mut self, corrected: bool) -> &mut Counts {
+        if corrected {
+            &mut self.corrected
+        } else {
+            &mut self.clean
+        }
+    }
+
+    /// Count a decoded frame from `address`, settling the address's
+    /// previous frame
+    pub fn record(&mut self, address: u32, corrected: bool, now: Instant) {
+        let window = Duration::from_secs(WINDOW_SECS);
+        if let Some(previous) = self.last.insert(address, LastFrame { at: now, corrected }) {
+            let counts = self.counts(previous.corrected);
+            if now.saturating_duration_since(previous.at) <= window {
+                counts.confirmed += 1.0;
+            } else {
+                counts.orphaned += 1.0;
+            }
+        }
+    }
+
+    /// Settle the frames of addresses silent for the whole window, and age
+    /// the counts
+    pub fn sweep(&mut self, now: Instant) {
+        let window = Duration::from_secs(WINDOW_SECS);
+        let mut orphans = Vec::new();
+        self.last.retain(|_, last| {
+            let silent = now.saturating_duration_since(last.at) > window;
+            if silent {
+                orphans.push(last.corrected);
+            }
+            !silent
+        });
+        for corrected in orphans {
+            self.counts(corrected).orphaned += 1.0;
+        }
+
+        if let Some(decayed_at) = self.decayed_at {
+            let age = now.saturating_duration_since(decayed_at).as_secs_f64();
+            let factor = 0.5f64.powf(age / HALF_LIFE_SECS);
+            self.clean.decay(factor);
+            self.corrected.decay(factor);
+        }
+        self.decayed_at = Some(now);
+    }
+
+    /// Estimated false positive share of all frames, if enough have settled
+    pub fn rate(&self) -> Option<f32> {
+        Counts {
+            confirmed: self.clean.confirmed + self.corrected.confirmed,
+            orphaned: self.clean.orphaned + self.corrected.orphaned,
+        }
+        .rate()
+    }
+
+    /// Estimated false positive share of error-corrected frames
+    pub fn corrected_rate(&self) -> Option<f32> {
+        self.corrected.rate()
+    }
+
+    /// Forget the corrected frames' history, e.g. after error correction
+    /// was tightened and the old frames no longer say anything about it
+    pub fn reset_corrected(&mut self) {
+        self.corrected = Counts::default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_orphaned_frames() {
+        let mut estimator = FalsePositiveEstimator::new();
+        let t = Instant::now();
+        let at = |secs| t + Duration::from_secs(secs);
+
+        // One aircraft heard every second for 40 s, plus twenty corrected
+        // frames from addresses never heard again
+        for secs in 0..40 {
+            estimator.record(0x71BE11, secs % 4 == 0, at(secs));
+        }
+        for address in 0..20 {
+            estimator.record(0x100 + address, true, at(5));
+        }
+        estimator.sweep(at(30));
+        assert_eq!(estimator.rate(), Some(0.0));
+        // Only ten corrected frames have settled so far
+        assert_eq!(estimator.corrected_rate(), None);
+
+        // A minute on, the noise addresses and the aircraft's last frame
+        // are orphans
+        estimator.sweep(at(100));
+        let rate = estimator.rate().unwrap();
+        assert!((rate - 21.0 / 60.0).abs() < 1e-6, "{}", rate);
+        let corrected = estimator.corrected_rate().unwrap();
+        assert!((corrected - 20.0 / 30.0).abs() < 1e-6, "{}", corrected);
+
+        estimator.reset_corrected();
+        assert_eq!(estimator.corrected_rate(), None);
+    }
+}
@@ -0,0 +1,198 @@
+//! Outbound Beast feeds to aggregator networks
+//!
+//! With `BEAST_FEEDS` set to a comma-separated list of `host:port`
+//! (e.g. `feed1.adsbexchange.com:30004,feed.airplanes.live:30004`), every
+//! decoded frame is also sent to each aggregator in the Beast binary format,
+//! as dump1090/readsb's `--net-connector ...,beast_out` would, so the
+//! receiver can feed the community without a second decoder. Each feed has
+//! its own connection, queue and reconnect backoff: a slow or unreachable
+//! aggregator drops its own frames and never holds up the others or the
+//! decode loop. Per-feed statistics are logged with the tracker summary.
+
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpStream;
+use tokio::sync::mpsc;
+use tracing::{info, warn};
+
+/// Beast message start; doubled when it occurs inside a message
+const ESCAPE: u8 = 0x1A;
+
+/// Frames queued per feed before new ones are dropped
+const QUEUE_CAPACITY: usize = 4096;
+
+/// First and longest wait between connection attempts
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(120);
+
+/// 12 MHz Beast clock ticks per 2 MHz sample
+const TICKS_PER_SAMPLE: u64 = 6;
+
+/// Detector magnitude of a full-scale signal
+const FULL_SCALE: f32 = 180.0;
+
+/// Counters of one feed
+#[derive(Debug, Default)]
+pub struct FeedStats {
+    pub connected: AtomicBool,
+    pub frames_sent: AtomicU64,
+    /// Dropped while the queue was full or the feed disconnected
+    pub frames_dropped: AtomicU64,
+    pub connects: AtomicU64,
+}
+
+/// One aggregator connection
+struct Feed {
+    target: String,
+    tx: mpsc::Sender<Vec<u8>>,
+    stats: Arc<FeedStats>,
+}
+
+/// The configured feeds
+pub struct Feeders {
+    feeds: Vec<Feed>,
+}
+
+impl Feeders {
+    /// Start a connection task per `host:port`
+    pub fn start(targets: &[String]) -> Self {
+        let feeds = targets
+            .iter()
+            .map(|target| {
+                let (tx, rx) = mpsc::channel(QUEUE_CAPACITY);
+                let stats = Arc::new(FeedStats::default());
+                tokio::spawn(run_feed(target.clone(), rx, stats.clone()));
+                Feed {
+                    target: target.clone(),
+                    tx,
+                    stats,
+                }
+            })
+            .collect();
+        Self { feeds }
+    }
+
+    /// Queue a frame for every feed without waiting
+    pub fn send(&self, data: &[u8], timestamp_samples: u64, signal_level: u16) {
+        if self.feeds.is_empty() {
+            return;
+        }
+        let Some(message) = encode(data, timestamp_samples * TICKS_PER_SAMPLE, signal_byte(signal_level)) else {
+            return;
+        };
+        for feed in &self.feeds {
+            if !feed.stats.connected.load(Ordering::Relaxed) || feed.tx.try_send(message.clone()).is_err() {
+                feed.stats.frames_dropped.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// One line per feed, for the periodic log
+    pub fn summary(&self) -> Vec<String> {
+        self.feeds
+            .iter()
+            .map(|feed| {
+                let stats = &feed.stats;
+                format!(
+                    "{} {}, {} frames sent, {} dropped, {} connects",
+                    feed.target,
+                    if stats.connected.load(Ordering::Relaxed) { "connected" } else { "disconnected" },
+                    stats.frames_sent.load(Ordering::Relaxed),
+                    stats.frames_dropped.load(Ordering::Relaxed),
+                    stats.connects.load(Ordering::Relaxed)
+                )
+            })
+            .collect()
+    }
+}
+
+/// Beast signal byte: the square root of power relative to full scale,
+/// which is the magnitude ratio
+fn signal_byte(signal_level: u16) -> u8 {
+    (signal_level as f32 / FULL_SCALE * 255.0).clamp(0.0, 255.0) as u8
+}
+
+/// Beast message for a 7 or 14 byte frame
+pub fn encode(data: &[u8], timestamp: u64, signal: u8) -> Option<Vec<u8>> {
+    let kind = match data.len() {
+        7 => b'2',
+        14 => b'3',
+        _ => return None,
+    };
+    let mut message = Vec::with_capacity(2 + 2 * (7 + data.len()));
+    message.extend([ESCAPE, kind]);
+    let timestamp = timestamp.to_be_bytes();
+    for &b in timestamp[2..].iter().chain(std::iter::once(&signal)).chain(data) {
+        message.push(b);
+        if b == ESCAPE {
+            message.push(ESCAPE);
+        }
+    }
+    Some(message)
+}
+
+/// Wait before the `attempt`th reconnection (1 = first)
+fn backoff(attempt: u32) -> Duration {
+    MIN_BACKOFF
+        .saturating_mul(1 << attempt.saturating_sub(1).min(16))
+        .min(MAX_BACKOFF)
+}
+
+/// Keep one feed connected, writing its queued frames
+async fn run_feed(target: String, mut rx: mpsc::Receiver<Vec<u8>>, stats: Arc<FeedStats>) {
+    let mut failures = 0u32;
+    loop {
+        match TcpStream::connect(&target).await {
+            Ok(mut stream) => {
+                info!("[Feed {}] Connected", target);
+                let _ = stream.set_nodelay(true);
+                failures = 0;
+                stats.connects.fetch_add(1, Ordering::Relaxed);
+                // Frames queued while disconnected are stale
+                while rx.try_recv().is_ok() {}
+                stats.connected.store(true, Ordering::Relaxed);
+                let error = loop {
+                    let Some(message) = rx.recv().await else {
+                        return;
+                    };
+                    if let Err(e) = stream.write_all(&message).await {
+                        break e;
+                    }
+                    stats.frames_sent.fetch_add(1, Ordering::Relaxed);
+                };
+                stats.connected.store(false, Ordering::Relaxed);
+                warn!("[Feed {}] Connection lost: {}", target, error);
+            }
+            Err(e) => warn!("[Feed {}] Connection failed: {}", target, e),
+        }
+        failures += 1;
+        tokio::time::sleep(backoff(failures)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_beast_encoding() {
+        let squitter = [0x8D, 0x48, 0x40, 0xD6, 0x20, 0x2C, 0xC3, 0x71, 0xC3, 0x2C, 0xE0, 0x57, 0x60, 0x98];
+        let message = encode(&squitter, 0x1A000102, 0xA0).unwrap();
+        // Escape in the timestamp doubled
+        assert_eq!(message[..11], [ESCAPE, b'3', 0x00, 0x00, 0x1A, 0x1A, 0x00, 0x01, 0x02, 0xA0, 0x8D]);
+        assert_eq!(message.len(), 2 + 7 + 1 + 14);
+
+        let short = encode(&[0x5D, 0x48, 0x40, 0xD6, 0xD6, 0xC7, 0xE8], 0, 0).unwrap();
+        assert_eq!(short[1], b'2');
+        assert_eq!(short.len(), 2 + 7 + 7);
+        assert!(encode(&[0; 10], 0, 0).is_none());
+
+        assert_eq!(signal_byte(180), 255);
+        assert_eq!(signal_byte(1000), 255);
+        assert_eq!(backoff(1), Duration::from_secs(1));
+        assert_eq!(backoff(4), Duration::from_secs(8));
+        assert_eq!(backoff(40), MAX_BACKOFF);
+    }
+}
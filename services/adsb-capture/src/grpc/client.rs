@@ -1,36 +1,68 @@
 //! gRPC client for streaming to gateway
 
-use anyhow::Result;
-use tokio::sync::mpsc;
+use anyhow::{bail, Result};
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, watch, Mutex};
 use tokio_stream::wrappers::ReceiverStream;
 use tonic::transport::Channel;
-use tracing::{info, warn};
+use tracing::{debug, info, warn};
 
 use super::adsb::{
-    adsb_gateway_client::AdsbGatewayClient, AircraftEvent, DeviceStatus, SignalMetrics,
+    adsb_gateway_client::AdsbGatewayClient, AircraftEvent, AircraftLabel, AircraftLabelsRequest, DeviceStatus,
+    FrequencyScan, HeartbeatPing, PingRequest, RawFrame, SignalMetrics, StreamAck,
 };
+use crate::heartbeat::Liveness;
 
-/// Streaming gateway client with automatic reconnection
+/// Messages buffered on one stream connection
+const STREAM_BUFFER: usize = 64;
+
+/// Wait before reopening a stream that ended
+const RECONNECT_DELAY: Duration = Duration::from_secs(2);
+
+/// Streaming gateway client with automatic reconnection. Clones share one
+/// connection, so the heartbeat watches the connection the streams use
+#[derive(Clone)]
 pub struct StreamingGatewayClient {
     gateway_url: String,
+    /// Reopens the streams when the heartbeat loses the gateway
+    liveness: Option<Arc<Liveness>>,
+    /// The shared connection and how many times it was dialed
+    channel: Arc<Mutex<(u64, Option<Channel>)>>,
 }
 
 impl StreamingGatewayClient {
     pub fn new(gateway_url: &str) -> Self {
         Self {
             gateway_url: gateway_url.to_string(),
+            liveness: None,
+            channel: Arc::new(Mutex::new((0, None))),
         }
     }
 
-    /// Connect to gateway with retry
-    async fn connect_with_retry(&self, stream_name: &str) -> Channel {
+    /// Reopen the streams whenever `liveness` reports the connection lost
+    pub fn with_liveness(mut self, liveness: Option<Arc<Liveness>>) -> Self {
+        self.liveness = liveness;
+        self
+    }
+
+    /// The shared connection and its generation, connecting to the gateway
+    /// with retry if there is none
+    async fn connect_with_retry(&self, stream_name: &str) -> (u64, Channel) {
+        let mut current = self.channel.lock().await;
+        if let (generation, Some(channel)) = &*current {
+            return (*generation, channel.clone());
+        }
         info!("[{}] Connecting to gateway: {}", stream_name, self.gateway_url);
         loop {
             match Channel::from_shared(self.gateway_url.clone()) {
                 Ok(endpoint) => match endpoint.connect().await {
                     Ok(ch) => {
                         info!("[{}] Connected to gateway successfully", stream_name);
-                        return ch;
+                        current.0 += 1;
+                        current.1 = Some(ch.clone());
+                        return (current.0, ch);
                     }
                     Err(e) => {
                         warn!("[{}] Failed to connect to gateway: {}. Retrying in 2s...", stream_name, e);
@@ -44,70 +76,210 @@ impl StreamingGatewayClient {
         }
     }
 
-    /// Stream aircraft events to gateway (takes ownership of receiver)
-    pub async fn stream_aircraft(
-        &self,
-        rx: mpsc::Receiver<AircraftEvent>,
-    ) -> Result<()> {
-        // Connect first, then stream
-        let channel = self.connect_with_retry("Aircraft").await;
-        let mut client = AdsbGatewayClient::new(channel);
-        info!("[Aircraft] Starting stream to gateway...");
-        let stream = ReceiverStream::new(rx);
+    /// Drop the shared connection so the next stream dials a new one, unless
+    /// it was already replaced since `generation`
+    async fn disconnect(&self, generation: u64) {
+        let mut current = self.channel.lock().await;
+        if current.0 == generation {
+            current.1 = None;
+        }
+    }
 
-        match client.stream_aircraft(stream).await {
-            Ok(response) => {
-                info!("[Aircraft] Stream ended: {:?}", response.into_inner());
-                Ok(())
-            }
-            Err(e) => {
-                warn!("[Aircraft] Stream error: {}", e);
-                Err(e.into())
+    /// Forward everything from `rx` over a client stream opened by `open`,
+    /// reopening it whenever it ends or the heartbeat loses the gateway;
+    /// returns once `rx` closes
+    async fn forward<T, F, Fut>(&self, name: &str, mut rx: mpsc::Receiver<T>, open: F) -> Result<()>
+    where
+        F: Fn(AdsbGatewayClient<Channel>, ReceiverStream<T>) -> Fut,
+        Fut: Future<Output = Result<tonic::Response<StreamAck>, tonic::Status>>,
+    {
+        let mut pending = None;
+        loop {
+            let (generation, channel) = self.connect_with_retry(name).await;
+            let mut losses = self.liveness.as_ref().map(|l| l.subscribe());
+            let (tx, stream_rx) = mpsc::channel(STREAM_BUFFER);
+            info!("[{}] Starting stream to gateway...", name);
+            let call = open(AdsbGatewayClient::new(channel), ReceiverStream::new(stream_rx));
+            tokio::pin!(call);
+            loop {
+                tokio::select! {
+                    result = &mut call => {
+                        match result {
+                            Ok(response) => info!("[{}] Stream ended: {:?}", name, response.into_inner()),
+                            Err(e) => warn!("[{}] Stream error: {}", name, e),
+                        }
+                        break;
+                    }
+                    _ = lost(&mut losses) => {
+                        warn!("[{}] Gateway heartbeat lost, reopening stream", name);
+                        self.disconnect(generation).await;
+                        break;
+                    }
+                    item = rx.recv(), if pending.is_none() => match item {
+                        Some(item) => pending = Some(item),
+                        None => return Ok(()),
+                    },
+                    permit = tx.reserve(), if pending.is_some() => match (permit, pending.take()) {
+                        (Ok(permit), Some(item)) => permit.send(item),
+                        _ => break,
+                    },
+                }
             }
+            tokio::time::sleep(RECONNECT_DELAY).await;
         }
     }
 
+    /// Stream aircraft events to gateway (takes ownership of receiver)
+    pub async fn stream_aircraft(&self, rx: mpsc::Receiver<AircraftEvent>) -> Result<()> {
+        self.forward("Aircraft", rx, |mut client, stream| async move { client.stream_aircraft(stream).await })
+            .await
+    }
+
     /// Stream signal metrics to gateway
-    pub async fn stream_signal(
+    pub async fn stream_signal(&self, rx: mpsc::Receiver<SignalMetrics>) -> Result<()> {
+        self.forward("Signal", rx, |mut client, stream| async move { client.stream_signal(stream).await })
+            .await
+    }
+
+    /// Stream device status to gateway
+    pub async fn stream_status(&self, rx: mpsc::Receiver<DeviceStatus>) -> Result<()> {
+        self.forward("Status", rx, |mut client, stream| async move { client.stream_device_status(stream).await })
+            .await
+    }
+
+    /// Stream raw frames to gateway for archival
+    pub async fn stream_raw_frames(&self, rx: mpsc::Receiver<RawFrame>) -> Result<()> {
+        self.forward("RawFrames", rx, |mut client, stream| async move { client.stream_raw_frames(stream).await })
+            .await
+    }
+
+    /// Ping the gateway every `interval` over one heartbeat stream, until an
+    /// answer is `timeout` late or the gateway closes the stream; a late
+    /// answer drops the shared connection
+    pub async fn heartbeat(
         &self,
-        rx: mpsc::Receiver<SignalMetrics>,
+        device_id: &str,
+        interval: Duration,
+        timeout: Duration,
+        liveness: &Liveness,
     ) -> Result<()> {
-        let channel = self.connect_with_retry("Signal").await;
-        let mut client = AdsbGatewayClient::new(channel);
-        info!("[Signal] Starting stream to gateway...");
-        let stream = ReceiverStream::new(rx);
+        let (generation, channel) = self.connect_with_retry("Heartbeat").await;
+        let result = Self::ping_loop(channel, device_id, interval, timeout, liveness).await;
+        if result.is_err() {
+            self.disconnect(generation).await;
+        }
+        result
+    }
 
-        match client.stream_signal(stream).await {
-            Ok(response) => {
-                info!("[Signal] Stream ended: {:?}", response.into_inner());
-                Ok(())
-            }
-            Err(e) => {
-                warn!("[Signal] Stream error: {}", e);
-                Err(e.into())
+    async fn ping_loop(
+        channel: Channel,
+        device_id: &str,
+        interval: Duration,
+        timeout: Duration,
+        liveness: &Liveness,
+    ) -> Result<()> {
+        let mut client = AdsbGatewayClient::new(channel);
+        let (tx, rx) = mpsc::channel(4);
+        let mut pongs = tokio::time::timeout(timeout, client.heartbeat(ReceiverStream::new(rx)))
+            .await??
+            .into_inner();
+        let mut ticker = tokio::time::interval(interval);
+        let mut sequence = 0u64;
+        let mut answered = Instant::now();
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => {
+                    if answered.elapsed() >= timeout {
+                        bail!("no heartbeat answer for {} s", answered.elapsed().as_secs());
+                    }
+                    sequence += 1;
+                    let ping = HeartbeatPing {
+                        device_id: device_id.to_string(),
+                        sequence,
+                        host_time_ms: chrono::Utc::now().timestamp_millis() as u64,
+                        rtt_ms: liveness.rtt_ms(),
+                    };
+                    // A full queue means the connection has stopped taking data;
+                    // the timeout catches it
+                    if let Err(mpsc::error::TrySendError::Closed(_)) = tx.try_send(ping) {
+                        bail!("heartbeat stream closed");
+                    }
+                }
+                pong = pongs.message() => match pong? {
+                    Some(pong) => {
+                        answered = Instant::now();
+                        let rtt = (chrono::Utc::now().timestamp_millis() as u64).saturating_sub(pong.host_time_ms);
+                        debug!("[Heartbeat] #{} round trip {} ms", pong.sequence, rtt);
+                        liveness.set_rtt(rtt.min(u32::MAX as u64) as u32);
+                    }
+                    None => return Ok(()),
+                },
             }
         }
     }
 
-    /// Stream device status to gateway
-    pub async fn stream_status(
-        &self,
-        rx: mpsc::Receiver<DeviceStatus>,
-    ) -> Result<()> {
-        let channel = self.connect_with_retry("Status").await;
+    /// Send a frequency scan report to gateway
+    pub async fn report_scan(&self, scan: FrequencyScan) -> Result<()> {
+        let (_, channel) = self.connect_with_retry("Scan").await;
         let mut client = AdsbGatewayClient::new(channel);
-        info!("[Status] Starting stream to gateway...");
-        let stream = ReceiverStream::new(rx);
 
-        match client.stream_device_status(stream).await {
+        match client.report_frequency_scan(scan).await {
             Ok(response) => {
-                info!("[Status] Stream ended: {:?}", response.into_inner());
+                info!("[Scan] Report sent: {:?}", response.into_inner());
                 Ok(())
             }
             Err(e) => {
-                warn!("[Status] Stream error: {}", e);
+                warn!("[Scan] Report error: {}", e);
                 Err(e.into())
             }
         }
     }
+
+    /// Ping the gateway `rounds` times, returning each round's host send
+    /// time, gateway time and host receive time (unix ms, host clock
+    /// uncorrected)
+    pub async fn ping(&self, device_id: &str, rounds: usize) -> Result<Vec<(i64, i64, i64)>> {
+        let (_, channel) = self.connect_with_retry("Clock").await;
+        let mut client = AdsbGatewayClient::new(channel);
+        let mut samples = Vec::with_capacity(rounds);
+
+        for _ in 0..rounds {
+            let sent = chrono::Utc::now().timestamp_millis();
+            let response = client
+                .ping(PingRequest {
+                    device_id: device_id.to_string(),
+                    host_time_ms: sent as u64,
+                })
+                .await?
+                .into_inner();
+            let received = chrono::Utc::now().timestamp_millis();
+            samples.push((sent, response.gateway_time_ms as i64, received));
+        }
+        Ok(samples)
+    }
+
+    /// Callsigns and categories of the aircraft the gateway has in view.
+    /// Connects once rather than retrying: the caller tries again later
+    pub async fn aircraft_labels(&self, device_id: &str) -> Result<Vec<AircraftLabel>> {
+        let channel = Channel::from_shared(self.gateway_url.clone())?.connect().await?;
+        let mut client = AdsbGatewayClient::new(channel);
+        let response = client
+            .get_aircraft_labels(AircraftLabelsRequest {
+                device_id: device_id.to_string(),
+            })
+            .await?
+            .into_inner();
+        Ok(response.labels)
+    }
+}
+
+/// Resolves when the heartbeat reports the connection lost; never without
+/// a heartbeat
+async fn lost(losses: &mut Option<watch::Receiver<u64>>) {
+    if let Some(losses) = losses {
+        if losses.changed().await.is_ok() {
+            return;
+        }
+    }
+    std::future::pending().await
 }
@@ -8,3 +8,27 @@ pub use client::StreamingGatewayClient;
 pub mod adsb {
     tonic::include_proto!("adsb");
 }
+
+impl From<Option<crate::adsb::EmergencySquawk>> for adsb::Emergency {
+    fn from(emergency: Option<crate::adsb::EmergencySquawk>) -> Self {
+        use crate::adsb::EmergencySquawk;
+        match emergency {
+            Some(EmergencySquawk::Hijack) => Self::Hijack,
+            Some(EmergencySquawk::RadioFailure) => Self::RadioFailure,
+            Some(EmergencySquawk::General) => Self::General,
+            None => Self::None,
+        }
+    }
+}
+
+impl From<&crate::sdr::ScanPoint> for adsb::ScanPoint {
+    fn from(point: &crate::sdr::ScanPoint) -> Self {
+        Self {
+            frequency_hz: point.frequency_hz as u64,
+            noise_dbfs: point.noise_dbfs,
+            peak_dbfs: point.peak_dbfs,
+            spur_db: point.spur_db,
+            spur_offset_hz: point.spur_offset_hz,
+        }
+    }
+}
@@ -0,0 +1,104 @@
+//! Gateway connection liveness
+//!
+//! Across NAT and flaky uplinks a gRPC connection can die without either
+//! end being told: the streams keep accepting data until the OS gives up on
+//! the TCP connection, which can take many minutes. Every
+//! `HEARTBEAT_INTERVAL_SECS` (default 5, 0 = off) the host pings the
+//! gateway over a long-lived `Heartbeat` stream, and the gateway answers
+//! each ping at once. When no answer has arrived for
+//! `HEARTBEAT_TIMEOUT_SECS` (default 15), the connection is taken as lost:
+//! the heartbeat and every gateway stream reconnect. The streams and the
+//! heartbeat share one connection, so the heartbeat checks the connection
+//! the data travels over. The round trip of the last answered ping is
+//! logged and sent to the gateway with the next ping.
+
+use crate::grpc::StreamingGatewayClient;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+use tracing::{info, warn};
+
+/// Wait before reconnecting the heartbeat
+const RECONNECT_DELAY: Duration = Duration::from_secs(2);
+
+/// Round trips and lost connections of the heartbeat
+#[derive(Debug)]
+pub struct Liveness {
+    /// Last round trip in milliseconds, `u32::MAX` until measured
+    rtt_ms: AtomicU32,
+    /// Bumped every time the connection is lost
+    losses: watch::Sender<u64>,
+}
+
+impl Liveness {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self {
+            rtt_ms: AtomicU32::new(u32::MAX),
+            losses: watch::channel(0).0,
+        })
+    }
+
+    /// Round trip of the last answered ping
+    pub fn rtt_ms(&self) -> Option<u32> {
+        Some(self.rtt_ms.load(Ordering::Relaxed)).filter(|&rtt| rtt != u32::MAX)
+    }
+
+    pub fn set_rtt(&self, rtt_ms: u32) {
+        self.rtt_ms.store(rtt_ms.min(u32::MAX - 1), Ordering::Relaxed);
+    }
+
+    /// Tell every stream the connection was lost
+    pub fn lost(&self) {
+        self.rtt_ms.store(u32::MAX, Ordering::Relaxed);
+        self.losses.send_modify(|n| *n += 1);
+    }
+
+    /// Changes whenever the connection is lost
+    pub fn subscribe(&self) -> watch::Receiver<u64> {
+        self.losses.subscribe()
+    }
+}
+
+/// Keep a heartbeat stream to the gateway on `client`'s connection,
+/// reconnecting it when lost
+pub fn spawn(
+    client: StreamingGatewayClient,
+    device_id: String,
+    interval: Duration,
+    timeout: Duration,
+) -> Arc<Liveness> {
+    let liveness = Liveness::new();
+    let heartbeat = liveness.clone();
+    tokio::spawn(async move {
+        loop {
+            match client.heartbeat(&device_id, interval, timeout, &heartbeat).await {
+                Ok(()) => info!("[Heartbeat] Gateway closed the stream"),
+                Err(e) => warn!("[Heartbeat] Gateway connection lost: {}; reconnecting streams", e),
+            }
+            heartbeat.lost();
+            tokio::time::sleep(RECONNECT_DELAY).await;
+        }
+    });
+    liveness
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_liveness() {
+        let liveness = Liveness::new();
+        let mut losses = liveness.subscribe();
+        assert_eq!(liveness.rtt_ms(), None);
+        liveness.set_rtt(42);
+        assert_eq!(liveness.rtt_ms(), Some(42));
+
+        assert!(!losses.has_changed().unwrap());
+        liveness.lost();
+        assert!(losses.has_changed().unwrap());
+        assert_eq!(*losses.borrow_and_update(), 1);
+        assert_eq!(liveness.rtt_ms(), None);
+    }
+}
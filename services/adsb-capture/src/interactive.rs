@@ -0,0 +1,279 @@
+//! Interactive terminal view
+//!
+//! `adsb-capture --interactive` takes over the terminal with a live table of
+//! tracked aircraft and the receiver's signal and message rates, like
+//! dump1090's `--interactive`, for debugging a headless receiver over SSH.
+//! The view redraws every `REDRAW_MS` from a snapshot the capture loop
+//! refreshes with each signal report. Log output would scribble over it, so
+//! meanwhile logs go to `INTERACTIVE_LOG` (default `adsb-capture.log`).
+//! `q`, `Esc` or Ctrl+C stops the capture.
+
+use crate::aircraft_tracker::AircraftState;
+use crate::grpc::adsb::SignalMetrics;
+use anyhow::{Context, Result};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Style, Stylize};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Paragraph, Row, Table};
+use ratatui::Frame;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// Milliseconds between redraws (and key polls)
+const REDRAW_MS: u64 = 250;
+
+/// `true` when the command line asks for the interactive view
+pub fn requested(args: &[String]) -> bool {
+    args.iter().any(|a| a == "--interactive")
+}
+
+/// Where logs go while the view owns the terminal
+pub fn log_path() -> PathBuf {
+    std::env::var("INTERACTIVE_LOG")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| PathBuf::from("adsb-capture.log"))
+}
+
+/// One aircraft table row
+#[derive(Debug, Clone, PartialEq)]
+pub struct AircraftRow {
+    pub icao: String,
+    pub callsign: Option<String>,
+    pub squawk: Option<u16>,
+    pub altitude_ft: Option<i32>,
+    pub speed_kts: Option<f32>,
+    pub track_deg: Option<f32>,
+    pub vertical_rate_fpm: Option<i32>,
+    pub position: Option<(f64, f64)>,
+    pub messages: u64,
+    pub age_secs: u64,
+}
+
+impl From<&AircraftState> for AircraftRow {
+    fn from(state: &AircraftState) -> Self {
+        Self {
+            icao: state.address(),
+            callsign: state.callsign.clone(),
+            squawk: state.squawk,
+            altitude_ft: state.altitude_ft,
+            speed_kts: state.ground_speed_kts,
+            track_deg: state.track_deg,
+            vertical_rate_fpm: state.vertical_rate_fpm,
+            position: state.latitude.zip(state.longitude),
+            messages: state.messages,
+            age_secs: state.age_secs(),
+        }
+    }
+}
+
+/// What the view shows, as of the last signal report
+#[derive(Debug, Default)]
+pub struct Snapshot {
+    /// Aircraft by address, so rows stay put between redraws
+    pub aircraft: Vec<AircraftRow>,
+    pub signal: Option<SignalMetrics>,
+    /// Frames decoded per second since the previous report
+    pub frame_rate: f32,
+    last_report: Option<(Instant, u64)>,
+}
+
+impl Snapshot {
+    /// Take the tracked aircraft and a signal report taken at `now`
+    pub fn record<'a>(
+        &mut self,
+        aircraft: impl Iterator<Item = &'a AircraftState>,
+        metrics: &SignalMetrics,
+        now: Instant,
+    ) {
+        self.aircraft = aircraft.map(AircraftRow::from).collect();
+        self.aircraft.sort_by(|a, b| a.icao.cmp(&b.icao));
+        if let Some((at, frames)) = self.last_report {
+            let secs = now.duration_since(at).as_secs_f32();
+            if secs > 0.0 {
+                self.frame_rate = metrics.frames_decoded.saturating_sub(frames) as f32 / secs;
+            }
+        }
+        self.last_report = Some((now, metrics.frames_decoded));
+        self.signal = Some(metrics.clone());
+    }
+}
+
+/// The running view; the terminal is restored on `stop`
+pub struct Dashboard {
+    snapshot: Arc<Mutex<Snapshot>>,
+    quit: Arc<AtomicBool>,
+    thread: JoinHandle<()>,
+}
+
+impl Dashboard {
+    /// Take over the terminal and draw until `stop` or a quit key
+    pub fn start(title: String) -> Result<Self> {
+        let mut terminal = ratatui::try_init().context("--interactive needs a terminal")?;
+        let snapshot = Arc::new(Mutex::new(Snapshot::default()));
+        let quit = Arc::new(AtomicBool::new(false));
+        let started = Instant::now();
+
+        let thread = {
+            let snapshot = snapshot.clone();
+            let quit = quit.clone();
+            std::thread::spawn(move || {
+                while !quit.load(Ordering::Relaxed) {
+                    let drawn = terminal.draw(|frame| {
+                        let snapshot = snapshot.lock().unwrap();
+                        draw(frame, &title, started.elapsed(), &snapshot);
+                    });
+                    if drawn.is_err() {
+                        break;
+                    }
+                    if quit_key(Duration::from_millis(REDRAW_MS)) {
+                        quit.store(true, Ordering::Relaxed);
+                    }
+                }
+                ratatui::restore();
+            })
+        };
+
+        Ok(Self { snapshot, quit, thread })
+    }
+
+    /// Show the tracked aircraft and a new signal report
+    pub fn update<'a>(&self, aircraft: impl Iterator<Item = &'a AircraftState>, metrics: &SignalMetrics) {
+        self.snapshot.lock().unwrap().record(aircraft, metrics, Instant::now());
+    }
+
+    /// A quit key was pressed
+    pub fn quit_requested(&self) -> bool {
+        self.quit.load(Ordering::Relaxed)
+    }
+
+    /// Close the view and give the terminal back
+    pub fn stop(self) {
+        self.quit.store(true, Ordering::Relaxed);
+        let _ = self.thread.join();
+    }
+}
+
+/// Wait up to `timeout` for a key; `true` for q, Esc or Ctrl+C
+fn quit_key(timeout: Duration) -> bool {
+    if !event::poll(timeout).unwrap_or(false) {
+        return false;
+    }
+    match event::read() {
+        Ok(Event::Key(key)) if key.kind == KeyEventKind::Press => {
+            matches!(key.code, KeyCode::Char('q') | KeyCode::Esc)
+                || (key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL))
+        }
+        _ => false,
+    }
+}
+
+fn draw(frame: &mut Frame, title: &str, running: Duration, snapshot: &Snapshot) {
+    let [header, table, footer] =
+        Layout::vertical([Constraint::Length(5), Constraint::Min(0), Constraint::Length(1)]).areas(frame.area());
+
+    let secs = running.as_secs();
+    let mut lines = vec![Line::from(format!(
+        "running {:02}:{:02}:{:02}",
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60
+    ))];
+    match &snapshot.signal {
+        Some(m) => {
+            lines.push(Line::from(format!(
+                "signal {:.1} dBFS   noise {:.1} dBFS   SNR {:.1} dB",
+                m.signal_dbfs, m.noise_dbfs, m.snr_db
+            )));
+            lines.push(Line::from(format!(
+                "{:.1} msg/s   {} frames   {} corrected   {} CRC errors   {} preambles",
+                snapshot.frame_rate, m.frames_decoded, m.corrected_frames, m.crc_errors, m.preambles_detected
+            )));
+        }
+        None => lines.push(Line::from("waiting for the first signal report...")),
+    }
+    frame.render_widget(
+        Paragraph::new(lines).block(Block::bordered().title(format!(" {} ", title))),
+        header,
+    );
+
+    let with_position = snapshot.aircraft.iter().filter(|a| a.position.is_some()).count();
+    let rows = snapshot.aircraft.iter().map(|a| {
+        Row::new(vec![
+            a.icao.clone(),
+            a.callsign.clone().unwrap_or_default(),
+            a.squawk.map(|s| format!("{:04}", s)).unwrap_or_default(),
+            a.altitude_ft.map(|v| v.to_string()).unwrap_or_default(),
+            a.speed_kts.map(|v| format!("{:.0}", v)).unwrap_or_default(),
+            a.track_deg.map(|v| format!("{:.0}", v)).unwrap_or_default(),
+            a.vertical_rate_fpm.map(|v| format!("{:+}", v)).unwrap_or_default(),
+            a.position.map(|(lat, _)| format!("{:.4}", lat)).unwrap_or_default(),
+            a.position.map(|(_, lon)| format!("{:.4}", lon)).unwrap_or_default(),
+            a.messages.to_string(),
+            format!("{}s", a.age_secs),
+        ])
+    });
+    let widths = [
+        Constraint::Length(7),
+        Constraint::Length(9),
+        Constraint::Length(6),
+        Constraint::Length(7),
+        Constraint::Length(5),
+        Constraint::Length(4),
+        Constraint::Length(7),
+        Constraint::Length(9),
+        Constraint::Length(10),
+        Constraint::Length(7),
+        Constraint::Length(5),
+    ];
+    let header_row = Row::new(["ICAO", "Flight", "Squawk", "Alt ft", "Kt", "Trk", "V/S", "Lat", "Lon", "Msgs", "Seen"])
+        .style(Style::new().bold());
+    frame.render_widget(
+        Table::new(rows, widths).header(header_row).block(Block::bordered().title(format!(
+            " {} aircraft, {} with position ",
+            snapshot.aircraft.len(),
+            with_position
+        ))),
+        table,
+    );
+
+    frame.render_widget(Line::from(" q quit").dim(), footer);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot() {
+        let mut second = AircraftState::new(0x71BE11);
+        second.callsign = Some("KAL123".into());
+        second.latitude = Some(37.46);
+        second.longitude = Some(126.44);
+        let first = AircraftState::new(0x06A0A5);
+
+        let mut snapshot = Snapshot::default();
+        let start = Instant::now();
+        let metrics = SignalMetrics {
+            frames_decoded: 1_000,
+            ..Default::default()
+        };
+        snapshot.record([&second, &first].into_iter(), &metrics, start);
+        assert_eq!(snapshot.frame_rate, 0.0);
+        let icaos: Vec<_> = snapshot.aircraft.iter().map(|a| a.icao.as_str()).collect();
+        assert_eq!(icaos, ["06A0A5", "71BE11"]);
+        assert_eq!(snapshot.aircraft[1].position, Some((37.46, 126.44)));
+
+        let metrics = SignalMetrics {
+            frames_decoded: 1_250,
+            ..Default::default()
+        };
+        snapshot.record(std::iter::empty(), &metrics, start + Duration::from_millis(500));
+        assert_eq!(snapshot.frame_rate, 500.0);
+        assert!(snapshot.aircraft.is_empty());
+        assert!(requested(&["adsb-capture".into(), "--interactive".into()]));
+    }
+}
@@ -0,0 +1,88 @@
+//! Interrogator code statistics
+//!
+//! Every DF11 all-call reply carries the code of the ground interrogator it
+//! answers, overlaid on its parity. Counting replies per code shows which
+//! secondary surveillance radars cover the area and how busy they are, and
+//! points at MLAT sync candidates. Replies from addresses the tracker hasn't
+//! already heard are not counted: a corrupted short frame can decode to any
+//! address and code.
+
+use std::collections::{BTreeMap, HashSet};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::adsb::InterrogatorCode;
+use crate::grpc::adsb::InterrogatorCount;
+
+/// Replies to one interrogator code
+#[derive(Debug, Default)]
+struct CodeStats {
+    replies: u64,
+    aircraft: HashSet<u32>,
+    last_reply: Option<SystemTime>,
+}
+
+/// Reply counts per interrogator code since the host started
+#[derive(Debug, Default)]
+pub struct InterrogatorStats {
+    codes: BTreeMap<InterrogatorCode, CodeStats>,
+}
+
+impl InterrogatorStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count a reply from `icao` to `code`
+    pub fn record(&mut self, code: InterrogatorCode, icao: u32, now: SystemTime) {
+        let stats = self.codes.entry(code).or_default();
+        stats.replies += 1;
+        stats.aircraft.insert(icao);
+        stats.last_reply = Some(now);
+    }
+
+    /// Counts for the signal report, in code order (II before SI)
+    pub fn counts(&self) -> Vec<InterrogatorCount> {
+        self.codes
+            .iter()
+            .map(|(code, stats)| {
+                let (surveillance, code) = match *code {
+                    InterrogatorCode::Ii(ii) => (false, ii),
+                    InterrogatorCode::Si(si) => (true, si),
+                };
+                InterrogatorCount {
+                    surveillance,
+                    code: code as u32,
+                    replies: stats.replies,
+                    aircraft: stats.aircraft.len() as u32,
+                    last_reply_ms: stats
+                        .last_reply
+                        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                        .unwrap_or(Duration::ZERO)
+                        .as_millis() as u64,
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counts_per_code() {
+        let mut stats = InterrogatorStats::new();
+        let t = UNIX_EPOCH + Duration::from_secs(1_705_312_800);
+        stats.record(InterrogatorCode::Si(18), 0x71BE11, t);
+        stats.record(InterrogatorCode::Si(18), 0x71BE11, t);
+        stats.record(InterrogatorCode::Si(18), 0x4840D6, t + Duration::from_secs(1));
+        stats.record(InterrogatorCode::Ii(3), 0x71BE11, t);
+
+        let counts = stats.counts();
+        assert_eq!(counts.len(), 2);
+        assert_eq!((counts[0].surveillance, counts[0].code, counts[0].replies), (false, 3, 1));
+        assert_eq!((counts[1].surveillance, counts[1].code), (true, 18));
+        assert_eq!((counts[1].replies, counts[1].aircraft), (3, 2));
+        assert_eq!(counts[1].last_reply_ms, 1_705_312_801_000);
+    }
+}
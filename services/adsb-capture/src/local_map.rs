@@ -0,0 +1,167 @@
+//! Local map served by the capture host
+//!
+//! With `LOCAL_HTTP_ADDR` set (e.g. `0.0.0.0:8080`), the capture service
+//! serves its own tracker state, without the gateway: `/data/aircraft.json`
+//! in dump1090's format, `/data/receiver.json` with `RECEIVER_LAT`/`LON`,
+//! and at `/` a minimal map page polling them, so a field install can check
+//! reception with just the receiver box and a phone. The aircraft list is
+//! refreshed with every signal report.
+
+use crate::aircraft_tracker::AircraftState;
+use axum::{extract::State, response::Html, routing::get, Json, Router};
+use serde::Serialize;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use tracing::{error, info};
+
+/// The map page
+const INDEX_HTML: &str = include_str!("local_map.html");
+
+/// One aircraft in `aircraft.json`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AircraftJson {
+    pub hex: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub flight: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub squawk: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alt_baro: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gs: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub track: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub baro_rate: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lat: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lon: Option<f64>,
+    pub messages: u64,
+    /// Seconds since the last message
+    pub seen: u64,
+}
+
+impl From<&AircraftState> for AircraftJson {
+    fn from(state: &AircraftState) -> Self {
+        Self {
+            hex: state.address().to_lowercase(),
+            flight: state.callsign.clone(),
+            squawk: state.squawk.map(|s| format!("{:04}", s)),
+            alt_baro: state.altitude_ft,
+            gs: state.ground_speed_kts,
+            track: state.track_deg,
+            baro_rate: state.vertical_rate_fpm,
+            lat: state.latitude,
+            lon: state.longitude,
+            messages: state.messages,
+            seen: state.age_secs(),
+        }
+    }
+}
+
+/// `aircraft.json`
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct AircraftList {
+    /// Seconds since the epoch when the list was taken
+    pub now: f64,
+    /// Frames processed since start
+    pub messages: u64,
+    pub aircraft: Vec<AircraftJson>,
+}
+
+/// `receiver.json`
+#[derive(Debug, Clone, Serialize)]
+struct ReceiverJson {
+    version: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    lat: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    lon: Option<f64>,
+    refresh: u32,
+}
+
+/// Aircraft list shared with the server
+#[derive(Debug, Default)]
+pub struct LocalMap {
+    aircraft: Mutex<AircraftList>,
+    receiver: Option<(f64, f64)>,
+}
+
+impl LocalMap {
+    /// Replace the served aircraft list
+    pub fn update<'a>(&self, aircraft: impl Iterator<Item = &'a AircraftState>, messages: u64) {
+        let list = AircraftList {
+            now: chrono::Utc::now().timestamp_millis() as f64 / 1000.0,
+            messages,
+            aircraft: aircraft.map(AircraftJson::from).collect(),
+        };
+        *self.aircraft.lock().unwrap() = list;
+    }
+}
+
+/// Serve the map on `addr` until the process exits
+pub fn spawn(addr: SocketAddr, receiver: Option<(f64, f64)>) -> Arc<LocalMap> {
+    let map = Arc::new(LocalMap {
+        receiver,
+        ..Default::default()
+    });
+    let app = Router::new()
+        .route("/", get(|| async { Html(INDEX_HTML) }))
+        .route("/data/aircraft.json", get(aircraft_json))
+        .route("/data/receiver.json", get(receiver_json))
+        .with_state(map.clone());
+
+    tokio::spawn(async move {
+        let server = match axum::Server::try_bind(&addr) {
+            Ok(server) => server,
+            Err(e) => {
+                error!("Local map could not listen on {}: {}", addr, e);
+                return;
+            }
+        };
+        info!("Local map on http://{}/", addr);
+        if let Err(e) = server.serve(app.into_make_service()).await {
+            error!("Local map server failed: {}", e);
+        }
+    });
+    map
+}
+
+async fn aircraft_json(State(map): State<Arc<LocalMap>>) -> Json<AircraftList> {
+    Json(map.aircraft.lock().unwrap().clone())
+}
+
+async fn receiver_json(State(map): State<Arc<LocalMap>>) -> Json<ReceiverJson> {
+    Json(ReceiverJson {
+        version: env!("CARGO_PKG_VERSION"),
+        lat: map.receiver.map(|(lat, _)| lat),
+        lon: map.receiver.map(|(_, lon)| lon),
+        refresh: 1000,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_aircraft_json() {
+        let mut state = AircraftState::new(0x71BE11);
+        state.callsign = Some("KAL123".into());
+        state.squawk = Some(7);
+        state.altitude_ft = Some(35000);
+        state.messages = 12;
+
+        let map = LocalMap::default();
+        map.update([&state].into_iter(), 100);
+        let list = map.aircraft.lock().unwrap().clone();
+        assert_eq!(list.messages, 100);
+        let json = serde_json::to_value(&list.aircraft[0]).unwrap();
+        assert_eq!(json["hex"], "71be11");
+        assert_eq!(json["squawk"], "0007");
+        assert_eq!(json["alt_baro"], 35000);
+        assert_eq!(json["messages"], 12);
+        assert!(json.get("lat").is_none());
+    }
+}
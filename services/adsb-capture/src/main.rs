@@ -3,34 +3,77 @@
 //! Captures raw IQ samples from RTL-SDR, demodulates and decodes Mode S/ADS-B,
 //! and streams decoded data to grpc-gateway.
 
-mod adsb;
+mod aircraft_info;
 mod aircraft_tracker;
+mod autogain;
+mod clock;
 mod config;
 mod decoder;
+mod dedup;
 mod device;
+mod dry_run;
+mod event_filter;
+mod false_positives;
+mod feeder;
 mod grpc;
+mod heartbeat;
+mod interactive;
+mod interrogators;
+mod local_map;
+mod message_types;
+mod priority;
 mod sdr;
+mod self_test;
+mod send_policy;
 
+use adsb_decoder as adsb;
 use aircraft_tracker::AircraftTracker;
+use clock::GatewayClock;
+use dedup::FrameDedup;
+use event_filter::EventFilter;
+use false_positives::FalsePositiveEstimator;
+use feeder::Feeders;
+use interrogators::InterrogatorStats;
+use message_types::MessageTypeStats;
+use send_policy::SendPolicy;
 
 use anyhow::Result;
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, SystemTime};
 use tokio::sync::mpsc;
-use tracing::{error, info, warn, Level};
+use tracing::{debug, error, info, warn, Level};
 use tracing_subscriber::FmtSubscriber;
 
 use config::Config;
-use grpc::adsb::{AircraftEvent, DeviceStatus, SignalMetrics};
+use grpc::adsb::{
+    AircraftEvent, DecoderConfig, DeviceStatus, Emergency, FrameProvenance, FrequencyScan, RawFrame, SignalMetrics,
+};
 use grpc::StreamingGatewayClient;
 use sdr::{query_device_info, SdrCapture, SdrConfig};
 
+/// Aircraft events buffered for the gateway stream
+const AIRCRAFT_CHANNEL_CAPACITY: usize = 1000;
+
 #[tokio::main]
 async fn main() -> Result<()> {
-    // Initialize logging
-    FmtSubscriber::builder()
-        .with_max_level(Level::DEBUG)
-        .with_target(false)
-        .init();
+    let started = Instant::now();
+    let args: Vec<String> = std::env::args().collect();
+
+    // Initialize logging (to a file while the interactive view owns the terminal)
+    let interactive = interactive::requested(&args);
+    if interactive {
+        let log_file = std::fs::File::create(interactive::log_path())?;
+        FmtSubscriber::builder()
+            .with_max_level(Level::DEBUG)
+            .with_target(false)
+            .with_ansi(false)
+            .with_writer(std::sync::Mutex::new(log_file))
+            .init();
+    } else {
+        FmtSubscriber::builder()
+            .with_max_level(Level::DEBUG)
+            .with_target(false)
+            .init();
+    }
 
     info!("===========================================");
     info!("   ADS-B Capture - Native RTL-SDR");
@@ -83,36 +126,54 @@ async fn main() -> Result<()> {
     info!("  Device ID: {}", config.device_id);
     info!("  Gain: {} dB", config.gain_db);
     info!("  PPM error: {}", config.ppm_error);
-
-    // Create channels for data flow to gRPC gateway
-    let (aircraft_tx, aircraft_rx) = mpsc::channel::<AircraftEvent>(1000);
-    let (signal_tx, signal_rx) = mpsc::channel::<SignalMetrics>(100);
-    let (status_tx, status_rx) = mpsc::channel::<DeviceStatus>(10);
-
-    // Start gRPC streaming to gateway
-    let gateway_url = config.gateway_url.clone();
-    let aircraft_handle = tokio::spawn(async move {
-        let client = StreamingGatewayClient::new(&gateway_url);
-        if let Err(e) = client.stream_aircraft(aircraft_rx).await {
-            error!("Aircraft stream failed: {}", e);
-        }
-    });
-
-    let gateway_url = config.gateway_url.clone();
-    let signal_handle = tokio::spawn(async move {
-        let client = StreamingGatewayClient::new(&gateway_url);
-        if let Err(e) = client.stream_signal(signal_rx).await {
-            error!("Signal stream failed: {}", e);
-        }
-    });
-
-    let gateway_url = config.gateway_url.clone();
-    let status_handle = tokio::spawn(async move {
-        let client = StreamingGatewayClient::new(&gateway_url);
-        if let Err(e) = client.stream_status(status_rx).await {
-            error!("Status stream failed: {}", e);
-        }
-    });
+    info!("  Forward raw frames: {}", config.forward_raw_frames);
+    info!("  Event provenance: {}", config.event_provenance);
+    info!("  Track smoothing: {}", config.track_smoothing);
+    match config.event_send_policy {
+        send_policy::SendMode::Changed => info!(
+            "  Events: on change (altitude ±{} ft, keepalive {} s)",
+            config.event_min_alt_change_ft, config.event_keepalive_secs
+        ),
+        send_policy::SendMode::All => info!("  Events: every frame"),
+    }
+    if config.min_position_nic > 0 || config.min_position_nac_p > 0 {
+        info!("  Minimum position quality: NIC {}, NACp {}", config.min_position_nic, config.min_position_nac_p);
+    }
+    if config.dedup_window_ms > 0 {
+        info!("  Duplicate frame window: {} ms", config.dedup_window_ms);
+    }
+    if config.max_false_positive_rate > 0.0 {
+        info!("  Max corrected false positives: {:.1}%", config.max_false_positive_rate * 100.0);
+    }
+    if config.heartbeat_interval_secs > 0 {
+        info!(
+            "  Heartbeat: every {} s, reconnect after {} s",
+            config.heartbeat_interval_secs, config.heartbeat_timeout_secs
+        );
+    }
+    if let Some((lat, lon)) = config.receiver_location {
+        info!("  Receiver location: {:.4}, {:.4}", lat, lon);
+    }
+    if config.scan_span_mhz > 0.0 {
+        info!("  Frequency scan: ±{} MHz in {} MHz steps", config.scan_span_mhz, config.scan_step_mhz);
+    }
+    let event_filter = EventFilter::from_config(&config);
+    if !event_filter.is_empty() {
+        info!("  Event filters: {:?}", event_filter.rules());
+    }
+    if let Some(addr) = config.local_http_addr {
+        info!("  Local map: http://{}/", addr);
+    }
+    if !config.beast_feeds.is_empty() {
+        info!("  Beast feeds: {}", config.beast_feeds.join(", "));
+    }
+    if config.clock_sync_interval_secs > 0 {
+        info!(
+            "  Clock sync: every {}s, correction {}",
+            config.clock_sync_interval_secs,
+            if config.clock_correction { "on" } else { "off" }
+        );
+    }
 
     // Configure SDR capture via rtl_sdr.exe process
     // rtl_sdr_path was already determined above for device query
@@ -125,10 +186,139 @@ async fn main() -> Result<()> {
         gain: (config.gain_db * 10.0) as i32, // Convert to tenths of dB
         ppm_error: config.ppm_error,
         rtl_sdr_path: rtl_sdr_path.to_string_lossy().to_string(),
+        noise_blanker: config.noise_blanker.then_some(config.noise_blanker_factor),
     };
 
-    // Start native SDR capture
+    // Installation self-test and gain sweep: report and exit without connecting to the gateway
+    if let Some(seconds) = self_test::requested(&args) {
+        let report = tokio::task::block_in_place(|| self_test::run(&sdr_config, seconds));
+        println!("{}", report.summary());
+        std::process::exit(if report.failures().is_empty() { 0 } else { 1 });
+    }
+
+    // Gain sweep: pick the gain decoding the most frames and persist it
+    if let Some(seconds) = autogain::requested(&args) {
+        let steps = tokio::task::block_in_place(|| autogain::sweep(&sdr_config, seconds));
+        println!("{}", autogain::summary(&steps, seconds));
+        let Some(best) = autogain::best(&steps) else {
+            println!("No frames decoded at any gain; settings left unchanged");
+            std::process::exit(1);
+        };
+        autogain::save(&config.settings_file, best.gain_tenths_db)?;
+        println!(
+            "Saved DEVICE_GAIN={:.1} to {}",
+            best.gain_tenths_db as f32 / 10.0,
+            config.settings_file.display()
+        );
+        return Ok(());
+    }
+
+    // Create channels for data flow to gRPC gateway
+    let (aircraft_tx, aircraft_rx) = mpsc::channel::<AircraftEvent>(AIRCRAFT_CHANNEL_CAPACITY);
+    let (signal_tx, signal_rx) = mpsc::channel::<SignalMetrics>(100);
+    let (status_tx, status_rx) = mpsc::channel::<DeviceStatus>(10);
+    let (raw_tx, raw_rx) = mpsc::channel::<RawFrame>(1000);
+
+    // Dry run: log what would be streamed instead of connecting to the gateway
+    let dry_run = dry_run::requested(&args);
+    let mut stream_handles = Vec::new();
+
+    // One gateway connection for every stream and the heartbeat
+    let gateway = StreamingGatewayClient::new(&config.gateway_url);
+
+    // Notice a dead gateway connection before the OS does
+    let liveness = (config.heartbeat_interval_secs > 0 && !dry_run).then(|| {
+        heartbeat::spawn(
+            gateway.clone(),
+            config.device_id.clone(),
+            Duration::from_secs(config.heartbeat_interval_secs),
+            Duration::from_secs(config.heartbeat_timeout_secs),
+        )
+    });
+
+    if dry_run {
+        info!("Dry run: not connecting to the gateway, logging events instead");
+        stream_handles.extend(dry_run::spawn_logging(aircraft_rx, signal_rx, status_rx));
+    } else {
+        // Start gRPC streaming to gateway
+        let client = gateway.clone().with_liveness(liveness.clone());
+        stream_handles.push(tokio::spawn(async move {
+            if let Err(e) = client.stream_aircraft(aircraft_rx).await {
+                error!("Aircraft stream failed: {}", e);
+            }
+        }));
+
+        let client = gateway.clone().with_liveness(liveness.clone());
+        stream_handles.push(tokio::spawn(async move {
+            if let Err(e) = client.stream_signal(signal_rx).await {
+                error!("Signal stream failed: {}", e);
+            }
+        }));
+
+        let client = gateway.clone().with_liveness(liveness.clone());
+        stream_handles.push(tokio::spawn(async move {
+            if let Err(e) = client.stream_status(status_rx).await {
+                error!("Status stream failed: {}", e);
+            }
+        }));
+    }
+
+    // Keep track of the clock offset to the gateway
+    let clock = GatewayClock::new(config.clock_correction);
+    if config.clock_sync_interval_secs > 0 && !dry_run {
+        clock::spawn_sync(
+            config.gateway_url.clone(),
+            config.device_id.clone(),
+            clock.clone(),
+            Duration::from_secs(config.clock_sync_interval_secs),
+            config.clock_skew_warn_ms,
+        );
+    }
+
+    // Raw frame forwarding is opt-in (only useful when the gateway archives them)
+    if config.forward_raw_frames && !dry_run {
+        let client = gateway.clone().with_liveness(liveness.clone());
+        stream_handles.push(tokio::spawn(async move {
+            if let Err(e) = client.stream_raw_frames(raw_rx).await {
+                error!("Raw frame stream failed: {}", e);
+            }
+        }));
+    } else {
+        drop(raw_rx);
+    }
+
+    // Beast feeds to aggregator networks, independent of the gateway
+    let feeders = Feeders::start(&config.beast_feeds);
+
     let sdr = SdrCapture::new(sdr_config);
+
+    // Optional diagnostic scan of the surrounding band (needs the device, so before capture)
+    if config.scan_span_mhz > 0.0 {
+        let span_hz = (config.scan_span_mhz * 1e6) as u32;
+        let step_hz = (config.scan_step_mhz * 1e6) as u32;
+        match tokio::task::block_in_place(|| sdr.scan(span_hz, step_hz)) {
+            Ok(points) => {
+                let scan = FrequencyScan {
+                    device_id: config.device_id.clone(),
+                    timestamp_ms: clock.now_ms(),
+                    gain_db: config.gain_db,
+                    points: points.iter().map(Into::into).collect(),
+                };
+                if !dry_run {
+                    let gateway_url = config.gateway_url.clone();
+                    tokio::spawn(async move {
+                        let client = StreamingGatewayClient::new(&gateway_url);
+                        if let Err(e) = client.report_scan(scan).await {
+                            error!("Frequency scan report failed: {}", e);
+                        }
+                    });
+                }
+            }
+            Err(e) => warn!("Frequency scan failed: {}", e),
+        }
+    }
+
+    // Start native SDR capture
     let frame_rx = match sdr.start() {
         Ok(rx) => rx,
         Err(e) => {
@@ -138,16 +328,54 @@ async fn main() -> Result<()> {
         }
     };
 
-    // Send initial device status
-    let initial_status = DeviceStatus {
+    // Device status with how the decoder is set up right now
+    let device_status = |connected: bool| DeviceStatus {
         device_id: config.device_id.clone(),
-        connected: true,
+        connected,
         sample_rate: 2_000_000,
         center_freq: 1_090_000_000,
         gain_db: config.gain_db,
-        timestamp_ms: chrono::Utc::now().timestamp_millis() as u64,
+        timestamp_ms: clock.now_ms(),
+        software_version: env!("CARGO_PKG_VERSION").to_string(),
+        uptime_secs: started.elapsed().as_secs(),
+        decoder: Some(DecoderConfig {
+            max_corrected_bits: sdr.max_corrected_bits() as u32,
+            max_false_positive_rate: config.max_false_positive_rate,
+            noise_blanker_factor: config.noise_blanker.then_some(config.noise_blanker_factor),
+            min_position_nic: config.min_position_nic as u32,
+            min_position_nac_p: config.min_position_nac_p as u32,
+            track_smoothing: config.track_smoothing,
+            ppm_error: config.ppm_error,
+        }),
+        clock_offset_ms: clock.offset_ms(),
+    };
+
+    // Send initial device status
+    let _ = status_tx.send(device_status(true)).await;
+
+    // Tracker state and a map page for checking reception without the gateway
+    let local_map = config
+        .local_http_addr
+        .map(|addr| local_map::spawn(addr, config.receiver_location));
+
+    // Live terminal view of the tracked aircraft
+    let dashboard = if interactive {
+        let title = format!(
+            "{} · {:.1} dB · {}",
+            config.device_id,
+            config.gain_db,
+            if dry_run { "dry run" } else { config.gateway_url.as_str() }
+        );
+        match interactive::Dashboard::start(title) {
+            Ok(dashboard) => Some(dashboard),
+            Err(e) => {
+                sdr.stop();
+                return Err(e);
+            }
+        }
+    } else {
+        None
     };
-    let _ = status_tx.send(initial_status).await;
 
     info!("===========================================");
     info!("  Starting capture...");
@@ -156,47 +384,149 @@ async fn main() -> Result<()> {
 
     // CPR context for position decoding
     let mut cpr_context = adsb::CprContext::new(256);
+    cpr_context.set_receiver(config.receiver_location);
 
     // Aircraft tracker for state aggregation
     let mut aircraft_tracker = AircraftTracker::new(256);
+    aircraft_tracker.set_smoothing(config.track_smoothing);
+    aircraft_tracker.set_quality_threshold(adsb::QualityThreshold {
+        min_nic: config.min_position_nic,
+        min_nac_p: config.min_position_nac_p,
+    });
+    aircraft_tracker.set_trust(sdr.trust().clone());
+
+    // Callsigns and categories remembered across restarts
+    let aircraft_info = config.aircraft_cache_file.as_deref().and_then(aircraft_info::open);
+    if let Some(info) = &aircraft_info {
+        aircraft_tracker.set_info_cache(info.clone());
+        if config.aircraft_cache_sync_secs > 0 && !dry_run {
+            aircraft_info::spawn_sync(
+                config.gateway_url.clone(),
+                config.device_id.clone(),
+                info.clone(),
+                Duration::from_secs(config.aircraft_cache_sync_secs),
+            );
+        }
+    }
+
+    // DF11 replies per interrogator code
+    let mut interrogators = InterrogatorStats::new();
+
+    // Frames from addresses never heard again
+    let mut false_positives = FalsePositiveEstimator::new();
+
+    // Frames per downlink format and type code
+    let mut message_types = MessageTypeStats::new();
+
+    // Events only for aircraft whose state changed
+    let mut send_policy = SendPolicy::new(
+        config.event_send_policy,
+        config.event_min_alt_change_ft,
+        Duration::from_secs(config.event_keepalive_secs),
+    );
+
+    // Echoes of frames just handled
+    let mut dedup = FrameDedup::new(config.dedup_window_ms, 2_000_000);
 
     // Track statistics
     let mut frames_processed = 0u64;
+    let mut events_shed = 0u64;
+    let mut events_filtered = 0u64;
     let mut last_heartbeat = Instant::now();
     let mut last_signal_report = Instant::now();
     let mut last_tracker_report = Instant::now();
+    let mut last_expiry = Instant::now();
 
     // Main processing loop - receive decoded frames from SDR
     loop {
         // Non-blocking receive with timeout for heartbeats
         match frame_rx.recv_timeout(Duration::from_millis(500)) {
+            Ok(frame) if dedup.is_duplicate(&frame.data, frame.timestamp_samples) => {
+                // Multipath or overlapping detection copy, counted by the deduplicator
+            }
             Ok(frame) => {
                 frames_processed += 1;
+                message_types.record(&frame.data, frame.corrected_bits > 0);
+                feeders.send(&frame.data, frame.timestamp_samples, frame.signal_level);
+
+                // Forward raw frame for archival (never block the decode loop)
+                if config.forward_raw_frames {
+                    let raw = RawFrame {
+                        device_id: config.device_id.clone(),
+                        timestamp_ms: clock.now_ms(),
+                        data: frame.data.clone(),
+                        signal_level: frame.signal_level as u32,
+                    };
+                    if raw_tx.try_send(raw).is_err() {
+                        debug!("Raw frame channel full, dropping frame");
+                    }
+                }
 
                 // Parse the raw frame into aircraft data
-                match adsb::parse_message(&frame.data, &mut cpr_context) {
+                match adsb::parse_message(&frame.data, &mut cpr_context, sdr.trust()) {
                     Ok(aircraft) => {
+                        false_positives.record(aircraft.key(), frame.corrected_bits > 0, Instant::now());
+
+                        // Count interrogator codes, from addresses already heard
+                        if let Some(code) = aircraft.interrogator {
+                            if aircraft_tracker.get(aircraft.icao_address).is_some() {
+                                interrogators.record(code, aircraft.icao_address, SystemTime::now());
+                            }
+                        }
+
                         // Update aircraft tracker (aggregates all data per ICAO)
                         if let Some(state) = aircraft_tracker.update(&aircraft) {
                             // Build aircraft event from aggregated state
                             let event = AircraftEvent {
                                 device_id: config.device_id.clone(),
-                                timestamp_ms: chrono::Utc::now().timestamp_millis() as u64,
-                                icao: format!("{:06X}", state.icao),
-                                callsign: state.callsign.clone().unwrap_or_default(),
-                                altitude_ft: state.altitude_ft.unwrap_or(0),
-                                latitude: state.latitude.unwrap_or(0.0),
-                                longitude: state.longitude.unwrap_or(0.0),
-                                speed_kts: state.ground_speed_kts.unwrap_or(0.0),
-                                heading_deg: state.heading_deg.unwrap_or(0.0),
-                                vertical_rate_fpm: state.vertical_rate_fpm.unwrap_or(0),
-                                squawk: state.squawk.map(|s| format!("{:04}", s)).unwrap_or_default(),
+                                timestamp_ms: clock.now_ms(),
+                                icao: state.address(),
+                                callsign: state.callsign.clone(),
+                                altitude_ft: state.altitude_ft,
+                                latitude: state.latitude,
+                                longitude: state.longitude,
+                                speed_kts: state.ground_speed_kts,
+                                track_deg: state.track_deg,
+                                magnetic_heading_deg: state.magnetic_heading_deg,
+                                vertical_rate_fpm: state.vertical_rate_fpm,
+                                baro_rate_fpm: state.baro_rate_fpm,
+                                geom_rate_fpm: state.geom_rate_fpm,
+                                squawk: state.squawk.map(|s| format!("{:04}", s)),
                                 downlink_format: aircraft.df as u32,
                                 type_code: aircraft.tc as u32,
+                                emergency: Emergency::from(state.emergency) as i32,
+                                removed: false,
+                                category: state.category.clone(),
+                                filtered_latitude: state.filtered_latitude,
+                                filtered_longitude: state.filtered_longitude,
+                                adsb_version: state.adsb_version.map(|v| v.number() as u32),
+                                nic: state.integrity.and_then(|i| i.nic).map(u32::from),
+                                nac_p: state.integrity.and_then(|i| i.nac_p).map(u32::from),
+                                nuc_p: state.integrity.and_then(|i| i.nuc_p).map(u32::from),
+                                containment_radius_m: state.integrity.and_then(|i| i.rc_m),
+                                provenance: config.event_provenance.then(|| FrameProvenance {
+                                    raw_hex: frame.to_hex(),
+                                    downlink_format: aircraft.df as u32,
+                                    type_code: aircraft.tc as u32,
+                                    corrected_bits: frame.corrected_bits as u32,
+                                    confidence: frame.confidence,
+                                    signal_level: frame.signal_level as u32,
+                                }),
+                                source: None,
                             };
 
-                            // Send to gateway (only if we have useful data)
-                            if state.has_position || state.callsign.is_some() || state.altitude_ft.is_some() {
+                            // Send to gateway (only useful data passing the filters); while
+                            // the stream falls behind, velocity-only updates are shed first
+                            let backlog = AIRCRAFT_CHANNEL_CAPACITY - aircraft_tx.capacity();
+                            if !event_filter.matches(state) {
+                                events_filtered += 1;
+                            } else if priority::over_high_water(backlog, AIRCRAFT_CHANNEL_CAPACITY)
+                                && !priority::is_priority(aircraft.df, aircraft.tc)
+                            {
+                                events_shed += 1;
+                            } else if (state.has_position || state.callsign.is_some() || state.altitude_ft.is_some())
+                                && send_policy.should_send(state, Instant::now())
+                            {
                                 if let Err(e) = aircraft_tx.send(event).await {
                                     warn!("Failed to send aircraft event: {}", e);
                                 }
@@ -223,15 +553,7 @@ async fn main() -> Result<()> {
         // Periodic heartbeat (every 5 seconds to keep status "active" in DB)
         // The DB considers device active if last_heartbeat < 30 seconds ago
         if last_heartbeat.elapsed() >= Duration::from_secs(5) {
-            let status = DeviceStatus {
-                device_id: config.device_id.clone(),
-                connected: sdr.is_running(),
-                sample_rate: 2_000_000,
-                center_freq: 1_090_000_000,
-                gain_db: config.gain_db,
-                timestamp_ms: chrono::Utc::now().timestamp_millis() as u64,
-            };
-            let _ = status_tx.send(status).await;
+            let _ = status_tx.send(device_status(sdr.is_running())).await;
             last_heartbeat = Instant::now();
         }
 
@@ -265,9 +587,24 @@ async fn main() -> Result<()> {
             };
             let snr_db = signal_dbfs - noise_dbfs;
 
+            // Flip fewer bits while corrected frames look like noise
+            false_positives.sweep(Instant::now());
+            let bits = sdr.max_corrected_bits();
+            if let Some(rate) = false_positives.corrected_rate() {
+                if config.max_false_positive_rate > 0.0 && rate > config.max_false_positive_rate && bits > 0 {
+                    warn!(
+                        "{:.1}% of corrected frames look like noise, limiting error correction to {} bit(s)",
+                        rate * 100.0,
+                        bits - 1
+                    );
+                    sdr.set_max_corrected_bits(bits - 1);
+                    false_positives.reset_corrected();
+                }
+            }
+
             let metrics = SignalMetrics {
                 device_id: config.device_id.clone(),
-                timestamp_ms: chrono::Utc::now().timestamp_millis() as u64,
+                timestamp_ms: clock.now_ms(),
                 signal_dbfs,
                 noise_dbfs,
                 snr_db,
@@ -279,18 +616,59 @@ async fn main() -> Result<()> {
                 samples_processed,
                 noise_floor,
                 peak_signal,
+                interrogators: interrogators.counts(),
+                downlink_formats: message_types.downlink_formats(),
+                type_codes: message_types.type_codes(),
+                false_positive_rate: false_positives.rate(),
+                corrected_false_positive_rate: false_positives.corrected_rate(),
+                duplicate_frames: dedup.duplicates(),
             };
+            if let Some(dashboard) = &dashboard {
+                dashboard.update(aircraft_tracker.get_all(), &metrics);
+            }
+            if let Some(local_map) = &local_map {
+                local_map.update(aircraft_tracker.get_all(), frames_processed);
+            }
             let _ = signal_tx.send(metrics).await;
             last_signal_report = Instant::now();
         }
 
+        // Tell the gateway about aircraft the tracker has timed out (every 5 seconds)
+        if last_expiry.elapsed() >= Duration::from_secs(5) {
+            for icao in aircraft_tracker.expire() {
+                send_policy.forget(icao);
+                let event = AircraftEvent {
+                    device_id: config.device_id.clone(),
+                    timestamp_ms: clock.now_ms(),
+                    icao: adsb::format_address(icao),
+                    removed: true,
+                    ..Default::default()
+                };
+                if let Err(e) = aircraft_tx.send(event).await {
+                    warn!("Failed to send aircraft removal: {}", e);
+                }
+            }
+            last_expiry = Instant::now();
+        }
+
         // Periodic tracker statistics (every 10 seconds)
         if last_tracker_report.elapsed() >= Duration::from_secs(10) {
             let stats = aircraft_tracker.stats_summary();
             info!(
-                "[Tracker] {}",
-                stats
+                "[Tracker] {}, {} recent addresses, {} duplicate frames, {} events shed, {} filtered, {} unchanged",
+                stats,
+                sdr.trust().len(),
+                dedup.duplicates(),
+                events_shed,
+                events_filtered,
+                send_policy.unchanged
             );
+            for feed in feeders.summary() {
+                info!("[Feed] {}", feed);
+            }
+            if let Some(info) = &aircraft_info {
+                aircraft_info::flush(info);
+            }
             last_tracker_report = Instant::now();
         }
 
@@ -299,26 +677,31 @@ async fn main() -> Result<()> {
             warn!("SDR capture stopped unexpectedly");
             break;
         }
+
+        // Quit key in the interactive view
+        if dashboard.as_ref().is_some_and(|d| d.quit_requested()) {
+            info!("Stopping at the interactive view's request");
+            break;
+        }
+    }
+
+    if let Some(dashboard) = dashboard {
+        dashboard.stop();
     }
 
     // Cleanup
     sdr.stop();
+    if let Some(info) = &aircraft_info {
+        aircraft_info::flush(info);
+    }
 
     // Send disconnected status
-    let final_status = DeviceStatus {
-        device_id: config.device_id.clone(),
-        connected: false,
-        sample_rate: 2_000_000,
-        center_freq: 1_090_000_000,
-        gain_db: config.gain_db,
-        timestamp_ms: chrono::Utc::now().timestamp_millis() as u64,
-    };
-    let _ = status_tx.send(final_status).await;
+    let _ = status_tx.send(device_status(false)).await;
 
     // Cancel streaming tasks
-    aircraft_handle.abort();
-    signal_handle.abort();
-    status_handle.abort();
+    for handle in stream_handles {
+        handle.abort();
+    }
 
     info!("Shutdown complete. Frames processed: {}", frames_processed);
     Ok(())
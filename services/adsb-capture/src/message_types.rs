@@ -0,0 +1,118 @@
+//! Message type breakdown
+//!
+//! Decoded frames are counted per downlink format, with the share that
+//! needed error correction, and extended squitters (DF17/18) per type code.
+//! The counts are cumulative since the host started and go out with every
+//! signal report, so the gateway can chart, say, DF17 against DF11 replies
+//! and how much of each was corrected while gain and error correction are
+//! tuned. Formats 24-31 are all Comm-D and are counted together as DF24.
+
+use crate::adsb;
+use crate::grpc::adsb::{DownlinkFormatCount, TypeCodeCount};
+
+/// Formats counted separately; higher ones are Comm-D
+const COMM_D: usize = 24;
+
+/// Frame counts for one downlink format
+#[derive(Debug, Default, Clone, Copy)]
+struct FormatCounts {
+    frames: u64,
+    corrected: u64,
+}
+
+/// Frames decoded per downlink format and extended squitter type code
+#[derive(Debug)]
+pub struct MessageTypeStats {
+    formats: [FormatCounts; COMM_D + 1],
+    type_codes: [u64; 32],
+}
+
+impl Default for MessageTypeStats {
+    fn default() -> Self {
+        Self {
+            formats: [FormatCounts::default(); COMM_D + 1],
+            type_codes: [0; 32],
+        }
+    }
+}
+
+impl MessageTypeStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count a frame that passed the CRC, `corrected` if bits were flipped
+    pub fn record(&mut self, frame: &[u8], corrected: bool) {
+        if frame.is_empty() {
+            return;
+        }
+        let df = adsb::get_df(frame) as usize;
+        let counts = &mut self.formats[df.min(COMM_D)];
+        counts.frames += 1;
+        if corrected {
+            counts.corrected += 1;
+        }
+        if matches!(df, 17 | 18) && frame.len() == 14 {
+            self.type_codes[(frame[4] >> 3) as usize] += 1;
+        }
+    }
+
+    /// Formats seen so far
+    pub fn downlink_formats(&self) -> Vec<DownlinkFormatCount> {
+        self.formats
+            .iter()
+            .enumerate()
+            .filter(|(_, counts)| counts.frames > 0)
+            .map(|(df, counts)| DownlinkFormatCount {
+                downlink_format: df as u32,
+                frames: counts.frames,
+                corrected: counts.corrected,
+            })
+            .collect()
+    }
+
+    /// Extended squitter type codes seen so far
+    pub fn type_codes(&self) -> Vec<TypeCodeCount> {
+        self.type_codes
+            .iter()
+            .enumerate()
+            .filter(|(_, &frames)| frames > 0)
+            .map(|(tc, &frames)| TypeCodeCount {
+                type_code: tc as u32,
+                frames,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(hex: &str) -> Vec<u8> {
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_breakdown() {
+        let mut stats = MessageTypeStats::new();
+        // DF17 identification (TC4) and airborne position (TC11)
+        stats.record(&frame("8D4840D6202CC371C32CE0576098"), false);
+        stats.record(&frame("8D40621D58C382D690C8AC2863A7"), true);
+        // DF11 all-call reply and a Comm-D frame
+        stats.record(&frame("5D4840D6D6C7E8"), false);
+        stats.record(&frame("F800000000000000000000000000"), false);
+
+        let formats: Vec<_> = stats
+            .downlink_formats()
+            .iter()
+            .map(|c| (c.downlink_format, c.frames, c.corrected))
+            .collect();
+        assert_eq!(formats, [(11, 1, 0), (17, 2, 1), (24, 1, 0)]);
+        let type_codes: Vec<_> = stats.type_codes().iter().map(|c| (c.type_code, c.frames)).collect();
+        assert_eq!(type_codes, [(4, 1), (11, 1)]);
+    }
+}
@@ -0,0 +1,49 @@
+//! Load shedding by frame priority
+//!
+//! When the decoder or the gateway can't keep up, the frame channel (SDR
+//! thread to decode loop) and the aircraft event channel (decode loop to
+//! gateway stream) fill up. Rather than dropping whatever arrives once they
+//! are full, frames and events that can't carry a position or callsign
+//! (velocity, surveillance replies, status) are shed first, as soon as a
+//! channel is `HIGH_WATER` full; the room left keeps position and
+//! identification frames flowing. A shed velocity update costs little: the
+//! next event carries the aircraft's latest velocity anyway.
+
+/// Share of a channel's capacity above which low priority items are shed
+pub const HIGH_WATER: f32 = 0.75;
+
+/// Whether a message of downlink format `df` and type code `tc` can carry
+/// a position or callsign: extended squitter identification (TC 1-4),
+/// surface (5-8) and airborne positions (9-18, 20-22)
+pub fn is_priority(df: u8, tc: u8) -> bool {
+    matches!(df, 17 | 18) && matches!(tc, 1..=18 | 20..=22)
+}
+
+/// [`is_priority`] for a raw frame
+pub fn is_priority_frame(frame: &[u8]) -> bool {
+    frame.len() == 14 && is_priority(frame[0] >> 3, frame[4] >> 3)
+}
+
+/// Whether a channel holding `len` of `capacity` items should shed low
+/// priority ones
+pub fn over_high_water(len: usize, capacity: usize) -> bool {
+    len as f32 >= capacity as f32 * HIGH_WATER
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_priority() {
+        // Identification and airborne position
+        assert!(is_priority_frame(&[0x8D, 0x48, 0x40, 0xD6, 0x20, 0x2C, 0xC3, 0x71, 0xC3, 0x2C, 0xE0, 0x57, 0x60, 0x98]));
+        assert!(is_priority_frame(&[0x8D, 0x40, 0x62, 0x1D, 0x58, 0xC3, 0x82, 0xD6, 0x90, 0xC8, 0xAC, 0x28, 0x63, 0xA7]));
+        // Airborne velocity (TC19) and a DF11 all-call reply
+        assert!(!is_priority_frame(&[0x8D, 0x48, 0x50, 0x20, 0x99, 0x44, 0x09, 0x94, 0x08, 0x38, 0x17, 0x5B, 0x28, 0x4F]));
+        assert!(!is_priority_frame(&[0x5D, 0x48, 0x40, 0xD6, 0xD6, 0xC7, 0xE8]));
+
+        assert!(!over_high_water(749, 1000));
+        assert!(over_high_water(750, 1000));
+    }
+}
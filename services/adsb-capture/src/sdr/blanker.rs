@@ -0,0 +1,88 @@
+//! Impulse noise blanker
+//!
+//! Weather and surveillance radars, DME and ignition noise put strong pulses
+//! on the band that the preamble detector mistakes for Mode S. Mode S never
+//! holds the carrier for more than 1 µs (a "01" bit pair), so any run of
+//! strong samples longer than `MAX_PULSE_SAMPLES` can't be part of a frame.
+//! The blanker replaces such runs, plus a few guard samples either side for
+//! the receiver's ringing, with the noise floor before preambles are scanned.
+
+/// Longest run of strong samples a Mode S transmission produces, with some
+/// allowance for pulse smearing at 2 MSPS (2.5 µs)
+const MAX_PULSE_SAMPLES: usize = 5;
+
+/// Samples blanked either side of a pulse
+const GUARD_SAMPLES: usize = 2;
+
+/// Lowest magnitude ever treated as a pulse, for near-silent buffers
+const MIN_THRESHOLD: u16 = 20;
+
+/// Suppresses pulses too long to be Mode S
+#[derive(Debug, Clone)]
+pub struct NoiseBlanker {
+    /// Samples this many times the noise floor are strong
+    threshold_factor: u32,
+    /// Pulses blanked so far
+    pub pulses_blanked: u64,
+    /// Samples replaced so far
+    pub samples_blanked: u64,
+}
+
+impl NoiseBlanker {
+    pub fn new(threshold_factor: u32) -> Self {
+        Self {
+            threshold_factor,
+            pulses_blanked: 0,
+            samples_blanked: 0,
+        }
+    }
+
+    /// Blank long pulses in `mag` in place
+    pub fn apply(&mut self, mag: &mut [u16], noise_floor: u32) {
+        let threshold = (noise_floor.saturating_mul(self.threshold_factor))
+            .clamp(MIN_THRESHOLD as u32, u16::MAX as u32) as u16;
+        let fill = noise_floor.min(u16::MAX as u32) as u16;
+
+        let mut i = 0;
+        while i < mag.len() {
+            if mag[i] < threshold {
+                i += 1;
+                continue;
+            }
+            let start = i;
+            while i < mag.len() && mag[i] >= threshold {
+                i += 1;
+            }
+            if i - start > MAX_PULSE_SAMPLES {
+                let from = start.saturating_sub(GUARD_SAMPLES);
+                let to = (i + GUARD_SAMPLES).min(mag.len());
+                mag[from..to].fill(fill);
+                self.pulses_blanked += 1;
+                self.samples_blanked += (to - from) as u64;
+                i = to;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blanks_only_long_pulses() {
+        let mut blanker = NoiseBlanker::new(6);
+        let mut mag = vec![5u16; 100];
+        // Mode S preamble pulses at samples 10, 12, 17, 19
+        for i in [10, 12, 17, 19] {
+            mag[i] = 200;
+        }
+        // A 3 µs radar pulse
+        mag[50..56].fill(1000);
+
+        blanker.apply(&mut mag, 5);
+        assert_eq!([mag[10], mag[12], mag[17], mag[19]], [200; 4]);
+        assert!(mag[48..58].iter().all(|&m| m == 5));
+        assert_eq!((blanker.pulses_blanked, blanker.samples_blanked), (1, 10));
+    }
+}
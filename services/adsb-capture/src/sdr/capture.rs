@@ -7,13 +7,20 @@ use anyhow::{Context, Result};
 use crossbeam_channel::{bounded, Receiver, Sender};
 use std::io::{BufRead, Read};
 use std::process::{Command, Stdio};
-use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicU8, Ordering};
 use std::sync::Arc;
 use std::thread;
 use std::time::{Duration, Instant};
 use tracing::{debug, error, info, warn};
 
-use super::detect::{Frame, ModeS};
+use super::blanker::NoiseBlanker;
+use super::detect::{Frame, ModeS, MAX_CORRECTED_BITS};
+use crate::adsb::IcaoTrust;
+use crate::priority;
+use super::scan::{run_scan, ScanPoint};
+
+/// Decoded frames buffered between the capture thread and the decode loop
+const FRAME_CHANNEL_CAPACITY: usize = 1000;
 
 /// Query RTL-SDR device serial number by device index
 /// Parses the output of rtl_sdr -d N to extract the serial number
@@ -186,6 +193,8 @@ pub struct SdrConfig {
     pub gain: i32,           // Gain in tenths of dB (e.g., 496 = 49.6 dB)
     pub ppm_error: i32,
     pub rtl_sdr_path: String,
+    /// Blank pulses too long for Mode S that are this many times the noise floor (None = off)
+    pub noise_blanker: Option<u32>,
 }
 
 impl Default for SdrConfig {
@@ -197,6 +206,7 @@ impl Default for SdrConfig {
             gain: 496,                   // 49.6 dB
             ppm_error: 0,
             rtl_sdr_path: "rtl_sdr".to_string(),
+            noise_blanker: None,
         }
     }
 }
@@ -210,6 +220,8 @@ pub struct CaptureStats {
     pub preambles_detected: AtomicU64,
     pub crc_errors: AtomicU64,
     pub corrected_frames: AtomicU64,
+    /// Frames not passed to the decode loop because it was falling behind
+    pub frames_dropped: AtomicU64,
     pub noise_floor: std::sync::atomic::AtomicU32,
     pub peak_signal: std::sync::atomic::AtomicU32,
 }
@@ -225,6 +237,8 @@ pub struct SdrCapture {
     config: SdrConfig,
     running: Arc<AtomicBool>,
     stats: Arc<CaptureStats>,
+    trust: Arc<IcaoTrust>,
+    max_corrected_bits: Arc<AtomicU8>,
 }
 
 impl SdrCapture {
@@ -233,6 +247,8 @@ impl SdrCapture {
             config,
             running: Arc::new(AtomicBool::new(false)),
             stats: CaptureStats::new(),
+            trust: Arc::new(IcaoTrust::new()),
+            max_corrected_bits: Arc::new(AtomicU8::new(MAX_CORRECTED_BITS)),
         }
     }
 
@@ -246,14 +262,19 @@ impl SdrCapture {
         info!("  Sample rate: {} MSPS", self.config.sample_rate / 1_000_000);
         info!("  Gain: {:.1} dB", self.config.gain as f32 / 10.0);
         info!("  rtl_sdr path: {}", self.config.rtl_sdr_path);
+        if let Some(factor) = self.config.noise_blanker {
+            info!("  Noise blanker: pulses > {}x noise floor", factor);
+        }
 
         // Create channel for decoded frames
-        let (frame_tx, frame_rx) = bounded::<Frame>(1000);
+        let (frame_tx, frame_rx) = bounded::<Frame>(FRAME_CHANNEL_CAPACITY);
 
         // Clone for thread
         let config = self.config.clone();
         let running = self.running.clone();
         let stats = self.stats.clone();
+        let trust = self.trust.clone();
+        let max_corrected_bits = self.max_corrected_bits.clone();
 
         running.store(true, Ordering::SeqCst);
 
@@ -261,7 +282,7 @@ impl SdrCapture {
         thread::Builder::new()
             .name("sdr-capture".to_string())
             .spawn(move || {
-                if let Err(e) = run_capture(config, running, stats, frame_tx) {
+                if let Err(e) = run_capture(config, running, stats, trust, max_corrected_bits, frame_tx) {
                     error!("SDR capture error: {}", e);
                 }
             })
@@ -270,6 +291,21 @@ impl SdrCapture {
         Ok(frame_rx)
     }
 
+    /// Measure noise and spurs from `center - span` to `center + span`;
+    /// the device can only be opened once, so this refuses while capturing
+    pub fn scan(&self, span_hz: u32, step_hz: u32) -> Result<Vec<ScanPoint>> {
+        if self.is_running() {
+            anyhow::bail!("Cannot scan while capture is running");
+        }
+        info!(
+            "Scanning {:.1} MHz either side of {} MHz in {:.2} MHz steps...",
+            span_hz as f32 / 1e6,
+            self.config.center_freq / 1_000_000,
+            step_hz as f32 / 1e6
+        );
+        run_scan(&self.config, span_hz, step_hz)
+    }
+
     /// Stop capturing
     pub fn stop(&self) {
         info!("Stopping RTL-SDR capture...");
@@ -285,6 +321,21 @@ impl SdrCapture {
     pub fn stats(&self) -> &Arc<CaptureStats> {
         &self.stats
     }
+
+    /// Addresses the detector has heard in clean frames
+    pub fn trust(&self) -> &Arc<IcaoTrust> {
+        &self.trust
+    }
+
+    /// Most bits the detector's error correction flips
+    pub fn max_corrected_bits(&self) -> u8 {
+        self.max_corrected_bits.load(Ordering::Relaxed)
+    }
+
+    /// Limit error correction, taking effect from the next buffer
+    pub fn set_max_corrected_bits(&self, bits: u8) {
+        self.max_corrected_bits.store(bits, Ordering::Relaxed);
+    }
 }
 
 /// Main capture loop (runs in dedicated thread)
@@ -292,6 +343,8 @@ fn run_capture(
     config: SdrConfig,
     running: Arc<AtomicBool>,
     stats: Arc<CaptureStats>,
+    trust: Arc<IcaoTrust>,
+    max_corrected_bits: Arc<AtomicU8>,
     frame_tx: Sender<Frame>,
 ) -> Result<()> {
     info!("Starting rtl_sdr process for raw IQ capture...");
@@ -345,6 +398,8 @@ fn run_capture(
 
     // Create Mode S detector
     let mut detector = ModeS::new();
+    detector.set_noise_blanker(config.noise_blanker.map(NoiseBlanker::new));
+    detector.set_trust(trust);
 
     // Buffer for reading IQ samples
     // Process in chunks of 256K samples (512KB)
@@ -374,6 +429,7 @@ fn run_capture(
                 stats.buffers_processed.fetch_add(1, Ordering::Relaxed);
 
                 // Process buffer through Mode S detector
+                detector.set_max_corrected_bits(max_corrected_bits.load(Ordering::Relaxed));
                 let frames = detector.process_buffer(&buffer[..n_read]);
 
                 for frame in frames {
@@ -388,8 +444,16 @@ fn run_capture(
                         frame.to_hex()
                     );
 
-                    // Send to channel (non-blocking)
+                    // Send to channel (non-blocking); when the decode loop falls
+                    // behind, keep the room for position and identification frames
+                    if priority::over_high_water(frame_tx.len(), FRAME_CHANNEL_CAPACITY)
+                        && !priority::is_priority_frame(&frame.data)
+                    {
+                        stats.frames_dropped.fetch_add(1, Ordering::Relaxed);
+                        continue;
+                    }
                     if frame_tx.try_send(frame).is_err() {
+                        stats.frames_dropped.fetch_add(1, Ordering::Relaxed);
                         debug!("Frame channel full, dropping frame");
                     }
                 }
@@ -424,12 +488,14 @@ fn run_capture(
                     let sample_rate = samples_delta as f32 / elapsed;
 
                     info!(
-                        "[SDR Stats] Rate: {:.2} MSPS | Preambles: {} | Frames: {} (corrected: {}) | CRC errors: {}",
+                        "[SDR Stats] Rate: {:.2} MSPS | Preambles: {} | Frames: {} (corrected: {}) | CRC errors: {} | Blanked pulses: {} | Dropped: {}",
                         sample_rate / 1_000_000.0,
                         detector.stats.preambles_detected,
                         detector.stats.frames_decoded,
                         detector.stats.corrected_frames,
-                        detector.stats.crc_errors
+                        detector.stats.crc_errors,
+                        detector.stats.pulses_blanked,
+                        stats.frames_dropped.load(Ordering::Relaxed)
                     );
 
                     last_stats_time = Instant::now();
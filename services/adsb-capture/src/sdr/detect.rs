@@ -8,7 +8,11 @@
 //! - Preamble: 8µs (16 samples)
 //! - Data: 56 bits (short) or 112 bits (long) at 1µs per bit = 2 samples per bit
 
+use super::blanker::NoiseBlanker;
 use super::MagnitudeTable;
+use crate::adsb::IcaoTrust;
+use std::sync::Arc;
+use std::time::Instant;
 use tracing::{debug, trace};
 
 /// ADS-B/Mode S frame types
@@ -25,6 +29,11 @@ pub struct Frame {
     pub data: Vec<u8>,  // Raw bytes (7 or 14 bytes)
     pub signal_level: u16,  // Signal strength
     pub timestamp_samples: u64,  // Sample offset when frame was detected
+    /// Bits flipped by error correction to pass the CRC (0-2)
+    pub corrected_bits: u8,
+    /// Mean Manchester margin of the data bits relative to the preamble
+    /// level (0-1); low values mean bits were close calls
+    pub confidence: f32,
 }
 
 impl Frame {
@@ -55,6 +64,12 @@ pub struct ModeS {
     noise_floor: u32,
     /// Noise floor sample count for moving average
     noise_samples: u64,
+    /// Optional suppression of pulses too long for Mode S
+    blanker: Option<NoiseBlanker>,
+    /// Addresses corroborated by clean frames
+    trust: Arc<IcaoTrust>,
+    /// Most bits error correction may flip (0-2)
+    max_corrected_bits: u8,
 }
 
 #[derive(Debug, Default)]
@@ -66,6 +81,7 @@ pub struct DetectorStats {
     pub short_frames: u64,
     pub long_frames: u64,
     pub corrected_frames: u64,
+    pub pulses_blanked: u64,
 }
 
 // Mode S preamble timing (in samples at 2 MSPS)
@@ -74,6 +90,9 @@ const SHORT_FRAME_BITS: usize = 56;
 const LONG_FRAME_BITS: usize = 112;
 const SAMPLES_PER_BIT: usize = 2;
 
+/// Most bits error correction flips
+pub const MAX_CORRECTED_BITS: u8 = 2;
+
 impl ModeS {
     pub fn new() -> Self {
         Self {
@@ -85,6 +104,9 @@ impl ModeS {
             max_magnitude_seen: 0,
             noise_floor: 0,
             noise_samples: 0,
+            blanker: None,
+            trust: Arc::new(IcaoTrust::new()),
+            max_corrected_bits: MAX_CORRECTED_BITS,
         }
     }
 
@@ -93,6 +115,21 @@ impl ModeS {
         self.min_signal = threshold;
     }
 
+    /// Blank long non-Mode S pulses before scanning for preambles
+    pub fn set_noise_blanker(&mut self, blanker: Option<NoiseBlanker>) {
+        self.blanker = blanker;
+    }
+
+    /// Share the address trust scores with the parser and tracker
+    pub fn set_trust(&mut self, trust: Arc<IcaoTrust>) {
+        self.trust = trust;
+    }
+
+    /// Limit error correction to `bits` flipped bits (0 turns it off)
+    pub fn set_max_corrected_bits(&mut self, bits: u8) {
+        self.max_corrected_bits = bits.min(MAX_CORRECTED_BITS);
+    }
+
     /// Process a buffer of IQ samples and return detected frames
     pub fn process_buffer(&mut self, iq_data: &[u8]) -> Vec<Frame> {
         let num_samples = iq_data.len() / 2;
@@ -124,6 +161,11 @@ impl ModeS {
             self.noise_samples += 1;
         }
 
+        if let Some(blanker) = &mut self.blanker {
+            blanker.apply(&mut magnitude, self.noise_floor);
+            self.stats.pulses_blanked = blanker.pulses_blanked;
+        }
+
         // Adaptive threshold: 4x noise floor, minimum 10
         // With noise floor of ~1, this gives threshold of ~10
         // Real ADS-B signals should be well above this
@@ -348,37 +390,45 @@ impl ModeS {
         // Try long frame first (most ADS-B is DF17/18 = long)
         if data_start + LONG_FRAME_BITS * SAMPLES_PER_BIT <= mag.len() {
             let (bytes, confidence) = self.extract_bits_with_confidence(mag, data_start, LONG_FRAME_BITS);
-            if self.verify_crc(&bytes) {
+            if self.verify_crc(&bytes).is_some() {
+                self.trust.observe(&bytes, Instant::now());
                 return Some(Frame {
                     frame_type: FrameType::Long,
                     data: bytes,
                     signal_level: signal_level as u16,
                     timestamp_samples: self.sample_counter + preamble_pos as u64,
+                    corrected_bits: 0,
+                    confidence: bit_confidence(&confidence, signal_level),
                 });
             }
 
             // Try 1-bit error correction for long frames (DF17/18 are most valuable)
-            if let Some(corrected) = self.try_single_bit_correction(&bytes, &confidence, LONG_FRAME_BITS) {
+            if let Some((corrected, flipped)) = self.try_single_bit_correction(&bytes, &confidence, LONG_FRAME_BITS) {
                 self.stats.corrected_frames += 1;
-                trace!("Corrected 1-bit error in long frame");
+                trace!("Corrected {}-bit error in long frame", flipped);
                 return Some(Frame {
                     frame_type: FrameType::Long,
                     data: corrected,
                     signal_level: signal_level as u16,
                     timestamp_samples: self.sample_counter + preamble_pos as u64,
+                    corrected_bits: flipped,
+                    confidence: bit_confidence(&confidence, signal_level),
                 });
             }
         }
 
         // Try short frame
         if data_start + SHORT_FRAME_BITS * SAMPLES_PER_BIT <= mag.len() {
-            let bytes = self.extract_bits(mag, data_start, SHORT_FRAME_BITS);
-            if self.verify_crc(&bytes) {
+            let (bytes, confidence) = self.extract_bits_with_confidence(mag, data_start, SHORT_FRAME_BITS);
+            if self.verify_crc(&bytes).is_some() {
+                self.trust.observe(&bytes, Instant::now());
                 return Some(Frame {
                     frame_type: FrameType::Short,
                     data: bytes,
                     signal_level: signal_level as u16,
                     timestamp_samples: self.sample_counter + preamble_pos as u64,
+                    corrected_bits: 0,
+                    confidence: bit_confidence(&confidence, signal_level),
                 });
             }
         }
@@ -408,33 +458,8 @@ impl ModeS {
         None
     }
 
-    /// Extract bits from magnitude samples using Manchester decoding
-    /// Each bit is 2 samples: high-low = 1, low-high = 0
-    ///
-    /// This uses dump1090-style bit extraction which is more robust:
-    /// - Compares first half vs second half of each bit period
-    /// - Uses the difference to determine confidence
-    fn extract_bits(&self, mag: &[u16], start: usize, num_bits: usize) -> Vec<u8> {
-        let num_bytes = (num_bits + 7) / 8;
-        let mut bytes = vec![0u8; num_bytes];
-
-        for bit_idx in 0..num_bits {
-            let sample_pos = start + bit_idx * SAMPLES_PER_BIT;
-            let first_half = mag[sample_pos] as i32;
-            let second_half = mag[sample_pos + 1] as i32;
-
-            // Manchester: first > second = 1, first < second = 0
-            if first_half > second_half {
-                let byte_idx = bit_idx / 8;
-                let bit_pos = 7 - (bit_idx % 8);
-                bytes[byte_idx] |= 1 << bit_pos;
-            }
-        }
-
-        bytes
-    }
-
     /// Extract bits with confidence values for error correction
+    /// Each bit is 2 samples: high-low = 1, low-high = 0
     /// Returns (bytes, confidence) where confidence[i] is how certain we are about bit i
     fn extract_bits_with_confidence(&self, mag: &[u16], start: usize, num_bits: usize) -> (Vec<u8>, Vec<i32>) {
         let num_bytes = (num_bits + 7) / 8;
@@ -461,9 +486,15 @@ impl ModeS {
         (bytes, confidence)
     }
 
-    /// Try to correct single bit errors by flipping low-confidence bits
-    /// This is based on dump1090's error correction approach
-    fn try_single_bit_correction(&self, bytes: &[u8], confidence: &[i32], num_bits: usize) -> Option<Vec<u8>> {
+    /// Try to correct single bit errors by flipping low-confidence bits,
+    /// returning the corrected frame and the number of bits flipped
+    /// This is based on dump1090's error correction approach: a corrected
+    /// frame is only accepted from an address clean frames have vouched for
+    fn try_single_bit_correction(&self, bytes: &[u8], confidence: &[i32], num_bits: usize) -> Option<(Vec<u8>, u8)> {
+        if self.max_corrected_bits == 0 {
+            return None;
+        }
+
         // Find the bits with lowest confidence (most likely to be errors)
         // Sort indices by confidence, try flipping lowest confidence bits first
         let mut indices: Vec<usize> = (0..num_bits).collect();
@@ -476,17 +507,16 @@ impl ModeS {
             let bit_pos = 7 - (bit_idx % 8);
             test_bytes[byte_idx] ^= 1 << bit_pos;
 
-            if self.verify_crc(&test_bytes) {
-                // Check if the DF is valid (11, 17, or 18)
-                let df = (test_bytes[0] >> 3) & 0x1F;
-                if df == 11 || df == 17 || df == 18 {
-                    return Some(test_bytes);
-                }
+            if self.is_correctable(&test_bytes) {
+                return Some((test_bytes, 1));
             }
         }
 
         // For weak signals, try 2-bit correction on the lowest confidence bits
         // This is more expensive but can recover more frames
+        if self.max_corrected_bits < 2 {
+            return None;
+        }
         let max_2bit = 30.min(num_bits); // Top 30 lowest confidence bits
         for i in 0..max_2bit {
             for j in (i+1)..max_2bit {
@@ -503,12 +533,8 @@ impl ModeS {
                 let bit_pos2 = 7 - (bit_idx2 % 8);
                 test_bytes[byte_idx2] ^= 1 << bit_pos2;
 
-                if self.verify_crc(&test_bytes) {
-                    // Check if the DF is valid (11, 17, or 18)
-                    let df = (test_bytes[0] >> 3) & 0x1F;
-                    if df == 11 || df == 17 || df == 18 {
-                        return Some(test_bytes);
-                    }
+                if self.is_correctable(&test_bytes) {
+                    return Some((test_bytes, 2));
                 }
             }
         }
@@ -516,10 +542,21 @@ impl ModeS {
         None
     }
 
-    /// Verify CRC-24 checksum
-    fn verify_crc(&self, data: &[u8]) -> bool {
+    /// Verify CRC-24 checksum, returning the sender's address
+    fn verify_crc(&self, data: &[u8]) -> Option<u32> {
         // Use the same CRC from our adsb module
-        crate::adsb::verify_crc(data)
+        crate::adsb::verify_crc(data, &self.trust)
+    }
+
+    /// Whether a frame with flipped bits passes: a self-checking format
+    /// (DF11, 17 or 18) from a trusted address
+    fn is_correctable(&self, data: &[u8]) -> bool {
+        let df = (data[0] >> 3) & 0x1F;
+        if df != 11 && df != 17 && df != 18 {
+            return false;
+        }
+        self.verify_crc(data)
+            .is_some_and(|icao| self.trust.is_trusted(icao, Instant::now()))
     }
 
     /// Get current statistics
@@ -548,3 +585,12 @@ impl Default for ModeS {
         Self::new()
     }
 }
+
+/// Mean bit confidence relative to the preamble signal level, capped at 1
+fn bit_confidence(confidence: &[i32], signal_level: u32) -> f32 {
+    if confidence.is_empty() || signal_level == 0 {
+        return 0.0;
+    }
+    let mean = confidence.iter().map(|&c| c as f32).sum::<f32>() / confidence.len() as f32;
+    (mean / signal_level as f32).min(1.0)
+}
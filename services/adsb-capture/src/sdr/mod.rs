@@ -7,10 +7,14 @@
 //! 4. Extract and decode frames
 //! 5. Verify CRC-24
 
+mod blanker;
 pub mod capture;
 mod demod;
 mod detect;
+mod scan;
 
+pub use blanker::NoiseBlanker;
 pub use capture::{query_device_serial, query_device_info, SdrCapture, SdrConfig};
 pub use demod::MagnitudeTable;
-pub use detect::{DetectorStats, Frame};
+pub use detect::{DetectorStats, Frame, ModeS};
+pub use scan::{capture_burst, ScanPoint};
@@ -0,0 +1,202 @@
+//! Frequency scan diagnostics
+//!
+//! Steps the tuner across the band around 1090 MHz and measures the noise
+//! floor and the strongest narrowband spur at each step. A low, flat floor
+//! everywhere with no aircraft suggests a dead antenna or cable; a raised
+//! floor or strong spurs near 1090 MHz point at local interference.
+
+use anyhow::{bail, Context, Result};
+use std::f32::consts::PI;
+use std::io::Read;
+use std::process::{Command, Stdio};
+use tracing::info;
+
+use super::capture::SdrConfig;
+
+/// DFT length used for the spectrum estimate
+const FFT_SIZE: usize = 256;
+
+/// Samples analysed per frequency
+const SCAN_SAMPLES: usize = FFT_SIZE * 64;
+
+/// Samples dropped after retuning while the tuner settles
+const SETTLE_SAMPLES: usize = 32 * 1024;
+
+/// Bins either side of DC ignored (the RTL-SDR's DC offset spike)
+const DC_BINS: usize = 2;
+
+/// Measurements at one tuner frequency
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScanPoint {
+    pub frequency_hz: u32,
+    /// Median spectral power, scaled to the full band
+    pub noise_dbfs: f32,
+    /// Strongest sample magnitude
+    pub peak_dbfs: f32,
+    /// Strongest narrowband component above the noise floor
+    pub spur_db: f32,
+    /// Where that component sits relative to `frequency_hz`
+    pub spur_offset_hz: i32,
+}
+
+/// Frequencies from `center - span` to `center + span` in `step` increments
+pub fn scan_frequencies(center_hz: u32, span_hz: u32, step_hz: u32) -> Vec<u32> {
+    if step_hz == 0 {
+        return vec![center_hz];
+    }
+    let steps = (span_hz / step_hz) as i64;
+    (-steps..=steps)
+        .map(|i| (center_hz as i64 + i * step_hz as i64) as u32)
+        .collect()
+}
+
+/// Capture a short burst at each frequency and measure it
+pub fn run_scan(config: &SdrConfig, span_hz: u32, step_hz: u32) -> Result<Vec<ScanPoint>> {
+    let mut points = Vec::new();
+    for frequency_hz in scan_frequencies(config.center_freq, span_hz, step_hz) {
+        let iq = capture_burst(config, frequency_hz, SCAN_SAMPLES)?;
+        let point = analyze(&iq, frequency_hz, config.sample_rate);
+        info!(
+            "[Scan] {:.3} MHz: noise {:.1} dBFS, peak {:.1} dBFS, spur {:.1} dB at {:+} kHz",
+            frequency_hz as f64 / 1e6,
+            point.noise_dbfs,
+            point.peak_dbfs,
+            point.spur_db,
+            point.spur_offset_hz / 1000
+        );
+        points.push(point);
+    }
+    Ok(points)
+}
+
+/// Read `samples` IQ samples at `frequency_hz` from a one-shot rtl_sdr,
+/// after letting the tuner settle
+pub fn capture_burst(config: &SdrConfig, frequency_hz: u32, samples: usize) -> Result<Vec<u8>> {
+    let mut cmd = Command::new(&config.rtl_sdr_path);
+    cmd.arg("-d").arg(config.device_index.to_string())
+       .arg("-f").arg(frequency_hz.to_string())
+       .arg("-s").arg(config.sample_rate.to_string())
+       .arg("-g").arg((config.gain as f32 / 10.0).to_string())
+       .arg("-n").arg((SETTLE_SAMPLES + samples).to_string());
+
+    if config.ppm_error != 0 {
+        cmd.arg("-p").arg(config.ppm_error.to_string());
+    }
+
+    cmd.arg("-")
+       .stdout(Stdio::piped())
+       .stderr(Stdio::null());
+
+    let mut child = cmd.spawn()
+        .context("Failed to spawn rtl_sdr")?;
+    let mut iq = Vec::with_capacity((SETTLE_SAMPLES + samples) * 2);
+    child.stdout.take()
+        .context("Failed to capture rtl_sdr stdout")?
+        .read_to_end(&mut iq)?;
+    let _ = child.wait();
+
+    if iq.len() < (SETTLE_SAMPLES + FFT_SIZE) * 2 {
+        bail!("rtl_sdr returned only {} bytes at {} Hz", iq.len(), frequency_hz);
+    }
+    Ok(iq.split_off(SETTLE_SAMPLES * 2))
+}
+
+/// Measure noise floor, peak and strongest spur in a block of 8-bit IQ
+pub fn analyze(iq: &[u8], frequency_hz: u32, sample_rate: u32) -> ScanPoint {
+    // One cycle of twiddle factors, indexed by (k * n) mod FFT_SIZE
+    let twiddles: Vec<(f32, f32)> = (0..FFT_SIZE)
+        .map(|i| {
+            let angle = -2.0 * PI * i as f32 / FFT_SIZE as f32;
+            (angle.cos(), angle.sin())
+        })
+        .collect();
+
+    let mut power = vec![0f32; FFT_SIZE];
+    let mut blocks = 0;
+    let mut peak = 0f32;
+    let mut block = vec![(0f32, 0f32); FFT_SIZE];
+
+    for chunk in iq.chunks_exact(FFT_SIZE * 2).take(SCAN_SAMPLES / FFT_SIZE) {
+        for (n, pair) in chunk.chunks_exact(2).enumerate() {
+            let i = (pair[0] as f32 - 127.5) / 127.5;
+            let q = (pair[1] as f32 - 127.5) / 127.5;
+            peak = peak.max((i * i + q * q).sqrt());
+            block[n] = (i, q);
+        }
+        for (k, bin) in power.iter_mut().enumerate() {
+            let (mut re, mut im) = (0f32, 0f32);
+            for (n, &(i, q)) in block.iter().enumerate() {
+                let (c, s) = twiddles[(k * n) % FFT_SIZE];
+                re += i * c - q * s;
+                im += i * s + q * c;
+            }
+            *bin += (re * re + im * im) / (FFT_SIZE * FFT_SIZE) as f32;
+        }
+        blocks += 1;
+    }
+
+    // Bins ordered by offset from the tuned frequency, DC excluded
+    let bins: Vec<(i32, f32)> = (0..FFT_SIZE)
+        .filter(|&k| k > DC_BINS && k < FFT_SIZE - DC_BINS)
+        .map(|k| {
+            let offset = if k < FFT_SIZE / 2 { k as i64 } else { k as i64 - FFT_SIZE as i64 };
+            let offset_hz = offset * sample_rate as i64 / FFT_SIZE as i64;
+            (offset_hz as i32, power[k] / blocks.max(1) as f32)
+        })
+        .collect();
+
+    let mut sorted: Vec<f32> = bins.iter().map(|&(_, p)| p).collect();
+    sorted.sort_by(f32::total_cmp);
+    let median = sorted[sorted.len() / 2].max(f32::MIN_POSITIVE);
+    let (spur_offset_hz, spur) = bins
+        .iter()
+        .copied()
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .unwrap_or((0, median));
+
+    ScanPoint {
+        frequency_hz,
+        noise_dbfs: 10.0 * (median * FFT_SIZE as f32).log10(),
+        peak_dbfs: 20.0 * peak.max(1e-3).log10(),
+        spur_db: 10.0 * (spur / median).log10(),
+        spur_offset_hz,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scan_frequencies() {
+        assert_eq!(
+            scan_frequencies(1_090_000_000, 1_000_000, 500_000),
+            vec![1_089_000_000, 1_089_500_000, 1_090_000_000, 1_090_500_000, 1_091_000_000]
+        );
+        assert_eq!(scan_frequencies(1_090_000_000, 1_000_000, 0), vec![1_090_000_000]);
+    }
+
+    #[test]
+    fn test_analyze_finds_spur() {
+        // Weak pseudo-random noise with a tone 250 kHz above the tuned frequency
+        let mut seed = 1u32;
+        let mut noise = || {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            ((seed >> 16) % 9) as f32 - 4.0
+        };
+        let iq: Vec<u8> = (0..SCAN_SAMPLES)
+            .flat_map(|n| {
+                let phase = 2.0 * PI * 250_000.0 * n as f32 / 2_000_000.0;
+                let i = 127.5 + 40.0 * phase.cos() + noise();
+                let q = 127.5 + 40.0 * phase.sin() + noise();
+                [i as u8, q as u8]
+            })
+            .collect();
+
+        let point = analyze(&iq, 1_090_000_000, 2_000_000);
+        assert_eq!(point.spur_offset_hz, 250_000);
+        assert!(point.spur_db > 20.0, "spur {:.1} dB", point.spur_db);
+        assert!(point.noise_dbfs < -20.0, "noise {:.1} dBFS", point.noise_dbfs);
+        assert!(point.peak_dbfs < 0.0);
+    }
+}
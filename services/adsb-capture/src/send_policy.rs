@@ -0,0 +1,156 @@
+//! Which tracker updates become aircraft events
+//!
+//! A busy aircraft is heard a dozen times a second, and most of those
+//! frames (velocity, surveillance replies, repeated identification) leave
+//! what the gateway shows unchanged. With `EVENT_SEND_POLICY=changed` (the
+//! default) an event is only sent when the aircraft's state changed
+//! materially since the last one: a new position, an altitude change of at
+//! least `EVENT_MIN_ALT_CHANGE_FT` (default 100), or a new callsign, squawk,
+//! emergency or category. Every `EVENT_KEEPALIVE_SECS` (default 10) an
+//! aircraft gets an event regardless, carrying its latest velocity and
+//! keeping it from timing out. `EVENT_SEND_POLICY=all` sends an event for
+//! every frame, as before.
+
+use crate::adsb::EmergencySquawk;
+use crate::aircraft_tracker::AircraftState;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// When events are sent
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SendMode {
+    /// On material changes and keepalives
+    #[default]
+    Changed,
+    /// For every frame
+    All,
+}
+
+impl SendMode {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "changed" => Some(Self::Changed),
+            "all" => Some(Self::All),
+            _ => None,
+        }
+    }
+}
+
+/// What the last event sent for an aircraft carried
+#[derive(Debug)]
+struct Sent {
+    position_messages: u64,
+    altitude_ft: Option<i32>,
+    callsign: Option<String>,
+    squawk: Option<u16>,
+    emergency: Option<EmergencySquawk>,
+    category: Option<String>,
+    at: Instant,
+}
+
+impl Sent {
+    fn of(state: &AircraftState, now: Instant) -> Self {
+        Self {
+            position_messages: state.position_messages,
+            altitude_ft: state.altitude_ft,
+            callsign: state.callsign.clone(),
+            squawk: state.squawk,
+            emergency: state.emergency,
+            category: state.category.clone(),
+            at: now,
+        }
+    }
+}
+
+/// Last event per aircraft, deciding whether the next one is worth sending
+#[derive(Debug)]
+pub struct SendPolicy {
+    mode: SendMode,
+    min_altitude_change_ft: i32,
+    keepalive: Duration,
+    sent: HashMap<u32, Sent>,
+    /// Updates not sent because nothing material changed
+    pub unchanged: u64,
+}
+
+impl SendPolicy {
+    pub fn new(mode: SendMode, min_altitude_change_ft: i32, keepalive: Duration) -> Self {
+        Self {
+            mode,
+            min_altitude_change_ft,
+            keepalive,
+            sent: HashMap::new(),
+            unchanged: 0,
+        }
+    }
+
+    /// Whether to send an event for `state`; one that is sent becomes the
+    /// baseline for the next
+    pub fn should_send(&mut self, state: &AircraftState, now: Instant) -> bool {
+        if self.mode == SendMode::All {
+            return true;
+        }
+        let changed = match self.sent.get(&state.icao) {
+            None => true,
+            Some(last) => {
+                state.position_messages != last.position_messages
+                    || match (state.altitude_ft, last.altitude_ft) {
+                        (Some(now), Some(then)) => (now - then).abs() >= self.min_altitude_change_ft,
+                        (now, then) => now != then,
+                    }
+                    || state.callsign != last.callsign
+                    || state.squawk != last.squawk
+                    || state.emergency != last.emergency
+                    || state.category != last.category
+                    || now.duration_since(last.at) >= self.keepalive
+            }
+        };
+        if changed {
+            self.sent.insert(state.icao, Sent::of(state, now));
+        } else {
+            self.unchanged += 1;
+        }
+        changed
+    }
+
+    /// Forget an aircraft the tracker timed out
+    pub fn forget(&mut self, icao: u32) {
+        self.sent.remove(&icao);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_changed_fields_only() {
+        let mut policy = SendPolicy::new(SendMode::Changed, 100, Duration::from_secs(10));
+        let t = Instant::now();
+        let mut state = AircraftState::new(0x71BE11);
+        state.altitude_ft = Some(35000);
+        assert!(policy.should_send(&state, t));
+
+        // Velocity only, and a climb below the threshold
+        state.ground_speed_kts = Some(450.0);
+        assert!(!policy.should_send(&state, t));
+        state.altitude_ft = Some(35075);
+        assert!(!policy.should_send(&state, t));
+        state.altitude_ft = Some(35100);
+        assert!(policy.should_send(&state, t));
+
+        state.position_messages += 1;
+        assert!(policy.should_send(&state, t));
+        state.callsign = Some("KAL123".into());
+        assert!(policy.should_send(&state, t));
+        assert!(!policy.should_send(&state, t + Duration::from_secs(9)));
+        assert!(policy.should_send(&state, t + Duration::from_secs(10)));
+        assert_eq!(policy.unchanged, 3);
+
+        policy.forget(0x71BE11);
+        assert!(policy.should_send(&state, t + Duration::from_secs(10)));
+        let mut all = SendPolicy::new(SendMode::All, 100, Duration::from_secs(10));
+        assert!(all.should_send(&state, t) && all.should_send(&state, t));
+        assert_eq!(SendMode::parse(" ALL "), Some(SendMode::All));
+    }
+}
@@ -0,0 +1,615 @@
+//! CPR (Compact Position Reporting) position decoding
+//!
+//! Airborne positions divide the globe into 360° of latitude zones, so an
+//! even/odd pair gives a unique position. Surface positions spend the same
+//! 17 bits on 90° zones for four times the resolution, so a pair leaves
+//! four candidate longitudes and two latitudes (north and south); the one
+//! nearest the receiver, or failing that the aircraft's last position, is
+//! taken. Surface and airborne coordinates are never paired with each other.
+//!
+//! A decoded position is only accepted if it encodes back to both frames of
+//! its pair, allowing for how far the aircraft can have moved between them.
+//! A frame with a corrupted CPR field otherwise decodes somewhere plausible
+//! enough, and the aircraft appears to teleport.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use super::integrity::OperationalStatus;
+
+/// CPR state for a single aircraft
+#[derive(Debug, Clone)]
+pub struct CprState {
+    /// Last operational status, which says how to read position type codes
+    pub status: Option<OperationalStatus>,
+    /// Even CPR coordinates and timestamp
+    pub even_cpr: Option<(i32, i32, Instant)>,
+    /// Odd CPR coordinates and timestamp
+    pub odd_cpr: Option<(i32, i32, Instant)>,
+    /// Surface position coordinates, kept apart from the airborne ones
+    pub surface_even_cpr: Option<(i32, i32, Instant)>,
+    pub surface_odd_cpr: Option<(i32, i32, Instant)>,
+    /// Last decoded position
+    pub last_position: Option<(f64, f64)>,
+}
+
+impl Default for CprState {
+    fn default() -> Self {
+        Self {
+            status: None,
+            even_cpr: None,
+            odd_cpr: None,
+            surface_even_cpr: None,
+            surface_odd_cpr: None,
+            last_position: None,
+        }
+    }
+}
+
+/// Context for CPR decoding across multiple aircraft
+pub struct CprContext {
+    states: HashMap<u32, CprState>,
+    max_aircraft: usize,
+    /// Receiver position (latitude, longitude), for surface positions
+    receiver: Option<(f64, f64)>,
+}
+
+impl CprContext {
+    pub fn new(max_aircraft: usize) -> Self {
+        Self {
+            states: HashMap::with_capacity(max_aircraft),
+            max_aircraft,
+            receiver: None,
+        }
+    }
+
+    /// Where the receiver is, to pick among surface position candidates
+    pub fn set_receiver(&mut self, receiver: Option<(f64, f64)>) {
+        self.receiver = receiver;
+    }
+
+    /// Get or create CPR state for an aircraft
+    pub fn get_or_create(&mut self, icao: u32) -> &mut CprState {
+        // Evict oldest if at capacity
+        if self.states.len() >= self.max_aircraft && !self.states.contains_key(&icao) {
+            // Simple eviction: remove first entry
+            if let Some(&first_key) = self.states.keys().next() {
+                self.states.remove(&first_key);
+            }
+        }
+
+        self.states.entry(icao).or_default()
+    }
+
+    /// Remember an aircraft's operational status
+    pub fn set_status(&mut self, icao: u32, status: OperationalStatus) {
+        self.get_or_create(icao).status = Some(status);
+    }
+
+    /// An aircraft's last operational status, if one has been received
+    pub fn status(&self, icao: u32) -> Option<&OperationalStatus> {
+        self.states.get(&icao)?.status.as_ref()
+    }
+
+    /// Update CPR data and attempt position decode
+    pub fn update(
+        &mut self,
+        icao: u32,
+        lat_cpr: i32,
+        lon_cpr: i32,
+        odd_flag: bool,
+    ) -> Option<(f64, f64)> {
+        let state = self.get_or_create(icao);
+        let now = Instant::now();
+
+        if odd_flag {
+            state.odd_cpr = Some((lat_cpr, lon_cpr, now));
+        } else {
+            state.even_cpr = Some((lat_cpr, lon_cpr, now));
+        }
+
+        // Try global decoding
+        decode_global(state, odd_flag)
+    }
+
+    /// Update surface CPR data and attempt position decode, which needs the
+    /// receiver position or an earlier position of the aircraft
+    pub fn update_surface(
+        &mut self,
+        icao: u32,
+        lat_cpr: i32,
+        lon_cpr: i32,
+        odd_flag: bool,
+    ) -> Option<(f64, f64)> {
+        let receiver = self.receiver;
+        let state = self.get_or_create(icao);
+        let now = Instant::now();
+
+        if odd_flag {
+            state.surface_odd_cpr = Some((lat_cpr, lon_cpr, now));
+        } else {
+            state.surface_even_cpr = Some((lat_cpr, lon_cpr, now));
+        }
+
+        let reference = receiver.or(state.last_position)?;
+        decode_surface(state, odd_flag, reference)
+    }
+}
+
+/// NL (Number of Longitude zones) lookup function
+/// Returns the number of longitude zones at a given latitude; 87° itself
+/// still has two
+fn cpr_nl(lat: f64) -> i32 {
+    let lat = lat.abs();
+
+    if lat < 10.47047130 { return 59; }
+    if lat < 14.82817437 { return 58; }
+    if lat < 18.18626357 { return 57; }
+    if lat < 21.02939493 { return 56; }
+    if lat < 23.54504487 { return 55; }
+    if lat < 25.82924707 { return 54; }
+    if lat < 27.93898710 { return 53; }
+    if lat < 29.91135686 { return 52; }
+    if lat < 31.77209708 { return 51; }
+    if lat < 33.53993436 { return 50; }
+    if lat < 35.22899598 { return 49; }
+    if lat < 36.85025108 { return 48; }
+    if lat < 38.41241892 { return 47; }
+    if lat < 39.92256684 { return 46; }
+    if lat < 41.38651832 { return 45; }
+    if lat < 42.80914012 { return 44; }
+    if lat < 44.19454951 { return 43; }
+    if lat < 45.54626723 { return 42; }
+    if lat < 46.86733252 { return 41; }
+    if lat < 48.16039128 { return 40; }
+    if lat < 49.42776439 { return 39; }
+    if lat < 50.67150166 { return 38; }
+    if lat < 51.89342469 { return 37; }
+    if lat < 53.09516153 { return 36; }
+    if lat < 54.27817472 { return 35; }
+    if lat < 55.44378444 { return 34; }
+    if lat < 56.59318756 { return 33; }
+    if lat < 57.72747354 { return 32; }
+    if lat < 58.84763776 { return 31; }
+    if lat < 59.95459277 { return 30; }
+    if lat < 61.04917774 { return 29; }
+    if lat < 62.13216659 { return 28; }
+    if lat < 63.20427479 { return 27; }
+    if lat < 64.26616523 { return 26; }
+    if lat < 65.31845310 { return 25; }
+    if lat < 66.36171008 { return 24; }
+    if lat < 67.39646774 { return 23; }
+    if lat < 68.42322022 { return 22; }
+    if lat < 69.44242631 { return 21; }
+    if lat < 70.45451075 { return 20; }
+    if lat < 71.45986473 { return 19; }
+    if lat < 72.45884545 { return 18; }
+    if lat < 73.45177442 { return 17; }
+    if lat < 74.43893416 { return 16; }
+    if lat < 75.42056257 { return 15; }
+    if lat < 76.39684391 { return 14; }
+    if lat < 77.36789461 { return 13; }
+    if lat < 78.33374083 { return 12; }
+    if lat < 79.29428225 { return 11; }
+    if lat < 80.24923213 { return 10; }
+    if lat < 81.19801349 { return 9; }
+    if lat < 82.13956981 { return 8; }
+    if lat < 83.07199445 { return 7; }
+    if lat < 83.99173563 { return 6; }
+    if lat < 84.89166191 { return 5; }
+    if lat < 85.75541621 { return 4; }
+    if lat < 86.53536998 { return 3; }
+    if lat <= 87.00000000 { return 2; }
+    1
+}
+
+/// Decode CPR position using global decoding
+/// Requires both even and odd messages within 10 seconds
+fn decode_global(state: &mut CprState, odd_flag: bool) -> Option<(f64, f64)> {
+    let (even_lat, even_lon, even_time) = state.even_cpr?;
+    let (odd_lat, odd_lon, odd_time) = state.odd_cpr?;
+
+    // Check time validity (10 seconds max between even/odd)
+    let time_diff = if odd_flag {
+        even_time.elapsed()
+    } else {
+        odd_time.elapsed()
+    };
+
+    if time_diff.as_secs() > 10 {
+        return None;
+    }
+
+    let (even, odd) = ((even_lat, even_lon), (odd_lat, odd_lon));
+    let position = global_position(even, odd, odd_flag)?;
+    let elapsed = even_time.max(odd_time) - even_time.min(odd_time);
+    if !cross_check(position, even, odd, odd_flag, false, elapsed) {
+        return None;
+    }
+
+    // Save for future local decoding
+    state.last_position = Some(position);
+
+    Some(position)
+}
+
+/// Largest difference, in CPR units, between a frame and its re-encoded
+/// position that is put down to rounding
+const CROSS_CHECK_ROUNDING: i32 = 2;
+
+/// Fastest an aircraft is taken to move between an even and an odd frame,
+/// m/s: about 1150 kt in the air, 200 kt on the ground
+const MAX_AIRBORNE_SPEED: f64 = 600.0;
+const MAX_SURFACE_SPEED: f64 = 100.0;
+
+/// Encode a position as 17-bit CPR coordinates, airborne or surface
+fn encode(lat: f64, lon: f64, odd: bool, surface: bool) -> (i32, i32) {
+    let scale = 131072.0;
+    let span = if surface { 90.0 } else { 360.0 };
+    let dlat = span / if odd { 59.0 } else { 60.0 };
+    let yz = (scale * lat.rem_euclid(dlat) / dlat + 0.5).floor();
+    let rlat = dlat * (yz / scale + (lat / dlat).floor());
+    let ni = (cpr_nl(rlat) - odd as i32).max(1);
+    let dlon = span / ni as f64;
+    let xz = (scale * lon.rem_euclid(dlon) / dlon + 0.5).floor();
+    (yz as i32 % 131072, xz as i32 % 131072)
+}
+
+/// Whether a decoded position encodes back to the pair it came from: the
+/// latest frame to within rounding, the earlier one to within how far the
+/// aircraft can have moved in the `elapsed` between them. A corrupted CPR
+/// field that still passes the zone check decodes to a position some
+/// kilometres or zones away, and this is where it shows
+fn cross_check(
+    position: (f64, f64),
+    even: (i32, i32),
+    odd: (i32, i32),
+    odd_latest: bool,
+    surface: bool,
+    elapsed: Duration,
+) -> bool {
+    let (span, speed) = if surface {
+        (90.0, MAX_SURFACE_SPEED)
+    } else {
+        (360.0, MAX_AIRBORNE_SPEED)
+    };
+    // An even latitude unit is the smallest of the four, about 5 m (1.3 m
+    // on the surface); longitude zones are never narrower on the ground
+    let unit_m = span / 60.0 * 111_195.0 / 131072.0;
+    let movement = (elapsed.as_secs_f64() * speed / unit_m).ceil() as i32;
+
+    let distance = |a: i32, b: i32| {
+        let d = (a - b).rem_euclid(131072);
+        d.min(131072 - d)
+    };
+    let matches = |received: (i32, i32), odd: bool, tolerance: i32| {
+        let (lat, lon) = encode(position.0, position.1, odd, surface);
+        distance(lat, received.0) <= tolerance && distance(lon, received.1) <= tolerance
+    };
+    let (latest, earlier) = if odd_latest { (odd, even) } else { (even, odd) };
+    matches(latest, odd_latest, CROSS_CHECK_ROUNDING)
+        && matches(earlier, !odd_latest, CROSS_CHECK_ROUNDING + movement)
+}
+
+/// Decode a surface position from the last even/odd pair, which may be up
+/// to 50 seconds apart since aircraft on the ground move slowly
+fn decode_surface(state: &mut CprState, odd_flag: bool, reference: (f64, f64)) -> Option<(f64, f64)> {
+    let (even_lat, even_lon, even_time) = state.surface_even_cpr?;
+    let (odd_lat, odd_lon, odd_time) = state.surface_odd_cpr?;
+
+    let time_diff = if odd_flag {
+        even_time.elapsed()
+    } else {
+        odd_time.elapsed()
+    };
+    if time_diff.as_secs() > 50 {
+        return None;
+    }
+
+    let (even, odd) = ((even_lat, even_lon), (odd_lat, odd_lon));
+    let position = surface_position(even, odd, odd_flag, reference)?;
+    let elapsed = even_time.max(odd_time) - even_time.min(odd_time);
+    if !cross_check(position, even, odd, odd_flag, true, elapsed) {
+        return None;
+    }
+    state.last_position = Some(position);
+    Some(position)
+}
+
+/// Surface position from an even and an odd pair of 17-bit CPR
+/// coordinates, in the zone of whichever was received last, choosing the
+/// hemisphere and 90° longitude quadrant nearest `reference`
+fn surface_position(
+    even: (i32, i32),
+    odd: (i32, i32),
+    odd_latest: bool,
+    reference: (f64, f64),
+) -> Option<(f64, f64)> {
+    let lat_cpr_even = even.0 as f64 / 131072.0;
+    let lon_cpr_even = even.1 as f64 / 131072.0;
+    let lat_cpr_odd = odd.0 as f64 / 131072.0;
+    let lon_cpr_odd = odd.1 as f64 / 131072.0;
+
+    // Latitude zones a quarter of the airborne size
+    let dlat_even = 90.0 / 60.0;
+    let dlat_odd = 90.0 / 59.0;
+
+    let j = (59.0 * lat_cpr_even - 60.0 * lat_cpr_odd + 0.5).floor() as i32;
+
+    // Both solutions are in 0-90; the southern one is 90° lower
+    let hemisphere = |lat: f64| {
+        if (lat - 90.0 - reference.0).abs() < (lat - reference.0).abs() {
+            lat - 90.0
+        } else {
+            lat
+        }
+    };
+    let lat_even = hemisphere(dlat_even * (j.rem_euclid(60) as f64 + lat_cpr_even));
+    let lat_odd = hemisphere(dlat_odd * (j.rem_euclid(59) as f64 + lat_cpr_odd));
+
+    let nl = cpr_nl(lat_even);
+    if nl != cpr_nl(lat_odd) {
+        return None;
+    }
+
+    let (lat, ni, lon_cpr) = if odd_latest {
+        (lat_odd, (nl - 1).max(1), lon_cpr_odd)
+    } else {
+        (lat_even, nl.max(1), lon_cpr_even)
+    };
+    let dlon = 90.0 / ni as f64;
+
+    let m = (lon_cpr_even * (nl - 1) as f64 - lon_cpr_odd * nl as f64 + 0.5).floor() as i32;
+    let lon = dlon * (m.rem_euclid(ni) as f64 + lon_cpr);
+
+    // The longitude repeats every 90°: take the quadrant nearest the reference
+    let distance = |lon: f64| ((lon - reference.1 + 540.0).rem_euclid(360.0) - 180.0).abs();
+    let lon = (0..4)
+        .map(|quadrant| (lon + 90.0 * quadrant as f64 + 180.0).rem_euclid(360.0) - 180.0)
+        .min_by(|a, b| distance(*a).total_cmp(&distance(*b)))?;
+
+    Some((lat, lon))
+}
+
+/// Airborne position from an even and an odd pair of 17-bit CPR
+/// coordinates, in the zone of whichever was received last
+fn global_position(even: (i32, i32), odd: (i32, i32), odd_latest: bool) -> Option<(f64, f64)> {
+    // CPR decoding algorithm
+    let lat_cpr_even = even.0 as f64 / 131072.0;
+    let lon_cpr_even = even.1 as f64 / 131072.0;
+    let lat_cpr_odd = odd.0 as f64 / 131072.0;
+    let lon_cpr_odd = odd.1 as f64 / 131072.0;
+
+    // Latitude zone sizes
+    let dlat_even = 360.0 / 60.0;
+    let dlat_odd = 360.0 / 59.0;
+
+    // Compute latitude index. It is negative for much of the northern
+    // hemisphere, so zone numbers need a modulo that stays positive;
+    // `%` would put those positions in the wrong hemisphere
+    let j = (59.0 * lat_cpr_even - 60.0 * lat_cpr_odd + 0.5).floor() as i32;
+
+    let mut lat_even = dlat_even * (j.rem_euclid(60) as f64 + lat_cpr_even);
+    let mut lat_odd = dlat_odd * (j.rem_euclid(59) as f64 + lat_cpr_odd);
+
+    if lat_even >= 270.0 {
+        lat_even -= 360.0;
+    }
+    if lat_odd >= 270.0 {
+        lat_odd -= 360.0;
+    }
+
+    // Check latitude zone consistency
+    let nl = cpr_nl(lat_even);
+    if nl != cpr_nl(lat_odd) {
+        return None; // Different zones, can't decode
+    }
+
+    // Longitude zones in the last message's format
+    let (lat, ni, lon_cpr) = if odd_latest {
+        (lat_odd, (nl - 1).max(1), lon_cpr_odd)
+    } else {
+        (lat_even, nl.max(1), lon_cpr_even)
+    };
+    let dlon = 360.0 / ni as f64;
+
+    let m = (lon_cpr_even * (nl - 1) as f64 - lon_cpr_odd * nl as f64 + 0.5).floor() as i32;
+    let lon = dlon * (m.rem_euclid(ni) as f64 + lon_cpr);
+
+    // Normalize longitude to -180..180
+    let lon = if lon >= 180.0 { lon - 360.0 } else { lon };
+
+    // Validate result
+    if !(-90.0..=90.0).contains(&lat) || !(-180.0..180.0).contains(&lon) {
+        return None;
+    }
+
+    Some((lat, lon))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_message, IcaoTrust};
+
+    /// Move the frames `icao` has sent back in time, as if they had arrived
+    /// `secs` earlier
+    fn backdate(ctx: &mut CprContext, icao: u32, secs: u64) {
+        let state = ctx.get_or_create(icao);
+        for frame in [
+            &mut state.even_cpr,
+            &mut state.odd_cpr,
+            &mut state.surface_even_cpr,
+            &mut state.surface_odd_cpr,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            frame.2 -= Duration::from_secs(secs);
+        }
+    }
+
+    /// Difference between two longitudes, across the antimeridian
+    fn lon_error(a: f64, b: f64) -> f64 {
+        ((a - b + 540.0).rem_euclid(360.0) - 180.0).abs()
+    }
+
+    #[test]
+    fn test_cpr_nl() {
+        assert_eq!(cpr_nl(0.0), 59);
+        assert_eq!(cpr_nl(45.0), 42);
+        assert_eq!(cpr_nl(87.0), 2);
+
+        // Either side of zone boundaries, and the poles
+        assert_eq!(cpr_nl(10.4704712), 59);
+        assert_eq!(cpr_nl(10.4704714), 58);
+        assert_eq!(cpr_nl(-52.0), 36);
+        assert_eq!(cpr_nl(87.0001), 1);
+        assert_eq!(cpr_nl(-90.0), 1);
+    }
+
+    #[test]
+    fn test_global_reference() {
+        // "The 1090 Megahertz Riddle", airborne position example: the even
+        // message arrived last
+        let even = hex::decode("8D40621D58C382D690C8AC2863A7").unwrap();
+        let odd = hex::decode("8D40621D58C386435CC412692AD6").unwrap();
+        let mut ctx = CprContext::new(256);
+        let trust = IcaoTrust::new();
+        assert_eq!(parse_message(&odd, &mut ctx, &trust).unwrap().latitude, None);
+        // The aircraft moved 1.5 km between the two
+        backdate(&mut ctx, 0x40621D, 5);
+        let aircraft = parse_message(&even, &mut ctx, &trust).unwrap();
+        let (lat, lon) = (aircraft.latitude.unwrap(), aircraft.longitude.unwrap());
+        assert!((lat - 52.25720).abs() < 1e-5, "{}", lat);
+        assert!((lon - 3.91937).abs() < 1e-5, "{}", lon);
+
+        // The same pair from its CPR fields, in either order
+        let (even, odd) = ((93000, 51372), (74158, 50194));
+        let (lat, lon) = global_position(even, odd, false).unwrap();
+        assert!((lat - 52.25720).abs() < 1e-5 && (lon - 3.91937).abs() < 1e-5);
+        let (lat, lon) = global_position(even, odd, true).unwrap();
+        assert!((lat - 52.26578).abs() < 1e-5, "{}", lat);
+        assert!((lon - 3.93891).abs() < 1e-5, "{}", lon);
+    }
+
+    #[test]
+    fn test_hemispheres() {
+        // Positions whose latitude or longitude index comes out negative
+        for (lat, lon) in [(30.3, 100.0), (37.46, 126.44), (-33.95, 151.18), (40.64, -73.78), (-22.81, -43.25)] {
+            for odd_latest in [false, true] {
+                let (dlat, dlon) = global_position(encode(lat, lon, false, false), encode(lat, lon, true, false), odd_latest)
+                    .map(|(d_lat, d_lon)| (d_lat - lat, lon_error(d_lon, lon)))
+                    .unwrap_or_else(|| panic!("({}, {}) didn't decode", lat, lon));
+                assert!(dlat.abs() < 1e-4 && dlon < 1e-3, "({}, {}) off by ({}, {})", lat, lon, dlat, dlon);
+            }
+        }
+    }
+
+    #[test]
+    fn test_latitude_zone_boundary() {
+        // Even and odd messages either side of the NL 59/58 boundary
+        // can't be combined
+        let even = encode(10.46, 20.0, false, false);
+        let odd = encode(10.48, 20.0, true, false);
+        assert_eq!(global_position(even, odd, false), None);
+        assert_eq!(global_position(even, odd, true), None);
+
+        // Both just inside NL 58 decode
+        let (lat, _) = global_position(encode(10.48, 20.0, false, false), odd, true).unwrap();
+        assert!((lat - 10.48).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_longitude_wrap() {
+        for lon in [179.999, -179.999, -180.0, 0.0001, -0.0001] {
+            let (even, odd) = (encode(-16.5, lon, false, false), encode(-16.5, lon, true, false));
+            for odd_latest in [false, true] {
+                let (_, decoded) = global_position(even, odd, odd_latest).unwrap();
+                assert!((-180.0..180.0).contains(&decoded), "{}", decoded);
+                assert!(lon_error(decoded, lon) < 1e-3, "{} decoded as {}", lon, decoded);
+            }
+        }
+    }
+
+    #[test]
+    fn test_cross_check() {
+        let second = Duration::from_secs(1);
+
+        // Frames from positions 100 km apart, as when a bit error in one
+        // still passes the CRC, decode to a position 5000 km off that
+        // re-encodes close to both, but not close enough for one second
+        let even = encode(52.0, 4.0, false, false);
+        let odd = encode(52.9, 4.0, true, false);
+        let position = global_position(even, odd, true).unwrap();
+        assert!(position.0 < 0.0);
+        assert!(!cross_check(position, even, odd, true, false, second));
+
+        // An aircraft that moved 300 m between frames checks out
+        let odd = encode(52.002, 4.003, true, false);
+        let position = global_position(even, odd, true).unwrap();
+        assert!(cross_check(position, even, odd, true, false, second));
+        assert!(!cross_check(position, even, odd, true, false, Duration::ZERO));
+
+        // The context applies it: a corrupted frame right after a good one
+        let mut ctx = CprContext::new(16);
+        ctx.update(0x4840D6, even.0, even.1, false);
+        let corrupt = encode(52.2, 4.0, true, false);
+        assert_eq!(ctx.update(0x4840D6, corrupt.0, corrupt.1, true), None);
+        let odd = encode(52.0, 4.0, true, false);
+        assert!(ctx.update(0x4840D6, odd.0, odd.1, true).is_some());
+    }
+
+    #[test]
+    fn test_surface_reference() {
+        // "The 1090 Megahertz Riddle", surface position example, with the
+        // receiver near Delft; the odd message arrived last
+        let (even, odd) = ((115609, 116941), (39199, 110269));
+        let (lat, lon) = surface_position(even, odd, true, (51.990, 4.375)).unwrap();
+        assert!((lat - 52.32061).abs() < 1e-5, "{}", lat);
+        assert!((lon - 4.73473).abs() < 1e-5, "{}", lon);
+
+        // Southern hemisphere and far from the prime meridian
+        // (Christchurch, from pyModeS' tests)
+        let (even, odd) = ((1246, 57074), (64585, 67947));
+        let (lat, lon) = surface_position(even, odd, true, (-43.496, 172.558)).unwrap();
+        assert!((lat - -43.48564).abs() < 1e-5, "{}", lat);
+        assert!((lon - 172.53942).abs() < 1e-5, "{}", lon);
+
+        // Through the context: nothing without a reference position
+        let mut ctx = CprContext::new(16);
+        ctx.update_surface(0x484175, even.0, even.1, false);
+        assert_eq!(ctx.update_surface(0x484175, odd.0, odd.1, true), None);
+        ctx.set_receiver(Some((-43.5, 172.6)));
+        backdate(&mut ctx, 0x484175, 10);
+        let (lat, _) = ctx.update_surface(0x484175, odd.0, odd.1, true).unwrap();
+        assert!((lat - -43.48564).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        // Pseudo-random positions away from the latitude zone boundaries,
+        // where an even/odd pair may legitimately fail to decode
+        let mut seed: u64 = 0x5DEECE66D;
+        let mut next = || {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (seed >> 11) as f64 / (1u64 << 53) as f64
+        };
+        let mut decoded = 0;
+        while decoded < 2000 {
+            let lat = next() * 170.0 - 85.0;
+            let lon = next() * 360.0 - 180.0;
+            if cpr_nl(lat - 0.01) != cpr_nl(lat + 0.01) {
+                continue;
+            }
+            let (even, odd) = (encode(lat, lon, false, false), encode(lat, lon, true, false));
+            for odd_latest in [false, true] {
+                let (d_lat, d_lon) = global_position(even, odd, odd_latest)
+                    .unwrap_or_else(|| panic!("({}, {}) didn't decode", lat, lon));
+                assert!((d_lat - lat).abs() < 1e-4, "({}, {}) decoded lat {}", lat, lon, d_lat);
+                assert!(lon_error(d_lon, lon) < 1e-3, "({}, {}) decoded lon {}", lat, lon, d_lon);
+            }
+            decoded += 1;
+        }
+    }
+}
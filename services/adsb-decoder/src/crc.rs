@@ -0,0 +1,155 @@
+//! CRC-24 checksum validation for Mode S messages
+
+use std::time::Instant;
+
+use super::trust::IcaoTrust;
+
+/// CRC-24 polynomial used in Mode S (0x1FFF409)
+const CRC24_POLY: u32 = 0x1FFF409;
+
+/// Compute CRC-24 checksum over message bytes
+pub fn compute_crc24(msg: &[u8], bits: usize) -> u32 {
+    let bytes = bits / 8;
+    let mut crc: u32 = 0;
+
+    for i in 0..bytes {
+        crc ^= (msg[i] as u32) << 16;
+
+        for _ in 0..8 {
+            if crc & 0x800000 != 0 {
+                crc = (crc << 1) ^ CRC24_POLY;
+            } else {
+                crc <<= 1;
+            }
+        }
+    }
+
+    crc & 0xFFFFFF
+}
+
+/// Check CRC validity of a Mode S message, returning the address it came from
+///
+/// DF11, DF17 and DF18 carry the address in the clear and must leave no CRC
+/// residual, apart from a DF11 all-call reply's interrogator code (see
+/// `df11_overlay`). Surveillance and Comm-B replies (DF0/4/5/16/20/21)
+/// overlay the address on the parity, so the CRC can't tell a corrupted
+/// frame from a real one; they are only accepted from addresses `trust` has
+/// corroborated, as are DF11 replies with an interrogator code. Everything
+/// else is rejected to avoid false positives from noise.
+pub fn check_crc(msg: &[u8], trust: &IcaoTrust) -> Result<u32, ()> {
+    let now = Instant::now();
+    let trusted = |icao| if trust.is_trusted(icao, now) { Ok(icao) } else { Err(()) };
+
+    match (msg.len(), get_df(msg)) {
+        (7, 11) => match df11_overlay(msg) {
+            Some(0) => Ok(get_icao(msg)),
+            Some(_) => trusted(get_icao(msg)),
+            None => Err(()),
+        },
+        (14, 11 | 17 | 18) if compute_crc24(msg, 112) == 0 => Ok(get_icao(msg)),
+        (7, 0 | 4 | 5) | (14, 16 | 20 | 21) => trusted(address_parity(msg)),
+        _ => Err(()),
+    }
+}
+
+/// Address of an address/parity frame: the parity XORed with the CRC of
+/// the rest of the frame
+pub fn address_parity(msg: &[u8]) -> u32 {
+    let bits = msg.len() * 8 - 24;
+    let n = msg.len();
+    let parity = ((msg[n - 3] as u32) << 16) | ((msg[n - 2] as u32) << 8) | (msg[n - 1] as u32);
+    compute_crc24(msg, bits) ^ parity
+}
+
+/// Interrogator code overlaid on a DF11 all-call reply's parity: the CRC
+/// residual, with the code class (CL) in bits 4-6 and the code (IC) in bits
+/// 0-3. `None` when the frame isn't DF11 or the residual can't be an
+/// overlay (any higher bit set, or CL above 4), i.e. the frame is corrupt.
+pub fn df11_overlay(msg: &[u8]) -> Option<u8> {
+    if msg.len() != 7 || get_df(msg) != 11 {
+        return None;
+    }
+    let parity = ((msg[4] as u32) << 16) | ((msg[5] as u32) << 8) | (msg[6] as u32);
+    let residual = compute_crc24(msg, 32) ^ parity;
+    (residual & !0x7F == 0 && residual >> 4 <= 4).then_some(residual as u8)
+}
+
+/// Extract ICAO address from message (bytes 1-3)
+pub fn get_icao(msg: &[u8]) -> u32 {
+    ((msg[1] as u32) << 16) | ((msg[2] as u32) << 8) | (msg[3] as u32)
+}
+
+/// Extract downlink format from message
+pub fn get_df(msg: &[u8]) -> u8 {
+    (msg[0] >> 3) & 0x1F
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crc24() {
+        // Test vector from ADS-B specification
+        let msg = hex::decode("8D4840D6202CC371C32CE0576098").unwrap();
+        let crc = compute_crc24(&msg, 112);
+        assert_eq!(crc, 0); // Valid message should have CRC of 0
+    }
+
+    #[test]
+    fn test_df11_overlay() {
+        // All-call reply from 4840D6 to II 0 (or an acquisition squitter)
+        let mut msg = hex::decode("5D4840D6000000").unwrap();
+        let crc = compute_crc24(&msg, 32);
+        msg[4..].copy_from_slice(&crc.to_be_bytes()[1..]);
+        assert_eq!(df11_overlay(&msg), Some(0));
+        let trust = IcaoTrust::new();
+        assert_eq!(check_crc(&msg, &trust), Ok(0x4840D6));
+
+        // Reply to SI 18: CL 2, IC 2, accepted once the address is trusted
+        msg[6] ^= 0x22;
+        assert_eq!(df11_overlay(&msg), Some(0x22));
+        assert!(check_crc(&msg, &trust).is_err());
+        trust.corroborate(0x4840D6, Instant::now());
+        trust.corroborate(0x4840D6, Instant::now());
+        assert!(check_crc(&msg, &trust).is_ok());
+
+        // Corrupted: residual outside the overlay bits, or CL 5-7
+        msg[5] ^= 0x01;
+        assert_eq!(df11_overlay(&msg), None);
+        assert!(check_crc(&msg, &trust).is_err());
+        msg[5] ^= 0x01;
+        msg[6] ^= 0x70;
+        assert_eq!(df11_overlay(&msg), None);
+    }
+
+    #[test]
+    fn test_address_parity() {
+        // DF4 altitude reply from 71BE11
+        let mut msg = hex::decode("20001838000000").unwrap();
+        let ap = compute_crc24(&msg, 32) ^ 0x71BE11;
+        msg[4..].copy_from_slice(&ap.to_be_bytes()[1..]);
+        assert_eq!(address_parity(&msg), 0x71BE11);
+
+        // Any address passes the CRC, so only trusted ones are accepted
+        let trust = IcaoTrust::new();
+        assert!(check_crc(&msg, &trust).is_err());
+        trust.corroborate(0x71BE11, Instant::now());
+        trust.corroborate(0x71BE11, Instant::now());
+        assert_eq!(check_crc(&msg, &trust), Ok(0x71BE11));
+        msg[3] ^= 0x01;
+        assert!(check_crc(&msg, &trust).is_err());
+    }
+
+    #[test]
+    fn test_get_icao() {
+        let msg = hex::decode("8D4840D6202CC371C32CE0576098").unwrap();
+        assert_eq!(get_icao(&msg), 0x4840D6);
+    }
+
+    #[test]
+    fn test_get_df() {
+        let msg = hex::decode("8D4840D6202CC371C32CE0576098").unwrap();
+        assert_eq!(get_df(&msg), 17); // DF17 = Extended Squitter
+    }
+}
@@ -0,0 +1,283 @@
+//! ADS-B version and position integrity
+//!
+//! What a position message's type code says about its quality depends on
+//! the transmitter's ADS-B version, which only the operational status
+//! message (TC 31) carries:
+//!
+//! - Version 0 (DO-260): the type code is a NUCp, a combined accuracy and
+//!   integrity figure.
+//! - Version 1 (DO-260A): the type code plus TC 31's NIC supplement give a
+//!   NIC and containment radius; accuracy comes separately as NACp.
+//! - Version 2 (DO-260B): as version 1, but with a second supplement bit in
+//!   the position message itself (NIC-B) and different radii for some codes.
+//!
+//! DO-260B has receivers assume version 0 until an operational status
+//! message says otherwise.
+
+/// Nautical mile in metres
+const NM: f32 = 1852.0;
+
+/// ADS-B version from TC 31
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum AdsbVersion {
+    /// DO-260
+    #[default]
+    V0,
+    /// DO-260A
+    V1,
+    /// DO-260B (and later, which keep its position semantics)
+    V2,
+}
+
+impl AdsbVersion {
+    pub fn number(self) -> u8 {
+        match self {
+            Self::V0 => 0,
+            Self::V1 => 1,
+            Self::V2 => 2,
+        }
+    }
+}
+
+impl From<u8> for AdsbVersion {
+    fn from(version: u8) -> Self {
+        match version {
+            0 => Self::V0,
+            1 => Self::V1,
+            _ => Self::V2,
+        }
+    }
+}
+
+/// Fields of an operational status message (TC 31) that affect how
+/// positions are read
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct OperationalStatus {
+    pub version: AdsbVersion,
+    /// NIC supplement (version 1) or NIC supplement-A (version 2)
+    pub nic_supplement_a: bool,
+    /// Navigation accuracy category for position (versions 1 and 2)
+    pub nac_p: u8,
+    /// Source integrity level (versions 1 and 2)
+    pub sil: u8,
+}
+
+/// Decode an operational status ME field (airborne or surface subtype)
+pub fn decode_operational_status(me: &[u8]) -> Option<OperationalStatus> {
+    if me.len() < 7 || me[0] >> 3 != 31 || me[0] & 0x07 > 1 {
+        return None;
+    }
+    let version = AdsbVersion::from(me[5] >> 5);
+    if version == AdsbVersion::V0 {
+        // Everything after the capability bits is reserved in version 0
+        return Some(OperationalStatus::default());
+    }
+    Some(OperationalStatus {
+        version,
+        nic_supplement_a: (me[5] >> 4) & 1 == 1,
+        nac_p: me[5] & 0x0F,
+        sil: (me[6] >> 4) & 0x03,
+    })
+}
+
+/// Integrity and accuracy of one position
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PositionIntegrity {
+    pub version: AdsbVersion,
+    /// Navigation integrity category (versions 1 and 2)
+    pub nic: Option<u8>,
+    /// Navigation uncertainty category (version 0)
+    pub nuc_p: Option<u8>,
+    /// Navigation accuracy category from the last TC 31 (versions 1 and 2)
+    pub nac_p: Option<u8>,
+    /// Containment radius (or version 0 protection limit), metres; `None`
+    /// when the transmitter doesn't know it
+    pub rc_m: Option<f32>,
+}
+
+impl PositionIntegrity {
+    /// NIC, or for version 0 the NIC whose containment radius covers the
+    /// NUCp's protection limit
+    pub fn nic_equivalent(&self) -> Option<u8> {
+        self.nic.or(self.nuc_p.map(|nuc_p| match nuc_p {
+            9 => 11,
+            8 => 10,
+            7 => 8,
+            3..=6 => nuc_p + 1,
+            1 | 2 => 1,
+            _ => 0,
+        }))
+    }
+}
+
+/// Lowest position quality accepted; 0 disables a limit
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct QualityThreshold {
+    pub min_nic: u8,
+    pub min_nac_p: u8,
+}
+
+impl QualityThreshold {
+    /// Whether a position passes. Categories the sender didn't report
+    /// (NACp in version 0, anything before the first position) aren't held
+    /// against it
+    pub fn accepts(&self, integrity: Option<&PositionIntegrity>) -> bool {
+        let Some(integrity) = integrity else {
+            return true;
+        };
+        let below = |value: Option<u8>, min: u8| min > 0 && value.is_some_and(|v| v < min);
+        !below(integrity.nic_equivalent(), self.min_nic) && !below(integrity.nac_p, self.min_nac_p)
+    }
+}
+
+/// Interpret a position message's type code (5-8 surface, 9-18 and 20-22
+/// airborne) for the sender's last operational status. `nic_b` is the
+/// position message's NIC supplement-B bit (the single antenna flag in
+/// version 0, where it is ignored)
+pub fn position_integrity(
+    tc: u8,
+    nic_b: bool,
+    status: Option<&OperationalStatus>,
+) -> Option<PositionIntegrity> {
+    let status = status.copied().unwrap_or_default();
+    let a = status.nic_supplement_a;
+    let (nic, nuc_p, rc_m) = match status.version {
+        AdsbVersion::V0 => {
+            let nuc_p = nuc_p(tc)?;
+            (None, Some(nuc_p), nuc_p_limit(nuc_p))
+        }
+        AdsbVersion::V1 | AdsbVersion::V2 => {
+            let (nic, rc) = match (tc, status.version) {
+                (5 | 9 | 20, _) => (11, Some(7.5)),
+                (6 | 10 | 21, _) => (10, Some(25.0)),
+                (7, _) if a => (9, Some(75.0)),
+                (7, _) => (8, Some(0.1 * NM)),
+                (8 | 18 | 22, _) => (0, None),
+                (11, AdsbVersion::V1) if a => (9, Some(75.0)),
+                (11, _) if a && nic_b => (9, Some(75.0)),
+                (11, _) => (8, Some(0.1 * NM)),
+                (12, _) => (7, Some(0.2 * NM)),
+                (13, AdsbVersion::V1) if a => (6, Some(0.5 * NM)),
+                (13, AdsbVersion::V1) => (6, Some(0.6 * NM)),
+                (13, _) if !a && nic_b => (6, Some(0.3 * NM)),
+                (13, _) if a && nic_b => (6, Some(0.6 * NM)),
+                (13, _) => (6, Some(0.5 * NM)),
+                (14, _) => (5, Some(NM)),
+                (15, _) => (4, Some(2.0 * NM)),
+                (16, AdsbVersion::V1) if a => (3, Some(4.0 * NM)),
+                (16, _) if a && nic_b => (3, Some(4.0 * NM)),
+                (16, _) => (2, Some(8.0 * NM)),
+                (17, _) => (1, Some(20.0 * NM)),
+                _ => return None,
+            };
+            (Some(nic), None, rc)
+        }
+    };
+    Some(PositionIntegrity {
+        version: status.version,
+        nic,
+        nuc_p,
+        nac_p: (status.version != AdsbVersion::V0).then_some(status.nac_p),
+        rc_m,
+    })
+}
+
+/// Version 0 NUCp for a position type code
+fn nuc_p(tc: u8) -> Option<u8> {
+    match tc {
+        5 | 9 | 20 => Some(9),
+        6 | 10 | 21 => Some(8),
+        7 => Some(6),
+        8 | 18 | 22 => Some(0),
+        11..=17 => Some(18 - tc),
+        _ => None,
+    }
+}
+
+/// Horizontal protection limit for a NUCp, metres
+fn nuc_p_limit(nuc_p: u8) -> Option<f32> {
+    match nuc_p {
+        9 => Some(7.5),
+        8 => Some(25.0),
+        7 => Some(0.1 * NM),
+        6 => Some(0.2 * NM),
+        5 => Some(0.5 * NM),
+        4 => Some(NM),
+        3 => Some(2.0 * NM),
+        2 => Some(10.0 * NM),
+        1 => Some(20.0 * NM),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_operational_status() {
+        // Airborne status, version 2, NIC-A 0, NACp 9, SIL 3
+        let me = [0xF8, 0x00, 0x00, 0x00, 0x00, 0x49, 0x30];
+        let status = decode_operational_status(&me).unwrap();
+        assert_eq!(status.version, AdsbVersion::V2);
+        assert!(!status.nic_supplement_a);
+        assert_eq!((status.nac_p, status.sil), (9, 3));
+
+        // Version 0 leaves the accuracy fields reserved
+        let me = [0xF8, 0x00, 0x00, 0x00, 0x00, 0x0F, 0x30];
+        assert_eq!(decode_operational_status(&me), Some(OperationalStatus::default()));
+        // Not TC 31, or a reserved subtype
+        assert!(decode_operational_status(&[0x58, 0, 0, 0, 0, 0x49, 0]).is_none());
+        assert!(decode_operational_status(&[0xFA, 0, 0, 0, 0, 0x49, 0]).is_none());
+    }
+
+    #[test]
+    fn test_position_integrity_by_version() {
+        let status = |version, a| OperationalStatus {
+            version,
+            nic_supplement_a: a,
+            nac_p: 8,
+            sil: 3,
+        };
+
+        // Unknown version reads as version 0
+        let v0 = position_integrity(13, true, None).unwrap();
+        assert_eq!((v0.nic, v0.nuc_p, v0.nac_p), (None, Some(5), None));
+        assert_eq!(v0.rc_m, Some(926.0));
+
+        // TC 13: the supplements pick the radius, and differ between versions
+        let v1 = position_integrity(13, false, Some(&status(AdsbVersion::V1, false))).unwrap();
+        assert_eq!((v1.nic, v1.nac_p), (Some(6), Some(8)));
+        assert!((v1.rc_m.unwrap() - 1111.2).abs() < 0.1);
+        let v2 = position_integrity(13, true, Some(&status(AdsbVersion::V2, false))).unwrap();
+        assert!((v2.rc_m.unwrap() - 555.6).abs() < 0.1);
+
+        // TC 11 in version 2 needs both supplements for NIC 9
+        let v2 = status(AdsbVersion::V2, true);
+        assert_eq!(position_integrity(11, true, Some(&v2)).unwrap().nic, Some(9));
+        assert_eq!(position_integrity(11, false, Some(&v2)).unwrap().nic, Some(8));
+        assert_eq!(position_integrity(18, false, Some(&v2)).unwrap().rc_m, None);
+        assert!(position_integrity(19, false, Some(&v2)).is_none());
+    }
+
+    #[test]
+    fn test_quality_threshold() {
+        let threshold = QualityThreshold { min_nic: 6, min_nac_p: 8 };
+        let status = |nac_p| OperationalStatus {
+            version: AdsbVersion::V2,
+            nac_p,
+            ..Default::default()
+        };
+
+        // NIC 7, NACp 9 passes; NACp 7 or NIC 5 doesn't
+        assert!(threshold.accepts(position_integrity(12, false, Some(&status(9))).as_ref()));
+        assert!(!threshold.accepts(position_integrity(12, false, Some(&status(7))).as_ref()));
+        assert!(!threshold.accepts(position_integrity(14, false, Some(&status(9))).as_ref()));
+
+        // Version 0 NUCp 5 counts as NIC 6 and has no NACp to check
+        assert!(threshold.accepts(position_integrity(13, false, None).as_ref()));
+        assert!(!threshold.accepts(position_integrity(15, false, None).as_ref()));
+        assert!(threshold.accepts(None));
+        assert!(QualityThreshold::default().accepts(position_integrity(18, false, None).as_ref()));
+    }
+}
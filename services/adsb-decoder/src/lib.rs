@@ -0,0 +1,25 @@
+//! Mode S / ADS-B message decoding
+//!
+//! Shared by the capture service, which feeds it frames from its own SDR
+//! demodulator, and the gateway, which feeds it frames received in Beast
+//! format from other decoders (dump1090, readsb).
+
+mod crc;
+mod cpr;
+mod integrity;
+pub mod parser;
+mod trust;
+mod types;
+
+pub use cpr::CprContext;
+pub use crc::get_df;
+pub use integrity::{AdsbVersion, PositionIntegrity, QualityThreshold};
+pub use parser::{parse_message, ParseError};
+pub use trust::IcaoTrust;
+pub use types::{format_address, AddressType, AircraftData, EmergencySquawk, InterrogatorCode};
+
+/// Verify CRC of a Mode S message (exposed for SDR decoder), returning the
+/// address it came from
+pub fn verify_crc(data: &[u8], trust: &IcaoTrust) -> Option<u32> {
+    crc::check_crc(data, trust).ok()
+}
@@ -0,0 +1,645 @@
+//! ADS-B message parser
+
+use super::cpr::CprContext;
+use super::crc::{check_crc, df11_overlay, get_df};
+use super::integrity::{decode_operational_status, position_integrity};
+use super::trust::IcaoTrust;
+use super::types::{AddressType, AircraftData, DownlinkFormat, InterrogatorCode};
+
+/// Callsign character lookup table
+const CALLSIGN_CHARS: &[u8; 64] = b"#ABCDEFGHIJKLMNOPQRSTUVWXYZ##### ###############0123456789######";
+
+/// Parse error types
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseError {
+    InvalidLength,
+    CrcError,
+    UnsupportedFormat,
+}
+
+/// Parse an ADS-B message; frames whose CRC can't check the address are
+/// only accepted from addresses in `trust`
+pub fn parse_message(
+    msg: &[u8],
+    cpr_ctx: &mut CprContext,
+    trust: &IcaoTrust,
+) -> Result<AircraftData, ParseError> {
+    let len = msg.len();
+    if len != 7 && len != 14 {
+        return Err(ParseError::InvalidLength);
+    }
+
+    // Check CRC, which also gives the address of address/parity formats
+    let Ok(icao_address) = check_crc(msg, trust) else {
+        return Err(ParseError::CrcError);
+    };
+
+    let mut aircraft = AircraftData::default();
+    aircraft.df = get_df(msg);
+    aircraft.icao_address = icao_address;
+
+    let df = DownlinkFormat::from(aircraft.df);
+
+    match df {
+        DownlinkFormat::ShortAirSurveillance | DownlinkFormat::LongAirSurveillance => {
+            // Altitude from AC field
+            if len >= 4 {
+                let ac = ((msg[2] as u16 & 0x1F) << 8) | msg[3] as u16;
+                aircraft.altitude_ft = Some(decode_ac13_altitude(ac));
+            }
+        }
+
+        DownlinkFormat::AltitudeReply | DownlinkFormat::CommBAltitude => {
+            // Altitude
+            if len >= 4 {
+                let ac = ((msg[2] as u16 & 0x1F) << 8) | msg[3] as u16;
+                aircraft.altitude_ft = Some(decode_ac13_altitude(ac));
+            }
+            if len == 14 {
+                aircraft.magnetic_heading_deg = decode_bds60_heading(&msg[4..11]);
+            }
+        }
+
+        DownlinkFormat::IdentityReply | DownlinkFormat::CommBIdentity => {
+            // Squawk code
+            aircraft.squawk = Some(decode_squawk(msg));
+            if len == 14 {
+                aircraft.magnetic_heading_deg = decode_bds60_heading(&msg[4..11]);
+            }
+        }
+
+        DownlinkFormat::AllCallReply => {
+            // ICAO address, which we already have, and the interrogator
+            // code overlaid on the parity
+            aircraft.interrogator = df11_overlay(msg).map(InterrogatorCode::from_overlay);
+        }
+
+        DownlinkFormat::ExtendedSquitter | DownlinkFormat::ExtendedSquitterNonTransponder => {
+            if len != 14 {
+                return Ok(aircraft);
+            }
+
+            // Type code from first 5 bits of ME field
+            aircraft.tc = (msg[4] >> 3) & 0x1F;
+
+            if df == DownlinkFormat::ExtendedSquitterNonTransponder {
+                match df18_address_type(msg, aircraft.tc) {
+                    Some(address_type) => aircraft.address_type = address_type,
+                    // TIS-B management or reserved
+                    None => return Ok(aircraft),
+                }
+                // Coarse TIS-B has its own ME layout
+                if msg[0] & 0x07 == 3 {
+                    return Ok(aircraft);
+                }
+            }
+
+            match aircraft.tc {
+                1..=4 => {
+                    // Aircraft identification
+                    aircraft.callsign = Some(decode_callsign(msg));
+                    aircraft.category = Some(decode_category(aircraft.tc, msg[4] & 0x07));
+                }
+                5..=8 => {
+                    // Surface position
+                    decode_surface_position(msg, &mut aircraft, cpr_ctx);
+                }
+                9..=18 => {
+                    // Airborne position (barometric altitude)
+                    decode_airborne_position(msg, &mut aircraft, cpr_ctx);
+                    aircraft.altitude_gnss = false;
+                }
+                19 => {
+                    // Airborne velocity
+                    decode_airborne_velocity(msg, &mut aircraft);
+                }
+                20..=22 => {
+                    // Airborne position (GNSS altitude)
+                    decode_airborne_position(msg, &mut aircraft, cpr_ctx);
+                    aircraft.altitude_gnss = true;
+                }
+                31 => {
+                    // Operational status: ADS-B version for later positions
+                    aircraft.operational_status = decode_operational_status(&msg[4..11]);
+                    if let Some(status) = aircraft.operational_status {
+                        cpr_ctx.set_status(aircraft.key(), status);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        _ => {}
+    }
+
+    Ok(aircraft)
+}
+
+/// Address type of a DF18 squitter from its control field (CF) and, for
+/// TIS-B and ADS-R, the IMF bit; `None` for TIS-B management and reserved
+/// control fields
+fn df18_address_type(msg: &[u8], tc: u8) -> Option<AddressType> {
+    // IMF sits where the message type has a bit TIS-B doesn't need: NIC-B
+    // in airborne positions, the time flag in surface positions and the
+    // intent change flag in velocities. Identification messages only go
+    // out for ICAO addresses
+    let imf = match tc {
+        5..=8 => (msg[6] >> 3) & 1 == 1,
+        9..=18 | 20..=22 => msg[4] & 1 == 1,
+        19 => msg[5] >> 7 == 1,
+        _ => false,
+    };
+    match msg[0] & 0x07 {
+        0 => Some(AddressType::Icao),
+        1 | 5 => Some(AddressType::Anonymous),
+        // Fine TIS-B, or coarse TIS-B with its IMF in the first ME bit
+        2 if imf => Some(AddressType::TisbTrack),
+        2 => Some(AddressType::Icao),
+        3 if msg[4] >> 7 == 1 => Some(AddressType::TisbTrack),
+        3 => Some(AddressType::Icao),
+        6 if imf => Some(AddressType::Anonymous),
+        6 => Some(AddressType::Icao),
+        _ => None,
+    }
+}
+
+/// Decode altitude from 13-bit AC code
+fn decode_ac13_altitude(ac13: u16) -> i32 {
+    // Q bit indicates 25ft or 100ft resolution
+    let q_bit = (ac13 >> 4) & 1;
+
+    if q_bit == 1 {
+        // 25 ft resolution
+        let n = ((ac13 & 0x1F80) >> 1) | (ac13 & 0x000F);
+        n as i32 * 25 - 1000
+    } else {
+        // 100 ft resolution with Gillham encoding (rarely used)
+        0
+    }
+}
+
+/// Decode altitude from 12-bit AC code
+fn decode_ac12_altitude(ac12: u16) -> i32 {
+    let q_bit = (ac12 >> 4) & 1;
+
+    if q_bit == 1 {
+        let n = ((ac12 & 0x0FE0) >> 1) | (ac12 & 0x000F);
+        n as i32 * 25 - 1000
+    } else {
+        0
+    }
+}
+
+/// Decode callsign from type codes 1-4
+fn decode_callsign(msg: &[u8]) -> String {
+    let mut chars = [0u8; 8];
+
+    // Extract 6-bit character codes from ME field
+    chars[0] = (msg[5] >> 2) & 0x3F;
+    chars[1] = ((msg[5] & 0x03) << 4) | ((msg[6] >> 4) & 0x0F);
+    chars[2] = ((msg[6] & 0x0F) << 2) | ((msg[7] >> 6) & 0x03);
+    chars[3] = msg[7] & 0x3F;
+    chars[4] = (msg[8] >> 2) & 0x3F;
+    chars[5] = ((msg[8] & 0x03) << 4) | ((msg[9] >> 4) & 0x0F);
+    chars[6] = ((msg[9] & 0x0F) << 2) | ((msg[10] >> 6) & 0x03);
+    chars[7] = msg[10] & 0x3F;
+
+    let mut callsign = String::with_capacity(8);
+    for &c in &chars {
+        let idx = c as usize;
+        if idx < CALLSIGN_CHARS.len() {
+            callsign.push(CALLSIGN_CHARS[idx] as char);
+        } else {
+            callsign.push(' ');
+        }
+    }
+
+    // Trim trailing spaces
+    callsign.trim_end().to_string()
+}
+
+/// Emitter category from the identification type code (4 = set A ... 1 = set D)
+/// and the 3-bit category field
+fn decode_category(tc: u8, ca: u8) -> String {
+    let set = (b'A' + 4 - tc) as char;
+    format!("{}{}", set, ca)
+}
+
+/// Decode airborne position (type codes 9-18, 20-22)
+fn decode_airborne_position(msg: &[u8], aircraft: &mut AircraftData, cpr_ctx: &mut CprContext) {
+    // Altitude in bytes 5-6 (12 bits)
+    let ac12 = ((msg[5] as u16) << 4) | ((msg[6] >> 4) as u16 & 0x0F);
+    let alt = decode_ac12_altitude(ac12);
+    if alt != 0 {
+        aircraft.altitude_ft = Some(alt);
+    }
+
+    // Type code semantics depend on the ADS-B version; the last ME bit
+    // before the altitude is NIC supplement-B
+    let nic_b = msg[4] & 1 == 1;
+    aircraft.integrity = position_integrity(
+        aircraft.tc,
+        nic_b,
+        cpr_ctx.status(aircraft.key()),
+    );
+
+    // CPR format flag (F): 0 = even, 1 = odd
+    let odd_flag = ((msg[6] >> 2) & 1) == 1;
+
+    // CPR latitude (17 bits)
+    let lat_cpr = ((msg[6] as i32 & 0x03) << 15)
+        | ((msg[7] as i32) << 7)
+        | ((msg[8] as i32 >> 1) & 0x7F);
+
+    // CPR longitude (17 bits)
+    let lon_cpr = ((msg[8] as i32 & 0x01) << 16)
+        | ((msg[9] as i32) << 8)
+        | (msg[10] as i32);
+
+    // Update CPR context and try to decode position
+    if let Some((lat, lon)) = cpr_ctx.update(aircraft.key(), lat_cpr, lon_cpr, odd_flag) {
+        aircraft.latitude = Some(lat);
+        aircraft.longitude = Some(lon);
+    }
+}
+
+/// Decode surface position (type codes 5-8)
+fn decode_surface_position(msg: &[u8], aircraft: &mut AircraftData, cpr_ctx: &mut CprContext) {
+    // Movement (7 bits): ground speed on a scale that is finer near zero
+    let movement = ((msg[4] & 0x07) << 4) | (msg[5] >> 4);
+    aircraft.ground_speed_kts = decode_movement(movement);
+
+    // Ground track (7 bits), when its status bit is set
+    if (msg[5] >> 3) & 1 == 1 {
+        let track = ((msg[5] & 0x07) << 4) | (msg[6] >> 4);
+        aircraft.track_deg = Some(track as f32 * 360.0 / 128.0);
+    }
+
+    // Surface positions have no NIC supplement-B bit
+    aircraft.integrity = position_integrity(aircraft.tc, false, cpr_ctx.status(aircraft.key()));
+
+    // F flag and CPR coordinates sit where they do in airborne positions
+    let odd_flag = ((msg[6] >> 2) & 1) == 1;
+    let lat_cpr = ((msg[6] as i32 & 0x03) << 15)
+        | ((msg[7] as i32) << 7)
+        | ((msg[8] as i32 >> 1) & 0x7F);
+    let lon_cpr = ((msg[8] as i32 & 0x01) << 16)
+        | ((msg[9] as i32) << 8)
+        | (msg[10] as i32);
+
+    if let Some((lat, lon)) = cpr_ctx.update_surface(aircraft.key(), lat_cpr, lon_cpr, odd_flag) {
+        aircraft.latitude = Some(lat);
+        aircraft.longitude = Some(lon);
+    }
+}
+
+/// Ground speed in knots from a surface position's movement field
+fn decode_movement(movement: u8) -> Option<f32> {
+    let m = movement as f32;
+    match movement {
+        1 => Some(0.0),
+        2..=8 => Some(0.125 * (m - 1.0)),
+        9..=12 => Some(1.0 + 0.25 * (m - 9.0)),
+        13..=38 => Some(2.0 + 0.5 * (m - 13.0)),
+        39..=93 => Some(15.0 + (m - 39.0)),
+        94..=108 => Some(70.0 + 2.0 * (m - 94.0)),
+        109..=123 => Some(100.0 + 5.0 * (m - 109.0)),
+        124 => Some(175.0),
+        // 0 is no information, 125-127 reserved
+        _ => None,
+    }
+}
+
+/// Decode airborne velocity (type code 19)
+fn decode_airborne_velocity(msg: &[u8], aircraft: &mut AircraftData) {
+    let subtype = msg[4] & 0x07;
+
+    match subtype {
+        1 | 2 => {
+            // Ground speed
+            let dew = ((msg[5] >> 2) & 1) == 1;
+            let vew = ((msg[5] as i32 & 0x03) << 8) | msg[6] as i32;
+            let dns = ((msg[7] >> 7) & 1) == 1;
+            let vns = ((msg[7] as i32 & 0x7F) << 3) | ((msg[8] >> 5) as i32 & 0x07);
+
+            if vew > 0 && vns > 0 {
+                let multiplier = if subtype == 2 { 4 } else { 1 };
+                let mut v_ew = (vew - 1) * multiplier;
+                let mut v_ns = (vns - 1) * multiplier;
+
+                if dew {
+                    v_ew = -v_ew;
+                }
+                if dns {
+                    v_ns = -v_ns;
+                }
+
+                let speed = ((v_ew * v_ew + v_ns * v_ns) as f64).sqrt() as f32;
+                let mut track = (v_ew as f64).atan2(v_ns as f64).to_degrees() as f32;
+                if track < 0.0 {
+                    track += 360.0;
+                }
+
+                aircraft.ground_speed_kts = Some(speed);
+                aircraft.track_deg = Some(track);
+            }
+
+            decode_vertical_rate(msg, aircraft);
+        }
+        3 | 4 => {
+            // Airspeed, with the aircraft's heading rather than its track
+            let hdg_avail = ((msg[5] >> 2) & 1) == 1;
+            let hdg = ((msg[5] as u16 & 0x03) << 8) | msg[6] as u16;
+
+            if hdg_avail {
+                aircraft.magnetic_heading_deg = Some(hdg as f32 * 360.0 / 1024.0);
+            }
+
+            let airspeed = ((msg[7] as u16 & 0x7F) << 3) | ((msg[8] >> 5) as u16 & 0x07);
+            if airspeed > 0 {
+                let multiplier = if subtype == 4 { 4 } else { 1 };
+                aircraft.ground_speed_kts = Some(((airspeed - 1) * multiplier) as f32);
+            }
+
+            decode_vertical_rate(msg, aircraft);
+        }
+        _ => {}
+    }
+}
+
+/// Vertical rate from an airborne velocity message
+///
+/// The source bit is 0 for GNSS (geometric) and 1 for barometric; the sign
+/// bit is set when descending.
+fn decode_vertical_rate(msg: &[u8], aircraft: &mut AircraftData) {
+    let vr_gnss = ((msg[8] >> 4) & 1) == 0;
+    let vr_sign = ((msg[8] >> 3) & 1) == 1;
+    let vr = ((msg[8] as i32 & 0x07) << 6) | ((msg[9] >> 2) as i32 & 0x3F);
+    if vr > 0 {
+        let mut vert_rate = (vr - 1) * 64;
+        if vr_sign {
+            vert_rate = -vert_rate;
+        }
+        aircraft.vertical_rate_fpm = Some(vert_rate);
+        aircraft.vertical_rate_gnss = vr_gnss;
+    }
+}
+
+/// Magnetic heading from a Comm-B reply, if its MB field reads as BDS 6,0
+/// (heading and speed report)
+///
+/// BDS 6,0 carries no identifier, so the field is only accepted when every
+/// part is consistent: unavailable subfields are all zero, speeds and
+/// vertical rates are plausible and at least one speed accompanies the
+/// heading. Comm-B replies only get here from addresses `IcaoTrust` trusts.
+fn decode_bds60_heading(mb: &[u8]) -> Option<f32> {
+    let mb = mb.iter().fold(0u64, |acc, &b| (acc << 8) | b as u64);
+    // Bits numbered 1-56 from the left, as in the specification
+    let field = |start: u32, len: u32| (mb >> (57 - start - len)) & ((1 << len) - 1);
+    let status = |bit: u32| field(bit, 1) == 1;
+
+    // (status bit, bits after it including any sign)
+    for (status_bit, len) in [(1, 11), (13, 10), (24, 10), (35, 10), (46, 10)] {
+        if !status(status_bit) && field(status_bit + 1, len) != 0 {
+            return None;
+        }
+    }
+    if !status(1) || !(status(13) || status(24)) {
+        return None;
+    }
+    let ias = field(14, 10);
+    if status(13) && (ias == 0 || ias > 500) {
+        return None;
+    }
+    let mach = field(25, 10) as f32 * 2.048 / 512.0;
+    if status(24) && (mach == 0.0 || mach > 1.0) {
+        return None;
+    }
+    for (status_bit, sign_bit) in [(35, 36), (46, 47)] {
+        if status(status_bit) {
+            let rate = field(sign_bit + 1, 9) as i32 * 32;
+            let rate = if status(sign_bit) { rate - 512 * 32 } else { rate };
+            if rate.abs() > 6000 {
+                return None;
+            }
+        }
+    }
+
+    let mut heading = field(3, 10) as f32 * 90.0 / 512.0;
+    if status(2) {
+        heading -= 180.0;
+    }
+    if heading < 0.0 {
+        heading += 360.0;
+    }
+    Some(heading)
+}
+
+/// Decode squawk from identity reply
+fn decode_squawk(msg: &[u8]) -> u16 {
+    let id13 = ((msg[2] as u16 & 0x1F) << 8) | msg[3] as u16;
+
+    // Decode from Gillham to squawk
+    let a = if id13 & 0x1000 != 0 { 4 } else { 0 }
+        + if id13 & 0x0200 != 0 { 2 } else { 0 }
+        + if id13 & 0x0040 != 0 { 1 } else { 0 };
+
+    let b = if id13 & 0x0800 != 0 { 4 } else { 0 }
+        + if id13 & 0x0100 != 0 { 2 } else { 0 }
+        + if id13 & 0x0020 != 0 { 1 } else { 0 };
+
+    let c = if id13 & 0x0400 != 0 { 4 } else { 0 }
+        + if id13 & 0x0080 != 0 { 2 } else { 0 }
+        + if id13 & 0x0010 != 0 { 1 } else { 0 };
+
+    let d = if id13 & 0x0008 != 0 { 4 } else { 0 }
+        + if id13 & 0x0004 != 0 { 2 } else { 0 }
+        + if id13 & 0x0002 != 0 { 1 } else { 0 };
+
+    a * 1000 + b * 100 + c * 10 + d
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    #[test]
+    fn test_decode_callsign() {
+        // Test with a known message
+        let msg = hex::decode("8D4840D6202CC371C32CE0576098").unwrap();
+        let callsign = decode_callsign(&msg);
+        // The actual callsign depends on the message content
+        assert!(!callsign.is_empty() || callsign.is_empty()); // Just verify it doesn't crash
+    }
+
+    #[test]
+    fn test_decode_category() {
+        assert_eq!(decode_category(4, 3), "A3");
+        assert_eq!(decode_category(1, 0), "D0");
+
+        let msg = hex::decode("8D4840D6202CC371C32CE0576098").unwrap();
+        let aircraft = parse_message(&msg, &mut CprContext::new(256), &IcaoTrust::new()).unwrap();
+        assert_eq!(aircraft.category.as_deref(), Some("A0"));
+    }
+
+    #[test]
+    fn test_parse_df17() {
+        let msg = hex::decode("8D4840D6202CC371C32CE0576098").unwrap();
+        let mut cpr_ctx = CprContext::new(256);
+        let result = parse_message(&msg, &mut cpr_ctx, &IcaoTrust::new());
+        assert!(result.is_ok());
+
+        let aircraft = result.unwrap();
+        assert_eq!(aircraft.df, 17);
+        assert_eq!(aircraft.icao_address, 0x4840D6);
+    }
+
+    #[test]
+    fn test_track_and_heading() {
+        // Ground speed velocity carries a track
+        let msg = hex::decode("8D485020994409940838175B284F").unwrap();
+        let aircraft = parse_message(&msg, &mut CprContext::new(256), &IcaoTrust::new()).unwrap();
+        assert!((aircraft.track_deg.unwrap() - 182.88).abs() < 0.01);
+        assert_eq!(aircraft.magnetic_heading_deg, None);
+        assert_eq!(aircraft.vertical_rate_fpm, Some(-832));
+        assert!(aircraft.vertical_rate_gnss);
+
+        // Airspeed velocity carries a heading
+        let msg = hex::decode("8DA05F219B06B6AF189400CBC33F").unwrap();
+        let aircraft = parse_message(&msg, &mut CprContext::new(256), &IcaoTrust::new()).unwrap();
+        assert!((aircraft.magnetic_heading_deg.unwrap() - 243.98).abs() < 0.01);
+        assert_eq!(aircraft.track_deg, None);
+        assert_eq!(aircraft.vertical_rate_fpm, Some(-2304));
+        assert!(!aircraft.vertical_rate_gnss);
+
+        // BDS 6,0 in a Comm-B reply; a BDS 5,0 track and turn report is not taken for one
+        let msg = hex::decode("A00004128F39F91A7E27C46ADC21").unwrap();
+        let heading = decode_bds60_heading(&msg[4..11]).unwrap();
+        assert!((heading - 42.71).abs() < 0.01, "{}", heading);
+        let msg = hex::decode("A000139381951536E024D4CCF6B5").unwrap();
+        assert_eq!(decode_bds60_heading(&msg[4..11]), None);
+    }
+
+    #[test]
+    fn test_surface_position() {
+        // "The 1090 Megahertz Riddle" surface position pair
+        let even = hex::decode("8C4841753AAB238733C8CD4020B1").unwrap();
+        let odd = hex::decode("8C4841753A8A35323FAEBDAC702D").unwrap();
+        let trust = IcaoTrust::new();
+
+        // No position without a reference
+        let mut cpr_ctx = CprContext::new(256);
+        parse_message(&even, &mut cpr_ctx, &trust).unwrap();
+        assert_eq!(parse_message(&odd, &mut cpr_ctx, &trust).unwrap().latitude, None);
+
+        let mut cpr_ctx = CprContext::new(256);
+        cpr_ctx.set_receiver(Some((51.990, 4.375)));
+        let aircraft = parse_message(&even, &mut cpr_ctx, &trust).unwrap();
+        assert_eq!(aircraft.icao_address, 0x484175);
+        assert_eq!(aircraft.ground_speed_kts, Some(18.0));
+        assert_eq!(aircraft.track_deg, Some(140.625));
+        assert_eq!(aircraft.altitude_ft, None);
+
+        // Taxiing 400 m between the two
+        if let Some((_, _, time)) = &mut cpr_ctx.get_or_create(0x484175).surface_even_cpr {
+            *time -= std::time::Duration::from_secs(20);
+        }
+        let aircraft = parse_message(&odd, &mut cpr_ctx, &trust).unwrap();
+        assert_eq!(aircraft.ground_speed_kts, Some(16.0));
+        assert!((aircraft.latitude.unwrap() - 52.32061).abs() < 1e-5);
+        assert!((aircraft.longitude.unwrap() - 4.73473).abs() < 1e-5);
+
+        assert_eq!(decode_movement(0), None);
+        assert_eq!(decode_movement(1), Some(0.0));
+        assert_eq!(decode_movement(124), Some(175.0));
+    }
+
+    #[test]
+    fn test_version_aware_integrity() {
+        // DF17 with CRC from an 11-byte body
+        let frame = |body: [u8; 11]| {
+            let mut msg = body.to_vec();
+            msg.extend_from_slice(&[0; 3]);
+            let crc = super::super::crc::compute_crc24(&msg, 88);
+            msg[11..].copy_from_slice(&crc.to_be_bytes()[1..]);
+            msg
+        };
+        let position = frame([0x8D, 0x48, 0x40, 0xD6, 0x69, 0xC3, 0x82, 0xD6, 0x90, 0xC8, 0xAC]);
+        let status = frame([0x8D, 0x48, 0x40, 0xD6, 0xF8, 0x00, 0x00, 0x00, 0x00, 0x39, 0x30]);
+        let mut ctx = CprContext::new(256);
+        let trust = IcaoTrust::new();
+
+        // TC 13 read as version 0 until a status message says otherwise
+        let aircraft = parse_message(&position, &mut ctx, &trust).unwrap();
+        let integrity = aircraft.integrity.unwrap();
+        assert_eq!((integrity.nuc_p, integrity.nic), (Some(5), None));
+
+        let aircraft = parse_message(&status, &mut ctx, &trust).unwrap();
+        assert_eq!(aircraft.operational_status.unwrap().nac_p, 9);
+
+        // Version 1, NIC supplement 1, NACp 9
+        let integrity = parse_message(&position, &mut ctx, &trust).unwrap().integrity.unwrap();
+        assert_eq!((integrity.nic, integrity.nac_p), (Some(6), Some(9)));
+        assert_eq!(integrity.rc_m, Some(926.0));
+    }
+
+    #[test]
+    fn test_df11_interrogator() {
+        let mut msg = hex::decode("5D4840D6000000").unwrap();
+        let crc = super::super::crc::compute_crc24(&msg, 32);
+        msg[4..].copy_from_slice(&crc.to_be_bytes()[1..]);
+        msg[6] ^= 0x22;
+        let trust = IcaoTrust::new();
+        assert_eq!(parse_message(&msg, &mut CprContext::new(256), &trust).unwrap_err(), ParseError::CrcError);
+        trust.corroborate(0x4840D6, Instant::now());
+        trust.corroborate(0x4840D6, Instant::now());
+        let aircraft = parse_message(&msg, &mut CprContext::new(256), &trust).unwrap();
+        assert_eq!(aircraft.icao_address, 0x4840D6);
+        assert_eq!(aircraft.interrogator, Some(InterrogatorCode::Si(18)));
+        assert_eq!(InterrogatorCode::from_overlay(0x05), InterrogatorCode::Ii(5));
+    }
+
+    #[test]
+    fn test_df18_address_types() {
+        // The version test's airborne position (TC 13, NIC-B/IMF clear) as
+        // DF18 with a given control field and IMF
+        let frame = |cf: u8, imf: bool| {
+            let mut msg = vec![0x90 | cf, 0x48, 0x40, 0xD6, 0x68 | imf as u8, 0xC3, 0x82];
+            msg.extend_from_slice(&[0xD6, 0x90, 0xC8, 0xAC, 0, 0, 0]);
+            let crc = super::super::crc::compute_crc24(&msg, 88);
+            msg[11..].copy_from_slice(&crc.to_be_bytes()[1..]);
+            msg
+        };
+        let trust = IcaoTrust::new();
+        let parse = |msg: &[u8]| parse_message(msg, &mut CprContext::new(256), &trust).unwrap();
+
+        assert_eq!(parse(&frame(0, false)).address_type, AddressType::Icao);
+        let anonymous = parse(&frame(1, false));
+        assert_eq!(anonymous.address_type, AddressType::Anonymous);
+        assert_eq!(anonymous.key(), 0x14840D6);
+        assert_eq!(super::super::format_address(anonymous.key()), "~4840D6");
+        assert_eq!(super::super::format_address(0x4840D6), "4840D6");
+        assert_eq!(parse(&frame(2, false)).address_type, AddressType::Icao);
+        assert_eq!(parse(&frame(2, true)).address_type, AddressType::TisbTrack);
+        assert_eq!(parse(&frame(6, true)).address_type, AddressType::Anonymous);
+
+        // Coarse TIS-B and management messages aren't decoded as ADS-B
+        assert_eq!(parse(&frame(3, false)).altitude_ft, None);
+        assert_eq!(parse(&frame(4, false)).integrity, None);
+        assert!(parse(&frame(0, false)).integrity.is_some());
+    }
+
+    #[test]
+    fn test_decode_emergency_squawk() {
+        // DF5 identity reply with ID bits A=7 B=7 C=0 D=0
+        let msg = [0x28, 0x00, 0x1B, 0x60, 0x00, 0x00, 0x00];
+        let squawk = decode_squawk(&msg);
+        assert_eq!(squawk, 7700);
+        assert_eq!(
+            crate::EmergencySquawk::from_squawk(squawk),
+            Some(crate::EmergencySquawk::General)
+        );
+        assert_eq!(crate::EmergencySquawk::from_squawk(1200), None);
+    }
+}
@@ -0,0 +1,161 @@
+//! Recently heard addresses
+//!
+//! Only DF11, DF17 and DF18 carry their address in the clear under a parity
+//! that checks it. Surveillance and Comm-B replies overlay the address on
+//! the parity, so any corrupted frame "passes" with some address, and error
+//! correction has a smaller version of the same problem: flipping bits until
+//! the CRC matches now and then turns noise into a valid-looking frame. As
+//! in dump1090, those marginal frames are only accepted from addresses that
+//! clean frames have recently corroborated.
+//!
+//! Each clean frame (an uncorrected DF17, DF18 with an ICAO address, or DF11
+//! without an interrogator code) adds one to its address's score, and scores
+//! halve every `HALF_LIFE_SECS`. An address is trusted from `TRUSTED_SCORE`,
+//! which takes two clean frames close together, something a lone false
+//! decode never manages, and stays trusted for up to about 50 seconds after
+//! its last clean frame.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use super::crc::{compute_crc24, df11_overlay, get_df, get_icao};
+
+/// Time for a score to halve
+const HALF_LIFE_SECS: f32 = 20.0;
+
+/// Lowest score of a trusted address
+const TRUSTED_SCORE: f32 = 1.5;
+
+/// Highest score, so busy aircraft don't stay trusted for minutes after
+/// they go quiet
+const MAX_SCORE: f32 = 8.0;
+
+/// Addresses are forgotten once their score decays below this
+const FORGET_SCORE: f32 = 0.05;
+
+#[derive(Debug, Clone, Copy)]
+struct Score {
+    value: f32,
+    updated: Instant,
+}
+
+impl Score {
+    fn at(&self, now: Instant) -> f32 {
+        let age = now.saturating_duration_since(self.updated).as_secs_f32();
+        self.value * 0.5f32.powf(age / HALF_LIFE_SECS)
+    }
+}
+
+/// Trust scores per address, shared by the detector, the parser and the
+/// tracker
+#[derive(Debug, Default)]
+pub struct IcaoTrust {
+    scores: Mutex<HashMap<u32, Score>>,
+}
+
+impl IcaoTrust {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count `msg` towards its address's score if it is a clean frame;
+    /// returns whether it was
+    pub fn observe(&self, msg: &[u8], now: Instant) -> bool {
+        match clean_address(msg) {
+            Some(icao) => {
+                self.corroborate(icao, now);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Add a clean frame from `icao`
+    pub fn corroborate(&self, icao: u32, now: Instant) {
+        if let Ok(mut scores) = self.scores.lock() {
+            let score = scores.entry(icao).or_insert(Score { value: 0.0, updated: now });
+            *score = Score {
+                value: (score.at(now) + 1.0).min(MAX_SCORE),
+                updated: now,
+            };
+        }
+    }
+
+    /// Current score of `icao`, 0 when it hasn't been heard
+    pub fn score(&self, icao: u32, now: Instant) -> f32 {
+        self.scores
+            .lock()
+            .ok()
+            .and_then(|scores| scores.get(&icao).map(|s| s.at(now)))
+            .unwrap_or(0.0)
+    }
+
+    /// Whether frames the CRC can't fully check are accepted from `icao`
+    pub fn is_trusted(&self, icao: u32, now: Instant) -> bool {
+        self.score(icao, now) >= TRUSTED_SCORE
+    }
+
+    /// Forget addresses whose score has decayed away
+    pub fn prune(&self, now: Instant) {
+        if let Ok(mut scores) = self.scores.lock() {
+            scores.retain(|_, score| score.at(now) >= FORGET_SCORE);
+        }
+    }
+
+    /// Number of addresses with a score
+    pub fn len(&self) -> usize {
+        self.scores.lock().map(|scores| scores.len()).unwrap_or(0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Address of a frame that corroborates it: self-checking, nothing left
+/// over in the parity, and (for DF18) an ICAO address
+fn clean_address(msg: &[u8]) -> Option<u32> {
+    let clean = match (msg.len(), get_df(msg)) {
+        (7, 11) => df11_overlay(msg) == Some(0),
+        (14, 17) => compute_crc24(msg, 112) == 0,
+        (14, 18) => msg[0] & 0x07 == 0 && compute_crc24(msg, 112) == 0,
+        _ => false,
+    };
+    clean.then(|| get_icao(msg))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_trust_decays() {
+        let trust = IcaoTrust::new();
+        let t = Instant::now();
+        let msg = hex::decode("8D4840D6202CC371C32CE0576098").unwrap();
+
+        // One clean frame isn't enough, a second one soon after is
+        assert!(trust.observe(&msg, t));
+        assert!(!trust.is_trusted(0x4840D6, t));
+        trust.observe(&msg, t + Duration::from_secs(2));
+        assert!(trust.is_trusted(0x4840D6, t + Duration::from_secs(2)));
+
+        // A flipped bit doesn't count
+        let mut corrupt = msg.clone();
+        corrupt[5] ^= 0x10;
+        assert!(!trust.observe(&corrupt, t));
+
+        // Busy aircraft are capped, so trust runs out within a minute
+        for secs in 0..60 {
+            trust.corroborate(0x71BE11, t + Duration::from_secs(secs));
+        }
+        let last = t + Duration::from_secs(59);
+        assert!(trust.is_trusted(0x71BE11, last + Duration::from_secs(45)));
+        assert!(!trust.is_trusted(0x71BE11, last + Duration::from_secs(60)));
+
+        trust.prune(last + Duration::from_secs(300));
+        assert_eq!(trust.len(), 0);
+    }
+}
@@ -0,0 +1,197 @@
+//! ADS-B data types
+
+use super::integrity::{OperationalStatus, PositionIntegrity};
+
+/// Downlink format identifiers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum DownlinkFormat {
+    ShortAirSurveillance = 0,
+    AltitudeReply = 4,
+    IdentityReply = 5,
+    AllCallReply = 11,
+    LongAirSurveillance = 16,
+    ExtendedSquitter = 17,
+    ExtendedSquitterNonTransponder = 18,
+    MilitaryExtendedSquitter = 19,
+    CommBAltitude = 20,
+    CommBIdentity = 21,
+    Unknown = 255,
+}
+
+impl From<u8> for DownlinkFormat {
+    fn from(df: u8) -> Self {
+        match df {
+            0 => Self::ShortAirSurveillance,
+            4 => Self::AltitudeReply,
+            5 => Self::IdentityReply,
+            11 => Self::AllCallReply,
+            16 => Self::LongAirSurveillance,
+            17 => Self::ExtendedSquitter,
+            18 => Self::ExtendedSquitterNonTransponder,
+            19 => Self::MilitaryExtendedSquitter,
+            20 => Self::CommBAltitude,
+            21 => Self::CommBIdentity,
+            _ => Self::Unknown,
+        }
+    }
+}
+
+/// Tracker and CPR keys for non-ICAO addresses have this bit set, so an
+/// anonymous address or TIS-B track number never shares state with the
+/// aircraft whose ICAO address has the same 24 bits
+pub const NON_ICAO_ADDRESS: u32 = 1 << 24;
+
+/// What the address field of an extended squitter holds
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AddressType {
+    /// ICAO aircraft address (DF17, and DF18 unless below)
+    #[default]
+    Icao,
+    /// Self-assigned or anonymous address: DF18 non-transponder devices
+    /// (CF 1), TIS-B relays of them (CF 5), and ADS-R rebroadcasts of 978
+    /// MHz UAT targets without an ICAO address (CF 6, IMF 1)
+    Anonymous,
+    /// TIS-B track file number of a radar-only target (CF 2/3, IMF 1)
+    TisbTrack,
+}
+
+/// Key of an address in the tracker and CPR context
+pub fn address_key(address: u32, address_type: AddressType) -> u32 {
+    match address_type {
+        AddressType::Icao => address,
+        AddressType::Anonymous | AddressType::TisbTrack => address | NON_ICAO_ADDRESS,
+    }
+}
+
+/// Address as sent to the gateway: six hex digits, with a `~` prefix for
+/// non-ICAO addresses (the readsb/tar1090 convention)
+pub fn format_address(key: u32) -> String {
+    if key & NON_ICAO_ADDRESS != 0 {
+        format!("~{:06X}", key & 0xFFFFFF)
+    } else {
+        format!("{:06X}", key)
+    }
+}
+
+/// Code of the interrogator a DF11 all-call reply answers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum InterrogatorCode {
+    /// Interrogator identifier (0-15); 0 also covers acquisition squitters
+    Ii(u8),
+    /// Surveillance identifier (1-63)
+    Si(u8),
+}
+
+impl InterrogatorCode {
+    /// From a DF11 parity overlay: code class in bits 4-6, code in bits 0-3
+    pub fn from_overlay(overlay: u8) -> Self {
+        let (cl, ic) = (overlay >> 4, overlay & 0x0F);
+        if cl == 0 {
+            Self::Ii(ic)
+        } else {
+            Self::Si(16 * (cl - 1) + ic)
+        }
+    }
+}
+
+/// Emergency declared by squawking a reserved code
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmergencySquawk {
+    /// 7500 - unlawful interference
+    Hijack,
+    /// 7600 - lost communications
+    RadioFailure,
+    /// 7700 - general emergency
+    General,
+}
+
+impl EmergencySquawk {
+    /// Classify a squawk code (decimal digits, e.g. 7700)
+    pub fn from_squawk(squawk: u16) -> Option<Self> {
+        match squawk {
+            7500 => Some(Self::Hijack),
+            7600 => Some(Self::RadioFailure),
+            7700 => Some(Self::General),
+            _ => None,
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            Self::Hijack => "hijack",
+            Self::RadioFailure => "radio failure",
+            Self::General => "general emergency",
+        }
+    }
+}
+
+/// Parsed aircraft data from ADS-B message
+#[derive(Debug, Clone, Default)]
+pub struct AircraftData {
+    /// ICAO 24-bit address
+    pub icao_address: u32,
+
+    /// Whether `icao_address` is really an ICAO address
+    pub address_type: AddressType,
+
+    /// Flight callsign (8 characters max)
+    pub callsign: Option<String>,
+
+    /// Emitter category (e.g. "A3" for a large aircraft)
+    pub category: Option<String>,
+
+    /// Latitude in degrees (-90 to 90)
+    pub latitude: Option<f64>,
+
+    /// Longitude in degrees (-180 to 180)
+    pub longitude: Option<f64>,
+
+    /// Barometric altitude in feet
+    pub altitude_ft: Option<i32>,
+
+    /// Ground speed in knots
+    pub ground_speed_kts: Option<f32>,
+
+    /// Ground track in degrees true (0-360), from ground-speed velocity
+    pub track_deg: Option<f32>,
+
+    /// Heading the nose points, degrees magnetic (0-360), from airspeed
+    /// velocity or Comm-B BDS 6,0
+    pub magnetic_heading_deg: Option<f32>,
+
+    /// Vertical rate in feet per minute, positive when climbing
+    pub vertical_rate_fpm: Option<i32>,
+
+    /// Squawk code (4-digit octal)
+    pub squawk: Option<u16>,
+
+    /// Downlink format
+    pub df: u8,
+
+    /// Type code (for DF17/18)
+    pub tc: u8,
+
+    /// Whether altitude is from GNSS (true) or barometric (false)
+    pub altitude_gnss: bool,
+
+    /// Whether vertical rate is from GNSS (true) or barometric (false)
+    pub vertical_rate_gnss: bool,
+
+    /// Interrogator answered by a DF11 all-call reply
+    pub interrogator: Option<InterrogatorCode>,
+
+    /// Operational status (TC 31): ADS-B version, NACp and SIL
+    pub operational_status: Option<OperationalStatus>,
+
+    /// Integrity and accuracy of the position, read for the sender's ADS-B
+    /// version (position type codes only)
+    pub integrity: Option<PositionIntegrity>,
+}
+
+impl AircraftData {
+    /// Tracker and CPR context key for the sender
+    pub fn key(&self) -> u32 {
+        address_key(self.icao_address, self.address_type)
+    }
+}
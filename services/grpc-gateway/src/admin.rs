@@ -0,0 +1,288 @@
+//! Data maintenance endpoints
+//!
+//! `/api/admin` lets operators clean up stored data without database
+//! access: delete positions left by a decoder bug, or move a device's rows
+//! to a new device ID after renaming a receiver. These endpoints always need
+//! an `admin` credential, for reads too, and are refused outright when the
+//! API is open (neither `API_KEYS` nor `JWT_SECRET` set).
+
+use crate::api::{parse_icao, ApiError};
+use crate::AppState;
+use axum::{
+    extract::{Query, State},
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tracing::{error, info};
+use utoipa::{IntoParams, ToSchema};
+
+/// Paths under this prefix need an admin credential
+pub const PREFIX: &str = "/api/admin";
+
+/// Longest device ID the schema holds
+const MAX_DEVICE_ID_LEN: usize = 64;
+
+/// Query parameters for deleting positions
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct PurgeParams {
+    /// Only this aircraft
+    icao: Option<String>,
+    /// RFC 3339; rows at or after this time
+    from: Option<String>,
+    /// RFC 3339; rows before this time
+    to: Option<String>,
+}
+
+/// Which positions to delete; at least one field is set
+#[derive(Debug, Clone, PartialEq)]
+pub struct PositionPurge {
+    pub icao: Option<String>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+}
+
+impl PurgeParams {
+    pub fn parse(&self) -> Result<PositionPurge, String> {
+        let parse_time = |name: &str, value: &Option<String>| {
+            value
+                .as_deref()
+                .map(|value| {
+                    DateTime::parse_from_rfc3339(value)
+                        .map(|t| t.with_timezone(&Utc))
+                        .map_err(|_| format!("{} must be an RFC 3339 timestamp", name))
+                })
+                .transpose()
+        };
+        let purge = PositionPurge {
+            icao: self.icao.as_deref().map(parse_icao).transpose()?,
+            from: parse_time("from", &self.from)?,
+            to: parse_time("to", &self.to)?,
+        };
+        if purge.icao.is_none() && purge.from.is_none() && purge.to.is_none() {
+            return Err("give icao, from or to; refusing to delete every position".into());
+        }
+        if let (Some(from), Some(to)) = (purge.from, purge.to) {
+            if from >= to {
+                return Err("from must be before to".into());
+            }
+        }
+        Ok(purge)
+    }
+}
+
+/// Result of a position purge
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PurgeResult {
+    /// Position rows deleted
+    pub deleted: u64,
+}
+
+/// Request body for renaming a device
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct DeviceRename {
+    /// Current device ID
+    pub from: String,
+    /// New device ID; rows are merged if it already has data
+    pub to: String,
+}
+
+impl DeviceRename {
+    fn validate(&self) -> Result<(), &'static str> {
+        for id in [&self.from, &self.to] {
+            if id.trim().is_empty() || id.trim() != id {
+                return Err("device IDs must be non-empty without surrounding spaces");
+            }
+            if id.len() > MAX_DEVICE_ID_LEN {
+                return Err("device IDs are at most 64 characters");
+            }
+        }
+        if self.from == self.to {
+            return Err("from and to are the same device");
+        }
+        Ok(())
+    }
+}
+
+/// Result of a device rename
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RenameResult {
+    /// Rows moved to the new device ID, across all tables
+    pub updated: u64,
+}
+
+/// Delete stored positions by aircraft and/or time range
+#[utoipa::path(
+    delete,
+    path = "/api/admin/positions",
+    tag = "admin",
+    params(PurgeParams),
+    responses(
+        (status = 200, description = "Positions deleted", body = PurgeResult),
+        (status = 400, description = "Invalid or missing filters", body = ApiError),
+        (status = 403, description = "Not an admin, or API auth not configured", body = ApiError),
+    ),
+    security(("api_key" = []), ("bearer" = []))
+)]
+pub async fn delete_positions(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<PurgeParams>,
+) -> impl IntoResponse {
+    let purge = match params.parse() {
+        Ok(purge) => purge,
+        Err(e) => return ApiError::bad_request(e),
+    };
+    match state.db_writer.delete_positions(&purge).await {
+        Ok(deleted) => {
+            info!("Deleted {} positions ({:?})", deleted, purge);
+            Json(PurgeResult { deleted }).into_response()
+        }
+        Err(e) => {
+            error!("Failed to delete positions: {}", e);
+            ApiError::internal(e)
+        }
+    }
+}
+
+/// Move every stored row of one device ID to another
+#[utoipa::path(
+    post,
+    path = "/api/admin/devices/rename",
+    tag = "admin",
+    request_body = DeviceRename,
+    responses(
+        (status = 200, description = "Rows moved", body = RenameResult),
+        (status = 400, description = "Invalid device IDs", body = ApiError),
+        (status = 403, description = "Not an admin, or API auth not configured", body = ApiError),
+    ),
+    security(("api_key" = []), ("bearer" = []))
+)]
+pub async fn rename_device(
+    State(state): State<Arc<AppState>>,
+    Json(rename): Json<DeviceRename>,
+) -> impl IntoResponse {
+    if let Err(e) = rename.validate() {
+        return ApiError::bad_request(e);
+    }
+    match state
+        .db_writer
+        .rename_device(&rename.from, &rename.to)
+        .await
+    {
+        Ok(updated) => {
+            info!(
+                "Renamed device {} to {} ({} rows)",
+                rename.from, rename.to, updated
+            );
+            Json(RenameResult { updated }).into_response()
+        }
+        Err(e) => {
+            error!("Failed to rename device {}: {}", rename.from, e);
+            ApiError::internal(e)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(icao: Option<&str>, from: Option<&str>, to: Option<&str>) -> PurgeParams {
+        PurgeParams {
+            icao: icao.map(str::to_string),
+            from: from.map(str::to_string),
+            to: to.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_purge_params() {
+        let purge = params(Some("71be11"), None, None).parse().unwrap();
+        assert_eq!(purge.icao.as_deref(), Some("71BE11"));
+        assert_eq!((purge.from, purge.to), (None, None));
+
+        let purge = params(
+            None,
+            Some("2024-01-15T00:00:00Z"),
+            Some("2024-01-16T00:00:00Z"),
+        )
+        .parse()
+        .unwrap();
+        assert!(purge.icao.is_none() && purge.from.is_some() && purge.to.is_some());
+
+        assert!(params(None, None, None).parse().is_err());
+        assert!(params(Some("XYZ"), None, None).parse().is_err());
+        assert!(params(
+            None,
+            Some("2024-01-16T00:00:00Z"),
+            Some("2024-01-15T00:00:00Z")
+        )
+        .parse()
+        .is_err());
+    }
+
+    #[test]
+    fn test_rename_validation() {
+        let rename = |from: &str, to: &str| DeviceRename {
+            from: from.into(),
+            to: to.into(),
+        };
+        assert!(rename("rtlsdr-0", "rooftop").validate().is_ok());
+        assert!(rename("rtlsdr-0", "rtlsdr-0").validate().is_err());
+        assert!(rename("", "rooftop").validate().is_err());
+        assert!(rename("rtlsdr-0", &"x".repeat(65)).validate().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_purge_and_rename() {
+        use crate::adsb::AircraftEvent;
+        use crate::sqlite_writer::SqliteWriter;
+        use crate::stats::{Period, StatsRollup};
+        use crate::storage::Storage;
+
+        let path = std::env::temp_dir().join(format!("admin-test-{}.db", std::process::id()));
+        let db = SqliteWriter::open(&path).await.unwrap();
+        for (icao, device) in [("71BE11", "old"), ("71BE12", "old"), ("71BE11", "new")] {
+            let event = AircraftEvent {
+                icao: icao.into(),
+                device_id: device.into(),
+                latitude: Some(37.5),
+                longitude: Some(126.4),
+                ..Default::default()
+            };
+            db.insert_position(&event, Utc::now(), 1).await.unwrap();
+        }
+        let bucket = Utc::now();
+        let rollup = |device: &str, messages| StatsRollup {
+            period: Period::Hour,
+            bucket,
+            device_id: device.into(),
+            messages,
+            positions: 0,
+            unique_aircraft: 0,
+            max_range_km: 0.0,
+            frames_decoded: 0,
+            crc_errors: 0,
+            downlink_formats: [(17, messages)].into(),
+        };
+        db.upsert_stats(&[rollup("old", 5), rollup("new", 7)]).await.unwrap();
+
+        // Two position rows and one aircraft_info row move; the rollup merges
+        assert_eq!(db.rename_device("old", "new").await.unwrap(), 4);
+        let rows = db.get_stats(Period::Hour, bucket).await.unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!((rows[0].device_id.as_str(), rows[0].messages), ("new", 12));
+        assert_eq!(rows[0].downlink_formats[&17], 12);
+
+        let purge = PositionPurge {
+            icao: Some("71BE11".into()),
+            from: None,
+            to: None,
+        };
+        assert_eq!(db.delete_positions(&purge).await.unwrap(), 2);
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,229 @@
+//! Current aircraft state
+//!
+//! The aircraft list and the `initial` message of WebSocket and SSE clients
+//! are served from memory rather than the `current_aircraft` view, so a page
+//! load no longer costs a database query, and the map fills up even without
+//! a database. The cache is fed from the live broadcast: every
+//! `position_update` already carries an aircraft's merged state, and with a
+//! shared `PUBSUB_BACKEND` the broadcast includes the aircraft streamed to
+//! other replicas. Aircraft leave the cache with their `aircraft_removed`
+//! message, or once nothing has been heard from them for as long as the
+//! view looks back. The database is still used for history.
+
+use crate::adsb::AircraftLabel;
+use crate::presence::STALE_SECS;
+use crate::pubsub::{LiveMessage, PubSub};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use serde_json::Value as JsonValue;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast;
+use tracing::debug;
+
+/// The fields of a `position_update` the aircraft list shows
+#[derive(Debug, Clone, Default, Deserialize)]
+struct PositionUpdate {
+    icao: String,
+    device_id: Option<String>,
+    callsign: Option<String>,
+    lat: Option<f64>,
+    lon: Option<f64>,
+    altitude: Option<i32>,
+    speed: Option<f32>,
+    track: Option<f32>,
+    magnetic_heading: Option<f32>,
+    vrate: Option<i32>,
+    baro_rate: Option<i32>,
+    geom_rate: Option<i32>,
+    squawk: Option<String>,
+    category: Option<String>,
+}
+
+/// An aircraft's latest update and when it was heard
+#[derive(Debug, Clone)]
+struct CachedAircraft {
+    update: PositionUpdate,
+    first_seen: DateTime<Utc>,
+    last_seen: DateTime<Utc>,
+    messages: i64,
+}
+
+impl CachedAircraft {
+    /// An aircraft list row, as the `current_aircraft` queries return them
+    fn row(&self) -> JsonValue {
+        let u = &self.update;
+        serde_json::json!({
+            "icao": u.icao,
+            "callsign": u.callsign,
+            "lat": u.lat,
+            "lon": u.lon,
+            "altitude": u.altitude,
+            "speed": u.speed,
+            "track": u.track,
+            "magnetic_heading": u.magnetic_heading,
+            "vrate": u.vrate,
+            "baro_rate": u.baro_rate,
+            "geom_rate": u.geom_rate,
+            "squawk": u.squawk,
+            "seen": self.last_seen.to_rfc3339(),
+            "messages": self.messages,
+        })
+    }
+}
+
+/// Latest state of every aircraft in view
+#[derive(Default)]
+pub struct AircraftCache {
+    aircraft: Mutex<HashMap<String, CachedAircraft>>,
+}
+
+impl AircraftCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Apply a live message; anything but position updates and removals is
+    /// ignored
+    pub fn apply(&self, msg: &LiveMessage, now: DateTime<Utc>) {
+        match msg.fields.kind.as_str() {
+            "position_update" => {
+                if let Ok(update) = serde_json::from_str(&msg.json) {
+                    self.update(update, now);
+                }
+            }
+            "aircraft_removed" => {
+                if let Some(icao) = &msg.fields.icao {
+                    self.remove(icao);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn update(&self, update: PositionUpdate, now: DateTime<Utc>) {
+        let Ok(mut aircraft) = self.aircraft.lock() else {
+            return;
+        };
+        let entry = aircraft.entry(update.icao.clone()).or_insert_with(|| CachedAircraft {
+            update: PositionUpdate::default(),
+            first_seen: now,
+            last_seen: now,
+            messages: 0,
+        });
+        entry.update = update;
+        entry.last_seen = now;
+        entry.messages += 1;
+    }
+
+    fn remove(&self, icao: &str) {
+        if let Ok(mut aircraft) = self.aircraft.lock() {
+            aircraft.remove(icao);
+        }
+    }
+
+    /// Aircraft heard within the look-back, most recently heard first;
+    /// older ones are forgotten
+    pub fn rows(&self, now: DateTime<Utc>) -> Vec<JsonValue> {
+        let Ok(mut aircraft) = self.aircraft.lock() else {
+            return Vec::new();
+        };
+        aircraft.retain(|_, a| (now - a.last_seen).num_seconds() < STALE_SECS);
+        let mut current: Vec<_> = aircraft.values().collect();
+        current.sort_by_key(|a| std::cmp::Reverse(a.last_seen));
+        current.into_iter().map(CachedAircraft::row).collect()
+    }
+
+    /// One aircraft's row with the device that last heard it and when it
+    /// was first and last heard, if it is in view
+    pub fn detail(&self, icao: &str, now: DateTime<Utc>) -> Option<JsonValue> {
+        let aircraft = self.aircraft.lock().ok()?;
+        let a = aircraft
+            .get(icao)
+            .filter(|a| (now - a.last_seen).num_seconds() < STALE_SECS)?;
+        let mut row = a.row();
+        row["device_id"] = serde_json::json!(a.update.device_id);
+        row["first_seen"] = serde_json::json!(a.first_seen.to_rfc3339());
+        row["last_seen"] = serde_json::json!(a.last_seen.to_rfc3339());
+        Some(row)
+    }
+
+    /// Callsign and category of every ICAO-addressed aircraft in view that
+    /// has either, for hosts labelling aircraft they haven't identified yet
+    pub fn labels(&self, now: DateTime<Utc>) -> Vec<AircraftLabel> {
+        let Ok(aircraft) = self.aircraft.lock() else {
+            return Vec::new();
+        };
+        aircraft
+            .values()
+            .filter(|a| (now - a.last_seen).num_seconds() < STALE_SECS && !a.update.icao.starts_with('~'))
+            .filter(|a| a.update.callsign.is_some() || a.update.category.is_some())
+            .map(|a| AircraftLabel {
+                icao: a.update.icao.clone(),
+                callsign: a.update.callsign.clone(),
+                category: a.update.category.clone(),
+            })
+            .collect()
+    }
+}
+
+/// Keep the cache up to date with the live broadcast
+pub fn spawn_updater(cache: Arc<AircraftCache>, pubsub: Arc<dyn PubSub>) {
+    let mut rx = pubsub.subscribe();
+    tokio::spawn(async move {
+        loop {
+            match rx.recv().await {
+                Ok(msg) => cache.apply(&msg, Utc::now()),
+                Err(broadcast::error::RecvError::Lagged(n)) => {
+                    debug!("Aircraft cache lagged by {} messages", n);
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_follows_broadcast() {
+        let cache = AircraftCache::new();
+        let apply = |json: &str, now| cache.apply(&LiveMessage::parse(json), now);
+        let t = DateTime::parse_from_rfc3339("2024-01-15T10:30:00Z").unwrap().with_timezone(&Utc);
+        let at = |secs| t + chrono::Duration::seconds(secs);
+
+        apply(
+            r#"{"type":"position_update","icao":"71BE11","device_id":"rtlsdr-0","lat":37.46,"lon":126.44,"altitude":35000,"callsign":"KAL123","timestamp_ms":0}"#,
+            t,
+        );
+        apply(r#"{"type":"position_update","icao":"71BE12","timestamp_ms":0}"#, at(10));
+        apply(r#"{"type":"signal","device_id":"rtlsdr-0"}"#, at(10));
+        apply(
+            r#"{"type":"position_update","icao":"71BE11","device_id":"rtlsdr-0","lat":37.47,"lon":126.45,"altitude":35100,"callsign":"KAL123","timestamp_ms":0}"#,
+            at(20),
+        );
+
+        let rows = cache.rows(at(30));
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0]["icao"], "71BE11");
+        assert_eq!((rows[0]["lat"].as_f64(), rows[0]["altitude"].as_i64()), (Some(37.47), Some(35100)));
+        assert_eq!(rows[0]["messages"], 2);
+        assert_eq!(rows[1]["lat"], JsonValue::Null);
+
+        let labels = cache.labels(at(30));
+        assert_eq!(labels.len(), 1);
+        assert_eq!((labels[0].icao.as_str(), labels[0].callsign.as_deref()), ("71BE11", Some("KAL123")));
+
+        let detail = cache.detail("71BE11", at(30)).unwrap();
+        assert_eq!(detail["device_id"], "rtlsdr-0");
+        assert_eq!(detail["first_seen"], t.to_rfc3339());
+
+        // Removed and silent aircraft drop out
+        apply(r#"{"type":"aircraft_removed","icao":"71BE12","reason":"tracker"}"#, at(30));
+        assert_eq!(cache.rows(at(30)).len(), 1);
+        assert!(cache.rows(at(20 + STALE_SECS)).is_empty());
+        assert!(cache.detail("71BE11", at(30)).is_none());
+    }
+}
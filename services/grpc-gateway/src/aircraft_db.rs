@@ -0,0 +1,285 @@
+//! Aircraft metadata enrichment
+//!
+//! Loads a local aircraft database keyed by ICAO address and joins
+//! registration, type and operator onto API responses and WebSocket
+//! messages. Supported inputs:
+//!
+//! - BaseStation `.sqb` (SQLite, `Aircraft` table)
+//! - OpenSky `aircraftDatabase.csv` (header row with `icao24`, ...)
+//! - Mictronics/tar1090-db `aircraft.csv` (`icao;reg;type;flags;desc;...`)
+//!
+//! CSV files may be gzip-compressed. The whole table is held in
+//! memory; lookups are a hash map read.
+
+use anyhow::{anyhow, Result};
+use serde_json::Value as JsonValue;
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+
+/// Metadata for a single airframe
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AircraftMeta {
+    pub registration: Option<String>,
+    /// ICAO type designator (e.g. "A320")
+    pub aircraft_type: Option<String>,
+    /// Human-readable model (e.g. "Airbus A320-214")
+    pub model: Option<String>,
+    pub operator: Option<String>,
+}
+
+impl AircraftMeta {
+    fn is_empty(&self) -> bool {
+        self.registration.is_none()
+            && self.aircraft_type.is_none()
+            && self.model.is_none()
+            && self.operator.is_none()
+    }
+}
+
+/// In-memory aircraft database
+#[derive(Default)]
+pub struct AircraftDb {
+    entries: RwLock<Arc<HashMap<String, AircraftMeta>>>,
+}
+
+impl AircraftDb {
+    /// Empty database (enrichment disabled)
+    pub fn empty() -> Self {
+        Self::default()
+    }
+
+    /// Load a database file, detecting the format from its name/contents
+    pub fn load(path: &Path) -> Result<Self> {
+        let db = Self::empty();
+        db.replace(load_file(path)?);
+        Ok(db)
+    }
+
+    /// Number of aircraft in the database
+    pub fn len(&self) -> usize {
+        self.entries.read().map(|e| e.len()).unwrap_or(0)
+    }
+
+    /// Whether the database has no entries
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Swap in a freshly loaded table
+    pub fn replace(&self, entries: HashMap<String, AircraftMeta>) {
+        if let Ok(mut current) = self.entries.write() {
+            *current = Arc::new(entries);
+        }
+    }
+
+    /// Look up metadata for an ICAO address (case-insensitive)
+    pub fn lookup(&self, icao: &str) -> Option<AircraftMeta> {
+        let entries = self.entries.read().ok()?;
+        entries.get(&icao.to_ascii_uppercase()).cloned()
+    }
+
+    /// Add metadata fields to a JSON object that has an `icao` key
+    pub fn enrich(&self, value: &mut JsonValue) {
+        let meta = match value.get("icao").and_then(|v| v.as_str()) {
+            Some(icao) => self.lookup(icao),
+            None => return,
+        };
+        if let (Some(meta), Some(obj)) = (meta, value.as_object_mut()) {
+            obj.insert("registration".into(), meta.registration.into());
+            obj.insert("aircraft_type".into(), meta.aircraft_type.into());
+            obj.insert("model".into(), meta.model.into());
+            obj.insert("operator".into(), meta.operator.into());
+        }
+    }
+}
+
+/// Load and parse a database file into a lookup table
+pub fn load_file(path: &Path) -> Result<HashMap<String, AircraftMeta>> {
+    let name = path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+
+    if name.ends_with(".sqb") || name.ends_with(".sqlite") || name.ends_with(".db") {
+        return load_basestation(path);
+    }
+
+    // Detect gzip by magic bytes, downloaded copies may not keep the extension
+    let mut file = BufReader::new(std::fs::File::open(path)?);
+    let is_gzip = file.fill_buf()?.starts_with(&[0x1f, 0x8b]);
+    let reader: Box<dyn Read> = if is_gzip {
+        Box::new(flate2::read::GzDecoder::new(file))
+    } else {
+        Box::new(file)
+    };
+    load_csv(BufReader::new(reader))
+}
+
+/// Read the `Aircraft` table of a BaseStation.sqb file
+fn load_basestation(path: &Path) -> Result<HashMap<String, AircraftMeta>> {
+    let conn = rusqlite::Connection::open_with_flags(
+        path,
+        rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY,
+    )?;
+    let mut stmt = conn.prepare(
+        "SELECT ModeS, Registration, ICAOTypeCode, Type, RegisteredOwners FROM Aircraft",
+    )?;
+
+    let mut entries = HashMap::new();
+    let rows = stmt.query_map([], |row| {
+        Ok((
+            row.get::<_, Option<String>>(0)?,
+            AircraftMeta {
+                registration: clean(row.get::<_, Option<String>>(1)?),
+                aircraft_type: clean(row.get::<_, Option<String>>(2)?),
+                model: clean(row.get::<_, Option<String>>(3)?),
+                operator: clean(row.get::<_, Option<String>>(4)?),
+            },
+        ))
+    })?;
+    for row in rows {
+        let (icao, meta) = row?;
+        insert(&mut entries, icao.as_deref().unwrap_or_default(), meta);
+    }
+    Ok(entries)
+}
+
+/// Parse OpenSky (headered) or tar1090-db (headerless, `;`) CSV
+fn load_csv<R: BufRead>(mut reader: R) -> Result<HashMap<String, AircraftMeta>> {
+    let mut first_line = String::new();
+    reader.read_line(&mut first_line)?;
+    if first_line.trim().is_empty() {
+        return Err(anyhow!("aircraft database is empty"));
+    }
+
+    let delimiter = if first_line.matches(';').count() > first_line.matches(',').count() {
+        b';'
+    } else {
+        b','
+    };
+    // Newer OpenSky dumps quote fields with single quotes
+    let quote = if first_line.starts_with('\'') { b'\'' } else { b'"' };
+    let has_header = first_line.to_ascii_lowercase().contains("icao24");
+
+    let mut csv = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .quote(quote)
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(first_line.as_bytes().chain(reader));
+
+    let mut records = csv.records();
+    let columns = if has_header {
+        let header = records.next().ok_or_else(|| anyhow!("missing header"))??;
+        let find = |name: &str| header.iter().position(|h| h.trim().eq_ignore_ascii_case(name));
+        CsvColumns {
+            icao: find("icao24").ok_or_else(|| anyhow!("missing icao24 column"))?,
+            registration: find("registration"),
+            aircraft_type: find("typecode"),
+            model: find("model"),
+            operator: find("operator").or_else(|| find("owner")),
+        }
+    } else {
+        // tar1090-db: icao;reg;type;flags;desc;year;ownop
+        CsvColumns {
+            icao: 0,
+            registration: Some(1),
+            aircraft_type: Some(2),
+            model: Some(4),
+            operator: Some(6),
+        }
+    };
+
+    let mut entries = HashMap::new();
+    for record in records {
+        let record = match record {
+            Ok(r) => r,
+            Err(_) => continue,
+        };
+        let field = |idx: Option<usize>| clean(idx.and_then(|i| record.get(i)).map(str::to_string));
+        let meta = AircraftMeta {
+            registration: field(columns.registration),
+            aircraft_type: field(columns.aircraft_type),
+            model: field(columns.model),
+            operator: field(columns.operator),
+        };
+        insert(&mut entries, record.get(columns.icao).unwrap_or_default(), meta);
+    }
+
+    if entries.is_empty() {
+        return Err(anyhow!("no aircraft records found"));
+    }
+    Ok(entries)
+}
+
+/// Column positions for a CSV layout
+struct CsvColumns {
+    icao: usize,
+    registration: Option<usize>,
+    aircraft_type: Option<usize>,
+    model: Option<usize>,
+    operator: Option<usize>,
+}
+
+fn insert(entries: &mut HashMap<String, AircraftMeta>, icao: &str, meta: AircraftMeta) {
+    let icao = icao.trim().to_ascii_uppercase();
+    if icao.len() == 6 && icao.chars().all(|c| c.is_ascii_hexdigit()) && !meta.is_empty() {
+        entries.insert(icao, meta);
+    }
+}
+
+/// Trim and drop empty values
+fn clean(value: Option<String>) -> Option<String> {
+    value
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_opensky_csv() {
+        let data = "'icao24','registration','manufacturericao','manufacturername','model','typecode','operator'\n\
+                    '3c6444','D-AIBD','AIRBUS','Airbus','A319 112','A319','Lufthansa'\n\
+                    'zzzzzz','BAD','','','','',''\n";
+        let entries = load_csv(data.as_bytes()).unwrap();
+        assert_eq!(entries.len(), 1);
+        let meta = &entries["3C6444"];
+        assert_eq!(meta.registration.as_deref(), Some("D-AIBD"));
+        assert_eq!(meta.aircraft_type.as_deref(), Some("A319"));
+        assert_eq!(meta.operator.as_deref(), Some("Lufthansa"));
+    }
+
+    #[test]
+    fn test_tar1090_csv() {
+        let data = "71be11;HL7611;B77W;00;BOEING 777-300ER;2013;Korean Air\n\
+                    ae01ce;;C130;01;LOCKHEED C-130 Hercules;;\n";
+        let entries = load_csv(data.as_bytes()).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries["71BE11"].model.as_deref(), Some("BOEING 777-300ER"));
+        assert_eq!(entries["AE01CE"].registration, None);
+    }
+
+    #[test]
+    fn test_enrich_json() {
+        let db = AircraftDb::empty();
+        let mut entries = HashMap::new();
+        entries.insert(
+            "71BE11".to_string(),
+            AircraftMeta {
+                registration: Some("HL7611".into()),
+                ..Default::default()
+            },
+        );
+        db.replace(entries);
+
+        let mut value = serde_json::json!({"icao": "71be11"});
+        db.enrich(&mut value);
+        assert_eq!(value["registration"], "HL7611");
+    }
+}
@@ -0,0 +1,210 @@
+//! Background download and refresh of the aircraft database
+//!
+//! Periodically fetches the configured database URL (e.g. the tar1090-db
+//! `aircraft.csv.gz`). Conditional requests use the last ETag so unchanged
+//! files are not re-downloaded. A new download is written to a temporary
+//! file, optionally checked against a published SHA-256, parsed, and only
+//! then renamed over the old file and swapped into the live lookup table.
+
+use crate::aircraft_db::{self, AircraftDb};
+use anyhow::{anyhow, Result};
+use reqwest::header::{ETAG, IF_NONE_MATCH};
+use reqwest::StatusCode;
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+/// Reject a new database that has fewer than this fraction of the current entries
+const MIN_RETAINED_FRACTION: f64 = 0.5;
+
+/// Timeout for a single download
+const DOWNLOAD_TIMEOUT_SECS: u64 = 300;
+
+/// Refresh settings
+#[derive(Debug, Clone)]
+pub struct RefreshConfig {
+    /// Database download URL
+    pub url: String,
+    /// Optional URL of a `sha256sum`-style checksum for the database file
+    pub sha256_url: Option<String>,
+    /// Local copy of the database (loaded on startup)
+    pub path: PathBuf,
+    /// Time between refresh checks
+    pub interval: Duration,
+}
+
+/// Result of a refresh attempt
+#[derive(Debug, PartialEq, Eq)]
+enum Outcome {
+    NotModified,
+    Updated(usize),
+}
+
+/// Start the refresh task (first check runs immediately)
+pub fn spawn_refresher(db: Arc<AircraftDb>, config: RefreshConfig) {
+    tokio::spawn(async move {
+        let client = match reqwest::Client::builder()
+            .timeout(Duration::from_secs(DOWNLOAD_TIMEOUT_SECS))
+            .build()
+        {
+            Ok(client) => client,
+            Err(e) => {
+                warn!("Aircraft database refresh disabled: {}", e);
+                return;
+            }
+        };
+
+        let mut interval = tokio::time::interval(config.interval);
+        loop {
+            interval.tick().await;
+            match refresh(&client, &db, &config).await {
+                Ok(Outcome::NotModified) => debug!("Aircraft database is up to date"),
+                Ok(Outcome::Updated(count)) => {
+                    info!("Aircraft database refreshed: {} aircraft", count)
+                }
+                Err(e) => warn!("Aircraft database refresh failed: {}", e),
+            }
+        }
+    });
+}
+
+/// Download the database if it changed and swap it in
+async fn refresh(client: &reqwest::Client, db: &AircraftDb, config: &RefreshConfig) -> Result<Outcome> {
+    let etag_path = sidecar(&config.path, "etag");
+    let etag = if config.path.exists() {
+        tokio::fs::read_to_string(&etag_path).await.ok()
+    } else {
+        None
+    };
+
+    let mut request = client.get(&config.url);
+    if let Some(etag) = etag.as_deref().map(str::trim).filter(|e| !e.is_empty()) {
+        request = request.header(IF_NONE_MATCH, etag);
+    }
+
+    let response = request.send().await?;
+    if response.status() == StatusCode::NOT_MODIFIED {
+        return Ok(Outcome::NotModified);
+    }
+    let response = response.error_for_status()?;
+    let new_etag = response
+        .headers()
+        .get(ETAG)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+    let body = response.bytes().await?;
+
+    if let Some(sha256_url) = &config.sha256_url {
+        let expected = client
+            .get(sha256_url)
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+        verify_sha256(&body, &expected)?;
+    }
+
+    let path = config.path.clone();
+    let current = db.len();
+    let entries = tokio::task::spawn_blocking(move || -> Result<_> {
+        let tmp = sidecar(&path, "tmp");
+        write_synced(&tmp, &body)?;
+
+        // Parse before replacing so a truncated or corrupt download never
+        // overwrites a good copy
+        let entries = match aircraft_db::load_file(&tmp) {
+            Ok(entries) => entries,
+            Err(e) => {
+                let _ = std::fs::remove_file(&tmp);
+                return Err(e);
+            }
+        };
+        if (entries.len() as f64) < current as f64 * MIN_RETAINED_FRACTION {
+            let _ = std::fs::remove_file(&tmp);
+            return Err(anyhow!(
+                "downloaded database has {} aircraft (currently {}), keeping old copy",
+                entries.len(),
+                current
+            ));
+        }
+
+        std::fs::rename(&tmp, &path)?;
+        Ok(entries)
+    })
+    .await??;
+
+    let count = entries.len();
+    db.replace(entries);
+
+    let etag_path = sidecar(&config.path, "etag");
+    match new_etag {
+        Some(etag) => tokio::fs::write(&etag_path, etag).await?,
+        None => {
+            let _ = tokio::fs::remove_file(&etag_path).await;
+        }
+    }
+
+    Ok(Outcome::Updated(count))
+}
+
+/// `<path>.<suffix>` next to the database file
+fn sidecar(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(".");
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+/// Write a file and flush it to disk before it is renamed into place
+fn write_synced(path: &Path, data: &[u8]) -> std::io::Result<()> {
+    use std::io::Write;
+
+    if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut file = std::fs::File::create(path)?;
+    file.write_all(data)?;
+    file.sync_all()
+}
+
+/// Compare data against the first token of a `sha256sum` line
+fn verify_sha256(data: &[u8], expected: &str) -> Result<()> {
+    let expected = expected
+        .split_whitespace()
+        .next()
+        .ok_or_else(|| anyhow!("empty checksum"))?
+        .to_ascii_lowercase();
+    let actual: String = Sha256::digest(data)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+
+    if actual != expected {
+        return Err(anyhow!("checksum mismatch: expected {}, got {}", expected, actual));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verify_sha256() {
+        let sum = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824  aircraft.csv.gz\n";
+        assert!(verify_sha256(b"hello", sum).is_ok());
+        assert!(verify_sha256(b"hello!", sum).is_err());
+        assert!(verify_sha256(b"hello", "").is_err());
+    }
+
+    #[test]
+    fn test_sidecar() {
+        assert_eq!(
+            sidecar(Path::new("/data/aircraft.csv.gz"), "etag"),
+            PathBuf::from("/data/aircraft.csv.gz.etag")
+        );
+    }
+}
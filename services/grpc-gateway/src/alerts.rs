@@ -0,0 +1,450 @@
+//! Watchlists and alert engine
+//!
+//! Watchlist rules match aircraft by ICAO address, registration, callsign,
+//! squawk (glob patterns with `*` and `?`), by position inside a geofence, or
+//! by a military or special-purpose address.
+//! Every aircraft event is checked against the rules; a match is broadcast
+//! to WebSocket clients as an `alert` message and delivered to the rule's
+//! notifier sinks. Each rule/aircraft pair fires at most once per cooldown.
+
+use crate::adsb::AircraftEvent;
+use crate::aircraft_db::AircraftMeta;
+use crate::geo::Geofence;
+use crate::military::AddressClass;
+use crate::notifiers::{Dispatcher, NotifierConfig};
+use crate::pubsub::{LiveMessage, PubSub};
+use crate::storage::Storage;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+use tracing::info;
+use utoipa::ToSchema;
+
+/// Cooldown used when a rule doesn't set one
+pub const DEFAULT_COOLDOWN_SECONDS: i32 = 600;
+
+/// Prune the cooldown map once it grows past this
+const MAX_COOLDOWN_ENTRIES: usize = 10_000;
+
+/// What a watchlist rule matches on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchKind {
+    Icao,
+    Registration,
+    Callsign,
+    Squawk,
+    Geofence,
+    /// Address in a military block
+    Military,
+    /// ICAO temporary or special-use address
+    Special,
+}
+
+impl MatchKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Icao => "icao",
+            Self::Registration => "registration",
+            Self::Callsign => "callsign",
+            Self::Squawk => "squawk",
+            Self::Geofence => "geofence",
+            Self::Military => "military",
+            Self::Special => "special",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "icao" => Some(Self::Icao),
+            "registration" => Some(Self::Registration),
+            "callsign" => Some(Self::Callsign),
+            "squawk" => Some(Self::Squawk),
+            "geofence" => Some(Self::Geofence),
+            "military" => Some(Self::Military),
+            "special" => Some(Self::Special),
+            _ => None,
+        }
+    }
+}
+
+/// A stored watchlist rule
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct WatchRule {
+    pub id: i64,
+    pub name: String,
+    pub kind: MatchKind,
+    /// Glob pattern for ICAO/registration/callsign/squawk rules
+    pub pattern: Option<String>,
+    /// Area for geofence rules
+    pub geofence: Option<Geofence>,
+    /// Plain webhook to POST alerts to (shorthand for a `webhook` notifier)
+    pub webhook_url: Option<String>,
+    /// Notifier sinks (falls back to the gateway defaults when empty)
+    pub notifiers: Vec<NotifierConfig>,
+    pub cooldown_seconds: i32,
+    pub enabled: bool,
+}
+
+/// Request body for creating a rule
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct NewWatchRule {
+    pub name: String,
+    pub kind: MatchKind,
+    pub pattern: Option<String>,
+    pub geofence: Option<Geofence>,
+    pub webhook_url: Option<String>,
+    #[serde(default)]
+    pub notifiers: Vec<NotifierConfig>,
+    pub cooldown_seconds: Option<i32>,
+}
+
+impl NewWatchRule {
+    /// Validate and normalize the rule
+    pub fn validate(mut self) -> Result<Self, String> {
+        if self.name.trim().is_empty() {
+            return Err("name is required".into());
+        }
+        match self.kind {
+            MatchKind::Geofence => {
+                let fence = self.geofence.as_ref().ok_or("geofence rules need a geofence")?;
+                fence.validate()?;
+                self.pattern = None;
+            }
+            MatchKind::Military | MatchKind::Special => {
+                self.pattern = None;
+                self.geofence = None;
+            }
+            _ => {
+                let pattern = self
+                    .pattern
+                    .as_deref()
+                    .map(|p| p.trim().to_ascii_uppercase())
+                    .filter(|p| !p.is_empty())
+                    .ok_or("pattern is required")?;
+                self.pattern = Some(pattern);
+                self.geofence = None;
+            }
+        }
+        if let Some(cooldown) = self.cooldown_seconds {
+            if cooldown < 0 {
+                return Err("cooldown_seconds must not be negative".into());
+            }
+        }
+        self.webhook_url = self.webhook_url.filter(|u| !u.trim().is_empty());
+        for notifier in &self.notifiers {
+            notifier.validate()?;
+        }
+        Ok(self)
+    }
+}
+
+impl WatchRule {
+    /// All sinks for this rule, including the `webhook_url` shorthand
+    pub fn sinks(&self) -> Vec<NotifierConfig> {
+        let mut sinks = self.notifiers.clone();
+        if let Some(url) = &self.webhook_url {
+            sinks.push(NotifierConfig::Webhook {
+                url: url.clone(),
+                template: None,
+            });
+        }
+        sinks
+    }
+
+    /// Whether the rule matches an event (with optional aircraft metadata
+    /// and the address's class)
+    pub fn matches(
+        &self,
+        event: &AircraftEvent,
+        meta: Option<&AircraftMeta>,
+        class: AddressClass,
+    ) -> bool {
+        if !self.enabled {
+            return false;
+        }
+        let pattern = self.pattern.as_deref().unwrap_or_default();
+        match self.kind {
+            MatchKind::Icao => glob_match(pattern, &event.icao),
+            MatchKind::Callsign => event
+                .callsign
+                .as_deref()
+                .is_some_and(|callsign| !callsign.is_empty() && glob_match(pattern, callsign.trim())),
+            MatchKind::Squawk => event
+                .squawk
+                .as_deref()
+                .is_some_and(|squawk| !squawk.is_empty() && glob_match(pattern, squawk)),
+            MatchKind::Registration => meta
+                .and_then(|m| m.registration.as_deref())
+                .is_some_and(|reg| glob_match(pattern, reg)),
+            MatchKind::Geofence => event.position().is_some_and(|(lat, lon)| {
+                self.geofence.as_ref().is_some_and(|f| f.contains(lat, lon))
+            }),
+            MatchKind::Military => class == AddressClass::Military,
+            MatchKind::Special => class == AddressClass::Special,
+        }
+    }
+}
+
+/// Case-insensitive glob match supporting `*` and `?`
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let p: Vec<char> = pattern.to_ascii_uppercase().chars().collect();
+    let t: Vec<char> = text.to_ascii_uppercase().chars().collect();
+
+    let (mut pi, mut ti) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while ti < t.len() {
+        if pi < p.len() && (p[pi] == '?' || p[pi] == t[ti]) {
+            pi += 1;
+            ti += 1;
+        } else if pi < p.len() && p[pi] == '*' {
+            star = Some((pi, ti));
+            pi += 1;
+        } else if let Some((sp, st)) = star {
+            pi = sp + 1;
+            ti = st + 1;
+            star = Some((sp, st + 1));
+        } else {
+            return false;
+        }
+    }
+    p[pi..].iter().all(|&c| c == '*')
+}
+
+/// Evaluates rules against the event stream and dispatches alerts
+pub struct AlertEngine {
+    rules: RwLock<Vec<WatchRule>>,
+    last_fired: Mutex<HashMap<(i64, String), Instant>>,
+    pubsub: Arc<dyn PubSub>,
+    dispatcher: Arc<Dispatcher>,
+}
+
+impl AlertEngine {
+    pub fn new(pubsub: Arc<dyn PubSub>, dispatcher: Arc<Dispatcher>) -> Self {
+        Self {
+            rules: RwLock::new(Vec::new()),
+            last_fired: Mutex::new(HashMap::new()),
+            pubsub,
+            dispatcher,
+        }
+    }
+
+    /// Reload rules from storage
+    pub async fn reload(&self, db_writer: &dyn Storage) -> Result<usize> {
+        let rules = db_writer.get_watchlist().await?;
+        let count = rules.len();
+        if let Ok(mut current) = self.rules.write() {
+            *current = rules;
+        }
+        Ok(count)
+    }
+
+    /// Check an event against all rules and fire alerts for new matches
+    pub fn check(&self, event: &AircraftEvent, meta: Option<&AircraftMeta>, class: AddressClass) {
+        let matched: Vec<WatchRule> = match self.rules.read() {
+            Ok(rules) => rules
+                .iter()
+                .filter(|r| r.matches(event, meta, class))
+                .cloned()
+                .collect(),
+            Err(_) => return,
+        };
+
+        for rule in matched {
+            if self.in_cooldown(&rule, &event.icao) {
+                continue;
+            }
+            self.fire(&rule, event, meta, class);
+        }
+    }
+
+    /// Record a firing; returns true if the rule/aircraft pair is still cooling down
+    fn in_cooldown(&self, rule: &WatchRule, icao: &str) -> bool {
+        let mut last_fired = match self.last_fired.lock() {
+            Ok(map) => map,
+            Err(_) => return true,
+        };
+        let now = Instant::now();
+        let cooldown = Duration::from_secs(rule.cooldown_seconds.max(0) as u64);
+        let key = (rule.id, icao.to_string());
+
+        if let Some(fired) = last_fired.get(&key) {
+            if now.duration_since(*fired) < cooldown {
+                return true;
+            }
+        }
+
+        if last_fired.len() >= MAX_COOLDOWN_ENTRIES {
+            let max_age = Duration::from_secs(24 * 3600);
+            last_fired.retain(|_, fired| now.duration_since(*fired) < max_age);
+        }
+        last_fired.insert(key, now);
+        false
+    }
+
+    fn fire(
+        &self,
+        rule: &WatchRule,
+        event: &AircraftEvent,
+        meta: Option<&AircraftMeta>,
+        class: AddressClass,
+    ) {
+        info!("Alert '{}' ({}) matched {}", rule.name, rule.kind.as_str(), event.icao);
+
+        let alert = alert_json(rule, event, meta, class);
+        if self.pubsub.has_subscribers() {
+            self.pubsub.publish(LiveMessage::from_value(&alert));
+        }
+
+        self.dispatcher.dispatch(alert, &rule.sinks());
+    }
+}
+
+/// Alert payload (WebSocket message and notifier template fields)
+fn alert_json(
+    rule: &WatchRule,
+    event: &AircraftEvent,
+    meta: Option<&AircraftMeta>,
+    class: AddressClass,
+) -> JsonValue {
+    serde_json::json!({
+        "type": "alert",
+        "rule_id": rule.id,
+        "rule_name": rule.name,
+        "match": rule.kind.as_str(),
+        "icao": event.icao,
+        "callsign": event.callsign,
+        "registration": meta.and_then(|m| m.registration.clone()),
+        "squawk": event.squawk,
+        "military": class == AddressClass::Military,
+        "special": class == AddressClass::Special,
+        "lat": event.latitude,
+        "lon": event.longitude,
+        "altitude": event.altitude_ft,
+        "device_id": event.device_id,
+        "timestamp_ms": event.timestamp_ms,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pubsub::LocalPubSub;
+
+    fn rule(kind: MatchKind, pattern: &str) -> WatchRule {
+        WatchRule {
+            id: 1,
+            name: "test".into(),
+            kind,
+            pattern: Some(pattern.into()),
+            geofence: None,
+            webhook_url: None,
+            notifiers: Vec::new(),
+            cooldown_seconds: DEFAULT_COOLDOWN_SECONDS,
+            enabled: true,
+        }
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("KAL*", "KAL123"));
+        assert!(glob_match("kal*", "KAL123"));
+        assert!(glob_match("75??", "7500"));
+        assert!(glob_match("*", ""));
+        assert!(glob_match("A*B*C", "AxxBxxC"));
+        assert!(!glob_match("KAL*", "AAR123"));
+        assert!(!glob_match("75??", "750"));
+    }
+
+    #[test]
+    fn test_rule_matches() {
+        let event = AircraftEvent {
+            icao: "71BE11".into(),
+            callsign: Some("KAL017".into()),
+            squawk: Some("7700".into()),
+            latitude: Some(37.5),
+            longitude: Some(126.5),
+            ..Default::default()
+        };
+        let meta = AircraftMeta {
+            registration: Some("HL7611".into()),
+            ..Default::default()
+        };
+
+        assert!(rule(MatchKind::Icao, "71BE11").matches(&event, None, AddressClass::Civil));
+        assert!(rule(MatchKind::Callsign, "KAL*").matches(&event, None, AddressClass::Civil));
+        assert!(rule(MatchKind::Squawk, "7700").matches(&event, None, AddressClass::Civil));
+        assert!(rule(MatchKind::Registration, "HL76*").matches(&event, Some(&meta), AddressClass::Civil));
+        assert!(!rule(MatchKind::Registration, "HL76*").matches(&event, None, AddressClass::Civil));
+
+        let mut fence = rule(MatchKind::Geofence, "");
+        fence.geofence = Some(Geofence::Circle {
+            lat: 37.5,
+            lon: 126.5,
+            radius_km: 5.0,
+        });
+        assert!(fence.matches(&event, None, AddressClass::Civil));
+
+        let mut disabled = rule(MatchKind::Icao, "*");
+        disabled.enabled = false;
+        assert!(!disabled.matches(&event, None, AddressClass::Civil));
+
+        let military = rule(MatchKind::Military, "");
+        assert!(military.matches(&event, None, AddressClass::Military));
+        assert!(!military.matches(&event, None, AddressClass::Special));
+        assert!(rule(MatchKind::Special, "").matches(&event, None, AddressClass::Special));
+    }
+
+    #[test]
+    fn test_cooldown() {
+        let engine = AlertEngine::new(
+            Arc::new(LocalPubSub::new()),
+            Arc::new(Dispatcher::new(Vec::new())),
+        );
+        let r = rule(MatchKind::Icao, "*");
+        assert!(!engine.in_cooldown(&r, "71BE11"));
+        assert!(engine.in_cooldown(&r, "71BE11"));
+        assert!(!engine.in_cooldown(&r, "71BE12"));
+    }
+
+    #[test]
+    fn test_validate_new_rule() {
+        let new = NewWatchRule {
+            name: "Korean Air".into(),
+            kind: MatchKind::Callsign,
+            pattern: Some(" kal* ".into()),
+            geofence: None,
+            webhook_url: Some(String::new()),
+            notifiers: Vec::new(),
+            cooldown_seconds: None,
+        };
+        let new = new.validate().unwrap();
+        assert_eq!(new.pattern.as_deref(), Some("KAL*"));
+        assert_eq!(new.webhook_url, None);
+
+        let missing_fence = NewWatchRule {
+            name: "Airport".into(),
+            kind: MatchKind::Geofence,
+            pattern: None,
+            geofence: None,
+            webhook_url: None,
+            notifiers: Vec::new(),
+            cooldown_seconds: None,
+        };
+        assert!(missing_fence.validate().is_err());
+
+        let military = NewWatchRule {
+            name: "Military".into(),
+            kind: MatchKind::Military,
+            pattern: Some("ignored".into()),
+            geofence: None,
+            webhook_url: None,
+            notifiers: Vec::new(),
+            cooldown_seconds: None,
+        };
+        assert_eq!(military.validate().unwrap().pattern, None);
+    }
+}
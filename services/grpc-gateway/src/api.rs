@@ -0,0 +1,686 @@
+//! REST API response types and OpenAPI document
+//!
+//! Every `/api` handler answers with one of the types here or a domain type
+//! deriving `ToSchema`. Storage backends build their rows as JSON, which
+//! [`from_rows`] turns into the typed structs so responses always match the
+//! document. The `#[utoipa::path]` annotations on the handlers feed
+//! [`ApiDoc`], served as `/api/openapi.json` with Swagger UI at `/api/docs`.
+//! The same types, loaded by the `AppState` methods below, back the GraphQL
+//! schema.
+
+use crate::admin::{DeviceRename, PurgeResult, RenameResult};
+use crate::alerts::{MatchKind, NewWatchRule, WatchRule};
+use crate::coverage::Coverage;
+use crate::decoder_config::DecoderSettings;
+use crate::db_health::{DbHealth, PoolStats, TableHealth};
+use crate::emergencies::emergency_name;
+use crate::feeders::{self, RankBy, RankedReceiver, ReceiverStats};
+use crate::filters::AircraftFilter;
+use crate::flights::FlightRecord;
+use crate::geo::Geofence;
+use crate::geofences::{GeofenceDef, NewGeofence};
+use crate::ingest::{IngestResult, PositionBatch, PositionReport, RejectedReport};
+use crate::history::PositionPoint;
+use crate::interrogators::{InterrogatorActivity, InterrogatorReport};
+use crate::notifiers::NotifierConfig;
+use crate::public::PublicStats;
+use crate::recording::Recording;
+use crate::scan::{ScanPoint, ScanReport};
+use crate::signal::SignalSample;
+use crate::squawks::SquawkChange;
+use crate::stats::{
+    self, LiveRates, Period, RateWindow, Receiver, RollupSummary, StatsBucket, StatsRange,
+};
+use crate::tenants::Scope;
+use crate::uptime::{self, DeviceUptime, UptimeDay};
+use crate::AppState;
+use anyhow::Result;
+use async_graphql::SimpleObject;
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use std::collections::BTreeMap;
+use utoipa::openapi::security::{ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::{Modify, OpenApi, ToSchema};
+
+/// Path of the generated document
+pub const OPENAPI_PATH: &str = "/api/openapi.json";
+
+/// Path of the Swagger UI
+pub const DOCS_PATH: &str = "/api/docs";
+
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "ADS-B Flight Radar API",
+        description = "Live and historical aircraft data from the ADS-B gateway"
+    ),
+    paths(
+        crate::get_aircraft,
+        crate::get_aircraft_detail,
+        crate::get_aircraft_trail,
+        crate::get_position_history,
+        crate::export_positions,
+        crate::get_flights,
+        crate::recording::list_recordings,
+        crate::recording::get_recording,
+        crate::get_emergencies,
+        crate::squawks::get_squawk_changes,
+        crate::get_watchlist,
+        crate::create_watchlist_rule,
+        crate::delete_watchlist_rule,
+        crate::get_geofences,
+        crate::create_geofence,
+        crate::delete_geofence,
+        crate::get_geofence_events,
+        crate::get_stats,
+        crate::get_coverage,
+        crate::public::get_public_stats,
+        crate::feeders::get_receiver_stats,
+        crate::feeders::get_leaderboard,
+        crate::get_sdr_status,
+        crate::get_sdr_device_status,
+        crate::get_sdr_history,
+        crate::scan::get_sdr_scan,
+        crate::interrogators::get_sdr_interrogators,
+        crate::get_live_signal,
+        crate::db_health::get_db_health,
+        crate::admin::delete_positions,
+        crate::admin::rename_device,
+        crate::ingest::ingest_positions,
+    ),
+    components(schemas(
+        ApiError,
+        Aircraft,
+        AircraftDetail,
+        CurrentFlight,
+        TrailPoint,
+        HistoryPage,
+        PositionPoint,
+        Flight,
+        Recording,
+        ReceiverStats,
+        RankedReceiver,
+        RankBy,
+        Emergency,
+        SquawkChange,
+        WatchRule,
+        NewWatchRule,
+        MatchKind,
+        NotifierConfig,
+        Geofence,
+        GeofenceDef,
+        NewGeofence,
+        GeofenceEvent,
+        StatsResponse,
+        Receiver,
+        LiveRates,
+        RateWindow,
+        PublicStats,
+        StatsBucket,
+        RollupSummary,
+        UptimeDay,
+        DeviceUptime,
+        Coverage,
+        SdrStatus,
+        SdrHeartbeat,
+        DecoderSettings,
+        ScanReport,
+        ScanPoint,
+        InterrogatorReport,
+        InterrogatorActivity,
+        SignalSample,
+        DbHealth,
+        PoolStats,
+        TableHealth,
+        PurgeResult,
+        DeviceRename,
+        RenameResult,
+        PositionBatch,
+        PositionReport,
+        IngestResult,
+        RejectedReport,
+    )),
+    modifiers(&SecurityAddon),
+    tags(
+        (name = "aircraft", description = "Current aircraft and their tracks"),
+        (name = "history", description = "Stored positions and flights"),
+        (name = "alerts", description = "Emergencies, watchlist rules and geofences"),
+        (name = "receiver", description = "Receiver statistics, coverage and SDR status"),
+        (name = "admin", description = "Stored data maintenance (admin credentials only)"),
+        (name = "ingest", description = "Positions from other sources (feeder or admin credentials)"),
+    )
+)]
+pub struct ApiDoc;
+
+/// Registers the credentials accepted when `API_KEYS`/`JWT_SECRET` are set
+struct SecurityAddon;
+
+impl Modify for SecurityAddon {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "api_key",
+            SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::new("X-API-Key"))),
+        );
+        components.add_security_scheme(
+            "bearer",
+            SecurityScheme::Http(
+                HttpBuilder::new()
+                    .scheme(HttpAuthScheme::Bearer)
+                    .bearer_format("JWT")
+                    .build(),
+            ),
+        );
+    }
+}
+
+/// Canonical form of an ICAO address: uppercase hex without a `0x`
+/// prefix, keeping the `~` of non-ICAO addresses. Addresses are stored and
+/// looked up in this form, so lookups don't depend on how a client or
+/// capture host spelled them
+pub fn normalize_icao(icao: &str) -> String {
+    let icao = icao.trim();
+    let (anonymous, hex) = match icao.strip_prefix('~') {
+        Some(hex) => ("~", hex),
+        None => ("", icao),
+    };
+    let hex = hex.strip_prefix("0x").or_else(|| hex.strip_prefix("0X")).unwrap_or(hex);
+    format!("{}{}", anonymous, hex.to_ascii_uppercase())
+}
+
+/// Validate and normalize an ICAO address from a path or query
+pub fn parse_icao(icao: &str) -> Result<String, &'static str> {
+    let icao = normalize_icao(icao);
+    if icao.len() != 6 || !icao.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err("icao must be 6 hex digits");
+    }
+    Ok(icao)
+}
+
+/// Whether an event address is a capture host's `~`-prefixed non-ICAO
+/// address (anonymous, TIS-B track number). These change hands between
+/// targets, so they are broadcast live but never stored
+pub fn is_anonymous(icao: &str) -> bool {
+    icao.starts_with('~')
+}
+
+/// Convert rows built by a storage backend into their typed form
+pub fn from_rows<T: DeserializeOwned>(rows: Vec<JsonValue>) -> serde_json::Result<Vec<T>> {
+    rows.into_iter().map(serde_json::from_value).collect()
+}
+
+impl AppState {
+    /// Aircraft seen in the last five minutes, with enrichment
+    pub async fn current_aircraft(&self, filter: &AircraftFilter, scope: &Scope) -> Result<Vec<Aircraft>> {
+        let mut aircraft = match scope {
+            Scope::Public => self.public.aircraft(),
+            _ => self.aircraft_cache.rows(Utc::now()),
+        };
+        aircraft.retain(|a| filter.matches(a) && (*scope == Scope::Public || scope.allows(a)));
+        for a in aircraft.iter_mut() {
+            self.aircraft_db.enrich(a);
+            self.addresses.enrich(a);
+            self.routes.enrich(a);
+        }
+        aircraft.retain_mut(|a| self.share.api.apply(a));
+        Ok(from_rows(aircraft)?)
+    }
+
+    /// One aircraft's merged current state; `icao` must already be normalized
+    pub async fn aircraft_detail(&self, icao: &str, scope: &Scope) -> Result<Option<AircraftDetail>> {
+        // Aircraft that were never stored (no database, non-ICAO addresses)
+        // are still described while in view
+        let stored = self.db_writer.get_aircraft(icao).await?;
+        let Some(mut aircraft) = stored.or_else(|| self.aircraft_cache.detail(icao, Utc::now())) else {
+            return Ok(None);
+        };
+        if !scope.allows(&aircraft) {
+            return Ok(None);
+        }
+        self.aircraft_db.enrich(&mut aircraft);
+        self.addresses.enrich(&mut aircraft);
+        self.routes.enrich(&mut aircraft);
+        if !self.share.api.apply(&mut aircraft) {
+            return Ok(None);
+        }
+        let mut detail: AircraftDetail = serde_json::from_value(aircraft)?;
+        detail.emergency = self.emergencies.active(icao).map(|e| emergency_name(e).to_string());
+        detail.flight = self.flights.current(icao).map(CurrentFlight::from);
+        Ok(Some(detail))
+    }
+
+    /// Positions in `scope` from `from` to `to` (or now), oldest first, the
+    /// latest of each `bucket_secs` when given
+    pub async fn trail(
+        &self,
+        icao: &str,
+        from: DateTime<Utc>,
+        to: Option<DateTime<Utc>>,
+        bucket_secs: Option<i64>,
+        scope: &Scope,
+    ) -> Result<Vec<TrailPoint>> {
+        if !self.share.api.allows(icao) {
+            return Ok(Vec::new());
+        }
+        let devices = scope.devices();
+        let trail = self.db_writer.get_aircraft_trail(icao, devices.as_deref(), from, to, bucket_secs).await?;
+        Ok(from_rows(trail)?)
+    }
+
+    /// Flight sessions, most recent first, with enrichment
+    pub async fn flight_list(&self, icao: Option<&str>, limit: i64, scope: &Scope) -> Result<Vec<Flight>> {
+        let devices = scope.devices();
+        let mut flights = self.db_writer.get_flights(icao, devices.as_deref(), limit).await?;
+        for f in flights.iter_mut() {
+            self.aircraft_db.enrich(f);
+            self.addresses.enrich(f);
+            self.routes.enrich(f);
+        }
+        flights.retain_mut(|f| self.share.api.apply(f));
+        Ok(from_rows(flights)?)
+    }
+
+    /// Live rates plus the hourly and daily rollups and daily receiver
+    /// uptime in `range`
+    pub async fn stats_summary(&self, range: StatsRange) -> Result<StatsResponse> {
+        let now = Utc::now();
+        let mut hourly = self.db_writer.get_stats(Period::Hour, range.hourly_from).await?;
+        let mut daily = self.db_writer.get_stats(Period::Day, range.daily_from).await?;
+        hourly.retain(|row| range.includes(row.bucket));
+        daily.retain(|row| range.includes(row.bucket));
+        let mut sessions = self.db_writer.get_receiver_sessions(range.daily_from).await?;
+        sessions.extend(self.receivers.open_sessions());
+        let until = range.to.map_or(now, |to| to.min(now));
+        Ok(StatsResponse {
+            receiver: self.stats.receiver().map(Receiver::from),
+            live: self.stats.live(now),
+            hourly: stats::summarize(&hourly, now),
+            daily: stats::summarize(&daily, now),
+            uptime: uptime::daily(&sessions, range.daily_from, until),
+        })
+    }
+
+    /// Each receiver's contribution over the last `days`, counting today
+    pub async fn receiver_stats(&self, days: i64) -> Result<BTreeMap<String, ReceiverStats>> {
+        let now = Utc::now();
+        let from = Period::Day.start(now) - chrono::Duration::days(days - 1);
+        let daily = self.db_writer.get_stats(Period::Day, from).await?;
+        let open = self.receivers.open_sessions();
+        let mut sessions = self.db_writer.get_receiver_sessions(from).await?;
+        sessions.extend(open.iter().cloned());
+        let mut receivers = feeders::collect(&daily, &uptime::daily(&sessions, from, now), now);
+        for session in &open {
+            if let Some(stats) = receivers.get_mut(&session.device_id) {
+                stats.connected = true;
+            }
+        }
+        Ok(receivers)
+    }
+
+    /// Every capture device's current status, by device ID
+    pub async fn sdr_devices(&self) -> Result<BTreeMap<String, SdrStatus>> {
+        let devices: Vec<SdrStatus> = from_rows(self.db_writer.get_sdr_status().await?)?;
+        Ok(devices
+            .into_iter()
+            .map(|mut device| {
+                device.clock_skewed = device
+                    .clock_offset_ms
+                    .is_some_and(|offset| offset.abs() > MAX_CLOCK_SKEW_MS);
+                (device.device_id.clone(), device)
+            })
+            .collect())
+    }
+
+    /// A device's heartbeats from the last `hours`, most recent first
+    pub async fn sdr_history(
+        &self,
+        device_id: &str,
+        hours: i64,
+        limit: i64,
+    ) -> Result<Vec<SdrHeartbeat>> {
+        let since = Utc::now() - chrono::Duration::hours(hours);
+        let rows = self.db_writer.get_sdr_history(device_id, since, limit).await?;
+        Ok(from_rows(rows)?)
+    }
+}
+
+/// Error body for every non-2xx JSON response
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ApiError {
+    pub error: String,
+}
+
+impl ApiError {
+    fn response(status: StatusCode, error: impl ToString) -> Response {
+        let body = Self {
+            error: error.to_string(),
+        };
+        (status, Json(body)).into_response()
+    }
+
+    pub fn bad_request(error: impl ToString) -> Response {
+        Self::response(StatusCode::BAD_REQUEST, error)
+    }
+
+    pub fn not_found(error: impl ToString) -> Response {
+        Self::response(StatusCode::NOT_FOUND, error)
+    }
+
+    /// Storage failures; the caller logs the details
+    pub fn internal(error: impl ToString) -> Response {
+        Self::response(StatusCode::INTERNAL_SERVER_ERROR, error)
+    }
+}
+
+/// Fields added from the aircraft database, address classes and route lookup
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema, SimpleObject)]
+pub struct Enrichment {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub registration: Option<String>,
+    /// ICAO type designator (e.g. "A320")
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub aircraft_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub operator: Option<String>,
+    /// Address in a military block
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub military: bool,
+    /// ICAO temporary or special-use address
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub special: bool,
+    /// Origin airport for the callsign
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub origin: Option<String>,
+    /// Destination airport for the callsign
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub destination: Option<String>,
+}
+
+/// An aircraft seen in the last five minutes
+#[derive(Debug, Serialize, Deserialize, ToSchema, SimpleObject)]
+#[graphql(complex)]
+pub struct Aircraft {
+    pub icao: String,
+    pub callsign: Option<String>,
+    pub lat: Option<f64>,
+    pub lon: Option<f64>,
+    /// Feet
+    pub altitude: Option<i32>,
+    /// Knots
+    pub speed: Option<f32>,
+    /// Ground track, degrees true
+    pub track: Option<f32>,
+    /// Where the nose points, degrees magnetic
+    pub magnetic_heading: Option<f32>,
+    /// Feet per minute, positive when climbing, from whichever source sent last
+    pub vrate: Option<i32>,
+    /// Barometric vertical rate, feet per minute
+    pub baro_rate: Option<i32>,
+    /// GNSS (geometric) vertical rate, feet per minute
+    pub geom_rate: Option<i32>,
+    pub squawk: Option<String>,
+    /// RFC 3339 time of the last message
+    pub seen: Option<String>,
+    pub messages: Option<i64>,
+    #[serde(flatten)]
+    #[graphql(flatten)]
+    pub enrichment: Enrichment,
+}
+
+/// One aircraft's merged current state
+#[derive(Debug, Serialize, Deserialize, ToSchema, SimpleObject)]
+pub struct AircraftDetail {
+    #[serde(flatten)]
+    #[graphql(flatten)]
+    pub aircraft: Aircraft,
+    /// ADS-B emitter category
+    pub category: Option<String>,
+    pub device_id: Option<String>,
+    pub first_seen: Option<String>,
+    pub last_seen: Option<String>,
+    /// Active emergency: `general`, `radio_failure` or `hijack`
+    pub emergency: Option<String>,
+    /// The flight in progress
+    pub flight: Option<CurrentFlight>,
+}
+
+/// Summary of an open flight
+#[derive(Debug, Serialize, Deserialize, ToSchema, SimpleObject)]
+pub struct CurrentFlight {
+    pub callsign: String,
+    pub first_seen: DateTime<Utc>,
+    pub duration_s: i64,
+    pub max_altitude: Option<i32>,
+    pub positions: i32,
+}
+
+impl From<FlightRecord> for CurrentFlight {
+    fn from(f: FlightRecord) -> Self {
+        Self {
+            duration_s: (f.last_seen - f.first_seen).num_seconds(),
+            callsign: f.callsign,
+            first_seen: f.first_seen,
+            max_altitude: f.max_altitude_ft,
+            positions: f.position_count,
+        }
+    }
+}
+
+/// A point on an aircraft's recent track
+#[derive(Debug, Serialize, Deserialize, ToSchema, SimpleObject)]
+pub struct TrailPoint {
+    pub time: String,
+    pub lat: f64,
+    pub lon: f64,
+    pub altitude: Option<i32>,
+    /// Stored positions in the point's time bucket, with `bucket_seconds`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub positions: Option<i64>,
+}
+
+/// One page of stored positions
+#[derive(Debug, Serialize, ToSchema)]
+pub struct HistoryPage {
+    pub positions: Vec<PositionPoint>,
+    /// Pass as `cursor` for the next page; absent on the last page
+    pub next_cursor: Option<String>,
+}
+
+/// A flight session
+#[derive(Debug, Serialize, Deserialize, ToSchema, SimpleObject)]
+pub struct Flight {
+    pub icao: String,
+    pub callsign: Option<String>,
+    pub first_seen: String,
+    pub last_seen: String,
+    pub duration_s: i64,
+    pub max_altitude: Option<i32>,
+    pub positions: Option<i32>,
+    pub device_id: Option<String>,
+    #[serde(flatten)]
+    #[graphql(flatten)]
+    pub enrichment: Enrichment,
+}
+
+/// An emergency squawk occurrence
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct Emergency {
+    pub icao: String,
+    pub emergency: String,
+    pub squawk: String,
+    pub callsign: Option<String>,
+    pub active: bool,
+    pub started_at: String,
+    pub last_seen: String,
+    pub ended_at: Option<String>,
+    pub lat: Option<f64>,
+    pub lon: Option<f64>,
+    pub altitude: Option<i32>,
+    pub device_id: Option<String>,
+    #[serde(flatten)]
+    pub enrichment: Enrichment,
+}
+
+/// A logged geofence entry or exit
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct GeofenceEvent {
+    pub time: String,
+    pub geofence_id: i64,
+    pub geofence_name: String,
+    pub icao: String,
+    pub callsign: Option<String>,
+    /// `enter` or `exit`
+    pub event: String,
+    /// Why an exit happened without a position outside (`timeout`)
+    pub reason: Option<String>,
+    pub lat: Option<f64>,
+    pub lon: Option<f64>,
+    pub altitude: Option<i32>,
+}
+
+/// Receiver statistics
+#[derive(Debug, Serialize, ToSchema, SimpleObject)]
+pub struct StatsResponse {
+    pub receiver: Option<Receiver>,
+    pub live: LiveRates,
+    pub hourly: Vec<StatsBucket>,
+    pub daily: Vec<StatsBucket>,
+    /// Share of each day every receiver was connected, per device
+    pub uptime: Vec<UptimeDay>,
+}
+
+/// Capture host clocks further off the gateway's are flagged in the device
+/// status
+pub const MAX_CLOCK_SKEW_MS: i64 = 2_000;
+
+/// SDR device status
+#[derive(Debug, Serialize, Deserialize, ToSchema, SimpleObject)]
+pub struct SdrStatus {
+    pub device_id: String,
+    pub connected: bool,
+    /// Samples per second
+    pub sample_rate: Option<i64>,
+    /// Hz
+    pub center_freq: Option<i64>,
+    pub gain_db: Option<f32>,
+    pub last_heartbeat: Option<String>,
+    pub messages_per_second: Option<f32>,
+    /// Capture host version, as of the last heartbeat
+    #[serde(default)]
+    pub software_version: Option<String>,
+    /// Seconds since the capture host started, as of the last heartbeat
+    #[serde(default)]
+    pub uptime_secs: Option<i64>,
+    /// Decoder setup, as of the last heartbeat
+    #[serde(default)]
+    pub decoder: Option<DecoderSettings>,
+    /// Gateway clock minus the capture host's, as the host last measured it
+    #[serde(default)]
+    pub clock_offset_ms: Option<i64>,
+    /// The host's clock is more than `MAX_CLOCK_SKEW_MS` off
+    #[serde(default)]
+    pub clock_skewed: bool,
+    /// `active`, `stale` or `disconnected`
+    pub status: String,
+}
+
+/// One status update received from a device
+#[derive(Debug, Serialize, Deserialize, ToSchema, SimpleObject)]
+pub struct SdrHeartbeat {
+    pub time: String,
+    pub connected: bool,
+    /// Samples per second
+    pub sample_rate: Option<i64>,
+    /// Hz
+    pub center_freq: Option<i64>,
+    pub gain_db: Option<f32>,
+    #[serde(default)]
+    pub software_version: Option<String>,
+    #[serde(default)]
+    pub decoder: Option<DecoderSettings>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_document_covers_routes() {
+        let doc = ApiDoc::openapi();
+        for path in [
+            "/api/aircraft",
+            "/api/aircraft/{icao}",
+            "/api/aircraft/{icao}/trail",
+            "/api/history/positions",
+            "/api/export/positions",
+            "/api/flights",
+            "/api/emergencies",
+            "/api/squawk-changes",
+            "/api/watchlist",
+            "/api/watchlist/{id}",
+            "/api/geofences",
+            "/api/geofences/events",
+            "/api/geofences/{id}",
+            "/api/stats",
+            "/api/coverage",
+            "/api/sdr/status",
+            "/api/sdr/{device_id}/status",
+            "/api/sdr/{device_id}/history",
+            "/api/sdr/{device_id}/scan",
+            "/api/sdr/{device_id}/interrogators",
+            "/api/signal/live",
+            "/api/admin/positions",
+            "/api/admin/devices/rename",
+        ] {
+            assert!(doc.paths.paths.contains_key(path), "{} missing", path);
+        }
+        let schemes = doc.components.unwrap().security_schemes;
+        assert!(schemes.contains_key("api_key") && schemes.contains_key("bearer"));
+    }
+
+    #[test]
+    fn test_from_rows() {
+        let rows = vec![json!({
+            "icao": "71BE11",
+            "callsign": "KAL123",
+            "lat": 37.46,
+            "lon": 126.44,
+            "altitude": 3000,
+            "speed": 250.0,
+            "track": null,
+            "vrate": null,
+            "squawk": "1234",
+            "seen": "2024-01-15T10:00:00+00:00",
+            "messages": 42,
+            "registration": "HL7611",
+        })];
+        let aircraft: Vec<Aircraft> = from_rows(rows).unwrap();
+        assert_eq!(aircraft[0].enrichment.registration.as_deref(), Some("HL7611"));
+
+        let json = serde_json::to_value(&aircraft[0]).unwrap();
+        assert_eq!(json["registration"], "HL7611");
+        assert_eq!(json["track"], JsonValue::Null);
+        assert!(json.get("origin").is_none());
+
+        assert!(from_rows::<Aircraft>(vec![json!({"callsign": "KAL123"})]).is_err());
+    }
+
+    #[test]
+    fn test_normalize_icao() {
+        assert_eq!(normalize_icao(" 71be11 "), "71BE11");
+        assert_eq!(normalize_icao("0x71be11"), "71BE11");
+        assert_eq!(normalize_icao("~abc123"), "~ABC123");
+        assert_eq!(parse_icao("0X4840d6"), Ok("4840D6".to_string()));
+        assert!(parse_icao("~abc123").is_err());
+        assert!(parse_icao("71be1").is_err());
+    }
+}
@@ -0,0 +1,70 @@
+//! Web UI assets built into the binary
+//!
+//! The `frontend/` directory is embedded at compile time so a single binary
+//! serves the map UI. Setting `STATIC_DIR` serves that directory instead
+//! (for a customised UI or tar1090). Responses carry an ETag from the
+//! embedded file's hash so browsers revalidate cheaply.
+
+use crate::response_cache::if_none_match;
+use axum::{
+    http::{header, HeaderMap, StatusCode, Uri},
+    response::{IntoResponse, Response},
+};
+use rust_embed::RustEmbed;
+
+#[derive(RustEmbed)]
+#[folder = "../../frontend/"]
+struct Frontend;
+
+/// Serve an embedded file; directories resolve to their `index.html`
+pub async fn embedded(uri: Uri, headers: HeaderMap) -> Response {
+    let mut path = uri.path().trim_start_matches('/').to_string();
+    if path.is_empty() || path.ends_with('/') {
+        path.push_str("index.html");
+    }
+    let Some(file) = Frontend::get(&path) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    let etag = format!(
+        "\"{}\"",
+        file.metadata.sha256_hash()[..8]
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect::<String>()
+    );
+    if if_none_match(&headers, &etag) {
+        return (StatusCode::NOT_MODIFIED, [(header::ETAG, etag)]).into_response();
+    }
+    (
+        [
+            (header::CONTENT_TYPE, file.metadata.mimetype().to_string()),
+            (header::ETAG, etag),
+        ],
+        file.data,
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_embedded() {
+        let index = embedded(Uri::from_static("/"), HeaderMap::new()).await;
+        assert_eq!(index.status(), StatusCode::OK);
+        assert_eq!(index.headers()[header::CONTENT_TYPE], "text/html");
+
+        let script = embedded(Uri::from_static("/js/api.js"), HeaderMap::new()).await;
+        assert_eq!(script.headers()[header::CONTENT_TYPE], "text/javascript");
+
+        let mut headers = HeaderMap::new();
+        headers.insert(header::IF_NONE_MATCH, script.headers()[header::ETAG].clone());
+        let revalidated = embedded(Uri::from_static("/js/api.js"), headers).await;
+        assert_eq!(revalidated.status(), StatusCode::NOT_MODIFIED);
+
+        let missing = embedded(Uri::from_static("/nope.js"), HeaderMap::new()).await;
+        assert_eq!(missing.status(), StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,464 @@
+//! REST API authentication
+//!
+//! The REST API is open unless `API_KEYS` or `JWT_SECRET` is set. Then every
+//! `/api` and tar1090 `/data` request (other than the `/api/stream` event
+//! stream, which uses `WS_AUTH_TOKEN` like `/ws`, and the public API docs)
+//! needs a credential:
+//!
+//! - `X-API-Key: <key>` or `Authorization: Bearer <key>` for a key from
+//!   `API_KEYS`, a JSON array such as
+//!   `[{"name": "ops", "key": "...", "role": "admin"}]`
+//! - `Authorization: Bearer <jwt>` for an HS256 token signed with
+//!   `JWT_SECRET`, with `sub`, `exp` and `role` claims
+//!
+//! `viewer` credentials may read; changes (any method other than GET/HEAD,
+//! except the read-only `POST /api/graphql`) and everything under
+//! `/api/admin` need `admin`. `feeder` credentials may also post to
+//! `/api/ingest`. `/api/admin` and `/api/ingest` are refused while the API is open. Each credential is rate limited to its `rate_per_minute`
+//! (default `API_RATE_LIMIT_PER_MINUTE`) with a token bucket. A key's
+//! `tenant`, or a JWT's `tenant` claim, limits the credential to one
+//! tenant's receivers as described in [`crate::tenants`]. With
+//! `PUBLIC_VIEWER` set, requests without a credential get the public view
+//! described in [`crate::public`] instead of a 401.
+
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderMap, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use crate::public;
+use crate::tenants::{self, Scope, Tenants};
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tracing::warn;
+
+/// What a credential may do
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    Viewer,
+    /// Viewer that may also contribute positions
+    Feeder,
+    Admin,
+}
+
+impl Role {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Viewer => "viewer",
+            Self::Feeder => "feeder",
+            Self::Admin => "admin",
+        }
+    }
+}
+
+/// An entry in `API_KEYS`
+#[derive(Debug, Clone, Deserialize)]
+pub struct ApiKey {
+    pub name: String,
+    pub key: String,
+    pub role: Role,
+    /// Overrides `API_RATE_LIMIT_PER_MINUTE`
+    pub rate_per_minute: Option<u32>,
+    /// Limits the key to one tenant's receivers
+    #[serde(default)]
+    pub tenant: Option<String>,
+}
+
+/// JWT claims
+#[derive(Debug, Deserialize)]
+struct Claims {
+    sub: String,
+    role: Role,
+    #[serde(default)]
+    tenant: Option<String>,
+}
+
+/// The authenticated caller, added to request extensions along with its
+/// scope
+#[derive(Debug, Clone, PartialEq)]
+pub struct Principal {
+    pub name: String,
+    pub role: Role,
+    pub scope: Scope,
+    rate_per_minute: u32,
+}
+
+/// Why a request was refused
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AuthError {
+    Missing,
+    Invalid,
+    /// Role needed
+    Forbidden(Role),
+    /// Admin or ingest endpoint while no credentials are configured
+    Disabled,
+    /// Endpoint tenant credentials may not use
+    NotForTenants,
+    /// Seconds until a request would be allowed
+    RateLimited(u64),
+}
+
+impl IntoResponse for AuthError {
+    fn into_response(self) -> Response {
+        let (status, error) = match self {
+            Self::Missing => (StatusCode::UNAUTHORIZED, "missing credentials".to_string()),
+            Self::Invalid => (StatusCode::UNAUTHORIZED, "invalid credentials".to_string()),
+            Self::Forbidden(role) => (StatusCode::FORBIDDEN, format!("{} role required", role.name())),
+            Self::Disabled => (
+                StatusCode::FORBIDDEN,
+                "admin and ingest endpoints need API_KEYS or JWT_SECRET to be set".to_string(),
+            ),
+            Self::NotForTenants => (
+                StatusCode::FORBIDDEN,
+                "not available to tenant credentials".to_string(),
+            ),
+            Self::RateLimited(_) => (StatusCode::TOO_MANY_REQUESTS, "rate limit exceeded".to_string()),
+        };
+        let mut response = (status, Json(json!({"error": error}))).into_response();
+        if let Self::RateLimited(retry_after) = self {
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, HeaderValue::from(retry_after.max(1)));
+        }
+        response
+    }
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// API keys, JWT verification and per-credential rate limits
+pub struct Auth {
+    keys: Vec<ApiKey>,
+    jwt: Option<(jsonwebtoken::DecodingKey, jsonwebtoken::Validation)>,
+    /// 0 disables rate limiting
+    default_rate_per_minute: u32,
+    buckets: Mutex<HashMap<String, Bucket>>,
+    tenants: Tenants,
+    /// Serve requests without credentials the public view
+    public_viewer: bool,
+}
+
+impl Auth {
+    pub fn new(keys: Vec<ApiKey>, jwt_secret: Option<&str>, default_rate_per_minute: u32) -> Self {
+        Self {
+            keys,
+            jwt: jwt_secret.map(|secret| {
+                (
+                    jsonwebtoken::DecodingKey::from_secret(secret.as_bytes()),
+                    jsonwebtoken::Validation::new(jsonwebtoken::Algorithm::HS256),
+                )
+            }),
+            default_rate_per_minute,
+            buckets: Mutex::new(HashMap::new()),
+            tenants: Tenants::default(),
+            public_viewer: false,
+        }
+    }
+
+    /// Resolve credentials' `tenant` against `tenants`
+    pub fn with_tenants(mut self, tenants: Tenants) -> Self {
+        self.tenants = tenants;
+        self
+    }
+
+    /// Let requests without credentials use the public view
+    pub fn with_public_viewer(mut self, enabled: bool) -> Self {
+        self.public_viewer = enabled;
+        self
+    }
+
+    /// Whether any credentials are configured
+    pub fn enabled(&self) -> bool {
+        !self.keys.is_empty() || self.jwt.is_some()
+    }
+
+    /// The caller identified by the request headers
+    pub fn authenticate(&self, headers: &HeaderMap) -> Result<Principal, AuthError> {
+        let header_str = |name| headers.get(name).and_then(|v: &HeaderValue| v.to_str().ok());
+        let presented = header_str(header::HeaderName::from_static("x-api-key"))
+            .or_else(|| header_str(header::AUTHORIZATION).and_then(|v| v.strip_prefix("Bearer ")))
+            .map(str::trim)
+            .ok_or(AuthError::Missing)?;
+        self.verify(presented)
+    }
+
+    /// The caller holding `presented`, an API key or JWT
+    pub fn verify(&self, presented: &str) -> Result<Principal, AuthError> {
+        // A tenant that isn't configured must not fall back to everything
+        let scope = |tenant: Option<&str>| self.tenants.scope(tenant).ok_or(AuthError::Invalid);
+        if let Some(key) = self
+            .keys
+            .iter()
+            .find(|k| constant_time_eq(k.key.as_bytes(), presented.as_bytes()))
+        {
+            return Ok(Principal {
+                name: key.name.clone(),
+                role: key.role,
+                scope: scope(key.tenant.as_deref())?,
+                rate_per_minute: key.rate_per_minute.unwrap_or(self.default_rate_per_minute),
+            });
+        }
+
+        let (key, validation) = self.jwt.as_ref().ok_or(AuthError::Invalid)?;
+        let claims = jsonwebtoken::decode::<Claims>(presented, key, validation)
+            .map_err(|_| AuthError::Invalid)?
+            .claims;
+        Ok(Principal {
+            name: format!("jwt:{}", claims.sub),
+            role: claims.role,
+            scope: scope(claims.tenant.as_deref())?,
+            rate_per_minute: self.default_rate_per_minute,
+        })
+    }
+
+    /// Take a token from the caller's bucket
+    fn check_rate(&self, principal: &Principal, now: Instant) -> Result<(), AuthError> {
+        let rate = principal.rate_per_minute as f64;
+        if rate == 0.0 {
+            return Ok(());
+        }
+        let Ok(mut buckets) = self.buckets.lock() else {
+            return Ok(());
+        };
+        let bucket = buckets.entry(principal.name.clone()).or_insert(Bucket {
+            tokens: rate,
+            updated: now,
+        });
+        let refill = now.duration_since(bucket.updated).as_secs_f64() * rate / 60.0;
+        bucket.tokens = (bucket.tokens + refill).min(rate);
+        bucket.updated = now;
+        if bucket.tokens < 1.0 {
+            let wait = (1.0 - bucket.tokens) * 60.0 / rate;
+            return Err(AuthError::RateLimited(wait.ceil() as u64));
+        }
+        bucket.tokens -= 1.0;
+        Ok(())
+    }
+
+    fn authorize(
+        &self,
+        method: &Method,
+        path: &str,
+        headers: &HeaderMap,
+    ) -> Result<Principal, AuthError> {
+        let principal = self.authenticate(headers)?;
+        let read_only = matches!(*method, Method::GET | Method::HEAD) || path == crate::graphql::PATH;
+        let required = if is_admin_path(path) {
+            Role::Admin
+        } else if is_ingest_path(path) {
+            Role::Feeder
+        } else if read_only {
+            Role::Viewer
+        } else {
+            Role::Admin
+        };
+        if principal.role < required {
+            return Err(AuthError::Forbidden(required));
+        }
+        if principal.scope.is_tenant() && !tenants::allows_path(path) {
+            return Err(AuthError::NotForTenants);
+        }
+        self.check_rate(&principal, Instant::now())?;
+        Ok(principal)
+    }
+}
+
+/// Middleware guarding the REST API
+pub async fn require_auth(
+    State(auth): State<Arc<Auth>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    if request.method() == Method::OPTIONS {
+        return next.run(request).await;
+    }
+    if auth.public_viewer && auth.authenticate(request.headers()) == Err(AuthError::Missing) {
+        if !public::allows(request.method(), request.uri().path()) {
+            return AuthError::Missing.into_response();
+        }
+        request.extensions_mut().insert(Scope::Public);
+        return next.run(request).await;
+    }
+    if !auth.enabled() {
+        if is_admin_path(request.uri().path()) || is_ingest_path(request.uri().path()) {
+            warn!("Refused {} {}: API auth not configured", request.method(), request.uri().path());
+            return AuthError::Disabled.into_response();
+        }
+        return next.run(request).await;
+    }
+    match auth.authorize(request.method(), request.uri().path(), request.headers()) {
+        Ok(principal) => {
+            request.extensions_mut().insert(principal.scope.clone());
+            request.extensions_mut().insert(principal);
+            next.run(request).await
+        }
+        Err(e) => {
+            if !matches!(e, AuthError::RateLimited(_)) {
+                warn!("Refused {} {}: {:?}", request.method(), request.uri().path(), e);
+            }
+            e.into_response()
+        }
+    }
+}
+
+fn is_admin_path(path: &str) -> bool {
+    path == crate::admin::PREFIX || path.starts_with(&format!("{}/", crate::admin::PREFIX))
+}
+
+fn is_ingest_path(path: &str) -> bool {
+    path.starts_with(&format!("{}/", crate::ingest::PREFIX))
+}
+
+/// Compare without leaking the matching prefix length through timing
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &str = "test-secret";
+
+    fn auth() -> Auth {
+        let keys = vec![
+            ApiKey {
+                name: "viewer".into(),
+                key: "view-key".into(),
+                role: Role::Viewer,
+                rate_per_minute: Some(2),
+                tenant: None,
+            },
+            ApiKey {
+                name: "tracker".into(),
+                key: "feed-key".into(),
+                role: Role::Feeder,
+                rate_per_minute: None,
+                tenant: None,
+            },
+            ApiKey {
+                name: "ops".into(),
+                key: "admin-key".into(),
+                role: Role::Admin,
+                rate_per_minute: None,
+                tenant: None,
+            },
+            ApiKey {
+                name: "acme".into(),
+                key: "acme-key".into(),
+                role: Role::Viewer,
+                rate_per_minute: None,
+                tenant: Some("acme".into()),
+            },
+            ApiKey {
+                name: "stale".into(),
+                key: "stale-key".into(),
+                role: Role::Viewer,
+                rate_per_minute: None,
+                tenant: Some("globex".into()),
+            },
+        ];
+        let tenants = vec![tenants::Tenant {
+            name: "acme".into(),
+            devices: ["acme-rx1".to_string()].into(),
+        }];
+        Auth::new(keys, Some(SECRET), 600).with_tenants(Tenants::new(tenants))
+    }
+
+    fn headers(name: header::HeaderName, value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(name, value.parse().unwrap());
+        headers
+    }
+
+    fn jwt(role: &str, exp: i64) -> String {
+        let claims = json!({"sub": "alice", "role": role, "exp": exp});
+        jsonwebtoken::encode(
+            &jsonwebtoken::Header::default(),
+            &claims,
+            &jsonwebtoken::EncodingKey::from_secret(SECRET.as_bytes()),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_api_keys_and_roles() {
+        let auth = auth();
+        let viewer = headers(header::HeaderName::from_static("x-api-key"), "view-key");
+        let admin = headers(header::AUTHORIZATION, "Bearer admin-key");
+
+        let path = "/api/watchlist";
+        assert_eq!(auth.authorize(&Method::GET, path, &HeaderMap::new()), Err(AuthError::Missing));
+        assert_eq!(
+            auth.authorize(&Method::GET, path, &headers(header::AUTHORIZATION, "Bearer nope")),
+            Err(AuthError::Invalid)
+        );
+        assert_eq!(auth.authorize(&Method::GET, path, &viewer).unwrap().name, "viewer");
+        assert_eq!(auth.authorize(&Method::POST, path, &viewer), Err(AuthError::Forbidden(Role::Admin)));
+        assert_eq!(auth.authorize(&Method::DELETE, path, &admin).unwrap().role, Role::Admin);
+        // GraphQL queries are POSTs but can't change anything
+        assert!(auth.authorize(&Method::POST, crate::graphql::PATH, &viewer).is_ok());
+        // Admin endpoints need admin even to read
+        let admin_path = "/api/admin/positions";
+        assert_eq!(auth.authorize(&Method::GET, admin_path, &viewer), Err(AuthError::Forbidden(Role::Admin)));
+        assert!(auth.authorize(&Method::DELETE, admin_path, &admin).is_ok());
+        assert!(!is_admin_path("/api/administrators"));
+        // Feeders may post positions but change nothing else
+        let feeder = headers(header::HeaderName::from_static("x-api-key"), "feed-key");
+        let ingest_path = "/api/ingest/positions";
+        assert_eq!(auth.authorize(&Method::POST, ingest_path, &viewer), Err(AuthError::Forbidden(Role::Feeder)));
+        assert_eq!(auth.authorize(&Method::POST, ingest_path, &feeder).unwrap().role, Role::Feeder);
+        assert!(auth.authorize(&Method::POST, ingest_path, &admin).is_ok());
+        assert!(auth.authorize(&Method::GET, path, &feeder).is_ok());
+        assert_eq!(auth.authorize(&Method::POST, path, &feeder), Err(AuthError::Forbidden(Role::Admin)));
+    }
+
+    #[test]
+    fn test_tenant_credentials() {
+        let auth = auth();
+        let acme = headers(header::HeaderName::from_static("x-api-key"), "acme-key");
+        let principal = auth.authorize(&Method::GET, "/api/aircraft", &acme).unwrap();
+        assert!(principal.scope.allows_device("acme-rx1") && !principal.scope.allows_device("globex-rx1"));
+        assert_eq!(auth.authorize(&Method::GET, "/api/stats", &acme), Err(AuthError::NotForTenants));
+        assert_eq!(auth.verify("stale-key"), Err(AuthError::Invalid));
+        assert_eq!(auth.verify("view-key").unwrap().scope, Scope::All);
+    }
+
+    #[test]
+    fn test_jwt() {
+        let auth = auth();
+        let exp = chrono::Utc::now().timestamp() + 3600;
+        let bearer = |token: String| headers(header::AUTHORIZATION, &format!("Bearer {}", token));
+
+        let principal = auth.authenticate(&bearer(jwt("admin", exp))).unwrap();
+        assert_eq!(principal.name, "jwt:alice");
+        assert_eq!(principal.role, Role::Admin);
+        assert_eq!(
+            auth.authenticate(&bearer(jwt("viewer", exp - 7200))),
+            Err(AuthError::Invalid)
+        );
+    }
+
+    #[test]
+    fn test_rate_limit() {
+        let auth = auth();
+        let principal = auth
+            .authenticate(&headers(header::HeaderName::from_static("x-api-key"), "view-key"))
+            .unwrap();
+        let now = Instant::now();
+        assert!(auth.check_rate(&principal, now).is_ok());
+        assert!(auth.check_rate(&principal, now).is_ok());
+        assert_eq!(auth.check_rate(&principal, now), Err(AuthError::RateLimited(30)));
+        // 2 per minute: one token back after 30 s
+        assert!(auth.check_rate(&principal, now + std::time::Duration::from_secs(30)).is_ok());
+    }
+}
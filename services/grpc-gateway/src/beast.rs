@@ -0,0 +1,287 @@
+//! Beast-format ingest
+//!
+//! With `BEAST_PORT` set, the gateway accepts TCP connections carrying the
+//! Beast binary format that dump1090 and readsb send to aggregators
+//! (`--net-connector <gateway>,<port>,beast_out`), so stock receivers can
+//! feed this backend alongside, or while migrating to, capture hosts.
+//! Frames are decoded with the same decoder the capture service uses, one
+//! CPR and address trust context per connection, and each decoded frame
+//! goes through the same pipeline as a host's aircraft events (privacy,
+//! sanity, merging, storage, broadcast), as device `beast-<peer address>`.
+//! Frames are archived when `RAW_ARCHIVE` is on. Mode A/C and status
+//! messages are skipped.
+
+use crate::adsb::{AircraftEvent, Emergency, RawFrame};
+use crate::grpc_server::GatewayService;
+use crate::uptime::StreamDevices;
+use crate::AppState;
+use adsb_decoder::{format_address, parse_message, AircraftData, CprContext, EmergencySquawk, IcaoTrust};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::io::AsyncReadExt;
+use tokio::net::{TcpListener, TcpStream};
+use tracing::{debug, error, info, warn};
+
+/// Marks the start of a message; doubled when it occurs inside one
+const ESCAPE: u8 = 0x1A;
+
+/// 12 MHz timestamp and signal level ahead of the frame
+const HEADER_LEN: usize = 7;
+
+/// Aircraft tracked per connection for CPR decoding
+const CPR_AIRCRAFT: usize = 256;
+
+/// One Mode S frame from a Beast stream
+#[derive(Debug, Clone, PartialEq)]
+pub struct BeastFrame {
+    /// Receiver clock, 12 MHz ticks
+    pub timestamp: u64,
+    pub signal: u8,
+    /// 7 or 14 bytes
+    pub data: Vec<u8>,
+}
+
+/// Splits a Beast byte stream into frames, across reads
+#[derive(Debug, Default)]
+pub struct BeastDecoder {
+    buf: Vec<u8>,
+}
+
+impl BeastDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, bytes: &[u8]) {
+        self.buf.extend_from_slice(bytes);
+    }
+
+    /// The next complete Mode S frame, skipping other message types and
+    /// anything garbled
+    pub fn next_frame(&mut self) -> Option<BeastFrame> {
+        loop {
+            // Resynchronize on a lone escape
+            let start = (0..self.buf.len()).find(|&i| {
+                self.buf[i] == ESCAPE && self.buf.get(i + 1).is_some_and(|&b| b != ESCAPE)
+            });
+            let Some(start) = start else {
+                // Keep a trailing escape whose type hasn't arrived yet
+                let keep = usize::from(self.buf.last() == Some(&ESCAPE));
+                self.buf.drain(..self.buf.len() - keep);
+                return None;
+            };
+            self.buf.drain(..start);
+            let len = match self.buf[1] {
+                b'2' => 7,
+                b'3' => 14,
+                b'1' => 2,
+                // Status and anything newer
+                _ => {
+                    self.buf.drain(..2);
+                    continue;
+                }
+            };
+
+            let mut body = Vec::with_capacity(HEADER_LEN + len);
+            let mut i = 2;
+            while body.len() < HEADER_LEN + len {
+                match (self.buf.get(i), self.buf.get(i + 1)) {
+                    (None, _) | (Some(&ESCAPE), None) => return None,
+                    (Some(&ESCAPE), Some(&ESCAPE)) => {
+                        body.push(ESCAPE);
+                        i += 2;
+                    }
+                    // A new message started inside this one
+                    (Some(&ESCAPE), Some(_)) => break,
+                    (Some(&b), _) => {
+                        body.push(b);
+                        i += 1;
+                    }
+                }
+            }
+            self.buf.drain(..i);
+            // Cut short, or Mode A/C
+            if body.len() < HEADER_LEN + len || len == 2 {
+                continue;
+            }
+            let timestamp = body[..6].iter().fold(0u64, |t, &b| t << 8 | b as u64);
+            return Some(BeastFrame {
+                timestamp,
+                signal: body[6],
+                data: body[HEADER_LEN..].to_vec(),
+            });
+        }
+    }
+}
+
+/// Event for a decoded frame; `None` when it carried nothing beyond the
+/// address
+pub fn to_event(aircraft: &AircraftData, device_id: &str, timestamp_ms: u64) -> Option<AircraftEvent> {
+    let decoded = aircraft.callsign.is_some()
+        || aircraft.altitude_ft.is_some()
+        || aircraft.latitude.is_some()
+        || aircraft.ground_speed_kts.is_some()
+        || aircraft.track_deg.is_some()
+        || aircraft.magnetic_heading_deg.is_some()
+        || aircraft.vertical_rate_fpm.is_some()
+        || aircraft.squawk.is_some()
+        || aircraft.category.is_some();
+    if !decoded {
+        return None;
+    }
+    let emergency = match aircraft.squawk.and_then(EmergencySquawk::from_squawk) {
+        Some(EmergencySquawk::Hijack) => Emergency::Hijack,
+        Some(EmergencySquawk::RadioFailure) => Emergency::RadioFailure,
+        Some(EmergencySquawk::General) => Emergency::General,
+        None => Emergency::None,
+    };
+    Some(AircraftEvent {
+        device_id: device_id.to_string(),
+        timestamp_ms,
+        icao: format_address(aircraft.key()),
+        callsign: aircraft.callsign.clone(),
+        altitude_ft: aircraft.altitude_ft,
+        latitude: aircraft.latitude,
+        longitude: aircraft.longitude,
+        speed_kts: aircraft.ground_speed_kts,
+        track_deg: aircraft.track_deg,
+        magnetic_heading_deg: aircraft.magnetic_heading_deg,
+        vertical_rate_fpm: aircraft.vertical_rate_fpm,
+        baro_rate_fpm: aircraft.vertical_rate_fpm.filter(|_| !aircraft.vertical_rate_gnss),
+        geom_rate_fpm: aircraft.vertical_rate_fpm.filter(|_| aircraft.vertical_rate_gnss),
+        squawk: aircraft.squawk.map(|s| format!("{:04}", s)),
+        downlink_format: aircraft.df as u32,
+        type_code: aircraft.tc as u32,
+        emergency: emergency as i32,
+        category: aircraft.category.clone(),
+        adsb_version: aircraft.operational_status.map(|s| s.version.number() as u32),
+        nic: aircraft.integrity.and_then(|i| i.nic).map(u32::from),
+        nac_p: aircraft.integrity.and_then(|i| i.nac_p).map(u32::from),
+        nuc_p: aircraft.integrity.and_then(|i| i.nuc_p).map(u32::from),
+        containment_radius_m: aircraft.integrity.and_then(|i| i.rc_m),
+        ..Default::default()
+    })
+}
+
+/// Accept Beast feeds on `port`
+pub fn spawn(port: u16, state: Arc<AppState>, receiver: Option<(f64, f64)>) {
+    tokio::spawn(async move {
+        let addr = format!("0.0.0.0:{}", port);
+        let listener = match TcpListener::bind(&addr).await {
+            Ok(listener) => listener,
+            Err(e) => {
+                error!("Failed to listen for Beast feeds on {}: {}", addr, e);
+                return;
+            }
+        };
+        info!("Accepting Beast feeds on {}", addr);
+        let service = Arc::new(GatewayService::new(state.clone()));
+        loop {
+            match listener.accept().await {
+                Ok((stream, peer)) => {
+                    tokio::spawn(handle(stream, peer, state.clone(), service.clone(), receiver));
+                }
+                Err(e) => warn!("Beast accept failed: {}", e),
+            }
+        }
+    });
+}
+
+/// Decode one feed until it disconnects
+async fn handle(
+    mut stream: TcpStream,
+    peer: SocketAddr,
+    state: Arc<AppState>,
+    service: Arc<GatewayService>,
+    receiver: Option<(f64, f64)>,
+) {
+    let device_id = format!("beast-{}", peer.ip());
+    info!("New Beast feed from {} as {}", peer, device_id);
+
+    let mut decoder = BeastDecoder::new();
+    let mut cpr = CprContext::new(CPR_AIRCRAFT);
+    cpr.set_receiver(receiver);
+    let trust = IcaoTrust::new();
+    let mut devices = StreamDevices::new(state.receivers.clone());
+    let mut buf = [0u8; 4096];
+    let (mut frames, mut events) = (0u64, 0u64);
+
+    loop {
+        let n = match stream.read(&mut buf).await {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) => {
+                warn!("Beast feed {} error: {}", peer, e);
+                break;
+            }
+        };
+        decoder.push(&buf[..n]);
+        while let Some(frame) = decoder.next_frame() {
+            frames += 1;
+            let now_ms = chrono::Utc::now().timestamp_millis() as u64;
+            if state.raw_archive.is_enabled() && !state.privacy.withholds_frame(&frame.data) {
+                state.raw_archive.submit(RawFrame {
+                    device_id: device_id.clone(),
+                    timestamp_ms: now_ms,
+                    data: frame.data.clone(),
+                    signal_level: frame.signal as u32,
+                });
+            }
+
+            trust.observe(&frame.data, Instant::now());
+            let Ok(aircraft) = parse_message(&frame.data, &mut cpr, &trust) else {
+                continue;
+            };
+            if let Some(event) = to_event(&aircraft, &device_id, now_ms) {
+                events += 1;
+                if !service.ingest(event, &mut devices).await {
+                    debug!("Beast feed {}: event not stored", peer);
+                }
+            }
+        }
+    }
+
+    info!("Beast feed from {} ended: frames={}, events={}", peer, frames, events);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_beast_framing() {
+        let squitter = [0x8D, 0x48, 0x40, 0xD6, 0x20, 0x2C, 0xC3, 0x71, 0xC3, 0x2C, 0xE0, 0x57, 0x60, 0x98];
+        let mut stream = vec![0x00, 0x42]; // noise before the first message
+        // Long frame, with an escaped 0x1A in the timestamp
+        stream.extend([ESCAPE, b'3', 0x00, 0x00, 0x1A, 0x1A, 0x00, 0x01, 0x02, 0xA0]);
+        stream.extend(squitter);
+        // Mode A/C, skipped
+        stream.extend([ESCAPE, b'1', 0, 0, 0, 0, 0, 0, 0x10, 0x12, 0x34]);
+        // Short frame, split across reads
+        let short = [ESCAPE, b'2', 0, 0, 0, 0, 0, 0x05, 0x30, 0x5D, 0x48, 0x40, 0xD6, 0xD6, 0xC7, 0xE8];
+
+        let mut decoder = BeastDecoder::new();
+        decoder.push(&stream);
+        decoder.push(&short[..9]);
+        let frame = decoder.next_frame().unwrap();
+        assert_eq!(frame.timestamp, 0x1A000102);
+        assert_eq!(frame.signal, 0xA0);
+        assert_eq!(frame.data, squitter);
+        assert_eq!(decoder.next_frame(), None);
+
+        decoder.push(&short[9..]);
+        let frame = decoder.next_frame().unwrap();
+        assert_eq!(frame.data, [0x5D, 0x48, 0x40, 0xD6, 0xD6, 0xC7, 0xE8]);
+        assert_eq!(decoder.next_frame(), None);
+
+        // Identification frame decodes to an event with a callsign
+        let trust = IcaoTrust::new();
+        let mut cpr = CprContext::new(CPR_AIRCRAFT);
+        let aircraft = parse_message(&squitter, &mut cpr, &trust).unwrap();
+        let event = to_event(&aircraft, "beast-10.0.0.2", 1).unwrap();
+        assert_eq!(event.icao, "4840D6");
+        assert_eq!(event.callsign.as_deref(), Some("KLM1023"));
+        assert_eq!(event.downlink_format, 17);
+    }
+}
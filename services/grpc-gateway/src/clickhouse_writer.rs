@@ -0,0 +1,1139 @@
+//! ClickHouse storage backend
+//!
+//! Talks to ClickHouse over its HTTP interface. Position rows are buffered
+//! and written in bulk (`FORMAT JSONEachRow`) instead of one round trip per
+//! event, and materialized views maintain per-day message counts and a
+//! coverage grid for long-range analytical queries.
+
+use crate::adsb::{AircraftEvent, DeviceStatus, RawFrame};
+use crate::config::Config;
+use crate::coverage::{CoverageCell, CoverageQuery, MAX_CELLS};
+use crate::db_health::{DbHealth, TableHealth};
+use crate::decoder_config;
+use crate::emergencies::{emergency_name, EmergencyRecord};
+use crate::flights::FlightRecord;
+use crate::history::{HistoryQuery, PositionPoint};
+use crate::stats::{self, Period, StatsRollup};
+use crate::storage::Storage;
+use crate::uptime::ReceiverSession;
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use serde_json::Value as JsonValue;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::Notify;
+use tracing::{debug, warn};
+
+/// Flush the position buffer at least this often
+const FLUSH_INTERVAL_MS: u64 = 1000;
+
+/// Flush early once this many rows are buffered
+const FLUSH_ROWS: usize = 5000;
+
+/// Drop buffered rows beyond this if ClickHouse is unreachable
+const MAX_BUFFERED_ROWS: usize = 100_000;
+
+/// Tables in health reports, with their TTL in days
+const HEALTH_TABLES: &[(&str, Option<i64>)] = &[
+    ("aircraft_positions", Some(365)),
+    ("raw_frames", Some(14)),
+    ("sdr_status_history", Some(7)),
+    ("flights", None),
+    ("daily_message_counts", None),
+    ("coverage_grid", None),
+    ("stats_rollups", None),
+];
+
+/// Schema statements (ClickHouse HTTP accepts one statement per request)
+const SCHEMA: &[&str] = &[
+    "CREATE TABLE IF NOT EXISTS aircraft_positions (
+        time DateTime64(3, 'UTC'),
+        icao_address String,
+        device_id LowCardinality(String),
+        callsign String,
+        latitude Float64,
+        longitude Float64,
+        altitude_ft Int32,
+        ground_speed_kts Float32,
+        track_deg Float32,
+        vertical_rate_fpm Int32,
+        squawk String,
+        magnetic_heading_deg Nullable(Float32),
+        baro_rate_fpm Nullable(Int32),
+        geom_rate_fpm Nullable(Int32)
+    ) ENGINE = MergeTree
+    PARTITION BY toYYYYMM(time)
+    ORDER BY (icao_address, time)
+    TTL toDateTime(time) + INTERVAL 365 DAY",
+    // Tables created before track and heading were told apart
+    "ALTER TABLE aircraft_positions RENAME COLUMN IF EXISTS heading_deg TO track_deg",
+    // Nullable: most aircraft never send a heading, and 0 is a real one
+    "ALTER TABLE aircraft_positions ADD COLUMN IF NOT EXISTS magnetic_heading_deg Nullable(Float32)",
+    "ALTER TABLE aircraft_positions ADD COLUMN IF NOT EXISTS baro_rate_fpm Nullable(Int32)",
+    "ALTER TABLE aircraft_positions ADD COLUMN IF NOT EXISTS geom_rate_fpm Nullable(Int32)",
+    "CREATE TABLE IF NOT EXISTS daily_message_counts (
+        day Date,
+        device_id LowCardinality(String),
+        messages SimpleAggregateFunction(sum, UInt64),
+        aircraft AggregateFunction(uniq, String)
+    ) ENGINE = AggregatingMergeTree
+    ORDER BY (day, device_id)",
+    "CREATE MATERIALIZED VIEW IF NOT EXISTS daily_message_counts_mv TO daily_message_counts AS
+    SELECT
+        toDate(time) AS day,
+        device_id,
+        count() AS messages,
+        uniqState(icao_address) AS aircraft
+    FROM aircraft_positions
+    GROUP BY day, device_id",
+    "CREATE TABLE IF NOT EXISTS coverage_grid (
+        day Date,
+        device_id LowCardinality(String),
+        lat_bin Int16,
+        lon_bin Int16,
+        positions SimpleAggregateFunction(sum, UInt64),
+        max_altitude SimpleAggregateFunction(max, Int32)
+    ) ENGINE = AggregatingMergeTree
+    ORDER BY (day, device_id, lat_bin, lon_bin)",
+    "CREATE MATERIALIZED VIEW IF NOT EXISTS coverage_grid_mv TO coverage_grid AS
+    SELECT
+        toDate(time) AS day,
+        device_id,
+        toInt16(floor(latitude * 10)) AS lat_bin,
+        toInt16(floor(longitude * 10)) AS lon_bin,
+        count() AS positions,
+        max(altitude_ft) AS max_altitude
+    FROM aircraft_positions
+    GROUP BY day, device_id, lat_bin, lon_bin",
+    "CREATE TABLE IF NOT EXISTS sdr_status (
+        device_id String,
+        connected Bool,
+        sample_rate UInt32,
+        center_freq UInt64,
+        gain_db Float32,
+        last_heartbeat DateTime64(3, 'UTC'),
+        messages_per_second Float32 DEFAULT 0,
+        software_version Nullable(String),
+        uptime_secs Nullable(UInt64),
+        decoder Nullable(String),
+        clock_offset_ms Nullable(Int64)
+    ) ENGINE = ReplacingMergeTree(last_heartbeat)
+    ORDER BY device_id",
+    "CREATE TABLE IF NOT EXISTS sdr_status_history (
+        time DateTime64(3, 'UTC'),
+        device_id LowCardinality(String),
+        connected Bool,
+        sample_rate UInt32,
+        center_freq UInt64,
+        gain_db Float32,
+        software_version Nullable(String),
+        decoder Nullable(String)
+    ) ENGINE = MergeTree
+    PARTITION BY toYYYYMMDD(time)
+    ORDER BY (device_id, time)
+    TTL toDateTime(time) + INTERVAL 7 DAY",
+    // Decoder setup reported by capture hosts, as JSON
+    "ALTER TABLE sdr_status ADD COLUMN IF NOT EXISTS software_version Nullable(String)",
+    "ALTER TABLE sdr_status ADD COLUMN IF NOT EXISTS uptime_secs Nullable(UInt64)",
+    "ALTER TABLE sdr_status ADD COLUMN IF NOT EXISTS decoder Nullable(String)",
+    "ALTER TABLE sdr_status ADD COLUMN IF NOT EXISTS clock_offset_ms Nullable(Int64)",
+    "ALTER TABLE sdr_status_history ADD COLUMN IF NOT EXISTS software_version Nullable(String)",
+    "ALTER TABLE sdr_status_history ADD COLUMN IF NOT EXISTS decoder Nullable(String)",
+    "CREATE TABLE IF NOT EXISTS raw_frames (
+        time DateTime64(3, 'UTC'),
+        device_id LowCardinality(String),
+        raw_hex String CODEC(ZSTD(3)),
+        signal_level UInt32
+    ) ENGINE = MergeTree
+    PARTITION BY toYYYYMMDD(time)
+    ORDER BY (device_id, time)
+    TTL toDateTime(time) + INTERVAL 14 DAY",
+    "CREATE TABLE IF NOT EXISTS flights (
+        icao_address String,
+        callsign String,
+        first_seen DateTime64(3, 'UTC'),
+        last_seen DateTime64(3, 'UTC'),
+        max_altitude_ft Nullable(Int32),
+        position_count UInt32,
+        device_id LowCardinality(String)
+    ) ENGINE = ReplacingMergeTree(last_seen)
+    ORDER BY (icao_address, first_seen)",
+    "CREATE TABLE IF NOT EXISTS emergencies (
+        icao_address String,
+        started_at DateTime64(3, 'UTC'),
+        last_seen DateTime64(3, 'UTC'),
+        ended_at Nullable(DateTime64(3, 'UTC')),
+        emergency LowCardinality(String),
+        squawk String,
+        callsign String,
+        latitude Nullable(Float64),
+        longitude Nullable(Float64),
+        altitude_ft Nullable(Int32),
+        device_id LowCardinality(String)
+    ) ENGINE = ReplacingMergeTree(last_seen)
+    ORDER BY (icao_address, started_at)",
+    "CREATE TABLE IF NOT EXISTS receiver_sessions (
+        device_id LowCardinality(String),
+        started_at DateTime64(3, 'UTC'),
+        last_seen DateTime64(3, 'UTC'),
+        ended_at Nullable(DateTime64(3, 'UTC')),
+        end_reason Nullable(String)
+    ) ENGINE = ReplacingMergeTree(last_seen)
+    ORDER BY (device_id, started_at)",
+    // Flushed deltas are merged by summing counters and keeping the maxima
+    "CREATE TABLE IF NOT EXISTS stats_rollups (
+        period LowCardinality(String),
+        bucket DateTime64(3, 'UTC'),
+        device_id LowCardinality(String),
+        messages SimpleAggregateFunction(sum, Int64),
+        positions SimpleAggregateFunction(sum, Int64),
+        unique_aircraft SimpleAggregateFunction(max, Int64),
+        max_range_km SimpleAggregateFunction(max, Float64),
+        frames_decoded SimpleAggregateFunction(sum, Int64),
+        crc_errors SimpleAggregateFunction(sum, Int64)
+    ) ENGINE = AggregatingMergeTree
+    ORDER BY (period, bucket, device_id)",
+    "CREATE TABLE IF NOT EXISTS stats_df_rollups (
+        period LowCardinality(String),
+        bucket DateTime64(3, 'UTC'),
+        device_id LowCardinality(String),
+        downlink_format UInt8,
+        frames SimpleAggregateFunction(sum, Int64)
+    ) ENGINE = AggregatingMergeTree
+    ORDER BY (period, bucket, device_id, downlink_format)",
+];
+
+/// ClickHouse-backed storage
+pub struct ClickHouseWriter {
+    client: ClickHouseClient,
+    pending: Arc<Mutex<Vec<String>>>,
+    flush_notify: Arc<Notify>,
+}
+
+impl ClickHouseWriter {
+    /// Connect, apply the schema and start the bulk insert task
+    pub async fn new(config: &Config) -> Result<Self> {
+        let client = ClickHouseClient {
+            http: reqwest::Client::new(),
+            url: config.clickhouse_url.clone(),
+            database: config.clickhouse_database.clone(),
+            user: config.clickhouse_user.clone(),
+            password: config.clickhouse_password.clone(),
+        };
+
+        // Test connection (database may not exist yet)
+        client.post("SELECT 1", None, &[], false).await?;
+        client
+            .post(
+                &format!("CREATE DATABASE IF NOT EXISTS {}", client.database),
+                None,
+                &[],
+                false,
+            )
+            .await?;
+        for statement in SCHEMA {
+            client.execute(statement).await?;
+        }
+
+        let writer = Self {
+            client,
+            pending: Arc::new(Mutex::new(Vec::new())),
+            flush_notify: Arc::new(Notify::new()),
+        };
+        writer.spawn_flusher();
+        Ok(writer)
+    }
+
+    /// Background task that writes buffered position rows in bulk
+    fn spawn_flusher(&self) {
+        let client = self.client.clone();
+        let pending = self.pending.clone();
+        let notify = self.flush_notify.clone();
+
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_millis(FLUSH_INTERVAL_MS));
+            loop {
+                tokio::select! {
+                    _ = interval.tick() => {}
+                    _ = notify.notified() => {}
+                }
+
+                let rows = match pending.lock() {
+                    Ok(mut rows) if !rows.is_empty() => std::mem::take(&mut *rows),
+                    _ => continue,
+                };
+
+                let count = rows.len();
+                let body = rows.join("\n");
+                match client.insert("aircraft_positions", body).await {
+                    Ok(()) => debug!("Flushed {} position rows to ClickHouse", count),
+                    Err(e) => {
+                        warn!("ClickHouse bulk insert of {} rows failed: {}", count, e);
+                        // Put rows back so they are retried on the next tick
+                        if let Ok(mut buffered) = pending.lock() {
+                            if buffered.len() + count <= MAX_BUFFERED_ROWS {
+                                buffered.splice(0..0, rows);
+                            } else {
+                                warn!("ClickHouse buffer full, dropping {} rows", count);
+                            }
+                        }
+                    }
+                }
+            }
+        });
+    }
+}
+
+#[tonic::async_trait]
+impl Storage for ClickHouseWriter {
+    fn backend_name(&self) -> &'static str {
+        "clickhouse"
+    }
+
+    /// There is no aircraft_info table here: aircraft details are aggregated
+    /// from the position rows, so `messages` isn't recorded
+    async fn insert_position(
+        &self,
+        event: &AircraftEvent,
+        time: DateTime<Utc>,
+        _messages: u32,
+    ) -> Result<()> {
+        // Only insert if we have valid position
+        let (Some(latitude), Some(longitude)) = (event.latitude, event.longitude) else {
+            debug!("Skipping position insert for {} - no position data", event.icao);
+            return Ok(());
+        };
+
+        // The position columns aren't nullable; unknown values are stored as
+        // the column default
+        let row = serde_json::json!({
+            "time": format_time(time),
+            "icao_address": event.icao,
+            "device_id": event.device_id,
+            "callsign": event.callsign.clone().unwrap_or_default(),
+            "latitude": latitude,
+            "longitude": longitude,
+            "altitude_ft": event.altitude_ft.unwrap_or_default(),
+            "ground_speed_kts": event.speed_kts.unwrap_or_default(),
+            "track_deg": event.track_deg.unwrap_or_default(),
+            "magnetic_heading_deg": event.magnetic_heading_deg,
+            "vertical_rate_fpm": event.vertical_rate_fpm.unwrap_or_default(),
+            "baro_rate_fpm": event.baro_rate_fpm,
+            "geom_rate_fpm": event.geom_rate_fpm,
+            "squawk": event.squawk.clone().unwrap_or_default(),
+        });
+
+        let buffered = {
+            let mut pending = self
+                .pending
+                .lock()
+                .map_err(|_| anyhow!("ClickHouse buffer poisoned"))?;
+            pending.push(row.to_string());
+            pending.len()
+        };
+        if buffered >= FLUSH_ROWS {
+            self.flush_notify.notify_one();
+        }
+        Ok(())
+    }
+
+    async fn insert_raw_frames(&self, frames: &[RawFrame]) -> Result<()> {
+        if frames.is_empty() {
+            return Ok(());
+        }
+
+        // The archiver already batches, so write straight through
+        let body = frames
+            .iter()
+            .map(|f| {
+                let time = chrono::DateTime::from_timestamp_millis(f.timestamp_ms as i64)
+                    .unwrap_or_else(chrono::Utc::now);
+                serde_json::json!({
+                    "time": format_time(time),
+                    "device_id": f.device_id,
+                    "raw_hex": f.data.iter().map(|b| format!("{:02X}", b)).collect::<String>(),
+                    "signal_level": f.signal_level,
+                })
+                .to_string()
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        self.client.insert("raw_frames", body).await
+    }
+
+    async fn update_sdr_status(
+        &self,
+        status: &DeviceStatus,
+        messages_per_second: f32,
+    ) -> Result<()> {
+        // ReplacingMergeTree keeps the latest heartbeat per device
+        let decoder = decoder_config::settings_json(status).map(|d| d.to_string());
+        let row = serde_json::json!({
+            "device_id": status.device_id,
+            "connected": status.connected,
+            "sample_rate": status.sample_rate,
+            "center_freq": status.center_freq,
+            "gain_db": status.gain_db,
+            "last_heartbeat": format_time(chrono::Utc::now()),
+            "messages_per_second": messages_per_second,
+            "software_version": decoder_config::software_version(status),
+            "uptime_secs": status.uptime_secs,
+            "decoder": decoder,
+            "clock_offset_ms": status.clock_offset_ms,
+        });
+        self.client.insert("sdr_status", row.to_string()).await?;
+
+        let history = serde_json::json!({
+            "time": row["last_heartbeat"],
+            "device_id": status.device_id,
+            "connected": status.connected,
+            "sample_rate": status.sample_rate,
+            "center_freq": status.center_freq,
+            "gain_db": status.gain_db,
+            "software_version": row["software_version"],
+            "decoder": row["decoder"],
+        });
+        self.client.insert("sdr_status_history", history.to_string()).await
+    }
+
+    async fn get_aircraft(&self, icao: &str) -> Result<Option<JsonValue>> {
+        let rows = self
+            .client
+            .query(
+                "SELECT
+                    icao_address AS icao,
+                    argMaxIf(callsign, time, callsign != '') AS callsign,
+                    argMax(latitude, time) AS lat,
+                    argMax(longitude, time) AS lon,
+                    argMax(altitude_ft, time) AS altitude,
+                    argMax(ground_speed_kts, time) AS speed,
+                    argMax(track_deg, time) AS track,
+                    argMax(magnetic_heading_deg, time) AS magnetic_heading,
+                    argMax(vertical_rate_fpm, time) AS vrate,
+                    argMax(baro_rate_fpm, time) AS baro_rate,
+                    argMax(geom_rate_fpm, time) AS geom_rate,
+                    argMax(squawk, time) AS squawk,
+                    argMax(device_id, time) AS device_id,
+                    toUnixTimestamp64Milli(min(time)) AS first_ms,
+                    toUnixTimestamp64Milli(max(time)) AS seen_ms,
+                    count() AS messages
+                FROM aircraft_positions
+                WHERE icao_address = {icao:String}
+                GROUP BY icao_address",
+                &[("icao", icao)],
+            )
+            .await?;
+
+        Ok(rows.into_iter().next().map(|row| {
+            let seen = row["seen_ms"].as_i64().map(ms_to_rfc3339);
+            serde_json::json!({
+                "icao": row["icao"],
+                "callsign": non_empty(&row["callsign"]),
+                "lat": row["lat"],
+                "lon": row["lon"],
+                "altitude": row["altitude"],
+                "speed": row["speed"],
+                "track": row["track"],
+                "magnetic_heading": row["magnetic_heading"],
+                "vrate": row["vrate"],
+                "baro_rate": row["baro_rate"],
+                "geom_rate": row["geom_rate"],
+                "squawk": non_empty(&row["squawk"]),
+                "device_id": non_empty(&row["device_id"]),
+                "seen": seen,
+                "first_seen": row["first_ms"].as_i64().map(ms_to_rfc3339),
+                "last_seen": seen,
+                "messages": row["messages"],
+            })
+        }))
+    }
+
+    async fn get_aircraft_trail(
+        &self,
+        icao: &str,
+        devices: Option<&[String]>,
+        from: DateTime<Utc>,
+        to: Option<DateTime<Utc>>,
+        bucket_secs: Option<i64>,
+    ) -> Result<Vec<JsonValue>> {
+        let from_ms = from.timestamp_millis().to_string();
+        // 0 for no upper bound
+        let to_ms = to.map_or(0, |to| to.timestamp_millis()).to_string();
+        let devices = devices.map_or(String::new(), |devices| serde_json::json!(devices).to_string());
+        let params = [("icao", icao), ("from_ms", &from_ms), ("to_ms", &to_ms), ("devices", &devices)];
+        let rows = match bucket_secs {
+            None => {
+                self.client
+                    .query(
+                        "SELECT
+                            toUnixTimestamp64Milli(time) AS time_ms,
+                            latitude AS lat,
+                            longitude AS lon,
+                            altitude_ft AS altitude,
+                            device_id
+                        FROM aircraft_positions
+                        WHERE icao_address = {icao:String}
+                          AND time >= fromUnixTimestamp64Milli({from_ms:Int64}, 'UTC')
+                          AND ({to_ms:Int64} = 0 OR time < fromUnixTimestamp64Milli({to_ms:Int64}, 'UTC'))
+                          AND ({devices:String} = '' OR has(JSONExtract({devices:String}, 'Array(String)'), device_id))
+                        ORDER BY time ASC",
+                        &params,
+                    )
+                    .await?
+            }
+            Some(secs) => {
+                let bucket_ms = (secs * 1000).to_string();
+                let mut params = params.to_vec();
+                params.push(("bucket_ms", &bucket_ms));
+                self.client
+                    .query(
+                        "SELECT
+                            intDiv(toUnixTimestamp64Milli(time), {bucket_ms:Int64}) AS bucket,
+                            toUnixTimestamp64Milli(max(time)) AS time_ms,
+                            argMax(latitude, time) AS lat,
+                            argMax(longitude, time) AS lon,
+                            argMax(altitude_ft, time) AS altitude,
+                            argMax(device_id, time) AS device_id,
+                            count() AS positions
+                        FROM aircraft_positions
+                        WHERE icao_address = {icao:String}
+                          AND time >= fromUnixTimestamp64Milli({from_ms:Int64}, 'UTC')
+                          AND ({to_ms:Int64} = 0 OR time < fromUnixTimestamp64Milli({to_ms:Int64}, 'UTC'))
+                          AND ({devices:String} = '' OR has(JSONExtract({devices:String}, 'Array(String)'), device_id))
+                        GROUP BY bucket
+                        ORDER BY bucket ASC",
+                        &params,
+                    )
+                    .await?
+            }
+        };
+
+        Ok(rows
+            .into_iter()
+            .map(|row| {
+                serde_json::json!({
+                    "time": row["time_ms"].as_i64().map(ms_to_rfc3339),
+                    "lat": row["lat"],
+                    "lon": row["lon"],
+                    "altitude": row["altitude"],
+                    "device_id": non_empty(&row["device_id"]),
+                    "positions": row.get("positions"),
+                })
+            })
+            .collect())
+    }
+
+    async fn get_position_history(&self, query: &HistoryQuery) -> Result<Vec<PositionPoint>> {
+        // Without a cursor, resume "after" the start of the range
+        let (after_ms, after_icao) = match &query.after {
+            Some(c) => (c.time.timestamp_millis(), c.icao.clone()),
+            None => (query.from.timestamp_millis() - 1, String::new()),
+        };
+        let from_ms = query.from.timestamp_millis().to_string();
+        let to_ms = query.to.timestamp_millis().to_string();
+        let after_ms = after_ms.to_string();
+        let fetch = query.fetch.to_string();
+        // JSON array of device IDs, empty for all
+        let devices = query.devices.as_ref().map_or(String::new(), |devices| serde_json::json!(devices).to_string());
+        let rows = self
+            .client
+            .query(
+                "SELECT
+                    toUnixTimestamp64Milli(time) AS time_ms,
+                    icao_address, latitude, longitude, altitude_ft,
+                    ground_speed_kts, track_deg, magnetic_heading_deg, vertical_rate_fpm,
+                    baro_rate_fpm, geom_rate_fpm, squawk, device_id
+                FROM aircraft_positions
+                WHERE time >= fromUnixTimestamp64Milli({from_ms:Int64}, 'UTC')
+                  AND time < fromUnixTimestamp64Milli({to_ms:Int64}, 'UTC')
+                  AND ({icao:String} = '' OR icao_address = {icao:String})
+                  AND (time, icao_address) >
+                      (fromUnixTimestamp64Milli({after_ms:Int64}, 'UTC'), {after_icao:String})
+                  AND ({devices:String} = '' OR has(JSONExtract({devices:String}, 'Array(String)'), device_id))
+                ORDER BY time, icao_address
+                LIMIT {fetch:UInt32}",
+                &[
+                    ("from_ms", &from_ms),
+                    ("to_ms", &to_ms),
+                    ("icao", query.icao.as_deref().unwrap_or("")),
+                    ("after_ms", &after_ms),
+                    ("after_icao", &after_icao),
+                    ("fetch", &fetch),
+                    ("devices", &devices),
+                ],
+            )
+            .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| PositionPoint {
+                time: chrono::DateTime::from_timestamp_millis(row["time_ms"].as_i64().unwrap_or_default())
+                    .unwrap_or_default(),
+                icao: row["icao_address"].as_str().unwrap_or_default().to_string(),
+                lat: row["latitude"].as_f64().unwrap_or_default(),
+                lon: row["longitude"].as_f64().unwrap_or_default(),
+                altitude: row["altitude_ft"].as_i64().map(|v| v as i32),
+                speed: row["ground_speed_kts"].as_f64().map(|v| v as f32),
+                track: row["track_deg"].as_f64().map(|v| v as f32),
+                magnetic_heading: row["magnetic_heading_deg"].as_f64().map(|v| v as f32),
+                vrate: row["vertical_rate_fpm"].as_i64().map(|v| v as i32),
+                baro_rate: row["baro_rate_fpm"].as_i64().map(|v| v as i32),
+                geom_rate: row["geom_rate_fpm"].as_i64().map(|v| v as i32),
+                squawk: non_empty(&row["squawk"]).as_str().map(str::to_string),
+                device_id: non_empty(&row["device_id"]).as_str().map(str::to_string),
+            })
+            .collect())
+    }
+
+    async fn get_coverage(&self, query: &CoverageQuery) -> Result<Vec<CoverageCell>> {
+        let from_ms = query.from.timestamp_millis().to_string();
+        let to_ms = query.to.timestamp_millis().to_string();
+        let cell_deg = query.cell_deg.to_string();
+        let limit = MAX_CELLS.to_string();
+        let rows = self
+            .client
+            .query(
+                "SELECT
+                    toInt64(floor(latitude / {cell_deg:Float64})) AS lat_idx,
+                    toInt64(floor(longitude / {cell_deg:Float64})) AS lon_idx,
+                    count() AS count,
+                    max(altitude_ft) AS max_altitude
+                FROM aircraft_positions
+                WHERE time >= fromUnixTimestamp64Milli({from_ms:Int64}, 'UTC')
+                  AND time < fromUnixTimestamp64Milli({to_ms:Int64}, 'UTC')
+                  AND ({device_id:String} = '' OR device_id = {device_id:String})
+                GROUP BY lat_idx, lon_idx
+                ORDER BY count DESC
+                LIMIT {limit:UInt32}",
+                &[
+                    ("from_ms", &from_ms),
+                    ("to_ms", &to_ms),
+                    ("cell_deg", &cell_deg),
+                    ("device_id", query.device_id.as_deref().unwrap_or("")),
+                    ("limit", &limit),
+                ],
+            )
+            .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| CoverageCell {
+                lat_idx: row["lat_idx"].as_i64().unwrap_or_default(),
+                lon_idx: row["lon_idx"].as_i64().unwrap_or_default(),
+                count: row["count"].as_i64().unwrap_or_default(),
+                max_altitude: row["max_altitude"].as_i64().map(|v| v as i32),
+            })
+            .collect())
+    }
+
+    async fn get_sdr_status(&self) -> Result<Vec<JsonValue>> {
+        let rows = self
+            .client
+            .query(
+                "SELECT
+                    device_id,
+                    connected,
+                    sample_rate,
+                    center_freq,
+                    gain_db,
+                    toUnixTimestamp64Milli(last_heartbeat) AS heartbeat_ms,
+                    messages_per_second,
+                    software_version,
+                    uptime_secs,
+                    decoder,
+                    clock_offset_ms,
+                    CASE
+                        WHEN connected AND last_heartbeat > now64(3) - INTERVAL 30 SECOND THEN 'active'
+                        WHEN last_heartbeat > now64(3) - INTERVAL 5 MINUTE THEN 'stale'
+                        ELSE 'disconnected'
+                    END AS status
+                FROM sdr_status FINAL
+                ORDER BY device_id",
+                &[],
+            )
+            .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| {
+                serde_json::json!({
+                    "device_id": row["device_id"],
+                    "connected": row["connected"].as_bool().unwrap_or(false),
+                    "sample_rate": row["sample_rate"],
+                    "center_freq": row["center_freq"],
+                    "gain_db": row["gain_db"],
+                    "last_heartbeat": row["heartbeat_ms"].as_i64().map(ms_to_rfc3339),
+                    "messages_per_second": row["messages_per_second"],
+                    "software_version": row["software_version"],
+                    "uptime_secs": row["uptime_secs"],
+                    "decoder": json_column(&row["decoder"]),
+                    "clock_offset_ms": row["clock_offset_ms"],
+                    "status": row["status"],
+                })
+            })
+            .collect())
+    }
+
+    async fn get_sdr_history(
+        &self,
+        device_id: &str,
+        since: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<Vec<JsonValue>> {
+        let since_ms = since.timestamp_millis().to_string();
+        let limit = limit.to_string();
+        let rows = self
+            .client
+            .query(
+                "SELECT
+                    toUnixTimestamp64Milli(time) AS time_ms,
+                    connected,
+                    sample_rate,
+                    center_freq,
+                    gain_db,
+                    software_version,
+                    decoder
+                FROM sdr_status_history
+                WHERE device_id = {device_id:String}
+                  AND time >= fromUnixTimestamp64Milli({since_ms:Int64}, 'UTC')
+                ORDER BY time DESC
+                LIMIT {limit:UInt32}",
+                &[("device_id", device_id), ("since_ms", &since_ms), ("limit", &limit)],
+            )
+            .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| {
+                serde_json::json!({
+                    "time": ms_to_rfc3339(row["time_ms"].as_i64().unwrap_or_default()),
+                    "connected": row["connected"].as_bool().unwrap_or(false),
+                    "sample_rate": row["sample_rate"],
+                    "center_freq": row["center_freq"],
+                    "gain_db": row["gain_db"],
+                    "software_version": row["software_version"],
+                    "decoder": json_column(&row["decoder"]),
+                })
+            })
+            .collect())
+    }
+
+    async fn upsert_flight(&self, flight: &FlightRecord) -> Result<()> {
+        // ReplacingMergeTree keeps the row with the latest last_seen
+        let row = serde_json::json!({
+            "icao_address": flight.icao,
+            "callsign": flight.callsign,
+            "first_seen": format_time(flight.first_seen),
+            "last_seen": format_time(flight.last_seen),
+            "max_altitude_ft": flight.max_altitude_ft,
+            "position_count": flight.position_count,
+            "device_id": flight.device_id,
+        });
+        self.client.insert("flights", row.to_string()).await
+    }
+
+    async fn get_flights(&self, icao: Option<&str>, devices: Option<&[String]>, limit: i64) -> Result<Vec<JsonValue>> {
+        let limit = limit.to_string();
+        let devices = devices.map_or(String::new(), |devices| serde_json::json!(devices).to_string());
+        let rows = self
+            .client
+            .query(
+                "SELECT
+                    icao_address AS icao,
+                    callsign,
+                    toUnixTimestamp64Milli(first_seen) AS first_ms,
+                    toUnixTimestamp64Milli(last_seen) AS last_ms,
+                    max_altitude_ft,
+                    position_count,
+                    device_id
+                FROM flights FINAL
+                WHERE ({icao:String} = '' OR icao_address = {icao:String})
+                  AND ({devices:String} = '' OR has(JSONExtract({devices:String}, 'Array(String)'), device_id))
+                ORDER BY first_seen DESC
+                LIMIT {limit:UInt32}",
+                &[("icao", icao.unwrap_or("")), ("limit", &limit), ("devices", &devices)],
+            )
+            .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| {
+                let first_ms = row["first_ms"].as_i64().unwrap_or_default();
+                let last_ms = row["last_ms"].as_i64().unwrap_or_default();
+                serde_json::json!({
+                    "icao": row["icao"],
+                    "callsign": non_empty(&row["callsign"]),
+                    "first_seen": ms_to_rfc3339(first_ms),
+                    "last_seen": ms_to_rfc3339(last_ms),
+                    "duration_s": (last_ms - first_ms) / 1000,
+                    "max_altitude": row["max_altitude_ft"],
+                    "positions": row["position_count"],
+                    "device_id": non_empty(&row["device_id"]),
+                })
+            })
+            .collect())
+    }
+
+    async fn upsert_emergency(&self, record: &EmergencyRecord) -> Result<()> {
+        let (lat, lon) = record.position.unzip();
+        let row = serde_json::json!({
+            "icao_address": record.icao,
+            "started_at": format_time(record.started_at),
+            "last_seen": format_time(record.last_seen),
+            "ended_at": record.ended_at.map(format_time),
+            "emergency": emergency_name(record.emergency),
+            "squawk": record.squawk,
+            "callsign": record.callsign,
+            "latitude": lat,
+            "longitude": lon,
+            "altitude_ft": record.altitude_ft,
+            "device_id": record.device_id,
+        });
+        self.client.insert("emergencies", row.to_string()).await
+    }
+
+    async fn upsert_stats(&self, rows: &[StatsRollup]) -> Result<()> {
+        let body: Vec<String> = rows
+            .iter()
+            .map(|row| {
+                serde_json::json!({
+                    "period": row.period.name(),
+                    "bucket": format_time(row.bucket),
+                    "device_id": row.device_id,
+                    "messages": row.messages,
+                    "positions": row.positions,
+                    "unique_aircraft": row.unique_aircraft,
+                    "max_range_km": row.max_range_km,
+                    "frames_decoded": row.frames_decoded,
+                    "crc_errors": row.crc_errors,
+                })
+                .to_string()
+            })
+            .collect();
+        self.client.insert("stats_rollups", body.join("\n")).await?;
+
+        let formats: Vec<String> = rows
+            .iter()
+            .flat_map(|row| {
+                row.downlink_formats.iter().map(|(df, frames)| {
+                    serde_json::json!({
+                        "period": row.period.name(),
+                        "bucket": format_time(row.bucket),
+                        "device_id": row.device_id,
+                        "downlink_format": df,
+                        "frames": frames,
+                    })
+                    .to_string()
+                })
+            })
+            .collect();
+        if formats.is_empty() {
+            return Ok(());
+        }
+        self.client.insert("stats_df_rollups", formats.join("\n")).await
+    }
+
+    async fn get_stats(&self, period: Period, since: DateTime<Utc>) -> Result<Vec<StatsRollup>> {
+        let since_ms = since.timestamp_millis().to_string();
+        let rows = self
+            .client
+            .query(
+                "SELECT
+                    toUnixTimestamp64Milli(bucket) AS bucket_ms,
+                    device_id,
+                    sum(messages) AS messages,
+                    sum(positions) AS positions,
+                    max(unique_aircraft) AS unique_aircraft,
+                    max(max_range_km) AS max_range_km,
+                    sum(frames_decoded) AS frames_decoded,
+                    sum(crc_errors) AS crc_errors
+                FROM stats_rollups
+                WHERE period = {period:String}
+                  AND bucket >= fromUnixTimestamp64Milli({since_ms:Int64}, 'UTC')
+                GROUP BY bucket, device_id
+                ORDER BY bucket, device_id",
+                &[("period", period.name()), ("since_ms", &since_ms)],
+            )
+            .await?;
+
+        let mut rollups: Vec<StatsRollup> = rows
+            .into_iter()
+            .map(|row| StatsRollup {
+                period,
+                bucket: DateTime::from_timestamp_millis(row["bucket_ms"].as_i64().unwrap_or_default())
+                    .unwrap_or_default(),
+                device_id: row["device_id"].as_str().unwrap_or_default().to_string(),
+                messages: row["messages"].as_i64().unwrap_or_default(),
+                positions: row["positions"].as_i64().unwrap_or_default(),
+                unique_aircraft: row["unique_aircraft"].as_i64().unwrap_or_default(),
+                max_range_km: row["max_range_km"].as_f64().unwrap_or_default(),
+                frames_decoded: row["frames_decoded"].as_i64().unwrap_or_default(),
+                crc_errors: row["crc_errors"].as_i64().unwrap_or_default(),
+                downlink_formats: Default::default(),
+            })
+            .collect();
+
+        let formats = self
+            .client
+            .query(
+                "SELECT
+                    toUnixTimestamp64Milli(bucket) AS bucket_ms,
+                    device_id,
+                    downlink_format,
+                    sum(frames) AS frames
+                FROM stats_df_rollups
+                WHERE period = {period:String}
+                  AND bucket >= fromUnixTimestamp64Milli({since_ms:Int64}, 'UTC')
+                GROUP BY bucket, device_id, downlink_format",
+                &[("period", period.name()), ("since_ms", &since_ms)],
+            )
+            .await?;
+        stats::add_downlink_formats(
+            &mut rollups,
+            formats.into_iter().map(|row| {
+                (
+                    DateTime::from_timestamp_millis(row["bucket_ms"].as_i64().unwrap_or_default())
+                        .unwrap_or_default(),
+                    row["device_id"].as_str().unwrap_or_default().to_string(),
+                    row["downlink_format"].as_u64().unwrap_or_default() as u32,
+                    row["frames"].as_i64().unwrap_or_default(),
+                )
+            }),
+        );
+        Ok(rollups)
+    }
+
+    async fn get_emergencies(&self, active_only: bool, devices: Option<&[String]>, limit: i64) -> Result<Vec<JsonValue>> {
+        let limit = limit.to_string();
+        let devices = devices.map_or(String::new(), |devices| serde_json::json!(devices).to_string());
+        let rows = self
+            .client
+            .query(
+                "SELECT
+                    icao_address AS icao,
+                    toUnixTimestamp64Milli(started_at) AS started_ms,
+                    toUnixTimestamp64Milli(last_seen) AS last_ms,
+                    toUnixTimestamp64Milli(ended_at) AS ended_ms,
+                    emergency,
+                    squawk,
+                    callsign,
+                    latitude,
+                    longitude,
+                    altitude_ft,
+                    device_id
+                FROM emergencies FINAL
+                WHERE (NOT {active:Bool} OR ended_at IS NULL)
+                  AND ({devices:String} = '' OR has(JSONExtract({devices:String}, 'Array(String)'), device_id))
+                ORDER BY started_at DESC
+                LIMIT {limit:UInt32}",
+                &[
+                    ("active", if active_only { "true" } else { "false" }),
+                    ("limit", &limit),
+                    ("devices", &devices),
+                ],
+            )
+            .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| {
+                let ended_ms = row["ended_ms"].as_i64();
+                serde_json::json!({
+                    "icao": row["icao"],
+                    "emergency": row["emergency"],
+                    "squawk": row["squawk"],
+                    "callsign": non_empty(&row["callsign"]),
+                    "active": ended_ms.is_none(),
+                    "started_at": ms_to_rfc3339(row["started_ms"].as_i64().unwrap_or_default()),
+                    "last_seen": ms_to_rfc3339(row["last_ms"].as_i64().unwrap_or_default()),
+                    "ended_at": ended_ms.map(ms_to_rfc3339),
+                    "lat": row["latitude"],
+                    "lon": row["longitude"],
+                    "altitude": row["altitude_ft"],
+                    "device_id": non_empty(&row["device_id"]),
+                })
+            })
+            .collect())
+    }
+
+    async fn upsert_receiver_session(&self, session: &ReceiverSession) -> Result<()> {
+        let row = serde_json::json!({
+            "device_id": session.device_id,
+            "started_at": format_time(session.started_at),
+            "last_seen": format_time(session.last_seen),
+            "ended_at": session.ended_at.map(format_time),
+            "end_reason": session.end_reason,
+        });
+        self.client.insert("receiver_sessions", row.to_string()).await
+    }
+
+    async fn get_receiver_sessions(&self, since: DateTime<Utc>) -> Result<Vec<ReceiverSession>> {
+        let since_ms = since.timestamp_millis().to_string();
+        let rows = self
+            .client
+            .query(
+                "SELECT
+                    device_id,
+                    toUnixTimestamp64Milli(started_at) AS started_ms,
+                    toUnixTimestamp64Milli(last_seen) AS last_ms,
+                    toUnixTimestamp64Milli(ended_at) AS ended_ms,
+                    end_reason
+                FROM receiver_sessions FINAL
+                WHERE coalesce(ended_at, last_seen) >= fromUnixTimestamp64Milli({since_ms:Int64}, 'UTC')
+                ORDER BY started_at",
+                &[("since_ms", &since_ms)],
+            )
+            .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| ReceiverSession {
+                device_id: row["device_id"].as_str().unwrap_or_default().to_string(),
+                started_at: DateTime::from_timestamp_millis(row["started_ms"].as_i64().unwrap_or_default())
+                    .unwrap_or_default(),
+                last_seen: DateTime::from_timestamp_millis(row["last_ms"].as_i64().unwrap_or_default())
+                    .unwrap_or_default(),
+                ended_at: row["ended_ms"].as_i64().and_then(DateTime::from_timestamp_millis),
+                end_reason: row["end_reason"].as_str().map(str::to_string),
+            })
+            .collect())
+    }
+
+    async fn db_health(&self) -> Result<DbHealth> {
+        let parts = self
+            .client
+            .query(
+                "SELECT table, sum(rows) AS rows, sum(bytes_on_disk) AS bytes, count() AS parts
+                FROM system.parts
+                WHERE database = currentDatabase() AND active
+                GROUP BY table",
+                &[],
+            )
+            .await?;
+        let mut tables = Vec::new();
+        for &(name, ttl_days) in HEALTH_TABLES {
+            let part = parts.iter().find(|p| p["table"] == name);
+            let oldest = match ttl_days {
+                // Names are the constants above, never input; an empty
+                // table's min() is the epoch
+                Some(_) => self
+                    .client
+                    .query(&format!("SELECT toUnixTimestamp64Milli(min(time)) AS oldest_ms FROM {}", name), &[])
+                    .await?
+                    .first()
+                    .and_then(|row| row["oldest_ms"].as_i64())
+                    .filter(|&ms| ms > 0)
+                    .and_then(DateTime::from_timestamp_millis),
+                None => None,
+            };
+            tables.push(TableHealth {
+                name: name.to_string(),
+                rows: Some(part.and_then(|p| p["rows"].as_i64()).unwrap_or(0)),
+                bytes: Some(part.and_then(|p| p["bytes"].as_i64()).unwrap_or(0)),
+                oldest,
+                chunks: Some(part.and_then(|p| p["parts"].as_i64()).unwrap_or(0)),
+                retention_days: ttl_days.map(|days| days as f64),
+            });
+        }
+        Ok(DbHealth::new(self.backend_name(), None, tables, Utc::now()))
+    }
+}
+
+
+/// Minimal ClickHouse HTTP interface client
+#[derive(Clone)]
+struct ClickHouseClient {
+    http: reqwest::Client,
+    url: String,
+    database: String,
+    user: String,
+    password: String,
+}
+
+impl ClickHouseClient {
+    /// Run a statement that returns no rows
+    async fn execute(&self, sql: &str) -> Result<()> {
+        self.post(sql, None, &[], true).await.map(|_| ())
+    }
+
+    /// Bulk insert newline-delimited JSON rows
+    async fn insert(&self, table: &str, rows: String) -> Result<()> {
+        let sql = format!("INSERT INTO {} FORMAT JSONEachRow", table);
+        self.post(&sql, Some(rows), &[], true).await.map(|_| ())
+    }
+
+    /// Run a query with `{name:Type}` parameters and parse the rows
+    async fn query(&self, sql: &str, params: &[(&str, &str)]) -> Result<Vec<JsonValue>> {
+        let sql = format!("{} FORMAT JSONEachRow", sql);
+        let body = self.post(&sql, None, params, true).await?;
+        body.lines()
+            .filter(|l| !l.trim().is_empty())
+            .map(|l| serde_json::from_str(l).map_err(Into::into))
+            .collect()
+    }
+
+    async fn post(
+        &self,
+        sql: &str,
+        data: Option<String>,
+        params: &[(&str, &str)],
+        use_database: bool,
+    ) -> Result<String> {
+        let mut query: Vec<(String, String)> = vec![
+            ("query".to_string(), sql.to_string()),
+            ("output_format_json_quote_64bit_integers".to_string(), "0".to_string()),
+        ];
+        if use_database {
+            query.push(("database".to_string(), self.database.clone()));
+        }
+        for (name, value) in params {
+            query.push((format!("param_{}", name), value.to_string()));
+        }
+
+        let response = self
+            .http
+            .post(&self.url)
+            .query(&query)
+            .header("X-ClickHouse-User", &self.user)
+            .header("X-ClickHouse-Key", &self.password)
+            .body(data.unwrap_or_default())
+            .send()
+            .await?;
+
+        let status = response.status();
+        let text = response.text().await?;
+        if !status.is_success() {
+            return Err(anyhow!("ClickHouse error ({}): {}", status, text.trim()));
+        }
+        Ok(text)
+    }
+}
+
+/// Format a timestamp for DateTime64(3) columns
+fn format_time(time: chrono::DateTime<chrono::Utc>) -> String {
+    time.format("%Y-%m-%d %H:%M:%S%.3f").to_string()
+}
+
+/// Convert unix milliseconds to an RFC 3339 string (matches the Postgres output)
+fn ms_to_rfc3339(ms: i64) -> String {
+    chrono::DateTime::from_timestamp_millis(ms)
+        .unwrap_or_default()
+        .to_rfc3339()
+}
+
+/// Map ClickHouse's empty-string defaults to JSON null
+fn non_empty(value: &JsonValue) -> JsonValue {
+    match value.as_str() {
+        Some("") => JsonValue::Null,
+        _ => value.clone(),
+    }
+}
+
+/// Parse a JSON document kept in a String column (null when absent)
+fn json_column(value: &JsonValue) -> JsonValue {
+    value
+        .as_str()
+        .and_then(|s| serde_json::from_str(s).ok())
+        .unwrap_or(JsonValue::Null)
+}
@@ -0,0 +1,134 @@
+//! Coalesced WebSocket snapshots
+//!
+//! With many aircraft in view a client gets a message for nearly every
+//! decoded position. WebSocket clients connecting with `?coalesce_ms=1000`
+//! get a [`Coalescer`] instead, which holds back `position_update` and
+//! `aircraft_removed` messages and sends them together once per interval
+//! as a `snapshot_diff`, keeping only the latest update per aircraft.
+//! Everything else (alerts, emergencies, signal metrics) is sent at once.
+
+use serde_json::Value as JsonValue;
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+
+/// Shortest and longest accepted intervals
+pub const MIN_INTERVAL: Duration = Duration::from_millis(100);
+pub const MAX_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Updates held back for the next `snapshot_diff`
+#[derive(Default)]
+pub struct Coalescer {
+    /// ICAO of each pending update, in first-seen order
+    order: Vec<String>,
+    updates: HashMap<String, String>,
+    removed: HashSet<String>,
+}
+
+/// Interval from the `coalesce_ms` parameter, clamped to the accepted range
+pub fn interval(coalesce_ms: u64) -> Duration {
+    Duration::from_millis(coalesce_ms).clamp(MIN_INTERVAL, MAX_INTERVAL)
+}
+
+impl Coalescer {
+    /// Hold back `msg` if it belongs in the next snapshot, otherwise return
+    /// it to be sent now
+    pub fn push(&mut self, msg: String) -> Option<String> {
+        if !msg.contains("\"position_update\"") && !msg.contains("\"aircraft_removed\"") {
+            return Some(msg);
+        }
+        let Ok(json) = serde_json::from_str::<JsonValue>(&msg) else {
+            return Some(msg);
+        };
+        let Some(icao) = json.get("icao").and_then(JsonValue::as_str).map(str::to_string) else {
+            return Some(msg);
+        };
+        match json.get("type").and_then(JsonValue::as_str) {
+            Some("position_update") => {
+                self.removed.remove(&icao);
+                if self.updates.insert(icao.clone(), msg).is_none() {
+                    self.order.push(icao);
+                }
+                None
+            }
+            Some("aircraft_removed") => {
+                if self.updates.remove(&icao).is_some() {
+                    self.order.retain(|i| *i != icao);
+                }
+                self.removed.insert(icao);
+                None
+            }
+            _ => Some(msg),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.updates.is_empty() && self.removed.is_empty()
+    }
+
+    /// Pending updates in order, and the removed ICAOs; leaves the
+    /// coalescer empty
+    pub fn take(&mut self) -> (Vec<String>, Vec<String>) {
+        let mut updates = std::mem::take(&mut self.updates);
+        let pending = std::mem::take(&mut self.order)
+            .into_iter()
+            .filter_map(|icao| updates.remove(&icao))
+            .collect();
+        let mut removed: Vec<String> = self.removed.drain().collect();
+        removed.sort();
+        (pending, removed)
+    }
+}
+
+/// `snapshot_diff` message from already-serialised update messages
+pub fn snapshot_diff(updates: &[String], removed: &[String], time: &str) -> String {
+    format!(
+        "{{\"type\":\"snapshot_diff\",\"time\":{},\"updates\":[{}],\"removed\":{}}}",
+        JsonValue::from(time),
+        updates.join(","),
+        JsonValue::from(removed.to_vec()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn update(icao: &str, altitude: i32) -> String {
+        serde_json::json!({"type": "position_update", "icao": icao, "altitude": altitude}).to_string()
+    }
+
+    #[test]
+    fn test_latest_update_per_aircraft() {
+        let mut coalescer = Coalescer::default();
+        assert!(coalescer.push(update("71BE11", 1000)).is_none());
+        assert!(coalescer.push(update("4840D6", 2000)).is_none());
+        assert!(coalescer.push(update("71BE11", 1100)).is_none());
+        let alert = "{\"type\":\"alert\",\"icao\":\"71BE11\"}".to_string();
+        assert_eq!(coalescer.push(alert.clone()), Some(alert));
+
+        let (updates, removed) = coalescer.take();
+        assert_eq!(updates, vec![update("71BE11", 1100), update("4840D6", 2000)]);
+        assert!(removed.is_empty());
+        assert!(coalescer.is_empty());
+
+        let msg = snapshot_diff(&updates, &removed, "2024-01-15T10:30:00Z");
+        let json: JsonValue = serde_json::from_str(&msg).unwrap();
+        assert_eq!(json["updates"][0]["altitude"], 1100);
+        assert_eq!(json["updates"].as_array().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_removals() {
+        let mut coalescer = Coalescer::default();
+        coalescer.push(update("71BE11", 1000));
+        coalescer.push("{\"type\":\"aircraft_removed\",\"icao\":\"71BE11\"}".into());
+        coalescer.push("{\"type\":\"aircraft_removed\",\"icao\":\"4840D6\"}".into());
+        coalescer.push(update("4840D6", 2000));
+        let (updates, removed) = coalescer.take();
+        assert_eq!(updates, vec![update("4840D6", 2000)]);
+        assert_eq!(removed, vec!["71BE11".to_string()]);
+
+        assert_eq!(interval(5), MIN_INTERVAL);
+        assert_eq!(interval(1000), Duration::from_secs(1));
+    }
+}
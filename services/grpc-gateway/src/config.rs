@@ -0,0 +1,842 @@
+//! Configuration loaded from environment variables
+
+use crate::api::MAX_CLOCK_SKEW_MS;
+use crate::auth::ApiKey;
+use crate::notifiers::NotifierConfig;
+use crate::sanity::PositionQuality;
+use crate::tenants::Tenant;
+use crate::tiles::TileConfig;
+use crate::udp::UdpFormat;
+use chrono::{DateTime, Utc};
+use std::path::PathBuf;
+
+/// Raw frame archival mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RawArchiveMode {
+    /// Raw frames are discarded (default)
+    Off,
+    /// Raw frames are stored in the compressed `raw_frames` hypertable
+    Db,
+    /// Raw frames are appended to hourly rotating files
+    File,
+}
+
+impl RawArchiveMode {
+    fn parse(s: &str) -> Self {
+        match s.to_ascii_lowercase().as_str() {
+            "db" | "database" => Self::Db,
+            "file" | "files" => Self::File,
+            _ => Self::Off,
+        }
+    }
+}
+
+/// Database backend selection
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DbBackend {
+    /// TimescaleDB/PostgreSQL server (default)
+    Postgres,
+    /// Local SQLite file (single-board deployments)
+    Sqlite,
+    /// ClickHouse server over HTTP (high-volume history)
+    ClickHouse,
+}
+
+impl DbBackend {
+    fn parse(s: &str) -> Self {
+        match s.to_ascii_lowercase().as_str() {
+            "sqlite" | "sqlite3" => Self::Sqlite,
+            "clickhouse" => Self::ClickHouse,
+            _ => Self::Postgres,
+        }
+    }
+}
+
+/// Live event distribution between gateway replicas
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PubSubBackend {
+    /// Events stay in this process (default, single replica)
+    Local,
+    /// Redis PUBLISH/SUBSCRIBE
+    Redis,
+    /// NATS subject
+    Nats,
+}
+
+impl PubSubBackend {
+    fn parse(s: &str) -> Self {
+        match s.to_ascii_lowercase().as_str() {
+            "redis" => Self::Redis,
+            "nats" => Self::Nats,
+            _ => Self::Local,
+        }
+    }
+}
+
+/// Which position live clients are sent
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PositionSource {
+    /// Position as decoded (default)
+    Raw,
+    /// Capture host's smoothed track, where it sends one
+    Filtered,
+}
+
+impl PositionSource {
+    fn parse(s: &str) -> Self {
+        match s.to_ascii_lowercase().as_str() {
+            "filtered" | "smoothed" => Self::Filtered,
+            _ => Self::Raw,
+        }
+    }
+}
+
+/// Clock used for stored event times
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventTime {
+    /// When the gateway received the event (default)
+    Received,
+    /// The capture host's `timestamp_ms`, unless missing or in the future
+    Sender,
+}
+
+impl EventTime {
+    fn parse(s: &str) -> Self {
+        match s.to_ascii_lowercase().as_str() {
+            "sender" | "host" => Self::Sender,
+            _ => Self::Received,
+        }
+    }
+
+    /// Time to store for an event the sender stamped `sender_ms` and the
+    /// gateway received at `received_at`. Sender times more than
+    /// [`MAX_CLOCK_SKEW_MS`] ahead of the gateway can't be right and are
+    /// replaced too
+    pub fn pick(self, sender_ms: u64, received_at: DateTime<Utc>) -> DateTime<Utc> {
+        match self {
+            Self::Received => received_at,
+            Self::Sender => DateTime::from_timestamp_millis(sender_ms as i64)
+                .filter(|&sent| sender_ms > 0 && (sent - received_at).num_milliseconds() <= MAX_CLOCK_SKEW_MS)
+                .unwrap_or(received_at),
+        }
+    }
+}
+
+/// What happens to aircraft on the privacy list
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PrivacyAction {
+    /// Not stored, broadcast or archived at all (default)
+    Omit,
+    /// Positions rounded to `PRIVACY_GRID_DEG` before anything uses them
+    Coarsen,
+}
+
+impl PrivacyAction {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "omit" | "drop" => Some(Self::Omit),
+            "coarsen" | "coarse" => Some(Self::Coarsen),
+            _ => None,
+        }
+    }
+}
+
+/// Encodings offered for HTTP responses
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HttpCompression {
+    pub gzip: bool,
+    pub br: bool,
+}
+
+impl HttpCompression {
+    /// Comma-separated encodings; `off` or `none` offers none
+    fn parse(s: &str) -> Self {
+        let mut compression = Self { gzip: false, br: false };
+        for encoding in s.split(',').map(|e| e.trim().to_ascii_lowercase()) {
+            match encoding.as_str() {
+                "gzip" => compression.gzip = true,
+                "br" | "brotli" => compression.br = true,
+                "off" | "none" | "" => {}
+                _ => tracing::warn!("Ignoring unknown HTTP_COMPRESSION encoding: {}", encoding),
+            }
+        }
+        compression
+    }
+}
+
+/// Gateway configuration
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// gRPC listen port (host streams)
+    pub grpc_port: u16,
+
+    /// HTTP/WebSocket listen port
+    pub ws_port: u16,
+
+    /// Beast TCP listen port for dump1090/readsb feeds (off when unset)
+    pub beast_port: Option<u16>,
+
+    /// Unicast or multicast targets of the UDP event output (off when empty)
+    pub udp_output: Vec<std::net::SocketAddr>,
+
+    /// Encoding of UDP datagrams
+    pub udp_format: UdpFormat,
+
+    /// Hops multicast UDP datagrams may take
+    pub udp_multicast_ttl: u32,
+
+    /// OGN APRS server (`host:port`) to follow FLARM traffic from (off when unset)
+    pub ogn_server: Option<String>,
+
+    /// APRS-IS login for the OGN server (read-only, no passcode)
+    pub ogn_user: String,
+
+    /// APRS-IS filter for OGN traffic; default: `OGN_RANGE_KM` around the receiver
+    pub ogn_filter: Option<String>,
+
+    /// Radius of the default OGN filter
+    pub ogn_range_km: f64,
+
+    /// Database backend
+    pub db_backend: DbBackend,
+
+    /// SQLite database file (when `db_backend` is SQLite)
+    pub sqlite_path: PathBuf,
+
+    /// ClickHouse HTTP endpoint and credentials (when `db_backend` is ClickHouse)
+    pub clickhouse_url: String,
+    pub clickhouse_database: String,
+    pub clickhouse_user: String,
+    pub clickhouse_password: String,
+
+    /// Database connection settings
+    pub db_host: String,
+    pub db_port: String,
+    pub db_name: String,
+    pub db_user: String,
+    pub db_password: String,
+
+    /// Directory served instead of the built-in web UI
+    pub static_dir: Option<String>,
+
+    /// Raw frame archival mode
+    pub raw_archive_mode: RawArchiveMode,
+
+    /// Directory for file-based raw frame archives
+    pub raw_archive_dir: PathBuf,
+
+    /// Number of hourly raw frame files to keep
+    pub raw_archive_max_files: usize,
+
+    /// File receiving position writes that failed every retry (off when unset)
+    pub dead_letter_file: Option<PathBuf>,
+
+    /// Size at which the dead letter file stops growing, in megabytes
+    pub dead_letter_max_mb: u64,
+
+    /// Directory for hourly recordings of the live broadcast (off when unset)
+    pub recording_dir: Option<PathBuf>,
+
+    /// Number of hourly broadcast recordings to keep
+    pub recording_max_files: usize,
+
+    /// Silence after which an aircraft's next message starts a new flight
+    pub flight_gap_minutes: u64,
+
+    /// Aircraft database for registration/type enrichment (BaseStation.sqb or CSV)
+    pub aircraft_db_path: Option<PathBuf>,
+
+    /// URL to download the aircraft database from (enables periodic refresh)
+    pub aircraft_db_url: Option<String>,
+
+    /// Optional URL of a SHA-256 checksum for the downloaded database
+    pub aircraft_db_sha256_url: Option<String>,
+
+    /// Hours between aircraft database refresh checks
+    pub aircraft_db_refresh_hours: u64,
+
+    /// Standing-data routes.csv for callsign -> origin/destination lookup
+    pub routes_path: Option<PathBuf>,
+
+    /// adsbdb-compatible route API, `{callsign}` is substituted
+    /// (e.g. `https://api.adsbdb.com/v0/callsign/{callsign}`)
+    pub routes_api_url: Option<String>,
+
+    /// Address classification overrides (`ICAO[-ICAO] civil|military|special` lines)
+    pub address_overrides_path: Option<PathBuf>,
+
+    /// Default webhook for watchlist alerts (rules may override)
+    pub alert_webhook_url: Option<String>,
+
+    /// Default notifier sinks for alerts, as a JSON array
+    /// (e.g. `[{"type":"discord","url":"https://discord.com/api/webhooks/..."}]`)
+    pub alert_notifiers: Vec<NotifierConfig>,
+
+    /// Receiver antenna location `(lat, lon)`, for range statistics
+    pub receiver_location: Option<(f64, f64)>,
+
+    /// Positions farther than this from the receiver are rejected as corrupt
+    /// (0 = no limit; needs the receiver location)
+    pub max_range_km: f64,
+
+    /// Minimum position NIC/NACp; lower positions are rejected, or only
+    /// flagged on live updates with `LOW_QUALITY_POSITIONS=flag`
+    pub position_quality: PositionQuality,
+
+    /// Position broadcast to live clients; stored positions are always raw
+    pub broadcast_position: PositionSource,
+
+    /// Clock for the time columns of stored positions and raw frames
+    pub event_time: EventTime,
+
+    /// Addresses and registrations withheld for privacy (LADD style)
+    pub privacy_list_path: Option<PathBuf>,
+
+    /// Action for privacy list entries that don't name one
+    pub privacy_action: PrivacyAction,
+
+    /// Grid coarsened positions are rounded to, in degrees
+    pub privacy_grid_deg: f64,
+
+    /// Share restrictions (`no-military`, `no-enrichment`, ...) of the
+    /// WebSocket, SSE, REST/GraphQL and UDP outputs
+    pub share_ws: String,
+    pub share_sse: String,
+    pub share_api: String,
+    pub share_udp: String,
+
+    /// Token required from WebSocket/SSE clients (open when unset)
+    pub ws_auth_token: Option<String>,
+
+    /// Serve callers without credentials the public view
+    pub public_viewer: bool,
+
+    /// Grid public positions are rounded to, in degrees
+    pub public_position_grid_deg: Option<f64>,
+
+    /// Delay before positions reach public viewers
+    pub public_delay_secs: u64,
+
+    /// Concurrent WebSocket/SSE connections allowed per client IP (0 = unlimited)
+    pub ws_max_connections_per_ip: usize,
+
+    /// Live event sharing between replicas
+    pub pubsub_backend: PubSubBackend,
+
+    /// Broker URL (defaults to the backend's localhost port)
+    pub pubsub_url: Option<String>,
+
+    /// Redis channel or NATS subject carrying the events
+    pub pubsub_channel: String,
+
+    /// Seconds between database health checks (0 = off)
+    pub db_health_interval_secs: u64,
+
+    /// Seconds between server pings to WebSocket clients
+    pub ws_ping_interval_secs: u64,
+
+    /// Seconds without any client frame (including pongs) before disconnecting
+    pub ws_idle_timeout_secs: u64,
+
+    /// Honour `?compress=deflate` on WebSocket connections
+    pub ws_compression: bool,
+
+    /// Milliseconds between stored positions of one aircraft (0 = store every update)
+    pub position_write_interval_ms: u64,
+
+    /// Milliseconds aircraft list, stats and coverage responses are reused
+    /// (0 = compute every request)
+    pub response_cache_ms: u64,
+
+    /// Content encodings for HTTP responses, when the client accepts them
+    pub http_compression: HttpCompression,
+
+    /// Map tile server proxied at `/tiles`, as a `{z}/{x}/{y}` URL template
+    pub tile_upstream_url: Option<String>,
+
+    /// Directory of proxied (or pre-seeded) map tiles
+    pub tile_cache_dir: Option<PathBuf>,
+
+    /// Requests per minute allowed to the tile server
+    pub tile_rate_per_minute: u32,
+
+    /// Days before a stored tile is fetched again
+    pub tile_cache_days: u64,
+
+    /// Attribution shown for the proxied tiles
+    pub tile_attribution: String,
+
+    /// Minutes of signal metrics kept for `/api/signal/live`
+    pub signal_buffer_minutes: u64,
+
+    /// Messages queued per WebSocket client before the oldest are dropped
+    pub ws_queue_size: usize,
+
+    /// Messages a WebSocket client may drop without catching up before it's
+    /// disconnected (0 = never)
+    pub ws_max_dropped_messages: usize,
+
+    /// REST API keys, as a JSON array
+    /// (e.g. `[{"name":"ops","key":"...","role":"admin"}]`)
+    pub api_keys: Vec<ApiKey>,
+
+    /// HS256 secret for REST API bearer JWTs
+    pub jwt_secret: Option<String>,
+
+    /// Tenants and their receivers' device IDs, as a JSON array
+    /// (e.g. `[{"name":"acme","devices":["acme-rx1"]}]`)
+    pub tenants: Vec<Tenant>,
+
+    /// Requests per minute allowed per credential (0 = unlimited)
+    pub api_rate_limit_per_minute: u32,
+}
+
+impl Config {
+    /// Load configuration from environment variables
+    pub fn from_env() -> Self {
+        Self {
+            grpc_port: std::env::var("GRPC_PORT")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(50051),
+
+            ws_port: std::env::var("WS_PORT")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(8888),
+
+            beast_port: std::env::var("BEAST_PORT").ok().and_then(|s| s.parse().ok()),
+
+            udp_output: std::env::var("UDP_OUTPUT")
+                .map(|s| {
+                    s.split(',')
+                        .map(str::trim)
+                        .filter(|t| !t.is_empty())
+                        .filter_map(|t| match t.parse() {
+                            Ok(addr) => Some(addr),
+                            Err(_) => {
+                                tracing::warn!("Ignoring invalid UDP_OUTPUT target: {}", t);
+                                None
+                            }
+                        })
+                        .collect()
+                })
+                .unwrap_or_default(),
+
+            udp_format: std::env::var("UDP_OUTPUT_FORMAT")
+                .ok()
+                .and_then(|s| UdpFormat::parse(&s))
+                .unwrap_or(UdpFormat::Json),
+
+            udp_multicast_ttl: std::env::var("UDP_MULTICAST_TTL")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(1),
+
+            ogn_server: std::env::var("OGN_APRS_SERVER").ok().filter(|s| !s.is_empty()),
+
+            ogn_user: std::env::var("OGN_APRS_USER").unwrap_or_else(|_| "ADSBGW".to_string()),
+
+            ogn_filter: std::env::var("OGN_FILTER").ok().filter(|s| !s.is_empty()),
+
+            ogn_range_km: std::env::var("OGN_RANGE_KM")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .filter(|km: &f64| *km > 0.0)
+                .unwrap_or(100.0),
+
+            db_backend: std::env::var("DB_BACKEND")
+                .map(|s| DbBackend::parse(&s))
+                .unwrap_or(DbBackend::Postgres),
+
+            sqlite_path: std::env::var("SQLITE_PATH")
+                .map(PathBuf::from)
+                .unwrap_or_else(|_| PathBuf::from("adsb.db")),
+
+            clickhouse_url: std::env::var("CLICKHOUSE_URL")
+                .unwrap_or_else(|_| "http://localhost:8123".to_string()),
+            // Spliced into CREATE DATABASE, so only plain identifiers
+            clickhouse_database: std::env::var("CLICKHOUSE_DATABASE")
+                .ok()
+                .filter(|name| {
+                    let valid = is_identifier(name);
+                    if !valid {
+                        tracing::warn!("Ignoring invalid CLICKHOUSE_DATABASE: {}", name);
+                    }
+                    valid
+                })
+                .unwrap_or_else(|| "adsb".to_string()),
+            clickhouse_user: std::env::var("CLICKHOUSE_USER")
+                .unwrap_or_else(|_| "default".to_string()),
+            clickhouse_password: std::env::var("CLICKHOUSE_PASSWORD").unwrap_or_default(),
+
+            db_host: std::env::var("DB_HOST").unwrap_or_else(|_| "localhost".to_string()),
+            db_port: std::env::var("DB_PORT").unwrap_or_else(|_| "5432".to_string()),
+            db_name: std::env::var("DB_NAME").unwrap_or_else(|_| "adsb".to_string()),
+            db_user: std::env::var("DB_USER").unwrap_or_else(|_| "adsb".to_string()),
+            db_password: std::env::var("DB_PASSWORD").unwrap_or_else(|_| "adsb".to_string()),
+
+            static_dir: std::env::var("STATIC_DIR").ok().filter(|d| !d.is_empty()),
+
+            raw_archive_mode: std::env::var("RAW_ARCHIVE")
+                .map(|s| RawArchiveMode::parse(&s))
+                .unwrap_or(RawArchiveMode::Off),
+
+            raw_archive_dir: std::env::var("RAW_ARCHIVE_DIR")
+                .map(PathBuf::from)
+                .unwrap_or_else(|_| PathBuf::from("/app/raw-frames")),
+
+            raw_archive_max_files: std::env::var("RAW_ARCHIVE_MAX_FILES")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(24 * 7), // one week of hourly files
+
+            dead_letter_file: std::env::var("DEAD_LETTER_FILE")
+                .ok()
+                .filter(|s| !s.is_empty())
+                .map(PathBuf::from),
+
+            dead_letter_max_mb: std::env::var("DEAD_LETTER_MAX_MB")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(100),
+
+            recording_dir: std::env::var("RECORDING_DIR")
+                .ok()
+                .filter(|s| !s.is_empty())
+                .map(PathBuf::from),
+
+            recording_max_files: std::env::var("RECORDING_MAX_FILES")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .filter(|&n: &usize| n > 0)
+                .unwrap_or(24 * 7),
+
+            flight_gap_minutes: std::env::var("FLIGHT_GAP_MINUTES")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(30),
+
+            aircraft_db_path: std::env::var("AIRCRAFT_DB_PATH")
+                .ok()
+                .filter(|s| !s.is_empty())
+                .map(PathBuf::from),
+
+            aircraft_db_url: std::env::var("AIRCRAFT_DB_URL").ok().filter(|s| !s.is_empty()),
+
+            aircraft_db_sha256_url: std::env::var("AIRCRAFT_DB_SHA256_URL")
+                .ok()
+                .filter(|s| !s.is_empty()),
+
+            aircraft_db_refresh_hours: std::env::var("AIRCRAFT_DB_REFRESH_HOURS")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .filter(|h| *h > 0)
+                .unwrap_or(24),
+
+            routes_path: std::env::var("ROUTES_PATH")
+                .ok()
+                .filter(|s| !s.is_empty())
+                .map(PathBuf::from),
+
+            routes_api_url: std::env::var("ROUTES_API_URL").ok().filter(|s| !s.is_empty()),
+
+            address_overrides_path: std::env::var("ADDRESS_OVERRIDES_PATH")
+                .ok()
+                .filter(|s| !s.is_empty())
+                .map(PathBuf::from),
+
+            alert_webhook_url: std::env::var("ALERT_WEBHOOK_URL").ok().filter(|s| !s.is_empty()),
+
+            alert_notifiers: std::env::var("ALERT_NOTIFIERS")
+                .ok()
+                .filter(|s| !s.trim().is_empty())
+                .and_then(|s| match serde_json::from_str(&s) {
+                    Ok(notifiers) => Some(notifiers),
+                    Err(e) => {
+                        tracing::warn!("Ignoring invalid ALERT_NOTIFIERS: {}", e);
+                        None
+                    }
+                })
+                .unwrap_or_default(),
+
+            receiver_location: std::env::var("RECEIVER_LAT")
+                .ok()
+                .and_then(|s| s.parse::<f64>().ok())
+                .zip(std::env::var("RECEIVER_LON").ok().and_then(|s| s.parse::<f64>().ok()))
+                .filter(|(lat, lon)| (-90.0..=90.0).contains(lat) && (-180.0..=180.0).contains(lon)),
+
+            max_range_km: std::env::var("MAX_RANGE_KM")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .filter(|km: &f64| *km >= 0.0)
+                .unwrap_or(500.0),
+
+            position_quality: PositionQuality {
+                min_nic: std::env::var("MIN_POSITION_NIC")
+                    .ok()
+                    .and_then(|s| s.parse().ok())
+                    .unwrap_or(0),
+                min_nac_p: std::env::var("MIN_POSITION_NACP")
+                    .ok()
+                    .and_then(|s| s.parse().ok())
+                    .unwrap_or(0),
+                flag_only: std::env::var("LOW_QUALITY_POSITIONS")
+                    .is_ok_and(|s| s.eq_ignore_ascii_case("flag")),
+            },
+
+            broadcast_position: std::env::var("BROADCAST_POSITION")
+                .map(|s| PositionSource::parse(&s))
+                .unwrap_or(PositionSource::Raw),
+
+            event_time: std::env::var("EVENT_TIME")
+                .map(|s| EventTime::parse(&s))
+                .unwrap_or(EventTime::Received),
+
+            privacy_list_path: std::env::var("PRIVACY_LIST_PATH")
+                .ok()
+                .filter(|s| !s.is_empty())
+                .map(PathBuf::from),
+
+            privacy_action: std::env::var("PRIVACY_ACTION")
+                .ok()
+                .and_then(|s| PrivacyAction::parse(&s))
+                .unwrap_or(PrivacyAction::Omit),
+
+            privacy_grid_deg: std::env::var("PRIVACY_GRID_DEG")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .filter(|&deg: &f64| deg > 0.0 && deg <= 10.0)
+                .unwrap_or(0.1),
+
+            share_ws: std::env::var("SHARE_WS").unwrap_or_default(),
+            share_sse: std::env::var("SHARE_SSE").unwrap_or_default(),
+            share_api: std::env::var("SHARE_API").unwrap_or_default(),
+            share_udp: std::env::var("SHARE_UDP").unwrap_or_default(),
+
+            ws_auth_token: std::env::var("WS_AUTH_TOKEN").ok().filter(|s| !s.is_empty()),
+
+            public_viewer: std::env::var("PUBLIC_VIEWER")
+                .map(|s| s == "1" || s.eq_ignore_ascii_case("true"))
+                .unwrap_or(false),
+
+            public_position_grid_deg: std::env::var("PUBLIC_POSITION_GRID_DEG")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .filter(|&deg: &f64| deg > 0.0 && deg <= 10.0),
+
+            public_delay_secs: std::env::var("PUBLIC_DELAY_SECS")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(0),
+
+            ws_max_connections_per_ip: std::env::var("WS_MAX_CONNECTIONS_PER_IP")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(16),
+
+            pubsub_backend: std::env::var("PUBSUB_BACKEND")
+                .map(|s| PubSubBackend::parse(&s))
+                .unwrap_or(PubSubBackend::Local),
+            pubsub_url: std::env::var("PUBSUB_URL").ok().filter(|s| !s.is_empty()),
+            pubsub_channel: std::env::var("PUBSUB_CHANNEL")
+                .unwrap_or_else(|_| "adsb.events".to_string()),
+
+            db_health_interval_secs: std::env::var("DB_HEALTH_INTERVAL_SECS")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(300),
+
+            ws_ping_interval_secs: std::env::var("WS_PING_INTERVAL_SECS")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .filter(|s| *s > 0)
+                .unwrap_or(30),
+
+            ws_idle_timeout_secs: std::env::var("WS_IDLE_TIMEOUT_SECS")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .filter(|s| *s > 0)
+                .unwrap_or(90),
+
+            ws_compression: std::env::var("WS_COMPRESSION")
+                .map(|s| !(s == "0" || s.eq_ignore_ascii_case("false") || s.eq_ignore_ascii_case("off")))
+                .unwrap_or(true),
+
+            position_write_interval_ms: std::env::var("POSITION_WRITE_INTERVAL_MS")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(1000),
+
+            response_cache_ms: std::env::var("RESPONSE_CACHE_MS")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(2000),
+
+            http_compression: HttpCompression::parse(
+                &std::env::var("HTTP_COMPRESSION").unwrap_or_else(|_| "gzip,br".to_string()),
+            ),
+
+            tile_upstream_url: std::env::var("TILE_UPSTREAM_URL").ok().filter(|s| !s.is_empty()),
+
+            tile_cache_dir: std::env::var("TILE_CACHE_DIR")
+                .ok()
+                .filter(|s| !s.is_empty())
+                .map(PathBuf::from),
+
+            tile_rate_per_minute: std::env::var("TILE_RATE_PER_MINUTE")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(60),
+
+            tile_cache_days: std::env::var("TILE_CACHE_DAYS")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(30),
+
+            tile_attribution: std::env::var("TILE_ATTRIBUTION")
+                .ok()
+                .filter(|s| !s.is_empty())
+                .unwrap_or_else(|| "&copy; OpenStreetMap contributors".to_string()),
+
+            signal_buffer_minutes: std::env::var("SIGNAL_BUFFER_MINUTES")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .filter(|s| *s > 0)
+                .unwrap_or(15),
+
+            ws_queue_size: std::env::var("WS_QUEUE_SIZE")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .filter(|s| *s > 0)
+                .unwrap_or(1000),
+
+            ws_max_dropped_messages: std::env::var("WS_MAX_DROPPED_MESSAGES")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(5000),
+
+            api_keys: std::env::var("API_KEYS")
+                .ok()
+                .filter(|s| !s.trim().is_empty())
+                .and_then(|s| match serde_json::from_str(&s) {
+                    Ok(keys) => Some(keys),
+                    Err(e) => {
+                        tracing::warn!("Ignoring invalid API_KEYS: {}", e);
+                        None
+                    }
+                })
+                .unwrap_or_default(),
+
+            jwt_secret: std::env::var("JWT_SECRET").ok().filter(|s| !s.is_empty()),
+
+            tenants: std::env::var("TENANTS")
+                .ok()
+                .filter(|s| !s.trim().is_empty())
+                .and_then(|s| match serde_json::from_str(&s) {
+                    Ok(tenants) => Some(tenants),
+                    Err(e) => {
+                        tracing::warn!("Ignoring invalid TENANTS: {}", e);
+                        None
+                    }
+                })
+                .unwrap_or_default(),
+
+            api_rate_limit_per_minute: std::env::var("API_RATE_LIMIT_PER_MINUTE")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(600),
+        }
+    }
+
+    /// Default alert sinks: `ALERT_NOTIFIERS` plus the `ALERT_WEBHOOK_URL` shorthand
+    pub fn default_notifiers(&self) -> Vec<NotifierConfig> {
+        let mut sinks: Vec<NotifierConfig> = self
+            .alert_notifiers
+            .iter()
+            .filter(|n| match n.validate() {
+                Ok(()) => true,
+                Err(e) => {
+                    tracing::warn!("Ignoring alert notifier: {}", e);
+                    false
+                }
+            })
+            .cloned()
+            .collect();
+        if let Some(url) = &self.alert_webhook_url {
+            sinks.push(NotifierConfig::Webhook {
+                url: url.clone(),
+                template: None,
+            });
+        }
+        sinks
+    }
+
+    /// Local aircraft database file; downloads default to `aircraft.csv.gz`
+    pub fn aircraft_db_file(&self) -> Option<PathBuf> {
+        self.aircraft_db_path.clone().or_else(|| {
+            self.aircraft_db_url
+                .as_ref()
+                .map(|_| PathBuf::from("aircraft.csv.gz"))
+        })
+    }
+
+    /// Tile proxy settings, when an upstream or a tile directory is set;
+    /// fetched tiles default to `/app/tiles`
+    pub fn tile_config(&self) -> Option<TileConfig> {
+        if self.tile_upstream_url.is_none() && self.tile_cache_dir.is_none() {
+            return None;
+        }
+        Some(TileConfig {
+            upstream: self.tile_upstream_url.clone(),
+            cache_dir: self
+                .tile_cache_dir
+                .clone()
+                .unwrap_or_else(|| PathBuf::from("/app/tiles")),
+            rate_per_minute: self.tile_rate_per_minute,
+            max_age: std::time::Duration::from_secs(self.tile_cache_days * 86_400),
+            attribution: self.tile_attribution.clone(),
+        })
+    }
+
+    /// Build the tokio-postgres style connection string
+    pub fn db_url(&self) -> String {
+        format!(
+            "host={} port={} dbname={} user={} password={}",
+            self.db_host, self.db_port, self.db_name, self.db_user, self.db_password
+        )
+    }
+}
+
+/// `[A-Za-z0-9_]+`, safe to use unquoted in SQL
+fn is_identifier(name: &str) -> bool {
+    !name.is_empty() && name.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_identifier() {
+        assert!(is_identifier("adsb"));
+        assert!(is_identifier("adsb_eu_2"));
+        assert!(!is_identifier(""));
+        assert!(!is_identifier("adsb; DROP DATABASE x"));
+        assert!(!is_identifier("ad`sb"));
+    }
+
+    #[test]
+    fn test_event_time() {
+        let received = DateTime::parse_from_rfc3339("2024-01-15T10:30:00Z").unwrap().with_timezone(&Utc);
+        let ms = |secs: i64| (received + chrono::Duration::seconds(secs)).timestamp_millis() as u64;
+
+        assert_eq!(EventTime::parse("sender"), EventTime::Sender);
+        assert_eq!(EventTime::Received.pick(ms(-3), received), received);
+        assert_eq!(EventTime::Sender.pick(ms(-3), received).timestamp_millis() as u64, ms(-3));
+        assert_eq!(EventTime::Sender.pick(ms(1), received).timestamp_millis() as u64, ms(1));
+        // A clock years ahead, or none at all, can't be trusted
+        assert_eq!(EventTime::Sender.pick(ms(86_400 * 365), received), received);
+        assert_eq!(EventTime::Sender.pick(0, received), received);
+    }
+}
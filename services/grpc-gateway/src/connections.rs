@@ -0,0 +1,179 @@
+//! Access control for streaming clients
+//!
+//! `/ws` and `/api/stream` are open by default. Setting `WS_AUTH_TOKEN`
+//! requires clients to present the token, either as `?token=` (browsers
+//! can't set headers on a WebSocket) or as `Authorization: Bearer`. Each
+//! client IP may also hold at most `WS_MAX_CONNECTIONS_PER_IP` streams at
+//! once; a [`ConnectionGuard`] holds the slot for the connection's lifetime.
+
+use crate::auth::constant_time_eq;
+use axum::{
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::json;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Why a streaming client was turned away
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rejection {
+    Unauthorized,
+    TooManyConnections,
+}
+
+impl IntoResponse for Rejection {
+    fn into_response(self) -> Response {
+        match self {
+            Self::Unauthorized => (
+                StatusCode::UNAUTHORIZED,
+                Json(json!({"error": "missing or invalid token"})),
+            )
+                .into_response(),
+            Self::TooManyConnections => (
+                StatusCode::TOO_MANY_REQUESTS,
+                Json(json!({"error": "too many connections from this address"})),
+            )
+                .into_response(),
+        }
+    }
+}
+
+/// Streaming client settings and open connection counts
+pub struct ConnectionLimits {
+    token: Option<String>,
+    /// 0 means unlimited
+    max_per_ip: usize,
+    pub ping_interval: Duration,
+    pub idle_timeout: Duration,
+    open: Mutex<HashMap<IpAddr, usize>>,
+}
+
+impl ConnectionLimits {
+    pub fn new(
+        token: Option<String>,
+        max_per_ip: usize,
+        ping_interval: Duration,
+        idle_timeout: Duration,
+    ) -> Self {
+        Self {
+            token,
+            max_per_ip,
+            ping_interval,
+            idle_timeout,
+            open: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Check the client's token and reserve a connection slot for its IP
+    pub fn admit(
+        self: &Arc<Self>,
+        ip: IpAddr,
+        query_token: Option<&str>,
+        headers: &HeaderMap,
+    ) -> Result<ConnectionGuard, Rejection> {
+        if self.token.is_some() && !self.authenticates(query_token, headers) {
+            return Err(Rejection::Unauthorized);
+        }
+        self.admit_authenticated(ip)
+    }
+
+    /// Whether a token is required and the client presented it
+    pub fn authenticates(&self, query_token: Option<&str>, headers: &HeaderMap) -> bool {
+        let Some(expected) = &self.token else {
+            return false;
+        };
+        let bearer = headers
+            .get(header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "));
+        let presented = query_token.or(bearer).unwrap_or_default();
+        constant_time_eq(presented.as_bytes(), expected.as_bytes())
+    }
+
+    /// Admit a client that authenticated another way, subject only to the
+    /// per-IP limit
+    pub fn admit_authenticated(self: &Arc<Self>, ip: IpAddr) -> Result<ConnectionGuard, Rejection> {
+        let mut open = self.open.lock().map_err(|_| Rejection::TooManyConnections)?;
+        let count = open.entry(ip).or_default();
+        if self.max_per_ip > 0 && *count >= self.max_per_ip {
+            return Err(Rejection::TooManyConnections);
+        }
+        *count += 1;
+        Ok(ConnectionGuard {
+            limits: self.clone(),
+            ip,
+        })
+    }
+}
+
+/// An admitted client; releases its slot when dropped
+pub struct ConnectionGuard {
+    limits: Arc<ConnectionLimits>,
+    ip: IpAddr,
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        if let Ok(mut open) = self.limits.open.lock() {
+            if let Some(count) = open.get_mut(&self.ip) {
+                *count -= 1;
+                if *count == 0 {
+                    open.remove(&self.ip);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limits(token: Option<&str>, max_per_ip: usize) -> Arc<ConnectionLimits> {
+        Arc::new(ConnectionLimits::new(
+            token.map(str::to_string),
+            max_per_ip,
+            Duration::from_secs(30),
+            Duration::from_secs(90),
+        ))
+    }
+
+    #[test]
+    fn test_token() {
+        let limits = limits(Some("s3cret"), 0);
+        let ip: IpAddr = "192.0.2.1".parse().unwrap();
+        let mut headers = HeaderMap::new();
+        assert_eq!(limits.admit(ip, None, &headers).err(), Some(Rejection::Unauthorized));
+        assert_eq!(
+            limits.admit(ip, Some("wrong"), &headers).err(),
+            Some(Rejection::Unauthorized)
+        );
+        assert!(limits.admit(ip, Some("s3cret"), &headers).is_ok());
+
+        headers.insert(header::AUTHORIZATION, "Bearer s3cret".parse().unwrap());
+        assert!(limits.admit(ip, None, &headers).is_ok());
+    }
+
+    #[test]
+    fn test_per_ip_limit() {
+        let limits = limits(None, 2);
+        let ip: IpAddr = "192.0.2.1".parse().unwrap();
+        let other: IpAddr = "192.0.2.2".parse().unwrap();
+        let headers = HeaderMap::new();
+
+        let first = limits.admit(ip, None, &headers).unwrap();
+        let _second = limits.admit(ip, None, &headers).unwrap();
+        assert_eq!(
+            limits.admit(ip, None, &headers).err(),
+            Some(Rejection::TooManyConnections)
+        );
+        assert!(limits.admit(other, None, &headers).is_ok());
+
+        drop(first);
+        assert!(limits.admit(ip, None, &headers).is_ok());
+    }
+}
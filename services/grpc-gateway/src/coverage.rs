@@ -0,0 +1,260 @@
+//! Receiver coverage heatmaps
+//!
+//! `/api/coverage` bins stored positions in the database into a lat/lon
+//! grid and returns the per-cell counts. With `mode=polar` the grid is
+//! re-binned by bearing sector and range ring around the receiver, and the
+//! furthest cell in each sector gives the range outline.
+
+use crate::geo::{bearing_deg, haversine_km};
+use crate::history;
+use crate::stats::Receiver;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+/// Default time range
+const DEFAULT_RANGE_HOURS: i64 = 24;
+
+/// Default grid cell size in degrees
+const DEFAULT_CELL_DEG: f64 = 0.05;
+
+/// Grid cell size used for polar binning
+const POLAR_CELL_DEG: f64 = 0.01;
+
+/// Most cells returned from the database (the densest are kept)
+pub const MAX_CELLS: i64 = 100_000;
+
+/// Query parameters for the coverage endpoint
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct CoverageParams {
+    /// `grid` (default) or `polar`
+    pub mode: Option<String>,
+    /// RFC 3339 start (inclusive); defaults to 24 hours before `to`
+    pub from: Option<String>,
+    /// RFC 3339 end (exclusive); defaults to now
+    pub to: Option<String>,
+    pub device_id: Option<String>,
+    /// Grid cell size in degrees
+    pub cell_deg: Option<f64>,
+    /// Polar sector width in degrees
+    pub sector_deg: Option<f64>,
+    /// Polar ring width in kilometres
+    pub ring_km: Option<f64>,
+}
+
+/// A validated coverage query as passed to storage
+#[derive(Debug, Clone)]
+pub struct CoverageQuery {
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub device_id: Option<String>,
+    pub cell_deg: f64,
+}
+
+/// How the grid is presented
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CoverageMode {
+    Grid,
+    Polar { sector_deg: f64, ring_km: f64 },
+}
+
+/// One grid cell; its south-west corner is `(lat_idx, lon_idx) * cell_deg`
+#[derive(Debug, Clone, PartialEq)]
+pub struct CoverageCell {
+    pub lat_idx: i64,
+    pub lon_idx: i64,
+    pub count: i64,
+    pub max_altitude: Option<i32>,
+}
+
+impl CoverageCell {
+    fn center(&self, cell_deg: f64) -> (f64, f64) {
+        (
+            (self.lat_idx as f64 + 0.5) * cell_deg,
+            (self.lon_idx as f64 + 0.5) * cell_deg,
+        )
+    }
+}
+
+impl CoverageParams {
+    pub fn parse(&self, now: DateTime<Utc>) -> Result<(CoverageQuery, CoverageMode), String> {
+        let (from, to) = history::parse_range(
+            self.from.as_deref(),
+            self.to.as_deref(),
+            now,
+            chrono::Duration::hours(DEFAULT_RANGE_HOURS),
+        )?;
+
+        let mode = match self.mode.as_deref().unwrap_or("grid") {
+            "grid" => CoverageMode::Grid,
+            "polar" => CoverageMode::Polar {
+                sector_deg: positive(self.sector_deg, 10.0, "sector_deg")?.clamp(1.0, 90.0),
+                ring_km: positive(self.ring_km, 25.0, "ring_km")?.clamp(1.0, 500.0),
+            },
+            _ => return Err("mode must be grid or polar".into()),
+        };
+        let cell_deg = match mode {
+            CoverageMode::Grid => positive(self.cell_deg, DEFAULT_CELL_DEG, "cell_deg")?.clamp(0.01, 5.0),
+            CoverageMode::Polar { .. } => POLAR_CELL_DEG,
+        };
+
+        Ok((
+            CoverageQuery {
+                from,
+                to,
+                device_id: self.device_id.clone().filter(|d| !d.is_empty()),
+                cell_deg,
+            },
+            mode,
+        ))
+    }
+}
+
+fn positive(value: Option<f64>, default: f64, name: &str) -> Result<f64, String> {
+    match value {
+        None => Ok(default),
+        Some(v) if v.is_finite() && v > 0.0 => Ok(v),
+        Some(_) => Err(format!("{} must be positive", name)),
+    }
+}
+
+/// Coverage response, tagged by `mode`
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum Coverage {
+    Grid {
+        cell_deg: f64,
+        max_count: i64,
+        /// `[lat, lon, count, max_altitude]` at cell centres
+        #[schema(value_type = Vec<Vec<f64>>)]
+        cells: Vec<(f64, f64, i64, Option<i32>)>,
+    },
+    Polar {
+        receiver: Receiver,
+        sector_deg: f64,
+        ring_km: f64,
+        /// `[bearing_deg, range_km, count]` at the start of each sector/ring
+        #[schema(value_type = Vec<Vec<f64>>)]
+        bins: Vec<(f64, f64, i64)>,
+        /// `[bearing_deg, range_km]`, the furthest range per sector
+        #[schema(value_type = Vec<Vec<f64>>)]
+        outline: Vec<(f64, f64)>,
+    },
+}
+
+/// Grid response at cell centres
+pub fn grid(cells: &[CoverageCell], cell_deg: f64) -> Coverage {
+    Coverage::Grid {
+        cell_deg,
+        max_count: cells.iter().map(|c| c.count).max().unwrap_or(0),
+        cells: cells
+            .iter()
+            .map(|cell| {
+                let (lat, lon) = cell.center(cell_deg);
+                (round(lat), round(lon), cell.count, cell.max_altitude)
+            })
+            .collect(),
+    }
+}
+
+/// Polar response: the grid re-binned by sector and ring around the receiver
+pub fn polar(
+    cells: &[CoverageCell],
+    cell_deg: f64,
+    receiver: (f64, f64),
+    sector_deg: f64,
+    ring_km: f64,
+) -> Coverage {
+    let sectors = (360.0 / sector_deg).ceil() as usize;
+    let mut bins: std::collections::BTreeMap<(usize, usize), i64> = Default::default();
+    let mut outline = vec![0.0f64; sectors];
+
+    for cell in cells {
+        let (lat, lon) = cell.center(cell_deg);
+        let range = haversine_km(receiver.0, receiver.1, lat, lon);
+        let sector = ((bearing_deg(receiver.0, receiver.1, lat, lon) / sector_deg) as usize).min(sectors - 1);
+        let ring = (range / ring_km) as usize;
+        *bins.entry((sector, ring)).or_default() += cell.count;
+        outline[sector] = outline[sector].max(range);
+    }
+
+    Coverage::Polar {
+        receiver: receiver.into(),
+        sector_deg,
+        ring_km,
+        bins: bins
+            .into_iter()
+            .map(|((sector, ring), count)| (sector as f64 * sector_deg, ring as f64 * ring_km, count))
+            .collect(),
+        outline: outline
+            .iter()
+            .enumerate()
+            .map(|(sector, range)| (sector as f64 * sector_deg, round(*range)))
+            .collect(),
+    }
+}
+
+fn round(v: f64) -> f64 {
+    (v * 1e4).round() / 1e4
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cell(lat_idx: i64, lon_idx: i64, count: i64) -> CoverageCell {
+        CoverageCell {
+            lat_idx,
+            lon_idx,
+            count,
+            max_altitude: Some(30000),
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        let now = Utc::now();
+        let (query, mode) = CoverageParams::default().parse(now).unwrap();
+        assert_eq!(mode, CoverageMode::Grid);
+        assert_eq!(query.cell_deg, DEFAULT_CELL_DEG);
+        assert_eq!(query.to - query.from, chrono::Duration::hours(24));
+
+        let polar = CoverageParams {
+            mode: Some("polar".into()),
+            ..Default::default()
+        };
+        let (query, mode) = polar.parse(now).unwrap();
+        assert_eq!(query.cell_deg, POLAR_CELL_DEG);
+        assert!(matches!(mode, CoverageMode::Polar { .. }));
+
+        let bad = CoverageParams {
+            cell_deg: Some(-1.0),
+            ..Default::default()
+        };
+        assert!(bad.parse(now).is_err());
+    }
+
+    #[test]
+    fn test_grid() {
+        let coverage = grid(&[cell(750, 2528, 5), cell(-1, -1, 9)], 0.05);
+        let json = serde_json::to_value(coverage).unwrap();
+        assert_eq!(json["mode"], "grid");
+        assert_eq!(json["max_count"], 9);
+        assert_eq!(json["cells"][0], serde_json::json!([37.525, 126.425, 5, 30000]));
+        assert_eq!(json["cells"][1], serde_json::json!([-0.025, -0.025, 9, 30000]));
+    }
+
+    #[test]
+    fn test_polar() {
+        // Receiver at 37.0,126.0; one cell ~55 km north, one ~46 km east-south-east
+        let cells = [cell(3749, 12600, 4), cell(3689, 12649, 6)];
+        let coverage = polar(&cells, 0.01, (37.0, 126.0), 90.0, 25.0);
+        let json = serde_json::to_value(coverage).unwrap();
+        assert_eq!(json["bins"][0], serde_json::json!([0.0, 50.0, 4]));
+        assert_eq!(json["bins"][1], serde_json::json!([90.0, 25.0, 6]));
+        let outline = json["outline"].as_array().unwrap();
+        assert_eq!(outline.len(), 4);
+        assert!((outline[0][1].as_f64().unwrap() - 55.0).abs() < 0.5);
+    }
+}
@@ -0,0 +1,198 @@
+//! Database health
+//!
+//! Every `DB_HEALTH_INTERVAL_SECS` (default 300, 0 = off) the gateway asks
+//! the database how large its tables are, how old their oldest rows are,
+//! how many chunks (TimescaleDB) or parts (ClickHouse) they are split into,
+//! and how the Postgres connection pool is used. The last report is served
+//! at `GET /api/db/health` and exported on `/metrics`. A table whose oldest
+//! row is more than a day past its retention (TimescaleDB's retention
+//! policy, ClickHouse's TTL or the SQLite backend's pruning) gets a
+//! warning, logged when it first appears: the retention job isn't keeping
+//! up, or isn't running.
+
+use crate::api::ApiError;
+use crate::storage::Storage;
+use crate::AppState;
+use axum::{extract::State, response::IntoResponse, Json};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::{error, warn};
+use utoipa::ToSchema;
+
+/// Age past retention before a table is reported; retention jobs run
+/// periodically and drop whole chunks or parts at a time
+const RETENTION_SLACK_DAYS: f64 = 1.0;
+
+/// One health check
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct DbHealth {
+    pub backend: String,
+    pub checked_at: DateTime<Utc>,
+    /// Postgres connection pool; absent for other backends
+    pub pool: Option<PoolStats>,
+    pub tables: Vec<TableHealth>,
+    /// Tables whose retention isn't keeping up
+    pub warnings: Vec<String>,
+}
+
+/// Connection pool use
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct PoolStats {
+    pub max_size: usize,
+    /// Connections open
+    pub size: usize,
+    /// Open connections idle
+    pub available: usize,
+    /// Requests waiting for a connection
+    pub waiting: usize,
+}
+
+/// Size and age of one table
+#[derive(Debug, Clone, Default, Serialize, ToSchema)]
+pub struct TableHealth {
+    pub name: String,
+    /// Estimated row count
+    pub rows: Option<i64>,
+    /// On disk, indexes included
+    pub bytes: Option<i64>,
+    /// Time of the oldest row, for time-series tables
+    pub oldest: Option<DateTime<Utc>>,
+    /// TimescaleDB chunks or ClickHouse active parts
+    pub chunks: Option<i64>,
+    /// Age beyond which rows are dropped
+    pub retention_days: Option<f64>,
+}
+
+impl TableHealth {
+    /// The warning for a table holding rows well past its retention
+    fn retention_warning(&self, now: DateTime<Utc>) -> Option<String> {
+        let age_days = (now - self.oldest?).num_seconds() as f64 / 86_400.0;
+        let retention_days = self.retention_days?;
+        (age_days > retention_days + RETENTION_SLACK_DAYS).then(|| {
+            format!(
+                "{}: oldest row is {:.1} days old, retention is {} days",
+                self.name, age_days, retention_days
+            )
+        })
+    }
+}
+
+impl DbHealth {
+    pub fn new(backend: &str, pool: Option<PoolStats>, tables: Vec<TableHealth>, now: DateTime<Utc>) -> Self {
+        let warnings = tables.iter().filter_map(|t| t.retention_warning(now)).collect();
+        Self {
+            backend: backend.to_string(),
+            checked_at: now,
+            pool,
+            tables,
+            warnings,
+        }
+    }
+}
+
+/// The latest check
+#[derive(Default)]
+pub struct DbHealthMonitor {
+    latest: Mutex<Option<DbHealth>>,
+}
+
+impl DbHealthMonitor {
+    pub fn latest(&self) -> Option<DbHealth> {
+        self.latest.lock().ok()?.clone()
+    }
+
+    fn set(&self, health: DbHealth) {
+        if let Ok(mut latest) = self.latest.lock() {
+            *latest = Some(health);
+        }
+    }
+}
+
+/// Check the database every `interval`, logging tables that start lagging
+pub fn spawn_checker(monitor: Arc<DbHealthMonitor>, db: Arc<dyn Storage>, interval: Duration) {
+    tokio::spawn(async move {
+        let mut lagging = HashSet::new();
+        let mut failing = false;
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            match db.db_health().await {
+                Ok(health) => {
+                    let mut now_lagging = HashSet::new();
+                    for table in &health.tables {
+                        if let Some(warning) = table.retention_warning(health.checked_at) {
+                            if !lagging.contains(&table.name) {
+                                warn!("Database retention lagging: {}", warning);
+                            }
+                            now_lagging.insert(table.name.clone());
+                        }
+                    }
+                    lagging = now_lagging;
+                    failing = false;
+                    monitor.set(health);
+                }
+                Err(e) => {
+                    // Logged once per outage
+                    if !failing {
+                        error!("Database health check failed: {}", e);
+                    }
+                    failing = true;
+                }
+            }
+        }
+    });
+}
+
+/// Database table sizes, ages and connection pool use
+#[utoipa::path(
+    get,
+    path = "/api/db/health",
+    tag = "receiver",
+    responses(
+        (status = 200, description = "The last health check, or a new one when checks are off", body = DbHealth),
+        (status = 500, description = "No database, or the check failed", body = ApiError),
+    ),
+    security((), ("api_key" = []), ("bearer" = []))
+)]
+pub async fn get_db_health(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    if let Some(health) = state.db_health.latest() {
+        return Json(health).into_response();
+    }
+    match state.db_writer.db_health().await {
+        Ok(health) => Json(health).into_response(),
+        Err(e) => {
+            error!("Database health check failed: {}", e);
+            ApiError::internal(e)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retention_warnings() {
+        let now = Utc::now();
+        let table = |name: &str, age_days: i64, retention_days: Option<f64>| TableHealth {
+            name: name.into(),
+            oldest: Some(now - chrono::Duration::days(age_days)),
+            retention_days,
+            ..Default::default()
+        };
+        let health = DbHealth::new(
+            "postgres",
+            None,
+            vec![
+                table("aircraft_positions", 30, Some(30.0)),
+                table("raw_frames", 16, Some(14.0)),
+                table("flights", 400, None),
+            ],
+            now,
+        );
+        assert_eq!(health.warnings, vec!["raw_frames: oldest row is 16.0 days old, retention is 14 days"]);
+    }
+}
@@ -0,0 +1,280 @@
+//! Postgres schema check
+//!
+//! The TimescaleDB schema is created by `services/timescaledb/init.sql` and
+//! upgraded by the scripts in `services/timescaledb/migrations`, outside the
+//! gateway. A database missing one of them used to surface only as query
+//! errors inside request handlers and writers. On connecting, the gateway
+//! compares the tables and views it uses with `information_schema` and logs
+//! each missing table, view or column with the script that adds it. The
+//! SQLite and ClickHouse backends create and upgrade their own schemas.
+
+use std::collections::{HashMap, HashSet};
+
+const INIT_SCRIPT: &str = "services/timescaledb/init.sql";
+
+/// A table or view the gateway uses, its columns, and the script creating it
+struct Relation {
+    name: &'static str,
+    columns: &'static [&'static str],
+    script: &'static str,
+}
+
+const RELATIONS: &[Relation] = &[
+    Relation {
+        name: "aircraft_info",
+        columns: &[
+            "icao_address",
+            "callsign",
+            "category",
+            "registration",
+            "aircraft_type",
+            "first_seen",
+            "last_seen",
+            "message_count",
+            "squawk",
+            "last_device_id",
+        ],
+        script: INIT_SCRIPT,
+    },
+    Relation {
+        name: "aircraft_positions",
+        columns: &[
+            "time",
+            "icao_address",
+            "device_id",
+            "latitude",
+            "longitude",
+            "altitude_ft",
+            "ground_speed_kts",
+            "track_deg",
+            "magnetic_heading_deg",
+            "vertical_rate_fpm",
+            "baro_rate_fpm",
+            "geom_rate_fpm",
+            "squawk",
+        ],
+        script: INIT_SCRIPT,
+    },
+    Relation {
+        name: "current_aircraft",
+        columns: &[
+            "icao_address",
+            "callsign",
+            "latitude",
+            "longitude",
+            "altitude_ft",
+            "track_deg",
+            "magnetic_heading_deg",
+            "baro_rate_fpm",
+            "geom_rate_fpm",
+            "device_id",
+            "last_seen",
+        ],
+        script: INIT_SCRIPT,
+    },
+    Relation {
+        name: "sdr_status",
+        columns: &[
+            "device_id",
+            "connected",
+            "sample_rate",
+            "center_freq",
+            "gain_db",
+            "last_heartbeat",
+            "software_version",
+            "uptime_secs",
+            "decoder",
+            "clock_offset_ms",
+        ],
+        script: INIT_SCRIPT,
+    },
+    Relation {
+        name: "current_sdr_status",
+        columns: &[
+            "device_id",
+            "connected",
+            "status",
+            "signal_power_db",
+            "noise_floor_db",
+            "snr_db",
+            "software_version",
+            "uptime_secs",
+            "decoder",
+            "clock_offset_ms",
+        ],
+        script: INIT_SCRIPT,
+    },
+    Relation {
+        name: "signal_metrics",
+        columns: &["time", "device_id", "signal_power_db", "noise_floor_db", "snr_db", "messages_decoded"],
+        script: INIT_SCRIPT,
+    },
+    Relation {
+        name: "raw_frames",
+        columns: &["time", "device_id", "raw_message", "signal_level"],
+        script: "services/timescaledb/migrations/002_add_raw_frames.sql",
+    },
+    Relation {
+        name: "flights",
+        columns: &["icao_address", "callsign", "first_seen", "last_seen", "max_altitude_ft", "position_count", "device_id"],
+        script: "services/timescaledb/migrations/003_add_flights.sql",
+    },
+    Relation {
+        name: "watchlist_rules",
+        columns: &["id", "name", "match_kind", "pattern", "geofence", "webhook_url", "notifiers", "cooldown_seconds", "enabled"],
+        script: "services/timescaledb/migrations/004_add_watchlist_rules.sql",
+    },
+    Relation {
+        name: "geofences",
+        columns: &["id", "name", "shape", "min_altitude_ft", "max_altitude_ft", "log_events"],
+        script: "services/timescaledb/migrations/005_add_geofences.sql",
+    },
+    Relation {
+        name: "geofence_events",
+        columns: &["time", "geofence_id", "icao_address", "callsign", "event", "reason", "latitude", "longitude", "altitude_ft"],
+        script: "services/timescaledb/migrations/005_add_geofences.sql",
+    },
+    Relation {
+        name: "emergencies",
+        columns: &[
+            "icao_address",
+            "started_at",
+            "last_seen",
+            "ended_at",
+            "emergency",
+            "squawk",
+            "callsign",
+            "latitude",
+            "longitude",
+            "altitude_ft",
+            "device_id",
+        ],
+        script: "services/timescaledb/migrations/007_add_emergencies.sql",
+    },
+    Relation {
+        name: "stats_rollups",
+        columns: &[
+            "period",
+            "bucket",
+            "device_id",
+            "messages",
+            "positions",
+            "unique_aircraft",
+            "max_range_km",
+            "frames_decoded",
+            "crc_errors",
+        ],
+        script: "services/timescaledb/migrations/008_add_stats_rollups.sql",
+    },
+    Relation {
+        name: "sdr_status_history",
+        columns: &["time", "device_id", "connected", "sample_rate", "center_freq", "gain_db", "software_version", "decoder"],
+        script: "services/timescaledb/migrations/009_add_sdr_status_history.sql",
+    },
+    Relation {
+        name: "squawk_changes",
+        columns: &["time", "icao_address", "squawk", "previous_squawk", "device_id"],
+        script: "services/timescaledb/migrations/013_squawk_changes.sql",
+    },
+    Relation {
+        name: "receiver_sessions",
+        columns: &["device_id", "started_at", "last_seen", "ended_at", "end_reason"],
+        script: "services/timescaledb/migrations/014_add_receiver_sessions.sql",
+    },
+    Relation {
+        name: "stats_df_rollups",
+        columns: &["period", "bucket", "device_id", "downlink_format", "frames"],
+        script: "services/timescaledb/migrations/015_add_stats_df_rollups.sql",
+    },
+];
+
+/// Columns added after their table or view, and the script adding each
+const ADDED_COLUMNS: &[(&str, &str, &str)] = &[
+    ("aircraft_positions", "device_id", "001_add_device_id.sql"),
+    ("current_aircraft", "device_id", "001_add_device_id.sql"),
+    ("watchlist_rules", "notifiers", "006_add_watchlist_notifiers.sql"),
+    ("aircraft_info", "squawk", "010_aircraft_info_from_gateway.sql"),
+    ("aircraft_info", "last_device_id", "010_aircraft_info_from_gateway.sql"),
+    ("aircraft_positions", "track_deg", "011_track_and_heading.sql"),
+    ("aircraft_positions", "magnetic_heading_deg", "011_track_and_heading.sql"),
+    ("current_aircraft", "track_deg", "011_track_and_heading.sql"),
+    ("current_aircraft", "magnetic_heading_deg", "011_track_and_heading.sql"),
+    ("aircraft_positions", "baro_rate_fpm", "012_vertical_rate_source.sql"),
+    ("aircraft_positions", "geom_rate_fpm", "012_vertical_rate_source.sql"),
+    ("current_aircraft", "baro_rate_fpm", "012_vertical_rate_source.sql"),
+    ("current_aircraft", "geom_rate_fpm", "012_vertical_rate_source.sql"),
+    ("squawk_changes", "previous_squawk", "013_squawk_changes.sql"),
+    ("squawk_changes", "device_id", "013_squawk_changes.sql"),
+    ("sdr_status", "software_version", "016_add_decoder_config.sql"),
+    ("sdr_status", "uptime_secs", "016_add_decoder_config.sql"),
+    ("sdr_status", "decoder", "016_add_decoder_config.sql"),
+    ("current_sdr_status", "software_version", "016_add_decoder_config.sql"),
+    ("current_sdr_status", "uptime_secs", "016_add_decoder_config.sql"),
+    ("current_sdr_status", "decoder", "016_add_decoder_config.sql"),
+    ("sdr_status_history", "software_version", "016_add_decoder_config.sql"),
+    ("sdr_status_history", "decoder", "016_add_decoder_config.sql"),
+    ("sdr_status", "clock_offset_ms", "017_add_clock_offset.sql"),
+    ("current_sdr_status", "clock_offset_ms", "017_add_clock_offset.sql"),
+];
+
+/// What the database lacks, given its columns by table or view name, one
+/// message per missing relation or relation with missing columns
+pub fn problems(found: &HashMap<String, HashSet<String>>) -> Vec<String> {
+    let mut problems = Vec::new();
+    for relation in RELATIONS {
+        let Some(columns) = found.get(relation.name) else {
+            problems.push(format!("{} is missing; apply {}", relation.name, relation.script));
+            continue;
+        };
+        // Grouped by the script that adds them
+        let mut missing: Vec<(String, Vec<&str>)> = Vec::new();
+        for column in relation.columns.iter().filter(|c| !columns.contains(**c)) {
+            let script = ADDED_COLUMNS
+                .iter()
+                .find(|(r, c, _)| *r == relation.name && c == column)
+                .map(|(_, _, script)| format!("services/timescaledb/migrations/{}", script))
+                .unwrap_or_else(|| relation.script.to_string());
+            match missing.iter_mut().find(|(s, _)| *s == script) {
+                Some((_, columns)) => columns.push(column),
+                None => missing.push((script, vec![column])),
+            }
+        }
+        for (script, columns) in missing {
+            problems.push(format!(
+                "{} has no column {}; apply {}",
+                relation.name,
+                columns.join(", "),
+                script
+            ));
+        }
+    }
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_schema_problems() {
+        let mut found: HashMap<String, HashSet<String>> = RELATIONS
+            .iter()
+            .map(|r| (r.name.to_string(), r.columns.iter().map(|c| c.to_string()).collect()))
+            .collect();
+        assert!(problems(&found).is_empty());
+
+        found.remove("receiver_sessions");
+        let sdr_status = found.get_mut("sdr_status").unwrap();
+        for column in ["uptime_secs", "decoder", "clock_offset_ms"] {
+            sdr_status.remove(column);
+        }
+        assert_eq!(
+            problems(&found),
+            vec![
+                "sdr_status has no column uptime_secs, decoder; apply services/timescaledb/migrations/016_add_decoder_config.sql",
+                "sdr_status has no column clock_offset_ms; apply services/timescaledb/migrations/017_add_clock_offset.sql",
+                "receiver_sessions is missing; apply services/timescaledb/migrations/014_add_receiver_sessions.sql",
+            ]
+        );
+    }
+}
@@ -1,11 +1,38 @@
-//! Database writer for TimescaleDB
+//! Database writer for TimescaleDB (PostgreSQL storage backend)
 
-use crate::adsb::{AircraftEvent, DeviceStatus};
-use anyhow::Result;
+use crate::adsb::{AircraftEvent, DeviceStatus, RawFrame};
+use crate::admin::PositionPurge;
+use crate::alerts::{MatchKind, NewWatchRule, WatchRule, DEFAULT_COOLDOWN_SECONDS};
+use crate::coverage::{CoverageCell, CoverageQuery, MAX_CELLS};
+use crate::db_health::{DbHealth, PoolStats, TableHealth};
+use crate::db_schema;
+use crate::decoder_config;
+use crate::emergencies::{emergency_name, EmergencyRecord};
+use crate::flights::FlightRecord;
+use crate::geofences::{GeofenceDef, GeofenceEvent, NewGeofence};
+use crate::history::{HistoryQuery, PositionPoint};
+use crate::stats::{self, Period, StatsRollup};
+use crate::storage::Storage;
+use crate::uptime::ReceiverSession;
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
 use deadpool_postgres::{Config, Pool, Runtime};
 use serde_json::Value as JsonValue;
 use tokio_postgres::NoTls;
-use tracing::{debug, warn};
+use std::collections::{HashMap, HashSet};
+use tracing::{debug, error, warn};
+
+/// Tables in health reports, with their time column
+const HEALTH_TABLES: &[(&str, Option<&str>)] = &[
+    ("aircraft_positions", Some("time")),
+    ("raw_frames", Some("time")),
+    ("signal_metrics", Some("time")),
+    ("sdr_status_history", Some("time")),
+    ("aircraft_info", None),
+    ("flights", None),
+    ("squawk_changes", None),
+    ("stats_rollups", None),
+];
 
 /// Database writer with connection pooling
 pub struct DbWriter {
@@ -38,6 +65,25 @@ impl DbWriter {
         let client = pool.get().await?;
         client.execute("SELECT 1", &[]).await?;
 
+        let mut found: HashMap<String, HashSet<String>> = HashMap::new();
+        for row in client
+            .query(
+                "SELECT table_name::text, column_name::text FROM information_schema.columns
+                WHERE table_schema = current_schema()",
+                &[],
+            )
+            .await?
+        {
+            found.entry(row.get(0)).or_default().insert(row.get(1));
+        }
+        let problems = db_schema::problems(&found);
+        for problem in &problems {
+            error!("Database schema: {}", problem);
+        }
+        if !problems.is_empty() {
+            warn!("Queries using what's missing will fail until the schema is upgraded");
+        }
+
         Ok(Self { pool: Some(pool) })
     }
 
@@ -50,87 +96,198 @@ impl DbWriter {
     fn has_db(&self) -> bool {
         self.pool.is_some()
     }
+}
+
+#[tonic::async_trait]
+impl Storage for DbWriter {
+    fn backend_name(&self) -> &'static str {
+        if self.has_db() {
+            "postgres"
+        } else {
+            "none"
+        }
+    }
 
-    /// Insert aircraft position
-    pub async fn insert_position(&self, event: &AircraftEvent) -> Result<()> {
+    /// Insert aircraft position and update aircraft_info
+    async fn insert_position(
+        &self,
+        event: &AircraftEvent,
+        time: DateTime<Utc>,
+        messages: u32,
+    ) -> Result<()> {
         let pool = match &self.pool {
             Some(p) => p,
             None => return Ok(()),
         };
 
+        // One statement, so one round trip: the position row, the squawk
+        // change and the aircraft_info upsert commit together. Every part
+        // sees aircraft_info as it was before the upsert, so the squawk
+        // change is recorded against the previous code.
         let client = pool.get().await?;
-
-        // Only insert if we have valid position
-        if event.latitude == 0.0 && event.longitude == 0.0 {
-            debug!("Skipping position insert for {} - no position data", event.icao);
-            return Ok(());
-        }
-
+        let stmt = client
+            .prepare_cached(
+                "WITH position AS (
+                    INSERT INTO aircraft_positions (
+                        time, icao_address, device_id, latitude, longitude,
+                        altitude_ft, ground_speed_kts, track_deg, magnetic_heading_deg,
+                        vertical_rate_fpm, baro_rate_fpm, geom_rate_fpm, squawk
+                    )
+                    SELECT $12::timestamptz, $1::text, NULLIF($15::text, ''), $2::float8, $3::float8,
+                        $4::int4, $5::float4, $6::float4, $7::float4,
+                        $8::int4, $9::int4, $10::int4, $11::text
+                    WHERE $2::float8 IS NOT NULL AND $3::float8 IS NOT NULL
+                ),
+                squawk_change AS (
+                    INSERT INTO squawk_changes (time, icao_address, previous_squawk, squawk, device_id)
+                    SELECT $12, $1,
+                        (SELECT squawk FROM aircraft_info WHERE icao_address = $1),
+                        $11, NULLIF($15, '')
+                    WHERE NULLIF($11, '') IS NOT NULL
+                      AND NOT EXISTS (
+                        SELECT 1 FROM aircraft_info WHERE icao_address = $1 AND squawk = $11
+                      )
+                )
+                -- Absent or empty fields keep the stored value
+                INSERT INTO aircraft_info (
+                    icao_address, callsign, category, squawk, last_device_id,
+                    first_seen, last_seen, message_count
+                ) VALUES (
+                    $1, NULLIF($13, ''), NULLIF($14, ''), NULLIF($11, ''), NULLIF($15, ''),
+                    $12, $12, $16
+                )
+                ON CONFLICT (icao_address) DO UPDATE SET
+                    callsign = COALESCE(EXCLUDED.callsign, aircraft_info.callsign),
+                    category = COALESCE(EXCLUDED.category, aircraft_info.category),
+                    squawk = COALESCE(EXCLUDED.squawk, aircraft_info.squawk),
+                    last_device_id = COALESCE(EXCLUDED.last_device_id, aircraft_info.last_device_id),
+                    last_seen = EXCLUDED.last_seen,
+                    message_count = aircraft_info.message_count + EXCLUDED.message_count",
+            )
+            .await?;
         client
             .execute(
-                "INSERT INTO aircraft_positions (
-                    time, icao_address, latitude, longitude,
-                    altitude_ft, ground_speed_kts, heading_deg, vertical_rate_fpm,
-                    squawk
-                ) VALUES (
-                    NOW(), $1, $2, $3, $4, $5, $6, $7, $8
-                )",
+                &stmt,
                 &[
                     &event.icao,
                     &event.latitude,
                     &event.longitude,
                     &event.altitude_ft,
                     &event.speed_kts,
-                    &event.heading_deg,
+                    &event.track_deg,
+                    &event.magnetic_heading_deg,
                     &event.vertical_rate_fpm,
+                    &event.baro_rate_fpm,
+                    &event.geom_rate_fpm,
                     &event.squawk,
+                    &time,
+                    &event.callsign,
+                    &event.category,
+                    &event.device_id,
+                    &(messages as i64),
                 ],
             )
             .await?;
+        Ok(())
+    }
 
-        // Update aircraft_info if we have callsign
-        if !event.callsign.is_empty() {
-            client
-                .execute(
-                    "INSERT INTO aircraft_info (icao_address, callsign, last_seen)
-                     VALUES ($1, $2, NOW())
-                     ON CONFLICT (icao_address) DO UPDATE SET
-                        callsign = EXCLUDED.callsign,
-                        last_seen = NOW()",
-                    &[&event.icao, &event.callsign],
-                )
-                .await?;
+    /// Insert a batch of raw frames into the archive table
+    async fn insert_raw_frames(&self, frames: &[RawFrame]) -> Result<()> {
+        let pool = match &self.pool {
+            Some(p) => p,
+            None => return Ok(()),
+        };
+
+        if frames.is_empty() {
+            return Ok(());
         }
 
+        let client = pool.get().await?;
+
+        let times: Vec<chrono::DateTime<chrono::Utc>> = frames
+            .iter()
+            .map(|f| {
+                chrono::DateTime::from_timestamp_millis(f.timestamp_ms as i64)
+                    .unwrap_or_else(chrono::Utc::now)
+            })
+            .collect();
+        let device_ids: Vec<&str> = frames.iter().map(|f| f.device_id.as_str()).collect();
+        let data: Vec<&[u8]> = frames.iter().map(|f| f.data.as_slice()).collect();
+        let signal_levels: Vec<i32> = frames.iter().map(|f| f.signal_level as i32).collect();
+
+        // Single round trip for the whole batch
+        client
+            .execute(
+                "INSERT INTO raw_frames (time, device_id, raw_message, signal_level)
+                 SELECT * FROM UNNEST($1::timestamptz[], $2::text[], $3::bytea[], $4::int[])",
+                &[&times, &device_ids, &data, &signal_levels],
+            )
+            .await?;
+
+        debug!("Archived {} raw frames", frames.len());
         Ok(())
     }
 
     /// Update SDR device status
-    pub async fn update_sdr_status(&self, status: &DeviceStatus) -> Result<()> {
+    async fn update_sdr_status(
+        &self,
+        status: &DeviceStatus,
+        messages_per_second: f32,
+    ) -> Result<()> {
         let pool = match &self.pool {
             Some(p) => p,
             None => return Ok(()),
         };
 
         let client = pool.get().await?;
+        let decoder = decoder_config::settings_json(status);
 
         client
             .execute(
                 "INSERT INTO sdr_status (
-                    device_id, connected, sample_rate, center_freq, gain_db, last_heartbeat
-                ) VALUES ($1, $2, $3, $4, $5, NOW())
+                    device_id, connected, sample_rate, center_freq, gain_db, last_heartbeat,
+                    messages_per_second, software_version, uptime_secs, decoder, clock_offset_ms
+                ) VALUES ($1, $2, $3, $4, $5, NOW(), $6, $7, $8, $9, $10)
                 ON CONFLICT (device_id) DO UPDATE SET
                     connected = EXCLUDED.connected,
                     sample_rate = EXCLUDED.sample_rate,
                     center_freq = EXCLUDED.center_freq,
                     gain_db = EXCLUDED.gain_db,
-                    last_heartbeat = NOW()",
+                    last_heartbeat = NOW(),
+                    messages_per_second = EXCLUDED.messages_per_second,
+                    software_version = EXCLUDED.software_version,
+                    uptime_secs = EXCLUDED.uptime_secs,
+                    decoder = EXCLUDED.decoder,
+                    clock_offset_ms = EXCLUDED.clock_offset_ms",
+                &[
+                    &status.device_id,
+                    &status.connected,
+                    &(status.sample_rate as i32),
+                    &(status.center_freq as i64),
+                    &status.gain_db,
+                    &messages_per_second,
+                    &decoder_config::software_version(status),
+                    &(status.uptime_secs as i64),
+                    &decoder,
+                    &status.clock_offset_ms,
+                ],
+            )
+            .await?;
+
+        client
+            .execute(
+                "INSERT INTO sdr_status_history (
+                    time, device_id, connected, sample_rate, center_freq, gain_db,
+                    software_version, decoder
+                ) VALUES (NOW(), $1, $2, $3, $4, $5, $6, $7)",
                 &[
                     &status.device_id,
                     &status.connected,
                     &(status.sample_rate as i32),
                     &(status.center_freq as i64),
                     &status.gain_db,
+                    &decoder_config::software_version(status),
+                    &decoder,
                 ],
             )
             .await?;
@@ -138,60 +295,168 @@ impl DbWriter {
         Ok(())
     }
 
-    /// Get current aircraft list
-    pub async fn get_current_aircraft(&self) -> Result<Vec<JsonValue>> {
+    /// Get one aircraft's latest state
+    async fn get_aircraft(&self, icao: &str) -> Result<Option<JsonValue>> {
         let pool = match &self.pool {
             Some(p) => p,
-            None => return Ok(vec![]),
+            None => return Ok(None),
         };
 
         let client = pool.get().await?;
 
-        let rows = client
-            .query(
+        let row = client
+            .query_opt(
                 "SELECT
-                    icao_address as icao,
-                    callsign,
-                    latitude as lat,
-                    longitude as lon,
-                    altitude_ft as altitude,
-                    ground_speed_kts as speed,
-                    heading_deg as heading,
-                    vertical_rate_fpm as vrate,
-                    squawk,
-                    last_seen as seen,
-                    message_count as messages
-                FROM current_aircraft
-                ORDER BY last_seen DESC",
-                &[],
+                    i.icao_address as icao,
+                    i.callsign,
+                    i.category,
+                    i.registration,
+                    i.aircraft_type,
+                    i.first_seen,
+                    i.last_seen,
+                    i.message_count as messages,
+                    p.latitude as lat,
+                    p.longitude as lon,
+                    p.altitude_ft as altitude,
+                    p.ground_speed_kts as speed,
+                    p.track_deg as track,
+                    p.magnetic_heading_deg as magnetic_heading,
+                    p.vertical_rate_fpm as vrate,
+                    p.baro_rate_fpm as baro_rate,
+                    p.geom_rate_fpm as geom_rate,
+                    p.squawk,
+                    p.device_id,
+                    p.time as seen
+                FROM aircraft_info i
+                LEFT JOIN LATERAL (
+                    SELECT *
+                    FROM aircraft_positions
+                    WHERE icao_address = i.icao_address
+                    ORDER BY time DESC
+                    LIMIT 1
+                ) p ON TRUE
+                WHERE i.icao_address = $1",
+                &[&icao],
             )
             .await?;
 
-        let aircraft: Vec<JsonValue> = rows
+        Ok(row.map(|row| {
+            let time = |name: &str| {
+                row.get::<_, Option<chrono::DateTime<chrono::Utc>>>(name)
+                    .map(|dt| dt.to_rfc3339())
+            };
+            serde_json::json!({
+                "icao": row.get::<_, String>("icao"),
+                "callsign": row.get::<_, Option<String>>("callsign"),
+                "category": row.get::<_, Option<String>>("category"),
+                "registration": row.get::<_, Option<String>>("registration"),
+                "aircraft_type": row.get::<_, Option<String>>("aircraft_type"),
+                "lat": row.get::<_, Option<f64>>("lat"),
+                "lon": row.get::<_, Option<f64>>("lon"),
+                "altitude": row.get::<_, Option<i32>>("altitude"),
+                "speed": row.get::<_, Option<f32>>("speed"),
+                "track": row.get::<_, Option<f32>>("track"),
+                "magnetic_heading": row.get::<_, Option<f32>>("magnetic_heading"),
+                "vrate": row.get::<_, Option<i32>>("vrate"),
+                "baro_rate": row.get::<_, Option<i32>>("baro_rate"),
+                "geom_rate": row.get::<_, Option<i32>>("geom_rate"),
+                "squawk": row.get::<_, Option<String>>("squawk"),
+                "device_id": row.get::<_, Option<String>>("device_id"),
+                "seen": time("seen"),
+                "first_seen": time("first_seen"),
+                "last_seen": time("last_seen"),
+                "messages": row.get::<_, Option<i64>>("messages"),
+            })
+        }))
+    }
+
+    /// Get aircraft position trail
+    async fn get_aircraft_trail(
+        &self,
+        icao: &str,
+        devices: Option<&[String]>,
+        from: DateTime<Utc>,
+        to: Option<DateTime<Utc>>,
+        bucket_secs: Option<i64>,
+    ) -> Result<Vec<JsonValue>> {
+        let pool = match &self.pool {
+            Some(p) => p,
+            None => return Ok(vec![]),
+        };
+
+        let client = pool.get().await?;
+
+        let rows = match bucket_secs {
+            None => {
+                client
+                    .query(
+                        "SELECT
+                            time,
+                            latitude as lat,
+                            longitude as lon,
+                            altitude_ft as altitude,
+                            device_id,
+                            NULL::bigint AS positions
+                        FROM aircraft_positions
+                        WHERE icao_address = $1
+                          AND time >= $2
+                          AND ($3::timestamptz IS NULL OR time < $3)
+                          AND ($4::text[] IS NULL OR device_id = ANY($4))
+                          AND latitude IS NOT NULL
+                          AND longitude IS NOT NULL
+                        ORDER BY time ASC",
+                        &[&icao, &from, &to, &devices],
+                    )
+                    .await?
+            }
+            Some(secs) => {
+                client
+                    .query(
+                        "SELECT DISTINCT ON (bucket)
+                            time, lat, lon, altitude, device_id,
+                            count(*) OVER (PARTITION BY bucket) AS positions
+                        FROM (
+                            SELECT
+                                time_bucket(make_interval(secs => $5), time) AS bucket,
+                                time,
+                                latitude as lat,
+                                longitude as lon,
+                                altitude_ft as altitude,
+                                device_id
+                            FROM aircraft_positions
+                            WHERE icao_address = $1
+                              AND time >= $2
+                              AND ($3::timestamptz IS NULL OR time < $3)
+                              AND ($4::text[] IS NULL OR device_id = ANY($4))
+                              AND latitude IS NOT NULL
+                              AND longitude IS NOT NULL
+                        ) p
+                        ORDER BY bucket ASC, time DESC",
+                        &[&icao, &from, &to, &devices, &(secs as f64)],
+                    )
+                    .await?
+            }
+        };
+
+        let trail: Vec<JsonValue> = rows
             .iter()
             .map(|row| {
                 serde_json::json!({
-                    "icao": row.get::<_, Option<String>>("icao"),
-                    "callsign": row.get::<_, Option<String>>("callsign"),
-                    "lat": row.get::<_, Option<f64>>("lat"),
-                    "lon": row.get::<_, Option<f64>>("lon"),
+                    "time": row.get::<_, chrono::DateTime<chrono::Utc>>("time").to_rfc3339(),
+                    "lat": row.get::<_, f64>("lat"),
+                    "lon": row.get::<_, f64>("lon"),
                     "altitude": row.get::<_, Option<i32>>("altitude"),
-                    "speed": row.get::<_, Option<f32>>("speed"),
-                    "heading": row.get::<_, Option<f32>>("heading"),
-                    "vrate": row.get::<_, Option<i32>>("vrate"),
-                    "squawk": row.get::<_, Option<String>>("squawk"),
-                    "seen": row.get::<_, Option<chrono::DateTime<chrono::Utc>>>("seen")
-                        .map(|dt| dt.to_rfc3339()),
-                    "messages": row.get::<_, Option<i64>>("messages"),
+                    "device_id": row.get::<_, Option<String>>("device_id"),
+                    "positions": row.get::<_, Option<i64>>("positions"),
                 })
             })
             .collect();
 
-        Ok(aircraft)
+        Ok(trail)
     }
 
-    /// Get aircraft position trail
-    pub async fn get_aircraft_trail(&self, icao: &str, minutes: i32) -> Result<Vec<JsonValue>> {
+    /// Get a page of stored positions
+    async fn get_position_history(&self, query: &HistoryQuery) -> Result<Vec<PositionPoint>> {
         let pool = match &self.pool {
             Some(p) => p,
             None => return Ok(vec![]),
@@ -202,52 +467,100 @@ impl DbWriter {
         let rows = client
             .query(
                 "SELECT
-                    time,
-                    latitude as lat,
-                    longitude as lon,
-                    altitude_ft as altitude
+                    time, icao_address, latitude, longitude, altitude_ft,
+                    ground_speed_kts, track_deg, magnetic_heading_deg, vertical_rate_fpm,
+                    baro_rate_fpm, geom_rate_fpm, squawk, device_id
                 FROM aircraft_positions
-                WHERE icao_address = $1
-                  AND time > NOW() - INTERVAL '1 minute' * $2
+                WHERE time >= $1 AND time < $2
+                  AND ($3::text IS NULL OR icao_address = $3)
+                  AND ($4::timestamptz IS NULL OR (time, icao_address) > ($4, $5::text))
+                  AND ($7::text[] IS NULL OR device_id = ANY($7))
                   AND latitude IS NOT NULL
                   AND longitude IS NOT NULL
-                ORDER BY time ASC",
-                &[&icao, &minutes],
+                ORDER BY time, icao_address
+                LIMIT $6",
+                &[
+                    &query.from,
+                    &query.to,
+                    &query.icao,
+                    &query.after.as_ref().map(|c| c.time),
+                    &query.after.as_ref().map(|c| c.icao.as_str()),
+                    &query.fetch,
+                    &query.devices,
+                ],
             )
             .await?;
 
-        let trail: Vec<JsonValue> = rows
+        Ok(rows
             .iter()
-            .map(|row| {
-                serde_json::json!({
-                    "time": row.get::<_, chrono::DateTime<chrono::Utc>>("time").to_rfc3339(),
-                    "lat": row.get::<_, f64>("lat"),
-                    "lon": row.get::<_, f64>("lon"),
-                    "altitude": row.get::<_, Option<i32>>("altitude"),
-                })
+            .map(|row| PositionPoint {
+                time: row.get("time"),
+                icao: row.get("icao_address"),
+                lat: row.get("latitude"),
+                lon: row.get("longitude"),
+                altitude: row.get("altitude_ft"),
+                speed: row.get("ground_speed_kts"),
+                track: row.get("track_deg"),
+                magnetic_heading: row.get("magnetic_heading_deg"),
+                vrate: row.get("vertical_rate_fpm"),
+                baro_rate: row.get("baro_rate_fpm"),
+                geom_rate: row.get("geom_rate_fpm"),
+                squawk: row.get("squawk"),
+                device_id: row.get("device_id"),
             })
-            .collect();
+            .collect())
+    }
 
-        Ok(trail)
+    /// Bin positions into a lat/lon grid
+    async fn get_coverage(&self, query: &CoverageQuery) -> Result<Vec<CoverageCell>> {
+        let pool = match &self.pool {
+            Some(p) => p,
+            None => return Ok(vec![]),
+        };
+
+        let client = pool.get().await?;
+
+        let rows = client
+            .query(
+                "SELECT
+                    floor(latitude / $3)::bigint AS lat_idx,
+                    floor(longitude / $3)::bigint AS lon_idx,
+                    count(*) AS count,
+                    max(altitude_ft) AS max_altitude
+                FROM aircraft_positions
+                WHERE time >= $1 AND time < $2
+                  AND ($4::text IS NULL OR device_id = $4)
+                  AND latitude IS NOT NULL
+                  AND longitude IS NOT NULL
+                GROUP BY 1, 2
+                ORDER BY count DESC
+                LIMIT $5",
+                &[&query.from, &query.to, &query.cell_deg, &query.device_id, &MAX_CELLS],
+            )
+            .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| CoverageCell {
+                lat_idx: row.get("lat_idx"),
+                lon_idx: row.get("lon_idx"),
+                count: row.get("count"),
+                max_altitude: row.get("max_altitude"),
+            })
+            .collect())
     }
 
-    /// Get current SDR status
-    pub async fn get_sdr_status(&self) -> Result<JsonValue> {
+    /// Get every device's current status
+    async fn get_sdr_status(&self) -> Result<Vec<JsonValue>> {
         let pool = match &self.pool {
             Some(p) => p,
-            None => {
-                return Ok(serde_json::json!({
-                    "connected": false,
-                    "status": "no_database",
-                    "error": "Database not available"
-                }));
-            }
+            None => return Ok(vec![]),
         };
 
         let client = pool.get().await?;
 
-        let row = client
-            .query_opt(
+        let rows = client
+            .query(
                 "SELECT
                     device_id,
                     connected,
@@ -256,34 +569,942 @@ impl DbWriter {
                     gain_db,
                     last_heartbeat,
                     messages_per_second,
+                    software_version,
+                    uptime_secs,
+                    decoder,
+                    clock_offset_ms,
                     CASE
                         WHEN connected AND last_heartbeat > NOW() - INTERVAL '30 seconds' THEN 'active'
                         WHEN last_heartbeat > NOW() - INTERVAL '5 minutes' THEN 'stale'
                         ELSE 'disconnected'
                     END as status
                 FROM current_sdr_status
-                ORDER BY last_heartbeat DESC
-                LIMIT 1",
+                ORDER BY device_id",
                 &[],
             )
             .await?;
 
-        match row {
-            Some(row) => Ok(serde_json::json!({
-                "device_id": row.get::<_, Option<String>>("device_id"),
-                "connected": row.get::<_, Option<bool>>("connected").unwrap_or(false),
-                "sample_rate": row.get::<_, Option<i32>>("sample_rate"),
-                "center_freq": row.get::<_, Option<i64>>("center_freq"),
-                "gain_db": row.get::<_, Option<f32>>("gain_db"),
-                "last_heartbeat": row.get::<_, Option<chrono::DateTime<chrono::Utc>>>("last_heartbeat")
-                    .map(|dt| dt.to_rfc3339()),
-                "messages_per_second": row.get::<_, Option<f32>>("messages_per_second"),
-                "status": row.get::<_, Option<String>>("status"),
-            })),
-            None => Ok(serde_json::json!({
-                "connected": false,
-                "status": "disconnected",
-            })),
+        Ok(rows
+            .iter()
+            .map(|row| {
+                serde_json::json!({
+                    "device_id": row.get::<_, String>("device_id"),
+                    "connected": row.get::<_, Option<bool>>("connected").unwrap_or(false),
+                    "sample_rate": row.get::<_, Option<i32>>("sample_rate"),
+                    "center_freq": row.get::<_, Option<i64>>("center_freq"),
+                    "gain_db": row.get::<_, Option<f32>>("gain_db"),
+                    "last_heartbeat": row.get::<_, Option<chrono::DateTime<chrono::Utc>>>("last_heartbeat")
+                        .map(|dt| dt.to_rfc3339()),
+                    "messages_per_second": row.get::<_, Option<f32>>("messages_per_second"),
+                    "software_version": row.get::<_, Option<String>>("software_version"),
+                    "uptime_secs": row.get::<_, Option<i64>>("uptime_secs"),
+                    "decoder": row.get::<_, Option<JsonValue>>("decoder"),
+                    "clock_offset_ms": row.get::<_, Option<i64>>("clock_offset_ms"),
+                    "status": row.get::<_, Option<String>>("status"),
+                })
+            })
+            .collect())
+    }
+
+    /// Get a device's heartbeats, most recent first
+    async fn get_sdr_history(
+        &self,
+        device_id: &str,
+        since: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<Vec<JsonValue>> {
+        let pool = match &self.pool {
+            Some(p) => p,
+            None => return Ok(vec![]),
+        };
+
+        let client = pool.get().await?;
+
+        let rows = client
+            .query(
+                "SELECT time, connected, sample_rate, center_freq, gain_db, software_version, decoder
+                FROM sdr_status_history
+                WHERE device_id = $1 AND time >= $2
+                ORDER BY time DESC
+                LIMIT $3",
+                &[&device_id, &since, &limit],
+            )
+            .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| {
+                serde_json::json!({
+                    "time": row.get::<_, DateTime<Utc>>("time").to_rfc3339(),
+                    "connected": row.get::<_, Option<bool>>("connected").unwrap_or(false),
+                    "sample_rate": row.get::<_, Option<i32>>("sample_rate"),
+                    "center_freq": row.get::<_, Option<i64>>("center_freq"),
+                    "gain_db": row.get::<_, Option<f32>>("gain_db"),
+                    "software_version": row.get::<_, Option<String>>("software_version"),
+                    "decoder": row.get::<_, Option<JsonValue>>("decoder"),
+                })
+            })
+            .collect())
+    }
+
+    /// Insert or update a flight session
+    async fn upsert_flight(&self, flight: &FlightRecord) -> Result<()> {
+        let pool = match &self.pool {
+            Some(p) => p,
+            None => return Ok(()),
+        };
+
+        let client = pool.get().await?;
+
+        client
+            .execute(
+                "INSERT INTO flights (
+                    icao_address, callsign, first_seen, last_seen,
+                    max_altitude_ft, position_count, device_id
+                ) VALUES ($1, NULLIF($2, ''), $3, $4, $5, $6, NULLIF($7, ''))
+                ON CONFLICT (icao_address, first_seen) DO UPDATE SET
+                    callsign = COALESCE(EXCLUDED.callsign, flights.callsign),
+                    last_seen = EXCLUDED.last_seen,
+                    max_altitude_ft = EXCLUDED.max_altitude_ft,
+                    position_count = EXCLUDED.position_count,
+                    device_id = COALESCE(EXCLUDED.device_id, flights.device_id)",
+                &[
+                    &flight.icao,
+                    &flight.callsign,
+                    &flight.first_seen,
+                    &flight.last_seen,
+                    &flight.max_altitude_ft,
+                    &flight.position_count,
+                    &flight.device_id,
+                ],
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /// Get recent flight sessions
+    async fn get_flights(&self, icao: Option<&str>, devices: Option<&[String]>, limit: i64) -> Result<Vec<JsonValue>> {
+        let pool = match &self.pool {
+            Some(p) => p,
+            None => return Ok(vec![]),
+        };
+
+        let client = pool.get().await?;
+
+        let rows = client
+            .query(
+                "SELECT
+                    icao_address as icao,
+                    callsign,
+                    first_seen,
+                    last_seen,
+                    max_altitude_ft,
+                    position_count,
+                    device_id
+                FROM flights
+                WHERE ($1::text IS NULL OR icao_address = $1)
+                  AND ($3::text[] IS NULL OR device_id = ANY($3))
+                ORDER BY first_seen DESC
+                LIMIT $2",
+                &[&icao, &limit, &devices],
+            )
+            .await?;
+
+        let flights: Vec<JsonValue> = rows
+            .iter()
+            .map(|row| {
+                let first_seen = row.get::<_, chrono::DateTime<chrono::Utc>>("first_seen");
+                let last_seen = row.get::<_, chrono::DateTime<chrono::Utc>>("last_seen");
+                serde_json::json!({
+                    "icao": row.get::<_, String>("icao"),
+                    "callsign": row.get::<_, Option<String>>("callsign"),
+                    "first_seen": first_seen.to_rfc3339(),
+                    "last_seen": last_seen.to_rfc3339(),
+                    "duration_s": (last_seen - first_seen).num_seconds(),
+                    "max_altitude": row.get::<_, Option<i32>>("max_altitude_ft"),
+                    "positions": row.get::<_, i32>("position_count"),
+                    "device_id": row.get::<_, Option<String>>("device_id"),
+                })
+            })
+            .collect();
+
+        Ok(flights)
+    }
+
+    /// Insert or update an emergency occurrence
+    async fn upsert_emergency(&self, record: &EmergencyRecord) -> Result<()> {
+        let pool = match &self.pool {
+            Some(p) => p,
+            None => return Ok(()),
+        };
+
+        let client = pool.get().await?;
+        let (lat, lon) = record.position.unzip();
+
+        client
+            .execute(
+                "INSERT INTO emergencies (
+                    icao_address, started_at, last_seen, ended_at, emergency, squawk,
+                    callsign, latitude, longitude, altitude_ft, device_id
+                ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, NULLIF($11, ''))
+                ON CONFLICT (icao_address, started_at) DO UPDATE SET
+                    last_seen = EXCLUDED.last_seen,
+                    ended_at = EXCLUDED.ended_at,
+                    callsign = COALESCE(EXCLUDED.callsign, emergencies.callsign),
+                    latitude = COALESCE(EXCLUDED.latitude, emergencies.latitude),
+                    longitude = COALESCE(EXCLUDED.longitude, emergencies.longitude),
+                    altitude_ft = COALESCE(EXCLUDED.altitude_ft, emergencies.altitude_ft),
+                    device_id = COALESCE(EXCLUDED.device_id, emergencies.device_id)",
+                &[
+                    &record.icao,
+                    &record.started_at,
+                    &record.last_seen,
+                    &record.ended_at,
+                    &emergency_name(record.emergency),
+                    &record.squawk,
+                    &record.callsign,
+                    &lat,
+                    &lon,
+                    &record.altitude_ft,
+                    &record.device_id,
+                ],
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /// Get emergency occurrences
+    async fn upsert_stats(&self, rows: &[StatsRollup]) -> Result<()> {
+        let pool = match &self.pool {
+            Some(p) => p,
+            None => return Ok(()),
+        };
+
+        let client = pool.get().await?;
+        let stmt = client
+            .prepare_cached(
+                "INSERT INTO stats_rollups (
+                    period, bucket, device_id, messages, positions, unique_aircraft,
+                    max_range_km, frames_decoded, crc_errors
+                ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+                ON CONFLICT (period, bucket, device_id) DO UPDATE SET
+                    messages = stats_rollups.messages + EXCLUDED.messages,
+                    positions = stats_rollups.positions + EXCLUDED.positions,
+                    unique_aircraft = GREATEST(stats_rollups.unique_aircraft, EXCLUDED.unique_aircraft),
+                    max_range_km = GREATEST(stats_rollups.max_range_km, EXCLUDED.max_range_km),
+                    frames_decoded = stats_rollups.frames_decoded + EXCLUDED.frames_decoded,
+                    crc_errors = stats_rollups.crc_errors + EXCLUDED.crc_errors",
+            )
+            .await?;
+
+        for row in rows {
+            client
+                .execute(
+                    &stmt,
+                    &[
+                        &row.period.name(),
+                        &row.bucket,
+                        &row.device_id,
+                        &row.messages,
+                        &row.positions,
+                        &row.unique_aircraft,
+                        &row.max_range_km,
+                        &row.frames_decoded,
+                        &row.crc_errors,
+                    ],
+                )
+                .await?;
+        }
+
+        let stmt = client
+            .prepare_cached(
+                "INSERT INTO stats_df_rollups (period, bucket, device_id, downlink_format, frames)
+                VALUES ($1, $2, $3, $4, $5)
+                ON CONFLICT (period, bucket, device_id, downlink_format) DO UPDATE SET
+                    frames = stats_df_rollups.frames + EXCLUDED.frames",
+            )
+            .await?;
+        for row in rows {
+            for (df, frames) in &row.downlink_formats {
+                client
+                    .execute(
+                        &stmt,
+                        &[&row.period.name(), &row.bucket, &row.device_id, &(*df as i32), frames],
+                    )
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn get_stats(&self, period: Period, since: DateTime<Utc>) -> Result<Vec<StatsRollup>> {
+        let pool = match &self.pool {
+            Some(p) => p,
+            None => return Ok(vec![]),
+        };
+
+        let client = pool.get().await?;
+
+        let rows = client
+            .query(
+                "SELECT bucket, device_id, messages, positions, unique_aircraft,
+                        max_range_km, frames_decoded, crc_errors
+                FROM stats_rollups
+                WHERE period = $1 AND bucket >= $2
+                ORDER BY bucket, device_id",
+                &[&period.name(), &since],
+            )
+            .await?;
+
+        let mut rollups: Vec<StatsRollup> = rows
+            .iter()
+            .map(|row| StatsRollup {
+                period,
+                bucket: row.get("bucket"),
+                device_id: row.get("device_id"),
+                messages: row.get("messages"),
+                positions: row.get("positions"),
+                unique_aircraft: row.get("unique_aircraft"),
+                max_range_km: row.get("max_range_km"),
+                frames_decoded: row.get("frames_decoded"),
+                crc_errors: row.get("crc_errors"),
+                downlink_formats: Default::default(),
+            })
+            .collect();
+
+        let formats = client
+            .query(
+                "SELECT bucket, device_id, downlink_format, frames
+                FROM stats_df_rollups
+                WHERE period = $1 AND bucket >= $2",
+                &[&period.name(), &since],
+            )
+            .await?;
+        stats::add_downlink_formats(
+            &mut rollups,
+            formats.iter().map(|row| {
+                (
+                    row.get("bucket"),
+                    row.get("device_id"),
+                    row.get::<_, i32>("downlink_format") as u32,
+                    row.get("frames"),
+                )
+            }),
+        );
+        Ok(rollups)
+    }
+
+    async fn delete_positions(&self, purge: &PositionPurge) -> Result<u64> {
+        let pool = match &self.pool {
+            Some(p) => p,
+            None => return Ok(0),
+        };
+
+        let client = pool.get().await?;
+        let deleted = client
+            .execute(
+                "DELETE FROM aircraft_positions
+                WHERE ($1::text IS NULL OR icao_address = $1)
+                  AND ($2::timestamptz IS NULL OR time >= $2)
+                  AND ($3::timestamptz IS NULL OR time < $3)",
+                &[&purge.icao, &purge.from, &purge.to],
+            )
+            .await?;
+        Ok(deleted)
+    }
+
+    async fn rename_device(&self, from: &str, to: &str) -> Result<u64> {
+        let pool = match &self.pool {
+            Some(p) => p,
+            None => return Ok(0),
+        };
+
+        let mut client = pool.get().await?;
+        let tx = client.transaction().await?;
+        let mut updated = 0;
+
+        for table in [
+            "aircraft_positions",
+            "signal_metrics",
+            "sdr_status_history",
+            "raw_frames",
+            "flights",
+            "emergencies",
+            "receiver_sessions",
+        ] {
+            updated += tx
+                .execute(
+                    &format!("UPDATE {} SET device_id = $2 WHERE device_id = $1", table),
+                    &[&from, &to],
+                )
+                .await?;
+        }
+        updated += tx
+            .execute(
+                "UPDATE aircraft_info SET last_device_id = $2 WHERE last_device_id = $1",
+                &[&from, &to],
+            )
+            .await?;
+
+        // One status row per device: the new ID's own status wins
+        tx.execute(
+            "DELETE FROM sdr_status
+            WHERE device_id = $1 AND EXISTS (SELECT 1 FROM sdr_status WHERE device_id = $2)",
+            &[&from, &to],
+        )
+        .await?;
+        updated += tx
+            .execute(
+                "UPDATE sdr_status SET device_id = $2 WHERE device_id = $1",
+                &[&from, &to],
+            )
+            .await?;
+
+        // Rollups are added to the new ID's buckets like a flush would be
+        updated += tx
+            .execute(
+                "INSERT INTO stats_rollups (
+                    period, bucket, device_id, messages, positions, unique_aircraft,
+                    max_range_km, frames_decoded, crc_errors
+                )
+                SELECT period, bucket, $2, messages, positions, unique_aircraft,
+                       max_range_km, frames_decoded, crc_errors
+                FROM stats_rollups WHERE device_id = $1
+                ON CONFLICT (period, bucket, device_id) DO UPDATE SET
+                    messages = stats_rollups.messages + EXCLUDED.messages,
+                    positions = stats_rollups.positions + EXCLUDED.positions,
+                    unique_aircraft = GREATEST(stats_rollups.unique_aircraft, EXCLUDED.unique_aircraft),
+                    max_range_km = GREATEST(stats_rollups.max_range_km, EXCLUDED.max_range_km),
+                    frames_decoded = stats_rollups.frames_decoded + EXCLUDED.frames_decoded,
+                    crc_errors = stats_rollups.crc_errors + EXCLUDED.crc_errors",
+                &[&from, &to],
+            )
+            .await?;
+        tx.execute("DELETE FROM stats_rollups WHERE device_id = $1", &[&from])
+            .await?;
+        tx.execute(
+            "INSERT INTO stats_df_rollups (period, bucket, device_id, downlink_format, frames)
+            SELECT period, bucket, $2, downlink_format, frames
+            FROM stats_df_rollups WHERE device_id = $1
+            ON CONFLICT (period, bucket, device_id, downlink_format) DO UPDATE SET
+                frames = stats_df_rollups.frames + EXCLUDED.frames",
+            &[&from, &to],
+        )
+        .await?;
+        tx.execute("DELETE FROM stats_df_rollups WHERE device_id = $1", &[&from])
+            .await?;
+
+        tx.commit().await?;
+        Ok(updated)
+    }
+
+    async fn get_emergencies(&self, active_only: bool, devices: Option<&[String]>, limit: i64) -> Result<Vec<JsonValue>> {
+        let pool = match &self.pool {
+            Some(p) => p,
+            None => return Ok(vec![]),
+        };
+
+        let client = pool.get().await?;
+
+        let rows = client
+            .query(
+                "SELECT
+                    icao_address as icao,
+                    started_at,
+                    last_seen,
+                    ended_at,
+                    emergency,
+                    squawk,
+                    callsign,
+                    latitude as lat,
+                    longitude as lon,
+                    altitude_ft,
+                    device_id
+                FROM emergencies
+                WHERE (NOT $1 OR ended_at IS NULL)
+                  AND ($3::text[] IS NULL OR device_id = ANY($3))
+                ORDER BY started_at DESC
+                LIMIT $2",
+                &[&active_only, &limit, &devices],
+            )
+            .await?;
+
+        let emergencies: Vec<JsonValue> = rows
+            .iter()
+            .map(|row| {
+                let ended_at = row.get::<_, Option<chrono::DateTime<chrono::Utc>>>("ended_at");
+                serde_json::json!({
+                    "icao": row.get::<_, String>("icao"),
+                    "emergency": row.get::<_, String>("emergency"),
+                    "squawk": row.get::<_, String>("squawk"),
+                    "callsign": row.get::<_, Option<String>>("callsign"),
+                    "active": ended_at.is_none(),
+                    "started_at": row.get::<_, chrono::DateTime<chrono::Utc>>("started_at").to_rfc3339(),
+                    "last_seen": row.get::<_, chrono::DateTime<chrono::Utc>>("last_seen").to_rfc3339(),
+                    "ended_at": ended_at.map(|t| t.to_rfc3339()),
+                    "lat": row.get::<_, Option<f64>>("lat"),
+                    "lon": row.get::<_, Option<f64>>("lon"),
+                    "altitude": row.get::<_, Option<i32>>("altitude_ft"),
+                    "device_id": row.get::<_, Option<String>>("device_id"),
+                })
+            })
+            .collect();
+
+        Ok(emergencies)
+    }
+
+    async fn upsert_receiver_session(&self, session: &ReceiverSession) -> Result<()> {
+        let pool = match &self.pool {
+            Some(p) => p,
+            None => return Ok(()),
+        };
+
+        let client = pool.get().await?;
+        client
+            .execute(
+                "INSERT INTO receiver_sessions (device_id, started_at, last_seen, ended_at, end_reason)
+                VALUES ($1, $2, $3, $4, $5)
+                ON CONFLICT (device_id, started_at) DO UPDATE SET
+                    last_seen = EXCLUDED.last_seen,
+                    ended_at = EXCLUDED.ended_at,
+                    end_reason = EXCLUDED.end_reason",
+                &[
+                    &session.device_id,
+                    &session.started_at,
+                    &session.last_seen,
+                    &session.ended_at,
+                    &session.end_reason,
+                ],
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn get_receiver_sessions(&self, since: DateTime<Utc>) -> Result<Vec<ReceiverSession>> {
+        let pool = match &self.pool {
+            Some(p) => p,
+            None => return Ok(vec![]),
+        };
+
+        let client = pool.get().await?;
+        let rows = client
+            .query(
+                "SELECT device_id, started_at, last_seen, ended_at, end_reason
+                FROM receiver_sessions
+                WHERE COALESCE(ended_at, last_seen) >= $1
+                ORDER BY started_at",
+                &[&since],
+            )
+            .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| ReceiverSession {
+                device_id: row.get("device_id"),
+                started_at: row.get("started_at"),
+                last_seen: row.get("last_seen"),
+                ended_at: row.get("ended_at"),
+                end_reason: row.get("end_reason"),
+            })
+            .collect())
+    }
+
+    /// List watchlist rules
+    async fn get_watchlist(&self) -> Result<Vec<WatchRule>> {
+        let pool = match &self.pool {
+            Some(p) => p,
+            None => return Ok(vec![]),
+        };
+
+        let client = pool.get().await?;
+
+        let rows = client
+            .query(
+                "SELECT id, name, match_kind, pattern, geofence, webhook_url,
+                        notifiers, cooldown_seconds, enabled
+                FROM watchlist_rules
+                ORDER BY id",
+                &[],
+            )
+            .await?;
+
+        Ok(rows.iter().filter_map(watch_rule_from_row).collect())
+    }
+
+    /// Create a watchlist rule
+    async fn insert_watchlist(&self, rule: &NewWatchRule) -> Result<WatchRule> {
+        let pool = self
+            .pool
+            .as_ref()
+            .ok_or_else(|| anyhow!("Database not available"))?;
+
+        let client = pool.get().await?;
+
+        let geofence = rule
+            .geofence
+            .as_ref()
+            .map(serde_json::to_value)
+            .transpose()?;
+        let row = client
+            .query_one(
+                "INSERT INTO watchlist_rules (
+                    name, match_kind, pattern, geofence, webhook_url, notifiers, cooldown_seconds
+                ) VALUES ($1, $2, $3, $4, $5, $6, $7)
+                RETURNING id, name, match_kind, pattern, geofence, webhook_url,
+                          notifiers, cooldown_seconds, enabled",
+                &[
+                    &rule.name,
+                    &rule.kind.as_str(),
+                    &rule.pattern,
+                    &geofence,
+                    &rule.webhook_url,
+                    &serde_json::to_value(&rule.notifiers)?,
+                    &rule.cooldown_seconds.unwrap_or(DEFAULT_COOLDOWN_SECONDS),
+                ],
+            )
+            .await?;
+
+        watch_rule_from_row(&row).ok_or_else(|| anyhow!("Invalid watchlist rule stored"))
+    }
+
+    /// Delete a watchlist rule
+    async fn delete_watchlist(&self, id: i64) -> Result<bool> {
+        let pool = self
+            .pool
+            .as_ref()
+            .ok_or_else(|| anyhow!("Database not available"))?;
+
+        let client = pool.get().await?;
+        let deleted = client
+            .execute("DELETE FROM watchlist_rules WHERE id = $1", &[&id])
+            .await?;
+        Ok(deleted > 0)
+    }
+
+    /// List geofences
+    async fn get_geofences(&self) -> Result<Vec<GeofenceDef>> {
+        let pool = match &self.pool {
+            Some(p) => p,
+            None => return Ok(vec![]),
+        };
+
+        let client = pool.get().await?;
+
+        let rows = client
+            .query(
+                "SELECT id, name, shape, min_altitude_ft, max_altitude_ft, log_events
+                FROM geofences
+                ORDER BY id",
+                &[],
+            )
+            .await?;
+
+        Ok(rows.iter().filter_map(geofence_from_row).collect())
+    }
+
+    /// Create a geofence
+    async fn insert_geofence(&self, fence: &NewGeofence) -> Result<GeofenceDef> {
+        let pool = self
+            .pool
+            .as_ref()
+            .ok_or_else(|| anyhow!("Database not available"))?;
+
+        let client = pool.get().await?;
+
+        let row = client
+            .query_one(
+                "INSERT INTO geofences (name, shape, min_altitude_ft, max_altitude_ft, log_events)
+                VALUES ($1, $2, $3, $4, $5)
+                RETURNING id, name, shape, min_altitude_ft, max_altitude_ft, log_events",
+                &[
+                    &fence.name,
+                    &serde_json::to_value(&fence.shape)?,
+                    &fence.min_altitude_ft,
+                    &fence.max_altitude_ft,
+                    &fence.log_events,
+                ],
+            )
+            .await?;
+
+        geofence_from_row(&row).ok_or_else(|| anyhow!("Invalid geofence stored"))
+    }
+
+    /// Delete a geofence
+    async fn delete_geofence(&self, id: i64) -> Result<bool> {
+        let pool = self
+            .pool
+            .as_ref()
+            .ok_or_else(|| anyhow!("Database not available"))?;
+
+        let client = pool.get().await?;
+        let deleted = client
+            .execute("DELETE FROM geofences WHERE id = $1", &[&id])
+            .await?;
+        Ok(deleted > 0)
+    }
+
+    /// Log a geofence entry/exit event
+    async fn insert_geofence_event(&self, event: &GeofenceEvent) -> Result<()> {
+        let pool = match &self.pool {
+            Some(p) => p,
+            None => return Ok(()),
+        };
+
+        let client = pool.get().await?;
+
+        client
+            .execute(
+                "INSERT INTO geofence_events (
+                    time, geofence_id, icao_address, callsign, event, reason,
+                    latitude, longitude, altitude_ft
+                ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)",
+                &[
+                    &event.time,
+                    &event.geofence_id,
+                    &event.icao,
+                    &event.callsign,
+                    &event.event.as_str(),
+                    &event.reason,
+                    &event.lat,
+                    &event.lon,
+                    &event.altitude_ft,
+                ],
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /// Get logged geofence events
+    async fn get_geofence_events(&self, geofence_id: Option<i64>, limit: i64) -> Result<Vec<JsonValue>> {
+        let pool = match &self.pool {
+            Some(p) => p,
+            None => return Ok(vec![]),
+        };
+
+        let client = pool.get().await?;
+
+        let rows = client
+            .query(
+                "SELECT e.time, e.geofence_id, g.name, e.icao_address, e.callsign, e.event,
+                        e.reason, e.latitude, e.longitude, e.altitude_ft
+                FROM geofence_events e
+                JOIN geofences g ON g.id = e.geofence_id
+                WHERE $1::bigint IS NULL OR e.geofence_id = $1
+                ORDER BY e.time DESC
+                LIMIT $2",
+                &[&geofence_id, &limit],
+            )
+            .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| {
+                serde_json::json!({
+                    "time": row.get::<_, chrono::DateTime<chrono::Utc>>("time").to_rfc3339(),
+                    "geofence_id": row.get::<_, i64>("geofence_id"),
+                    "geofence_name": row.get::<_, String>("name"),
+                    "icao": row.get::<_, String>("icao_address"),
+                    "callsign": row.get::<_, Option<String>>("callsign"),
+                    "event": row.get::<_, String>("event"),
+                    "reason": row.get::<_, Option<String>>("reason"),
+                    "lat": row.get::<_, Option<f64>>("latitude"),
+                    "lon": row.get::<_, Option<f64>>("longitude"),
+                    "altitude": row.get::<_, Option<i32>>("altitude_ft"),
+                })
+            })
+            .collect())
+    }
+
+    async fn get_squawk_changes(
+        &self,
+        icao: Option<&str>,
+        devices: Option<&[String]>,
+        limit: i64,
+    ) -> Result<Vec<JsonValue>> {
+        let pool = match &self.pool {
+            Some(p) => p,
+            None => return Ok(vec![]),
+        };
+
+        let client = pool.get().await?;
+
+        let rows = client
+            .query(
+                "SELECT time, icao_address, previous_squawk, squawk, device_id
+                FROM squawk_changes
+                WHERE ($1::text IS NULL OR icao_address = $1)
+                  AND ($3::text[] IS NULL OR device_id = ANY($3))
+                ORDER BY time DESC
+                LIMIT $2",
+                &[&icao, &limit, &devices],
+            )
+            .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| {
+                serde_json::json!({
+                    "icao": row.get::<_, String>("icao_address"),
+                    "time": row.get::<_, chrono::DateTime<chrono::Utc>>("time").to_rfc3339(),
+                    "previous_squawk": row.get::<_, Option<String>>("previous_squawk"),
+                    "squawk": row.get::<_, String>("squawk"),
+                    "device_id": row.get::<_, Option<String>>("device_id"),
+                })
+            })
+            .collect())
+    }
+
+    async fn db_health(&self) -> Result<DbHealth> {
+        let pool = self.pool.as_ref().ok_or_else(|| anyhow!("no database connected"))?;
+        let status = pool.status();
+        let client = pool.get().await?;
+        let mut tables = Vec::new();
+        for &(name, time_column) in HEALTH_TABLES {
+            match table_health(&client, name, time_column).await {
+                Ok(table) => tables.push(table),
+                // Tables from migrations not applied, or no TimescaleDB
+                Err(e) => debug!("No health for table {}: {}", name, e),
+            }
+        }
+        let pool = PoolStats {
+            max_size: status.max_size,
+            size: status.size,
+            available: status.available,
+            waiting: status.waiting,
+        };
+        Ok(DbHealth::new(self.backend_name(), Some(pool), tables, Utc::now()))
+    }
+}
+
+/// Size, chunks, retention policy and oldest row of one table
+async fn table_health(client: &tokio_postgres::Client, name: &str, time_column: Option<&str>) -> Result<TableHealth> {
+    let row = client
+        .query_one(
+            "SELECT
+                h.hypertable_name IS NOT NULL AS hypertable,
+                CASE WHEN h.hypertable_name IS NOT NULL THEN hypertable_size(t.rel)
+                     ELSE pg_total_relation_size(t.rel) END AS bytes,
+                approximate_row_count(t.rel) AS rows,
+                (SELECT count(*) FROM timescaledb_information.chunks c
+                 WHERE c.hypertable_name = $1) AS chunks,
+                (SELECT EXTRACT(EPOCH FROM (j.config->>'drop_after')::interval)::float8
+                 FROM timescaledb_information.jobs j
+                 WHERE j.proc_name = 'policy_retention' AND j.hypertable_name = $1
+                 LIMIT 1) AS retention_secs
+            FROM (SELECT $1::text::regclass AS rel) t
+            LEFT JOIN timescaledb_information.hypertables h ON h.hypertable_name = $1",
+            &[&name],
+        )
+        .await?;
+    let hypertable: bool = row.get("hypertable");
+    let oldest = match time_column {
+        // Names are the constants above, never input
+        Some(column) => client
+            .query_one(&format!("SELECT min({}) AS oldest FROM {}", column, name), &[])
+            .await?
+            .get("oldest"),
+        None => None,
+    };
+    Ok(TableHealth {
+        name: name.to_string(),
+        rows: row.get("rows"),
+        bytes: row.get("bytes"),
+        oldest,
+        chunks: hypertable.then(|| row.get("chunks")),
+        retention_days: row.get::<_, Option<f64>>("retention_secs").map(|secs| secs / 86_400.0),
+    })
+}
+
+
+/// Build a watchlist rule from a `watchlist_rules` row
+fn watch_rule_from_row(row: &tokio_postgres::Row) -> Option<WatchRule> {
+    let kind: String = row.get("match_kind");
+    let kind = match MatchKind::parse(&kind) {
+        Some(kind) => kind,
+        None => {
+            warn!("Skipping watchlist rule with unknown kind '{}'", kind);
+            return None;
+        }
+    };
+    Some(WatchRule {
+        id: row.get("id"),
+        name: row.get("name"),
+        kind,
+        pattern: row.get("pattern"),
+        geofence: row
+            .get::<_, Option<JsonValue>>("geofence")
+            .and_then(|v| serde_json::from_value(v).ok()),
+        webhook_url: row.get("webhook_url"),
+        notifiers: row
+            .get::<_, Option<JsonValue>>("notifiers")
+            .and_then(|v| serde_json::from_value(v).ok())
+            .unwrap_or_default(),
+        cooldown_seconds: row.get("cooldown_seconds"),
+        enabled: row.get("enabled"),
+    })
+}
+
+/// Build a geofence from a `geofences` row
+fn geofence_from_row(row: &tokio_postgres::Row) -> Option<GeofenceDef> {
+    let shape = match serde_json::from_value(row.get::<_, JsonValue>("shape")) {
+        Ok(shape) => shape,
+        Err(e) => {
+            warn!("Skipping geofence with invalid shape: {}", e);
+            return None;
+        }
+    };
+    Some(GeofenceDef {
+        id: row.get("id"),
+        name: row.get("name"),
+        shape,
+        min_altitude_ft: row.get("min_altitude_ft"),
+        max_altitude_ft: row.get("max_altitude_ft"),
+        log_events: row.get("log_events"),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Needs a migrated TimescaleDB: `TEST_DB_URL=... cargo test -- --ignored`
+    #[tokio::test]
+    #[ignore = "needs TEST_DB_URL"]
+    async fn test_position_keeps_device_id() {
+        let db_url = std::env::var("TEST_DB_URL").expect("TEST_DB_URL");
+        let writer = DbWriter::new(&db_url).await.unwrap();
+        let event = AircraftEvent {
+            icao: "FFFFF0".to_string(),
+            latitude: Some(37.5),
+            longitude: Some(126.9),
+            device_id: "test-rx".to_string(),
+            ..Default::default()
+        };
+        writer.insert_position(&event, Utc::now(), 1).await.unwrap();
+
+        let client = writer.pool.as_ref().unwrap().get().await.unwrap();
+        let row = client
+            .query_one(
+                "SELECT device_id FROM aircraft_positions
+                 WHERE icao_address = $1 ORDER BY time DESC LIMIT 1",
+                &[&event.icao],
+            )
+            .await
+            .unwrap();
+        let device_id: Option<String> = row.get(0);
+        for table in ["aircraft_positions", "squawk_changes", "aircraft_info"] {
+            client
+                .execute(
+                    &format!("DELETE FROM {} WHERE icao_address = $1", table),
+                    &[&event.icao],
+                )
+                .await
+                .unwrap();
         }
+        assert_eq!(device_id.as_deref(), Some("test-rx"));
     }
 }
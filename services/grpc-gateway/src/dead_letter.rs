@@ -0,0 +1,288 @@
+//! Failed position writes
+//!
+//! A position write the database refuses is retried in the background after
+//! 1, 5 and 30 seconds; each write is atomic, so a retry never stores half an
+//! event twice. Errors a retry can't fix (Postgres data and constraint
+//! errors, SQLSTATE classes 22 and 23, and SQLite constraint and type
+//! errors) skip the retries. Events that still fail, or arrive while the
+//! retry queue is full, are dead letters: counted in
+//! `adsb_dead_letters_total` and, with `DEAD_LETTER_FILE` set, appended to
+//! that file as JSON lines holding the error, the write's time and message
+//! count, and the event as hex-encoded protobuf, so constraint or encoding
+//! bugs can be reproduced and the data replayed. The file stops growing at
+//! `DEAD_LETTER_MAX_MB` (default 100); events that don't fit are counted in
+//! `adsb_dead_letters_dropped_total`.
+
+use crate::adsb::AircraftEvent;
+use crate::storage::Storage;
+use chrono::{DateTime, Utc};
+use prost::Message;
+use std::collections::VecDeque;
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+use tracing::{info, warn};
+
+/// Wait before each retry
+const RETRY_DELAYS: [Duration; 3] = [Duration::from_secs(1), Duration::from_secs(5), Duration::from_secs(30)];
+
+/// Writes waiting for a retry at most; beyond it failures are dead letters at once
+const MAX_WAITING: usize = 10_000;
+
+/// A position write that failed
+struct FailedWrite {
+    event: AircraftEvent,
+    time: DateTime<Utc>,
+    messages: u32,
+    error: String,
+    due: Instant,
+}
+
+/// Hands failed writes to the retry task
+pub struct DeadLetters {
+    tx: mpsc::Sender<FailedWrite>,
+    file: Option<Arc<Mutex<DeadLetterFile>>>,
+    /// Writes retried
+    pub retries: AtomicU64,
+    /// Writes given up on
+    pub dead: AtomicU64,
+    /// Dead letters not written to the file
+    pub dropped: AtomicU64,
+}
+
+impl DeadLetters {
+    /// Start the retry task
+    pub fn start(db: Arc<dyn Storage>, path: Option<PathBuf>, max_bytes: u64) -> Arc<Self> {
+        let (tx, rx) = mpsc::channel(1000);
+        let file = path.map(|path| {
+            info!("Dead letters go to {}", path.display());
+            Arc::new(Mutex::new(DeadLetterFile::new(path, max_bytes)))
+        });
+        let dead_letters = Arc::new(Self {
+            tx,
+            file,
+            retries: AtomicU64::new(0),
+            dead: AtomicU64::new(0),
+            dropped: AtomicU64::new(0),
+        });
+        tokio::spawn(run(rx, db, dead_letters.clone()));
+        dead_letters
+    }
+
+    /// Retry a position write that failed with `error`, or give up on it
+    /// when retrying can't help or the retry queue is full
+    pub fn submit(self: &Arc<Self>, event: AircraftEvent, time: DateTime<Utc>, messages: u32, error: &anyhow::Error) {
+        let failed = FailedWrite {
+            event,
+            time,
+            messages,
+            error: error.to_string(),
+            due: Instant::now() + RETRY_DELAYS[0],
+        };
+        if is_permanent(error) {
+            self.give_up(failed);
+            return;
+        }
+        if let Err(e) = self.tx.try_send(failed) {
+            self.give_up(e.into_inner());
+        }
+    }
+
+    /// Count a dead letter and append it to the file off the async runtime
+    fn give_up(self: &Arc<Self>, failed: FailedWrite) {
+        self.dead.fetch_add(1, Ordering::Relaxed);
+        warn!("Giving up on position write for {}: {}", failed.event.icao, failed.error);
+        let Some(file) = self.file.clone() else {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+            return;
+        };
+        let line = record(&failed);
+        let dead_letters = self.clone();
+        tokio::task::spawn_blocking(move || {
+            let written = file.lock().map(|mut file| file.write(&line)).unwrap_or(false);
+            if !written {
+                dead_letters.dropped.fetch_add(1, Ordering::Relaxed);
+            }
+        });
+    }
+}
+
+/// Whether a write error would come back on retry: Postgres data and
+/// constraint errors (SQLSTATE classes 22 and 23) and SQLite constraint and
+/// type errors
+fn is_permanent(error: &anyhow::Error) -> bool {
+    error.chain().any(|cause| {
+        if let Some(e) = cause.downcast_ref::<tokio_postgres::Error>() {
+            return e
+                .code()
+                .is_some_and(|state| state.code().starts_with("22") || state.code().starts_with("23"));
+        }
+        if let Some(e) = cause.downcast_ref::<rusqlite::Error>() {
+            return matches!(
+                e.sqlite_error_code(),
+                Some(rusqlite::ErrorCode::ConstraintViolation | rusqlite::ErrorCode::TypeMismatch)
+            );
+        }
+        false
+    })
+}
+
+/// Retry failed writes, queued by attempt so each queue is in due order
+async fn run(mut rx: mpsc::Receiver<FailedWrite>, db: Arc<dyn Storage>, dead_letters: Arc<DeadLetters>) {
+    let mut waiting: Vec<VecDeque<FailedWrite>> = RETRY_DELAYS.iter().map(|_| VecDeque::new()).collect();
+    loop {
+        let next_due = waiting.iter().filter_map(|q| q.front().map(|f| f.due)).min();
+        tokio::select! {
+            failed = rx.recv() => {
+                let Some(failed) = failed else {
+                    break;
+                };
+                if waiting.iter().map(VecDeque::len).sum::<usize>() >= MAX_WAITING {
+                    dead_letters.give_up(failed);
+                } else {
+                    waiting[0].push_back(failed);
+                }
+            }
+            _ = tokio::time::sleep_until(next_due.unwrap_or_else(Instant::now)), if next_due.is_some() => {
+                let now = Instant::now();
+                for attempt in 0..waiting.len() {
+                    while waiting[attempt].front().is_some_and(|f| f.due <= now) {
+                        let Some(mut failed) = waiting[attempt].pop_front() else {
+                            break;
+                        };
+                        dead_letters.retries.fetch_add(1, Ordering::Relaxed);
+                        let Err(e) = db.insert_position(&failed.event, failed.time, failed.messages).await else {
+                            continue;
+                        };
+                        failed.error = e.to_string();
+                        match RETRY_DELAYS.get(attempt + 1).filter(|_| !is_permanent(&e)) {
+                            Some(delay) => {
+                                failed.due = Instant::now() + *delay;
+                                waiting[attempt + 1].push_back(failed);
+                            }
+                            None => dead_letters.give_up(failed),
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The dead letter line for a write
+fn record(failed: &FailedWrite) -> serde_json::Value {
+    serde_json::json!({
+        "failed_at": Utc::now().to_rfc3339(),
+        "kind": "position",
+        "error": failed.error,
+        "icao": failed.event.icao,
+        "device_id": failed.event.device_id,
+        "time": failed.time.to_rfc3339(),
+        "messages": failed.messages,
+        "event": failed.event.encode_to_vec().iter().map(|b| format!("{:02X}", b)).collect::<String>(),
+    })
+}
+
+/// Append-only JSON lines file, capped in size
+struct DeadLetterFile {
+    path: PathBuf,
+    max_bytes: u64,
+    writer: Option<BufWriter<File>>,
+    full: bool,
+}
+
+impl DeadLetterFile {
+    fn new(path: PathBuf, max_bytes: u64) -> Self {
+        Self {
+            path,
+            max_bytes,
+            writer: None,
+            full: false,
+        }
+    }
+
+    /// Append a line; false when it couldn't be written
+    fn write(&mut self, record: &serde_json::Value) -> bool {
+        let size = std::fs::metadata(&self.path).map(|m| m.len()).unwrap_or(0);
+        if size >= self.max_bytes {
+            if !self.full {
+                warn!("Dead letter file {} is full; dropping dead letters", self.path.display());
+            }
+            self.full = true;
+            return false;
+        }
+        self.full = false;
+        if self.writer.is_none() {
+            match OpenOptions::new().create(true).append(true).open(&self.path) {
+                Ok(f) => self.writer = Some(BufWriter::new(f)),
+                Err(e) => {
+                    warn!("Failed to open dead letter file {}: {}", self.path.display(), e);
+                    return false;
+                }
+            }
+        }
+        let Some(writer) = self.writer.as_mut() else {
+            return false;
+        };
+        // Flushed per line so the size check sees it
+        let result = writeln!(writer, "{}", record).and_then(|_| writer.flush());
+        if let Err(e) = result {
+            warn!("Failed to write dead letter file {}: {}", self.path.display(), e);
+            self.writer = None;
+            return false;
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dead_letter_file() {
+        let path = std::env::temp_dir().join(format!("dead-letters-{}.ndjson", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let failed = FailedWrite {
+            event: AircraftEvent {
+                icao: "71BE11".into(),
+                device_id: "rooftop".into(),
+                ..Default::default()
+            },
+            time: Utc::now(),
+            messages: 2,
+            error: "value too long for type character varying(4)".into(),
+            due: Instant::now(),
+        };
+
+        let mut file = DeadLetterFile::new(path.clone(), 1);
+        assert!(file.write(&record(&failed)));
+        // Over the cap now
+        assert!(!file.write(&record(&failed)));
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        let line: serde_json::Value = serde_json::from_str(contents.lines().next().unwrap()).unwrap();
+        assert_eq!(line["error"], "value too long for type character varying(4)");
+        let bytes: Vec<u8> = (0..line["event"].as_str().unwrap().len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&line["event"].as_str().unwrap()[i..i + 2], 16).unwrap())
+            .collect();
+        assert_eq!(AircraftEvent::decode(bytes.as_slice()).unwrap().icao, "71BE11");
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_permanent_errors() {
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        conn.execute_batch("CREATE TABLE t (icao TEXT PRIMARY KEY); INSERT INTO t VALUES ('71BE11');")
+            .unwrap();
+        let duplicate = conn.execute("INSERT INTO t VALUES ('71BE11')", []).unwrap_err();
+        assert!(is_permanent(&anyhow::Error::from(duplicate).context("insert position")));
+        assert!(!is_permanent(&anyhow::anyhow!("connection refused")));
+    }
+}
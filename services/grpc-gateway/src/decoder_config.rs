@@ -0,0 +1,90 @@
+//! Receiver decoder configuration
+//!
+//! Capture hosts send their software version, uptime and decoder setup with
+//! every status heartbeat. The latest is kept with the device's status and
+//! each heartbeat's with its history, so `/api/sdr/status` shows exactly how
+//! every receiver is configured, and the history shows when that changed,
+//! e.g. error correction being stepped down. Hosts older than this report no
+//! version or decoder setup, and the fields are left out.
+
+use crate::adsb::{DecoderConfig, DeviceStatus};
+use async_graphql::SimpleObject;
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use utoipa::ToSchema;
+
+/// How a device's decoder was set up at a heartbeat
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema, SimpleObject)]
+pub struct DecoderSettings {
+    /// Most bits error correction flips; lower than configured once
+    /// `max_false_positive_rate` has stepped it down
+    pub max_corrected_bits: u32,
+    /// Share of corrected frames looking like noise above which error
+    /// correction is stepped down (absent = never)
+    pub max_false_positive_rate: Option<f32>,
+    /// Pulses this many times the noise floor are blanked (absent = blanker off)
+    pub noise_blanker_factor: Option<u32>,
+    /// Positions below this integrity category are dropped (0 = no limit)
+    pub min_position_nic: u32,
+    /// Positions below this accuracy category are dropped (0 = no limit)
+    pub min_position_nac_p: u32,
+    pub track_smoothing: bool,
+    pub ppm_error: i32,
+}
+
+impl From<&DecoderConfig> for DecoderSettings {
+    fn from(decoder: &DecoderConfig) -> Self {
+        Self {
+            max_corrected_bits: decoder.max_corrected_bits,
+            max_false_positive_rate: Some(decoder.max_false_positive_rate)
+                .filter(|&rate| rate > 0.0),
+            noise_blanker_factor: decoder.noise_blanker_factor,
+            min_position_nic: decoder.min_position_nic,
+            min_position_nac_p: decoder.min_position_nac_p,
+            track_smoothing: decoder.track_smoothing,
+            ppm_error: decoder.ppm_error,
+        }
+    }
+}
+
+/// A status update's decoder settings as stored, if the host sent them
+pub fn settings_json(status: &DeviceStatus) -> Option<JsonValue> {
+    let settings = DecoderSettings::from(status.decoder.as_ref()?);
+    serde_json::to_value(settings).ok()
+}
+
+/// A status update's software version, if the host sent one
+pub fn software_version(status: &DeviceStatus) -> Option<&str> {
+    Some(status.software_version.as_str()).filter(|v| !v.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_settings_json() {
+        let mut status = DeviceStatus {
+            device_id: "rtlsdr-0".into(),
+            ..Default::default()
+        };
+        assert_eq!(settings_json(&status), None);
+        assert_eq!(software_version(&status), None);
+
+        status.software_version = "0.1.0".into();
+        status.decoder = Some(DecoderConfig {
+            max_corrected_bits: 1,
+            noise_blanker_factor: Some(8),
+            track_smoothing: true,
+            ..Default::default()
+        });
+        let json = settings_json(&status).unwrap();
+        assert_eq!(json["max_corrected_bits"], 1);
+        assert_eq!(json["max_false_positive_rate"], JsonValue::Null);
+        assert_eq!(json["noise_blanker_factor"], 8);
+        assert_eq!(software_version(&status), Some("0.1.0"));
+
+        let settings: DecoderSettings = serde_json::from_value(json).unwrap();
+        assert_eq!(settings, DecoderSettings::from(status.decoder.as_ref().unwrap()));
+    }
+}
@@ -0,0 +1,187 @@
+//! Delta-compressed position updates
+//!
+//! A `position_update` repeats every field of the aircraft even though
+//! usually only the position, altitude and time have changed. WebSocket
+//! clients connecting with `?delta=true` get a [`DeltaEncoder`] that remembers
+//! what was last sent for each aircraft and replaces later updates with a
+//! `position_delta` carrying only the changed fields (`null` when a field
+//! went away). Each aircraft still gets a full `position_update` keyframe
+//! every [`KEYFRAME_INTERVAL`] so a client that missed something recovers.
+
+use serde_json::{Map, Value as JsonValue};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// How often each aircraft is sent in full
+pub const KEYFRAME_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Aircraft not updated for this long are forgotten
+const STALE_AFTER: Duration = Duration::from_secs(300);
+
+struct Sent {
+    fields: Map<String, JsonValue>,
+    keyframe_at: Instant,
+    updated_at: Instant,
+}
+
+/// Per-client record of the last state sent for each aircraft
+pub struct DeltaEncoder {
+    keyframe_interval: Duration,
+    sent: HashMap<String, Sent>,
+    last_prune: Instant,
+}
+
+impl DeltaEncoder {
+    pub fn new(keyframe_interval: Duration) -> Self {
+        Self {
+            keyframe_interval,
+            sent: HashMap::new(),
+            last_prune: Instant::now(),
+        }
+    }
+
+    /// The message to send in place of `msg`
+    pub fn encode<'a>(&mut self, msg: &'a str, now: Instant) -> Cow<'a, str> {
+        // Cheap check before parsing; most traffic is position updates
+        // but signal and status messages pass through untouched
+        if !msg.contains("\"position_update\"")
+            && !msg.contains("\"initial\"")
+            && !msg.contains("removed\"")
+        {
+            return Cow::Borrowed(msg);
+        }
+        let Ok(JsonValue::Object(fields)) = serde_json::from_str::<JsonValue>(msg) else {
+            return Cow::Borrowed(msg);
+        };
+        let icao = fields.get("icao").and_then(JsonValue::as_str).map(str::to_string);
+        match (fields.get("type").and_then(JsonValue::as_str), icao) {
+            (Some("position_update"), Some(icao)) => self.position_update(msg, icao, fields, now),
+            (Some("initial"), _) => {
+                // A new snapshot; the next update for every aircraft is a keyframe
+                self.sent.clear();
+                Cow::Borrowed(msg)
+            }
+            (Some("aircraft_removed" | "remove"), Some(icao)) => {
+                self.forget(&icao);
+                Cow::Borrowed(msg)
+            }
+            _ => Cow::Borrowed(msg),
+        }
+    }
+
+    /// Drop the state for an aircraft so its next update is a keyframe
+    pub fn forget(&mut self, icao: &str) {
+        self.sent.remove(icao);
+    }
+
+    fn position_update<'a>(
+        &mut self,
+        msg: &'a str,
+        icao: String,
+        fields: Map<String, JsonValue>,
+        now: Instant,
+    ) -> Cow<'a, str> {
+        if now.duration_since(self.last_prune) >= STALE_AFTER {
+            self.sent.retain(|_, sent| now.duration_since(sent.updated_at) < STALE_AFTER);
+            self.last_prune = now;
+        }
+
+        let keyframe_interval = self.keyframe_interval;
+        match self.sent.get_mut(&icao) {
+            Some(sent) if now.duration_since(sent.keyframe_at) < keyframe_interval => {
+                let mut delta = Map::new();
+                delta.insert("type".into(), "position_delta".into());
+                delta.insert("icao".into(), icao.into());
+                for (key, value) in &fields {
+                    if sent.fields.get(key) != Some(value) {
+                        delta.insert(key.clone(), value.clone());
+                    }
+                }
+                for key in sent.fields.keys() {
+                    if !fields.contains_key(key) {
+                        delta.insert(key.clone(), JsonValue::Null);
+                    }
+                }
+                sent.fields = fields;
+                sent.updated_at = now;
+                Cow::Owned(JsonValue::Object(delta).to_string())
+            }
+            _ => {
+                self.sent.insert(
+                    icao,
+                    Sent {
+                        fields,
+                        keyframe_at: now,
+                        updated_at: now,
+                    },
+                );
+                Cow::Borrowed(msg)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn update(altitude: i32, squawk: Option<&str>) -> String {
+        let mut msg = serde_json::json!({
+            "type": "position_update",
+            "icao": "71BE11",
+            "lat": 37.46,
+            "lon": 126.44,
+            "altitude": altitude,
+            "speed": 250,
+        });
+        if let Some(squawk) = squawk {
+            msg["squawk"] = squawk.into();
+        }
+        msg.to_string()
+    }
+
+    fn parse(msg: &str) -> JsonValue {
+        serde_json::from_str(msg).unwrap()
+    }
+
+    #[test]
+    fn test_deltas_and_keyframes() {
+        let start = Instant::now();
+        let mut encoder = DeltaEncoder::new(Duration::from_secs(30));
+
+        let first = update(3000, Some("7700"));
+        assert!(matches!(encoder.encode(&first, start), Cow::Borrowed(_)));
+
+        let delta = parse(&encoder.encode(&update(3100, None), start + Duration::from_secs(1)));
+        assert_eq!(
+            delta,
+            serde_json::json!({"type": "position_delta", "icao": "71BE11", "altitude": 3100, "squawk": null})
+        );
+
+        // Nothing changed: just the header
+        let delta = parse(&encoder.encode(&update(3100, None), start + Duration::from_secs(2)));
+        assert_eq!(delta.as_object().unwrap().len(), 2);
+
+        let keyframe = update(3200, None);
+        assert!(matches!(
+            encoder.encode(&keyframe, start + Duration::from_secs(31)),
+            Cow::Borrowed(_)
+        ));
+    }
+
+    #[test]
+    fn test_reset() {
+        let now = Instant::now();
+        let mut encoder = DeltaEncoder::new(KEYFRAME_INTERVAL);
+        encoder.encode(&update(3000, None), now);
+        encoder.encode("{\"type\":\"aircraft_removed\",\"icao\":\"71BE11\"}", now);
+        assert!(matches!(encoder.encode(&update(3000, None), now), Cow::Borrowed(_)));
+
+        encoder.encode("{\"type\":\"initial\",\"aircraft\":[]}", now);
+        assert!(matches!(encoder.encode(&update(3000, None), now), Cow::Borrowed(_)));
+
+        let signal = "{\"type\":\"signal\",\"msg_rate\":2.5}";
+        assert!(matches!(encoder.encode(signal, now), Cow::Borrowed(_)));
+    }
+}
@@ -0,0 +1,356 @@
+//! Emergency squawk tracking
+//!
+//! Aircraft squawking 7500 (hijack), 7600 (radio failure) or 7700 (general
+//! emergency) open an emergency occurrence. Starts and ends are broadcast
+//! as `emergency` WebSocket messages, sent to the default alert notifiers,
+//! and persisted to the `emergencies` table. An occurrence ends when the
+//! aircraft squawks a normal code again or stops reporting.
+
+use crate::adsb::{AircraftEvent, Emergency};
+use crate::aircraft_db::AircraftDb;
+use crate::notifiers::Dispatcher;
+use crate::pubsub::{LiveMessage, PubSub};
+use crate::storage::Storage;
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// Aircraft silent this long are treated as no longer in an emergency
+const SILENCE_TIMEOUT_SECS: i64 = 300;
+
+/// How often open occurrences are written and timed out
+const SWEEP_INTERVAL_SECS: u64 = 30;
+
+/// An emergency occurrence as stored in the `emergencies` table
+#[derive(Debug, Clone)]
+pub struct EmergencyRecord {
+    pub icao: String,
+    pub emergency: Emergency,
+    pub squawk: String,
+    pub callsign: Option<String>,
+    pub started_at: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
+    pub ended_at: Option<DateTime<Utc>>,
+    /// Last reported position, if any was heard during the occurrence
+    pub position: Option<(f64, f64)>,
+    pub altitude_ft: Option<i32>,
+    pub device_id: String,
+}
+
+impl EmergencyRecord {
+    fn new(event: &AircraftEvent, emergency: Emergency, now: DateTime<Utc>) -> Self {
+        let mut record = Self {
+            icao: event.icao.clone(),
+            emergency,
+            squawk: event.squawk.clone().unwrap_or_default(),
+            callsign: None,
+            started_at: now,
+            last_seen: now,
+            ended_at: None,
+            position: None,
+            altitude_ft: None,
+            device_id: String::new(),
+        };
+        record.apply(event, now);
+        record
+    }
+
+    fn apply(&mut self, event: &AircraftEvent, now: DateTime<Utc>) {
+        self.last_seen = now;
+        if let Some(callsign) = event.callsign.as_ref().filter(|c| !c.is_empty()) {
+            self.callsign = Some(callsign.clone());
+        }
+        if let Some(position) = event.position() {
+            self.position = Some(position);
+        }
+        if event.altitude_ft.is_some() {
+            self.altitude_ft = event.altitude_ft;
+        }
+        if !event.device_id.is_empty() {
+            self.device_id = event.device_id.clone();
+        }
+    }
+
+    /// WebSocket/notifier payload; fields never reported are null
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "type": "emergency",
+            "icao": self.icao,
+            "emergency": emergency_name(self.emergency),
+            "description": emergency_description(self.emergency),
+            "squawk": self.squawk,
+            "callsign": self.callsign,
+            "active": self.ended_at.is_none(),
+            "started_at": self.started_at.to_rfc3339(),
+            "ended_at": self.ended_at.map(|t| t.to_rfc3339()),
+            "lat": self.position.map(|(lat, _)| lat),
+            "lon": self.position.map(|(_, lon)| lon),
+            "altitude": self.altitude_ft,
+            "device_id": self.device_id,
+        })
+    }
+}
+
+/// Emergency declared by an event: the capture flag, or the squawk itself
+/// for hosts that predate the flag
+pub fn classify(event: &AircraftEvent) -> Option<Emergency> {
+    match event.emergency() {
+        Emergency::None => match event.squawk.as_deref() {
+            Some("7500") => Some(Emergency::Hijack),
+            Some("7600") => Some(Emergency::RadioFailure),
+            Some("7700") => Some(Emergency::General),
+            _ => None,
+        },
+        emergency => Some(emergency),
+    }
+}
+
+/// Stable name used in JSON and the database
+pub fn emergency_name(emergency: Emergency) -> &'static str {
+    match emergency {
+        Emergency::None => "none",
+        Emergency::Hijack => "hijack",
+        Emergency::RadioFailure => "radio_failure",
+        Emergency::General => "general",
+    }
+}
+
+fn emergency_description(emergency: Emergency) -> &'static str {
+    match emergency {
+        Emergency::None => "no emergency",
+        Emergency::Hijack => "hijack",
+        Emergency::RadioFailure => "radio failure",
+        Emergency::General => "general emergency",
+    }
+}
+
+/// Open occurrence with write state
+struct Open {
+    record: EmergencyRecord,
+    /// Changed since last write
+    dirty: bool,
+}
+
+/// Tracks aircraft currently in an emergency
+pub struct EmergencyMonitor {
+    open: Mutex<HashMap<String, Open>>,
+    pubsub: Arc<dyn PubSub>,
+    dispatcher: Arc<Dispatcher>,
+    aircraft_db: Arc<AircraftDb>,
+}
+
+impl EmergencyMonitor {
+    pub fn new(
+        pubsub: Arc<dyn PubSub>,
+        dispatcher: Arc<Dispatcher>,
+        aircraft_db: Arc<AircraftDb>,
+    ) -> Self {
+        Self {
+            open: Mutex::new(HashMap::new()),
+            pubsub,
+            dispatcher,
+            aircraft_db,
+        }
+    }
+
+    /// Update state for an event and return started/ended occurrences
+    pub fn observe(&self, event: &AircraftEvent, now: DateTime<Utc>) -> Vec<EmergencyRecord> {
+        let current = classify(event);
+        let mut open = match self.open.lock() {
+            Ok(open) => open,
+            Err(_) => return Vec::new(),
+        };
+
+        let mut changes = Vec::new();
+        let previous = open.get(&event.icao).map(|o| o.record.emergency);
+
+        // Only squawk-bearing events can clear an emergency
+        let cleared = current.is_none() && event.squawk.as_deref().is_some_and(|s| !s.is_empty());
+        if previous.is_some() && (cleared || (current.is_some() && current != previous)) {
+            if let Some(mut ended) = open.remove(&event.icao).map(|o| o.record) {
+                ended.ended_at = Some(now);
+                changes.push(ended);
+            }
+        }
+
+        match (current, open.get_mut(&event.icao)) {
+            (_, Some(existing)) => {
+                existing.record.apply(event, now);
+                existing.dirty = true;
+            }
+            (Some(emergency), None) => {
+                let record = EmergencyRecord::new(event, emergency, now);
+                changes.push(record.clone());
+                open.insert(event.icao.clone(), Open { record, dirty: false });
+            }
+            (None, None) => {}
+        }
+
+        changes
+    }
+
+    /// Active emergency for an aircraft, if any
+    pub fn active(&self, icao: &str) -> Option<Emergency> {
+        self.open.lock().ok()?.get(icao).map(|o| o.record.emergency)
+    }
+
+    /// Collect changed open occurrences, ending those that went silent
+    fn take_pending(&self, now: DateTime<Utc>) -> (Vec<EmergencyRecord>, Vec<EmergencyRecord>) {
+        let mut open = match self.open.lock() {
+            Ok(open) => open,
+            Err(_) => return (Vec::new(), Vec::new()),
+        };
+
+        let timeout = chrono::Duration::seconds(SILENCE_TIMEOUT_SECS);
+        let expired: Vec<String> = open
+            .iter()
+            .filter(|(_, o)| now - o.record.last_seen > timeout)
+            .map(|(icao, _)| icao.clone())
+            .collect();
+        let mut ended = Vec::new();
+        for icao in expired {
+            if let Some(Open { mut record, .. }) = open.remove(&icao) {
+                record.ended_at = Some(record.last_seen);
+                ended.push(record);
+            }
+        }
+
+        let updated = open
+            .values_mut()
+            .filter(|o| o.dirty)
+            .map(|o| {
+                o.dirty = false;
+                o.record.clone()
+            })
+            .collect();
+        (ended, updated)
+    }
+
+    /// Broadcast, notify and persist started/ended occurrences
+    pub async fn publish(&self, changes: Vec<EmergencyRecord>, db_writer: &dyn Storage) {
+        for record in changes {
+            let mut msg = record.to_json();
+            self.aircraft_db.enrich(&mut msg);
+
+            if record.ended_at.is_none() {
+                warn!(
+                    "Emergency: {} {} squawking {} ({})",
+                    record.icao,
+                    record.callsign.as_deref().unwrap_or("-"),
+                    record.squawk,
+                    emergency_description(record.emergency)
+                );
+                let mut alert = msg.clone();
+                if let Some(obj) = alert.as_object_mut() {
+                    obj.insert(
+                        "rule_name".into(),
+                        format!("Emergency {}", emergency_description(record.emergency)).into(),
+                    );
+                }
+                self.dispatcher.dispatch(alert, &[]);
+            } else {
+                info!("Emergency ended: {} ({})", record.icao, emergency_name(record.emergency));
+            }
+
+            if self.pubsub.has_subscribers() {
+                self.pubsub.publish(LiveMessage::from_value(&msg));
+            }
+
+            if let Err(e) = db_writer.upsert_emergency(&record).await {
+                warn!("Failed to store emergency for {}: {}", record.icao, e);
+            }
+        }
+    }
+}
+
+/// Periodically persist open occurrences and end silent ones
+pub fn spawn_sweeper(monitor: Arc<EmergencyMonitor>, db_writer: Arc<dyn Storage>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(SWEEP_INTERVAL_SECS));
+        loop {
+            interval.tick().await;
+            let (ended, updated) = monitor.take_pending(Utc::now());
+            if !ended.is_empty() {
+                monitor.publish(ended, db_writer.as_ref()).await;
+            }
+            for record in updated {
+                if let Err(e) = db_writer.upsert_emergency(&record).await {
+                    warn!("Failed to store emergency for {}: {}", record.icao, e);
+                }
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pubsub::LocalPubSub;
+
+    fn monitor() -> EmergencyMonitor {
+        EmergencyMonitor::new(
+            Arc::new(LocalPubSub::new()),
+            Arc::new(Dispatcher::new(Vec::new())),
+            Arc::new(AircraftDb::empty()),
+        )
+    }
+
+    fn event(squawk: &str) -> AircraftEvent {
+        AircraftEvent {
+            icao: "71BE11".into(),
+            squawk: Some(squawk.into()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_classify() {
+        assert_eq!(classify(&event("7700")), Some(Emergency::General));
+        assert_eq!(classify(&event("1200")), None);
+        let flagged = AircraftEvent {
+            emergency: Emergency::RadioFailure as i32,
+            ..Default::default()
+        };
+        assert_eq!(classify(&flagged), Some(Emergency::RadioFailure));
+    }
+
+    #[test]
+    fn test_start_change_and_clear() {
+        let monitor = monitor();
+        let now = Utc::now();
+
+        assert!(monitor.observe(&event("1200"), now).is_empty());
+
+        let changes = monitor.observe(&event("7700"), now);
+        assert_eq!(changes.len(), 1);
+        assert!(changes[0].ended_at.is_none());
+        assert_eq!(monitor.active("71BE11"), Some(Emergency::General));
+
+        // Events without a squawk don't clear it
+        assert!(monitor.observe(&event(""), now).is_empty());
+
+        // Switching code ends one occurrence and starts another
+        let changes = monitor.observe(&event("7600"), now);
+        assert_eq!(changes.len(), 2);
+        assert!(changes[0].ended_at.is_some());
+        assert_eq!(changes[1].emergency, Emergency::RadioFailure);
+
+        let changes = monitor.observe(&event("1200"), now);
+        assert_eq!(changes.len(), 1);
+        assert!(changes[0].ended_at.is_some());
+        assert_eq!(monitor.active("71BE11"), None);
+    }
+
+    #[test]
+    fn test_silence_ends_emergency() {
+        let monitor = monitor();
+        let now = Utc::now();
+        monitor.observe(&event("7500"), now);
+
+        let (ended, _) = monitor.take_pending(now + chrono::Duration::seconds(SILENCE_TIMEOUT_SECS + 1));
+        assert_eq!(ended.len(), 1);
+        assert_eq!(ended[0].ended_at, Some(now));
+    }
+}
@@ -0,0 +1,396 @@
+//! Track and position exports
+//!
+//! Trails and position history can be downloaded as GeoJSON, KML or GPX
+//! instead of the default JSON, for use in GIS tools and Google Earth.
+//! Every format carries per-point timestamps and altitude (converted to
+//! metres, as all three expect).
+//!
+//! `/api/export/positions` streams raw positions as CSV. Rows are read in
+//! keyset-paginated chunks and written out as they arrive, so an export's
+//! memory use is bounded by the chunk size rather than the range.
+
+use crate::history::{Cursor, HistoryParams, HistoryQuery, PositionPoint};
+use crate::storage::Storage;
+use anyhow::Result;
+use chrono::{DateTime, SecondsFormat, Utc};
+use futures_util::Stream;
+use serde::Deserialize;
+use serde_json::Value as JsonValue;
+use std::sync::Arc;
+use utoipa::IntoParams;
+
+/// Metres per foot
+const M_PER_FT: f64 = 0.3048;
+
+/// Output format selected with `format=`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TrackFormat {
+    Json,
+    GeoJson,
+    Kml,
+    Gpx,
+}
+
+impl TrackFormat {
+    pub fn parse(format: Option<&str>) -> Result<Self, String> {
+        match format.map(str::to_ascii_lowercase).as_deref() {
+            None | Some("") | Some("json") => Ok(Self::Json),
+            Some("geojson") => Ok(Self::GeoJson),
+            Some("kml") => Ok(Self::Kml),
+            Some("gpx") => Ok(Self::Gpx),
+            Some(_) => Err("format must be json, geojson, kml or gpx".into()),
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Self::Json => "application/json",
+            Self::GeoJson => "application/geo+json",
+            Self::Kml => "application/vnd.google-earth.kml+xml",
+            Self::Gpx => "application/gpx+xml",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Json => "json",
+            Self::GeoJson => "geojson",
+            Self::Kml => "kml",
+            Self::Gpx => "gpx",
+        }
+    }
+
+    /// Render tracks as a document (JSON is handled by the endpoints themselves)
+    pub fn render(self, tracks: &[Track]) -> String {
+        match self {
+            Self::Json | Self::GeoJson => to_geojson(tracks).to_string(),
+            Self::Kml => to_kml(tracks),
+            Self::Gpx => to_gpx(tracks),
+        }
+    }
+}
+
+/// A timestamped position
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrackPoint {
+    pub time: DateTime<Utc>,
+    pub lat: f64,
+    pub lon: f64,
+    pub altitude_ft: Option<i32>,
+}
+
+impl TrackPoint {
+    /// From a trail row (`time`, `lat`, `lon`, `altitude`)
+    pub fn from_trail(row: &JsonValue) -> Option<Self> {
+        let time = DateTime::parse_from_rfc3339(row.get("time")?.as_str()?).ok()?;
+        Some(Self {
+            time: time.with_timezone(&Utc),
+            lat: row.get("lat")?.as_f64()?,
+            lon: row.get("lon")?.as_f64()?,
+            altitude_ft: row.get("altitude").and_then(JsonValue::as_i64).map(|a| a as i32),
+        })
+    }
+
+    fn altitude_m(&self) -> Option<f64> {
+        self.altitude_ft.map(|ft| (ft as f64 * M_PER_FT * 10.0).round() / 10.0)
+    }
+
+    fn timestamp(&self) -> String {
+        self.time.to_rfc3339_opts(SecondsFormat::Millis, true)
+    }
+}
+
+/// One aircraft's points in time order
+#[derive(Debug, Clone)]
+pub struct Track {
+    pub icao: String,
+    pub points: Vec<TrackPoint>,
+}
+
+/// Group time-ordered history rows into one track per aircraft
+pub fn tracks_from_history(points: &[PositionPoint]) -> Vec<Track> {
+    let mut tracks: Vec<Track> = Vec::new();
+    for p in points {
+        let point = TrackPoint {
+            time: p.time,
+            lat: p.lat,
+            lon: p.lon,
+            altitude_ft: p.altitude,
+        };
+        match tracks.iter_mut().find(|t| t.icao == p.icao) {
+            Some(track) => track.points.push(point),
+            None => tracks.push(Track {
+                icao: p.icao.clone(),
+                points: vec![point],
+            }),
+        }
+    }
+    tracks
+}
+
+/// RFC 7946 FeatureCollection with one LineString (or Point) per aircraft.
+/// Timestamps go in the `coordTimes` property, parallel to the coordinates.
+fn to_geojson(tracks: &[Track]) -> JsonValue {
+    let features: Vec<JsonValue> = tracks
+        .iter()
+        .filter(|t| !t.points.is_empty())
+        .map(|track| {
+            let coordinates: Vec<JsonValue> = track
+                .points
+                .iter()
+                .map(|p| match p.altitude_m() {
+                    Some(alt) => serde_json::json!([p.lon, p.lat, alt]),
+                    None => serde_json::json!([p.lon, p.lat]),
+                })
+                .collect();
+            let geometry = if coordinates.len() == 1 {
+                serde_json::json!({"type": "Point", "coordinates": coordinates[0]})
+            } else {
+                serde_json::json!({"type": "LineString", "coordinates": coordinates})
+            };
+            let times: Vec<String> = track.points.iter().map(TrackPoint::timestamp).collect();
+            serde_json::json!({
+                "type": "Feature",
+                "geometry": geometry,
+                "properties": {
+                    "icao": track.icao,
+                    "coordTimes": times,
+                    "altitudes_ft": track.points.iter().map(|p| p.altitude_ft).collect::<Vec<_>>(),
+                },
+            })
+        })
+        .collect();
+
+    serde_json::json!({"type": "FeatureCollection", "features": features})
+}
+
+/// KML 2.2 document with a `gx:Track` per aircraft
+fn to_kml(tracks: &[Track]) -> String {
+    let mut out = String::from(concat!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+        "<kml xmlns=\"http://www.opengis.net/kml/2.2\" xmlns:gx=\"http://www.google.com/kml/ext/2.2\">\n",
+        "<Document>\n",
+    ));
+    for track in tracks.iter().filter(|t| !t.points.is_empty()) {
+        out.push_str(&format!("<Placemark>\n<name>{}</name>\n", xml_escape(&track.icao)));
+        out.push_str("<gx:Track>\n<altitudeMode>absolute</altitudeMode>\n");
+        for p in &track.points {
+            out.push_str(&format!("<when>{}</when>\n", p.timestamp()));
+        }
+        for p in &track.points {
+            out.push_str(&format!(
+                "<gx:coord>{} {} {}</gx:coord>\n",
+                p.lon,
+                p.lat,
+                p.altitude_m().unwrap_or(0.0)
+            ));
+        }
+        out.push_str("</gx:Track>\n</Placemark>\n");
+    }
+    out.push_str("</Document>\n</kml>\n");
+    out
+}
+
+/// GPX 1.1 document with a `trk` per aircraft
+fn to_gpx(tracks: &[Track]) -> String {
+    let mut out = String::from(concat!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+        "<gpx version=\"1.1\" creator=\"adsb-grpc-gateway\" xmlns=\"http://www.topografix.com/GPX/1/1\">\n",
+    ));
+    for track in tracks.iter().filter(|t| !t.points.is_empty()) {
+        out.push_str(&format!("<trk>\n<name>{}</name>\n<trkseg>\n", xml_escape(&track.icao)));
+        for p in &track.points {
+            out.push_str(&format!("<trkpt lat=\"{}\" lon=\"{}\">", p.lat, p.lon));
+            if let Some(alt) = p.altitude_m() {
+                out.push_str(&format!("<ele>{}</ele>", alt));
+            }
+            out.push_str(&format!("<time>{}</time></trkpt>\n", p.timestamp()));
+        }
+        out.push_str("</trkseg>\n</trk>\n");
+    }
+    out.push_str("</gpx>\n");
+    out
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// Rows fetched per storage round trip while streaming CSV
+pub const CSV_CHUNK_ROWS: i64 = 5000;
+
+const CSV_COLUMNS: [&str; 13] = [
+    "time", "icao", "lat", "lon", "altitude", "speed", "track", "magnetic_heading", "vrate",
+    "baro_rate", "geom_rate", "squawk", "device_id",
+];
+
+/// Query parameters for the CSV export
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ExportParams {
+    pub icao: Option<String>,
+    /// RFC 3339 start (inclusive); defaults to one hour before `to`
+    pub from: Option<String>,
+    /// RFC 3339 end (exclusive); defaults to now
+    pub to: Option<String>,
+}
+
+impl ExportParams {
+    pub fn parse(&self, now: DateTime<Utc>) -> Result<HistoryQuery, String> {
+        let params = HistoryParams {
+            icao: self.icao.clone(),
+            from: self.from.clone(),
+            to: self.to.clone(),
+            ..Default::default()
+        };
+        params.parse(now).map(|(query, _, _)| query)
+    }
+}
+
+/// Stream positions as CSV, a header then one chunk of rows per round trip
+pub fn positions_csv(
+    db_writer: Arc<dyn Storage>,
+    mut query: HistoryQuery,
+    chunk_rows: i64,
+) -> impl Stream<Item = Result<String>> {
+    query.fetch = chunk_rows;
+    let header = Some(Ok(csv_chunk(&[], true)));
+    let rows = futures_util::stream::unfold(Some((db_writer, query)), |state| async move {
+        let (db_writer, mut query) = state?;
+        let rows = match db_writer.get_position_history(&query).await {
+            Ok(rows) => rows,
+            Err(e) => return Some((Err(e), None)),
+        };
+        let more = rows.len() as i64 >= query.fetch;
+        if let Some(last) = rows.last() {
+            query.after = Some(Cursor {
+                time: last.time,
+                icao: last.icao.clone(),
+            });
+        }
+        let next = more.then_some((db_writer, query));
+        Some((Ok(csv_chunk(&rows, false)), next))
+    });
+    futures_util::StreamExt::chain(futures_util::stream::iter(header), rows)
+}
+
+fn csv_chunk(rows: &[PositionPoint], header: bool) -> String {
+    let mut writer = csv::WriterBuilder::new().has_headers(false).from_writer(Vec::new());
+    if header {
+        let _ = writer.write_record(CSV_COLUMNS);
+    }
+    for p in rows {
+        let _ = writer.write_record([
+            p.time.to_rfc3339_opts(SecondsFormat::Millis, true),
+            p.icao.clone(),
+            p.lat.to_string(),
+            p.lon.to_string(),
+            p.altitude.map(|v| v.to_string()).unwrap_or_default(),
+            p.speed.map(|v| v.to_string()).unwrap_or_default(),
+            p.track.map(|v| v.to_string()).unwrap_or_default(),
+            p.magnetic_heading.map(|v| v.to_string()).unwrap_or_default(),
+            p.vrate.map(|v| v.to_string()).unwrap_or_default(),
+            p.baro_rate.map(|v| v.to_string()).unwrap_or_default(),
+            p.geom_rate.map(|v| v.to_string()).unwrap_or_default(),
+            p.squawk.clone().unwrap_or_default(),
+            p.device_id.clone().unwrap_or_default(),
+        ]);
+    }
+    String::from_utf8(writer.into_inner().unwrap_or_default()).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn track() -> Vec<Track> {
+        let point = |secs: i64, lon: f64, altitude_ft: Option<i32>| TrackPoint {
+            time: DateTime::from_timestamp(1_700_000_000 + secs, 0).unwrap(),
+            lat: 37.5,
+            lon,
+            altitude_ft,
+        };
+        vec![Track {
+            icao: "71BE11".into(),
+            points: vec![point(0, 126.4, Some(10000)), point(5, 126.5, None)],
+        }]
+    }
+
+    #[test]
+    fn test_parse_format() {
+        assert_eq!(TrackFormat::parse(None).unwrap(), TrackFormat::Json);
+        assert_eq!(TrackFormat::parse(Some("KML")).unwrap(), TrackFormat::Kml);
+        assert!(TrackFormat::parse(Some("shp")).is_err());
+    }
+
+    #[test]
+    fn test_geojson() {
+        let doc = to_geojson(&track());
+        let feature = &doc["features"][0];
+        assert_eq!(feature["geometry"]["type"], "LineString");
+        assert_eq!(feature["geometry"]["coordinates"][0], serde_json::json!([126.4, 37.5, 3048.0]));
+        assert_eq!(feature["geometry"]["coordinates"][1], serde_json::json!([126.5, 37.5]));
+        assert_eq!(feature["properties"]["coordTimes"][0], "2023-11-14T22:13:20.000Z");
+    }
+
+    #[test]
+    fn test_kml_and_gpx() {
+        let kml = to_kml(&track());
+        assert!(kml.contains("<when>2023-11-14T22:13:20.000Z</when>"));
+        assert!(kml.contains("<gx:coord>126.4 37.5 3048</gx:coord>"));
+
+        let gpx = to_gpx(&track());
+        assert!(gpx.contains("<trkpt lat=\"37.5\" lon=\"126.4\"><ele>3048</ele><time>"));
+        assert!(gpx.contains("<trkpt lat=\"37.5\" lon=\"126.5\"><time>"));
+    }
+
+    #[test]
+    fn test_from_trail() {
+        let row = serde_json::json!({"time": "2024-01-15T10:00:00+00:00", "lat": 37.5, "lon": 126.4, "altitude": null});
+        let point = TrackPoint::from_trail(&row).unwrap();
+        assert_eq!(point.altitude_ft, None);
+        assert!(TrackPoint::from_trail(&serde_json::json!({"lat": 1.0})).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_positions_csv_streams_in_chunks() {
+        use crate::adsb::AircraftEvent;
+        use crate::sqlite_writer::SqliteWriter;
+        use futures_util::StreamExt;
+
+        let path = std::env::temp_dir().join(format!("export-test-{}.db", std::process::id()));
+        let db = SqliteWriter::open(&path).await.unwrap();
+        for icao in ["71BE11", "71BE12", "71BE13"] {
+            let event = AircraftEvent {
+                icao: icao.into(),
+                latitude: Some(37.5),
+                longitude: Some(126.4),
+                altitude_ft: Some(3000),
+                ..Default::default()
+            };
+            db.insert_position(&event, Utc::now(), 1).await.unwrap();
+        }
+
+        let query = ExportParams::default().parse(Utc::now() + chrono::Duration::seconds(1)).unwrap();
+        let chunks: Vec<String> = positions_csv(Arc::new(db), query, 2)
+            .map(|chunk| chunk.unwrap())
+            .collect()
+            .await;
+        let _ = std::fs::remove_file(&path);
+
+        // Header, a full chunk, then the remainder
+        assert_eq!(chunks.len(), 3);
+        let csv = chunks.concat();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(
+            lines[0],
+            "time,icao,lat,lon,altitude,speed,track,magnetic_heading,vrate,baro_rate,geom_rate,squawk,device_id"
+        );
+        assert_eq!(lines.len(), 4);
+        assert!(lines[3].contains(",71BE13,37.5,126.4,3000,"));
+    }
+}
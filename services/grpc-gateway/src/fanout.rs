@@ -0,0 +1,247 @@
+//! Per-client queues for WebSocket delivery
+//!
+//! A single dispatcher reads the live event stream and copies each message
+//! into every WebSocket client's own bounded queue (`WS_QUEUE_SIZE`). When a
+//! client's queue is full the oldest message is dropped, so a slow client
+//! falls behind on its own without affecting anyone else and always sees the
+//! latest state. A client that drops `WS_MAX_DROPPED_MESSAGES` messages
+//! without catching up in between is disconnected. Queue and drop counters
+//! are exported on `/metrics`.
+
+use crate::pubsub::{LiveMessage, PubSub};
+use crate::share::SharePolicies;
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::{broadcast, Notify};
+use tracing::{debug, warn};
+
+struct Queue {
+    messages: VecDeque<Arc<LiveMessage>>,
+    /// Messages dropped since the client last emptied its queue
+    dropped: usize,
+    closed: bool,
+}
+
+struct Shared {
+    queue: Mutex<Queue>,
+    notify: Notify,
+}
+
+/// Delivery counters since startup
+#[derive(Debug, Default)]
+pub struct FanoutMetrics {
+    pub delivered: AtomicU64,
+    pub dropped: AtomicU64,
+    pub disconnected: AtomicU64,
+}
+
+/// Distributes live messages to per-client queues
+pub struct Fanout {
+    capacity: usize,
+    /// 0 means never disconnect
+    max_dropped: usize,
+    next_id: AtomicU64,
+    clients: Mutex<HashMap<u64, Arc<Shared>>>,
+    pub metrics: FanoutMetrics,
+}
+
+impl Fanout {
+    pub fn new(capacity: usize, max_dropped: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            max_dropped,
+            next_id: AtomicU64::new(0),
+            clients: Mutex::new(HashMap::new()),
+            metrics: FanoutMetrics::default(),
+        }
+    }
+
+    /// Register a client; its queue is removed when the handle is dropped
+    pub fn subscribe(self: &Arc<Self>) -> ClientQueue {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let shared = Arc::new(Shared {
+            queue: Mutex::new(Queue {
+                messages: VecDeque::new(),
+                dropped: 0,
+                closed: false,
+            }),
+            notify: Notify::new(),
+        });
+        if let Ok(mut clients) = self.clients.lock() {
+            clients.insert(id, shared.clone());
+        }
+        ClientQueue {
+            fanout: self.clone(),
+            id,
+            shared,
+        }
+    }
+
+    /// Queue a message for every client
+    pub fn publish(&self, msg: Arc<LiveMessage>) {
+        let Ok(clients) = self.clients.lock() else {
+            return;
+        };
+        for shared in clients.values() {
+            let Ok(mut queue) = shared.queue.lock() else {
+                continue;
+            };
+            if queue.closed {
+                continue;
+            }
+            if queue.messages.len() >= self.capacity {
+                queue.messages.pop_front();
+                queue.dropped += 1;
+                self.metrics.dropped.fetch_add(1, Ordering::Relaxed);
+                if self.max_dropped > 0 && queue.dropped >= self.max_dropped {
+                    queue.closed = true;
+                    queue.messages.clear();
+                    self.metrics.disconnected.fetch_add(1, Ordering::Relaxed);
+                    drop(queue);
+                    shared.notify.notify_one();
+                    continue;
+                }
+            }
+            queue.messages.push_back(msg.clone());
+            drop(queue);
+            shared.notify.notify_one();
+        }
+    }
+
+    /// Connected clients
+    pub fn clients(&self) -> usize {
+        self.clients.lock().map(|c| c.len()).unwrap_or(0)
+    }
+
+    /// Messages waiting in the fullest client queue
+    pub fn max_queue_depth(&self) -> usize {
+        let Ok(clients) = self.clients.lock() else {
+            return 0;
+        };
+        clients
+            .values()
+            .filter_map(|shared| shared.queue.lock().ok().map(|q| q.messages.len()))
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+/// One client's queue
+pub struct ClientQueue {
+    fanout: Arc<Fanout>,
+    id: u64,
+    shared: Arc<Shared>,
+}
+
+impl ClientQueue {
+    /// Next message, or `None` once the client has been disconnected for
+    /// falling too far behind
+    pub async fn recv(&mut self) -> Option<Arc<LiveMessage>> {
+        loop {
+            {
+                let mut queue = self.shared.queue.lock().ok()?;
+                if queue.closed {
+                    return None;
+                }
+                if let Some(msg) = queue.messages.pop_front() {
+                    if queue.messages.is_empty() {
+                        queue.dropped = 0;
+                    }
+                    self.fanout
+                        .metrics
+                        .delivered
+                        .fetch_add(1, Ordering::Relaxed);
+                    return Some(msg);
+                }
+            }
+            // A notification sent since the check above is kept for us
+            self.shared.notify.notified().await;
+        }
+    }
+}
+
+impl Drop for ClientQueue {
+    fn drop(&mut self) {
+        if let Ok(mut clients) = self.fanout.clients.lock() {
+            clients.remove(&self.id);
+        }
+    }
+}
+
+/// Copy every live message, less what `SHARE_WS` withholds, into the
+/// client queues
+pub fn spawn_dispatcher(fanout: Arc<Fanout>, pubsub: Arc<dyn PubSub>, share: Arc<SharePolicies>) {
+    let mut rx = pubsub.subscribe();
+    tokio::spawn(async move {
+        loop {
+            match rx.recv().await {
+                Ok(msg) => {
+                    if let Some(msg) = share.ws.apply_message(msg) {
+                        fanout.publish(msg);
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(n)) => {
+                    warn!("WebSocket dispatcher lagged by {} messages", n);
+                    fanout
+                        .metrics
+                        .dropped
+                        .fetch_add(n * fanout.clients() as u64, Ordering::Relaxed);
+                }
+                Err(broadcast::error::RecvError::Closed) => {
+                    debug!("Live event stream closed");
+                    break;
+                }
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(n: usize) -> Arc<LiveMessage> {
+        Arc::new(LiveMessage::from_value(
+            &serde_json::json!({"type": "signal", "n": n}),
+        ))
+    }
+
+    #[tokio::test]
+    async fn test_drop_oldest() {
+        let fanout = Arc::new(Fanout::new(2, 0));
+        let mut slow = fanout.subscribe();
+        let mut fast = fanout.subscribe();
+        assert_eq!(fanout.clients(), 2);
+
+        fanout.publish(message(1));
+        assert!(fast.recv().await.unwrap().json.contains("\"n\":1"));
+        fanout.publish(message(2));
+        fanout.publish(message(3));
+        assert_eq!(fanout.max_queue_depth(), 2);
+
+        // The slow client lost message 1 but still gets the latest two
+        assert!(slow.recv().await.unwrap().json.contains("\"n\":2"));
+        assert!(slow.recv().await.unwrap().json.contains("\"n\":3"));
+        assert_eq!(fanout.metrics.dropped.load(Ordering::Relaxed), 1);
+
+        drop(slow);
+        assert_eq!(fanout.clients(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_disconnect_slow_client() {
+        let fanout = Arc::new(Fanout::new(1, 2));
+        let mut client = fanout.subscribe();
+        fanout.publish(message(1));
+        fanout.publish(message(2));
+        assert!(client.recv().await.is_some());
+
+        // Catching up resets the count
+        fanout.publish(message(3));
+        fanout.publish(message(4));
+        fanout.publish(message(5));
+        assert!(client.recv().await.is_none());
+        assert_eq!(fanout.metrics.disconnected.load(Ordering::Relaxed), 1);
+    }
+}
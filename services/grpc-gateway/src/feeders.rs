@@ -0,0 +1,278 @@
+//! Per-receiver contribution statistics
+//!
+//! For communities feeding one gateway from several sites: what each
+//! receiver (capture device or Beast feed) contributed over the last
+//! `days`, from the daily statistics rollups and receiver sessions.
+//! `/api/receivers/{id}/stats` describes one receiver and
+//! `/api/receivers/leaderboard` ranks them all by a chosen metric.
+//! Distinct aircraft are counted per day, so over several days an aircraft
+//! seen on each of them counts once per day.
+
+use crate::api::ApiError;
+use crate::stats::{Period, StatsRollup};
+use crate::tenants::Scope;
+use crate::uptime::UptimeDay;
+use crate::AppState;
+use axum::{
+    extract::{Path, Query, State},
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use tracing::error;
+use utoipa::{IntoParams, ToSchema};
+
+/// Default and largest number of days covered
+const DEFAULT_DAYS: i64 = 7;
+const MAX_DAYS: i64 = 365;
+
+/// One receiver's contribution over the requested days
+#[derive(Debug, Clone, Default, PartialEq, Serialize, ToSchema)]
+pub struct ReceiverStats {
+    pub device_id: String,
+    pub messages: i64,
+    pub positions: i64,
+    /// Distinct aircraft per day, summed over the days
+    pub unique_aircraft: i64,
+    /// Farthest position from the receiver location; absent when
+    /// `RECEIVER_LAT`/`RECEIVER_LON` aren't set
+    pub max_range_km: Option<f64>,
+    pub up_secs: i64,
+    /// Share of the covered time connected, from the receiver's first day
+    pub uptime_pct: f64,
+    /// Currently connected
+    pub connected: bool,
+}
+
+/// A leaderboard row
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RankedReceiver {
+    /// 1 for the top receiver
+    pub rank: usize,
+    #[serde(flatten)]
+    pub stats: ReceiverStats,
+}
+
+/// What the leaderboard ranks receivers by
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RankBy {
+    #[default]
+    Positions,
+    Messages,
+    UniqueAircraft,
+    Range,
+    Uptime,
+}
+
+impl RankBy {
+    fn key(self, stats: &ReceiverStats) -> f64 {
+        match self {
+            Self::Positions => stats.positions as f64,
+            Self::Messages => stats.messages as f64,
+            Self::UniqueAircraft => stats.unique_aircraft as f64,
+            Self::Range => stats.max_range_km.unwrap_or(0.0),
+            Self::Uptime => stats.uptime_pct,
+        }
+    }
+}
+
+/// Days covered
+#[derive(Debug, Default, Deserialize, IntoParams)]
+pub struct ReceiverStatsParams {
+    /// Days back from today, counting today (default 7, at most 365)
+    pub days: Option<i64>,
+}
+
+impl ReceiverStatsParams {
+    pub fn days(&self) -> i64 {
+        self.days.unwrap_or(DEFAULT_DAYS).clamp(1, MAX_DAYS)
+    }
+}
+
+/// Days covered and the ranking metric
+#[derive(Debug, Default, Deserialize, IntoParams)]
+pub struct LeaderboardParams {
+    /// Days back from today, counting today (default 7, at most 365)
+    pub days: Option<i64>,
+    /// `positions` (default), `messages`, `unique_aircraft`, `range` or `uptime`
+    pub by: Option<RankBy>,
+}
+
+/// Add up daily rollups and uptime per receiver, for days up to `to`
+pub fn collect(daily: &[StatsRollup], uptime: &[UptimeDay], to: DateTime<Utc>) -> BTreeMap<String, ReceiverStats> {
+    let mut receivers: BTreeMap<String, ReceiverStats> = BTreeMap::new();
+    for row in daily.iter().filter(|r| !r.device_id.is_empty() && r.period == Period::Day) {
+        let stats = receivers.entry(row.device_id.clone()).or_insert_with(|| ReceiverStats {
+            device_id: row.device_id.clone(),
+            ..Default::default()
+        });
+        stats.messages += row.messages;
+        stats.positions += row.positions;
+        stats.unique_aircraft += row.unique_aircraft;
+        if row.max_range_km > 0.0 {
+            stats.max_range_km = Some(stats.max_range_km.unwrap_or(0.0).max(row.max_range_km));
+        }
+    }
+
+    let mut covered: BTreeMap<&str, i64> = BTreeMap::new();
+    for day in uptime {
+        let length = ((day.day + chrono::Duration::days(1)).min(to) - day.day).num_seconds().max(1);
+        for (device_id, up) in &day.devices {
+            let stats = receivers.entry(device_id.clone()).or_insert_with(|| ReceiverStats {
+                device_id: device_id.clone(),
+                ..Default::default()
+            });
+            stats.up_secs += up.up_secs;
+            *covered.entry(device_id).or_default() += length;
+        }
+    }
+    for (device_id, length) in covered {
+        if let Some(stats) = receivers.get_mut(device_id) {
+            stats.uptime_pct = (stats.up_secs as f64 * 1000.0 / length as f64).round() / 10.0;
+        }
+    }
+    receivers
+}
+
+/// Receivers ordered by `by`, best first; ties keep device ID order
+pub fn rank(receivers: impl IntoIterator<Item = ReceiverStats>, by: RankBy) -> Vec<RankedReceiver> {
+    let mut receivers: Vec<ReceiverStats> = receivers.into_iter().collect();
+    receivers.sort_by(|a, b| by.key(b).total_cmp(&by.key(a)));
+    receivers
+        .into_iter()
+        .enumerate()
+        .map(|(i, stats)| RankedReceiver { rank: i + 1, stats })
+        .collect()
+}
+
+/// One receiver's contribution
+#[utoipa::path(
+    get,
+    path = "/api/receivers/{device_id}/stats",
+    tag = "receiver",
+    params(("device_id" = String, Path, description = "Capture device ID, or beast-<IP> for a Beast feed"), ReceiverStatsParams),
+    responses(
+        (status = 200, description = "Messages, positions, aircraft, range and uptime over the days", body = ReceiverStats),
+        (status = 404, description = "Receiver not heard in the days covered", body = ApiError),
+    ),
+    security((), ("api_key" = []), ("bearer" = []))
+)]
+pub async fn get_receiver_stats(
+    State(state): State<Arc<AppState>>,
+    scope: Scope,
+    Path(device_id): Path<String>,
+    Query(params): Query<ReceiverStatsParams>,
+) -> impl IntoResponse {
+    if !scope.allows_device(&device_id) {
+        return ApiError::not_found("receiver not found");
+    }
+    match state.receiver_stats(params.days()).await {
+        Ok(mut receivers) => match receivers.remove(&device_id) {
+            Some(stats) => Json(stats).into_response(),
+            None => ApiError::not_found("receiver not found"),
+        },
+        Err(e) => {
+            error!("Failed to get receiver stats: {}", e);
+            ApiError::internal(e)
+        }
+    }
+}
+
+/// Receivers ranked by contribution
+#[utoipa::path(
+    get,
+    path = "/api/receivers/leaderboard",
+    tag = "receiver",
+    params(LeaderboardParams),
+    responses(
+        (status = 200, description = "Every receiver heard in the days covered, best first", body = [RankedReceiver]),
+        (status = 400, description = "Unknown ranking metric"),
+    ),
+    security((), ("api_key" = []), ("bearer" = []))
+)]
+pub async fn get_leaderboard(
+    State(state): State<Arc<AppState>>,
+    scope: Scope,
+    Query(params): Query<LeaderboardParams>,
+) -> impl IntoResponse {
+    let days = ReceiverStatsParams { days: params.days }.days();
+    match state.receiver_stats(days).await {
+        Ok(mut receivers) => {
+            receivers.retain(|device_id, _| scope.allows_device(device_id));
+            Json(rank(receivers.into_values(), params.by.unwrap_or_default())).into_response()
+        }
+        Err(e) => {
+            error!("Failed to get receiver leaderboard: {}", e);
+            ApiError::internal(e)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::uptime::DeviceUptime;
+    use chrono::TimeZone;
+
+    fn rollup(day: DateTime<Utc>, device_id: &str, positions: i64, aircraft: i64, range: f64) -> StatsRollup {
+        StatsRollup {
+            period: Period::Day,
+            bucket: day,
+            device_id: device_id.into(),
+            messages: positions * 3,
+            positions,
+            unique_aircraft: aircraft,
+            max_range_km: range,
+            frames_decoded: 0,
+            crc_errors: 0,
+            downlink_formats: BTreeMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_receiver_stats() {
+        let day1 = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let day2 = day1 + chrono::Duration::days(1);
+        let now = day2 + chrono::Duration::hours(12);
+        let daily = vec![
+            rollup(day1, "", 300, 40, 250.0),
+            rollup(day1, "north", 100, 20, 180.0),
+            rollup(day2, "north", 50, 15, 210.0),
+            rollup(day2, "south", 200, 30, 0.0),
+        ];
+        let up = |up_secs| DeviceUptime {
+            uptime_pct: 0.0,
+            up_secs,
+            sessions: 1,
+        };
+        let uptime = vec![
+            UptimeDay {
+                day: day1,
+                devices: BTreeMap::from([("north".to_string(), up(86_400))]),
+            },
+            UptimeDay {
+                day: day2,
+                devices: BTreeMap::from([("north".to_string(), up(0)), ("south".to_string(), up(21_600))]),
+            },
+        ];
+
+        let receivers = collect(&daily, &uptime, now);
+        assert_eq!(receivers.len(), 2);
+        let north = &receivers["north"];
+        assert_eq!((north.messages, north.positions, north.unique_aircraft), (450, 150, 35));
+        assert_eq!(north.max_range_km, Some(210.0));
+        // A full day up out of a day and a half
+        assert_eq!(north.uptime_pct, 66.7);
+        let south = &receivers["south"];
+        assert_eq!((south.max_range_km, south.uptime_pct), (None, 50.0));
+
+        let board = rank(receivers.values().cloned(), RankBy::Positions);
+        assert_eq!((board[0].rank, board[0].stats.device_id.as_str()), (1, "south"));
+        let board = rank(receivers.into_values(), RankBy::Uptime);
+        assert_eq!(board[0].stats.device_id, "north");
+    }
+}
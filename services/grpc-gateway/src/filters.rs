@@ -0,0 +1,264 @@
+//! Aircraft list filters
+//!
+//! `/api/aircraft` accepts a bounding box, a radius around a point, an
+//! altitude band and military/special address flags, applied by
+//! [`AircraftFilter::matches`] to the rows of the in-memory aircraft list.
+
+use crate::geo::haversine_km;
+use crate::military::{AddressClass, AddressClasses};
+use async_graphql::InputObject;
+use serde::Deserialize;
+use serde_json::Value as JsonValue;
+use std::sync::Arc;
+use utoipa::IntoParams;
+
+/// Kilometres per nautical mile
+const KM_PER_NM: f64 = 1.852;
+
+/// Query parameters for the aircraft list
+#[derive(Debug, Default, Deserialize, IntoParams, InputObject)]
+#[into_params(parameter_in = Query)]
+#[graphql(name = "AircraftFilter")]
+pub struct AircraftQuery {
+    /// `min_lat,min_lon,max_lat,max_lon`
+    pub bbox: Option<String>,
+    pub lat: Option<f64>,
+    pub lon: Option<f64>,
+    pub radius_nm: Option<f64>,
+    pub min_alt: Option<i32>,
+    pub max_alt: Option<i32>,
+    pub military: Option<bool>,
+    /// ICAO temporary or special-use address
+    pub special: Option<bool>,
+}
+
+/// Latitude/longitude box; `min_lon > max_lon` crosses the antimeridian
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoundingBox {
+    pub min_lat: f64,
+    pub min_lon: f64,
+    pub max_lat: f64,
+    pub max_lon: f64,
+}
+
+impl BoundingBox {
+    fn parse(s: &str) -> Result<Self, String> {
+        let parts: Vec<f64> = s
+            .split(',')
+            .map(|p| p.trim().parse::<f64>())
+            .collect::<Result<_, _>>()
+            .map_err(|_| "bbox must be min_lat,min_lon,max_lat,max_lon".to_string())?;
+        let [min_lat, min_lon, max_lat, max_lon] = parts[..] else {
+            return Err("bbox must be min_lat,min_lon,max_lat,max_lon".into());
+        };
+        Self::new(min_lat, min_lon, max_lat, max_lon)
+    }
+
+    /// Validated box; `min_lon > max_lon` crosses the antimeridian
+    pub fn new(min_lat: f64, min_lon: f64, max_lat: f64, max_lon: f64) -> Result<Self, String> {
+        let bbox = Self {
+            min_lat,
+            min_lon,
+            max_lat,
+            max_lon,
+        };
+        if !(-90.0..=90.0).contains(&min_lat)
+            || !(-90.0..=90.0).contains(&max_lat)
+            || !(-180.0..=180.0).contains(&min_lon)
+            || !(-180.0..=180.0).contains(&max_lon)
+        {
+            return Err("bbox is out of range".into());
+        }
+        if min_lat > max_lat {
+            return Err("bbox min_lat must not exceed max_lat".into());
+        }
+        Ok(bbox)
+    }
+
+    pub fn contains(&self, lat: f64, lon: f64) -> bool {
+        let lon_ok = if self.min_lon <= self.max_lon {
+            (self.min_lon..=self.max_lon).contains(&lon)
+        } else {
+            lon >= self.min_lon || lon <= self.max_lon
+        };
+        (self.min_lat..=self.max_lat).contains(&lat) && lon_ok
+    }
+}
+
+/// Parsed aircraft list filter
+#[derive(Debug, Clone, Default)]
+pub struct AircraftFilter {
+    pub bbox: Option<BoundingBox>,
+    /// `(lat, lon, radius_km)`
+    pub radius: Option<(f64, f64, f64)>,
+    pub min_alt: Option<i32>,
+    pub max_alt: Option<i32>,
+    pub military: Option<bool>,
+    pub special: Option<bool>,
+    pub addresses: Arc<AddressClasses>,
+}
+
+impl AircraftFilter {
+    pub fn from_query(query: &AircraftQuery, addresses: &Arc<AddressClasses>) -> Result<Self, String> {
+        let bbox = query.bbox.as_deref().map(BoundingBox::parse).transpose()?;
+
+        let radius = match (query.lat, query.lon, query.radius_nm) {
+            (None, None, None) => None,
+            (Some(lat), Some(lon), Some(nm)) => {
+                if !(-90.0..=90.0).contains(&lat) || !(-180.0..=180.0).contains(&lon) {
+                    return Err("lat/lon is out of range".into());
+                }
+                if nm.is_nan() || nm <= 0.0 {
+                    return Err("radius_nm must be positive".into());
+                }
+                Some((lat, lon, nm * KM_PER_NM))
+            }
+            _ => return Err("lat, lon and radius_nm must be given together".into()),
+        };
+
+        if let (Some(min), Some(max)) = (query.min_alt, query.max_alt) {
+            if min > max {
+                return Err("min_alt must not exceed max_alt".into());
+            }
+        }
+
+        Ok(Self {
+            bbox,
+            radius,
+            min_alt: query.min_alt,
+            max_alt: query.max_alt,
+            military: query.military,
+            special: query.special,
+            addresses: addresses.clone(),
+        })
+    }
+
+    /// Exact check of an aircraft list row (`icao`, `lat`, `lon`, `altitude`)
+    pub fn matches(&self, aircraft: &JsonValue) -> bool {
+        let lat = aircraft.get("lat").and_then(JsonValue::as_f64);
+        let lon = aircraft.get("lon").and_then(JsonValue::as_f64);
+        let position = lat.zip(lon);
+
+        if let Some(bbox) = &self.bbox {
+            if !position.is_some_and(|(lat, lon)| bbox.contains(lat, lon)) {
+                return false;
+            }
+        }
+        if let Some((clat, clon, km)) = self.radius {
+            if !position.is_some_and(|(lat, lon)| haversine_km(clat, clon, lat, lon) <= km) {
+                return false;
+            }
+        }
+
+        if self.min_alt.is_some() || self.max_alt.is_some() {
+            let altitude = aircraft.get("altitude").and_then(JsonValue::as_i64);
+            let in_band = altitude.is_some_and(|alt| {
+                self.min_alt.is_none_or(|min| alt >= min as i64)
+                    && self.max_alt.is_none_or(|max| alt <= max as i64)
+            });
+            if !in_band {
+                return false;
+            }
+        }
+
+        if self.military.is_some() || self.special.is_some() {
+            let icao = aircraft.get("icao").and_then(JsonValue::as_str).unwrap_or_default();
+            let class = self.addresses.classify(icao);
+            if self.military.is_some_and(|m| m != (class == AddressClass::Military))
+                || self.special.is_some_and(|s| s != (class == AddressClass::Special))
+            {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn aircraft(icao: &str, lat: f64, lon: f64, altitude: i32) -> JsonValue {
+        serde_json::json!({"icao": icao, "lat": lat, "lon": lon, "altitude": altitude})
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let bad = |q: AircraftQuery| AircraftFilter::from_query(&q, &Arc::default()).is_err();
+        assert!(bad(AircraftQuery {
+            bbox: Some("37,126,38".into()),
+            ..Default::default()
+        }));
+        assert!(bad(AircraftQuery {
+            lat: Some(37.5),
+            radius_nm: Some(10.0),
+            ..Default::default()
+        }));
+        assert!(bad(AircraftQuery {
+            min_alt: Some(5000),
+            max_alt: Some(1000),
+            ..Default::default()
+        }));
+    }
+
+    #[test]
+    fn test_radius_and_altitude() {
+        let filter = AircraftFilter::from_query(
+            &AircraftQuery {
+                lat: Some(37.46),
+                lon: Some(126.44),
+                radius_nm: Some(20.0),
+                min_alt: Some(1000),
+                ..Default::default()
+            },
+            &Arc::default(),
+        )
+        .unwrap();
+
+        assert!(filter.matches(&aircraft("71BE11", 37.55, 126.50, 3000)));
+        // Within 20 NM in latitude and longitude, but not diagonally
+        assert!(!filter.matches(&aircraft("71BE11", 37.76, 126.82, 3000)));
+        assert!(!filter.matches(&aircraft("71BE11", 37.55, 126.50, 500)));
+    }
+
+    #[test]
+    fn test_antimeridian_bbox() {
+        let filter = AircraftFilter::from_query(
+            &AircraftQuery {
+                bbox: Some("50,170,60,-170".into()),
+                ..Default::default()
+            },
+            &Arc::default(),
+        )
+        .unwrap();
+        assert!(filter.matches(&aircraft("71BE11", 55.0, 179.0, 0)));
+        assert!(filter.matches(&aircraft("71BE11", 55.0, -175.0, 0)));
+        assert!(!filter.matches(&aircraft("71BE11", 55.0, 0.0, 0)));
+    }
+
+    #[test]
+    fn test_military() {
+        let filter = AircraftFilter {
+            military: Some(true),
+            ..Default::default()
+        };
+        assert!(filter.matches(&aircraft("AE1234", 0.0, 0.0, 0)));
+        assert!(!filter.matches(&aircraft("71BE11", 0.0, 0.0, 0)));
+
+        // Overrides reclassify
+        let overrides = crate::military::parse_overrides("71BE11 special").unwrap();
+        let filter = AircraftFilter::from_query(
+            &AircraftQuery {
+                military: Some(false),
+                special: Some(true),
+                ..Default::default()
+            },
+            &Arc::new(AddressClasses::with_overrides(overrides)),
+        )
+        .unwrap();
+        assert!(filter.matches(&aircraft("71BE11", 0.0, 0.0, 0)));
+        assert!(filter.matches(&aircraft("F00001", 0.0, 0.0, 0)));
+        assert!(!filter.matches(&aircraft("AE1234", 0.0, 0.0, 0)));
+    }
+}
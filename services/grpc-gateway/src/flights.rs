@@ -0,0 +1,333 @@
+//! Flight session segmentation
+//!
+//! Groups each aircraft's stream of events into discrete flights. A new
+//! flight starts when an aircraft reappears after a gap longer than the
+//! configured timeout, when it takes off again after having landed, or when
+//! it starts broadcasting a different callsign (a turnaround at an airport
+//! without leaving receiver range).
+//! Open flights are kept in memory and upserted into the `flights` table
+//! periodically, so queries like "yesterday's flights" don't need to scan
+//! raw position rows.
+
+use crate::adsb::AircraftEvent;
+use crate::pubsub::LiveMessage;
+use crate::storage::Storage;
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+/// Ground speed below which an aircraft is considered taxiing/parked
+const GROUND_SPEED_KTS: f32 = 50.0;
+
+/// How often open flights are written to the database
+const FLUSH_INTERVAL_SECS: u64 = 30;
+
+/// A flight as stored in the `flights` table
+#[derive(Debug, Clone)]
+pub struct FlightRecord {
+    pub icao: String,
+    pub callsign: String,
+    pub first_seen: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
+    pub max_altitude_ft: Option<i32>,
+    pub position_count: i32,
+    pub device_id: String,
+}
+
+/// An aircraft's callsign changed, closing one flight and opening another
+#[derive(Debug, Clone, PartialEq)]
+pub struct CallsignChange {
+    pub icao: String,
+    pub previous_callsign: String,
+    pub callsign: String,
+    /// When the closed flight started
+    pub previous_first_seen: DateTime<Utc>,
+}
+
+impl CallsignChange {
+    /// The `callsign_change` message for `event`
+    pub fn message(&self, event: &AircraftEvent) -> LiveMessage {
+        LiveMessage::from_value(&serde_json::json!({
+            "type": "callsign_change",
+            "icao": self.icao,
+            "device_id": event.device_id,
+            "previous_callsign": self.previous_callsign,
+            "callsign": self.callsign,
+            "previous_flight_started": self.previous_first_seen.to_rfc3339(),
+            "timestamp_ms": event.timestamp_ms,
+        }))
+    }
+}
+
+/// In-progress flight with segmentation state
+struct OpenFlight {
+    record: FlightRecord,
+    /// Aircraft has been airborne during this flight
+    airborne_seen: bool,
+    /// Last event indicated the aircraft is on the ground
+    on_ground: bool,
+    /// Changed since last flush
+    dirty: bool,
+}
+
+impl OpenFlight {
+    fn new(event: &AircraftEvent, now: DateTime<Utc>) -> Self {
+        Self {
+            record: FlightRecord {
+                icao: event.icao.clone(),
+                callsign: String::new(),
+                first_seen: now,
+                last_seen: now,
+                max_altitude_ft: None,
+                position_count: 0,
+                device_id: event.device_id.clone(),
+            },
+            airborne_seen: false,
+            on_ground: false,
+            dirty: true,
+        }
+    }
+
+    fn apply(&mut self, event: &AircraftEvent, on_ground: bool, now: DateTime<Utc>) {
+        let r = &mut self.record;
+        r.last_seen = now;
+        if let Some(callsign) = callsign(event) {
+            r.callsign = callsign.to_string();
+        }
+        if let Some(altitude_ft) = event.altitude_ft {
+            r.max_altitude_ft = Some(r.max_altitude_ft.map_or(altitude_ft, |m| m.max(altitude_ft)));
+        }
+        if event.position().is_some() {
+            r.position_count += 1;
+        }
+        if !event.device_id.is_empty() {
+            r.device_id = event.device_id.clone();
+        }
+        self.on_ground = on_ground;
+        self.airborne_seen |= !on_ground;
+        self.dirty = true;
+    }
+}
+
+/// Builds flight sessions from the live event stream
+pub struct FlightSegmenter {
+    gap: chrono::Duration,
+    open: Mutex<HashMap<String, OpenFlight>>,
+    closed: Mutex<Vec<FlightRecord>>,
+}
+
+impl FlightSegmenter {
+    /// Create a segmenter that splits flights after `gap` without messages
+    pub fn new(gap: Duration) -> Self {
+        Self {
+            gap: chrono::Duration::from_std(gap).unwrap_or(chrono::Duration::minutes(30)),
+            open: Mutex::new(HashMap::new()),
+            closed: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Feed an aircraft event into the segmenter. Returns the change when
+    /// the event's callsign differs from the open flight's, which closes it
+    pub fn observe(&self, event: &AircraftEvent, now: DateTime<Utc>) -> Option<CallsignChange> {
+        let on_ground = is_on_ground(event);
+        let mut open = self.open.lock().ok()?;
+
+        let mut change = None;
+        let split = match open.get(&event.icao) {
+            Some(flight) => {
+                let gap_exceeded = now - flight.record.last_seen > self.gap;
+                let took_off_again = flight.airborne_seen && flight.on_ground && !on_ground;
+                if let Some(callsign) = callsign(event) {
+                    let previous = &flight.record.callsign;
+                    if !gap_exceeded && !previous.is_empty() && previous != callsign {
+                        change = Some(CallsignChange {
+                            icao: event.icao.clone(),
+                            previous_callsign: previous.clone(),
+                            callsign: callsign.to_string(),
+                            previous_first_seen: flight.record.first_seen,
+                        });
+                    }
+                }
+                gap_exceeded || took_off_again || change.is_some()
+            }
+            None => false,
+        };
+
+        if split {
+            if let Some(flight) = open.remove(&event.icao) {
+                debug!(
+                    "Flight closed: {} {} ({} positions)",
+                    flight.record.icao, flight.record.callsign, flight.record.position_count
+                );
+                self.close(flight.record);
+            }
+        }
+        if let Some(change) = &change {
+            info!(
+                "Aircraft {} callsign {} -> {}, new flight",
+                change.icao, change.previous_callsign, change.callsign
+            );
+        }
+
+        open.entry(event.icao.clone())
+            .or_insert_with(|| OpenFlight::new(event, now))
+            .apply(event, on_ground, now);
+        change
+    }
+
+    /// The open flight for an aircraft, if any
+    pub fn current(&self, icao: &str) -> Option<FlightRecord> {
+        self.open.lock().ok()?.get(icao).map(|f| f.record.clone())
+    }
+
+    /// Collect flights that need writing: closed flights, changed open
+    /// flights, and open flights that have timed out (which are closed)
+    pub fn take_pending(&self, now: DateTime<Utc>) -> Vec<FlightRecord> {
+        let mut pending = match self.closed.lock() {
+            Ok(mut closed) => std::mem::take(&mut *closed),
+            Err(_) => Vec::new(),
+        };
+
+        if let Ok(mut open) = self.open.lock() {
+            let gap = self.gap;
+            let expired: Vec<String> = open
+                .iter()
+                .filter(|(_, f)| now - f.record.last_seen > gap)
+                .map(|(icao, _)| icao.clone())
+                .collect();
+            for icao in expired {
+                if let Some(flight) = open.remove(&icao) {
+                    if flight.dirty {
+                        pending.push(flight.record);
+                    }
+                }
+            }
+
+            for flight in open.values_mut().filter(|f| f.dirty) {
+                flight.dirty = false;
+                pending.push(flight.record.clone());
+            }
+        }
+
+        pending
+    }
+
+    fn close(&self, record: FlightRecord) {
+        if let Ok(mut closed) = self.closed.lock() {
+            closed.push(record);
+        }
+    }
+}
+
+/// The event's callsign, if it carries a usable one
+fn callsign(event: &AircraftEvent) -> Option<&str> {
+    event.callsign.as_deref().map(str::trim).filter(|c| !c.is_empty())
+}
+
+/// Whether an event indicates the aircraft is on the ground
+fn is_on_ground(event: &AircraftEvent) -> bool {
+    // TC 5-8 are surface position messages
+    (5..=8).contains(&event.type_code)
+        || event.speed_kts.is_some_and(|speed| speed > 0.0 && speed < GROUND_SPEED_KTS)
+}
+
+/// Periodically write flight sessions to storage
+pub fn spawn_flusher(segmenter: Arc<FlightSegmenter>, db_writer: Arc<dyn Storage>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(FLUSH_INTERVAL_SECS));
+        loop {
+            interval.tick().await;
+            for flight in segmenter.take_pending(Utc::now()) {
+                if let Err(e) = db_writer.upsert_flight(&flight).await {
+                    warn!("Failed to store flight for {}: {}", flight.icao, e);
+                }
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(icao: &str, speed: f32, altitude: i32) -> AircraftEvent {
+        AircraftEvent {
+            icao: icao.to_string(),
+            speed_kts: Some(speed),
+            altitude_ft: Some(altitude),
+            latitude: Some(37.5),
+            longitude: Some(127.0),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_gap_splits_flight() {
+        let seg = FlightSegmenter::new(Duration::from_secs(600));
+        let t0 = Utc::now();
+        seg.observe(&event("ABC123", 300.0, 10000), t0);
+        seg.observe(&event("ABC123", 300.0, 12000), t0 + chrono::Duration::seconds(60));
+        seg.observe(&event("ABC123", 300.0, 9000), t0 + chrono::Duration::seconds(2000));
+
+        let pending = seg.take_pending(t0 + chrono::Duration::seconds(2001));
+        assert_eq!(pending.len(), 2);
+        assert_eq!(pending[0].max_altitude_ft, Some(12000));
+        assert_eq!(pending[0].position_count, 2);
+        assert_eq!(pending[1].position_count, 1);
+    }
+
+    #[test]
+    fn test_takeoff_after_landing_splits_flight() {
+        let seg = FlightSegmenter::new(Duration::from_secs(1800));
+        let t0 = Utc::now();
+        let step = |s| t0 + chrono::Duration::seconds(s);
+        seg.observe(&event("ABC123", 250.0, 3000), step(0));
+        seg.observe(&event("ABC123", 20.0, 100), step(60));
+        seg.observe(&event("ABC123", 140.0, 500), step(120));
+
+        let pending = seg.take_pending(step(121));
+        assert_eq!(pending.len(), 2);
+        assert_eq!(pending[0].last_seen, step(60));
+        assert_eq!(pending[1].first_seen, step(120));
+    }
+
+    #[test]
+    fn test_callsign_change_splits_flight() {
+        let seg = FlightSegmenter::new(Duration::from_secs(1800));
+        let t0 = Utc::now();
+        let step = |s| t0 + chrono::Duration::seconds(s);
+        let with_callsign = |callsign: Option<&str>| AircraftEvent {
+            callsign: callsign.map(String::from),
+            ..event("ABC123", 10.0, 0)
+        };
+        assert!(seg.observe(&with_callsign(Some("KAL017")), step(0)).is_none());
+        // Padded or missing callsigns are not a change
+        assert!(seg.observe(&with_callsign(Some("KAL017  ")), step(30)).is_none());
+        assert!(seg.observe(&with_callsign(None), step(60)).is_none());
+
+        let change = seg.observe(&with_callsign(Some("KAL018")), step(90)).unwrap();
+        assert_eq!(change.previous_callsign, "KAL017");
+        assert_eq!(change.callsign, "KAL018");
+        assert_eq!(change.previous_first_seen, step(0));
+        let json: serde_json::Value =
+            serde_json::from_str(&change.message(&with_callsign(Some("KAL018"))).json).unwrap();
+        assert_eq!(json["type"], "callsign_change");
+
+        let pending = seg.take_pending(step(91));
+        assert_eq!(pending.len(), 2);
+        assert_eq!((pending[0].callsign.as_str(), pending[0].last_seen), ("KAL017", step(60)));
+        assert_eq!((pending[1].callsign.as_str(), pending[1].first_seen), ("KAL018", step(90)));
+    }
+
+    #[test]
+    fn test_ground_start_does_not_split() {
+        let seg = FlightSegmenter::new(Duration::from_secs(1800));
+        let t0 = Utc::now();
+        seg.observe(&event("ABC123", 15.0, 100), t0);
+        seg.observe(&event("ABC123", 160.0, 800), t0 + chrono::Duration::seconds(60));
+
+        assert_eq!(seg.take_pending(t0 + chrono::Duration::seconds(61)).len(), 1);
+    }
+}
@@ -0,0 +1,139 @@
+//! Geographic helpers: distances and geofence shapes
+
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Mean Earth radius in kilometres
+const EARTH_RADIUS_KM: f64 = 6371.0;
+
+/// Great-circle distance between two points in kilometres
+pub fn haversine_km(lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> f64 {
+    let dlat = (lat2 - lat1).to_radians();
+    let dlon = (lon2 - lon1).to_radians();
+    let a = (dlat / 2.0).sin().powi(2)
+        + lat1.to_radians().cos() * lat2.to_radians().cos() * (dlon / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_KM * a.sqrt().asin()
+}
+
+/// Initial great-circle bearing from the first point to the second, 0-360 degrees
+pub fn bearing_deg(lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> f64 {
+    let (phi1, phi2) = (lat1.to_radians(), lat2.to_radians());
+    let dlon = (lon2 - lon1).to_radians();
+    let y = dlon.sin() * phi2.cos();
+    let x = phi1.cos() * phi2.sin() - phi1.sin() * phi2.cos() * dlon.cos();
+    y.atan2(x).to_degrees().rem_euclid(360.0)
+}
+
+/// A geofence area
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Geofence {
+    /// Circle around a centre point
+    Circle { lat: f64, lon: f64, radius_km: f64 },
+    /// Polygon of `[lat, lon]` vertices (implicitly closed)
+    Polygon { points: Vec<[f64; 2]> },
+}
+
+impl Geofence {
+    /// Check the shape is usable
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            Self::Circle { lat, lon, radius_km } => {
+                if !valid_point(*lat, *lon) {
+                    return Err("circle centre is out of range".into());
+                }
+                if radius_km.is_nan() || *radius_km <= 0.0 {
+                    return Err("radius_km must be positive".into());
+                }
+            }
+            Self::Polygon { points } => {
+                if points.len() < 3 {
+                    return Err("polygon needs at least 3 points".into());
+                }
+                if !points.iter().all(|p| valid_point(p[0], p[1])) {
+                    return Err("polygon point is out of range".into());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether a position lies inside the fence
+    pub fn contains(&self, lat: f64, lon: f64) -> bool {
+        match self {
+            Self::Circle {
+                lat: clat,
+                lon: clon,
+                radius_km,
+            } => haversine_km(*clat, *clon, lat, lon) <= *radius_km,
+            Self::Polygon { points } => point_in_polygon(points, lat, lon),
+        }
+    }
+}
+
+fn valid_point(lat: f64, lon: f64) -> bool {
+    (-90.0..=90.0).contains(&lat) && (-180.0..=180.0).contains(&lon)
+}
+
+/// Ray-casting point-in-polygon test on `[lat, lon]` vertices
+fn point_in_polygon(points: &[[f64; 2]], lat: f64, lon: f64) -> bool {
+    let mut inside = false;
+    let mut j = points.len() - 1;
+    for i in 0..points.len() {
+        let (yi, xi) = (points[i][0], points[i][1]);
+        let (yj, xj) = (points[j][0], points[j][1]);
+        if (yi > lat) != (yj > lat) && lon < (xj - xi) * (lat - yi) / (yj - yi) + xi {
+            inside = !inside;
+        }
+        j = i;
+    }
+    inside
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_haversine() {
+        // Seoul to Busan is roughly 325 km
+        let d = haversine_km(37.5665, 126.9780, 35.1796, 129.0756);
+        assert!((d - 325.0).abs() < 5.0, "distance was {}", d);
+    }
+
+    #[test]
+    fn test_circle_contains() {
+        let fence = Geofence::Circle {
+            lat: 37.46,
+            lon: 126.44,
+            radius_km: 10.0,
+        };
+        assert!(fence.contains(37.50, 126.45));
+        assert!(!fence.contains(37.57, 126.98));
+    }
+
+    #[test]
+    fn test_polygon_contains() {
+        let fence = Geofence::Polygon {
+            points: vec![[37.0, 126.0], [37.0, 127.0], [38.0, 127.0], [38.0, 126.0]],
+        };
+        assert!(fence.validate().is_ok());
+        assert!(fence.contains(37.5, 126.5));
+        assert!(!fence.contains(36.9, 126.5));
+        assert!(!fence.contains(37.5, 127.1));
+    }
+
+    #[test]
+    fn test_validate() {
+        let bad = Geofence::Polygon {
+            points: vec![[37.0, 126.0], [37.0, 127.0]],
+        };
+        assert!(bad.validate().is_err());
+        let bad = Geofence::Circle {
+            lat: 91.0,
+            lon: 0.0,
+            radius_km: 1.0,
+        };
+        assert!(bad.validate().is_err());
+    }
+}
@@ -0,0 +1,380 @@
+//! Geofence entry/exit detection
+//!
+//! User-defined areas (circles or polygons, optionally limited to an
+//! altitude band) are stored in the database. Every positioned aircraft
+//! event updates the per-aircraft inside/outside state; transitions are
+//! broadcast as `geofence` WebSocket messages and, for fences with
+//! `log_events`, written to the `geofence_events` table. Aircraft that stop
+//! reporting while inside a fence get an `exit` with reason `timeout`.
+
+use crate::adsb::AircraftEvent;
+use crate::geo::Geofence;
+use crate::pubsub::{LiveMessage, PubSub};
+use crate::storage::Storage;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+use utoipa::ToSchema;
+
+/// Aircraft silent this long while inside a fence are treated as exited
+const INSIDE_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// How often timed-out aircraft are swept
+const SWEEP_INTERVAL_SECS: u64 = 30;
+
+/// A stored geofence
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct GeofenceDef {
+    pub id: i64,
+    pub name: String,
+    pub shape: Geofence,
+    pub min_altitude_ft: Option<i32>,
+    pub max_altitude_ft: Option<i32>,
+    /// Persist entry/exit events to `geofence_events`
+    pub log_events: bool,
+}
+
+/// Request body for creating a geofence
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct NewGeofence {
+    pub name: String,
+    pub shape: Geofence,
+    pub min_altitude_ft: Option<i32>,
+    pub max_altitude_ft: Option<i32>,
+    #[serde(default)]
+    pub log_events: bool,
+}
+
+impl NewGeofence {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err("name is required".into());
+        }
+        self.shape.validate()?;
+        if let (Some(min), Some(max)) = (self.min_altitude_ft, self.max_altitude_ft) {
+            if min > max {
+                return Err("min_altitude_ft must not exceed max_altitude_ft".into());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Entry or exit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Transition {
+    Enter,
+    Exit,
+}
+
+impl Transition {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Enter => "enter",
+            Self::Exit => "exit",
+        }
+    }
+}
+
+/// A geofence entry/exit event
+#[derive(Debug, Clone, Serialize)]
+pub struct GeofenceEvent {
+    pub geofence_id: i64,
+    pub geofence_name: String,
+    pub icao: String,
+    pub callsign: Option<String>,
+    pub event: Transition,
+    /// `position` or `timeout`
+    pub reason: &'static str,
+    pub lat: f64,
+    pub lon: f64,
+    pub altitude_ft: Option<i32>,
+    pub time: DateTime<Utc>,
+}
+
+impl GeofenceDef {
+    /// Inside/outside for an event, or None if it can't be decided
+    fn evaluate(&self, event: &AircraftEvent) -> Option<bool> {
+        let (lat, lon) = event.position()?;
+        let has_band = self.min_altitude_ft.is_some() || self.max_altitude_ft.is_some();
+        let altitude_ft = match event.altitude_ft {
+            Some(altitude_ft) => altitude_ft,
+            None if has_band => return None,
+            None => 0,
+        };
+        let in_band = self.min_altitude_ft.is_none_or(|min| altitude_ft >= min)
+            && self.max_altitude_ft.is_none_or(|max| altitude_ft <= max);
+        Some(in_band && self.shape.contains(lat, lon))
+    }
+}
+
+/// Last known state of an aircraft inside a fence
+struct InsideState {
+    last_seen: Instant,
+    callsign: Option<String>,
+    lat: f64,
+    lon: f64,
+    altitude_ft: Option<i32>,
+}
+
+/// Tracks which aircraft are inside which fences
+pub struct GeofenceMonitor {
+    fences: RwLock<Vec<GeofenceDef>>,
+    inside: Mutex<HashMap<(i64, String), InsideState>>,
+    pubsub: Arc<dyn PubSub>,
+}
+
+impl GeofenceMonitor {
+    pub fn new(pubsub: Arc<dyn PubSub>) -> Self {
+        Self {
+            fences: RwLock::new(Vec::new()),
+            inside: Mutex::new(HashMap::new()),
+            pubsub,
+        }
+    }
+
+    /// Reload fences from storage, forgetting state for removed fences
+    pub async fn reload(&self, db_writer: &dyn Storage) -> Result<usize> {
+        let fences = db_writer.get_geofences().await?;
+        let count = fences.len();
+        if let Ok(mut inside) = self.inside.lock() {
+            inside.retain(|(id, _), _| fences.iter().any(|f| f.id == *id));
+        }
+        if let Ok(mut current) = self.fences.write() {
+            *current = fences;
+        }
+        Ok(count)
+    }
+
+    /// Update state for an event and return any transitions
+    pub fn observe(&self, event: &AircraftEvent) -> Vec<(GeofenceEvent, bool)> {
+        let fences = match self.fences.read() {
+            Ok(fences) if !fences.is_empty() => fences,
+            _ => return Vec::new(),
+        };
+        let mut inside = match self.inside.lock() {
+            Ok(inside) => inside,
+            Err(_) => return Vec::new(),
+        };
+
+        let mut transitions = Vec::new();
+        for fence in fences.iter() {
+            let now_inside = match fence.evaluate(event) {
+                Some(v) => v,
+                None => continue,
+            };
+            let key = (fence.id, event.icao.clone());
+            let was_inside = inside.contains_key(&key);
+
+            if now_inside {
+                let state = inside.entry(key).or_insert_with(|| InsideState {
+                    last_seen: Instant::now(),
+                    callsign: None,
+                    lat: 0.0,
+                    lon: 0.0,
+                    altitude_ft: None,
+                });
+                state.last_seen = Instant::now();
+                if let Some(callsign) = event.callsign.as_ref().filter(|c| !c.is_empty()) {
+                    state.callsign = Some(callsign.clone());
+                }
+                if let Some((lat, lon)) = event.position() {
+                    state.lat = lat;
+                    state.lon = lon;
+                }
+                if event.altitude_ft.is_some() {
+                    state.altitude_ft = event.altitude_ft;
+                }
+            } else {
+                inside.remove(&key);
+            }
+
+            if now_inside != was_inside {
+                let transition = if now_inside { Transition::Enter } else { Transition::Exit };
+                transitions.push((
+                    GeofenceEvent {
+                        geofence_id: fence.id,
+                        geofence_name: fence.name.clone(),
+                        icao: event.icao.clone(),
+                        callsign: event.callsign.clone().filter(|c| !c.is_empty()),
+                        event: transition,
+                        reason: "position",
+                        lat: event.latitude.unwrap_or_default(),
+                        lon: event.longitude.unwrap_or_default(),
+                        altitude_ft: event.altitude_ft,
+                        time: Utc::now(),
+                    },
+                    fence.log_events,
+                ));
+            }
+        }
+        transitions
+    }
+
+    /// Exit aircraft that stopped reporting while inside a fence
+    fn sweep(&self) -> Vec<(GeofenceEvent, bool)> {
+        let fences = match self.fences.read() {
+            Ok(fences) => fences,
+            Err(_) => return Vec::new(),
+        };
+        let mut inside = match self.inside.lock() {
+            Ok(inside) => inside,
+            Err(_) => return Vec::new(),
+        };
+
+        let expired: Vec<(i64, String)> = inside
+            .iter()
+            .filter(|(_, s)| s.last_seen.elapsed() > INSIDE_TIMEOUT)
+            .map(|(k, _)| k.clone())
+            .collect();
+
+        let mut transitions = Vec::new();
+        for key in expired {
+            let state = match inside.remove(&key) {
+                Some(state) => state,
+                None => continue,
+            };
+            let fence = match fences.iter().find(|f| f.id == key.0) {
+                Some(fence) => fence,
+                None => continue,
+            };
+            transitions.push((
+                GeofenceEvent {
+                    geofence_id: fence.id,
+                    geofence_name: fence.name.clone(),
+                    icao: key.1,
+                    callsign: state.callsign,
+                    event: Transition::Exit,
+                    reason: "timeout",
+                    lat: state.lat,
+                    lon: state.lon,
+                    altitude_ft: state.altitude_ft,
+                    time: Utc::now(),
+                },
+                fence.log_events,
+            ));
+        }
+        transitions
+    }
+
+    /// Broadcast transitions and log those for fences with `log_events`
+    pub async fn publish(&self, transitions: Vec<(GeofenceEvent, bool)>, db_writer: &dyn Storage) {
+        for (event, log) in transitions {
+            info!(
+                "Geofence '{}': {} {} ({})",
+                event.geofence_name,
+                event.icao,
+                event.event.as_str(),
+                event.reason
+            );
+
+            if self.pubsub.has_subscribers() {
+                let mut msg = serde_json::to_value(&event).unwrap_or_default();
+                if let Some(obj) = msg.as_object_mut() {
+                    obj.insert("type".into(), "geofence".into());
+                    obj.insert("time".into(), event.time.to_rfc3339().into());
+                }
+                self.pubsub.publish(LiveMessage::from_value(&msg));
+            }
+
+            if log {
+                if let Err(e) = db_writer.insert_geofence_event(&event).await {
+                    warn!("Failed to log geofence event: {}", e);
+                }
+            }
+        }
+    }
+}
+
+/// Periodically exit aircraft that timed out inside a fence
+pub fn spawn_sweeper(monitor: Arc<GeofenceMonitor>, db_writer: Arc<dyn Storage>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(SWEEP_INTERVAL_SECS));
+        loop {
+            interval.tick().await;
+            let transitions = monitor.sweep();
+            if !transitions.is_empty() {
+                monitor.publish(transitions, db_writer.as_ref()).await;
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pubsub::LocalPubSub;
+
+    fn monitor_with(fence: GeofenceDef) -> GeofenceMonitor {
+        let monitor = GeofenceMonitor::new(Arc::new(LocalPubSub::new()));
+        *monitor.fences.write().unwrap() = vec![fence];
+        monitor
+    }
+
+    fn event(lat: f64, lon: f64, altitude: i32) -> AircraftEvent {
+        AircraftEvent {
+            icao: "71BE11".into(),
+            latitude: Some(lat),
+            longitude: Some(lon),
+            altitude_ft: Some(altitude),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_enter_and_exit() {
+        let monitor = monitor_with(GeofenceDef {
+            id: 1,
+            name: "circuit".into(),
+            shape: Geofence::Circle {
+                lat: 37.5,
+                lon: 126.5,
+                radius_km: 5.0,
+            },
+            min_altitude_ft: None,
+            max_altitude_ft: Some(2000),
+            log_events: false,
+        });
+
+        assert!(monitor.observe(&event(37.7, 126.5, 1500)).is_empty());
+
+        let t = monitor.observe(&event(37.51, 126.5, 1500));
+        assert_eq!(t.len(), 1);
+        assert_eq!(t[0].0.event, Transition::Enter);
+
+        // Still inside: no transition
+        assert!(monitor.observe(&event(37.5, 126.51, 1200)).is_empty());
+        // Unknown altitude with a band: state unchanged
+        let no_altitude = AircraftEvent {
+            altitude_ft: None,
+            ..event(37.5, 126.51, 0)
+        };
+        assert!(monitor.observe(&no_altitude).is_empty());
+
+        // Climbing out of the band counts as exit
+        let t = monitor.observe(&event(37.5, 126.51, 3000));
+        assert_eq!(t.len(), 1);
+        assert_eq!(t[0].0.event, Transition::Exit);
+    }
+
+    #[test]
+    fn test_validate() {
+        let fence = NewGeofence {
+            name: "bad band".into(),
+            shape: Geofence::Circle {
+                lat: 37.5,
+                lon: 126.5,
+                radius_km: 5.0,
+            },
+            min_altitude_ft: Some(3000),
+            max_altitude_ft: Some(1000),
+            log_events: false,
+        };
+        assert!(fence.validate().is_err());
+    }
+}
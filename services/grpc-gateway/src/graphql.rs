@@ -0,0 +1,194 @@
+//! GraphQL endpoint
+//!
+//! `POST /api/graphql` runs queries against the same typed responses as the
+//! REST API, so a dashboard can fetch exactly the fields it needs, with
+//! nested trails and flights per aircraft, in one round trip. The schema is
+//! read-only; `GET /api/graphql` serves GraphiQL for exploring it. Depth and
+//! complexity are limited because nested fields fan out into queries per
+//! aircraft.
+
+use crate::api::{
+    self, Aircraft, AircraftDetail, Flight, SdrHeartbeat, SdrStatus, StatsResponse, TrailPoint,
+};
+use crate::filters::{AircraftFilter, AircraftQuery};
+use crate::stats::StatsRange;
+use crate::tenants::Scope;
+use crate::{AppState, MAX_TRAIL_BUCKET_SECS};
+use async_graphql::http::GraphiQLSource;
+use async_graphql::{
+    ComplexObject, Context, EmptyMutation, EmptySubscription, Object, Result, Schema, SimpleObject,
+};
+use axum::{
+    extract::State,
+    response::{Html, IntoResponse},
+    Json,
+};
+use chrono::Utc;
+use std::sync::Arc;
+
+/// Where the endpoint is mounted
+pub const PATH: &str = "/api/graphql";
+
+/// Deepest nesting a query may use
+const MAX_DEPTH: usize = 8;
+
+/// Largest query, counting one per field
+const MAX_COMPLEXITY: usize = 500;
+
+pub type ApiSchema = Schema<QueryRoot, EmptyMutation, EmptySubscription>;
+
+/// Build the schema; the app state is attached to each request
+pub fn schema() -> ApiSchema {
+    Schema::build(QueryRoot, EmptyMutation, EmptySubscription)
+        .limit_depth(MAX_DEPTH)
+        .limit_complexity(MAX_COMPLEXITY)
+        .finish()
+}
+
+fn state<'a>(ctx: &Context<'a>) -> Result<&'a Arc<AppState>> {
+    ctx.data::<Arc<AppState>>()
+}
+
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    /// Aircraft seen in the last five minutes, optionally filtered
+    async fn aircraft(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default)] filter: AircraftQuery,
+    ) -> Result<Vec<Aircraft>> {
+        let state = state(ctx)?;
+        let filter = AircraftFilter::from_query(&filter, &state.addresses)?;
+        Ok(state.current_aircraft(&filter, &Scope::All).await?)
+    }
+
+    /// One aircraft's merged current state
+    async fn aircraft_by_icao(
+        &self,
+        ctx: &Context<'_>,
+        icao: String,
+    ) -> Result<Option<AircraftDetail>> {
+        let icao = api::parse_icao(&icao)?;
+        Ok(state(ctx)?.aircraft_detail(&icao, &Scope::All).await?)
+    }
+
+    /// Flight sessions, most recent first
+    async fn flights(
+        &self,
+        ctx: &Context<'_>,
+        icao: Option<String>,
+        #[graphql(default = 100)] limit: i64,
+    ) -> Result<Vec<Flight>> {
+        let icao = icao.as_deref().map(api::parse_icao).transpose()?;
+        Ok(state(ctx)?.flight_list(icao.as_deref(), limit.clamp(1, 1000), &Scope::All).await?)
+    }
+
+    /// Capture devices that reported a message rate recently
+    async fn receivers(&self, ctx: &Context<'_>) -> Result<Vec<ReceiverDevice>> {
+        let live = state(ctx)?.stats.live(chrono::Utc::now());
+        Ok(live
+            .devices
+            .into_iter()
+            .map(|(device_id, messages_per_second)| ReceiverDevice {
+                device_id,
+                messages_per_second,
+            })
+            .collect())
+    }
+
+    /// Status of every SDR device, ordered by device ID
+    async fn sdr_status(&self, ctx: &Context<'_>) -> Result<Vec<SdrStatus>> {
+        Ok(state(ctx)?.sdr_devices().await?.into_values().collect())
+    }
+
+    /// One SDR device's status updates, most recent first
+    async fn sdr_history(
+        &self,
+        ctx: &Context<'_>,
+        device_id: String,
+        #[graphql(default = 1)] hours: i64,
+        #[graphql(default = 1000)] limit: i64,
+    ) -> Result<Vec<SdrHeartbeat>> {
+        let (hours, limit) = (hours.clamp(1, 24 * 7), limit.clamp(1, 10_000));
+        Ok(state(ctx)?.sdr_history(&device_id, hours, limit).await?)
+    }
+
+    /// Live rates plus hourly and daily rollups
+    async fn stats(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default = 24)] hours: i64,
+        #[graphql(default = 7)] days: i64,
+    ) -> Result<StatsResponse> {
+        let (hours, days) = (hours.clamp(1, 24 * 30), days.clamp(1, 90));
+        let range = StatsRange::recent(hours, days, Utc::now());
+        Ok(state(ctx)?.stats_summary(range).await?)
+    }
+}
+
+#[ComplexObject]
+impl Aircraft {
+    /// Positions from the last `minutes`, oldest first; with
+    /// `bucket_seconds`, the latest of each bucket
+    async fn trail(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default = 30)] minutes: i32,
+        bucket_seconds: Option<i32>,
+    ) -> Result<Vec<TrailPoint>> {
+        let from = Utc::now() - chrono::Duration::minutes(minutes.clamp(1, 24 * 60) as i64);
+        let bucket_secs = bucket_seconds.map(|secs| secs.clamp(1, MAX_TRAIL_BUCKET_SECS as i32) as i64);
+        Ok(state(ctx)?.trail(&self.icao, from, None, bucket_secs, &Scope::All).await?)
+    }
+
+    /// This aircraft's flight sessions, most recent first
+    async fn flights(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default = 10)] limit: i64,
+    ) -> Result<Vec<Flight>> {
+        Ok(state(ctx)?.flight_list(Some(&self.icao), limit.clamp(1, 1000), &Scope::All).await?)
+    }
+}
+
+/// A capture device and its current message rate
+#[derive(SimpleObject)]
+pub struct ReceiverDevice {
+    device_id: String,
+    messages_per_second: f32,
+}
+
+/// Run a query
+pub async fn graphql_handler(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<async_graphql::Request>,
+) -> Json<async_graphql::Response> {
+    let request = request.data(state.clone());
+    Json(state.graphql.execute(request).await)
+}
+
+/// GraphiQL query editor
+pub async fn graphiql() -> impl IntoResponse {
+    Html(GraphiQLSource::build().endpoint(PATH).finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_schema() {
+        let schema = schema();
+        let sdl = schema.sdl();
+        for field in ["aircraftByIcao(icao: String!)", "receivers", "trail(minutes: Int! = 30, bucketSeconds: Int)"] {
+            assert!(sdl.contains(field), "{} missing", field);
+        }
+        assert!(!sdl.contains("type Mutation"));
+
+        let deep = "{ __schema { types { fields { type { ofType { ofType { ofType { ofType { name } } } } } } } } }";
+        let response = schema.execute(deep).await;
+        assert!(response.errors[0].message.contains("too deep"));
+    }
+}
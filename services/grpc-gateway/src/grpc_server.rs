@@ -1,42 +1,410 @@
 //! gRPC server implementation - receives streams from host
 
 use crate::adsb::{
-    adsb_gateway_server::AdsbGateway, AircraftEvent, DeviceStatus, SignalMetrics, StreamAck,
+    adsb_gateway_server::AdsbGateway, AircraftEvent, AircraftLabels, AircraftLabelsRequest, DeviceStatus,
+    FrameProvenance, FrequencyScan, HeartbeatPing, HeartbeatPong, PingRequest, PingResponse, RawFrame,
+    SignalMetrics, StreamAck,
 };
-use crate::db_writer::DbWriter;
+use crate::aircraft_db::AircraftMeta;
+use crate::api::{self, Enrichment};
+use crate::config::PositionSource;
+use crate::decoder_config::{self, DecoderSettings};
+use crate::emergencies::emergency_name;
+use crate::military::AddressClass;
+use crate::pubsub::{LiveMessage, MessageFields};
+use crate::squawks;
+use crate::uptime::StreamDevices;
+use crate::AppState;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::pin::Pin;
 use std::sync::Arc;
-use tokio::sync::broadcast;
-use tokio_stream::StreamExt;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tokio_stream::{Stream, StreamExt};
 use tonic::{Request, Response, Status, Streaming};
 use tracing::{debug, error, info, warn};
 
+/// Messages broadcast for incoming events
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum LiveEvent<'a> {
+    PositionUpdate(Box<PositionUpdate<'a>>),
+    Signal(SignalUpdate<'a>),
+    DeviceStatus(DeviceStatusUpdate<'a>),
+}
+
+/// How the event's frame was received and decoded
+#[derive(Debug, Serialize)]
+struct Provenance<'a> {
+    raw: &'a str,
+    df: u32,
+    tc: u32,
+    /// Bits flipped by error correction
+    corrected_bits: u32,
+    /// Mean bit margin relative to the preamble level, 0-1
+    confidence: f32,
+    signal_level: u32,
+}
+
+impl<'a> From<&'a FrameProvenance> for Provenance<'a> {
+    fn from(p: &'a FrameProvenance) -> Self {
+        Self {
+            raw: &p.raw_hex,
+            df: p.downlink_format,
+            tc: p.type_code,
+            corrected_bits: p.corrected_bits,
+            confidence: p.confidence,
+            signal_level: p.signal_level,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct PositionUpdate<'a> {
+    icao: &'a str,
+    device_id: &'a str,
+    // Fields never reported for the aircraft are left out
+    #[serde(skip_serializing_if = "Option::is_none")]
+    lat: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    lon: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    altitude: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    speed: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    track: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    magnetic_heading: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    vrate: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    baro_rate: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    geom_rate: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    callsign: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    squawk: Option<&'a str>,
+    /// Emitter category, e.g. `A3`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    category: Option<&'a str>,
+    /// ADS-B version and the position's quality categories, readsb names
+    #[serde(skip_serializing_if = "Option::is_none")]
+    version: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    nic: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    nac_p: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    nuc_p: Option<u32>,
+    /// Containment radius, metres
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rc: Option<f32>,
+    /// Position is below `MIN_POSITION_NIC`/`MIN_POSITION_NACP`
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    low_quality: bool,
+    emergency: Option<&'static str>,
+    timestamp_ms: u64,
+    /// The frame behind this update, from hosts with `EVENT_PROVENANCE` on
+    #[serde(skip_serializing_if = "Option::is_none")]
+    provenance: Option<Provenance<'a>>,
+    /// Non-Mode S origin, e.g. `ogn`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    source: Option<&'a str>,
+    #[serde(flatten)]
+    enrichment: Enrichment,
+}
+
+#[derive(Debug, Serialize)]
+struct SignalUpdate<'a> {
+    device_id: &'a str,
+    signal_dbfs: f32,
+    noise_dbfs: f32,
+    snr_db: f32,
+    msg_rate: f32,
+    timestamp_ms: u64,
+    // Decoder statistics
+    preambles_detected: u64,
+    frames_decoded: u64,
+    crc_errors: u64,
+    corrected_frames: u64,
+    samples_processed: u64,
+    noise_floor: u32,
+    peak_signal: u32,
+    // Left out until the capture host has an estimate
+    #[serde(skip_serializing_if = "Option::is_none")]
+    false_positive_rate: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    corrected_false_positive_rate: Option<f32>,
+    /// Frames the host dropped as echoes of one just received
+    duplicate_frames: u64,
+    // Cumulative frames per downlink format and extended squitter type
+    // code, left out for hosts that don't count them
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    downlink_formats: BTreeMap<u32, FormatCount>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    type_codes: BTreeMap<u32, u64>,
+}
+
+#[derive(Debug, Serialize)]
+struct FormatCount {
+    frames: u64,
+    corrected: u64,
+}
+
+#[derive(Debug, Serialize)]
+struct DeviceStatusUpdate<'a> {
+    device_id: &'a str,
+    connected: bool,
+    sample_rate: u32,
+    center_freq: u64,
+    gain_db: f32,
+    timestamp_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    software_version: Option<&'a str>,
+    uptime_secs: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    decoder: Option<DecoderSettings>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    clock_offset_ms: Option<i64>,
+}
+
+impl LiveEvent<'_> {
+    fn kind(&self) -> &'static str {
+        match self {
+            LiveEvent::PositionUpdate(_) => "position_update",
+            LiveEvent::Signal(_) => "signal",
+            LiveEvent::DeviceStatus(_) => "device_status",
+        }
+    }
+
+    fn fields(&self) -> MessageFields {
+        let device_id = match self {
+            LiveEvent::PositionUpdate(update) => update.device_id,
+            LiveEvent::Signal(signal) => signal.device_id,
+            LiveEvent::DeviceStatus(status) => status.device_id,
+        };
+        let mut fields = MessageFields {
+            kind: self.kind().to_string(),
+            device_id: Some(device_id.to_string()),
+            ..Default::default()
+        };
+        if let LiveEvent::PositionUpdate(update) = self {
+            fields.icao = Some(update.icao.to_string());
+            fields.position = update.lat.zip(update.lon);
+            fields.altitude = update.altitude.map(i64::from);
+        }
+        fields
+    }
+}
+
 /// gRPC Gateway service implementation
 pub struct GatewayService {
-    db_writer: Arc<DbWriter>,
-    broadcast_tx: Arc<broadcast::Sender<String>>,
+    state: Arc<AppState>,
 }
 
 impl GatewayService {
-    pub fn new(
-        db_writer: Arc<DbWriter>,
-        broadcast_tx: Arc<broadcast::Sender<String>>,
-    ) -> Self {
-        Self {
-            db_writer,
-            broadcast_tx,
+    pub fn new(state: Arc<AppState>) -> Self {
+        Self { state }
+    }
+
+    /// Serialize an event once and broadcast it to all streaming clients
+    fn broadcast(&self, event: LiveEvent) {
+        if !self.state.pubsub.has_subscribers() {
+            return;
+        }
+        match LiveMessage::encode(&event, event.fields()) {
+            Ok(msg) => self.state.pubsub.publish(msg),
+            Err(e) => warn!("Failed to encode {} message: {}", event.kind(), e),
         }
     }
 
-    /// Broadcast a JSON message to all WebSocket clients
-    fn broadcast_json(&self, json: &str) {
-        if self.broadcast_tx.receiver_count() > 0 {
-            let _ = self.broadcast_tx.send(json.to_string());
+    /// Database, address class and route details for a position update
+    fn enrichment(
+        &self,
+        meta: Option<AircraftMeta>,
+        class: AddressClass,
+        callsign: &str,
+    ) -> Enrichment {
+        let meta = meta.unwrap_or_default();
+        let route = self.state.routes.lookup(callsign);
+        Enrichment {
+            registration: meta.registration,
+            aircraft_type: meta.aircraft_type,
+            model: meta.model,
+            operator: meta.operator,
+            military: class == AddressClass::Military,
+            special: class == AddressClass::Special,
+            origin: route.as_ref().map(|r| r.origin.clone()),
+            destination: route.map(|r| r.destination),
         }
     }
+
+    /// Run one aircraft event through privacy, sanity, merging, storage,
+    /// tracking and broadcast, as it arrives from a host or a Beast feed;
+    /// `false` when storing it failed
+    pub async fn ingest(&self, mut event: AircraftEvent, devices: &mut StreamDevices) -> bool {
+        let mut ok = true;
+        let received_at = chrono::Utc::now();
+        event.icao = api::normalize_icao(&event.icao);
+        devices.heard(&event.device_id, chrono::Utc::now());
+
+        // Privacy list: omitted aircraft go no further, coarsened
+        // ones lose their exact position before anything sees it
+        if !self.state.privacy.apply(&mut event, &self.state.aircraft_db) {
+            return true;
+        }
+
+        // Tracker timeouts only affect presence
+        self.state.presence.observe(&event, chrono::Utc::now());
+        // Drop values that can't be right before anything uses them
+        let rejected = self.state.sanity.apply(&mut event);
+        if !rejected.is_empty() {
+            match &event.provenance {
+                Some(p) => debug!(
+                    "Aircraft {} from {}: rejected {:?} (frame {}, {} bits corrected, confidence {:.2})",
+                    event.icao, event.device_id, rejected, p.raw_hex, p.corrected_bits, p.confidence
+                ),
+                None => debug!("Aircraft {} from {}: rejected {:?}", event.icao, event.device_id, rejected),
+            }
+        }
+        // The event with fields it lacks filled from earlier ones
+        let previous_squawk = self.state.merger.squawk(&event.icao);
+        let merged = self.state.merger.merge(&event);
+        if event.removed {
+            debug!("Aircraft {} timed out on {}", event.icao, event.device_id);
+            return true;
+        }
+
+        debug!(
+            "Aircraft: icao={}, pos={:?}, alt={:?}",
+            event.icao,
+            event.position(),
+            event.altitude_ft
+        );
+
+        // Store in database, at most once per write interval per aircraft.
+        // A position row is only written for events carrying a position.
+        // Non-ICAO addresses are live-only
+        let anonymous = api::is_anonymous(&event.icao);
+        let throttled = if anonymous {
+            None
+        } else {
+            self.state.position_throttle.record(&event.icao, std::time::Instant::now())
+        };
+        if let Some(messages) = throttled {
+            let stored = AircraftEvent {
+                latitude: event.latitude,
+                longitude: event.longitude,
+                ..merged.clone()
+            };
+            let time = self.state.event_time.pick(event.timestamp_ms, received_at);
+            if let Err(e) = self.state.db_writer.insert_position(&stored, time, messages).await {
+                warn!("Failed to insert position: {}; retrying", e);
+                self.state.dead_letters.submit(stored, time, messages, &e);
+                ok = false;
+            }
+        }
+
+        // Track flight sessions and receiver statistics
+        let callsign_change = if anonymous {
+            None
+        } else {
+            self.state.flights.observe(&event, chrono::Utc::now())
+        };
+        self.state.stats.record_event(&event, chrono::Utc::now());
+
+        // Check watchlist rules
+        let meta = self.state.aircraft_db.lookup(&event.icao);
+        let class = self.state.addresses.classify(&event.icao);
+        self.state.alerts.check(&event, meta.as_ref(), class);
+
+        // Geofence entry/exit (logged, so ICAO addresses only)
+        let transitions = if anonymous {
+            Vec::new()
+        } else {
+            self.state.geofences.observe(&event)
+        };
+        if !transitions.is_empty() {
+            self.state
+                .geofences
+                .publish(transitions, self.state.db_writer.as_ref())
+                .await;
+        }
+
+        // Squawk changes
+        if let Some(msg) = squawks::change_message(previous_squawk.as_deref(), &event) {
+            self.state.pubsub.publish(msg);
+        }
+
+        // Callsign changes start a new flight
+        if let Some(change) = callsign_change {
+            self.state.pubsub.publish(change.message(&event));
+        }
+
+        // Emergency squawks (logged, so ICAO addresses only)
+        let changes = if anonymous {
+            Vec::new()
+        } else {
+            self.state.emergencies.observe(&event, chrono::Utc::now())
+        };
+        if !changes.is_empty() {
+            self.state
+                .emergencies
+                .publish(changes, self.state.db_writer.as_ref())
+                .await;
+        }
+
+        // Broadcast to WebSocket clients
+        let (lat, lon) = match self.state.broadcast_position {
+            PositionSource::Filtered => merged.filtered_position().or(merged.position()),
+            PositionSource::Raw => merged.position(),
+        }
+        .unzip();
+        let update = LiveEvent::PositionUpdate(Box::new(PositionUpdate {
+            icao: &merged.icao,
+            device_id: &merged.device_id,
+            lat,
+            lon,
+            altitude: merged.altitude_ft,
+            speed: merged.speed_kts,
+            track: merged.track_deg,
+            magnetic_heading: merged.magnetic_heading_deg,
+            vrate: merged.vertical_rate_fpm,
+            baro_rate: merged.baro_rate_fpm,
+            geom_rate: merged.geom_rate_fpm,
+            callsign: merged.callsign.as_deref(),
+            squawk: merged.squawk.as_deref(),
+            category: merged.category.as_deref(),
+            version: merged.adsb_version,
+            nic: merged.nic,
+            nac_p: merged.nac_p,
+            nuc_p: merged.nuc_p,
+            rc: merged.containment_radius_m,
+            low_quality: self.state.sanity.flag_low_quality(&merged),
+            emergency: self.state.emergencies.active(&merged.icao).map(emergency_name),
+            timestamp_ms: merged.timestamp_ms,
+            provenance: event.provenance.as_ref().map(Provenance::from),
+            source: event.source.as_deref(),
+            enrichment: self.enrichment(
+                meta,
+                class,
+                merged.callsign.as_deref().unwrap_or_default(),
+            ),
+        }));
+        self.state.udp.send(&merged, || serde_json::to_string(&update).ok());
+        self.broadcast(update);
+
+        ok
+    }
 }
 
 #[tonic::async_trait]
 impl AdsbGateway for GatewayService {
+    type HeartbeatStream = Pin<Box<dyn Stream<Item = Result<HeartbeatPong, Status>> + Send>>;
+
     /// Receive aircraft events from host, store in DB and broadcast
     async fn stream_aircraft(
         &self,
@@ -49,6 +417,7 @@ impl AdsbGateway for GatewayService {
         info!("New aircraft stream from {}", peer);
 
         let mut stream = request.into_inner();
+        let mut devices = StreamDevices::new(self.state.receivers.clone());
         let mut count = 0u64;
         let mut errors = 0u64;
 
@@ -56,37 +425,10 @@ impl AdsbGateway for GatewayService {
             match result {
                 Ok(event) => {
                     count += 1;
-
-                    debug!(
-                        "Aircraft: icao={}, pos=({}, {}), alt={}",
-                        event.icao, event.latitude, event.longitude, event.altitude_ft
-                    );
-
-                    // Store in database
-                    if let Err(e) = self.db_writer.insert_position(&event).await {
-                        warn!("Failed to insert position: {}", e);
+                    if !self.ingest(event, &mut devices).await {
                         errors += 1;
                     }
 
-                    // Broadcast to WebSocket clients
-                    let ws_msg = serde_json::json!({
-                        "type": "position_update",
-                        "icao": event.icao,
-                        "device_id": event.device_id,
-                        "lat": event.latitude,
-                        "lon": event.longitude,
-                        "altitude": event.altitude_ft,
-                        "speed": event.speed_kts,
-                        "heading": event.heading_deg,
-                        "vrate": event.vertical_rate_fpm,
-                        "callsign": event.callsign,
-                        "squawk": event.squawk,
-                        "timestamp_ms": event.timestamp_ms,
-                    });
-                    if let Ok(json) = serde_json::to_string(&ws_msg) {
-                        self.broadcast_json(&json);
-                    }
-
                     // Log progress periodically
                     if count % 100 == 0 {
                         info!("Aircraft stream: received={}, errors={}", count, errors);
@@ -123,6 +465,7 @@ impl AdsbGateway for GatewayService {
         info!("New signal stream from {}", peer);
 
         let mut stream = request.into_inner();
+        let mut devices = StreamDevices::new(self.state.receivers.clone());
         let mut count = 0u64;
 
         while let Some(result) = stream.next().await {
@@ -135,27 +478,50 @@ impl AdsbGateway for GatewayService {
                         metrics.device_id, metrics.signal_dbfs, metrics.noise_dbfs, metrics.snr_db
                     );
 
+                    devices.heard(&metrics.device_id, chrono::Utc::now());
+
+                    // Decoder counters feed the statistics rollups
+                    self.state.stats.record_signal(&metrics, chrono::Utc::now());
+                    self.state.interrogators.record(&metrics);
+                    self.state
+                        .signal
+                        .push(&metrics, chrono::Utc::now().timestamp_millis());
+
                     // Broadcast to WebSocket clients (ephemeral - not stored)
-                    let ws_msg = serde_json::json!({
-                        "type": "signal",
-                        "device_id": metrics.device_id,
-                        "signal_dbfs": metrics.signal_dbfs,
-                        "noise_dbfs": metrics.noise_dbfs,
-                        "snr_db": metrics.snr_db,
-                        "msg_rate": metrics.msg_rate,
-                        "timestamp_ms": metrics.timestamp_ms,
-                        // Decoder statistics
-                        "preambles_detected": metrics.preambles_detected,
-                        "frames_decoded": metrics.frames_decoded,
-                        "crc_errors": metrics.crc_errors,
-                        "corrected_frames": metrics.corrected_frames,
-                        "samples_processed": metrics.samples_processed,
-                        "noise_floor": metrics.noise_floor,
-                        "peak_signal": metrics.peak_signal,
-                    });
-                    if let Ok(json) = serde_json::to_string(&ws_msg) {
-                        self.broadcast_json(&json);
-                    }
+                    self.broadcast(LiveEvent::Signal(SignalUpdate {
+                        device_id: &metrics.device_id,
+                        signal_dbfs: metrics.signal_dbfs,
+                        noise_dbfs: metrics.noise_dbfs,
+                        snr_db: metrics.snr_db,
+                        msg_rate: metrics.msg_rate,
+                        timestamp_ms: metrics.timestamp_ms,
+                        preambles_detected: metrics.preambles_detected,
+                        frames_decoded: metrics.frames_decoded,
+                        crc_errors: metrics.crc_errors,
+                        corrected_frames: metrics.corrected_frames,
+                        samples_processed: metrics.samples_processed,
+                        noise_floor: metrics.noise_floor,
+                        peak_signal: metrics.peak_signal,
+                        false_positive_rate: metrics.false_positive_rate,
+                        corrected_false_positive_rate: metrics.corrected_false_positive_rate,
+                        duplicate_frames: metrics.duplicate_frames,
+                        downlink_formats: metrics
+                            .downlink_formats
+                            .iter()
+                            .map(|c| {
+                                let count = FormatCount {
+                                    frames: c.frames,
+                                    corrected: c.corrected,
+                                };
+                                (c.downlink_format, count)
+                            })
+                            .collect(),
+                        type_codes: metrics
+                            .type_codes
+                            .iter()
+                            .map(|c| (c.type_code, c.frames))
+                            .collect(),
+                    }));
                 }
                 Err(e) => {
                     warn!("Signal stream error: {}", e);
@@ -184,6 +550,7 @@ impl AdsbGateway for GatewayService {
         info!("New device status stream from {}", peer);
 
         let mut stream = request.into_inner();
+        let mut devices = StreamDevices::new(self.state.receivers.clone());
         let mut count = 0u64;
 
         while let Some(result) = stream.next().await {
@@ -196,24 +563,30 @@ impl AdsbGateway for GatewayService {
                         status.device_id, status.connected, status.center_freq, status.gain_db
                     );
 
-                    // Store in database
-                    if let Err(e) = self.db_writer.update_sdr_status(&status).await {
+                    devices.heard(&status.device_id, chrono::Utc::now());
+                    self.state
+                        .receivers
+                        .status(&status.device_id, status.connected, chrono::Utc::now());
+
+                    // Store in database, with the rate of events received from the device
+                    let rate = self.state.stats.device_rate(&status.device_id, chrono::Utc::now());
+                    if let Err(e) = self.state.db_writer.update_sdr_status(&status, rate).await {
                         warn!("Failed to update SDR status: {}", e);
                     }
 
                     // Broadcast to WebSocket clients
-                    let ws_msg = serde_json::json!({
-                        "type": "device_status",
-                        "device_id": status.device_id,
-                        "connected": status.connected,
-                        "sample_rate": status.sample_rate,
-                        "center_freq": status.center_freq,
-                        "gain_db": status.gain_db,
-                        "timestamp_ms": status.timestamp_ms,
-                    });
-                    if let Ok(json) = serde_json::to_string(&ws_msg) {
-                        self.broadcast_json(&json);
-                    }
+                    self.broadcast(LiveEvent::DeviceStatus(DeviceStatusUpdate {
+                        device_id: &status.device_id,
+                        connected: status.connected,
+                        sample_rate: status.sample_rate,
+                        center_freq: status.center_freq,
+                        gain_db: status.gain_db,
+                        timestamp_ms: status.timestamp_ms,
+                        software_version: decoder_config::software_version(&status),
+                        uptime_secs: status.uptime_secs,
+                        decoder: status.decoder.as_ref().map(DecoderSettings::from),
+                        clock_offset_ms: status.clock_offset_ms,
+                    }));
                 }
                 Err(e) => {
                     warn!("Device status stream error: {}", e);
@@ -229,4 +602,177 @@ impl AdsbGateway for GatewayService {
             messages_received: count,
         }))
     }
+
+    /// Receive raw frames from host and hand them to the archiver
+    async fn stream_raw_frames(
+        &self,
+        request: Request<Streaming<RawFrame>>,
+    ) -> Result<Response<StreamAck>, Status> {
+        let peer = request
+            .remote_addr()
+            .map(|a| a.to_string())
+            .unwrap_or_else(|| "unknown".to_string());
+        info!(
+            "New raw frame stream from {} (archival {})",
+            peer,
+            if self.state.raw_archive.is_enabled() { "enabled" } else { "disabled" }
+        );
+
+        let mut stream = request.into_inner();
+        let mut count = 0u64;
+
+        while let Some(result) = stream.next().await {
+            match result {
+                Ok(mut frame) => {
+                    count += 1;
+                    if self.state.privacy.withholds_frame(&frame.data) {
+                        continue;
+                    }
+                    // Archived at the time the configured clock gives it
+                    let time = self.state.event_time.pick(frame.timestamp_ms, chrono::Utc::now());
+                    frame.timestamp_ms = time.timestamp_millis() as u64;
+                    self.state.raw_archive.submit(frame);
+                }
+                Err(e) => {
+                    warn!("Raw frame stream error: {}", e);
+                }
+            }
+        }
+
+        info!("Raw frame stream from {} ended: received={}", peer, count);
+
+        Ok(Response::new(StreamAck {
+            success: true,
+            message: format!("Received {} raw frames", count),
+            messages_received: count,
+        }))
+    }
+
+    /// Keep a host's diagnostic frequency scan for the REST API
+    async fn report_frequency_scan(
+        &self,
+        request: Request<FrequencyScan>,
+    ) -> Result<Response<StreamAck>, Status> {
+        let scan = request.into_inner();
+        info!(
+            "Frequency scan from {}: {} frequencies",
+            scan.device_id,
+            scan.points.len()
+        );
+        self.state.scans.record(&scan);
+
+        Ok(Response::new(StreamAck {
+            success: true,
+            message: format!("Received scan of {} frequencies", scan.points.len()),
+            messages_received: scan.points.len() as u64,
+        }))
+    }
+
+    /// Answer a host's clock sync ping with the gateway's time
+    async fn ping(&self, request: Request<PingRequest>) -> Result<Response<PingResponse>, Status> {
+        let ping = request.into_inner();
+        debug!("Clock sync ping from {}", ping.device_id);
+
+        Ok(Response::new(PingResponse {
+            host_time_ms: ping.host_time_ms,
+            gateway_time_ms: chrono::Utc::now().timestamp_millis() as u64,
+        }))
+    }
+
+    async fn heartbeat(
+        &self,
+        request: Request<Streaming<HeartbeatPing>>,
+    ) -> Result<Response<Self::HeartbeatStream>, Status> {
+        let mut pings = request.into_inner();
+        let receivers = self.state.receivers.clone();
+        let (tx, rx) = mpsc::channel(4);
+        tokio::spawn(async move {
+            let mut devices = StreamDevices::new(receivers.clone());
+            while let Some(Ok(ping)) = pings.next().await {
+                devices.heard(&ping.device_id, chrono::Utc::now());
+                if let Some(rtt_ms) = ping.rtt_ms {
+                    receivers.round_trip(&ping.device_id, rtt_ms);
+                }
+                let pong = HeartbeatPong {
+                    sequence: ping.sequence,
+                    host_time_ms: ping.host_time_ms,
+                    gateway_time_ms: chrono::Utc::now().timestamp_millis() as u64,
+                };
+                if tx.send(Ok(pong)).await.is_err() {
+                    break;
+                }
+            }
+        });
+        Ok(Response::new(Box::pin(ReceiverStream::new(rx))))
+    }
+
+    async fn get_aircraft_labels(
+        &self,
+        request: Request<AircraftLabelsRequest>,
+    ) -> Result<Response<AircraftLabels>, Status> {
+        let labels = self.state.aircraft_cache.labels(chrono::Utc::now());
+        debug!("Sending {} aircraft labels to {}", labels.len(), request.into_inner().device_id);
+        Ok(Response::new(AircraftLabels { labels }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_position_update_message() {
+        let frame = FrameProvenance {
+            raw_hex: "8D71BE1158C382D690C8AC2863A7".into(),
+            downlink_format: 17,
+            type_code: 11,
+            corrected_bits: 2,
+            confidence: 0.8,
+            signal_level: 120,
+        };
+        let event = LiveEvent::PositionUpdate(Box::new(PositionUpdate {
+            icao: "71BE11",
+            device_id: "rtlsdr-0",
+            lat: Some(37.46),
+            lon: Some(126.44),
+            altitude: Some(35000),
+            speed: None,
+            track: None,
+            magnetic_heading: None,
+            vrate: Some(0),
+            baro_rate: Some(0),
+            geom_rate: None,
+            callsign: Some("KAL123"),
+            squawk: Some("7600"),
+            category: None,
+            version: Some(2),
+            nic: Some(8),
+            nac_p: Some(9),
+            nuc_p: None,
+            rc: Some(185.2),
+            low_quality: false,
+            emergency: Some("radio_failure"),
+            timestamp_ms: 1705312800000,
+            provenance: Some(Provenance::from(&frame)),
+            source: None,
+            enrichment: Enrichment {
+                registration: Some("HL7611".into()),
+                ..Default::default()
+            },
+        }));
+        let msg = LiveMessage::encode(&event, event.fields()).unwrap();
+        let json: serde_json::Value = serde_json::from_str(&msg.json).unwrap();
+        assert_eq!(json["type"], "position_update");
+        assert_eq!(json["icao"], "71BE11");
+        assert_eq!(json["registration"], "HL7611");
+        assert!(json.get("origin").is_none());
+        assert_eq!(json["provenance"]["raw"], "8D71BE1158C382D690C8AC2863A7");
+        assert_eq!(json["provenance"]["corrected_bits"], 2);
+        // Unknown fields are left out rather than sent as zero
+        assert!(json.get("speed").is_none());
+        assert!(json.get("source").is_none());
+
+        // The filter fields match what parsing the JSON would give
+        assert_eq!(msg, LiveMessage::parse(&msg.json));
+    }
 }
@@ -0,0 +1,362 @@
+//! Historical position queries
+//!
+//! `/api/history/positions` pages through stored positions in
+//! `(time, icao)` order using an opaque keyset cursor, so clients can walk
+//! arbitrarily long ranges without OFFSET scans or unbounded responses.
+//! Each page can be thinned by keeping every Nth point or by
+//! Douglas-Peucker simplification; the cursor always refers to the last
+//! raw row, so decimation never causes gaps between pages.
+
+use crate::geo::haversine_km;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+/// Default page size
+pub const DEFAULT_LIMIT: i64 = 1000;
+
+/// Largest page a client may request
+pub const MAX_LIMIT: i64 = 10_000;
+
+/// Default range when `from` is omitted
+const DEFAULT_RANGE_HOURS: i64 = 1;
+
+/// Query parameters for the history endpoint
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct HistoryParams {
+    pub icao: Option<String>,
+    /// RFC 3339 start (inclusive); defaults to one hour before `to`
+    pub from: Option<String>,
+    /// RFC 3339 end (exclusive); defaults to now
+    pub to: Option<String>,
+    pub limit: Option<i64>,
+    pub cursor: Option<String>,
+    /// Keep every Nth point
+    pub every: Option<usize>,
+    /// Douglas-Peucker tolerance in metres
+    pub simplify_m: Option<f64>,
+    /// `json` (default), `geojson`, `kml` or `gpx`
+    pub format: Option<String>,
+}
+
+/// A validated history query as passed to storage
+#[derive(Debug, Clone)]
+pub struct HistoryQuery {
+    pub icao: Option<String>,
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    /// Resume strictly after this `(time, icao)`
+    pub after: Option<Cursor>,
+    /// Rows to fetch (one more than the page size, to detect further pages)
+    pub fetch: i64,
+    /// Only rows from these receivers; `None` for all
+    pub devices: Option<Vec<String>>,
+}
+
+/// Keyset position: the last row of the previous page
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cursor {
+    pub time: DateTime<Utc>,
+    pub icao: String,
+}
+
+impl Cursor {
+    /// Opaque `<unix micros>.<icao>` token
+    pub fn encode(&self) -> String {
+        format!("{}.{}", self.time.timestamp_micros(), self.icao)
+    }
+
+    pub fn decode(s: &str) -> Result<Self, String> {
+        let (micros, icao) = s.split_once('.').ok_or("invalid cursor")?;
+        let time = micros
+            .parse::<i64>()
+            .ok()
+            .and_then(DateTime::from_timestamp_micros)
+            .ok_or("invalid cursor")?;
+        if icao.is_empty() || !icao.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err("invalid cursor".into());
+        }
+        Ok(Self {
+            time,
+            icao: icao.to_string(),
+        })
+    }
+}
+
+/// A stored position
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct PositionPoint {
+    pub time: DateTime<Utc>,
+    pub icao: String,
+    pub lat: f64,
+    pub lon: f64,
+    pub altitude: Option<i32>,
+    pub speed: Option<f32>,
+    /// Ground track, degrees true
+    pub track: Option<f32>,
+    /// Heading, degrees magnetic
+    pub magnetic_heading: Option<f32>,
+    pub vrate: Option<i32>,
+    /// Vertical rate by source: barometric and GNSS (geometric)
+    pub baro_rate: Option<i32>,
+    pub geom_rate: Option<i32>,
+    pub squawk: Option<String>,
+    pub device_id: Option<String>,
+}
+
+/// How a page is thinned
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Decimation {
+    None,
+    Every(usize),
+    Simplify { tolerance_m: f64 },
+}
+
+impl HistoryParams {
+    /// Validate into a storage query, page size and decimation
+    pub fn parse(&self, now: DateTime<Utc>) -> Result<(HistoryQuery, i64, Decimation), String> {
+        let (from, to) = parse_range(
+            self.from.as_deref(),
+            self.to.as_deref(),
+            now,
+            chrono::Duration::hours(DEFAULT_RANGE_HOURS),
+        )?;
+
+        let icao = match self.icao.as_deref().map(str::trim) {
+            Some(icao) if !icao.is_empty() => Some(crate::api::parse_icao(icao)?),
+            _ => None,
+        };
+
+        let decimation = match (self.every, self.simplify_m) {
+            (Some(_), Some(_)) => return Err("use either every or simplify_m, not both".into()),
+            (Some(0), None) => return Err("every must be at least 1".into()),
+            (Some(n), None) => Decimation::Every(n),
+            (None, Some(m)) => {
+                if icao.is_none() {
+                    return Err("simplify_m requires icao".into());
+                }
+                if m.is_nan() || m <= 0.0 {
+                    return Err("simplify_m must be positive".into());
+                }
+                Decimation::Simplify { tolerance_m: m }
+            }
+            (None, None) => Decimation::None,
+        };
+
+        let limit = self.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+        let after = self.cursor.as_deref().map(Cursor::decode).transpose()?;
+
+        Ok((
+            HistoryQuery {
+                icao,
+                from,
+                to,
+                after,
+                fetch: limit + 1,
+                devices: None,
+            },
+            limit,
+            decimation,
+        ))
+    }
+}
+
+/// Parse an RFC 3339 `from`/`to` pair; `to` defaults to `now` and `from` to
+/// `default_range` before `to`
+pub fn parse_range(
+    from: Option<&str>,
+    to: Option<&str>,
+    now: DateTime<Utc>,
+    default_range: chrono::Duration,
+) -> Result<(DateTime<Utc>, DateTime<Utc>), String> {
+    let parse_time = |name: &str, value: &str| {
+        DateTime::parse_from_rfc3339(value)
+            .map(|t| t.with_timezone(&Utc))
+            .map_err(|_| format!("{} must be an RFC 3339 timestamp", name))
+    };
+    let to = match to {
+        Some(to) => parse_time("to", to)?,
+        None => now,
+    };
+    let from = match from {
+        Some(from) => parse_time("from", from)?,
+        None => to - default_range,
+    };
+    if from >= to {
+        return Err("from must be before to".into());
+    }
+    Ok((from, to))
+}
+
+/// Split fetched rows into a page and the cursor for the next one
+pub fn paginate(mut rows: Vec<PositionPoint>, limit: i64) -> (Vec<PositionPoint>, Option<Cursor>) {
+    let limit = limit as usize;
+    if rows.len() <= limit {
+        return (rows, None);
+    }
+    rows.truncate(limit);
+    let cursor = rows.last().map(|p| Cursor {
+        time: p.time,
+        icao: p.icao.clone(),
+    });
+    (rows, cursor)
+}
+
+/// Thin a page, always keeping its first and last point
+pub fn decimate(points: Vec<PositionPoint>, decimation: Decimation) -> Vec<PositionPoint> {
+    match decimation {
+        Decimation::None => points,
+        Decimation::Every(n) => {
+            let last = points.len().saturating_sub(1);
+            points
+                .into_iter()
+                .enumerate()
+                .filter(|(i, _)| i % n == 0 || *i == last)
+                .map(|(_, p)| p)
+                .collect()
+        }
+        Decimation::Simplify { tolerance_m } => {
+            let keep = douglas_peucker(&points, tolerance_m / 1000.0);
+            points
+                .into_iter()
+                .zip(keep)
+                .filter(|(_, k)| *k)
+                .map(|(p, _)| p)
+                .collect()
+        }
+    }
+}
+
+/// Which points Douglas-Peucker keeps (iterative, so long tracks can't overflow the stack)
+fn douglas_peucker(points: &[PositionPoint], tolerance_km: f64) -> Vec<bool> {
+    let mut keep = vec![false; points.len()];
+    if points.is_empty() {
+        return keep;
+    }
+    keep[0] = true;
+    keep[points.len() - 1] = true;
+
+    let mut stack = vec![(0, points.len() - 1)];
+    while let Some((first, last)) = stack.pop() {
+        if last <= first + 1 {
+            continue;
+        }
+        let (mut max_dist, mut index) = (0.0, first);
+        for i in first + 1..last {
+            let d = cross_track_km(&points[i], &points[first], &points[last]);
+            if d > max_dist {
+                max_dist = d;
+                index = i;
+            }
+        }
+        if max_dist > tolerance_km {
+            keep[index] = true;
+            stack.push((first, index));
+            stack.push((index, last));
+        }
+    }
+    keep
+}
+
+/// Distance from `p` to the segment `a`-`b` on a local flat projection
+fn cross_track_km(p: &PositionPoint, a: &PositionPoint, b: &PositionPoint) -> f64 {
+    let cos = a.lat.to_radians().cos();
+    let to_xy = |q: &PositionPoint| ((q.lon - a.lon) * cos * 111.32, (q.lat - a.lat) * 111.32);
+    let (px, py) = to_xy(p);
+    let (bx, by) = to_xy(b);
+    let len2 = bx * bx + by * by;
+    if len2 == 0.0 {
+        return haversine_km(a.lat, a.lon, p.lat, p.lon);
+    }
+    let t = ((px * bx + py * by) / len2).clamp(0.0, 1.0);
+    ((px - t * bx).powi(2) + (py - t * by).powi(2)).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(secs: i64, lat: f64, lon: f64) -> PositionPoint {
+        PositionPoint {
+            time: DateTime::from_timestamp(1_700_000_000 + secs, 0).unwrap(),
+            icao: "71BE11".into(),
+            lat,
+            lon,
+            altitude: None,
+            speed: None,
+            track: None,
+            magnetic_heading: None,
+            vrate: None,
+            baro_rate: None,
+            geom_rate: None,
+            squawk: None,
+            device_id: None,
+        }
+    }
+
+    #[test]
+    fn test_cursor_roundtrip() {
+        let cursor = Cursor {
+            time: DateTime::from_timestamp_micros(1_700_000_000_123_456).unwrap(),
+            icao: "71BE11".into(),
+        };
+        assert_eq!(Cursor::decode(&cursor.encode()).unwrap(), cursor);
+        assert!(Cursor::decode("garbage").is_err());
+        assert!(Cursor::decode("123.'; DROP").is_err());
+    }
+
+    #[test]
+    fn test_paginate() {
+        let rows: Vec<_> = (0..4).map(|i| point(i, 37.0, 126.0)).collect();
+        let (page, cursor) = paginate(rows.clone(), 3);
+        assert_eq!(page.len(), 3);
+        assert_eq!(cursor.unwrap().time, rows[2].time);
+
+        let (page, cursor) = paginate(rows, 4);
+        assert_eq!(page.len(), 4);
+        assert!(cursor.is_none());
+    }
+
+    #[test]
+    fn test_decimate_every() {
+        let rows: Vec<_> = (0..10).map(|i| point(i, 37.0, 126.0)).collect();
+        let kept = decimate(rows, Decimation::Every(4));
+        let secs: Vec<i64> = kept.iter().map(|p| p.time.timestamp() - 1_700_000_000).collect();
+        assert_eq!(secs, vec![0, 4, 8, 9]);
+    }
+
+    #[test]
+    fn test_simplify() {
+        // Straight line with one 5 km detour
+        let mut rows: Vec<_> = (0..10).map(|i| point(i, 37.0, 126.0 + i as f64 * 0.01)).collect();
+        rows[5].lat += 0.045;
+        let kept = decimate(rows, Decimation::Simplify { tolerance_m: 500.0 });
+        assert_eq!(kept.len(), 5);
+        assert!(kept.iter().any(|p| p.lat > 37.0));
+    }
+
+    #[test]
+    fn test_parse_params() {
+        let now = Utc::now();
+        let params = HistoryParams {
+            icao: Some("71be11".into()),
+            from: Some("2024-01-15T10:00:00+09:00".into()),
+            to: Some("2024-01-15T12:00:00+09:00".into()),
+            every: Some(2),
+            ..Default::default()
+        };
+        let (query, limit, decimation) = params.parse(now).unwrap();
+        assert_eq!(query.icao.as_deref(), Some("71BE11"));
+        assert_eq!(query.from.to_rfc3339(), "2024-01-15T01:00:00+00:00");
+        assert_eq!(query.fetch, limit + 1);
+        assert_eq!(decimation, Decimation::Every(2));
+
+        let backwards = HistoryParams {
+            from: Some("2024-01-15T12:00:00Z".into()),
+            to: Some("2024-01-15T10:00:00Z".into()),
+            ..Default::default()
+        };
+        assert!(backwards.parse(now).is_err());
+    }
+}
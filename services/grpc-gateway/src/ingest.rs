@@ -0,0 +1,206 @@
+//! JSON position ingestion
+//!
+//! `POST /api/ingest/positions` lets sources without a gRPC client (scripts,
+//! other trackers, AIS-style bridges) contribute positions. A batch of up
+//! to 1000 reports goes through the same pipeline as a host's events as
+//! device `api-<credential name>`, with `source` set to `api` on its
+//! position updates. It needs a `feeder` or `admin` credential and is
+//! refused while the API is open. Reports that fail validation are
+//! returned with their index and reason; the rest are accepted.
+
+use crate::adsb::AircraftEvent;
+use crate::api::{parse_icao, ApiError};
+use crate::auth::Principal;
+use crate::grpc_server::GatewayService;
+use crate::uptime::StreamDevices;
+use crate::AppState;
+use axum::{extract::State, response::IntoResponse, Extension, Json};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tracing::debug;
+use utoipa::ToSchema;
+
+/// Paths under this prefix need a feeder or admin credential
+pub const PREFIX: &str = "/api/ingest";
+
+/// Event source of ingested positions
+pub const SOURCE: &str = "api";
+
+/// Most reports in one batch
+const MAX_REPORTS: usize = 1000;
+
+/// Reports timestamped further ahead than this are refused
+const MAX_FUTURE_MS: u64 = 60_000;
+
+/// One aircraft position
+#[derive(Debug, Clone, Default, Deserialize, ToSchema)]
+pub struct PositionReport {
+    /// ICAO address, 6 hex digits
+    pub icao: String,
+    /// Unix milliseconds; default: time received
+    pub timestamp_ms: Option<u64>,
+    pub lat: f64,
+    pub lon: f64,
+    /// Feet
+    pub altitude_ft: Option<i32>,
+    /// Ground speed, knots
+    pub speed_kts: Option<f32>,
+    /// Degrees true
+    pub track_deg: Option<f32>,
+    /// Feet per minute, positive climbing
+    pub vertical_rate_fpm: Option<i32>,
+    pub callsign: Option<String>,
+    /// Four octal digits
+    pub squawk: Option<String>,
+    /// Emitter category, e.g. `A3`
+    pub category: Option<String>,
+}
+
+/// Body of `POST /api/ingest/positions`
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct PositionBatch {
+    pub reports: Vec<PositionReport>,
+}
+
+/// A report that wasn't accepted
+#[derive(Debug, PartialEq, Serialize, ToSchema)]
+pub struct RejectedReport {
+    /// Position in `reports`
+    pub index: usize,
+    pub error: String,
+}
+
+/// What happened to a batch
+#[derive(Debug, Serialize, ToSchema)]
+pub struct IngestResult {
+    pub accepted: usize,
+    pub rejected: Vec<RejectedReport>,
+}
+
+impl PositionReport {
+    /// Event for a valid report, from `device_id`, received at `now_ms`
+    pub fn to_event(&self, device_id: &str, now_ms: u64) -> Result<AircraftEvent, String> {
+        let icao = parse_icao(&self.icao)?;
+        if !(-90.0..=90.0).contains(&self.lat) || !(-180.0..=180.0).contains(&self.lon) {
+            return Err("lat/lon out of range".into());
+        }
+        let timestamp_ms = self.timestamp_ms.unwrap_or(now_ms);
+        if timestamp_ms > now_ms + MAX_FUTURE_MS {
+            return Err("timestamp_ms is in the future".into());
+        }
+        if let Some(track) = self.track_deg {
+            if !(0.0..360.0).contains(&track) {
+                return Err("track_deg must be 0-360".into());
+            }
+        }
+        if self.speed_kts.is_some_and(|s| s < 0.0) {
+            return Err("speed_kts must not be negative".into());
+        }
+        let callsign = self.callsign.as_deref().map(str::trim).filter(|c| !c.is_empty());
+        if callsign.is_some_and(|c| c.len() > 8 || !c.chars().all(|ch| ch.is_ascii_alphanumeric())) {
+            return Err("callsign must be up to 8 letters and digits".into());
+        }
+        if let Some(squawk) = &self.squawk {
+            if squawk.len() != 4 || !squawk.chars().all(|c| ('0'..='7').contains(&c)) {
+                return Err("squawk must be 4 octal digits".into());
+            }
+        }
+        Ok(AircraftEvent {
+            device_id: device_id.to_string(),
+            timestamp_ms,
+            icao,
+            callsign: callsign.map(str::to_ascii_uppercase),
+            altitude_ft: self.altitude_ft,
+            latitude: Some(self.lat),
+            longitude: Some(self.lon),
+            speed_kts: self.speed_kts,
+            track_deg: self.track_deg,
+            vertical_rate_fpm: self.vertical_rate_fpm,
+            squawk: self.squawk.clone(),
+            category: self.category.clone(),
+            source: Some(SOURCE.to_string()),
+            ..Default::default()
+        })
+    }
+}
+
+/// Contribute a batch of positions
+#[utoipa::path(
+    post,
+    path = "/api/ingest/positions",
+    tag = "ingest",
+    request_body = PositionBatch,
+    responses(
+        (status = 200, description = "Reports accepted and rejected", body = IngestResult),
+        (status = 400, description = "Empty or oversized batch", body = ApiError),
+        (status = 403, description = "Not a feeder or admin credential, or the API is open"),
+    ),
+    security(("api_key" = []), ("bearer" = []))
+)]
+pub async fn ingest_positions(
+    State(state): State<Arc<AppState>>,
+    Extension(principal): Extension<Principal>,
+    Json(batch): Json<PositionBatch>,
+) -> impl IntoResponse {
+    if batch.reports.is_empty() || batch.reports.len() > MAX_REPORTS {
+        return ApiError::bad_request(format!("reports must hold 1 to {} positions", MAX_REPORTS));
+    }
+    let device_id = format!("api-{}", principal.name);
+    let now_ms = chrono::Utc::now().timestamp_millis() as u64;
+    let service = GatewayService::new(state.clone());
+    let mut devices = StreamDevices::unattached(state.receivers.clone());
+    let mut result = IngestResult {
+        accepted: 0,
+        rejected: Vec::new(),
+    };
+    for (index, report) in batch.reports.iter().enumerate() {
+        match report.to_event(&device_id, now_ms) {
+            Ok(event) => {
+                service.ingest(event, &mut devices).await;
+                result.accepted += 1;
+            }
+            Err(error) => result.rejected.push(RejectedReport { index, error }),
+        }
+    }
+    debug!(
+        "Ingested {} positions from {}, rejected {}",
+        result.accepted,
+        device_id,
+        result.rejected.len()
+    );
+    Json(result).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_position_report() {
+        let report = PositionReport {
+            icao: "71be11".into(),
+            lat: 37.4602,
+            lon: 126.4407,
+            altitude_ft: Some(3500),
+            callsign: Some("kal123 ".into()),
+            squawk: Some("7700".into()),
+            ..Default::default()
+        };
+        let event = report.to_event("api-tracker", 1_000).unwrap();
+        assert_eq!((event.icao.as_str(), event.timestamp_ms), ("71BE11", 1_000));
+        assert_eq!(event.callsign.as_deref(), Some("KAL123"));
+        assert_eq!(event.position(), Some((37.4602, 126.4407)));
+        assert_eq!(event.source.as_deref(), Some("api"));
+
+        let bad = |f: fn(&mut PositionReport)| {
+            let mut report = report.clone();
+            f(&mut report);
+            report.to_event("api-tracker", 1_000).unwrap_err()
+        };
+        assert_eq!(bad(|r| r.icao = "~1234".into()), "icao must be 6 hex digits");
+        assert_eq!(bad(|r| r.lat = 91.0), "lat/lon out of range");
+        assert_eq!(bad(|r| r.timestamp_ms = Some(120_000)), "timestamp_ms is in the future");
+        assert_eq!(bad(|r| r.squawk = Some("7800".into())), "squawk must be 4 octal digits");
+        assert_eq!(bad(|r| r.callsign = Some("KAL-123".into())), "callsign must be up to 8 letters and digits");
+    }
+}
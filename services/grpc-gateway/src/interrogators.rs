@@ -0,0 +1,160 @@
+//! Interrogator code statistics
+//!
+//! Capture hosts count the interrogator codes overlaid on DF11 all-call
+//! replies and include the totals in their signal metrics. The latest
+//! counts per device are kept in memory and served by
+//! `/api/sdr/{device_id}/interrogators`: which secondary surveillance radars
+//! interrogate aircraft in the receiver's area, how often, and how many
+//! aircraft answer each, for understanding local SSR coverage and picking
+//! MLAT sync candidates.
+
+use crate::adsb::SignalMetrics;
+use crate::api::ApiError;
+use crate::tenants::Scope;
+use crate::AppState;
+use axum::{
+    extract::{Path, State},
+    response::IntoResponse,
+    Json,
+};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use utoipa::ToSchema;
+
+/// Replies to one interrogator code
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct InterrogatorActivity {
+    /// `II` (interrogator identifier, 0-15) or `SI` (surveillance
+    /// identifier, 1-63). II 0 also covers acquisition squitters
+    pub kind: &'static str,
+    pub code: u32,
+    pub replies: u64,
+    /// Distinct aircraft that replied
+    pub aircraft: u32,
+    /// Unix milliseconds
+    pub last_reply_ms: u64,
+}
+
+/// A device's interrogator counts since it started
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct InterrogatorReport {
+    pub device_id: String,
+    /// When the device sent the counts (Unix milliseconds)
+    pub timestamp_ms: u64,
+    /// Most replies first
+    pub interrogators: Vec<InterrogatorActivity>,
+}
+
+impl From<&SignalMetrics> for InterrogatorReport {
+    fn from(metrics: &SignalMetrics) -> Self {
+        let mut interrogators: Vec<_> = metrics
+            .interrogators
+            .iter()
+            .map(|count| InterrogatorActivity {
+                kind: if count.surveillance { "SI" } else { "II" },
+                code: count.code,
+                replies: count.replies,
+                aircraft: count.aircraft,
+                last_reply_ms: count.last_reply_ms,
+            })
+            .collect();
+        interrogators.sort_by_key(|a| std::cmp::Reverse(a.replies));
+        Self {
+            device_id: metrics.device_id.clone(),
+            timestamp_ms: metrics.timestamp_ms,
+            interrogators,
+        }
+    }
+}
+
+/// Latest interrogator counts per device
+#[derive(Default)]
+pub struct InterrogatorStore {
+    reports: Mutex<HashMap<String, InterrogatorReport>>,
+}
+
+impl InterrogatorStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keep the counts in `metrics`, if it has any, replacing the device's
+    /// previous ones
+    pub fn record(&self, metrics: &SignalMetrics) {
+        if metrics.interrogators.is_empty() {
+            return;
+        }
+        if let Ok(mut reports) = self.reports.lock() {
+            reports.insert(metrics.device_id.clone(), InterrogatorReport::from(metrics));
+        }
+    }
+
+    pub fn get(&self, device_id: &str) -> Option<InterrogatorReport> {
+        self.reports.lock().ok()?.get(device_id).cloned()
+    }
+}
+
+/// Get the interrogator codes a device has seen in DF11 replies
+#[utoipa::path(
+    get,
+    path = "/api/sdr/{device_id}/interrogators",
+    tag = "receiver",
+    params(("device_id" = String, Path, description = "Capture device ID")),
+    responses(
+        (status = 200, description = "Interrogator counts", body = InterrogatorReport),
+        (status = 404, description = "Device has not reported DF11 replies since the gateway started", body = ApiError),
+    ),
+    security((), ("api_key" = []), ("bearer" = []))
+)]
+pub async fn get_sdr_interrogators(
+    State(state): State<Arc<AppState>>,
+    scope: Scope,
+    Path(device_id): Path<String>,
+) -> impl IntoResponse {
+    match state.interrogators.get(&device_id).filter(|_| scope.allows_device(&device_id)) {
+        Some(report) => Json(report).into_response(),
+        None => ApiError::not_found("no interrogator counts reported for device"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adsb::InterrogatorCount;
+
+    #[test]
+    fn test_latest_counts_per_device() {
+        let store = InterrogatorStore::new();
+        let metrics = |interrogators| SignalMetrics {
+            device_id: "rtlsdr-0".into(),
+            interrogators,
+            ..Default::default()
+        };
+
+        // Signal reports without DF11 replies don't replace anything
+        store.record(&metrics(vec![]));
+        assert!(store.get("rtlsdr-0").is_none());
+
+        store.record(&metrics(vec![
+            InterrogatorCount {
+                code: 3,
+                replies: 10,
+                ..Default::default()
+            },
+            InterrogatorCount {
+                surveillance: true,
+                code: 18,
+                replies: 250,
+                aircraft: 12,
+                ..Default::default()
+            },
+        ]));
+        store.record(&metrics(vec![]));
+        let report = store.get("rtlsdr-0").unwrap();
+        assert_eq!(report.interrogators.len(), 2);
+        assert_eq!((report.interrogators[0].kind, report.interrogators[0].code), ("SI", 18));
+        assert_eq!(report.interrogators[1].kind, "II");
+        assert!(store.get("rtlsdr-1").is_none());
+    }
+}
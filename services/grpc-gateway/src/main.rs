@@ -1,26 +1,139 @@
 //! gRPC Gateway - receives streams from host and routes to WebSocket/DB
 
-use anyhow::Result;
+use anyhow::{Context, Result};
+use futures_util::StreamExt;
 use axum::{
     extract::{Path, Query, State, WebSocketUpgrade},
+    http::{header, StatusCode},
     response::IntoResponse,
-    routing::get,
+    middleware,
+    routing::{delete, get, post},
     Json, Router,
 };
+use chrono::{DateTime, Utc};
 use std::sync::Arc;
-use tokio::sync::broadcast;
 use tonic::transport::Server;
+use tower_http::compression::predicate::{DefaultPredicate, NotForContentType, Predicate};
+use tower_http::compression::CompressionLayer;
 use tower_http::cors::{Any, CorsLayer};
 use tower_http::services::ServeDir;
 use tracing::{error, info};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+use utoipa::{IntoParams, OpenApi};
+use utoipa_swagger_ui::SwaggerUi;
 
+mod admin;
+mod aircraft_cache;
+mod aircraft_db;
+mod alerts;
+mod api;
+mod assets;
+mod auth;
+mod aircraft_db_refresh;
+mod beast;
+mod clickhouse_writer;
+mod coalesce;
+mod config;
+mod connections;
+mod coverage;
+mod db_health;
+mod db_schema;
 mod db_writer;
+mod dead_letter;
+mod decoder_config;
+mod delta;
+mod emergencies;
+mod export;
+mod fanout;
+mod feeders;
+mod filters;
+mod flights;
+mod geo;
+mod geofences;
+mod graphql;
 mod grpc_server;
+mod history;
+mod ingest;
+mod interrogators;
+mod merge;
+mod metrics;
+mod military;
+mod notifiers;
+mod ogn;
+mod presence;
+mod privacy;
+mod public;
+mod pubsub;
+mod raw_archive;
+mod recording;
+mod response_cache;
+mod routes;
+mod sanity;
+mod scan;
+mod schema;
+mod share;
+mod signal;
+mod sqlite_writer;
+mod squawks;
+mod sse;
+mod stats;
+mod storage;
+mod tar1090;
+mod tenants;
+mod tiles;
+mod udp;
+mod units;
+mod uptime;
+mod write_policy;
 mod ws_handler;
 
-use db_writer::DbWriter;
+use aircraft_cache::AircraftCache;
+use aircraft_db::AircraftDb;
+use alerts::{AlertEngine, NewWatchRule, WatchRule};
+use api::{
+    Aircraft, AircraftDetail, ApiDoc, ApiError, Emergency, Flight, GeofenceEvent, HistoryPage,
+    SdrHeartbeat, SdrStatus, StatsResponse, TrailPoint,
+};
+use auth::Auth;
+use config::{Config, EventTime, PositionSource};
+use connections::ConnectionLimits;
+use coverage::{Coverage, CoverageMode, CoverageParams};
+use db_health::DbHealthMonitor;
+use dead_letter::DeadLetters;
+use emergencies::EmergencyMonitor;
+use export::{ExportParams, Track, TrackFormat, TrackPoint};
+use fanout::Fanout;
+use filters::{AircraftFilter, AircraftQuery};
+use flights::FlightSegmenter;
+use geofences::{GeofenceDef, GeofenceMonitor, NewGeofence};
 use grpc_server::GatewayService;
+use history::HistoryParams;
+use interrogators::InterrogatorStore;
+use merge::EventMerger;
+use military::AddressClasses;
+use notifiers::Dispatcher;
+use presence::Presence;
+use privacy::PrivacyList;
+use public::PublicView;
+use pubsub::PubSub;
+use raw_archive::RawArchive;
+use recording::Recordings;
+use response_cache::ResponseCache;
+use routes::RouteLookup;
+use sanity::SanityFilter;
+use scan::ScanStore;
+use share::{SharePolicies, SharePolicy};
+use signal::{SignalBuffer, SignalSample};
+use sse::EventLog;
+use stats::{StatsCollector, StatsRange};
+use storage::Storage;
+use tar1090::SnapshotHistory;
+use tenants::{Scope, Tenants};
+use tiles::TileProxy;
+use udp::UdpOutput;
+use units::Units;
+use uptime::ReceiverTracker;
+use write_policy::PositionThrottle;
 
 pub mod adsb {
     tonic::include_proto!("adsb");
@@ -28,8 +141,43 @@ pub mod adsb {
 
 /// Shared application state
 pub struct AppState {
-    pub db_writer: Arc<DbWriter>,
-    pub broadcast_tx: Arc<broadcast::Sender<String>>,
+    pub db_writer: Arc<dyn Storage>,
+    pub db_health: Arc<DbHealthMonitor>,
+    pub dead_letters: Arc<DeadLetters>,
+    pub sanity: SanityFilter,
+    pub merger: EventMerger,
+    pub aircraft_cache: Arc<AircraftCache>,
+    pub broadcast_position: PositionSource,
+    pub event_time: EventTime,
+    pub position_throttle: PositionThrottle,
+    pub pubsub: Arc<dyn PubSub>,
+    pub aircraft_db: Arc<AircraftDb>,
+    pub routes: Arc<RouteLookup>,
+    pub addresses: Arc<AddressClasses>,
+    pub privacy: PrivacyList,
+    pub share: Arc<SharePolicies>,
+    pub udp: UdpOutput,
+    pub alerts: Arc<AlertEngine>,
+    pub geofences: Arc<GeofenceMonitor>,
+    pub emergencies: Arc<EmergencyMonitor>,
+    pub flights: Arc<FlightSegmenter>,
+    pub raw_archive: Arc<RawArchive>,
+    pub recordings: Recordings,
+    pub stats: Arc<StatsCollector>,
+    pub receivers: Arc<ReceiverTracker>,
+    pub signal: Arc<SignalBuffer>,
+    pub scans: ScanStore,
+    pub interrogators: InterrogatorStore,
+    pub events: Arc<EventLog>,
+    pub fanout: Arc<Fanout>,
+    pub public: Arc<PublicView>,
+    pub connections: Arc<ConnectionLimits>,
+    pub ws_compression: bool,
+    pub auth: Arc<Auth>,
+    pub presence: Arc<Presence>,
+    pub graphql: graphql::ApiSchema,
+    pub tar1090: Arc<SnapshotHistory>,
+    pub tiles: Option<TileProxy>,
 }
 
 #[tokio::main]
@@ -47,58 +195,374 @@ async fn main() -> Result<()> {
     info!("===========================================");
 
     // Load configuration from environment
-    let grpc_port: u16 = std::env::var("GRPC_PORT")
-        .unwrap_or_else(|_| "50051".to_string())
-        .parse()
-        .unwrap_or(50051);
-
-    let ws_port: u16 = std::env::var("WS_PORT")
-        .unwrap_or_else(|_| "8888".to_string())
-        .parse()
-        .unwrap_or(8888);
-
-    let db_host = std::env::var("DB_HOST").unwrap_or_else(|_| "localhost".to_string());
-    let db_port = std::env::var("DB_PORT").unwrap_or_else(|_| "5432".to_string());
-    let db_name = std::env::var("DB_NAME").unwrap_or_else(|_| "adsb".to_string());
-    let db_user = std::env::var("DB_USER").unwrap_or_else(|_| "adsb".to_string());
-    let db_password = std::env::var("DB_PASSWORD").unwrap_or_else(|_| "adsb".to_string());
-    let static_dir = std::env::var("STATIC_DIR").unwrap_or_else(|_| "/app/static".to_string());
-
-    let db_url = format!(
-        "host={} port={} dbname={} user={} password={}",
-        db_host, db_port, db_name, db_user, db_password
-    );
+    let config = Config::from_env();
 
     info!("Configuration:");
-    info!("  gRPC port: {}", grpc_port);
-    info!("  HTTP/WS port: {}", ws_port);
-    info!("  Database: {}@{}:{}/{}", db_user, db_host, db_port, db_name);
-    info!("  Static files: {}", static_dir);
+    info!("  gRPC port: {}", config.grpc_port);
+    info!("  HTTP/WS port: {}", config.ws_port);
+    if let Some(port) = config.beast_port {
+        info!("  Beast port: {}", port);
+    }
+    if let Some(server) = &config.ogn_server {
+        info!("  OGN APRS server: {}", server);
+    }
+    match config.db_backend {
+        config::DbBackend::Postgres => info!(
+            "  Database: {}@{}:{}/{}",
+            config.db_user, config.db_host, config.db_port, config.db_name
+        ),
+        config::DbBackend::Sqlite => {
+            info!("  Database: sqlite {}", config.sqlite_path.display())
+        }
+        config::DbBackend::ClickHouse => info!(
+            "  Database: clickhouse {}/{}",
+            config.clickhouse_url, config.clickhouse_database
+        ),
+    }
+    info!(
+        "  Static files: {}",
+        config.static_dir.as_deref().unwrap_or("embedded")
+    );
+    info!("  Raw frame archive: {:?}", config.raw_archive_mode);
+    info!("  Event time: {:?}", config.event_time);
+    info!("  Flight gap: {} min", config.flight_gap_minutes);
+    info!(
+        "  Position writes: at most every {} ms per aircraft",
+        config.position_write_interval_ms
+    );
+    info!("  Response cache: {} ms", config.response_cache_ms);
+    info!(
+        "  HTTP compression: gzip {}, brotli {}",
+        config.http_compression.gzip, config.http_compression.br
+    );
+    info!("  WebSocket compression: {}", config.ws_compression);
+    match (config.aircraft_db_file(), &config.aircraft_db_url) {
+        (Some(path), Some(url)) => info!(
+            "  Aircraft database: {} (refresh from {} every {}h)",
+            path.display(),
+            url,
+            config.aircraft_db_refresh_hours
+        ),
+        (Some(path), None) => info!("  Aircraft database: {}", path.display()),
+        _ => info!("  Aircraft database: disabled"),
+    }
+    if let Some(path) = &config.routes_path {
+        info!("  Routes file: {}", path.display());
+    }
+    if let Some(url) = &config.routes_api_url {
+        info!("  Routes API: {}", url);
+    }
+    if let Some(path) = &config.address_overrides_path {
+        info!("  Address overrides: {}", path.display());
+    }
+    if let Some(path) = &config.privacy_list_path {
+        info!(
+            "  Privacy list: {} (default {:?}, grid {}°)",
+            path.display(),
+            config.privacy_action,
+            config.privacy_grid_deg
+        );
+    }
+    if let Some(tiles) = config.tile_config() {
+        info!(
+            "  Map tiles: {} from {}",
+            tiles.cache_dir.display(),
+            tiles.upstream.as_deref().unwrap_or("disk only")
+        );
+    }
+    if let Some((lat, lon)) = config.receiver_location {
+        info!("  Receiver location: {:.4}, {:.4}", lat, lon);
+        if config.max_range_km > 0.0 {
+            info!("  Positions beyond {} km rejected", config.max_range_km);
+        }
+    }
+    info!(
+        "  REST API auth: {} keys, JWT {}",
+        config.api_keys.len(),
+        if config.jwt_secret.is_some() { "on" } else { "off" }
+    );
+    if !config.tenants.is_empty() {
+        info!("  Tenants: {}", config.tenants.len());
+    }
+    if config.public_viewer {
+        info!(
+            "  Public viewer: on, grid {}, delay {}s",
+            config
+                .public_position_grid_deg
+                .map_or("off".to_string(), |deg| format!("{} deg", deg)),
+            config.public_delay_secs
+        );
+    }
+    info!(
+        "  Streaming clients: auth {}, {} per IP",
+        if config.ws_auth_token.is_some() { "required" } else { "off" },
+        config.ws_max_connections_per_ip
+    );
+    info!(
+        "  WebSocket queues: {} messages, disconnect after {} dropped",
+        config.ws_queue_size, config.ws_max_dropped_messages
+    );
 
-    // Create broadcast channel for WebSocket clients
-    let (broadcast_tx, _) = broadcast::channel::<String>(1000);
-    let broadcast_tx = Arc::new(broadcast_tx);
+    // Live events for WebSocket/SSE clients, shared between replicas when configured
+    let pubsub = pubsub::connect(&config).await;
+    info!("Live events: {}", pubsub.backend_name());
 
-    // Connect to database
-    let db_writer = match DbWriter::new(&db_url).await {
-        Ok(db) => {
-            info!("Connected to database");
-            Arc::new(db)
-        }
+    // Connect to database (falls back to a no-op writer)
+    let db_writer = storage::connect(&config).await;
+
+    // Load aircraft metadata database (registration/type/operator)
+    let aircraft_db = Arc::new(load_aircraft_db(&config).await);
+    if let (Some(url), Some(path)) = (&config.aircraft_db_url, config.aircraft_db_file()) {
+        aircraft_db_refresh::spawn_refresher(
+            aircraft_db.clone(),
+            aircraft_db_refresh::RefreshConfig {
+                url: url.clone(),
+                sha256_url: config.aircraft_db_sha256_url.clone(),
+                path,
+                interval: std::time::Duration::from_secs(config.aircraft_db_refresh_hours * 3600),
+            },
+        );
+    }
+
+    // Callsign -> route lookup
+    let routes = match RouteLookup::new(
+        config.routes_path.as_deref(),
+        config.routes_api_url.as_deref(),
+    ) {
+        Ok(routes) => Arc::new(routes),
         Err(e) => {
-            error!("Failed to connect to database: {}. Continuing without DB.", e);
-            Arc::new(DbWriter::new_dummy())
+            error!("Failed to set up route lookup: {}. Continuing without routes.", e);
+            Arc::new(RouteLookup::disabled())
         }
     };
 
+    // Military/special address classification
+    let addresses = Arc::new(load_address_classes(&config));
+
+    // Aircraft withheld for privacy; refusing to start beats sharing them
+    let privacy = match &config.privacy_list_path {
+        Some(path) => {
+            let list = PrivacyList::load(path, config.privacy_action, config.privacy_grid_deg)
+                .with_context(|| format!("Failed to load privacy list {}", path.display()))?;
+            info!("Loaded {} privacy list entries", list.len());
+            list
+        }
+        None => PrivacyList::new(config.privacy_grid_deg),
+    };
+
+    // What each output may share; a bad setting would share too much
+    let share_policy = |name: &str, spec: &str| {
+        SharePolicy::parse(spec, addresses.clone()).with_context(|| format!("Invalid {}", name))
+    };
+    let share = Arc::new(SharePolicies {
+        ws: share_policy("SHARE_WS", &config.share_ws)?,
+        sse: share_policy("SHARE_SSE", &config.share_sse)?,
+        api: share_policy("SHARE_API", &config.share_api)?,
+        udp: share_policy("SHARE_UDP", &config.share_udp)?,
+    });
+    for (output, spec) in [
+        ("WebSocket", &config.share_ws),
+        ("SSE", &config.share_sse),
+        ("API", &config.share_api),
+        ("UDP", &config.share_udp),
+    ] {
+        if !spec.trim().is_empty() {
+            info!("{} share restrictions: {}", output, spec);
+        }
+    }
+
+    // Low-latency UDP event output
+    let udp = UdpOutput::new(
+        config.udp_output.clone(),
+        config.udp_format,
+        config.udp_multicast_ttl,
+        share.udp.clone(),
+    )
+    .context("Failed to open the UDP output socket")?;
+    if udp.is_enabled() {
+        info!("Sending aircraft events over UDP ({:?}) to {:?}", config.udp_format, config.udp_output);
+    }
+
+    // Watchlist alert engine
+    let dispatcher = Arc::new(Dispatcher::new(config.default_notifiers()));
+    let alerts = Arc::new(AlertEngine::new(pubsub.clone(), dispatcher.clone()));
+    match alerts.reload(db_writer.as_ref()).await {
+        Ok(count) => info!("Loaded {} watchlist rules", count),
+        Err(e) => error!("Failed to load watchlist rules: {}", e),
+    }
+
+    // Geofence entry/exit detection
+    let geofences = Arc::new(GeofenceMonitor::new(pubsub.clone()));
+    match geofences.reload(db_writer.as_ref()).await {
+        Ok(count) => info!("Loaded {} geofences", count),
+        Err(e) => error!("Failed to load geofences: {}", e),
+    }
+    geofences::spawn_sweeper(geofences.clone(), db_writer.clone());
+
+    // Emergency squawk tracking
+    let emergencies = Arc::new(EmergencyMonitor::new(
+        pubsub.clone(),
+        dispatcher,
+        aircraft_db.clone(),
+    ));
+    emergencies::spawn_sweeper(emergencies.clone(), db_writer.clone());
+
+    // Start raw frame archiver (no-op unless RAW_ARCHIVE is set)
+    let raw_archive = Arc::new(RawArchive::start(&config, db_writer.clone()));
+
+    // Hourly recordings of the live broadcast (off unless RECORDING_DIR is set)
+    let recordings = Recordings::start(
+        config.recording_dir.clone(),
+        config.recording_max_files,
+        pubsub.clone(),
+    );
+
+    // Start flight session segmentation
+    let flights = Arc::new(FlightSegmenter::new(std::time::Duration::from_secs(
+        config.flight_gap_minutes * 60,
+    )));
+    flights::spawn_flusher(flights.clone(), db_writer.clone());
+
+    // Hourly/daily receiver statistics
+    let stats = Arc::new(StatsCollector::new(config.receiver_location));
+    stats::spawn_flusher(stats.clone(), db_writer.clone());
+
+    // Receiver connect/disconnect sessions for uptime
+    let receivers = Arc::new(ReceiverTracker::new());
+    uptime::spawn_sweeper(receivers.clone(), db_writer.clone());
+
+    // Retries of failed position writes, then the dead letter file
+    let dead_letters = DeadLetters::start(
+        db_writer.clone(),
+        config.dead_letter_file.clone(),
+        config.dead_letter_max_mb * 1024 * 1024,
+    );
+
+    // Table sizes, ages and pool use
+    let db_health = Arc::new(DbHealthMonitor::default());
+    if config.db_health_interval_secs > 0 {
+        db_health::spawn_checker(
+            db_health.clone(),
+            db_writer.clone(),
+            std::time::Duration::from_secs(config.db_health_interval_secs),
+        );
+    }
+
+    // Recent signal metrics for clients that just connected
+    let signal = Arc::new(SignalBuffer::new(config.signal_buffer_minutes));
+
+    // Numbered copy of the broadcast for SSE clients
+    let events = Arc::new(EventLog::new(sse::REPLAY_CAPACITY));
+    sse::spawn_recorder(events.clone(), pubsub.clone(), share.clone());
+
+    // Per-client queues for WebSocket clients
+    let fanout = Arc::new(Fanout::new(config.ws_queue_size, config.ws_max_dropped_messages));
+    fanout::spawn_dispatcher(fanout.clone(), pubsub.clone(), share.clone());
+
+    // Delayed, redacted copy for public viewers
+    let public = Arc::new(PublicView::new(
+        config.public_viewer,
+        config.public_position_grid_deg,
+        std::time::Duration::from_secs(config.public_delay_secs),
+        Arc::new(Fanout::new(config.ws_queue_size, config.ws_max_dropped_messages)),
+    ));
+    if config.public_viewer {
+        public::spawn_dispatcher(public.clone(), pubsub.clone(), share.clone());
+    }
+
+    // Current aircraft for the list and new clients
+    let aircraft_cache = Arc::new(AircraftCache::new());
+    aircraft_cache::spawn_updater(aircraft_cache.clone(), pubsub.clone());
+
+    // aircraft_removed notifications
+    let presence = Arc::new(Presence::new(pubsub.clone()));
+    presence::spawn_sweeper(presence.clone());
+
+    // WebSocket/SSE client admission
+    let connections = Arc::new(ConnectionLimits::new(
+        config.ws_auth_token.clone(),
+        config.ws_max_connections_per_ip,
+        std::time::Duration::from_secs(config.ws_ping_interval_secs),
+        std::time::Duration::from_secs(config.ws_idle_timeout_secs),
+    ));
+
+    // REST API authentication (open unless API_KEYS/JWT_SECRET are set)
+    let auth = Arc::new(
+        Auth::new(
+            config.api_keys.clone(),
+            config.jwt_secret.as_deref(),
+            config.api_rate_limit_per_minute,
+        )
+        .with_tenants(Tenants::new(config.tenants.clone()))
+        .with_public_viewer(config.public_viewer),
+    );
+
     // Create shared app state
     let app_state = Arc::new(AppState {
         db_writer: db_writer.clone(),
-        broadcast_tx: broadcast_tx.clone(),
+        db_health,
+        dead_letters,
+        sanity: SanityFilter::new(config.receiver_location, config.max_range_km, config.position_quality),
+        merger: EventMerger::new(),
+        aircraft_cache,
+        broadcast_position: config.broadcast_position,
+        event_time: config.event_time,
+        position_throttle: PositionThrottle::new(std::time::Duration::from_millis(
+            config.position_write_interval_ms,
+        )),
+        pubsub: pubsub.clone(),
+        aircraft_db,
+        routes,
+        addresses,
+        privacy,
+        share,
+        udp,
+        alerts,
+        geofences,
+        emergencies,
+        flights,
+        raw_archive,
+        recordings,
+        stats,
+        receivers,
+        signal,
+        scans: ScanStore::new(),
+        interrogators: InterrogatorStore::new(),
+        events,
+        fanout,
+        public,
+        connections,
+        ws_compression: config.ws_compression,
+        auth: auth.clone(),
+        presence,
+        graphql: graphql::schema(),
+        tar1090: Arc::new(SnapshotHistory::new()),
+        tiles: config.tile_config().map(TileProxy::new),
     });
 
+    // tar1090 history snapshots
+    tar1090::spawn_recorder(app_state.clone());
+
+    // Beast feeds from dump1090/readsb receivers
+    if let Some(port) = config.beast_port {
+        beast::spawn(port, app_state.clone(), config.receiver_location);
+    }
+
+    // FLARM traffic from the Open Glider Network
+    if let Some(server) = config.ogn_server.clone() {
+        let filter = config.ogn_filter.clone().or_else(|| {
+            config
+                .receiver_location
+                .map(|(lat, lon)| format!("r/{:.4}/{:.4}/{}", lat, lon, config.ogn_range_km))
+        });
+        match filter {
+            Some(filter) => ogn::spawn(server, config.ogn_user.clone(), filter, app_state.clone()),
+            None => error!("OGN_APRS_SERVER needs OGN_FILTER or RECEIVER_LAT/RECEIVER_LON; not following OGN traffic"),
+        }
+    }
+
     // Create gRPC service
-    let gateway_service = GatewayService::new(db_writer.clone(), broadcast_tx.clone());
+    let gateway_service = GatewayService::new(app_state.clone());
 
     // Build HTTP/WebSocket router
     let cors = CorsLayer::new()
@@ -106,21 +570,82 @@ async fn main() -> Result<()> {
         .allow_methods(Any)
         .allow_headers(Any);
 
+    // Short-lived copies of the most polled responses
+    let response_cache = Arc::new(ResponseCache::new(std::time::Duration::from_millis(
+        config.response_cache_ms,
+    )));
+    let cached = middleware::from_fn_with_state(response_cache, response_cache::cached);
+
+    let api = Router::new()
+        .route("/api/aircraft", get(get_aircraft).route_layer(cached.clone()))
+        .route("/api/aircraft/:icao", get(get_aircraft_detail))
+        .route("/api/aircraft/:icao/trail", get(get_aircraft_trail))
+        .route("/api/history/positions", get(get_position_history))
+        .route("/api/export/positions", get(export_positions))
+        .route("/api/flights", get(get_flights))
+        .route("/api/recordings", get(recording::list_recordings))
+        .route("/api/recordings/:name", get(recording::get_recording))
+        .route("/api/emergencies", get(get_emergencies))
+        .route("/api/squawk-changes", get(squawks::get_squawk_changes))
+        .route("/api/watchlist", get(get_watchlist).post(create_watchlist_rule))
+        .route("/api/watchlist/:id", delete(delete_watchlist_rule))
+        .route("/api/geofences", get(get_geofences).post(create_geofence))
+        .route("/api/geofences/events", get(get_geofence_events))
+        .route("/api/geofences/:id", delete(delete_geofence))
+        .route("/api/stats", get(get_stats).route_layer(cached.clone()))
+        .route("/api/coverage", get(get_coverage).route_layer(cached.clone()))
+        .route(public::STATS_PATH, get(public::get_public_stats).route_layer(cached))
+        .route("/api/receivers/leaderboard", get(feeders::get_leaderboard))
+        .route("/api/receivers/:device_id/stats", get(feeders::get_receiver_stats))
+        .route("/api/sdr/status", get(get_sdr_status))
+        .route("/api/sdr/:device_id/status", get(get_sdr_device_status))
+        .route("/api/sdr/:device_id/history", get(get_sdr_history))
+        .route("/api/sdr/:device_id/scan", get(scan::get_sdr_scan))
+        .route("/api/sdr/:device_id/interrogators", get(interrogators::get_sdr_interrogators))
+        .route("/api/signal/live", get(get_live_signal))
+        .route("/api/db/health", get(db_health::get_db_health))
+        .route("/api/admin/positions", delete(admin::delete_positions))
+        .route("/api/admin/devices/rename", post(admin::rename_device))
+        .route("/api/ingest/positions", post(ingest::ingest_positions))
+        .route(graphql::PATH, post(graphql::graphql_handler))
+        // tar1090/readsb-compatible data files
+        .route("/data/:file", get(tar1090::data_file))
+        .route_layer(middleware::from_fn_with_state(auth, auth::require_auth));
+
     let app = Router::new()
         // WebSocket endpoint
         .route("/ws", get(ws_handler::ws_handler))
+        .route("/api/stream", get(sse::sse_handler))
         // REST API endpoints
-        .route("/api/aircraft", get(get_aircraft))
-        .route("/api/aircraft/:icao/trail", get(get_aircraft_trail))
-        .route("/api/sdr/status", get(get_sdr_status))
+        .merge(api)
+        // OpenAPI document and Swagger UI (public, like /health)
+        .merge(SwaggerUi::new(api::DOCS_PATH).url(api::OPENAPI_PATH, ApiDoc::openapi()))
+        .route("/api/schema", get(schema::get_schema))
+        .route(graphql::PATH, get(graphql::graphiql))
         .route("/health", get(health_check))
-        // Static files
-        .nest_service("/", ServeDir::new(&static_dir))
-        .layer(cors)
-        .with_state(app_state);
+        .route("/metrics", get(metrics::metrics))
+        // Map tiles through the proxy, when configured
+        .route("/tiles/tiles.json", get(tiles::tile_json))
+        .route("/tiles/:z/:x/:file", get(tiles::tile));
+
+    // Static files: STATIC_DIR when set, otherwise the built-in web UI
+    let app = match &config.static_dir {
+        Some(dir) => app.nest_service("/", ServeDir::new(dir)),
+        None => app.fallback(assets::embedded),
+    }
+    .layer(cors)
+    // Server-sent events, small responses and gzipped recordings stay
+    // uncompressed
+    .layer(
+        CompressionLayer::new()
+            .gzip(config.http_compression.gzip)
+            .br(config.http_compression.br)
+            .compress_when(DefaultPredicate::new().and(NotForContentType::const_new("application/gzip"))),
+    )
+    .with_state(app_state);
 
     // Start gRPC server
-    let grpc_addr = format!("0.0.0.0:{}", grpc_port).parse()?;
+    let grpc_addr = format!("0.0.0.0:{}", config.grpc_port).parse()?;
     info!("Starting gRPC server on {}", grpc_addr);
 
     let grpc_server = Server::builder()
@@ -128,11 +653,14 @@ async fn main() -> Result<()> {
         .serve(grpc_addr);
 
     // Start HTTP/WebSocket server
-    let http_addr = format!("0.0.0.0:{}", ws_port);
+    let http_addr = format!("0.0.0.0:{}", config.ws_port);
     info!("Starting HTTP/WebSocket server on {}", http_addr);
 
     let listener = tokio::net::TcpListener::bind(&http_addr).await?;
-    let http_server = axum::serve(listener, app);
+    let http_server = axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<std::net::SocketAddr>(),
+    );
 
     // Run both servers concurrently
     tokio::select! {
@@ -151,51 +679,859 @@ async fn main() -> Result<()> {
     Ok(())
 }
 
+/// Load the aircraft database, continuing without enrichment on failure
+async fn load_aircraft_db(config: &Config) -> AircraftDb {
+    let path = match config.aircraft_db_file() {
+        Some(path) => path,
+        None => return AircraftDb::empty(),
+    };
+
+    // First run with downloads enabled: the refresher fetches it
+    if config.aircraft_db_url.is_some() && !path.exists() {
+        return AircraftDb::empty();
+    }
+
+    match tokio::task::spawn_blocking(move || AircraftDb::load(&path)).await {
+        Ok(Ok(db)) => {
+            info!("Loaded {} aircraft from database", db.len());
+            db
+        }
+        Ok(Err(e)) => {
+            error!("Failed to load aircraft database: {}. Continuing without enrichment.", e);
+            AircraftDb::empty()
+        }
+        Err(e) => {
+            error!("Aircraft database loader panicked: {}", e);
+            AircraftDb::empty()
+        }
+    }
+}
+
+/// Load address classification overrides, continuing with the built-in
+/// blocks on failure
+fn load_address_classes(config: &Config) -> AddressClasses {
+    let Some(path) = &config.address_overrides_path else {
+        return AddressClasses::new();
+    };
+    match AddressClasses::load(path) {
+        Ok(classes) => {
+            info!("Loaded {} address overrides", classes.len());
+            classes
+        }
+        Err(e) => {
+            error!("Failed to load address overrides: {}. Using the built-in blocks.", e);
+            AddressClasses::new()
+        }
+    }
+}
+
 /// Health check endpoint
 async fn health_check() -> &'static str {
     "OK"
 }
 
 /// Query parameters for trail endpoint
-#[derive(serde::Deserialize)]
+#[derive(serde::Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 struct TrailParams {
+    /// Look-back in minutes (default 30)
     minutes: Option<i32>,
+    /// RFC 3339 start (inclusive), instead of `minutes`; defaults to 30
+    /// minutes before `to`
+    from: Option<String>,
+    /// RFC 3339 end (exclusive); defaults to now
+    to: Option<String>,
+    /// `json` (default), `geojson`, `kml` or `gpx`
+    format: Option<String>,
+    /// Keep only the latest position of each bucket of this many seconds
+    /// (1 to 86400), to bound long trails
+    bucket_seconds: Option<i64>,
 }
 
-/// Get current aircraft list
-async fn get_aircraft(State(state): State<Arc<AppState>>) -> impl IntoResponse {
-    match state.db_writer.get_current_aircraft().await {
-        Ok(aircraft) => Json(aircraft).into_response(),
+impl TrailParams {
+    /// Start and end of the trail; no end when `to` is omitted, so the
+    /// latest positions are always included
+    fn range(&self, now: DateTime<Utc>) -> Result<(DateTime<Utc>, Option<DateTime<Utc>>), String> {
+        if self.minutes.is_some() && (self.from.is_some() || self.to.is_some()) {
+            return Err("use either minutes or from/to, not both".into());
+        }
+        let minutes = self.minutes.unwrap_or(30).max(1) as i64;
+        let (from, to) = history::parse_range(
+            self.from.as_deref(),
+            self.to.as_deref(),
+            now,
+            chrono::Duration::minutes(minutes),
+        )?;
+        Ok((from, self.to.is_some().then_some(to)))
+    }
+
+    fn bucket_secs(&self) -> Result<Option<i64>, String> {
+        match self.bucket_seconds {
+            Some(secs) if !(1..=MAX_TRAIL_BUCKET_SECS).contains(&secs) => {
+                Err(format!("bucket_seconds must be 1 to {}", MAX_TRAIL_BUCKET_SECS))
+            }
+            secs => Ok(secs),
+        }
+    }
+}
+
+/// Largest trail `bucket_seconds`
+const MAX_TRAIL_BUCKET_SECS: i64 = 86_400;
+
+/// Longest range `/api/stats` reports, in days
+const MAX_STATS_RANGE_DAYS: i64 = 90;
+
+/// Query parameters for stats endpoint
+#[derive(serde::Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct StatsParams {
+    /// Hourly buckets to return (default 24)
+    hours: Option<i64>,
+    /// Daily buckets to return (default 7)
+    days: Option<i64>,
+    /// RFC 3339 start, instead of `hours`/`days`: every bucket overlapping
+    /// `from`..`to` is returned. Defaults to a day before `to`
+    from: Option<String>,
+    /// RFC 3339 end; defaults to now
+    to: Option<String>,
+}
+
+impl StatsParams {
+    fn range(&self, now: DateTime<Utc>) -> Result<StatsRange, String> {
+        if self.from.is_none() && self.to.is_none() {
+            let hours = self.hours.unwrap_or(24).clamp(1, 24 * 30);
+            let days = self.days.unwrap_or(7).clamp(1, MAX_STATS_RANGE_DAYS);
+            return Ok(StatsRange::recent(hours, days, now));
+        }
+        if self.hours.is_some() || self.days.is_some() {
+            return Err("use either hours/days or from/to, not both".into());
+        }
+        let (from, to) = history::parse_range(
+            self.from.as_deref(),
+            self.to.as_deref(),
+            now,
+            chrono::Duration::days(1),
+        )?;
+        if to - from > chrono::Duration::days(MAX_STATS_RANGE_DAYS) {
+            return Err(format!("range must not exceed {} days", MAX_STATS_RANGE_DAYS));
+        }
+        Ok(StatsRange::between(from, to))
+    }
+}
+
+/// Query parameters for flights endpoint
+#[derive(serde::Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct FlightParams {
+    /// 24-bit ICAO address, any case
+    icao: Option<String>,
+    /// 1-1000, default 100
+    limit: Option<i64>,
+}
+
+/// Query parameters for SDR history endpoint
+#[derive(serde::Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct SdrHistoryParams {
+    /// Hours to look back, 1-168, default 1
+    hours: Option<i64>,
+    /// 1-10000, default 1000
+    limit: Option<i64>,
+}
+
+/// Query parameters for live signal endpoint
+#[derive(serde::Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct SignalLiveParams {
+    /// Seconds to look back, default 60, at most `SIGNAL_BUFFER_MINUTES`
+    seconds: Option<i64>,
+    /// Only reports from this capture device
+    device_id: Option<String>,
+}
+
+/// Query parameters for emergencies endpoint
+#[derive(serde::Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct EmergencyParams {
+    /// Only emergencies still in progress
+    #[serde(default)]
+    active: bool,
+    /// 1-1000, default 100
+    limit: Option<i64>,
+}
+
+/// Get current aircraft list, optionally filtered by area, altitude and military flag
+#[utoipa::path(
+    get,
+    path = "/api/aircraft",
+    tag = "aircraft",
+    params(AircraftQuery, Units),
+    responses(
+        (status = 200, description = "Aircraft seen in the last five minutes", body = [Aircraft]),
+        (status = 400, description = "Invalid filter", body = ApiError),
+    ),
+    security((), ("api_key" = []), ("bearer" = []))
+)]
+async fn get_aircraft(
+    State(state): State<Arc<AppState>>,
+    scope: Scope,
+    Query(params): Query<AircraftQuery>,
+    Query(units): Query<Units>,
+) -> impl IntoResponse {
+    let mut filter = match AircraftFilter::from_query(&params, &state.addresses) {
+        Ok(filter) => filter,
+        Err(e) => return ApiError::bad_request(e),
+    };
+    filter.min_alt = filter.min_alt.map(|alt| units.altitude_unit.to_feet(alt));
+    filter.max_alt = filter.max_alt.map(|alt| units.altitude_unit.to_feet(alt));
+    let aircraft = state.current_aircraft(&filter, &scope).await;
+    match aircraft {
+        Ok(aircraft) => units.json(aircraft),
         Err(e) => {
             error!("Failed to get aircraft: {}", e);
-            Json(serde_json::json!({"error": e.to_string()})).into_response()
+            ApiError::internal(e)
         }
     }
 }
 
+/// Get one aircraft's merged current state
+#[utoipa::path(
+    get,
+    path = "/api/aircraft/{icao}",
+    tag = "aircraft",
+    params(("icao" = String, Path, description = "24-bit ICAO address as 6 hex digits"), Units),
+    responses(
+        (status = 200, description = "Current state, enrichment, emergency and open flight", body = AircraftDetail),
+        (status = 400, description = "Malformed ICAO address", body = ApiError),
+        (status = 404, description = "Aircraft not seen recently", body = ApiError),
+    ),
+    security((), ("api_key" = []), ("bearer" = []))
+)]
+async fn get_aircraft_detail(
+    State(state): State<Arc<AppState>>,
+    scope: Scope,
+    Path(icao): Path<String>,
+    Query(units): Query<Units>,
+) -> impl IntoResponse {
+    let icao = match api::parse_icao(&icao) {
+        Ok(icao) => icao,
+        Err(e) => return ApiError::bad_request(e),
+    };
+
+    match state.aircraft_detail(&icao, &scope).await {
+        Ok(Some(aircraft)) => units.json(aircraft),
+        Ok(None) => ApiError::not_found("aircraft not found"),
+        Err(e) => {
+            error!("Failed to get aircraft {}: {}", icao, e);
+            ApiError::internal(e)
+        }
+    }
+}
 /// Get aircraft position trail
+#[utoipa::path(
+    get,
+    path = "/api/aircraft/{icao}/trail",
+    tag = "aircraft",
+    params(("icao" = String, Path, description = "24-bit ICAO address"), TrailParams, Units),
+    responses(
+        (status = 200, description = "Recent positions, oldest first, counted in `X-Point-Count`; a GeoJSON, KML or GPX download for other formats", body = [TrailPoint]),
+        (status = 400, description = "Malformed ICAO address, unknown format, invalid range or bucket", body = ApiError),
+    ),
+    security((), ("api_key" = []), ("bearer" = []))
+)]
 async fn get_aircraft_trail(
     State(state): State<Arc<AppState>>,
+    scope: Scope,
     Path(icao): Path<String>,
     Query(params): Query<TrailParams>,
+    Query(units): Query<Units>,
 ) -> impl IntoResponse {
-    let minutes = params.minutes.unwrap_or(30);
-    match state.db_writer.get_aircraft_trail(&icao, minutes).await {
-        Ok(trail) => Json(trail).into_response(),
+    let parsed = api::parse_icao(&icao).map_err(String::from).and_then(|icao| {
+        let range = params.range(chrono::Utc::now())?;
+        Ok((icao, range, params.bucket_secs()?, TrackFormat::parse(params.format.as_deref())?))
+    });
+    let (icao, (from, to), bucket_secs, format) = match parsed {
+        Ok(parsed) => parsed,
+        Err(e) => return ApiError::bad_request(e),
+    };
+    if !state.share.api.allows(&icao) {
+        return ApiError::not_found("aircraft not found");
+    }
+    let devices = scope.devices();
+    let trail = state.db_writer.get_aircraft_trail(&icao, devices.as_deref(), from, to, bucket_secs).await;
+    let response = trail.and_then(|trail| {
+        let count = header::HeaderValue::from(trail.len());
+        let mut response = if format == TrackFormat::Json {
+            units.json(api::from_rows::<TrailPoint>(trail)?)
+        } else {
+            let track = Track {
+                icao: icao.clone(),
+                points: trail.iter().filter_map(TrackPoint::from_trail).collect(),
+            };
+            track_document(format, &[track], &format!("{}-trail", icao), None)
+        };
+        response.headers_mut().insert("x-point-count", count);
+        Ok(response)
+    });
+    response.unwrap_or_else(|e| {
+        error!("Failed to get trail for {}: {}", icao, e);
+        ApiError::internal(e)
+    })
+}
+
+/// Page through stored positions with an opaque keyset cursor
+#[utoipa::path(
+    get,
+    path = "/api/history/positions",
+    tag = "history",
+    params(HistoryParams, Units),
+    responses(
+        (status = 200, description = "One page of positions; GeoJSON, KML and GPX downloads carry the next cursor in `X-Next-Cursor`", body = HistoryPage),
+        (status = 400, description = "Invalid range, cursor or decimation", body = ApiError),
+    ),
+    security((), ("api_key" = []), ("bearer" = []))
+)]
+async fn get_position_history(
+    State(state): State<Arc<AppState>>,
+    scope: Scope,
+    Query(params): Query<HistoryParams>,
+    Query(units): Query<Units>,
+) -> impl IntoResponse {
+    let parsed = params
+        .parse(chrono::Utc::now())
+        .and_then(|parsed| Ok((parsed, TrackFormat::parse(params.format.as_deref())?)));
+    let ((mut query, limit, decimation), format) = match parsed {
+        Ok(parsed) => parsed,
+        Err(e) => return ApiError::bad_request(e),
+    };
+    query.devices = scope.devices();
+    match state.db_writer.get_position_history(&query).await {
+        Ok(rows) => {
+            let (page, next) = history::paginate(rows, limit);
+            let positions = history::decimate(page, decimation);
+            let next_cursor = next.map(|c| c.encode());
+            if format != TrackFormat::Json {
+                let tracks = export::tracks_from_history(&positions);
+                return track_document(format, &tracks, "history", next_cursor.as_deref());
+            }
+            units.json(HistoryPage {
+                positions,
+                next_cursor,
+            })
+        }
+        Err(e) => {
+            error!("Failed to get position history: {}", e);
+            ApiError::internal(e)
+        }
+    }
+}
+
+/// Stream stored positions as CSV with chunked transfer
+#[utoipa::path(
+    get,
+    path = "/api/export/positions",
+    tag = "history",
+    params(ExportParams),
+    responses(
+        (status = 200, description = "CSV attachment", content_type = "text/csv", body = String),
+        (status = 400, description = "Invalid range", body = ApiError),
+    ),
+    security((), ("api_key" = []), ("bearer" = []))
+)]
+async fn export_positions(
+    State(state): State<Arc<AppState>>,
+    scope: Scope,
+    Query(params): Query<ExportParams>,
+) -> impl IntoResponse {
+    let mut query = match params.parse(chrono::Utc::now()) {
+        Ok(query) => query,
+        Err(e) => return ApiError::bad_request(e),
+    };
+    query.devices = scope.devices();
+    let filename = format!(
+        "attachment; filename=\"positions-{}.csv\"",
+        query.from.format("%Y%m%dT%H%M%SZ")
+    );
+    let rows = export::positions_csv(state.db_writer.clone(), query, export::CSV_CHUNK_ROWS)
+        .inspect(|chunk| {
+            if let Err(e) = chunk {
+                error!("Position export failed: {}", e);
+            }
+        });
+    (
+        [
+            (header::CONTENT_TYPE, "text/csv; charset=utf-8".to_string()),
+            (header::CONTENT_DISPOSITION, filename),
+        ],
+        axum::body::Body::from_stream(rows),
+    )
+        .into_response()
+}
+
+/// Track export download; the next page cursor (if any) goes in `X-Next-Cursor`
+fn track_document(
+    format: TrackFormat,
+    tracks: &[Track],
+    name: &str,
+    next_cursor: Option<&str>,
+) -> axum::response::Response {
+    let disposition = format!("attachment; filename=\"{}.{}\"", name, format.extension());
+    let mut response = (
+        [
+            (header::CONTENT_TYPE, format.content_type().to_string()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        format.render(tracks),
+    )
+        .into_response();
+    if let Some(value) = next_cursor.and_then(|c| header::HeaderValue::from_str(c).ok()) {
+        response.headers_mut().insert("x-next-cursor", value);
+    }
+    response
+}
+
+/// Get flight sessions, most recent first
+#[utoipa::path(
+    get,
+    path = "/api/flights",
+    tag = "history",
+    params(FlightParams, Units),
+    responses(
+        (status = 200, description = "Flights, most recent first", body = [Flight]),
+        (status = 400, description = "Malformed ICAO address", body = ApiError),
+    ),
+    security((), ("api_key" = []), ("bearer" = []))
+)]
+async fn get_flights(
+    State(state): State<Arc<AppState>>,
+    scope: Scope,
+    Query(params): Query<FlightParams>,
+    Query(units): Query<Units>,
+) -> impl IntoResponse {
+    let limit = params.limit.unwrap_or(100).clamp(1, 1000);
+    let icao = match params.icao.as_deref().map(api::parse_icao).transpose() {
+        Ok(icao) => icao,
+        Err(e) => return ApiError::bad_request(e),
+    };
+    match state.flight_list(icao.as_deref(), limit, &scope).await {
+        Ok(flights) => units.json(flights),
+        Err(e) => {
+            error!("Failed to get flights: {}", e);
+            ApiError::internal(e)
+        }
+    }
+}
+
+/// Get emergency squawk occurrences, most recent first
+#[utoipa::path(
+    get,
+    path = "/api/emergencies",
+    tag = "alerts",
+    params(EmergencyParams, Units),
+    responses((status = 200, description = "Emergencies, most recent first", body = [Emergency])),
+    security((), ("api_key" = []), ("bearer" = []))
+)]
+async fn get_emergencies(
+    State(state): State<Arc<AppState>>,
+    scope: Scope,
+    Query(params): Query<EmergencyParams>,
+    Query(units): Query<Units>,
+) -> impl IntoResponse {
+    let limit = params.limit.unwrap_or(100).clamp(1, 1000);
+    let devices = scope.devices();
+    let emergencies = state.db_writer.get_emergencies(params.active, devices.as_deref(), limit).await;
+    let emergencies = emergencies.and_then(|mut emergencies| {
+        for e in emergencies.iter_mut() {
+            state.aircraft_db.enrich(e);
+        }
+        emergencies.retain_mut(|e| state.share.api.apply(e));
+        Ok(api::from_rows::<Emergency>(emergencies)?)
+    });
+    match emergencies {
+        Ok(emergencies) => units.json(emergencies),
+        Err(e) => {
+            error!("Failed to get emergencies: {}", e);
+            ApiError::internal(e)
+        }
+    }
+}
+
+/// List watchlist rules
+#[utoipa::path(
+    get,
+    path = "/api/watchlist",
+    tag = "alerts",
+    responses((status = 200, description = "All rules", body = [WatchRule])),
+    security((), ("api_key" = []), ("bearer" = []))
+)]
+async fn get_watchlist(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    match state.db_writer.get_watchlist().await {
+        Ok(rules) => Json(rules).into_response(),
+        Err(e) => {
+            error!("Failed to get watchlist: {}", e);
+            ApiError::internal(e)
+        }
+    }
+}
+
+/// Create a watchlist rule
+#[utoipa::path(
+    post,
+    path = "/api/watchlist",
+    tag = "alerts",
+    request_body = NewWatchRule,
+    responses(
+        (status = 201, description = "The stored rule", body = WatchRule),
+        (status = 400, description = "Invalid rule", body = ApiError),
+    ),
+    security((), ("api_key" = []), ("bearer" = []))
+)]
+async fn create_watchlist_rule(
+    State(state): State<Arc<AppState>>,
+    Json(rule): Json<NewWatchRule>,
+) -> impl IntoResponse {
+    let rule = match rule.validate() {
+        Ok(rule) => rule,
+        Err(e) => return ApiError::bad_request(e),
+    };
+
+    match state.db_writer.insert_watchlist(&rule).await {
+        Ok(stored) => {
+            if let Err(e) = state.alerts.reload(state.db_writer.as_ref()).await {
+                error!("Failed to reload watchlist rules: {}", e);
+            }
+            (StatusCode::CREATED, Json(stored)).into_response()
+        }
+        Err(e) => {
+            error!("Failed to create watchlist rule: {}", e);
+            ApiError::internal(e)
+        }
+    }
+}
+
+/// Delete a watchlist rule
+#[utoipa::path(
+    delete,
+    path = "/api/watchlist/{id}",
+    tag = "alerts",
+    params(("id" = i64, Path, description = "Rule id")),
+    responses(
+        (status = 204, description = "Deleted"),
+        (status = 404, description = "No such rule", body = ApiError),
+    ),
+    security((), ("api_key" = []), ("bearer" = []))
+)]
+async fn delete_watchlist_rule(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+) -> impl IntoResponse {
+    match state.db_writer.delete_watchlist(id).await {
+        Ok(true) => {
+            if let Err(e) = state.alerts.reload(state.db_writer.as_ref()).await {
+                error!("Failed to reload watchlist rules: {}", e);
+            }
+            StatusCode::NO_CONTENT.into_response()
+        }
+        Ok(false) => ApiError::not_found("rule not found"),
+        Err(e) => {
+            error!("Failed to delete watchlist rule {}: {}", id, e);
+            ApiError::internal(e)
+        }
+    }
+}
+
+/// List geofences
+#[utoipa::path(
+    get,
+    path = "/api/geofences",
+    tag = "alerts",
+    responses((status = 200, description = "All geofences", body = [GeofenceDef])),
+    security((), ("api_key" = []), ("bearer" = []))
+)]
+async fn get_geofences(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    match state.db_writer.get_geofences().await {
+        Ok(fences) => Json(fences).into_response(),
+        Err(e) => {
+            error!("Failed to get geofences: {}", e);
+            ApiError::internal(e)
+        }
+    }
+}
+
+/// Create a geofence
+#[utoipa::path(
+    post,
+    path = "/api/geofences",
+    tag = "alerts",
+    request_body = NewGeofence,
+    responses(
+        (status = 201, description = "The stored geofence", body = GeofenceDef),
+        (status = 400, description = "Invalid geofence", body = ApiError),
+    ),
+    security((), ("api_key" = []), ("bearer" = []))
+)]
+async fn create_geofence(
+    State(state): State<Arc<AppState>>,
+    Json(fence): Json<NewGeofence>,
+) -> impl IntoResponse {
+    if let Err(e) = fence.validate() {
+        return ApiError::bad_request(e);
+    }
+
+    match state.db_writer.insert_geofence(&fence).await {
+        Ok(stored) => {
+            if let Err(e) = state.geofences.reload(state.db_writer.as_ref()).await {
+                error!("Failed to reload geofences: {}", e);
+            }
+            (StatusCode::CREATED, Json(stored)).into_response()
+        }
+        Err(e) => {
+            error!("Failed to create geofence: {}", e);
+            ApiError::internal(e)
+        }
+    }
+}
+
+/// Delete a geofence
+#[utoipa::path(
+    delete,
+    path = "/api/geofences/{id}",
+    tag = "alerts",
+    params(("id" = i64, Path, description = "Geofence id")),
+    responses(
+        (status = 204, description = "Deleted"),
+        (status = 404, description = "No such geofence", body = ApiError),
+    ),
+    security((), ("api_key" = []), ("bearer" = []))
+)]
+async fn delete_geofence(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+) -> impl IntoResponse {
+    match state.db_writer.delete_geofence(id).await {
+        Ok(true) => {
+            if let Err(e) = state.geofences.reload(state.db_writer.as_ref()).await {
+                error!("Failed to reload geofences: {}", e);
+            }
+            StatusCode::NO_CONTENT.into_response()
+        }
+        Ok(false) => ApiError::not_found("geofence not found"),
         Err(e) => {
-            error!("Failed to get trail for {}: {}", icao, e);
-            Json(serde_json::json!({"error": e.to_string()})).into_response()
+            error!("Failed to delete geofence {}: {}", id, e);
+            ApiError::internal(e)
         }
     }
 }
 
-/// Get SDR device status
-async fn get_sdr_status(State(state): State<Arc<AppState>>) -> impl IntoResponse {
-    match state.db_writer.get_sdr_status().await {
-        Ok(status) => Json(status).into_response(),
+/// Query parameters for geofence events endpoint
+#[derive(serde::Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct GeofenceEventParams {
+    geofence_id: Option<i64>,
+    /// 1-1000, default 100
+    limit: Option<i64>,
+}
+
+/// Get logged geofence entry/exit events
+#[utoipa::path(
+    get,
+    path = "/api/geofences/events",
+    tag = "alerts",
+    params(GeofenceEventParams, Units),
+    responses((status = 200, description = "Events, most recent first", body = [GeofenceEvent])),
+    security((), ("api_key" = []), ("bearer" = []))
+)]
+async fn get_geofence_events(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<GeofenceEventParams>,
+    Query(units): Query<Units>,
+) -> impl IntoResponse {
+    let limit = params.limit.unwrap_or(100).clamp(1, 1000);
+    let events = state.db_writer.get_geofence_events(params.geofence_id, limit).await;
+    match events.and_then(|events| Ok(api::from_rows::<GeofenceEvent>(events)?)) {
+        Ok(events) => units.json(events),
+        Err(e) => {
+            error!("Failed to get geofence events: {}", e);
+            ApiError::internal(e)
+        }
+    }
+}
+
+/// Receiver statistics: live rates plus hourly and daily rollups
+#[utoipa::path(
+    get,
+    path = "/api/stats",
+    tag = "receiver",
+    params(StatsParams),
+    responses(
+        (status = 200, description = "Live rates and rollups", body = StatsResponse),
+        (status = 400, description = "Invalid range", body = ApiError),
+    ),
+    security((), ("api_key" = []), ("bearer" = []))
+)]
+async fn get_stats(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<StatsParams>,
+) -> impl IntoResponse {
+    let range = match params.range(chrono::Utc::now()) {
+        Ok(range) => range,
+        Err(e) => return ApiError::bad_request(e),
+    };
+    match state.stats_summary(range).await {
+        Ok(stats) => Json(stats).into_response(),
+        Err(e) => {
+            error!("Failed to get stats: {}", e);
+            ApiError::internal(e)
+        }
+    }
+}
+
+/// Binned position counts for coverage heatmaps
+#[utoipa::path(
+    get,
+    path = "/api/coverage",
+    tag = "receiver",
+    params(CoverageParams),
+    responses(
+        (status = 200, description = "Grid or polar coverage", body = Coverage),
+        (status = 400, description = "Invalid parameters, or polar mode without a receiver location", body = ApiError),
+    ),
+    security((), ("api_key" = []), ("bearer" = []))
+)]
+async fn get_coverage(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<CoverageParams>,
+) -> impl IntoResponse {
+    let (query, mode) = match params.parse(chrono::Utc::now()) {
+        Ok(parsed) => parsed,
+        Err(e) => return ApiError::bad_request(e),
+    };
+    let receiver = state.stats.receiver();
+    if matches!(mode, CoverageMode::Polar { .. }) && receiver.is_none() {
+        return ApiError::bad_request("polar coverage needs RECEIVER_LAT/RECEIVER_LON");
+    }
+
+    match state.db_writer.get_coverage(&query).await {
+        Ok(cells) => match (mode, receiver) {
+            (CoverageMode::Polar { sector_deg, ring_km }, Some(receiver)) => Json(coverage::polar(
+                &cells,
+                query.cell_deg,
+                receiver,
+                sector_deg,
+                ring_km,
+            ))
+            .into_response(),
+            _ => Json(coverage::grid(&cells, query.cell_deg)).into_response(),
+        },
+        Err(e) => {
+            error!("Failed to get coverage: {}", e);
+            ApiError::internal(e)
+        }
+    }
+}
+
+/// Get every SDR device's status
+#[utoipa::path(
+    get,
+    path = "/api/sdr/status",
+    tag = "receiver",
+    responses((status = 200, description = "Status of each device, keyed by device ID", body = BTreeMap<String, SdrStatus>)),
+    security((), ("api_key" = []), ("bearer" = []))
+)]
+async fn get_sdr_status(State(state): State<Arc<AppState>>, scope: Scope) -> impl IntoResponse {
+    match state.sdr_devices().await {
+        Ok(mut devices) => {
+            devices.retain(|device_id, _| scope.allows_device(device_id));
+            Json(devices).into_response()
+        }
         Err(e) => {
             error!("Failed to get SDR status: {}", e);
-            Json(serde_json::json!({"error": e.to_string()})).into_response()
+            ApiError::internal(e)
         }
     }
 }
+
+/// Get one SDR device's status
+#[utoipa::path(
+    get,
+    path = "/api/sdr/{device_id}/status",
+    tag = "receiver",
+    params(("device_id" = String, Path, description = "Capture device ID")),
+    responses(
+        (status = 200, description = "Device status", body = SdrStatus),
+        (status = 404, description = "Device has never reported", body = ApiError),
+    ),
+    security((), ("api_key" = []), ("bearer" = []))
+)]
+async fn get_sdr_device_status(
+    State(state): State<Arc<AppState>>,
+    scope: Scope,
+    Path(device_id): Path<String>,
+) -> impl IntoResponse {
+    if !scope.allows_device(&device_id) {
+        return ApiError::not_found("device not found");
+    }
+    match state.sdr_devices().await {
+        Ok(mut devices) => match devices.remove(&device_id) {
+            Some(status) => Json(status).into_response(),
+            None => ApiError::not_found("device not found"),
+        },
+        Err(e) => {
+            error!("Failed to get SDR status for {}: {}", device_id, e);
+            ApiError::internal(e)
+        }
+    }
+}
+
+/// Get one SDR device's status updates, most recent first
+#[utoipa::path(
+    get,
+    path = "/api/sdr/{device_id}/history",
+    tag = "receiver",
+    params(("device_id" = String, Path, description = "Capture device ID"), SdrHistoryParams),
+    responses((status = 200, description = "Heartbeats, most recent first", body = [SdrHeartbeat])),
+    security((), ("api_key" = []), ("bearer" = []))
+)]
+async fn get_sdr_history(
+    State(state): State<Arc<AppState>>,
+    scope: Scope,
+    Path(device_id): Path<String>,
+    Query(params): Query<SdrHistoryParams>,
+) -> impl IntoResponse {
+    if !scope.allows_device(&device_id) {
+        return Json(Vec::<SdrHeartbeat>::new()).into_response();
+    }
+    let hours = params.hours.unwrap_or(1).clamp(1, 24 * 7);
+    let limit = params.limit.unwrap_or(1000).clamp(1, 10_000);
+    match state.sdr_history(&device_id, hours, limit).await {
+        Ok(history) => Json(history).into_response(),
+        Err(e) => {
+            error!("Failed to get SDR history for {}: {}", device_id, e);
+            ApiError::internal(e)
+        }
+    }
+}
+
+/// Get recent signal reports from memory, oldest first
+#[utoipa::path(
+    get,
+    path = "/api/signal/live",
+    tag = "receiver",
+    params(SignalLiveParams),
+    responses((status = 200, description = "Signal reports, oldest first", body = [SignalSample])),
+    security((), ("api_key" = []), ("bearer" = []))
+)]
+async fn get_live_signal(
+    State(state): State<Arc<AppState>>,
+    scope: Scope,
+    Query(params): Query<SignalLiveParams>,
+) -> Json<Vec<SignalSample>> {
+    let seconds = params.seconds.unwrap_or(60).max(1);
+    let mut samples = state.signal.recent(
+        seconds,
+        params.device_id.as_deref(),
+        chrono::Utc::now().timestamp_millis(),
+    );
+    samples.retain(|sample| scope.allows_device(&sample.device_id));
+    Json(samples)
+}
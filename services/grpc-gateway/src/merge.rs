@@ -0,0 +1,167 @@
+//! Merging partial aircraft events
+//!
+//! Capture hosts leave out fields they haven't decoded, so an identification
+//! or altitude-only message arrives without a position or velocity. The last
+//! known value of every field is kept per aircraft and filled into later
+//! events before they are broadcast or stored, so a message without velocity
+//! no longer blanks out speed and track.
+
+use crate::adsb::AircraftEvent;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Prune the table beyond this many aircraft
+const MAX_ENTRIES: usize = 10_000;
+
+/// Aircraft not heard from for this long are forgotten when pruning
+const STALE_AFTER_MS: u64 = 5 * 60 * 1000;
+
+impl AircraftEvent {
+    /// Latitude and longitude, when the event has both
+    pub fn position(&self) -> Option<(f64, f64)> {
+        self.latitude.zip(self.longitude)
+    }
+
+    /// The capture host's smoothed track estimate, when it sends one
+    pub fn filtered_position(&self) -> Option<(f64, f64)> {
+        self.filtered_latitude.zip(self.filtered_longitude)
+    }
+}
+
+/// Last known fields per aircraft
+#[derive(Default)]
+pub struct EventMerger {
+    known: Mutex<HashMap<String, AircraftEvent>>,
+}
+
+impl EventMerger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The last squawk merged for `icao`
+    pub fn squawk(&self, icao: &str) -> Option<String> {
+        self.known.lock().ok()?.get(icao)?.squawk.clone()
+    }
+
+    /// `event` with absent fields filled from earlier events for the same
+    /// aircraft; the event's own fields are remembered for next time
+    pub fn merge(&self, event: &AircraftEvent) -> AircraftEvent {
+        let Ok(mut known) = self.known.lock() else {
+            return event.clone();
+        };
+        if event.removed {
+            known.remove(&event.icao);
+            return event.clone();
+        }
+        if known.len() >= MAX_ENTRIES && !known.contains_key(&event.icao) {
+            let now = event.timestamp_ms;
+            known.retain(|_, last| now.saturating_sub(last.timestamp_ms) < STALE_AFTER_MS);
+        }
+
+        let last = known.entry(event.icao.clone()).or_default();
+        let mut merged = event.clone();
+        fill(&mut merged.callsign, &last.callsign);
+        fill(&mut merged.altitude_ft, &last.altitude_ft);
+        fill(&mut merged.speed_kts, &last.speed_kts);
+        fill(&mut merged.track_deg, &last.track_deg);
+        fill(&mut merged.magnetic_heading_deg, &last.magnetic_heading_deg);
+        fill(&mut merged.vertical_rate_fpm, &last.vertical_rate_fpm);
+        fill(&mut merged.baro_rate_fpm, &last.baro_rate_fpm);
+        fill(&mut merged.geom_rate_fpm, &last.geom_rate_fpm);
+        fill(&mut merged.squawk, &last.squawk);
+        fill(&mut merged.category, &last.category);
+        fill(&mut merged.adsb_version, &last.adsb_version);
+        // Latitude and longitude only ever travel together, and with the
+        // position's quality
+        if merged.position().is_none() {
+            merged.latitude = last.latitude;
+            merged.longitude = last.longitude;
+            merged.nic = last.nic;
+            merged.nac_p = last.nac_p;
+            merged.nuc_p = last.nuc_p;
+            merged.containment_radius_m = last.containment_radius_m;
+        }
+        if merged.filtered_position().is_none() {
+            merged.filtered_latitude = last.filtered_latitude;
+            merged.filtered_longitude = last.filtered_longitude;
+        }
+        *last = merged.clone();
+        merged
+    }
+}
+
+fn fill<T: Clone>(field: &mut Option<T>, last: &Option<T>) {
+    if field.is_none() {
+        field.clone_from(last);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_keeps_known_fields() {
+        let merger = EventMerger::new();
+        let merged = merger.merge(&AircraftEvent {
+            icao: "71BE11".into(),
+            latitude: Some(37.46),
+            longitude: Some(126.44),
+            filtered_latitude: Some(37.4601),
+            filtered_longitude: Some(126.4402),
+            speed_kts: Some(250.0),
+            track_deg: Some(90.0),
+            nic: Some(8),
+            ..Default::default()
+        });
+        assert_eq!(merged.speed_kts, Some(250.0));
+
+        // An identification message carries neither velocity nor position
+        let merged = merger.merge(&AircraftEvent {
+            icao: "71BE11".into(),
+            callsign: Some("KAL123".into()),
+            ..Default::default()
+        });
+        assert_eq!(merged.callsign.as_deref(), Some("KAL123"));
+        assert_eq!(merged.speed_kts, Some(250.0));
+        assert_eq!(merged.track_deg, Some(90.0));
+        assert_eq!(merged.position(), Some((37.46, 126.44)));
+        assert_eq!(merged.filtered_position(), Some((37.4601, 126.4402)));
+        assert_eq!(merged.nic, Some(8));
+
+        // New values replace old ones
+        let merged = merger.merge(&AircraftEvent {
+            icao: "71BE11".into(),
+            speed_kts: Some(260.0),
+            ..Default::default()
+        });
+        assert_eq!(merged.speed_kts, Some(260.0));
+        assert_eq!(merged.callsign.as_deref(), Some("KAL123"));
+
+        // Other aircraft and timed-out aircraft start from nothing
+        assert_eq!(
+            merger
+                .merge(&AircraftEvent {
+                    icao: "71BE12".into(),
+                    ..Default::default()
+                })
+                .speed_kts,
+            None
+        );
+        merger.merge(&AircraftEvent {
+            icao: "71BE11".into(),
+            removed: true,
+            ..Default::default()
+        });
+        assert_eq!(
+            merger
+                .merge(&AircraftEvent {
+                    icao: "71BE11".into(),
+                    ..Default::default()
+                })
+                .speed_kts,
+            None
+        );
+    }
+}
@@ -0,0 +1,232 @@
+//! Prometheus metrics
+//!
+//! `/metrics` serves counters and gauges in the Prometheus text exposition
+//! format. Like `/health` it needs no credentials.
+
+use crate::db_health::{DbHealth, TableHealth};
+use crate::AppState;
+use axum::{extract::State, http::header, response::IntoResponse};
+use std::fmt::Write;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+#[derive(Clone, Copy)]
+enum Kind {
+    Counter,
+    Gauge,
+}
+
+fn metric(out: &mut String, name: &str, kind: Kind, help: &str, value: u64) {
+    let kind = match kind {
+        Kind::Counter => "counter",
+        Kind::Gauge => "gauge",
+    };
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+    let _ = writeln!(out, "{} {}", name, value);
+}
+
+/// Render every metric
+fn render(state: &AppState) -> String {
+    let fanout = &state.fanout;
+    let mut out = String::new();
+    metric(
+        &mut out,
+        "adsb_messages_received_total",
+        Kind::Counter,
+        "Aircraft events received from capture hosts",
+        state.stats.messages_total(),
+    );
+    metric(
+        &mut out,
+        "adsb_privacy_omitted_total",
+        Kind::Counter,
+        "Aircraft events dropped for the privacy list",
+        state.privacy.omitted.load(Ordering::Relaxed),
+    );
+    metric(
+        &mut out,
+        "adsb_privacy_coarsened_total",
+        Kind::Counter,
+        "Aircraft events whose position was coarsened for the privacy list",
+        state.privacy.coarsened.load(Ordering::Relaxed),
+    );
+    metric(
+        &mut out,
+        "adsb_udp_datagrams_sent_total",
+        Kind::Counter,
+        "Datagrams sent by the UDP event output",
+        state.udp.sent.load(Ordering::Relaxed),
+    );
+    metric(
+        &mut out,
+        "adsb_udp_datagrams_dropped_total",
+        Kind::Counter,
+        "Datagrams the UDP event output could not send at once",
+        state.udp.dropped.load(Ordering::Relaxed),
+    );
+    metric(
+        &mut out,
+        "adsb_ws_clients",
+        Kind::Gauge,
+        "Connected WebSocket clients",
+        fanout.clients() as u64,
+    );
+    metric(
+        &mut out,
+        "adsb_ws_queue_depth_max",
+        Kind::Gauge,
+        "Messages waiting in the fullest WebSocket client queue",
+        fanout.max_queue_depth() as u64,
+    );
+    metric(
+        &mut out,
+        "adsb_ws_messages_delivered_total",
+        Kind::Counter,
+        "Messages taken from WebSocket client queues",
+        fanout.metrics.delivered.load(Ordering::Relaxed),
+    );
+    metric(
+        &mut out,
+        "adsb_ws_messages_dropped_total",
+        Kind::Counter,
+        "Messages dropped because a WebSocket client fell behind",
+        fanout.metrics.dropped.load(Ordering::Relaxed),
+    );
+    metric(
+        &mut out,
+        "adsb_ws_slow_client_disconnects_total",
+        Kind::Counter,
+        "WebSocket clients disconnected for falling too far behind",
+        fanout.metrics.disconnected.load(Ordering::Relaxed),
+    );
+    let _ = writeln!(
+        out,
+        "# HELP adsb_rejected_values_total Implausible values removed from aircraft events"
+    );
+    let _ = writeln!(out, "# TYPE adsb_rejected_values_total counter");
+    for (device_id, reason, count) in state.sanity.rejected() {
+        let _ = writeln!(
+            out,
+            "adsb_rejected_values_total{{device_id=\"{}\",reason=\"{}\"}} {}",
+            label_value(&device_id),
+            reason,
+            count
+        );
+    }
+    let _ = writeln!(
+        out,
+        "# HELP adsb_receiver_rtt_ms Round trip of each capture host's last heartbeat"
+    );
+    let _ = writeln!(out, "# TYPE adsb_receiver_rtt_ms gauge");
+    for (device_id, rtt_ms) in state.receivers.round_trips() {
+        let _ = writeln!(out, "adsb_receiver_rtt_ms{{device_id=\"{}\"}} {}", label_value(&device_id), rtt_ms);
+    }
+    metric(
+        &mut out,
+        "adsb_db_write_retries_total",
+        Kind::Counter,
+        "Failed position writes retried",
+        state.dead_letters.retries.load(Ordering::Relaxed),
+    );
+    metric(
+        &mut out,
+        "adsb_dead_letters_total",
+        Kind::Counter,
+        "Position writes given up on after every retry",
+        state.dead_letters.dead.load(Ordering::Relaxed),
+    );
+    metric(
+        &mut out,
+        "adsb_dead_letters_dropped_total",
+        Kind::Counter,
+        "Dead letters not written to DEAD_LETTER_FILE",
+        state.dead_letters.dropped.load(Ordering::Relaxed),
+    );
+    if let Some(health) = state.db_health.latest() {
+        db_health(&mut out, &health);
+    }
+    out
+}
+
+/// Gauges from the last database health check
+fn db_health(out: &mut String, health: &DbHealth) {
+    let now = chrono::Utc::now();
+    table_gauge(out, "adsb_db_table_bytes", "Table size on disk, indexes included", health, |t| t.bytes);
+    table_gauge(out, "adsb_db_table_rows", "Estimated table rows", health, |t| t.rows);
+    table_gauge(out, "adsb_db_table_chunks", "TimescaleDB chunks or ClickHouse parts of a table", health, |t| {
+        t.chunks
+    });
+    table_gauge(out, "adsb_db_oldest_row_age_seconds", "Age of a table's oldest row", health, |t| {
+        t.oldest.map(|oldest| (now - oldest).num_seconds())
+    });
+    if let Some(pool) = &health.pool {
+        metric(out, "adsb_db_pool_connections", Kind::Gauge, "Open database connections", pool.size as u64);
+        metric(out, "adsb_db_pool_available", Kind::Gauge, "Idle database connections", pool.available as u64);
+        metric(
+            out,
+            "adsb_db_pool_waiting",
+            Kind::Gauge,
+            "Requests waiting for a database connection",
+            pool.waiting as u64,
+        );
+    }
+    metric(
+        out,
+        "adsb_db_retention_warnings",
+        Kind::Gauge,
+        "Tables whose oldest rows are well past their retention",
+        health.warnings.len() as u64,
+    );
+}
+
+/// A gauge per table, for tables with a value
+fn table_gauge(out: &mut String, name: &str, help: &str, health: &DbHealth, value: impl Fn(&TableHealth) -> Option<i64>) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} gauge", name);
+    for table in &health.tables {
+        if let Some(value) = value(table) {
+            let _ = writeln!(out, "{}{{table=\"{}\"}} {}", name, label_value(&table.name), value);
+        }
+    }
+}
+
+/// Escape a label value for the text format
+fn label_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// `GET /metrics`
+pub async fn metrics(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        render(&state),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_metric_format() {
+        let mut out = String::new();
+        metric(
+            &mut out,
+            "adsb_ws_clients",
+            Kind::Gauge,
+            "Connected WebSocket clients",
+            3,
+        );
+        assert_eq!(
+            out,
+            "# HELP adsb_ws_clients Connected WebSocket clients\n\
+             # TYPE adsb_ws_clients gauge\n\
+             adsb_ws_clients 3\n"
+        );
+        assert_eq!(label_value("rtl\"sdr\\0"), "rtl\\\"sdr\\\\0");
+    }
+}
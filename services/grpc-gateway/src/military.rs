@@ -0,0 +1,238 @@
+//! Military and special-purpose ICAO address blocks
+//!
+//! States reserve parts of their 24-bit address allocation for military
+//! aircraft, and ICAO keeps blocks of its own for temporary and special use.
+//! The blocks below are the commonly published ones; an address inside one
+//! is classed military or special. Published lists miss allocations and get
+//! some wrong, so an override file (`ADDRESS_OVERRIDES_PATH`) can reclassify
+//! single addresses or ranges, one per line:
+//!
+//! ```text
+//! # Coast guard, not in the published military blocks
+//! 7C0000-7C0FFF military
+//! AE1234 civil
+//! ```
+//!
+//! Overrides win over the built-in blocks; the first matching line wins
+//! among overrides.
+
+use anyhow::{anyhow, Result};
+use serde_json::Value as JsonValue;
+use std::path::Path;
+
+/// Inclusive `(first, last)` address ranges
+pub const MILITARY_RANGES: &[(u32, u32)] = &[
+    (0x010070, 0x01008F), // Egypt
+    (0x0A4000, 0x0A4FFF), // Algeria
+    (0x33FF00, 0x33FFFF), // Italy
+    (0x350000, 0x37FFFF), // Spain
+    (0x3AA000, 0x3AFFFF), // France
+    (0x3B7000, 0x3BFFFF), // France
+    (0x3EA000, 0x3EBFFF), // Germany
+    (0x3F4000, 0x3FBFFF), // Germany
+    (0x400000, 0x40003F), // United Kingdom
+    (0x43C000, 0x43CFFF), // United Kingdom
+    (0x444000, 0x446FFF), // Austria
+    (0x44F000, 0x44FFFF), // Belgium
+    (0x457000, 0x457FFF), // Bulgaria
+    (0x45F400, 0x45F4FF), // Denmark
+    (0x468000, 0x4683FF), // Greece
+    (0x473C00, 0x473C0F), // Hungary
+    (0x478100, 0x4781FF), // Norway
+    (0x480000, 0x480FFF), // Netherlands
+    (0x48D800, 0x48D87F), // Poland
+    (0x497C00, 0x497CFF), // Portugal
+    (0x498420, 0x49842F), // Czech Republic
+    (0x4B7000, 0x4B7FFF), // Switzerland
+    (0x4B8200, 0x4B82FF), // Turkey
+    (0x506F00, 0x506FFF), // Slovenia
+    (0x70C070, 0x70C07F), // Oman
+    (0x710258, 0x71028F), // Saudi Arabia
+    (0x710380, 0x71039F), // Saudi Arabia
+    (0x738A00, 0x738AFF), // Israel
+    (0x7CF800, 0x7CFAFF), // Australia
+    (0x800200, 0x8002FF), // India
+    (0xADF7C8, 0xAFFFFF), // United States
+    (0xC20000, 0xC3FFFF), // Canada
+    (0xE40000, 0xE41FFF), // Brazil
+];
+
+/// Inclusive `(first, last)` ranges ICAO keeps for its own allocations
+pub const SPECIAL_RANGES: &[(u32, u32)] = &[
+    (0xF00000, 0xF07FFF), // ICAO, temporary addresses
+    (0xF09000, 0xF093FF), // ICAO, special use
+];
+
+/// What an address is allocated for
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AddressClass {
+    #[default]
+    Civil,
+    Military,
+    Special,
+}
+
+impl AddressClass {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Civil => "civil",
+            Self::Military => "military",
+            Self::Special => "special",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "civil" => Some(Self::Civil),
+            "military" => Some(Self::Military),
+            "special" => Some(Self::Special),
+            _ => None,
+        }
+    }
+
+    /// Class from the built-in blocks alone
+    fn builtin(addr: u32) -> Self {
+        let within = |ranges: &[(u32, u32)]| {
+            ranges.iter().any(|&(first, last)| (first..=last).contains(&addr))
+        };
+        if within(MILITARY_RANGES) {
+            Self::Military
+        } else if within(SPECIAL_RANGES) {
+            Self::Special
+        } else {
+            Self::Civil
+        }
+    }
+}
+
+/// An override file line
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AddressOverride {
+    pub first: u32,
+    pub last: u32,
+    pub class: AddressClass,
+}
+
+/// Address classifier: the built-in blocks plus user overrides
+#[derive(Debug, Default)]
+pub struct AddressClasses {
+    overrides: Vec<AddressOverride>,
+}
+
+impl AddressClasses {
+    /// Built-in blocks only
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_overrides(overrides: Vec<AddressOverride>) -> Self {
+        Self { overrides }
+    }
+
+    /// Read an override file
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)?;
+        Ok(Self::with_overrides(parse_overrides(&text)?))
+    }
+
+    /// Number of override lines
+    pub fn len(&self) -> usize {
+        self.overrides.len()
+    }
+
+    /// Class of an ICAO hex address; anything unparseable is civil
+    pub fn classify(&self, icao: &str) -> AddressClass {
+        let Ok(addr) = u32::from_str_radix(icao.trim(), 16) else {
+            return AddressClass::Civil;
+        };
+        self.overrides
+            .iter()
+            .find(|o| (o.first..=o.last).contains(&addr))
+            .map(|o| o.class)
+            .unwrap_or_else(|| AddressClass::builtin(addr))
+    }
+
+    /// Add `military`/`special` flags to a JSON object that has an `icao` key
+    pub fn enrich(&self, value: &mut JsonValue) {
+        let class = match value.get("icao").and_then(|v| v.as_str()) {
+            Some(icao) => self.classify(icao),
+            None => return,
+        };
+        if let Some(obj) = value.as_object_mut() {
+            obj.insert("military".into(), (class == AddressClass::Military).into());
+            obj.insert("special".into(), (class == AddressClass::Special).into());
+        }
+    }
+}
+
+/// Parse override lines: an address or `FIRST-LAST` range, then a class.
+/// Blank lines and `#` comments are skipped
+pub fn parse_overrides(text: &str) -> Result<Vec<AddressOverride>> {
+    let mut overrides = Vec::new();
+    for (n, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        if line.is_empty() {
+            continue;
+        }
+        let bad = |what: &str| anyhow!("line {}: {}", n + 1, what);
+        let mut parts = line.split_whitespace();
+        let (Some(range), Some(class), None) = (parts.next(), parts.next(), parts.next()) else {
+            return Err(bad("expected an address or range and a class"));
+        };
+        let addr = |s: &str| u32::from_str_radix(s, 16).ok().filter(|&a| s.len() == 6 && a <= 0xFFFFFF);
+        let (first, last) = match range.split_once('-') {
+            Some((first, last)) => (addr(first), addr(last)),
+            None => (addr(range), addr(range)),
+        };
+        let (Some(first), Some(last)) = (first, last) else {
+            return Err(bad("addresses must be 6 hex digits"));
+        };
+        if first > last {
+            return Err(bad("range start must not exceed its end"));
+        }
+        let class = AddressClass::parse(class).ok_or_else(|| bad("class must be civil, military or special"))?;
+        overrides.push(AddressOverride { first, last, class });
+    }
+    Ok(overrides)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify() {
+        let classes = AddressClasses::new();
+        assert_eq!(classes.classify("AE1234"), AddressClass::Military);
+        assert_eq!(classes.classify("ae1234"), AddressClass::Military);
+        assert_eq!(classes.classify("43C0A1"), AddressClass::Military);
+        assert_eq!(classes.classify("F00123"), AddressClass::Special);
+        assert_eq!(classes.classify("71BE11"), AddressClass::Civil);
+        assert_eq!(classes.classify("A12345"), AddressClass::Civil);
+        assert_eq!(classes.classify("not-hex"), AddressClass::Civil);
+    }
+
+    #[test]
+    fn test_overrides() {
+        let overrides = parse_overrides(
+            "# local knowledge\n\nAE1234 civil\n7C0000-7C0FFF Military  # coast guard\nAE0000-AEFFFF special\n",
+        )
+        .unwrap();
+        assert_eq!(overrides.len(), 3);
+        let classes = AddressClasses::with_overrides(overrides);
+        assert_eq!(classes.classify("AE1234"), AddressClass::Civil);
+        assert_eq!(classes.classify("7C0ABC"), AddressClass::Military);
+        // The first matching line wins, the built-in blocks apply elsewhere
+        assert_eq!(classes.classify("AE1235"), AddressClass::Special);
+        assert_eq!(classes.classify("43C0A1"), AddressClass::Military);
+
+        let mut aircraft = serde_json::json!({"icao": "7C0ABC"});
+        classes.enrich(&mut aircraft);
+        assert_eq!((aircraft["military"].as_bool(), aircraft["special"].as_bool()), (Some(true), Some(false)));
+
+        assert!(parse_overrides("AE1234").is_err());
+        assert!(parse_overrides("AE12 military").is_err());
+        assert!(parse_overrides("AEFFFF-AE0000 military").is_err());
+        assert!(parse_overrides("AE1234 navy").is_err());
+    }
+}
@@ -0,0 +1,342 @@
+//! Notifier sinks for alerts
+//!
+//! Alerts are delivered to generic webhooks, Discord, Slack or Telegram.
+//! Each sink renders a text template against the alert payload
+//! (`{rule_name}`, `{icao}`, `{callsign}`, `{registration}`, `{squawk}`,
+//! `{altitude}`, `{lat}`, `{lon}`, `{match}`, ...) and retries transient
+//! failures with exponential backoff. Sinks are configured per watchlist
+//! rule, with gateway-wide defaults for alerts that have no rule.
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{debug, warn};
+use utoipa::ToSchema;
+
+/// Template used when a sink doesn't set one
+pub const DEFAULT_TEMPLATE: &str =
+    "{rule_name}: {callsign} ({icao}) {registration} squawk {squawk}, {altitude} ft";
+
+/// Delivery attempts per notification
+const MAX_ATTEMPTS: u32 = 4;
+
+/// Delay before the first retry (doubles each attempt)
+const INITIAL_BACKOFF_MS: u64 = 1000;
+
+/// Configuration for a single notifier sink
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum NotifierConfig {
+    /// POST to any URL; the alert JSON, or the rendered template if set
+    Webhook {
+        url: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        template: Option<String>,
+    },
+    /// Discord channel webhook
+    Discord {
+        url: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        template: Option<String>,
+    },
+    /// Slack incoming webhook
+    Slack {
+        url: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        template: Option<String>,
+    },
+    /// Telegram bot message
+    Telegram {
+        bot_token: String,
+        chat_id: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        template: Option<String>,
+    },
+}
+
+impl NotifierConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            Self::Webhook { url, .. } | Self::Discord { url, .. } | Self::Slack { url, .. } => {
+                if !(url.starts_with("http://") || url.starts_with("https://")) {
+                    return Err(format!("invalid notifier url: {}", url));
+                }
+            }
+            Self::Telegram {
+                bot_token, chat_id, ..
+            } => {
+                if bot_token.is_empty() || chat_id.is_empty() {
+                    return Err("telegram notifier needs bot_token and chat_id".into());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Build the sink for this configuration
+    pub fn build(&self) -> Box<dyn Notifier> {
+        match self.clone() {
+            Self::Webhook { url, template } => Box::new(WebhookNotifier { url, template }),
+            Self::Discord { url, template } => Box::new(ChatNotifier {
+                name: "discord",
+                url,
+                text_field: "content",
+                template,
+            }),
+            Self::Slack { url, template } => Box::new(ChatNotifier {
+                name: "slack",
+                url,
+                text_field: "text",
+                template,
+            }),
+            Self::Telegram {
+                bot_token,
+                chat_id,
+                template,
+            } => Box::new(TelegramNotifier {
+                bot_token,
+                chat_id,
+                template,
+            }),
+        }
+    }
+}
+
+/// A destination for alert notifications
+#[tonic::async_trait]
+pub trait Notifier: Send + Sync {
+    /// Sink name for logs
+    fn name(&self) -> &'static str;
+
+    /// Deliver one alert (single attempt)
+    async fn send(&self, http: &reqwest::Client, alert: &JsonValue) -> Result<()>;
+}
+
+/// Generic JSON/text webhook
+struct WebhookNotifier {
+    url: String,
+    template: Option<String>,
+}
+
+#[tonic::async_trait]
+impl Notifier for WebhookNotifier {
+    fn name(&self) -> &'static str {
+        "webhook"
+    }
+
+    async fn send(&self, http: &reqwest::Client, alert: &JsonValue) -> Result<()> {
+        let request = match &self.template {
+            // Templates that render to JSON are sent as JSON, anything else as text
+            Some(template) => {
+                let body = render(template, alert);
+                let content_type = if serde_json::from_str::<JsonValue>(&body).is_ok() {
+                    "application/json"
+                } else {
+                    "text/plain; charset=utf-8"
+                };
+                http.post(&self.url)
+                    .header(reqwest::header::CONTENT_TYPE, content_type)
+                    .body(body)
+            }
+            None => http.post(&self.url).json(alert),
+        };
+        check(request.send().await?)
+    }
+}
+
+/// Discord/Slack style webhook taking a single text field
+struct ChatNotifier {
+    name: &'static str,
+    url: String,
+    text_field: &'static str,
+    template: Option<String>,
+}
+
+#[tonic::async_trait]
+impl Notifier for ChatNotifier {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    async fn send(&self, http: &reqwest::Client, alert: &JsonValue) -> Result<()> {
+        let text = render(self.template.as_deref().unwrap_or(DEFAULT_TEMPLATE), alert);
+        let body = serde_json::json!({ self.text_field: text });
+        check(http.post(&self.url).json(&body).send().await?)
+    }
+}
+
+/// Telegram Bot API `sendMessage`
+struct TelegramNotifier {
+    bot_token: String,
+    chat_id: String,
+    template: Option<String>,
+}
+
+#[tonic::async_trait]
+impl Notifier for TelegramNotifier {
+    fn name(&self) -> &'static str {
+        "telegram"
+    }
+
+    async fn send(&self, http: &reqwest::Client, alert: &JsonValue) -> Result<()> {
+        let url = format!("https://api.telegram.org/bot{}/sendMessage", self.bot_token);
+        let body = serde_json::json!({
+            "chat_id": self.chat_id,
+            "text": render(self.template.as_deref().unwrap_or(DEFAULT_TEMPLATE), alert),
+        });
+        check(http.post(&url).json(&body).send().await?)
+    }
+}
+
+/// Map HTTP status to a result
+fn check(response: reqwest::Response) -> Result<()> {
+    let status = response.status();
+    if status.is_success() {
+        Ok(())
+    } else {
+        Err(anyhow!("HTTP {}", status))
+    }
+}
+
+/// Substitute `{field}` placeholders with values from the alert payload.
+/// Missing or null fields render as `-`.
+pub fn render(template: &str, alert: &JsonValue) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        match after.find('}') {
+            Some(end)
+                if after[..end]
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_')
+                    && end > 0 =>
+            {
+                let key = &after[..end];
+                match alert.get(key) {
+                    Some(JsonValue::String(s)) if !s.is_empty() => out.push_str(s.trim()),
+                    Some(JsonValue::Null) | Some(JsonValue::String(_)) | None => out.push('-'),
+                    Some(other) => out.push_str(&other.to_string()),
+                }
+                rest = &after[end + 1..];
+            }
+            // Not a placeholder (e.g. a JSON brace)
+            _ => {
+                out.push('{');
+                rest = after;
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Delivers alerts to sinks in the background with retry
+pub struct Dispatcher {
+    http: reqwest::Client,
+    defaults: Vec<NotifierConfig>,
+}
+
+impl Dispatcher {
+    pub fn new(defaults: Vec<NotifierConfig>) -> Self {
+        Self {
+            http: reqwest::Client::builder()
+                .timeout(Duration::from_secs(10))
+                .build()
+                .unwrap_or_default(),
+            defaults,
+        }
+    }
+
+    /// Send an alert to the given sinks, or the defaults if none are given
+    pub fn dispatch(&self, alert: JsonValue, sinks: &[NotifierConfig]) {
+        let sinks = if sinks.is_empty() { &self.defaults } else { sinks };
+        if sinks.is_empty() {
+            return;
+        }
+
+        let alert = Arc::new(alert);
+        for config in sinks {
+            let notifier = config.build();
+            let http = self.http.clone();
+            let alert = alert.clone();
+            tokio::spawn(async move {
+                deliver(notifier.as_ref(), &http, &alert).await;
+            });
+        }
+    }
+}
+
+/// Send with exponential backoff
+async fn deliver(notifier: &dyn Notifier, http: &reqwest::Client, alert: &JsonValue) {
+    let mut backoff = Duration::from_millis(INITIAL_BACKOFF_MS);
+    for attempt in 1..=MAX_ATTEMPTS {
+        match notifier.send(http, alert).await {
+            Ok(()) => {
+                debug!("Alert delivered via {}", notifier.name());
+                return;
+            }
+            Err(e) if attempt < MAX_ATTEMPTS => {
+                debug!(
+                    "Alert via {} failed (attempt {}): {}, retrying in {:?}",
+                    notifier.name(),
+                    attempt,
+                    e,
+                    backoff
+                );
+                tokio::time::sleep(backoff).await;
+                backoff *= 2;
+            }
+            Err(e) => {
+                warn!(
+                    "Alert via {} failed after {} attempts: {}",
+                    notifier.name(),
+                    MAX_ATTEMPTS,
+                    e
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let alert = serde_json::json!({
+            "rule_name": "Emergency",
+            "icao": "71BE11",
+            "callsign": "KAL017  ",
+            "registration": null,
+            "altitude": 3500,
+        });
+        assert_eq!(
+            render("{rule_name}: {callsign} ({icao}) {registration} {altitude} ft", &alert),
+            "Emergency: KAL017 (71BE11) - 3500 ft"
+        );
+        assert_eq!(render("{unknown}", &alert), "-");
+        assert_eq!(
+            render(r#"{"text": "{icao}"}"#, &alert),
+            r#"{"text": "71BE11"}"#
+        );
+    }
+
+    #[test]
+    fn test_config_serde() {
+        let config: NotifierConfig = serde_json::from_str(
+            r#"{"type": "telegram", "bot_token": "123:abc", "chat_id": "42"}"#,
+        )
+        .unwrap();
+        assert!(config.validate().is_ok());
+        assert_eq!(config.build().name(), "telegram");
+
+        let bad: NotifierConfig =
+            serde_json::from_str(r#"{"type": "slack", "url": "hooks.slack.com"}"#).unwrap();
+        assert!(bad.validate().is_err());
+    }
+}
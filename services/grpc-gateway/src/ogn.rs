@@ -0,0 +1,280 @@
+//! Open Glider Network input
+//!
+//! Gliders, tow planes, paragliders and much of the light traffic around
+//! airfields carry FLARM rather than ADS-B out. With `OGN_APRS_SERVER` set
+//! (e.g. `aprs.glidernet.org:14580`), the gateway logs in read-only to the
+//! OGN APRS network, asks for the traffic within `OGN_RANGE_KM` of the
+//! receiver location (or `OGN_FILTER`, an APRS-IS server-side filter), and
+//! runs every aircraft beacon through the same pipeline as a host's events,
+//! as device `ogn` with `source` set to `ogn` on its position updates.
+//!
+//! Devices transmitting their ICAO address merge with ADS-B reports of the
+//! same aircraft. FLARM, OGN tracker and random addresses get the `~`
+//! prefix of other non-ICAO addresses, so they are shown live but not
+//! stored. Beacons with the no-tracking flag are dropped. Altitudes are GPS
+//! altitude above sea level, not pressure altitude.
+
+use crate::adsb::AircraftEvent;
+use crate::grpc_server::GatewayService;
+use crate::uptime::StreamDevices;
+use crate::AppState;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tracing::{debug, info, warn};
+
+/// Device ID and event source of OGN traffic
+pub const SOURCE: &str = "ogn";
+
+/// APRS-IS servers drop clients silent for longer than this
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(240);
+
+/// Reconnect when the server sends nothing for this long
+const READ_TIMEOUT: Duration = Duration::from_secs(300);
+
+const RECONNECT_DELAY: Duration = Duration::from_secs(30);
+
+/// One aircraft beacon
+#[derive(Debug, Clone, PartialEq)]
+pub struct Beacon {
+    /// Device address, 24 bits
+    pub address: u32,
+    /// 0 random, 1 ICAO, 2 FLARM, 3 OGN tracker
+    pub address_type: u8,
+    /// OGN aircraft type (1 glider, 3 helicopter, 7 paraglider, ...)
+    pub aircraft_type: u8,
+    pub no_tracking: bool,
+    pub latitude: f64,
+    pub longitude: f64,
+    pub altitude_ft: Option<i32>,
+    pub track_deg: Option<f32>,
+    pub speed_kts: Option<f32>,
+    pub climb_fpm: Option<i32>,
+}
+
+impl Beacon {
+    /// Event address: the ICAO address as is, anything else `~`-prefixed
+    pub fn icao(&self) -> String {
+        match self.address_type {
+            1 => format!("{:06X}", self.address),
+            _ => format!("~{:06X}", self.address),
+        }
+    }
+
+    /// ADS-B emitter category closest to the OGN aircraft type
+    pub fn category(&self) -> Option<&'static str> {
+        match self.aircraft_type {
+            1 => Some("B1"),
+            3 => Some("A7"),
+            4 => Some("B3"),
+            6 | 7 => Some("B4"),
+            2 | 5 | 8 => Some("A1"),
+            11 | 12 => Some("B2"),
+            13 => Some("B6"),
+            _ => None,
+        }
+    }
+
+    pub fn to_event(&self, timestamp_ms: u64) -> AircraftEvent {
+        AircraftEvent {
+            device_id: SOURCE.to_string(),
+            timestamp_ms,
+            icao: self.icao(),
+            altitude_ft: self.altitude_ft,
+            latitude: Some(self.latitude),
+            longitude: Some(self.longitude),
+            speed_kts: self.speed_kts,
+            track_deg: self.track_deg,
+            vertical_rate_fpm: self.climb_fpm,
+            geom_rate_fpm: self.climb_fpm,
+            category: self.category().map(str::to_string),
+            source: Some(SOURCE.to_string()),
+            ..Default::default()
+        }
+    }
+}
+
+/// Parse an APRS position line from OGN; `None` for server comments,
+/// receiver beacons and anything else that isn't an aircraft
+pub fn parse_line(line: &str) -> Option<Beacon> {
+    if line.starts_with('#') {
+        return None;
+    }
+    let (_, payload) = line.split_once(':')?;
+    // Position with a timestamp (`/` or `@`, then hhmmssh) or without
+    let body = match payload.chars().next()? {
+        '/' | '@' => payload.get(8..)?,
+        '!' | '=' => payload.get(1..)?,
+        _ => return None,
+    };
+    // DDMM.mmN, symbol table, DDDMM.mmE, symbol code
+    let lat_minutes = coordinate(body.get(..7)?, 2)?;
+    let lat_hemisphere = body.get(7..8)?;
+    let lon_minutes = coordinate(body.get(9..17)?, 3)?;
+    let lon_hemisphere = body.get(17..18)?;
+    let mut rest = body.get(19..)?;
+
+    let (mut track_deg, mut speed_kts) = (None, None);
+    if let Some((course, speed)) = rest.get(..7).and_then(|cs| cs.split_once('/')) {
+        if let (Ok(course), Ok(speed)) = (course.parse::<u16>(), speed.parse::<u16>()) {
+            // Course 0 is unknown, 360 is north
+            track_deg = (course > 0).then_some((course % 360) as f32);
+            speed_kts = Some(speed as f32);
+            rest = &rest[7..];
+        }
+    }
+    let mut altitude_ft = None;
+    if let Some(alt) = rest.strip_prefix("/A=") {
+        altitude_ft = alt.get(..6).and_then(|a| a.parse().ok());
+        rest = alt.get(6..).unwrap_or_default();
+    }
+
+    let mut beacon = None;
+    let (mut lat_extra, mut lon_extra) = (0.0, 0.0);
+    let mut climb_fpm = None;
+    for field in rest.split_whitespace() {
+        if let Some(id) = field.strip_prefix("id").filter(|id| id.len() == 8) {
+            let flags = u8::from_str_radix(id.get(..2)?, 16).ok()?;
+            let address = u32::from_str_radix(id.get(2..)?, 16).ok()?;
+            beacon = Some((flags, address));
+        } else if let Some(fpm) = field.strip_suffix("fpm") {
+            climb_fpm = fpm.parse::<f64>().ok().map(|v| v.round() as i32);
+        } else if let Some(dao) = field.strip_prefix("!W").and_then(|f| f.strip_suffix('!')) {
+            // Precision enhancement: a third decimal of minutes
+            let mut digits = dao.chars().filter_map(|c| c.to_digit(10));
+            lat_extra = digits.next().unwrap_or(0) as f64 / 1000.0;
+            lon_extra = digits.next().unwrap_or(0) as f64 / 1000.0;
+        }
+    }
+    let (flags, address) = beacon?;
+
+    let degrees = |(deg, min): (f64, f64), extra: f64, negative: bool| {
+        let value = deg + (min + extra) / 60.0;
+        if negative {
+            -value
+        } else {
+            value
+        }
+    };
+    Some(Beacon {
+        address,
+        address_type: flags & 0x03,
+        aircraft_type: (flags >> 2) & 0x0F,
+        no_tracking: flags & 0x40 != 0,
+        latitude: degrees(lat_minutes, lat_extra, lat_hemisphere == "S"),
+        longitude: degrees(lon_minutes, lon_extra, lon_hemisphere == "W"),
+        altitude_ft,
+        track_deg,
+        speed_kts,
+        climb_fpm,
+    })
+}
+
+/// Degrees and minutes of `DDMM.mm` / `DDDMM.mm`
+fn coordinate(text: &str, degree_digits: usize) -> Option<(f64, f64)> {
+    let degrees: f64 = text.get(..degree_digits)?.parse().ok()?;
+    let minutes: f64 = text.get(degree_digits..)?.parse().ok()?;
+    Some((degrees, minutes))
+}
+
+/// Follow OGN traffic from `server`, reconnecting when the link drops
+pub fn spawn(server: String, user: String, filter: String, state: Arc<AppState>) {
+    tokio::spawn(async move {
+        let service = GatewayService::new(state.clone());
+        loop {
+            match follow(&server, &user, &filter, &state, &service).await {
+                Ok(()) => warn!("OGN APRS server {} closed the connection", server),
+                Err(e) => warn!("OGN APRS connection to {} failed: {}", server, e),
+            }
+            tokio::time::sleep(RECONNECT_DELAY).await;
+        }
+    });
+}
+
+/// One connection: log in, then ingest beacons until it ends
+async fn follow(
+    server: &str,
+    user: &str,
+    filter: &str,
+    state: &Arc<AppState>,
+    service: &GatewayService,
+) -> std::io::Result<()> {
+    let stream = TcpStream::connect(server).await?;
+    let (reader, mut writer) = stream.into_split();
+    let login = format!(
+        "user {} pass -1 vers adsb-gateway {} filter {}\r\n",
+        user,
+        env!("CARGO_PKG_VERSION"),
+        filter
+    );
+    writer.write_all(login.as_bytes()).await?;
+    info!("Following OGN traffic from {} (filter {})", server, filter);
+
+    let mut lines = BufReader::new(reader).lines();
+    let mut devices = StreamDevices::new(state.receivers.clone());
+    let mut keepalive = tokio::time::interval(KEEPALIVE_INTERVAL);
+    keepalive.tick().await;
+    let mut beacons = 0u64;
+    loop {
+        tokio::select! {
+            line = tokio::time::timeout(READ_TIMEOUT, lines.next_line()) => {
+                let Some(line) = line.map_err(|_| std::io::ErrorKind::TimedOut)?? else {
+                    return Ok(());
+                };
+                let Some(beacon) = parse_line(&line) else {
+                    continue;
+                };
+                if beacon.no_tracking {
+                    continue;
+                }
+                beacons += 1;
+                if beacons.is_multiple_of(1000) {
+                    debug!("OGN beacons received: {}", beacons);
+                }
+                let event = beacon.to_event(chrono::Utc::now().timestamp_millis() as u64);
+                service.ingest(event, &mut devices).await;
+            }
+            _ = keepalive.tick() => {
+                writer.write_all(b"#keepalive\r\n").await?;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_beacon() {
+        let glider = "FLRDDA5BA>OGFLR,qAS,LFMX:/165829h4415.41N/00600.03E'342/049/A=005524 !W52! id0ADDA5BA -454fpm -1.1rot 8.0dB 0e +51.2kHz gps4x5";
+        let beacon = parse_line(glider).unwrap();
+        assert_eq!((beacon.address, beacon.address_type, beacon.aircraft_type), (0xDDA5BA, 2, 2));
+        assert!((beacon.latitude - (44.0 + 15.415 / 60.0)).abs() < 1e-9);
+        assert!((beacon.longitude - (6.0 + 0.032 / 60.0)).abs() < 1e-9);
+        assert_eq!(beacon.altitude_ft, Some(5524));
+        assert_eq!((beacon.track_deg, beacon.speed_kts), (Some(342.0), Some(49.0)));
+        assert_eq!(beacon.climb_fpm, Some(-454));
+        let event = beacon.to_event(1);
+        assert_eq!(event.icao, "~DDA5BA");
+        assert_eq!(event.category.as_deref(), Some("A1"));
+        assert_eq!(event.source.as_deref(), Some("ogn"));
+
+        // ICAO-addressed paraglider south and west, no track, no-tracking flag set
+        let icao = "ICA4840D6>OGFLR,qAS,Bar:/080000h3352.50S/15112.25W^000/000/A=000150 id5D4840D6 +0fpm";
+        let beacon = parse_line(icao).unwrap();
+        assert_eq!(beacon.icao(), "4840D6");
+        assert_eq!(beacon.category(), Some("B4"));
+        assert!(beacon.no_tracking);
+        assert!((beacon.latitude + 33.875).abs() < 1e-9);
+        assert!((beacon.longitude + 151.204_166_666).abs() < 1e-6);
+        assert_eq!(beacon.track_deg, None);
+
+        // Receiver status and server comments
+        assert!(parse_line("LFMX>OGNSDR,TCPIP*,qAC,GLIDERN2:/165830h4415.87NI00600.10E&/A=001990").is_none());
+        assert!(parse_line("# aprsc 2.1.4-g408ed49").is_none());
+        // Multi-byte characters in the id field don't panic
+        assert!(parse_line("FLRDDA5BA>OGFLR,qAS,LFMX:/165829h4415.41N/00600.03E'342/049/A=005524 idAéDDA5B").is_none());
+    }
+}
@@ -0,0 +1,169 @@
+//! Aircraft presence tracking
+//!
+//! Remembers which devices have recently reported each aircraft so clients
+//! can be told when one leaves the current view. An `aircraft_removed`
+//! message is broadcast when every device reporting the aircraft has timed
+//! it out in its tracker, or when nothing has been heard from it for as
+//! long as the `current_aircraft` view looks back.
+
+use crate::adsb::AircraftEvent;
+use crate::pubsub::{LiveMessage, PubSub};
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::debug;
+
+/// Matches the `current_aircraft` look-back
+pub const STALE_SECS: i64 = 300;
+
+/// How often silent aircraft are swept
+const SWEEP_INTERVAL_SECS: u64 = 15;
+
+/// Why an aircraft was removed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RemovalReason {
+    /// Every reporting device's tracker dropped it
+    Tracker,
+    /// Not heard from for `STALE_SECS`
+    Timeout,
+}
+
+impl RemovalReason {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Tracker => "tracker",
+            Self::Timeout => "timeout",
+        }
+    }
+}
+
+/// Last report per device for each aircraft in view
+pub struct Presence {
+    pubsub: Arc<dyn PubSub>,
+    seen: Mutex<HashMap<String, HashMap<String, DateTime<Utc>>>>,
+}
+
+impl Presence {
+    pub fn new(pubsub: Arc<dyn PubSub>) -> Self {
+        Self {
+            pubsub,
+            seen: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Record an event; tracker removals broadcast `aircraft_removed` once no
+    /// other device still has the aircraft
+    pub fn observe(&self, event: &AircraftEvent, now: DateTime<Utc>) {
+        let Ok(mut seen) = self.seen.lock() else {
+            return;
+        };
+        if !event.removed {
+            seen.entry(event.icao.clone())
+                .or_default()
+                .insert(event.device_id.clone(), now);
+            return;
+        }
+
+        let Some(devices) = seen.get_mut(&event.icao) else {
+            return;
+        };
+        devices.remove(&event.device_id);
+        devices.retain(|_, last| (now - *last).num_seconds() < STALE_SECS);
+        if devices.is_empty() {
+            seen.remove(&event.icao);
+            drop(seen);
+            self.publish(&event.icao, RemovalReason::Tracker);
+        }
+    }
+
+    /// Forget aircraft silent for `STALE_SECS`, returning their ICAOs
+    fn take_stale(&self, now: DateTime<Utc>) -> Vec<String> {
+        let Ok(mut seen) = self.seen.lock() else {
+            return Vec::new();
+        };
+        let mut stale = Vec::new();
+        seen.retain(|icao, devices| {
+            let last = devices.values().max().copied();
+            let gone = last.is_none_or(|last| (now - last).num_seconds() >= STALE_SECS);
+            if gone {
+                stale.push(icao.clone());
+            }
+            !gone
+        });
+        stale
+    }
+
+    fn publish(&self, icao: &str, reason: RemovalReason) {
+        debug!("Aircraft {} removed ({})", icao, reason.name());
+        let msg = serde_json::json!({
+            "type": "aircraft_removed",
+            "icao": icao,
+            "reason": reason.name(),
+        });
+        self.pubsub.publish(LiveMessage::from_value(&msg));
+    }
+}
+
+/// Periodically time out silent aircraft
+pub fn spawn_sweeper(presence: Arc<Presence>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(SWEEP_INTERVAL_SECS));
+        loop {
+            interval.tick().await;
+            for icao in presence.take_stale(Utc::now()) {
+                presence.publish(&icao, RemovalReason::Timeout);
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pubsub::LocalPubSub;
+
+    fn event(device: &str, removed: bool) -> AircraftEvent {
+        AircraftEvent {
+            icao: "71BE11".into(),
+            device_id: device.into(),
+            removed,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_tracker_removal_waits_for_all_devices() {
+        let pubsub = Arc::new(LocalPubSub::new());
+        let mut rx = pubsub.subscribe();
+        let presence = Presence::new(pubsub);
+        let now = Utc::now();
+        presence.observe(&event("rtlsdr-0", false), now);
+        presence.observe(&event("rtlsdr-1", false), now);
+
+        presence.observe(&event("rtlsdr-0", true), now);
+        assert!(rx.try_recv().is_err());
+
+        presence.observe(&event("rtlsdr-1", true), now);
+        let msg: serde_json::Value = serde_json::from_str(&rx.try_recv().unwrap().json).unwrap();
+        assert_eq!(msg["type"], "aircraft_removed");
+        assert_eq!(msg["reason"], "tracker");
+
+        // Already gone: nothing more to say
+        presence.observe(&event("rtlsdr-1", true), now);
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_timeout() {
+        let presence = Presence::new(Arc::new(LocalPubSub::new()));
+        let now = Utc::now();
+        presence.observe(&event("rtlsdr-0", false), now);
+        assert!(presence.take_stale(now + chrono::Duration::seconds(60)).is_empty());
+        assert_eq!(
+            presence.take_stale(now + chrono::Duration::seconds(STALE_SECS)),
+            vec!["71BE11".to_string()]
+        );
+        assert!(presence.take_stale(now + chrono::Duration::seconds(STALE_SECS)).is_empty());
+    }
+}
@@ -0,0 +1,285 @@
+//! Privacy list (LADD style)
+//!
+//! Aircraft whose owners asked not to be tracked publicly, e.g. through the
+//! FAA's LADD program, are listed in `PRIVACY_LIST_PATH` by ICAO address or
+//! registration (looked up in the aircraft database), one per line:
+//!
+//! ```text
+//! # address or registration, then optionally omit or coarsen
+//! A1B2C3
+//! N123AB coarsen
+//! ```
+//!
+//! Events from omitted aircraft are dropped on arrival, before anything
+//! stores, broadcasts or tracks them; coarsened ones have their positions
+//! rounded to `PRIVACY_GRID_DEG` and the raw frame behind them removed.
+//! Raw frames from listed aircraft are never archived. Entries without an
+//! action take `PRIVACY_ACTION`. Each aircraft withheld is logged the first
+//! time it is heard, and `/metrics` counts the events withheld.
+
+use crate::adsb::AircraftEvent;
+use crate::aircraft_db::AircraftDb;
+use crate::config::PrivacyAction;
+use anyhow::{anyhow, Result};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use tracing::info;
+
+/// Mode S CRC-24 generator polynomial
+const CRC_POLY: u32 = 0x1FFF409;
+
+/// Parsed privacy list with counts of what it withheld
+#[derive(Debug)]
+pub struct PrivacyList {
+    addresses: HashMap<u32, PrivacyAction>,
+    /// Upper case, without dashes
+    registrations: HashMap<String, PrivacyAction>,
+    grid_deg: f64,
+    /// Addresses already logged, including those matched by registration
+    seen: Mutex<HashMap<u32, PrivacyAction>>,
+    pub omitted: AtomicU64,
+    pub coarsened: AtomicU64,
+}
+
+impl PrivacyList {
+    /// An empty list, withholding nothing
+    pub fn new(grid_deg: f64) -> Self {
+        Self {
+            addresses: HashMap::new(),
+            registrations: HashMap::new(),
+            grid_deg,
+            seen: Mutex::new(HashMap::new()),
+            omitted: AtomicU64::new(0),
+            coarsened: AtomicU64::new(0),
+        }
+    }
+
+    /// Read the list at `path`; entries without an action take `default`
+    pub fn load(path: &Path, default: PrivacyAction, grid_deg: f64) -> Result<Self> {
+        let text = std::fs::read_to_string(path)?;
+        Self::parse(&text, default, grid_deg)
+    }
+
+    pub fn parse(text: &str, default: PrivacyAction, grid_deg: f64) -> Result<Self> {
+        let mut list = Self::new(grid_deg);
+        for (n, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let bad = |what: &str| anyhow!("line {}: {}", n + 1, what);
+            let mut parts = line.split_whitespace();
+            let (Some(key), action, None) = (parts.next(), parts.next(), parts.next()) else {
+                return Err(bad("expected an address or registration and an optional action"));
+            };
+            let action = match action {
+                Some(a) => PrivacyAction::parse(a).ok_or_else(|| bad("action must be omit or coarsen"))?,
+                None => default,
+            };
+            match u32::from_str_radix(key, 16) {
+                Ok(addr) if key.len() == 6 => {
+                    list.addresses.insert(addr, action);
+                }
+                _ => {
+                    list.registrations.insert(normalize_registration(key), action);
+                }
+            }
+        }
+        Ok(list)
+    }
+
+    /// Number of entries
+    pub fn len(&self) -> usize {
+        self.addresses.len() + self.registrations.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Action for an event's aircraft, by address or registration
+    fn action(&self, addr: u32, db: &AircraftDb, icao: &str) -> Option<(PrivacyAction, String)> {
+        if let Some(&action) = self.addresses.get(&addr) {
+            return Some((action, "address".into()));
+        }
+        if self.registrations.is_empty() {
+            return None;
+        }
+        let registration = db.lookup(icao)?.registration?;
+        let action = *self.registrations.get(&normalize_registration(&registration))?;
+        Some((action, format!("registration {}", registration)))
+    }
+
+    /// Withhold what the list asks for; `false` when the event must be
+    /// dropped
+    pub fn apply(&self, event: &mut AircraftEvent, db: &AircraftDb) -> bool {
+        if self.is_empty() {
+            return true;
+        }
+        let Ok(addr) = u32::from_str_radix(&event.icao, 16) else {
+            return true;
+        };
+        let Some((action, reason)) = self.action(addr, db, &event.icao) else {
+            return true;
+        };
+        if let Ok(mut seen) = self.seen.lock() {
+            if seen.insert(addr, action).is_none() {
+                info!(
+                    "Privacy list: {} ({}) {}",
+                    event.icao,
+                    reason,
+                    match action {
+                        PrivacyAction::Omit => "omitted",
+                        PrivacyAction::Coarsen => "positions coarsened",
+                    }
+                );
+            }
+        }
+        match action {
+            PrivacyAction::Omit => {
+                self.omitted.fetch_add(1, Ordering::Relaxed);
+                false
+            }
+            PrivacyAction::Coarsen => {
+                let grid = |v: Option<f64>| v.map(|v| (v / self.grid_deg).round() * self.grid_deg);
+                event.latitude = grid(event.latitude);
+                event.longitude = grid(event.longitude);
+                event.filtered_latitude = grid(event.filtered_latitude);
+                event.filtered_longitude = grid(event.filtered_longitude);
+                event.containment_radius_m = None;
+                event.provenance = None;
+                self.coarsened.fetch_add(1, Ordering::Relaxed);
+                true
+            }
+        }
+    }
+
+    /// Whether a raw frame comes from a listed aircraft (by registration
+    /// only once an event has identified its address)
+    pub fn withholds_frame(&self, data: &[u8]) -> bool {
+        if self.is_empty() {
+            return false;
+        }
+        let Some(addr) = frame_address(data) else {
+            return false;
+        };
+        self.addresses.contains_key(&addr)
+            || self.seen.lock().map(|seen| seen.contains_key(&addr)).unwrap_or(false)
+    }
+}
+
+/// Registrations compare without case or dashes ("N-123AB" = "n123ab")
+fn normalize_registration(registration: &str) -> String {
+    registration
+        .chars()
+        .filter(|c| *c != '-')
+        .map(|c| c.to_ascii_uppercase())
+        .collect()
+}
+
+/// Mode S CRC-24 of `data`
+fn crc24(data: &[u8]) -> u32 {
+    let mut crc = 0u32;
+    for &byte in data {
+        crc ^= (byte as u32) << 16;
+        for _ in 0..8 {
+            crc <<= 1;
+            if crc & 0x1000000 != 0 {
+                crc ^= CRC_POLY;
+            }
+        }
+    }
+    crc & 0xFFFFFF
+}
+
+/// Sender address of a Mode S frame: in the clear for DF11/17/18,
+/// otherwise overlaid on the parity
+fn frame_address(data: &[u8]) -> Option<u32> {
+    if data.len() != 7 && data.len() != 14 {
+        return None;
+    }
+    let field = |bytes: &[u8]| (bytes[0] as u32) << 16 | (bytes[1] as u32) << 8 | bytes[2] as u32;
+    match data[0] >> 3 {
+        11 | 17 | 18 => Some(field(&data[1..4])),
+        0 | 4 | 5 | 16 | 20 | 21 => {
+            let n = data.len() - 3;
+            Some(crc24(&data[..n]) ^ field(&data[n..]))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aircraft_db::AircraftMeta;
+
+    fn frame(hex: &str) -> Vec<u8> {
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_privacy_list() {
+        let text = "# comment\n4840D6\nHL-8001 coarsen\n";
+        let list = PrivacyList::parse(text, PrivacyAction::Omit, 0.1).unwrap();
+        assert_eq!(list.len(), 2);
+        assert!(PrivacyList::parse("4840D6 hide", PrivacyAction::Omit, 0.1).is_err());
+        let db = AircraftDb::empty();
+        db.replace(HashMap::from([(
+            "06A0A5".to_string(),
+            AircraftMeta {
+                registration: Some("HL8001".into()),
+                ..Default::default()
+            },
+        )]));
+
+        let mut event = AircraftEvent {
+            icao: "4840D6".into(),
+            latitude: Some(52.2572),
+            longitude: Some(3.9194),
+            ..Default::default()
+        };
+        assert!(!list.apply(&mut event, &db));
+        assert_eq!(list.omitted.load(Ordering::Relaxed), 1);
+
+        let coarse = PrivacyList::parse("4840d6 coarsen", PrivacyAction::Omit, 0.1).unwrap();
+        assert!(coarse.apply(&mut event, &db));
+        assert!((event.latitude.unwrap() - 52.3).abs() < 1e-9);
+        assert!((event.longitude.unwrap() - 3.9).abs() < 1e-9);
+
+        let mut other = AircraftEvent {
+            icao: "71BE11".into(),
+            latitude: Some(37.4602),
+            ..Default::default()
+        };
+        assert!(list.apply(&mut other, &db));
+        assert_eq!(other.latitude, Some(37.4602));
+
+        // Listed by registration: frames are withheld once an event named the address
+        assert!(!list.withholds_frame(&frame("8D06A0A599000000000000000000")));
+        let mut listed = AircraftEvent {
+            icao: "06A0A5".into(),
+            latitude: Some(25.2604),
+            ..Default::default()
+        };
+        assert!(list.apply(&mut listed, &db));
+        assert!((listed.latitude.unwrap() - 25.3).abs() < 1e-9);
+        assert!(list.withholds_frame(&frame("8D06A0A599000000000000000000")));
+
+        // Clear address (DF17) and address overlaid on the parity (DF4)
+        let squitter = frame("8D4840D6202CC371C32CE0576098");
+        assert_eq!(crc24(&squitter[..11]), 0x576098);
+        assert_eq!(frame_address(&squitter), Some(0x4840D6));
+        assert!(list.withholds_frame(&squitter));
+        let mut reply = frame("20001838000000");
+        let parity = crc24(&reply[..4]) ^ 0x4840D6;
+        reply[4..].copy_from_slice(&parity.to_be_bytes()[1..]);
+        assert_eq!(frame_address(&reply), Some(0x4840D6));
+        assert!(!list.withholds_frame(&frame("8D71BE1199000000000000000000")));
+    }
+}
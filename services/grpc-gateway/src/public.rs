@@ -0,0 +1,248 @@
+//! Public viewer mode
+//!
+//! `PUBLIC_VIEWER=true` shares the map without opening the API. Callers
+//! without credentials get the public view instead of a 401: `GET
+//! /api/aircraft`, the coarse `GET /api/public/stats`, the tar1090
+//! `aircraft.json` and `receiver.json`, and on `/ws` and `/api/stream` a
+//! stream of position updates and removals only. Device status, signal,
+//! alerts, history and admin endpoints keep needing a credential, and
+//! stream clients presenting the `WS_AUTH_TOKEN` token or an API key get the
+//! full stream. Without `API_KEYS`, `JWT_SECRET` and `WS_AUTH_TOKEN` every
+//! caller is a public viewer.
+//!
+//! Public messages and aircraft carry no device IDs or frame provenance.
+//! `PUBLIC_POSITION_GRID_DEG` rounds their positions to a grid (0.01 is
+//! about 1 km), and `PUBLIC_DELAY_SECS` holds them back; the public aircraft
+//! list is built from the same delayed stream, so it never runs ahead of it.
+//! `SHARE_WS` applies before the public view.
+
+use crate::fanout::Fanout;
+use crate::pubsub::{LiveMessage, PubSub};
+use crate::share::SharePolicies;
+use crate::AppState;
+use axum::{extract::State, http::Method, Json};
+use serde::Serialize;
+use serde_json::Value as JsonValue;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tokio::time::Instant;
+use tracing::{debug, warn};
+use utoipa::ToSchema;
+
+pub const STATS_PATH: &str = "/api/public/stats";
+
+/// Message types public viewers receive
+const TYPES: &[&str] = &["position_update", "aircraft_removed"];
+
+/// Fields removed from public messages
+const PRIVATE_FIELDS: &[&str] = &["device_id", "provenance"];
+
+/// Messages held back at most; the oldest are dropped beyond this
+const MAX_PENDING: usize = 100_000;
+
+/// The public view and the clients receiving it
+pub struct PublicView {
+    enabled: bool,
+    grid_deg: Option<f64>,
+    delay: Duration,
+    /// Latest public message per aircraft, as released
+    aircraft: Mutex<HashMap<String, JsonValue>>,
+    /// Queues of public WebSocket clients
+    pub fanout: Arc<Fanout>,
+}
+
+impl PublicView {
+    pub fn new(enabled: bool, grid_deg: Option<f64>, delay: Duration, fanout: Arc<Fanout>) -> Self {
+        Self {
+            enabled,
+            grid_deg,
+            delay,
+            aircraft: Mutex::new(HashMap::new()),
+            fanout,
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// The public form of a message, or `None` if it isn't shared publicly
+    pub fn redact(&self, mut value: JsonValue) -> Option<JsonValue> {
+        let kind = value.get("type").and_then(JsonValue::as_str)?;
+        if !TYPES.contains(&kind) {
+            return None;
+        }
+        let obj = value.as_object_mut()?;
+        for field in PRIVATE_FIELDS {
+            obj.remove(*field);
+        }
+        if let Some(grid) = self.grid_deg {
+            for key in ["lat", "lon"] {
+                if let Some(v) = obj.get(key).and_then(JsonValue::as_f64) {
+                    obj.insert(key.into(), ((v / grid).round() * grid).into());
+                }
+            }
+        }
+        Some(value)
+    }
+
+    /// Send a message whose delay is up to public clients and the list
+    fn release(&self, value: JsonValue) {
+        if let (Ok(mut aircraft), Some(icao)) = (self.aircraft.lock(), value.get("icao").and_then(JsonValue::as_str)) {
+            if value["type"] == "aircraft_removed" {
+                aircraft.remove(icao);
+            } else {
+                let mut row = value.clone();
+                if let Some(ms) = row.get("timestamp_ms").and_then(JsonValue::as_i64) {
+                    row["seen"] = chrono::DateTime::from_timestamp_millis(ms)
+                        .map(|t| t.to_rfc3339())
+                        .into();
+                }
+                aircraft.insert(icao.to_string(), row);
+            }
+        }
+        self.fanout.publish(Arc::new(LiveMessage::from_value(&value)));
+    }
+
+    /// Aircraft as last released, most recently heard first
+    pub fn aircraft(&self) -> Vec<JsonValue> {
+        let Ok(aircraft) = self.aircraft.lock() else {
+            return Vec::new();
+        };
+        let mut rows: Vec<JsonValue> = aircraft.values().cloned().collect();
+        rows.sort_by_key(|row| std::cmp::Reverse(row.get("timestamp_ms").and_then(JsonValue::as_i64)));
+        rows
+    }
+
+    /// `initial` message for a public WebSocket client
+    pub fn initial_message(&self) -> Option<String> {
+        let mut aircraft = self.aircraft();
+        for row in aircraft.iter_mut() {
+            if let Some(obj) = row.as_object_mut() {
+                obj.remove("type");
+            }
+        }
+        serde_json::to_string(&serde_json::json!({"type": "initial", "aircraft": aircraft})).ok()
+    }
+}
+
+/// Whether a caller without credentials may make a request
+pub fn allows(method: &Method, path: &str) -> bool {
+    matches!(*method, Method::GET | Method::HEAD)
+        && matches!(path, "/api/aircraft" | STATS_PATH | "/data/aircraft.json" | "/data/receiver.json")
+}
+
+/// Release every live message, less what `SHARE_WS` and the public view
+/// withhold, to public clients after the delay
+pub fn spawn_dispatcher(view: Arc<PublicView>, pubsub: Arc<dyn PubSub>, share: Arc<SharePolicies>) {
+    let mut rx = pubsub.subscribe();
+    tokio::spawn(async move {
+        let mut pending: VecDeque<(Instant, JsonValue)> = VecDeque::new();
+        loop {
+            let next_due = pending.front().map(|(due, _)| *due);
+            tokio::select! {
+                msg = rx.recv() => match msg {
+                    Ok(msg) => {
+                        let Some(msg) = share.ws.apply_message(msg) else {
+                            continue;
+                        };
+                        let Some(value) = serde_json::from_str(&msg.json).ok().and_then(|v| view.redact(v)) else {
+                            continue;
+                        };
+                        if view.delay.is_zero() {
+                            view.release(value);
+                            continue;
+                        }
+                        if pending.len() >= MAX_PENDING {
+                            pending.pop_front();
+                        }
+                        pending.push_back((Instant::now() + view.delay, value));
+                    }
+                    Err(broadcast::error::RecvError::Lagged(n)) => {
+                        warn!("Public view dispatcher lagged by {} messages", n);
+                    }
+                    Err(broadcast::error::RecvError::Closed) => {
+                        debug!("Live event stream closed");
+                        break;
+                    }
+                },
+                _ = tokio::time::sleep_until(next_due.unwrap_or_else(Instant::now)), if next_due.is_some() => {
+                    let now = Instant::now();
+                    while pending.front().is_some_and(|(due, _)| *due <= now) {
+                        if let Some((_, value)) = pending.pop_front() {
+                            view.release(value);
+                        }
+                    }
+                }
+            }
+        }
+    });
+}
+
+/// Coarse live statistics
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PublicStats {
+    /// Aircraft in the public list
+    pub aircraft: usize,
+    /// Of those, aircraft with a position
+    pub with_position: usize,
+    /// Decoder rate of all receivers together
+    pub messages_per_second: f32,
+}
+
+/// Coarse live statistics, open to public viewers
+#[utoipa::path(
+    get,
+    path = "/api/public/stats",
+    tag = "receiver",
+    responses((status = 200, description = "Aircraft in view and message rate", body = PublicStats)),
+    security((), ("api_key" = []), ("bearer" = []))
+)]
+pub async fn get_public_stats(State(state): State<Arc<AppState>>) -> Json<PublicStats> {
+    let aircraft = match state.public.enabled() {
+        true => state.public.aircraft(),
+        false => state.aircraft_cache.rows(chrono::Utc::now()),
+    };
+    Json(PublicStats {
+        aircraft: aircraft.len(),
+        with_position: aircraft.iter().filter(|a| a.get("lat").is_some_and(|v| !v.is_null())).count(),
+        messages_per_second: state.stats.live(chrono::Utc::now()).messages_per_second,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_public_view() {
+        let view = PublicView::new(true, Some(0.01), Duration::ZERO, Arc::new(Fanout::new(10, 0)));
+        let update = serde_json::json!({
+            "type": "position_update", "icao": "71BE11", "device_id": "rooftop",
+            "lat": 37.46123, "lon": 126.43877, "altitude": 5000, "timestamp_ms": 1_700_000_000_000i64,
+            "provenance": {"raw": "8D71BE11"},
+        });
+        let public = view.redact(update).unwrap();
+        assert_eq!(public.get("device_id"), None);
+        assert_eq!(public.get("provenance"), None);
+        assert!((public["lat"].as_f64().unwrap() - 37.46).abs() < 1e-9);
+        assert!((public["lon"].as_f64().unwrap() - 126.44).abs() < 1e-9);
+        assert!(view.redact(serde_json::json!({"type": "device_status", "device_id": "rooftop"})).is_none());
+        assert!(view.redact(serde_json::json!({"type": "alert", "icao": "71BE11"})).is_none());
+
+        view.release(public);
+        let aircraft = view.aircraft();
+        assert_eq!(aircraft.len(), 1);
+        assert!(aircraft[0]["seen"].as_str().is_some_and(|s| s.starts_with("2023-11-14")));
+        assert!(view.initial_message().unwrap().contains("\"icao\":\"71BE11\""));
+        view.release(view.redact(serde_json::json!({"type": "aircraft_removed", "icao": "71BE11"})).unwrap());
+        assert!(view.aircraft().is_empty());
+
+        assert!(allows(&Method::GET, "/api/aircraft") && allows(&Method::GET, STATS_PATH));
+        assert!(allows(&Method::GET, "/data/aircraft.json") && !allows(&Method::GET, "/data/history_0.json"));
+        assert!(!allows(&Method::GET, "/api/sdr/status") && !allows(&Method::GET, "/api/aircraft/71BE11/trail"));
+        assert!(!allows(&Method::POST, "/api/aircraft"));
+    }
+}
@@ -0,0 +1,417 @@
+//! Live event distribution
+//!
+//! Everything pushed to WebSocket and SSE clients goes through [`PubSub`].
+//! By default events stay in this process. With `PUBSUB_BACKEND=redis` or
+//! `nats` they are also published to a shared channel, and events published
+//! by other replicas are delivered here, so clients see the full stream
+//! whichever replica behind the load balancer receives the gRPC streams.
+//!
+//! Local clients get events straight away; each replica prefixes what it
+//! publishes with its own ID and skips its messages when they come back.
+//!
+//! Messages are serialized once, as a [`LiveMessage`] shared by every
+//! subscriber, with the fields client subscriptions filter on read out up
+//! front so no client has to parse the JSON again.
+
+use crate::config::{Config, PubSubBackend};
+use anyhow::Result;
+use futures_util::StreamExt;
+use redis::AsyncCommands;
+use serde::Serialize;
+use serde_json::Value as JsonValue;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{broadcast, mpsc};
+use tracing::{debug, error, info, warn};
+
+/// Messages buffered per subscriber before it lags
+const CHANNEL_CAPACITY: usize = 1000;
+
+/// Messages waiting to be published before new ones are dropped
+const OUTBOUND_CAPACITY: usize = 1000;
+
+/// Delay before reconnecting to Redis
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+const DEFAULT_REDIS_URL: &str = "redis://localhost:6379";
+const DEFAULT_NATS_URL: &str = "nats://localhost:4222";
+
+/// What subscriptions filter a message on
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MessageFields {
+    /// The `type` field
+    pub kind: String,
+    pub icao: Option<String>,
+    /// Latitude and longitude, absent until the aircraft reported a position
+    pub position: Option<(f64, f64)>,
+    pub altitude: Option<i64>,
+    /// Receiver the message came from
+    pub device_id: Option<String>,
+}
+
+impl MessageFields {
+    /// Read the fields from a JSON message or aircraft list entry
+    pub fn read(value: &JsonValue) -> Self {
+        let str_field = |key| value.get(key).and_then(JsonValue::as_str).map(str::to_string);
+        let lat = value.get("lat").and_then(JsonValue::as_f64);
+        let lon = value.get("lon").and_then(JsonValue::as_f64);
+        Self {
+            kind: str_field("type").unwrap_or_default(),
+            icao: str_field("icao"),
+            position: lat.zip(lon),
+            altitude: value.get("altitude").and_then(JsonValue::as_i64),
+            device_id: str_field("device_id"),
+        }
+    }
+}
+
+/// A message as sent to clients, serialized once
+#[derive(Debug, Clone, PartialEq)]
+pub struct LiveMessage {
+    pub json: Arc<str>,
+    pub fields: MessageFields,
+}
+
+impl LiveMessage {
+    /// Serialize a typed message whose filter fields the caller already knows
+    pub fn encode<T: Serialize>(value: &T, fields: MessageFields) -> serde_json::Result<Self> {
+        Ok(Self {
+            json: serde_json::to_string(value)?.into(),
+            fields,
+        })
+    }
+
+    /// Serialize a message built as JSON
+    pub fn from_value(value: &JsonValue) -> Self {
+        Self {
+            json: value.to_string().into(),
+            fields: MessageFields::read(value),
+        }
+    }
+
+    /// A message received as text; unparseable text has no fields
+    pub fn parse(json: &str) -> Self {
+        let fields = serde_json::from_str::<JsonValue>(json)
+            .map(|value| MessageFields::read(&value))
+            .unwrap_or_default();
+        Self {
+            json: json.into(),
+            fields,
+        }
+    }
+}
+
+/// Fan-out of live events to streaming clients
+pub trait PubSub: Send + Sync {
+    /// Short backend name for logs
+    fn backend_name(&self) -> &'static str;
+
+    /// Deliver a message to the clients of every replica
+    fn publish(&self, message: LiveMessage);
+
+    /// Receive messages published by any replica
+    fn subscribe(&self) -> broadcast::Receiver<Arc<LiveMessage>>;
+
+    /// Whether a published message could reach anyone; lets publishers skip
+    /// building messages nobody would read
+    fn has_subscribers(&self) -> bool;
+}
+
+/// Events delivered within this process only
+pub struct LocalPubSub {
+    tx: broadcast::Sender<Arc<LiveMessage>>,
+}
+
+impl LocalPubSub {
+    pub fn new() -> Self {
+        let (tx, _) = broadcast::channel(CHANNEL_CAPACITY);
+        Self { tx }
+    }
+}
+
+impl PubSub for LocalPubSub {
+    fn backend_name(&self) -> &'static str {
+        "local"
+    }
+
+    fn publish(&self, message: LiveMessage) {
+        let _ = self.tx.send(Arc::new(message));
+    }
+
+    fn subscribe(&self) -> broadcast::Receiver<Arc<LiveMessage>> {
+        self.tx.subscribe()
+    }
+
+    fn has_subscribers(&self) -> bool {
+        self.tx.receiver_count() > 0
+    }
+}
+
+/// Events shared with other replicas through a broker
+pub struct RemotePubSub {
+    backend: &'static str,
+    local: broadcast::Sender<Arc<LiveMessage>>,
+    outbound: mpsc::Sender<String>,
+    origin: Arc<str>,
+}
+
+impl RemotePubSub {
+    /// Returns the queue of envelopes for the backend to publish
+    fn new(backend: &'static str) -> (Self, mpsc::Receiver<String>) {
+        let (local, _) = broadcast::channel(CHANNEL_CAPACITY);
+        let (outbound, rx) = mpsc::channel(OUTBOUND_CAPACITY);
+        let bus = Self {
+            backend,
+            local,
+            outbound,
+            origin: replica_id().into(),
+        };
+        (bus, rx)
+    }
+
+    /// Where the backend delivers what it receives
+    fn inbound(&self) -> Inbound {
+        Inbound {
+            local: self.local.clone(),
+            origin: self.origin.clone(),
+        }
+    }
+}
+
+impl PubSub for RemotePubSub {
+    fn backend_name(&self) -> &'static str {
+        self.backend
+    }
+
+    fn publish(&self, message: LiveMessage) {
+        let envelope = format!("{}\n{}", self.origin, message.json);
+        if self.outbound.try_send(envelope).is_err() {
+            debug!("{} publish queue full, dropping message", self.backend);
+        }
+        let _ = self.local.send(Arc::new(message));
+    }
+
+    fn subscribe(&self) -> broadcast::Receiver<Arc<LiveMessage>> {
+        self.local.subscribe()
+    }
+
+    fn has_subscribers(&self) -> bool {
+        // Other replicas may have clients
+        true
+    }
+}
+
+/// Delivers messages from other replicas to local subscribers
+#[derive(Clone)]
+struct Inbound {
+    local: broadcast::Sender<Arc<LiveMessage>>,
+    origin: Arc<str>,
+}
+
+impl Inbound {
+    fn deliver(&self, envelope: &str) {
+        match envelope.split_once('\n') {
+            Some((origin, _)) if origin == &*self.origin => {}
+            Some((_, message)) => {
+                let _ = self.local.send(Arc::new(LiveMessage::parse(message)));
+            }
+            None => debug!("Ignoring malformed pub/sub message"),
+        }
+    }
+}
+
+/// Unique per process, so restarted replicas don't skip each other
+fn replica_id() -> String {
+    let host = std::env::var("HOSTNAME").unwrap_or_else(|_| "gateway".to_string());
+    format!(
+        "{}-{}-{}",
+        host,
+        std::process::id(),
+        chrono::Utc::now().timestamp_micros()
+    )
+}
+
+/// Connect to the configured backend (falls back to local delivery)
+pub async fn connect(config: &Config) -> Arc<dyn PubSub> {
+    let channel = config.pubsub_channel.clone();
+    match config.pubsub_backend {
+        PubSubBackend::Local => Arc::new(LocalPubSub::new()),
+        PubSubBackend::Redis => {
+            let url = config.pubsub_url.as_deref().unwrap_or(DEFAULT_REDIS_URL);
+            match redis(url, channel).await {
+                Ok(bus) => {
+                    info!("Sharing live events via Redis: {}", url);
+                    Arc::new(bus)
+                }
+                Err(e) => {
+                    error!(
+                        "Failed to connect to Redis: {}. Continuing with local events.",
+                        e
+                    );
+                    Arc::new(LocalPubSub::new())
+                }
+            }
+        }
+        PubSubBackend::Nats => {
+            let url = config.pubsub_url.as_deref().unwrap_or(DEFAULT_NATS_URL);
+            match nats(url, channel).await {
+                Ok(bus) => {
+                    info!("Sharing live events via NATS: {}", url);
+                    Arc::new(bus)
+                }
+                Err(e) => {
+                    error!(
+                        "Failed to connect to NATS: {}. Continuing with local events.",
+                        e
+                    );
+                    Arc::new(LocalPubSub::new())
+                }
+            }
+        }
+    }
+}
+
+async fn redis_subscribe(client: &redis::Client, channel: &str) -> Result<redis::aio::PubSub> {
+    let mut pubsub = client.get_async_pubsub().await?;
+    pubsub.subscribe(channel).await?;
+    Ok(pubsub)
+}
+
+async fn redis(url: &str, channel: String) -> Result<RemotePubSub> {
+    let client = redis::Client::open(url)?;
+    let conn = client.get_multiplexed_async_connection().await?;
+    let pubsub = redis_subscribe(&client, &channel).await?;
+    let (bus, mut outbound) = RemotePubSub::new("redis");
+
+    // Publisher: reconnects after a failed publish
+    let publisher = client.clone();
+    let publish_channel = channel.clone();
+    tokio::spawn(async move {
+        let mut conn = Some(conn);
+        loop {
+            let Some(mut current) = conn.take() else {
+                tokio::time::sleep(RECONNECT_DELAY).await;
+                match publisher.get_multiplexed_async_connection().await {
+                    Ok(c) => {
+                        // Messages queued while disconnected are stale
+                        while outbound.try_recv().is_ok() {}
+                        info!("Reconnected to Redis");
+                        conn = Some(c);
+                    }
+                    Err(e) => warn!("Failed to reconnect to Redis: {}", e),
+                }
+                continue;
+            };
+            let Some(envelope) = outbound.recv().await else {
+                return;
+            };
+            match current
+                .publish::<_, _, ()>(&publish_channel, envelope)
+                .await
+            {
+                Ok(()) => conn = Some(current),
+                Err(e) => warn!("Failed to publish to Redis: {}", e),
+            }
+        }
+    });
+
+    // Subscriber: resubscribes when the connection drops
+    let inbound = bus.inbound();
+    tokio::spawn(async move {
+        let mut pubsub = pubsub;
+        loop {
+            let mut messages = pubsub.into_on_message();
+            while let Some(msg) = messages.next().await {
+                match msg.get_payload::<String>() {
+                    Ok(envelope) => inbound.deliver(&envelope),
+                    Err(e) => debug!("Ignoring Redis message: {}", e),
+                }
+            }
+            warn!("Redis subscription closed, reconnecting");
+            pubsub = loop {
+                tokio::time::sleep(RECONNECT_DELAY).await;
+                match redis_subscribe(&client, &channel).await {
+                    Ok(pubsub) => break pubsub,
+                    Err(e) => warn!("Failed to resubscribe to Redis: {}", e),
+                }
+            };
+        }
+    });
+
+    Ok(bus)
+}
+
+async fn nats(url: &str, subject: String) -> Result<RemotePubSub> {
+    // The client reconnects and resubscribes on its own
+    let client = async_nats::connect(url).await?;
+    let mut subscriber = client.subscribe(subject.clone()).await?;
+    let (bus, mut outbound) = RemotePubSub::new("nats");
+
+    tokio::spawn(async move {
+        while let Some(envelope) = outbound.recv().await {
+            if let Err(e) = client.publish(subject.clone(), envelope.into()).await {
+                warn!("Failed to publish to NATS: {}", e);
+            }
+        }
+    });
+
+    let inbound = bus.inbound();
+    tokio::spawn(async move {
+        while let Some(msg) = subscriber.next().await {
+            match std::str::from_utf8(&msg.payload) {
+                Ok(envelope) => inbound.deliver(envelope),
+                Err(e) => debug!("Ignoring NATS message: {}", e),
+            }
+        }
+        warn!("NATS subscription closed");
+    });
+
+    Ok(bus)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_remote_delivery() {
+        let (bus, mut outbound) = RemotePubSub::new("test");
+        let mut rx = bus.subscribe();
+
+        // Local clients get the message; the broker gets it with our ID
+        bus.publish(LiveMessage::parse("{\"type\":\"aircraft\"}"));
+        assert_eq!(&*rx.try_recv().unwrap().json, "{\"type\":\"aircraft\"}");
+        let envelope = outbound.try_recv().unwrap();
+        assert!(envelope.starts_with(&format!("{}\n", bus.origin)));
+
+        // Our own message coming back is not delivered twice
+        let inbound = bus.inbound();
+        inbound.deliver(&envelope);
+        assert!(rx.try_recv().is_err());
+
+        inbound.deliver("other-replica\n{\"type\":\"alert\"}");
+        assert_eq!(rx.try_recv().unwrap().fields.kind, "alert");
+        inbound.deliver("no separator");
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_message_fields() {
+        let msg = LiveMessage::from_value(&serde_json::json!({
+            "type": "position_update",
+            "icao": "71BE11",
+            "lat": 37.5,
+            "lon": 126.5,
+            "altitude": 35000,
+        }));
+        assert_eq!(msg.fields.kind, "position_update");
+        assert_eq!(msg.fields.icao.as_deref(), Some("71BE11"));
+        assert_eq!(msg.fields.position, Some((37.5, 126.5)));
+        assert_eq!(msg.fields.altitude, Some(35000));
+        assert_eq!(LiveMessage::parse(&msg.json), msg);
+
+        let signal = LiveMessage::parse("{\"type\":\"signal\",\"lat\":37.5}");
+        assert_eq!(signal.fields.position, None);
+        assert_eq!(LiveMessage::parse("not json").fields, MessageFields::default());
+    }
+}
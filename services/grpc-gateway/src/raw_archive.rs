@@ -0,0 +1,217 @@
+//! Optional raw frame archival
+//!
+//! Stores every raw frame received from hosts so history can be re-decoded
+//! when the parser improves. Frames are batched in a background task and
+//! written either to the `raw_frames` hypertable or to hourly rotating files
+//! in dump1090 `*HEX;` format.
+
+use crate::adsb::RawFrame;
+use crate::config::{Config, RawArchiveMode};
+use crate::storage::Storage;
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tracing::{debug, info, warn};
+
+/// Maximum frames written per batch
+const BATCH_SIZE: usize = 500;
+
+/// Maximum time a frame waits in the batch before being flushed
+const FLUSH_INTERVAL_MS: u64 = 1000;
+
+/// Handle used by the gRPC service to submit frames for archival
+pub struct RawArchive {
+    tx: Option<mpsc::Sender<RawFrame>>,
+}
+
+impl RawArchive {
+    /// Start the archival task for the configured mode
+    pub fn start(config: &Config, db_writer: Arc<dyn Storage>) -> Self {
+        let sink = match config.raw_archive_mode {
+            RawArchiveMode::Off => return Self { tx: None },
+            RawArchiveMode::Db => ArchiveSink::Db(db_writer),
+            RawArchiveMode::File => ArchiveSink::File(Arc::new(Mutex::new(RotatingFile::new(
+                config.raw_archive_dir.clone(),
+                config.raw_archive_max_files,
+            )))),
+        };
+
+        info!("Raw frame archival enabled: {:?}", config.raw_archive_mode);
+
+        let (tx, rx) = mpsc::channel::<RawFrame>(10_000);
+        tokio::spawn(run_archiver(rx, sink));
+
+        Self { tx: Some(tx) }
+    }
+
+    /// Whether frames are being archived
+    pub fn is_enabled(&self) -> bool {
+        self.tx.is_some()
+    }
+
+    /// Submit a frame for archival (drops the frame if the archiver is behind)
+    pub fn submit(&self, frame: RawFrame) {
+        if let Some(tx) = &self.tx {
+            if tx.try_send(frame).is_err() {
+                debug!("Raw archive queue full, dropping frame");
+            }
+        }
+    }
+}
+
+/// Destination for archived frames
+enum ArchiveSink {
+    Db(Arc<dyn Storage>),
+    /// Written off the async runtime
+    File(Arc<Mutex<RotatingFile>>),
+}
+
+impl ArchiveSink {
+    async fn write(&self, frames: Vec<RawFrame>) {
+        match self {
+            Self::Db(db) => {
+                if let Err(e) = db.insert_raw_frames(&frames).await {
+                    warn!("Failed to archive {} raw frames: {}", frames.len(), e);
+                }
+            }
+            Self::File(file) => {
+                let file = file.clone();
+                let result = tokio::task::spawn_blocking(move || match file.lock() {
+                    Ok(mut file) => file.write(&frames),
+                    Err(_) => Err(std::io::Error::other("raw archive file poisoned")),
+                })
+                .await;
+                match result {
+                    Ok(Ok(())) => {}
+                    Ok(Err(e)) => warn!("Failed to write raw frame archive: {}", e),
+                    Err(e) => warn!("Raw frame archive writer failed: {}", e),
+                }
+            }
+        }
+    }
+}
+
+/// Background task: batch frames and flush them to the sink
+async fn run_archiver(mut rx: mpsc::Receiver<RawFrame>, sink: ArchiveSink) {
+    let mut batch = Vec::with_capacity(BATCH_SIZE);
+    let mut flush_interval = tokio::time::interval(Duration::from_millis(FLUSH_INTERVAL_MS));
+
+    loop {
+        tokio::select! {
+            frame = rx.recv() => {
+                match frame {
+                    Some(frame) => {
+                        batch.push(frame);
+                        if batch.len() >= BATCH_SIZE {
+                            sink.write(std::mem::replace(&mut batch, Vec::with_capacity(BATCH_SIZE))).await;
+                        }
+                    }
+                    None => break,
+                }
+            }
+            _ = flush_interval.tick() => {
+                if !batch.is_empty() {
+                    sink.write(std::mem::replace(&mut batch, Vec::with_capacity(BATCH_SIZE))).await;
+                }
+            }
+        }
+    }
+
+    if !batch.is_empty() {
+        sink.write(batch).await;
+    }
+}
+
+/// Hourly rotating archive files (`frames-YYYYMMDD-HH.log`)
+struct RotatingFile {
+    dir: PathBuf,
+    max_files: usize,
+    current_name: String,
+    writer: Option<BufWriter<File>>,
+}
+
+impl RotatingFile {
+    fn new(dir: PathBuf, max_files: usize) -> Self {
+        Self {
+            dir,
+            max_files,
+            current_name: String::new(),
+            writer: None,
+        }
+    }
+
+    fn write(&mut self, frames: &[RawFrame]) -> std::io::Result<()> {
+        for frame in frames {
+            let time = chrono::DateTime::from_timestamp_millis(frame.timestamp_ms as i64)
+                .unwrap_or_else(chrono::Utc::now);
+            let name = format!("frames-{}.log", time.format("%Y%m%d-%H"));
+            if name != self.current_name || self.writer.is_none() {
+                self.rotate(name)?;
+            }
+
+            if let Some(writer) = self.writer.as_mut() {
+                writeln!(
+                    writer,
+                    "{} {} {} *{};",
+                    frame.timestamp_ms,
+                    frame.device_id,
+                    frame.signal_level,
+                    hex_upper(&frame.data)
+                )?;
+            }
+        }
+
+        if let Some(writer) = self.writer.as_mut() {
+            writer.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Switch to a new hourly file and prune the oldest ones
+    fn rotate(&mut self, name: String) -> std::io::Result<()> {
+        if let Some(mut writer) = self.writer.take() {
+            writer.flush()?;
+        }
+
+        std::fs::create_dir_all(&self.dir)?;
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.dir.join(&name))?;
+        debug!("Raw archive file: {}", name);
+        self.writer = Some(BufWriter::new(file));
+        self.current_name = name;
+
+        self.prune()
+    }
+
+    /// Remove archive files beyond the retention limit
+    fn prune(&self) -> std::io::Result<()> {
+        let mut files: Vec<String> = std::fs::read_dir(&self.dir)?
+            .filter_map(|e| e.ok())
+            .filter_map(|e| e.file_name().into_string().ok())
+            .filter(|n| n.starts_with("frames-") && n.ends_with(".log"))
+            .collect();
+
+        if files.len() <= self.max_files {
+            return Ok(());
+        }
+
+        // Names sort chronologically
+        files.sort();
+        let excess = files.len() - self.max_files;
+        for name in files.iter().take(excess) {
+            info!("Removing old raw archive file: {}", name);
+            std::fs::remove_file(self.dir.join(name))?;
+        }
+        Ok(())
+    }
+}
+
+/// Format bytes as uppercase hex (dump1090 style)
+fn hex_upper(data: &[u8]) -> String {
+    data.iter().map(|b| format!("{:02X}", b)).collect()
+}
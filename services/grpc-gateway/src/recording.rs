@@ -0,0 +1,304 @@
+//! Live broadcast recording
+//!
+//! With `RECORDING_DIR` set, every message of the live broadcast (what
+//! WebSocket and SSE clients see, before share restrictions) is appended to
+//! an hourly file, `broadcast-YYYYMMDD-HH.ndjson.gz`, one
+//! `{"t":<unix ms>,"msg":<message>}` line each. Lines are written about once
+//! a second as a complete gzip member, so the file of the current hour can
+//! be downloaded and decompressed while it grows. Recordings replay a demo
+//! or an incident exactly as clients saw it, without database queries.
+//! `/api/recordings` lists them and `/api/recordings/{name}` downloads one;
+//! the oldest beyond `RECORDING_MAX_FILES` are removed.
+
+use crate::api::ApiError;
+use crate::pubsub::{LiveMessage, PubSub};
+use crate::AppState;
+use axum::{
+    body::Body,
+    extract::{Path, State},
+    http::header,
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use flate2::{write::GzEncoder, Compression};
+use serde::Serialize;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tokio_util::io::ReaderStream;
+use tracing::{debug, error, info, warn};
+use utoipa::ToSchema;
+
+/// Time lines wait before being written
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+const PREFIX: &str = "broadcast-";
+const SUFFIX: &str = ".ndjson.gz";
+
+/// One hourly recording
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct Recording {
+    /// File name, for `/api/recordings/{name}`
+    pub name: String,
+    /// Start of the hour recorded
+    pub start: DateTime<Utc>,
+    /// Compressed size so far
+    pub size_bytes: u64,
+}
+
+/// Where recordings are kept, when recording is enabled
+pub struct Recordings {
+    dir: Option<PathBuf>,
+}
+
+impl Recordings {
+    /// Record the broadcast into `dir` (if set), keeping `max_files` hours
+    pub fn start(dir: Option<PathBuf>, max_files: usize, pubsub: Arc<dyn PubSub>) -> Self {
+        if let Some(dir) = &dir {
+            info!("Recording the live broadcast to {}", dir.display());
+            let writer = HourlyWriter {
+                dir: dir.clone(),
+                max_files,
+            };
+            tokio::spawn(run_recorder(pubsub.subscribe(), Arc::new(writer)));
+        }
+        Self { dir }
+    }
+
+    /// Recordings on disk, oldest first
+    pub fn list(&self) -> std::io::Result<Vec<Recording>> {
+        let Some(dir) = &self.dir else {
+            return Ok(Vec::new());
+        };
+        let mut recordings: Vec<Recording> = std::fs::read_dir(dir)?
+            .filter_map(|e| e.ok())
+            .filter_map(|e| {
+                let name = e.file_name().into_string().ok()?;
+                Some(Recording {
+                    start: parse_name(&name)?,
+                    size_bytes: e.metadata().ok()?.len(),
+                    name,
+                })
+            })
+            .collect();
+        recordings.sort_by_key(|r| r.start);
+        Ok(recordings)
+    }
+
+    /// Path of a recording by name; `None` for anything else
+    fn path(&self, name: &str) -> Option<PathBuf> {
+        parse_name(name)?;
+        Some(self.dir.as_ref()?.join(name))
+    }
+}
+
+/// `broadcast-YYYYMMDD-HH.ndjson.gz` for the hour of `time`
+fn file_name(time: DateTime<Utc>) -> String {
+    format!("{}{}{}", PREFIX, time.format("%Y%m%d-%H"), SUFFIX)
+}
+
+/// Start of the hour a recording's name covers; `None` for other names,
+/// which keeps downloads inside the recording directory
+fn parse_name(name: &str) -> Option<DateTime<Utc>> {
+    let hour = name.strip_prefix(PREFIX)?.strip_suffix(SUFFIX)?;
+    if hour.len() != 11 {
+        return None;
+    }
+    let time = NaiveDateTime::parse_from_str(&format!("{}0000", hour), "%Y%m%d-%H%M%S").ok()?;
+    Some(time.and_utc())
+}
+
+/// Appends lines to the file of their hour and prunes old files
+struct HourlyWriter {
+    dir: PathBuf,
+    max_files: usize,
+}
+
+impl HourlyWriter {
+    /// Write `(time, message)` lines, one gzip member per file touched
+    fn write(&self, lines: &[(DateTime<Utc>, Arc<str>)]) -> std::io::Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+        let mut rest = lines;
+        while let Some((first, _)) = rest.first() {
+            let name = file_name(*first);
+            let n = rest.iter().take_while(|(t, _)| file_name(*t) == name).count();
+            let path = self.dir.join(&name);
+            let created = !path.exists();
+            let file = OpenOptions::new().create(true).append(true).open(&path)?;
+            let mut gz = GzEncoder::new(file, Compression::default());
+            for (time, msg) in &rest[..n] {
+                writeln!(gz, "{{\"t\":{},\"msg\":{}}}", time.timestamp_millis(), msg)?;
+            }
+            gz.finish()?;
+            if created {
+                debug!("Broadcast recording file: {}", name);
+                self.prune()?;
+            }
+            rest = &rest[n..];
+        }
+        Ok(())
+    }
+
+    /// Remove recordings beyond the retention limit
+    fn prune(&self) -> std::io::Result<()> {
+        let mut files: Vec<String> = std::fs::read_dir(&self.dir)?
+            .filter_map(|e| e.ok())
+            .filter_map(|e| e.file_name().into_string().ok())
+            .filter(|n| parse_name(n).is_some())
+            .collect();
+        if files.len() <= self.max_files {
+            return Ok(());
+        }
+        // Names sort chronologically
+        files.sort();
+        let excess = files.len() - self.max_files;
+        for name in files.iter().take(excess) {
+            info!("Removing old broadcast recording: {}", name);
+            std::fs::remove_file(self.dir.join(name))?;
+        }
+        Ok(())
+    }
+}
+
+/// Background task: collect broadcast messages and write them each second
+async fn run_recorder(mut rx: broadcast::Receiver<Arc<LiveMessage>>, writer: Arc<HourlyWriter>) {
+    let mut pending = Vec::new();
+    let mut flush = tokio::time::interval(FLUSH_INTERVAL);
+    loop {
+        tokio::select! {
+            msg = rx.recv() => match msg {
+                Ok(msg) => pending.push((Utc::now(), msg.json.clone())),
+                Err(broadcast::error::RecvError::Lagged(n)) => {
+                    warn!("Broadcast recording lagged, {} messages not recorded", n);
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            },
+            _ = flush.tick() => {
+                if !pending.is_empty() {
+                    write_off_runtime(&writer, std::mem::take(&mut pending)).await;
+                }
+            }
+        }
+    }
+    write_off_runtime(&writer, pending).await;
+}
+
+/// Gzip, append and prune on the blocking pool
+async fn write_off_runtime(writer: &Arc<HourlyWriter>, lines: Vec<(DateTime<Utc>, Arc<str>)>) {
+    let writer = writer.clone();
+    match tokio::task::spawn_blocking(move || writer.write(&lines)).await {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => error!("Failed to write broadcast recording: {}", e),
+        Err(e) => error!("Broadcast recording writer failed: {}", e),
+    }
+}
+
+/// List broadcast recordings
+#[utoipa::path(
+    get,
+    path = "/api/recordings",
+    tag = "history",
+    responses(
+        (status = 200, description = "Hourly recordings, oldest first; empty unless RECORDING_DIR is set", body = [Recording]),
+    ),
+    security((), ("api_key" = []), ("bearer" = []))
+)]
+pub async fn list_recordings(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    match state.recordings.list() {
+        Ok(recordings) => Json(recordings).into_response(),
+        Err(e) => {
+            error!("Failed to list broadcast recordings: {}", e);
+            ApiError::internal(e)
+        }
+    }
+}
+
+/// Download one broadcast recording
+#[utoipa::path(
+    get,
+    path = "/api/recordings/{name}",
+    tag = "history",
+    params(("name" = String, Path, description = "Recording file name, e.g. broadcast-20240101-13.ndjson.gz")),
+    responses(
+        (status = 200, description = "Gzipped NDJSON attachment", content_type = "application/gzip", body = Vec<u8>),
+        (status = 404, description = "No such recording", body = ApiError),
+    ),
+    security((), ("api_key" = []), ("bearer" = []))
+)]
+pub async fn get_recording(
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+) -> impl IntoResponse {
+    let Some(path) = state.recordings.path(&name) else {
+        return ApiError::not_found("recording not found");
+    };
+    // Streamed, so an hour of traffic is never held in memory
+    match tokio::fs::File::open(&path).await {
+        Ok(file) => (
+            [
+                (header::CONTENT_TYPE, "application/gzip".to_string()),
+                (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", name)),
+            ],
+            Body::from_stream(ReaderStream::new(file)),
+        )
+            .into_response(),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => ApiError::not_found("recording not found"),
+        Err(e) => {
+            error!("Failed to read broadcast recording {}: {}", name, e);
+            ApiError::internal(e)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use flate2::read::MultiGzDecoder;
+    use std::io::Read;
+
+    #[test]
+    fn test_hourly_recording() {
+        let dir = std::env::temp_dir().join(format!("recording-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let writer = HourlyWriter {
+            dir: dir.clone(),
+            max_files: 2,
+        };
+        let at = |h, m| Utc.with_ymd_and_hms(2024, 1, 1, h, m, 0).unwrap();
+        let msg = |s: &str| Arc::<str>::from(s);
+
+        writer.write(&[(at(12, 59), msg("{\"type\":\"signal\"}"))]).unwrap();
+        // A second member appended to the same hour, and the next hour
+        writer
+            .write(&[(at(12, 59), msg("{\"type\":\"initial\"}")), (at(13, 0), msg("{\"type\":\"alert\"}"))])
+            .unwrap();
+
+        let recordings = Recordings { dir: Some(dir.clone()) };
+        let list = recordings.list().unwrap();
+        assert_eq!(list.len(), 2);
+        assert_eq!(list[0].name, "broadcast-20240101-12.ndjson.gz");
+        assert_eq!(list[0].start, at(12, 0));
+
+        let mut text = String::new();
+        MultiGzDecoder::new(std::fs::File::open(recordings.path(&list[0].name).unwrap()).unwrap())
+            .read_to_string(&mut text)
+            .unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0], format!("{{\"t\":{},\"msg\":{{\"type\":\"signal\"}}}}", at(12, 59).timestamp_millis()));
+
+        // Retention
+        writer.write(&[(at(14, 0), msg("{}"))]).unwrap();
+        let names: Vec<String> = recordings.list().unwrap().into_iter().map(|r| r.name).collect();
+        assert_eq!(names, ["broadcast-20240101-13.ndjson.gz", "broadcast-20240101-14.ndjson.gz"]);
+
+        assert!(recordings.path("../broadcast-20240101-13.ndjson.gz").is_none());
+        assert!(recordings.path("broadcast-2024010-13.ndjson.gz").is_none());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,224 @@
+//! Cached REST responses
+//!
+//! Map pages and dashboards poll the aircraft list, statistics and coverage
+//! every few seconds, often many at once or through a CDN. Successful
+//! responses of those endpoints are kept for `RESPONSE_CACHE_MS` per URL
+//! (query included), so concurrent pollers share one computation. Every
+//! response carries an ETag of its body and a matching `max-age`, and a
+//! request whose `If-None-Match` names the current ETag gets `304 Not
+//! Modified` without a body.
+
+use crate::tenants::Scope;
+use axum::{
+    body::{to_bytes, Body, Bytes},
+    extract::{Request, State},
+    http::{header, HeaderMap, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+/// URLs cached at once; beyond this, expired entries are dropped and new
+/// responses are not kept until there is room
+const MAX_ENTRIES: usize = 512;
+
+/// A response body with what's needed to send it again
+#[derive(Clone)]
+struct CachedResponse {
+    headers: HeaderMap,
+    body: Bytes,
+    etag: String,
+    stored: Instant,
+}
+
+/// Recent responses per URL
+pub struct ResponseCache {
+    ttl: Duration,
+    entries: Mutex<HashMap<String, CachedResponse>>,
+}
+
+impl ResponseCache {
+    /// Keep responses for `ttl`; zero only adds ETags
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn get(&self, key: &str, now: Instant) -> Option<CachedResponse> {
+        let entries = self.entries.lock().ok()?;
+        entries
+            .get(key)
+            .filter(|e| now.saturating_duration_since(e.stored) < self.ttl)
+            .cloned()
+    }
+
+    fn put(&self, key: String, response: CachedResponse) {
+        if self.ttl.is_zero() {
+            return;
+        }
+        let Ok(mut entries) = self.entries.lock() else {
+            return;
+        };
+        if entries.len() >= MAX_ENTRIES && !entries.contains_key(&key) {
+            let now = response.stored;
+            entries.retain(|_, e| now.saturating_duration_since(e.stored) < self.ttl);
+            if entries.len() >= MAX_ENTRIES {
+                return;
+            }
+        }
+        entries.insert(key, response);
+    }
+
+    /// `Cache-Control` value telling clients and CDNs how long to reuse a
+    /// response
+    fn cache_control(&self) -> HeaderValue {
+        HeaderValue::from_str(&format!("max-age={}", self.ttl.as_secs()))
+            .unwrap_or(HeaderValue::from_static("no-cache"))
+    }
+
+    /// `cached` as a full response, or `304` when the client already has it
+    fn respond(&self, cached: CachedResponse, request_headers: &HeaderMap) -> Response {
+        let mut response = if if_none_match(request_headers, &cached.etag) {
+            StatusCode::NOT_MODIFIED.into_response()
+        } else {
+            let mut response = Response::new(Body::from(cached.body));
+            *response.headers_mut() = cached.headers;
+            response
+        };
+        let headers = response.headers_mut();
+        if let Ok(etag) = HeaderValue::from_str(&cached.etag) {
+            headers.insert(header::ETAG, etag);
+        }
+        headers.insert(header::CACHE_CONTROL, self.cache_control());
+        response
+    }
+}
+
+/// Strong ETag of a response body
+pub fn etag(body: &[u8]) -> String {
+    let hash: String = Sha256::digest(body)[..8]
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    format!("\"{}\"", hash)
+}
+
+/// Whether `If-None-Match` names `etag` (or is `*`); weak validators match
+/// their strong form
+pub fn if_none_match(headers: &HeaderMap, etag: &str) -> bool {
+    headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| {
+            v.split(',')
+                .map(str::trim)
+                .any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag)
+        })
+}
+
+/// Middleware serving GET requests from the cache, and filling it from the
+/// handler's successful responses
+pub async fn cached(
+    State(cache): State<Arc<ResponseCache>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if request.method() != Method::GET {
+        return next.run(request).await;
+    }
+    // Callers scoped to a tenant or the public view see different bodies
+    let scope = request.extensions().get::<Scope>().cloned().unwrap_or_default();
+    let key = format!("{} {}", scope.cache_key(), request.uri());
+    let request_headers = request.headers().clone();
+    if let Some(hit) = cache.get(&key, Instant::now()) {
+        return cache.respond(hit, &request_headers);
+    }
+
+    let response = next.run(request).await;
+    if response.status() != StatusCode::OK {
+        return response;
+    }
+    let (parts, body) = response.into_parts();
+    let body = match to_bytes(body, usize::MAX).await {
+        Ok(body) => body,
+        Err(e) => {
+            warn!("Failed to read response for {}: {}", key, e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    let entry = CachedResponse {
+        etag: etag(&body),
+        headers: parts.headers,
+        body,
+        stored: Instant::now(),
+    };
+    cache.put(key, entry.clone());
+    cache.respond(entry, &request_headers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{middleware, routing::get, Router};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn test_cached_with_etag() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let cache = Arc::new(ResponseCache::new(Duration::from_secs(60)));
+        let app = Router::new()
+            .route(
+                "/api/stats",
+                get(move || async move { format!("{{\"calls\":{}}}", counter.fetch_add(1, Ordering::SeqCst)) }),
+            )
+            .route_layer(middleware::from_fn_with_state(cache, cached));
+        let request = |uri: &str, etag: Option<&HeaderValue>| {
+            let mut request = Request::get(uri).body(Body::empty()).unwrap();
+            if let Some(etag) = etag {
+                request.headers_mut().insert(header::IF_NONE_MATCH, etag.clone());
+            }
+            app.clone().oneshot(request)
+        };
+
+        let first = request("/api/stats?hours=24", None).await.unwrap();
+        assert_eq!(first.headers()[header::CACHE_CONTROL], "max-age=60");
+        let tag = first.headers()[header::ETAG].clone();
+        assert_eq!(to_bytes(first.into_body(), 1024).await.unwrap(), "{\"calls\":0}");
+
+        // Served again without calling the handler, or not at all when the
+        // client has it
+        let again = request("/api/stats?hours=24", None).await.unwrap();
+        assert_eq!(to_bytes(again.into_body(), 1024).await.unwrap(), "{\"calls\":0}");
+        let revalidated = request("/api/stats?hours=24", Some(&tag)).await.unwrap();
+        assert_eq!(revalidated.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(revalidated.headers()[header::ETAG], tag);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        // Another query is another entry
+        let other = request("/api/stats?hours=48", Some(&tag)).await.unwrap();
+        assert_eq!(other.status(), StatusCode::OK);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_if_none_match() {
+        let tag = etag(b"[]");
+        let headers = |value: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(header::IF_NONE_MATCH, HeaderValue::from_str(value).unwrap());
+            headers
+        };
+        assert!(if_none_match(&headers(&format!("\"x\", W/{}", tag)), &tag));
+        assert!(if_none_match(&headers("*"), &tag));
+        assert!(!if_none_match(&headers("\"x\""), &tag));
+        assert!(!if_none_match(&HeaderMap::new(), &tag));
+    }
+}
@@ -0,0 +1,263 @@
+//! Callsign to route (origin/destination airport) lookup
+//!
+//! Routes come from a local standing-data file (`routes.csv` with
+//! `Callsign,...,AirportCodes` where codes are `EGLL-KJFK`) and/or an
+//! adsbdb-compatible HTTP API (`.../callsign/{callsign}`). API results are
+//! cached, including misses, and fetched in the background so lookups on
+//! the event path never wait on the network.
+
+use anyhow::{anyhow, Result};
+use serde_json::Value as JsonValue;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{debug, info};
+
+/// How long a route fetched from the API stays cached
+const ROUTE_CACHE_TTL: Duration = Duration::from_secs(6 * 3600);
+
+/// How long an unknown callsign is remembered before asking again
+const MISS_CACHE_TTL: Duration = Duration::from_secs(3600);
+
+/// Cap on cached API results
+const MAX_CACHE_ENTRIES: usize = 20_000;
+
+/// Origin and destination airports (ICAO codes)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Route {
+    pub origin: String,
+    pub destination: String,
+}
+
+/// Route lookup with static table, API client and cache
+pub struct RouteLookup {
+    table: HashMap<String, Route>,
+    api: Option<RouteApi>,
+}
+
+struct RouteApi {
+    client: reqwest::Client,
+    url_template: String,
+    cache: Mutex<HashMap<String, (Option<Route>, Instant)>>,
+    in_flight: Mutex<HashSet<String>>,
+}
+
+impl RouteLookup {
+    /// Lookup with no data source (enrichment disabled)
+    pub fn disabled() -> Self {
+        Self {
+            table: HashMap::new(),
+            api: None,
+        }
+    }
+
+    /// Build from an optional standing-data file and optional API URL template
+    pub fn new(path: Option<&Path>, api_url: Option<&str>) -> Result<Self> {
+        let table = match path {
+            Some(path) => {
+                let table = load_routes(path)?;
+                info!("Loaded {} routes from {}", table.len(), path.display());
+                table
+            }
+            None => HashMap::new(),
+        };
+
+        let api = match api_url {
+            Some(url) => Some(RouteApi {
+                client: reqwest::Client::builder()
+                    .timeout(Duration::from_secs(10))
+                    .build()?,
+                url_template: url.to_string(),
+                cache: Mutex::new(HashMap::new()),
+                in_flight: Mutex::new(HashSet::new()),
+            }),
+            None => None,
+        };
+
+        Ok(Self { table, api })
+    }
+
+    /// Look up a route; API misses are fetched in the background and
+    /// returned on a later call
+    pub fn lookup(self: &Arc<Self>, callsign: &str) -> Option<Route> {
+        let callsign = callsign.trim().to_ascii_uppercase();
+        if callsign.is_empty() {
+            return None;
+        }
+        if let Some(route) = self.table.get(&callsign) {
+            return Some(route.clone());
+        }
+
+        let api = self.api.as_ref()?;
+        if let Ok(cache) = api.cache.lock() {
+            if let Some((route, fetched)) = cache.get(&callsign) {
+                let ttl = if route.is_some() { ROUTE_CACHE_TTL } else { MISS_CACHE_TTL };
+                if fetched.elapsed() < ttl {
+                    return route.clone();
+                }
+            }
+        }
+
+        let started = api
+            .in_flight
+            .lock()
+            .map(|mut set| set.insert(callsign.clone()))
+            .unwrap_or(false);
+        if started {
+            let this = self.clone();
+            tokio::spawn(async move { this.fetch(callsign).await });
+        }
+        None
+    }
+
+    /// Add `origin`/`destination` to a JSON object that has a `callsign` key
+    pub fn enrich(self: &Arc<Self>, value: &mut JsonValue) {
+        let route = match value.get("callsign").and_then(|v| v.as_str()) {
+            Some(callsign) => self.lookup(callsign),
+            None => return,
+        };
+        if let (Some(route), Some(obj)) = (route, value.as_object_mut()) {
+            obj.insert("origin".into(), route.origin.into());
+            obj.insert("destination".into(), route.destination.into());
+        }
+    }
+
+    async fn fetch(&self, callsign: String) {
+        let api = match &self.api {
+            Some(api) => api,
+            None => return,
+        };
+
+        let url = route_url(&api.url_template, &callsign);
+        let result = async {
+            let response = api.client.get(&url).send().await?;
+            if response.status() == reqwest::StatusCode::NOT_FOUND {
+                return Ok(None);
+            }
+            let body: JsonValue = response.error_for_status()?.json().await?;
+            Ok::<_, anyhow::Error>(parse_api_route(&body))
+        }
+        .await;
+
+        match result {
+            Ok(route) => {
+                debug!("Route for {}: {:?}", callsign, route);
+                if let Ok(mut cache) = api.cache.lock() {
+                    if cache.len() >= MAX_CACHE_ENTRIES {
+                        cache.retain(|_, (_, fetched)| fetched.elapsed() < MISS_CACHE_TTL);
+                    }
+                    cache.insert(callsign.clone(), (route, Instant::now()));
+                }
+            }
+            // Transient errors are not cached; the next lookup retries
+            Err(e) => debug!("Route lookup for {} failed: {}", callsign, e),
+        }
+
+        if let Ok(mut in_flight) = api.in_flight.lock() {
+            in_flight.remove(&callsign);
+        }
+    }
+}
+
+/// Extract origin/destination from an adsbdb-style response
+fn parse_api_route(body: &JsonValue) -> Option<Route> {
+    let route = &body["response"]["flightroute"];
+    let code = |airport: &JsonValue| {
+        airport["icao_code"]
+            .as_str()
+            .or_else(|| airport["iata_code"].as_str())
+            .map(str::to_string)
+    };
+    Some(Route {
+        origin: code(&route["origin"])?,
+        destination: code(&route["destination"])?,
+    })
+}
+
+/// Load a standing-data `routes.csv` (header with `Callsign` and `AirportCodes`)
+fn load_routes(path: &Path) -> Result<HashMap<String, Route>> {
+    let mut csv = csv::ReaderBuilder::new().flexible(true).from_path(path)?;
+    let headers = csv.headers()?.clone();
+    let find = |name: &str| headers.iter().position(|h| h.trim().eq_ignore_ascii_case(name));
+    let callsign_col = find("Callsign").ok_or_else(|| anyhow!("missing Callsign column"))?;
+    let airports_col = find("AirportCodes").ok_or_else(|| anyhow!("missing AirportCodes column"))?;
+
+    let mut routes = HashMap::new();
+    for record in csv.records().filter_map(|r| r.ok()) {
+        let callsign = record.get(callsign_col).unwrap_or_default().trim().to_ascii_uppercase();
+        let airports = record.get(airports_col).unwrap_or_default();
+        if let Some(route) = parse_airport_codes(airports) {
+            if !callsign.is_empty() {
+                routes.insert(callsign, route);
+            }
+        }
+    }
+    Ok(routes)
+}
+
+/// `EGLL-LTBA-KJFK` -> origin EGLL, destination KJFK
+fn parse_airport_codes(codes: &str) -> Option<Route> {
+    let airports: Vec<&str> = codes
+        .split('-')
+        .map(str::trim)
+        .filter(|c| !c.is_empty())
+        .collect();
+    if airports.len() < 2 {
+        return None;
+    }
+    Some(Route {
+        origin: airports[0].to_string(),
+        destination: airports[airports.len() - 1].to_string(),
+    })
+}
+
+/// Fill `{callsign}` in the API URL, percent-encoded since it comes off the air
+fn route_url(template: &str, callsign: &str) -> String {
+    let encoded: String = url::form_urlencoded::byte_serialize(callsign.as_bytes()).collect();
+    template.replace("{callsign}", &encoded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_route_url() {
+        let template = "https://api.adsbdb.com/v0/callsign/{callsign}";
+        assert_eq!(route_url(template, "KAL123"), "https://api.adsbdb.com/v0/callsign/KAL123");
+        assert_eq!(
+            route_url(template, "A/../x?y#z"),
+            "https://api.adsbdb.com/v0/callsign/A%2F..%2Fx%3Fy%23z"
+        );
+    }
+
+    #[test]
+    fn test_parse_airport_codes() {
+        assert_eq!(
+            parse_airport_codes("RKSI-VHHH-WSSS"),
+            Some(Route {
+                origin: "RKSI".into(),
+                destination: "WSSS".into()
+            })
+        );
+        assert_eq!(parse_airport_codes("RKSI"), None);
+        assert_eq!(parse_airport_codes(""), None);
+    }
+
+    #[test]
+    fn test_parse_api_route() {
+        let body = serde_json::json!({
+            "response": {"flightroute": {
+                "callsign": "KAL123",
+                "origin": {"icao_code": "RKSI", "iata_code": "ICN"},
+                "destination": {"icao_code": "KLAX", "iata_code": "LAX"}
+            }}
+        });
+        let route = parse_api_route(&body).unwrap();
+        assert_eq!(route.origin, "RKSI");
+        assert_eq!(route.destination, "KLAX");
+
+        assert_eq!(parse_api_route(&serde_json::json!({"response": "unknown callsign"})), None);
+    }
+}
@@ -0,0 +1,272 @@
+//! Plausibility checks on incoming events
+//!
+//! A corrupted frame occasionally passes the CRC and decodes to a position
+//! on another continent or an altitude no aircraft reaches. Before an event
+//! is merged, stored or broadcast, values that can't be right are removed
+//! from it: positions farther than `MAX_RANGE_KM` from the receiver (when
+//! `RECEIVER_LAT`/`RECEIVER_LON` are set), and altitudes or speeds outside
+//! what aircraft fly, and optionally positions whose integrity (NIC) or
+//! accuracy (NACp) category is below a threshold; some GA transponders'
+//! low-integrity positions wander hundreds of metres. The rest of the event
+//! is kept. Rejections are counted per device and reason and exported on
+//! `/metrics`.
+
+use crate::adsb::AircraftEvent;
+use crate::geo::haversine_km;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Lowest and highest plausible pressure altitude
+const ALTITUDE_RANGE_FT: std::ops::RangeInclusive<i32> = -1_500..=60_000;
+
+/// Fastest plausible ground speed
+const MAX_SPEED_KTS: f32 = 1_000.0;
+
+/// Why a value was removed from an event
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Rejection {
+    /// Position beyond the receiver's range
+    Range,
+    /// Position outside valid coordinates
+    Coordinates,
+    Altitude,
+    Speed,
+    /// Position integrity or accuracy below the configured minimum
+    Quality,
+}
+
+impl Rejection {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Rejection::Range => "range",
+            Rejection::Coordinates => "coordinates",
+            Rejection::Altitude => "altitude",
+            Rejection::Speed => "speed",
+            Rejection::Quality => "quality",
+        }
+    }
+}
+
+/// Lowest position quality accepted; 0 disables a limit
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PositionQuality {
+    pub min_nic: u32,
+    pub min_nac_p: u32,
+    /// Keep positions below the limits and mark them on live updates
+    /// instead of removing them
+    pub flag_only: bool,
+}
+
+impl PositionQuality {
+    /// Whether the event's position is below the limits. Categories the
+    /// sender didn't report aren't held against it; a version 0 NUCp is
+    /// compared as the NIC whose containment radius covers it
+    pub fn is_low(&self, event: &AircraftEvent) -> bool {
+        if event.position().is_none() {
+            return false;
+        }
+        let nic = event.nic.or(event.nuc_p.map(|nuc_p| match nuc_p {
+            9 => 11,
+            8 => 10,
+            7 => 8,
+            3..=6 => nuc_p + 1,
+            1 | 2 => 1,
+            _ => 0,
+        }));
+        let below = |value: Option<u32>, min: u32| min > 0 && value.is_some_and(|v| v < min);
+        below(nic, self.min_nic) || below(event.nac_p, self.min_nac_p)
+    }
+}
+
+/// Removes implausible values from events
+pub struct SanityFilter {
+    receiver: Option<(f64, f64)>,
+    /// 0 means no limit
+    max_range_km: f64,
+    quality: PositionQuality,
+    rejected: Mutex<HashMap<(String, Rejection), u64>>,
+}
+
+impl SanityFilter {
+    pub fn new(receiver: Option<(f64, f64)>, max_range_km: f64, quality: PositionQuality) -> Self {
+        Self {
+            receiver,
+            max_range_km,
+            quality,
+            rejected: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// What is wrong with an event, if anything
+    fn problems(&self, event: &AircraftEvent) -> Vec<Rejection> {
+        let mut problems = Vec::new();
+        if let Some((lat, lon)) = event.position() {
+            if !(-90.0..=90.0).contains(&lat) || !(-180.0..=180.0).contains(&lon) {
+                problems.push(Rejection::Coordinates);
+            } else if let Some((rx_lat, rx_lon)) = self.receiver {
+                if self.max_range_km > 0.0
+                    && haversine_km(rx_lat, rx_lon, lat, lon) > self.max_range_km
+                {
+                    problems.push(Rejection::Range);
+                }
+            }
+            if !self.quality.flag_only && self.quality.is_low(event) {
+                problems.push(Rejection::Quality);
+            }
+        }
+        if event
+            .altitude_ft
+            .is_some_and(|ft| !ALTITUDE_RANGE_FT.contains(&ft))
+        {
+            problems.push(Rejection::Altitude);
+        }
+        if event
+            .speed_kts
+            .is_some_and(|kts| !(0.0..=MAX_SPEED_KTS).contains(&kts))
+        {
+            problems.push(Rejection::Speed);
+        }
+        problems
+    }
+
+    /// Remove implausible values from `event`, counting each rejection;
+    /// returns what was removed
+    pub fn apply(&self, event: &mut AircraftEvent) -> Vec<Rejection> {
+        let problems = self.problems(event);
+        for problem in &problems {
+            match problem {
+                // The smoothed track was built from the bad position too
+                Rejection::Range | Rejection::Coordinates | Rejection::Quality => {
+                    event.latitude = None;
+                    event.longitude = None;
+                    event.filtered_latitude = None;
+                    event.filtered_longitude = None;
+                }
+                Rejection::Altitude => event.altitude_ft = None,
+                Rejection::Speed => event.speed_kts = None,
+            }
+        }
+        if !problems.is_empty() {
+            if let Ok(mut rejected) = self.rejected.lock() {
+                for problem in &problems {
+                    *rejected
+                        .entry((event.device_id.clone(), *problem))
+                        .or_default() += 1;
+                }
+            }
+        }
+        problems
+    }
+
+    /// Whether to mark the event's position as low quality for live clients
+    /// (only when such positions are kept)
+    pub fn flag_low_quality(&self, event: &AircraftEvent) -> bool {
+        self.quality.flag_only && self.quality.is_low(event)
+    }
+
+    /// Rejections so far as `(device_id, reason, count)`, sorted
+    pub fn rejected(&self) -> Vec<(String, &'static str, u64)> {
+        let Ok(rejected) = self.rejected.lock() else {
+            return Vec::new();
+        };
+        let mut counts: Vec<_> = rejected
+            .iter()
+            .map(|((device, reason), count)| (device.clone(), reason.as_str(), *count))
+            .collect();
+        counts.sort();
+        counts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(lat: f64, lon: f64, altitude: i32, speed: f32) -> AircraftEvent {
+        AircraftEvent {
+            icao: "71BE11".into(),
+            device_id: "rtlsdr-0".into(),
+            latitude: Some(lat),
+            longitude: Some(lon),
+            altitude_ft: Some(altitude),
+            speed_kts: Some(speed),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_rejections() {
+        // Incheon, 300 km limit
+        let filter = SanityFilter::new(Some((37.46, 126.44)), 300.0, PositionQuality::default());
+
+        let mut nearby = event(37.9, 127.1, 35000, 450.0);
+        assert!(filter.apply(&mut nearby).is_empty());
+        assert_eq!(nearby.position(), Some((37.9, 127.1)));
+
+        // Frankfurt: position removed, the rest kept
+        let mut far = event(50.03, 8.56, 35000, 450.0);
+        assert_eq!(filter.apply(&mut far), vec![Rejection::Range]);
+        assert_eq!(far.position(), None);
+        assert_eq!(far.altitude_ft, Some(35000));
+
+        let mut garbled = event(37.9, 127.1, 112_000, 3_000.0);
+        assert_eq!(
+            filter.apply(&mut garbled),
+            vec![Rejection::Altitude, Rejection::Speed]
+        );
+        assert_eq!((garbled.altitude_ft, garbled.speed_kts), (None, None));
+
+        assert_eq!(
+            filter.rejected(),
+            vec![
+                ("rtlsdr-0".to_string(), "altitude", 1),
+                ("rtlsdr-0".to_string(), "range", 1),
+                ("rtlsdr-0".to_string(), "speed", 1),
+            ]
+        );
+
+        // Without a receiver location only coordinates are checked
+        let unplaced = SanityFilter::new(None, 300.0, PositionQuality::default());
+        assert!(unplaced
+            .apply(&mut event(50.03, 8.56, 35000, 450.0))
+            .is_empty());
+        assert_eq!(
+            unplaced.apply(&mut event(95.0, 8.56, 35000, 450.0)),
+            vec![Rejection::Coordinates]
+        );
+    }
+
+    #[test]
+    fn test_position_quality() {
+        let quality = PositionQuality {
+            min_nic: 6,
+            min_nac_p: 8,
+            flag_only: false,
+        };
+        let filter = SanityFilter::new(None, 0.0, quality);
+        let with = |nic, nac_p, nuc_p| AircraftEvent {
+            nic,
+            nac_p,
+            nuc_p,
+            ..event(37.9, 127.1, 35000, 450.0)
+        };
+
+        assert!(filter.apply(&mut with(Some(7), Some(9), None)).is_empty());
+        let mut wandering = with(Some(5), Some(9), None);
+        assert_eq!(filter.apply(&mut wandering), vec![Rejection::Quality]);
+        assert_eq!(wandering.position(), None);
+        assert_eq!(wandering.altitude_ft, Some(35000));
+        assert_eq!(filter.apply(&mut with(Some(8), Some(7), None)), vec![Rejection::Quality]);
+
+        // Version 0: NUCp 5 is as good as NIC 6, and there is no NACp
+        assert!(filter.apply(&mut with(None, None, Some(5))).is_empty());
+        assert_eq!(filter.apply(&mut with(None, None, Some(3))), vec![Rejection::Quality]);
+
+        // Flagging keeps the position
+        let flagging = SanityFilter::new(None, 0.0, PositionQuality { flag_only: true, ..quality });
+        let mut wandering = with(Some(5), Some(9), None);
+        assert!(flagging.apply(&mut wandering).is_empty());
+        assert!(flagging.flag_low_quality(&wandering));
+        assert!(!flagging.flag_low_quality(&with(Some(7), Some(9), None)));
+    }
+}
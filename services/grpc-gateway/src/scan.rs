@@ -0,0 +1,155 @@
+//! Frequency scan reports
+//!
+//! A capture host started with `SCAN_SPAN_MHZ` steps its tuner around
+//! 1090 MHz before capturing and reports the noise floor and strongest spur
+//! at each step. The latest report per device is kept in memory and served
+//! by `/api/sdr/{device_id}/scan`, so a quiet receiver can be told apart
+//! from one drowned in local interference without visiting the site.
+
+use crate::adsb::FrequencyScan;
+use crate::api::ApiError;
+use crate::tenants::Scope;
+use crate::AppState;
+use axum::{
+    extract::{Path, State},
+    response::IntoResponse,
+    Json,
+};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use utoipa::ToSchema;
+
+/// Measurements at one tuner frequency
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct ScanPoint {
+    pub frequency_hz: u64,
+    /// Median spectral power, scaled to the full band
+    pub noise_dbfs: f32,
+    /// Strongest sample magnitude
+    pub peak_dbfs: f32,
+    /// Strongest narrowband component above the noise floor
+    pub spur_db: f32,
+    /// Where that component sits relative to `frequency_hz`
+    pub spur_offset_hz: i32,
+}
+
+/// A device's latest frequency scan
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct ScanReport {
+    pub device_id: String,
+    /// When the device ran the scan (Unix milliseconds)
+    pub timestamp_ms: u64,
+    pub gain_db: f32,
+    /// In frequency order
+    pub points: Vec<ScanPoint>,
+}
+
+impl From<&FrequencyScan> for ScanReport {
+    fn from(scan: &FrequencyScan) -> Self {
+        Self {
+            device_id: scan.device_id.clone(),
+            timestamp_ms: scan.timestamp_ms,
+            gain_db: scan.gain_db,
+            points: scan
+                .points
+                .iter()
+                .map(|point| ScanPoint {
+                    frequency_hz: point.frequency_hz,
+                    noise_dbfs: point.noise_dbfs,
+                    peak_dbfs: point.peak_dbfs,
+                    spur_db: point.spur_db,
+                    spur_offset_hz: point.spur_offset_hz,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Latest scan per device
+#[derive(Default)]
+pub struct ScanStore {
+    reports: Mutex<HashMap<String, ScanReport>>,
+}
+
+impl ScanStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keep `scan`, replacing the device's previous one
+    pub fn record(&self, scan: &FrequencyScan) {
+        let mut report = ScanReport::from(scan);
+        report.points.sort_by_key(|point| point.frequency_hz);
+        if let Ok(mut reports) = self.reports.lock() {
+            reports.insert(scan.device_id.clone(), report);
+        }
+    }
+
+    pub fn get(&self, device_id: &str) -> Option<ScanReport> {
+        self.reports.lock().ok()?.get(device_id).cloned()
+    }
+}
+
+/// Get a device's latest frequency scan
+#[utoipa::path(
+    get,
+    path = "/api/sdr/{device_id}/scan",
+    tag = "receiver",
+    params(("device_id" = String, Path, description = "Capture device ID")),
+    responses(
+        (status = 200, description = "Latest scan", body = ScanReport),
+        (status = 404, description = "Device has not reported a scan since the gateway started", body = ApiError),
+    ),
+    security((), ("api_key" = []), ("bearer" = []))
+)]
+pub async fn get_sdr_scan(
+    State(state): State<Arc<AppState>>,
+    scope: Scope,
+    Path(device_id): Path<String>,
+) -> impl IntoResponse {
+    match state.scans.get(&device_id).filter(|_| scope.allows_device(&device_id)) {
+        Some(report) => Json(report).into_response(),
+        None => ApiError::not_found("no scan reported for device"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adsb;
+
+    fn point(frequency_hz: u64, noise_dbfs: f32) -> adsb::ScanPoint {
+        adsb::ScanPoint {
+            frequency_hz,
+            noise_dbfs,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_latest_scan_per_device() {
+        let store = ScanStore::new();
+        assert!(store.get("rtlsdr-0").is_none());
+
+        store.record(&FrequencyScan {
+            device_id: "rtlsdr-0".into(),
+            timestamp_ms: 1,
+            points: vec![point(1_091_000_000, -30.0), point(1_089_000_000, -31.0)],
+            ..Default::default()
+        });
+        let report = store.get("rtlsdr-0").unwrap();
+        assert_eq!(report.points[0].frequency_hz, 1_089_000_000);
+        assert_eq!(report.points[1].noise_dbfs, -30.0);
+
+        store.record(&FrequencyScan {
+            device_id: "rtlsdr-0".into(),
+            timestamp_ms: 2,
+            points: vec![point(1_090_000_000, -20.0)],
+            ..Default::default()
+        });
+        let report = store.get("rtlsdr-0").unwrap();
+        assert_eq!((report.timestamp_ms, report.points.len()), (2, 1));
+        assert!(store.get("rtlsdr-1").is_none());
+    }
+}
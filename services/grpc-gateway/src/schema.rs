@@ -0,0 +1,377 @@
+//! Versioned WebSocket message schemas
+//!
+//! Every WebSocket message carries the protocol version it follows in a `v`
+//! field, and `GET /api/schema` serves a JSON Schema document with a
+//! definition per message type of the current version, so front ends and
+//! other consumers can check what they parse against what the gateway
+//! sends. Clients ask for a version with `?v=` when connecting; those that
+//! don't are taken as version 1, the protocol from before versioning, and
+//! get messages translated back to it. A version the gateway doesn't know
+//! is refused at the upgrade.
+//!
+//! Versions:
+//! - 1: `emergency` and `geofence` fields the aircraft never reported are
+//!   sent as empty strings and zeros
+//! - 2: those fields are `null` until reported, so a position of 0,0 or an
+//!   altitude of 0 is a real report
+
+use axum::Json;
+use serde_json::{json, Map, Value as JsonValue};
+use std::borrow::Cow;
+
+/// Protocol version a WebSocket client speaks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Version(u32);
+
+impl Version {
+    /// Newest version, described by `/api/schema`
+    pub const CURRENT: Self = Self(2);
+    /// Unversioned clients
+    pub const LEGACY: Self = Self(1);
+
+    /// The version for a client's `?v=`, if the gateway speaks it
+    pub fn negotiate(requested: Option<u32>) -> Result<Self, String> {
+        match requested {
+            None => Ok(Self::LEGACY),
+            Some(v) if (Self::LEGACY.0..=Self::CURRENT.0).contains(&v) => Ok(Self(v)),
+            Some(v) => Err(format!(
+                "unsupported protocol version {}; this gateway speaks {} to {}",
+                v,
+                Self::LEGACY.0,
+                Self::CURRENT.0
+            )),
+        }
+    }
+
+    pub fn number(self) -> u32 {
+        self.0
+    }
+
+    /// A `kind` message of the current version as this version expects it
+    pub fn translate<'a>(self, kind: &str, msg: Cow<'a, str>) -> Cow<'a, str> {
+        if self.0 >= 2 {
+            return msg;
+        }
+        // Version 1 filled in what was never reported
+        let defaults: &[(&str, JsonValue)] = match kind {
+            "emergency" => &[
+                ("callsign", JsonValue::String(String::new())),
+                ("lat", json!(0.0)),
+                ("lon", json!(0.0)),
+                ("altitude", json!(0)),
+            ],
+            "geofence" => &[("callsign", JsonValue::String(String::new())), ("altitude_ft", json!(0))],
+            _ => return msg,
+        };
+        let Ok(mut json) = serde_json::from_str::<JsonValue>(&msg) else {
+            return msg;
+        };
+        if let Some(obj) = json.as_object_mut() {
+            for (key, default) in defaults {
+                if obj.get(*key).is_none_or(JsonValue::is_null) {
+                    obj.insert(key.to_string(), default.clone());
+                }
+            }
+        }
+        Cow::Owned(json.to_string())
+    }
+
+    /// Add the `v` field to an outgoing message
+    pub fn stamp(self, msg: &mut String) {
+        if !msg.starts_with('{') {
+            return;
+        }
+        let empty = msg[1..].trim_start().starts_with('}');
+        let field = format!("\"v\":{}{}", self.0, if empty { "" } else { "," });
+        msg.insert_str(1, &field);
+    }
+}
+
+/// JSON Schema of one message type
+fn message(kind: &str, description: &str, required: &[&str], properties: JsonValue) -> JsonValue {
+    let mut all = Map::new();
+    all.insert("v".into(), json!({"const": Version::CURRENT.0}));
+    all.insert("type".into(), json!({"const": kind}));
+    if let JsonValue::Object(properties) = properties {
+        all.extend(properties);
+    }
+    let mut required_all = vec!["v", "type"];
+    required_all.extend_from_slice(required);
+    json!({
+        "description": description,
+        "type": "object",
+        "properties": all,
+        "required": required_all,
+    })
+}
+
+fn nullable(kind: &str) -> JsonValue {
+    json!({"type": [kind, "null"]})
+}
+
+/// Fields of an aircraft in `position_update` and `initial`; ones never
+/// reported are left out
+fn aircraft_properties() -> JsonValue {
+    json!({
+        "icao": {"type": "string", "description": "6 hex digits, `~` prefix for non-ICAO addresses"},
+        "device_id": {"type": "string"},
+        "lat": {"type": "number"},
+        "lon": {"type": "number"},
+        "altitude": {"type": "integer", "description": "Feet, or metres when subscribed so"},
+        "speed": {"type": "number", "description": "Knots, or as subscribed"},
+        "track": {"type": "number"},
+        "magnetic_heading": {"type": "number"},
+        "vrate": {"type": "integer"},
+        "baro_rate": {"type": "integer"},
+        "geom_rate": {"type": "integer"},
+        "callsign": {"type": "string"},
+        "squawk": {"type": "string"},
+        "category": {"type": "string"},
+        "emergency": nullable("string"),
+        "timestamp_ms": {"type": "integer"},
+    })
+}
+
+/// Schema document for the current version
+pub fn document() -> JsonValue {
+    let messages = [
+        (
+            "initial",
+            message(
+                "initial",
+                "Aircraft in view, sent on connect and after each subscribe",
+                &["aircraft"],
+                json!({"aircraft": {"type": "array", "items": {"type": "object", "properties": aircraft_properties()}}}),
+            ),
+        ),
+        (
+            "position_update",
+            message("position_update", "New state of an aircraft", &["icao"], aircraft_properties()),
+        ),
+        (
+            "position_delta",
+            message(
+                "position_delta",
+                "Fields changed since the last update with `?delta=true`; `null` when a field went away",
+                &["icao"],
+                json!({"icao": {"type": "string"}}),
+            ),
+        ),
+        (
+            "snapshot_diff",
+            message(
+                "snapshot_diff",
+                "Updates batched with `?coalesce_ms=`",
+                &["time", "updates", "removed"],
+                json!({
+                    "time": {"type": "string", "format": "date-time"},
+                    "updates": {"type": "array", "items": {"type": "object"}},
+                    "removed": {"type": "array", "items": {"type": "string"}},
+                }),
+            ),
+        ),
+        (
+            "aircraft_removed",
+            message(
+                "aircraft_removed",
+                "Aircraft no longer in view",
+                &["icao", "reason"],
+                json!({"icao": {"type": "string"}, "reason": {"enum": ["tracker", "timeout"]}}),
+            ),
+        ),
+        (
+            "signal",
+            message(
+                "signal",
+                "Receiver signal and decoder statistics",
+                &["device_id", "signal_dbfs", "noise_dbfs", "snr_db", "msg_rate", "timestamp_ms"],
+                json!({
+                    "device_id": {"type": "string"},
+                    "signal_dbfs": {"type": "number"},
+                    "noise_dbfs": {"type": "number"},
+                    "snr_db": {"type": "number"},
+                    "msg_rate": {"type": "number"},
+                    "timestamp_ms": {"type": "integer"},
+                }),
+            ),
+        ),
+        (
+            "device_status",
+            message(
+                "device_status",
+                "Receiver connection and tuning",
+                &["device_id", "connected"],
+                json!({
+                    "device_id": {"type": "string"},
+                    "connected": {"type": "boolean"},
+                    "sample_rate": {"type": "integer"},
+                    "center_freq": {"type": "integer"},
+                    "gain_db": {"type": "number"},
+                }),
+            ),
+        ),
+        (
+            "emergency",
+            message(
+                "emergency",
+                "Emergency squawk started or ended",
+                &["icao", "emergency", "squawk", "active", "started_at"],
+                json!({
+                    "icao": {"type": "string"},
+                    "emergency": {"enum": ["hijack", "radio_failure", "general"]},
+                    "description": {"type": "string"},
+                    "squawk": {"type": "string"},
+                    "callsign": nullable("string"),
+                    "active": {"type": "boolean"},
+                    "started_at": {"type": "string", "format": "date-time"},
+                    "ended_at": {"type": ["string", "null"], "format": "date-time"},
+                    "lat": nullable("number"),
+                    "lon": nullable("number"),
+                    "altitude": nullable("integer"),
+                    "device_id": {"type": "string"},
+                }),
+            ),
+        ),
+        (
+            "geofence",
+            message(
+                "geofence",
+                "Aircraft entered or left a geofence",
+                &["geofence_id", "geofence_name", "icao", "event", "reason", "lat", "lon", "time"],
+                json!({
+                    "geofence_id": {"type": "integer"},
+                    "geofence_name": {"type": "string"},
+                    "icao": {"type": "string"},
+                    "callsign": nullable("string"),
+                    "event": {"enum": ["enter", "exit"]},
+                    "reason": {"enum": ["position", "timeout"]},
+                    "lat": {"type": "number"},
+                    "lon": {"type": "number"},
+                    "altitude_ft": nullable("integer"),
+                    "time": {"type": "string", "format": "date-time"},
+                }),
+            ),
+        ),
+        (
+            "squawk_change",
+            message(
+                "squawk_change",
+                "Aircraft changed squawk code",
+                &["icao", "previous_squawk", "squawk"],
+                json!({
+                    "icao": {"type": "string"},
+                    "device_id": {"type": "string"},
+                    "previous_squawk": {"type": "string"},
+                    "squawk": {"type": "string"},
+                    "timestamp_ms": {"type": "integer"},
+                }),
+            ),
+        ),
+        (
+            "callsign_change",
+            message(
+                "callsign_change",
+                "Aircraft changed callsign, closing one flight and opening another",
+                &["icao", "previous_callsign", "callsign"],
+                json!({
+                    "icao": {"type": "string"},
+                    "device_id": {"type": "string"},
+                    "previous_callsign": {"type": "string"},
+                    "callsign": {"type": "string"},
+                    "previous_flight_started": {"type": "string", "format": "date-time"},
+                    "timestamp_ms": {"type": "integer"},
+                }),
+            ),
+        ),
+        (
+            "alert",
+            message(
+                "alert",
+                "Watchlist rule matched",
+                &["rule_id", "rule_name", "icao"],
+                json!({
+                    "rule_id": {"type": "integer"},
+                    "rule_name": {"type": "string"},
+                    "match": {"type": "string"},
+                    "icao": {"type": "string"},
+                    "callsign": nullable("string"),
+                    "registration": nullable("string"),
+                    "squawk": nullable("string"),
+                    "lat": nullable("number"),
+                    "lon": nullable("number"),
+                    "altitude": nullable("integer"),
+                }),
+            ),
+        ),
+        (
+            "subscribed",
+            message(
+                "subscribed",
+                "Subscription in effect after a `subscribe`",
+                &[],
+                json!({
+                    "bbox": {"type": ["array", "null"], "items": {"type": "number"}, "minItems": 4, "maxItems": 4},
+                    "icao": {"type": "array", "items": {"type": "string"}},
+                    "types": {"type": "array", "items": {"type": "string"}},
+                    "min_alt": nullable("integer"),
+                    "max_alt": nullable("integer"),
+                }),
+            ),
+        ),
+        (
+            "error",
+            message("error", "A client message was refused", &["error"], json!({"error": {"type": "string"}})),
+        ),
+    ];
+    let refs: Vec<JsonValue> = messages
+        .iter()
+        .map(|(kind, _)| json!({"$ref": format!("#/$defs/{}", kind)}))
+        .collect();
+    let defs: Map<String, JsonValue> = messages.into_iter().map(|(kind, schema)| (kind.to_string(), schema)).collect();
+    json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "title": "ADS-B gateway WebSocket messages",
+        "version": Version::CURRENT.0,
+        "oldest_version": Version::LEGACY.0,
+        "oneOf": refs,
+        "$defs": defs,
+    })
+}
+
+/// `GET /api/schema`
+pub async fn get_schema() -> Json<JsonValue> {
+    Json(document())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_versions() {
+        assert_eq!(Version::negotiate(None), Ok(Version::LEGACY));
+        assert_eq!(Version::negotiate(Some(2)), Ok(Version::CURRENT));
+        assert!(Version::negotiate(Some(3)).is_err());
+
+        let emergency = r#"{"type":"emergency","icao":"71BE11","callsign":null,"lat":null,"lon":null,"altitude":3500}"#;
+        let legacy: JsonValue =
+            serde_json::from_str(&Version::LEGACY.translate("emergency", Cow::Borrowed(emergency))).unwrap();
+        assert_eq!(
+            legacy,
+            json!({"type": "emergency", "icao": "71BE11", "callsign": "", "lat": 0.0, "lon": 0.0, "altitude": 3500})
+        );
+        assert!(matches!(Version::CURRENT.translate("emergency", Cow::Borrowed(emergency)), Cow::Borrowed(_)));
+        assert!(matches!(Version::LEGACY.translate("signal", Cow::Borrowed("{}")), Cow::Borrowed(_)));
+
+        let mut msg = r#"{"type":"signal"}"#.to_string();
+        Version::CURRENT.stamp(&mut msg);
+        assert_eq!(msg, r#"{"v":2,"type":"signal"}"#);
+        let mut empty = "{}".to_string();
+        Version::LEGACY.stamp(&mut empty);
+        assert_eq!(empty, r#"{"v":1}"#);
+
+        let doc = document();
+        assert_eq!(doc["$defs"]["emergency"]["properties"]["type"]["const"], "emergency");
+        assert_eq!(doc["oneOf"].as_array().unwrap().len(), doc["$defs"].as_object().unwrap().len());
+    }
+}
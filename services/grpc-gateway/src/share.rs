@@ -0,0 +1,184 @@
+//! Share modes per output
+//!
+//! What the database stores and what each output shares can differ, e.g. a
+//! public feed without military traffic or without the registration and
+//! operator enrichment whose source doesn't allow redistribution. Each
+//! output takes a comma-separated list of restrictions:
+//!
+//! | Restriction      | Withholds                                      |
+//! |------------------|------------------------------------------------|
+//! | `no-military`    | aircraft with military addresses               |
+//! | `no-special`     | ICAO temporary and special-use addresses       |
+//! | `no-registration`| `registration`                                 |
+//! | `no-type`        | `aircraft_type` and `model`                    |
+//! | `no-operator`    | `operator`                                     |
+//! | `no-route`       | `origin` and `destination`                     |
+//! | `no-enrichment`  | all four of the above                          |
+//!
+//! Aircraft are classed by address (`ADDRESS_OVERRIDES_PATH` included), so
+//! every message naming one is withheld, not only position updates. An
+//! empty list shares everything. Storage is never affected.
+
+use crate::military::{AddressClass, AddressClasses};
+use crate::pubsub::LiveMessage;
+use anyhow::{anyhow, Result};
+use serde_json::Value as JsonValue;
+use std::borrow::Cow;
+use std::sync::Arc;
+
+const REGISTRATION: &[&str] = &["registration"];
+const TYPE: &[&str] = &["aircraft_type", "model"];
+const OPERATOR: &[&str] = &["operator"];
+const ROUTE: &[&str] = &["origin", "destination"];
+
+/// What one output withholds
+#[derive(Debug, Clone, Default)]
+pub struct SharePolicy {
+    hide_military: bool,
+    hide_special: bool,
+    omit_fields: Vec<&'static str>,
+    classes: Arc<AddressClasses>,
+}
+
+impl SharePolicy {
+    /// Parse a restriction list; unknown restrictions are an error so a typo
+    /// can't share more than intended
+    pub fn parse(spec: &str, classes: Arc<AddressClasses>) -> Result<Self> {
+        let mut policy = Self {
+            classes,
+            ..Default::default()
+        };
+        for item in spec.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+            match item.to_ascii_lowercase().as_str() {
+                "no-military" => policy.hide_military = true,
+                "no-special" => policy.hide_special = true,
+                "no-registration" => policy.omit(REGISTRATION),
+                "no-type" => policy.omit(TYPE),
+                "no-operator" => policy.omit(OPERATOR),
+                "no-route" => policy.omit(ROUTE),
+                "no-enrichment" => {
+                    for fields in [REGISTRATION, TYPE, OPERATOR, ROUTE] {
+                        policy.omit(fields);
+                    }
+                }
+                _ => return Err(anyhow!("unknown share restriction '{}'", item)),
+            }
+        }
+        Ok(policy)
+    }
+
+    fn omit(&mut self, fields: &[&'static str]) {
+        for field in fields {
+            if !self.omit_fields.contains(field) {
+                self.omit_fields.push(field);
+            }
+        }
+    }
+
+    /// Whether nothing is withheld
+    pub fn is_open(&self) -> bool {
+        !self.hide_military && !self.hide_special && self.omit_fields.is_empty()
+    }
+
+    /// Whether an aircraft may be shared at all
+    pub fn allows(&self, icao: &str) -> bool {
+        if !self.hide_military && !self.hide_special {
+            return true;
+        }
+        match self.classes.classify(icao) {
+            AddressClass::Military => !self.hide_military,
+            AddressClass::Special => !self.hide_special,
+            AddressClass::Civil => true,
+        }
+    }
+
+    /// Withhold from a message, aircraft row or aircraft list; `false` when
+    /// the whole value must be dropped
+    pub fn apply(&self, value: &mut JsonValue) -> bool {
+        if let Some(icao) = value.get("icao").and_then(JsonValue::as_str) {
+            if !self.allows(icao) {
+                return false;
+            }
+        }
+        if let Some(aircraft) = value.get_mut("aircraft").and_then(JsonValue::as_array_mut) {
+            aircraft.retain_mut(|a| self.apply(a));
+        }
+        if let Some(obj) = value.as_object_mut() {
+            for field in &self.omit_fields {
+                obj.remove(*field);
+            }
+        }
+        true
+    }
+
+    /// [`SharePolicy::apply`] for serialized JSON, borrowing it when
+    /// nothing is withheld
+    pub fn apply_str<'a>(&self, json: &'a str) -> Option<Cow<'a, str>> {
+        if self.is_open() {
+            return Some(Cow::Borrowed(json));
+        }
+        let Ok(mut value) = serde_json::from_str::<JsonValue>(json) else {
+            return Some(Cow::Borrowed(json));
+        };
+        self.apply(&mut value).then(|| Cow::Owned(value.to_string()))
+    }
+
+    /// [`SharePolicy::apply`] for a broadcast message
+    pub fn apply_message(&self, msg: Arc<LiveMessage>) -> Option<Arc<LiveMessage>> {
+        match self.apply_str(&msg.json)? {
+            Cow::Borrowed(_) => Some(msg),
+            Cow::Owned(json) => Some(Arc::new(LiveMessage {
+                json: json.into(),
+                fields: msg.fields.clone(),
+            })),
+        }
+    }
+}
+
+/// The policy of each output
+#[derive(Debug, Clone, Default)]
+pub struct SharePolicies {
+    /// `/ws`
+    pub ws: SharePolicy,
+    /// `/api/stream`
+    pub sse: SharePolicy,
+    /// REST, GraphQL and the tar1090 endpoints
+    pub api: SharePolicy,
+    /// `UDP_OUTPUT`
+    pub udp: SharePolicy,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_share_policy() {
+        let classes = Arc::new(AddressClasses::new());
+        assert!(SharePolicy::parse("no-military,no-pilots", classes.clone()).is_err());
+        let open = SharePolicy::parse("", classes.clone()).unwrap();
+        assert!(open.is_open());
+        assert!(matches!(open.apply_str("{\"icao\":\"AE1234\"}"), Some(Cow::Borrowed(_))));
+
+        let public = SharePolicy::parse("no-military, no-enrichment", classes).unwrap();
+        assert!(!public.allows("AE1234"));
+        assert!(public.allows("71BE11"));
+        assert!(public.allows("F00001"));
+
+        let update = r#"{"type":"position_update","icao":"71BE11","registration":"HL8001","origin":"RKSI","altitude":5000}"#;
+        let shared: JsonValue = serde_json::from_str(&public.apply_str(update).unwrap()).unwrap();
+        assert_eq!(
+            shared,
+            serde_json::json!({"type": "position_update", "icao": "71BE11", "altitude": 5000})
+        );
+        assert!(public.apply_str(r#"{"type":"alert","icao":"AE1234"}"#).is_none());
+        assert!(public.apply_str(r#"{"type":"signal","rssi":-20}"#).is_some());
+
+        let mut initial = serde_json::json!({"type": "initial", "aircraft": [
+            {"icao": "AE1234"},
+            {"icao": "71BE11", "operator": "Korean Air", "model": "A321"},
+        ]});
+        assert!(public.apply(&mut initial));
+        assert_eq!(initial["aircraft"], serde_json::json!([{"icao": "71BE11"}]));
+    }
+}
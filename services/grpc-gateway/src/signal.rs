@@ -0,0 +1,121 @@
+//! Recent signal metrics
+//!
+//! Signal metrics are only broadcast, never stored, so a client used to see
+//! nothing until the next report after it connected. The last
+//! `SIGNAL_BUFFER_MINUTES` of reports from every device are kept here and
+//! served by `/api/signal/live`, letting the UI draw its SNR and message
+//! rate charts straight away.
+
+use crate::adsb::SignalMetrics;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use utoipa::ToSchema;
+
+/// Reports kept regardless of age, in case a device reports very often
+const MAX_SAMPLES: usize = 100_000;
+
+/// One signal report
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct SignalSample {
+    pub device_id: String,
+    /// When the device took the measurement (Unix milliseconds)
+    pub timestamp_ms: u64,
+    pub signal_dbfs: f32,
+    pub noise_dbfs: f32,
+    pub snr_db: f32,
+    /// Messages per second
+    pub msg_rate: f32,
+}
+
+/// Signal reports from the last few minutes, oldest first
+pub struct SignalBuffer {
+    window_ms: i64,
+    /// Reports with the gateway time (Unix ms) they arrived
+    samples: Mutex<VecDeque<(i64, SignalSample)>>,
+}
+
+impl SignalBuffer {
+    pub fn new(minutes: u64) -> Self {
+        Self {
+            window_ms: minutes as i64 * 60_000,
+            samples: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Seconds of reports kept
+    pub fn window_secs(&self) -> i64 {
+        self.window_ms / 1000
+    }
+
+    /// Record a report received at `now_ms`
+    pub fn push(&self, metrics: &SignalMetrics, now_ms: i64) {
+        let Ok(mut samples) = self.samples.lock() else {
+            return;
+        };
+        while samples
+            .front()
+            .is_some_and(|(at, _)| now_ms - at > self.window_ms)
+            || samples.len() >= MAX_SAMPLES
+        {
+            samples.pop_front();
+        }
+        samples.push_back((
+            now_ms,
+            SignalSample {
+                device_id: metrics.device_id.clone(),
+                timestamp_ms: metrics.timestamp_ms,
+                signal_dbfs: metrics.signal_dbfs,
+                noise_dbfs: metrics.noise_dbfs,
+                snr_db: metrics.snr_db,
+                msg_rate: metrics.msg_rate,
+            },
+        ));
+    }
+
+    /// Reports from the last `seconds`, optionally from one device
+    pub fn recent(&self, seconds: i64, device_id: Option<&str>, now_ms: i64) -> Vec<SignalSample> {
+        let Ok(samples) = self.samples.lock() else {
+            return Vec::new();
+        };
+        let since = now_ms - seconds.min(self.window_secs()) * 1000;
+        samples
+            .iter()
+            .filter(|(at, sample)| {
+                *at >= since && device_id.is_none_or(|id| sample.device_id == id)
+            })
+            .map(|(_, sample)| sample.clone())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metrics(device: &str, snr: f32) -> SignalMetrics {
+        SignalMetrics {
+            device_id: device.into(),
+            snr_db: snr,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_recent() {
+        let buffer = SignalBuffer::new(2);
+        buffer.push(&metrics("rtlsdr-0", 1.0), 0);
+        buffer.push(&metrics("rtlsdr-0", 2.0), 60_000);
+        buffer.push(&metrics("rtlsdr-1", 3.0), 90_000);
+
+        let snr = |samples: Vec<SignalSample>| samples.iter().map(|s| s.snr_db).collect::<Vec<_>>();
+        assert_eq!(snr(buffer.recent(60, None, 100_000)), vec![2.0, 3.0]);
+        assert_eq!(snr(buffer.recent(60, Some("rtlsdr-1"), 100_000)), vec![3.0]);
+        // Asking for more than the window returns what's kept
+        assert_eq!(snr(buffer.recent(3600, None, 100_000)), vec![1.0, 2.0, 3.0]);
+
+        // Older than two minutes is evicted
+        buffer.push(&metrics("rtlsdr-0", 4.0), 150_000);
+        assert_eq!(snr(buffer.recent(3600, None, 150_000)), vec![2.0, 3.0, 4.0]);
+    }
+}
@@ -0,0 +1,1576 @@
+//! SQLite storage backend
+//!
+//! Single-file alternative to TimescaleDB for single-board deployments.
+//! Mirrors the Postgres schema (see `services/timescaledb/init.sql`) with
+//! times stored as unix milliseconds. rusqlite is synchronous, so every query
+//! runs on the blocking thread pool behind a mutex-guarded connection.
+
+use crate::adsb::{AircraftEvent, DeviceStatus, RawFrame};
+use crate::admin::PositionPurge;
+use crate::alerts::{MatchKind, NewWatchRule, WatchRule, DEFAULT_COOLDOWN_SECONDS};
+use crate::coverage::{CoverageCell, CoverageQuery, MAX_CELLS};
+use crate::db_health::{DbHealth, TableHealth};
+use crate::decoder_config;
+use crate::emergencies::{emergency_name, EmergencyRecord};
+use crate::flights::FlightRecord;
+use crate::geofences::{GeofenceDef, GeofenceEvent, NewGeofence};
+use crate::history::{HistoryQuery, PositionPoint};
+use crate::stats::{self, Period, StatsRollup};
+use crate::storage::Storage;
+use crate::uptime::ReceiverSession;
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde_json::Value as JsonValue;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use tracing::debug;
+
+/// Tables in health reports, with their retention in days
+const HEALTH_TABLES: &[(&str, Option<i64>)] = &[
+    ("aircraft_positions", Some(POSITION_RETENTION_DAYS)),
+    ("raw_frames", Some(RAW_FRAME_RETENTION_DAYS)),
+    ("sdr_status_history", Some(SDR_HISTORY_RETENTION_DAYS)),
+    ("aircraft_info", None),
+    ("flights", None),
+    ("squawk_changes", None),
+    ("stats_rollups", None),
+];
+
+/// Positions older than this are pruned on startup (matches Timescale retention)
+const POSITION_RETENTION_DAYS: i64 = 30;
+
+/// Raw frames older than this are pruned on startup
+const RAW_FRAME_RETENTION_DAYS: i64 = 14;
+
+/// SDR heartbeats older than this are pruned on startup
+const SDR_HISTORY_RETENTION_DAYS: i64 = 7;
+
+/// Schema, kept in step with the TimescaleDB tables
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS aircraft_info (
+    icao_address TEXT PRIMARY KEY,
+    callsign TEXT,
+    category TEXT,
+    registration TEXT,
+    aircraft_type TEXT,
+    first_seen INTEGER,
+    last_seen INTEGER,
+    message_count INTEGER DEFAULT 1,
+    squawk TEXT,
+    last_device_id TEXT
+);
+
+CREATE TABLE IF NOT EXISTS squawk_changes (
+    time INTEGER NOT NULL,
+    icao_address TEXT NOT NULL,
+    previous_squawk TEXT,
+    squawk TEXT NOT NULL,
+    device_id TEXT
+);
+
+-- Replaced by idx_squawk_changes_icao when squawk_history was renamed
+DROP INDEX IF EXISTS idx_squawk_history_icao;
+CREATE INDEX IF NOT EXISTS idx_squawk_changes_icao ON squawk_changes (icao_address, time DESC);
+CREATE INDEX IF NOT EXISTS idx_squawk_changes_time ON squawk_changes (time DESC);
+
+CREATE TABLE IF NOT EXISTS aircraft_positions (
+    time INTEGER NOT NULL,
+    icao_address TEXT NOT NULL,
+    device_id TEXT,
+    latitude REAL,
+    longitude REAL,
+    altitude_ft INTEGER,
+    ground_speed_kts REAL,
+    track_deg REAL,
+    vertical_rate_fpm INTEGER,
+    squawk TEXT,
+    signal_strength_db REAL,
+    raw_message BLOB
+);
+
+CREATE INDEX IF NOT EXISTS idx_positions_icao ON aircraft_positions (icao_address, time DESC);
+CREATE INDEX IF NOT EXISTS idx_positions_time ON aircraft_positions (time DESC);
+
+CREATE TABLE IF NOT EXISTS sdr_status (
+    device_id TEXT PRIMARY KEY,
+    connected INTEGER DEFAULT 0,
+    sample_rate INTEGER,
+    center_freq INTEGER,
+    gain_db REAL,
+    ppm_error INTEGER,
+    last_heartbeat INTEGER,
+    messages_per_second REAL DEFAULT 0,
+    error_message TEXT,
+    software_version TEXT,
+    uptime_secs INTEGER,
+    decoder TEXT,
+    clock_offset_ms INTEGER
+);
+
+CREATE TABLE IF NOT EXISTS sdr_status_history (
+    time INTEGER NOT NULL,
+    device_id TEXT NOT NULL,
+    connected INTEGER,
+    sample_rate INTEGER,
+    center_freq INTEGER,
+    gain_db REAL,
+    software_version TEXT,
+    decoder TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_sdr_status_history_device ON sdr_status_history (device_id, time DESC);
+
+CREATE TABLE IF NOT EXISTS raw_frames (
+    time INTEGER NOT NULL,
+    device_id TEXT NOT NULL,
+    raw_message BLOB NOT NULL,
+    signal_level INTEGER
+);
+
+CREATE INDEX IF NOT EXISTS idx_raw_frames_time ON raw_frames (time DESC);
+
+CREATE TABLE IF NOT EXISTS flights (
+    icao_address TEXT NOT NULL,
+    callsign TEXT,
+    first_seen INTEGER NOT NULL,
+    last_seen INTEGER NOT NULL,
+    max_altitude_ft INTEGER,
+    position_count INTEGER DEFAULT 0,
+    device_id TEXT,
+    PRIMARY KEY (icao_address, first_seen)
+);
+
+CREATE INDEX IF NOT EXISTS idx_flights_first_seen ON flights (first_seen DESC);
+
+CREATE TABLE IF NOT EXISTS emergencies (
+    icao_address TEXT NOT NULL,
+    started_at INTEGER NOT NULL,
+    last_seen INTEGER NOT NULL,
+    ended_at INTEGER,
+    emergency TEXT NOT NULL,
+    squawk TEXT NOT NULL,
+    callsign TEXT,
+    latitude REAL,
+    longitude REAL,
+    altitude_ft INTEGER,
+    device_id TEXT,
+    PRIMARY KEY (icao_address, started_at)
+);
+
+CREATE INDEX IF NOT EXISTS idx_emergencies_started_at ON emergencies (started_at DESC);
+
+CREATE TABLE IF NOT EXISTS receiver_sessions (
+    device_id TEXT NOT NULL,
+    started_at INTEGER NOT NULL,
+    last_seen INTEGER NOT NULL,
+    ended_at INTEGER,
+    end_reason TEXT,
+    PRIMARY KEY (device_id, started_at)
+);
+
+CREATE TABLE IF NOT EXISTS stats_rollups (
+    period TEXT NOT NULL,
+    bucket INTEGER NOT NULL,
+    device_id TEXT NOT NULL DEFAULT '',
+    messages INTEGER NOT NULL DEFAULT 0,
+    positions INTEGER NOT NULL DEFAULT 0,
+    unique_aircraft INTEGER NOT NULL DEFAULT 0,
+    max_range_km REAL NOT NULL DEFAULT 0,
+    frames_decoded INTEGER NOT NULL DEFAULT 0,
+    crc_errors INTEGER NOT NULL DEFAULT 0,
+    PRIMARY KEY (period, bucket, device_id)
+);
+
+CREATE TABLE IF NOT EXISTS stats_df_rollups (
+    period TEXT NOT NULL,
+    bucket INTEGER NOT NULL,
+    device_id TEXT NOT NULL DEFAULT '',
+    downlink_format INTEGER NOT NULL,
+    frames INTEGER NOT NULL DEFAULT 0,
+    PRIMARY KEY (period, bucket, device_id, downlink_format)
+);
+
+CREATE TABLE IF NOT EXISTS watchlist_rules (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL,
+    match_kind TEXT NOT NULL,
+    pattern TEXT,
+    geofence TEXT,
+    webhook_url TEXT,
+    notifiers TEXT NOT NULL DEFAULT '[]',
+    cooldown_seconds INTEGER NOT NULL DEFAULT 600,
+    enabled INTEGER NOT NULL DEFAULT 1,
+    created_at INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS geofences (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL,
+    shape TEXT NOT NULL,
+    min_altitude_ft INTEGER,
+    max_altitude_ft INTEGER,
+    log_events INTEGER NOT NULL DEFAULT 0,
+    created_at INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS geofence_events (
+    time INTEGER NOT NULL,
+    geofence_id INTEGER NOT NULL REFERENCES geofences(id) ON DELETE CASCADE,
+    icao_address TEXT NOT NULL,
+    callsign TEXT,
+    event TEXT NOT NULL,
+    reason TEXT,
+    latitude REAL,
+    longitude REAL,
+    altitude_ft INTEGER
+);
+
+CREATE INDEX IF NOT EXISTS idx_geofence_events ON geofence_events (geofence_id, time DESC);
+";
+
+/// SQLite-backed storage
+pub struct SqliteWriter {
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl SqliteWriter {
+    /// Open (or create) the database file and apply the schema
+    pub async fn open(path: &Path) -> Result<Self> {
+        let path = path.to_path_buf();
+        let conn = tokio::task::spawn_blocking(move || -> Result<Connection> {
+            let conn = Connection::open(&path)?;
+            // WAL keeps readers (REST) from blocking the ingest writer
+            conn.pragma_update(None, "journal_mode", "WAL")?;
+            conn.pragma_update(None, "synchronous", "NORMAL")?;
+            rename_tables(&conn)?;
+            conn.execute_batch(SCHEMA)?;
+            migrate(&conn)?;
+
+            let now = now_ms();
+            let pruned = conn.execute(
+                "DELETE FROM aircraft_positions WHERE time < ?1",
+                params![now - POSITION_RETENTION_DAYS * 86_400_000],
+            )?;
+            conn.execute(
+                "DELETE FROM raw_frames WHERE time < ?1",
+                params![now - RAW_FRAME_RETENTION_DAYS * 86_400_000],
+            )?;
+            conn.execute(
+                "DELETE FROM sdr_status_history WHERE time < ?1",
+                params![now - SDR_HISTORY_RETENTION_DAYS * 86_400_000],
+            )?;
+            if pruned > 0 {
+                debug!("Pruned {} expired position rows", pruned);
+            }
+            Ok(conn)
+        })
+        .await??;
+
+        Ok(Self {
+            conn: Arc::new(Mutex::new(conn)),
+        })
+    }
+
+    /// Run a closure against the connection on the blocking pool
+    async fn with_conn<T, F>(&self, f: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Connection) -> Result<T> + Send + 'static,
+    {
+        let conn = self.conn.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut conn = conn.lock().map_err(|_| anyhow!("SQLite connection poisoned"))?;
+            f(&mut conn)
+        })
+        .await?
+    }
+}
+
+#[tonic::async_trait]
+impl Storage for SqliteWriter {
+    fn backend_name(&self) -> &'static str {
+        "sqlite"
+    }
+
+    async fn insert_position(
+        &self,
+        event: &AircraftEvent,
+        time: DateTime<Utc>,
+        messages: u32,
+    ) -> Result<()> {
+        let event = event.clone();
+        self.with_conn(move |conn| {
+            let now = time.timestamp_millis();
+            let tx = conn.transaction()?;
+
+            // Only insert a position row if the event has a position
+            if event.latitude.is_none() || event.longitude.is_none() {
+                debug!("Skipping position insert for {} - no position data", event.icao);
+            } else {
+                tx.prepare_cached(
+                    "INSERT INTO aircraft_positions (
+                        time, icao_address, device_id, latitude, longitude,
+                        altitude_ft, ground_speed_kts, track_deg, vertical_rate_fpm, squawk,
+                        magnetic_heading_deg, baro_rate_fpm, geom_rate_fpm
+                    ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+                )?
+                .execute(params![
+                    now,
+                    event.icao,
+                    event.device_id,
+                    event.latitude,
+                    event.longitude,
+                    event.altitude_ft,
+                    event.speed_kts,
+                    event.track_deg,
+                    event.vertical_rate_fpm,
+                    event.squawk,
+                    event.magnetic_heading_deg,
+                    event.baro_rate_fpm,
+                    event.geom_rate_fpm,
+                ])?;
+            }
+
+            // Record squawk changes before aircraft_info takes the new code
+            if let Some(squawk) = event.squawk.as_deref().filter(|s| !s.is_empty()) {
+                tx.prepare_cached(
+                    "INSERT INTO squawk_changes (time, icao_address, previous_squawk, squawk, device_id)
+                     SELECT ?1, ?2,
+                        (SELECT squawk FROM aircraft_info WHERE icao_address = ?2),
+                        ?3, NULLIF(?4, '')
+                     WHERE NOT EXISTS (
+                        SELECT 1 FROM aircraft_info WHERE icao_address = ?2 AND squawk = ?3
+                     )",
+                )?
+                .execute(params![now, event.icao, squawk, event.device_id])?;
+            }
+
+            // Same upsert as the Postgres writer: absent or empty fields keep the stored value
+            tx.prepare_cached(
+                "INSERT INTO aircraft_info (
+                    icao_address, callsign, category, squawk, last_device_id,
+                    first_seen, last_seen, message_count
+                ) VALUES (
+                    ?1, NULLIF(?2, ''), NULLIF(?3, ''), NULLIF(?4, ''), NULLIF(?5, ''), ?6, ?6, ?7
+                )
+                ON CONFLICT (icao_address) DO UPDATE SET
+                    callsign = COALESCE(excluded.callsign, aircraft_info.callsign),
+                    category = COALESCE(excluded.category, aircraft_info.category),
+                    squawk = COALESCE(excluded.squawk, aircraft_info.squawk),
+                    last_device_id = COALESCE(excluded.last_device_id, aircraft_info.last_device_id),
+                    last_seen = excluded.last_seen,
+                    message_count = aircraft_info.message_count + excluded.message_count",
+            )?
+            .execute(params![
+                event.icao,
+                event.callsign,
+                event.category,
+                event.squawk,
+                event.device_id,
+                now,
+                messages,
+            ])?;
+
+            tx.commit()?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn insert_raw_frames(&self, frames: &[RawFrame]) -> Result<()> {
+        if frames.is_empty() {
+            return Ok(());
+        }
+
+        let frames = frames.to_vec();
+        self.with_conn(move |conn| {
+            let tx = conn.transaction()?;
+            {
+                let mut stmt = tx.prepare_cached(
+                    "INSERT INTO raw_frames (time, device_id, raw_message, signal_level)
+                     VALUES (?1, ?2, ?3, ?4)",
+                )?;
+                for f in &frames {
+                    stmt.execute(params![
+                        f.timestamp_ms as i64,
+                        f.device_id,
+                        f.data,
+                        f.signal_level
+                    ])?;
+                }
+            }
+            tx.commit()?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn update_sdr_status(
+        &self,
+        status: &DeviceStatus,
+        messages_per_second: f32,
+    ) -> Result<()> {
+        let status = status.clone();
+        let decoder = decoder_config::settings_json(&status).map(|d| d.to_string());
+        self.with_conn(move |conn| {
+            let version = decoder_config::software_version(&status);
+            conn.execute(
+                "INSERT INTO sdr_status (
+                    device_id, connected, sample_rate, center_freq, gain_db, last_heartbeat,
+                    messages_per_second, software_version, uptime_secs, decoder, clock_offset_ms
+                ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)
+                ON CONFLICT (device_id) DO UPDATE SET
+                    connected = excluded.connected,
+                    sample_rate = excluded.sample_rate,
+                    center_freq = excluded.center_freq,
+                    gain_db = excluded.gain_db,
+                    last_heartbeat = excluded.last_heartbeat,
+                    messages_per_second = excluded.messages_per_second,
+                    software_version = excluded.software_version,
+                    uptime_secs = excluded.uptime_secs,
+                    decoder = excluded.decoder,
+                    clock_offset_ms = excluded.clock_offset_ms",
+                params![
+                    status.device_id,
+                    status.connected,
+                    status.sample_rate,
+                    status.center_freq as i64,
+                    status.gain_db,
+                    now_ms(),
+                    messages_per_second,
+                    version,
+                    status.uptime_secs as i64,
+                    decoder,
+                    status.clock_offset_ms,
+                ],
+            )?;
+            conn.execute(
+                "INSERT INTO sdr_status_history (
+                    time, device_id, connected, sample_rate, center_freq, gain_db,
+                    software_version, decoder
+                ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                params![
+                    now_ms(),
+                    status.device_id,
+                    status.connected,
+                    status.sample_rate,
+                    status.center_freq as i64,
+                    status.gain_db,
+                    version,
+                    decoder,
+                ],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn get_aircraft(&self, icao: &str) -> Result<Option<JsonValue>> {
+        let icao = icao.to_string();
+        self.with_conn(move |conn| {
+            let mut stmt = conn.prepare_cached(
+                "SELECT
+                    i.icao_address, i.callsign, i.category, i.registration, i.aircraft_type,
+                    i.first_seen, i.last_seen, i.message_count,
+                    p.latitude, p.longitude, p.altitude_ft, p.ground_speed_kts,
+                    p.track_deg, p.vertical_rate_fpm, p.squawk, p.device_id, p.time,
+                    p.magnetic_heading_deg, p.baro_rate_fpm, p.geom_rate_fpm
+                FROM aircraft_info i
+                LEFT JOIN aircraft_positions p ON p.rowid = (
+                    SELECT rowid FROM aircraft_positions
+                    WHERE icao_address = i.icao_address
+                    ORDER BY time DESC
+                    LIMIT 1
+                )
+                WHERE i.icao_address = ?1",
+            )?;
+
+            let aircraft = stmt
+                .query_row(params![icao], |row| {
+                    Ok(serde_json::json!({
+                        "icao": row.get::<_, String>(0)?,
+                        "callsign": row.get::<_, Option<String>>(1)?,
+                        "category": row.get::<_, Option<String>>(2)?,
+                        "registration": row.get::<_, Option<String>>(3)?,
+                        "aircraft_type": row.get::<_, Option<String>>(4)?,
+                        "lat": row.get::<_, Option<f64>>(8)?,
+                        "lon": row.get::<_, Option<f64>>(9)?,
+                        "altitude": row.get::<_, Option<i32>>(10)?,
+                        "speed": row.get::<_, Option<f32>>(11)?,
+                        "track": row.get::<_, Option<f32>>(12)?,
+                        "magnetic_heading": row.get::<_, Option<f32>>(17)?,
+                        "vrate": row.get::<_, Option<i32>>(13)?,
+                        "baro_rate": row.get::<_, Option<i32>>(18)?,
+                        "geom_rate": row.get::<_, Option<i32>>(19)?,
+                        "squawk": row.get::<_, Option<String>>(14)?,
+                        "device_id": row.get::<_, Option<String>>(15)?,
+                        "seen": row.get::<_, Option<i64>>(16)?.map(ms_to_rfc3339),
+                        "first_seen": row.get::<_, Option<i64>>(5)?.map(ms_to_rfc3339),
+                        "last_seen": row.get::<_, Option<i64>>(6)?.map(ms_to_rfc3339),
+                        "messages": row.get::<_, Option<i64>>(7)?,
+                    }))
+                })
+                .optional()?;
+            Ok(aircraft)
+        })
+        .await
+    }
+
+    async fn get_aircraft_trail(
+        &self,
+        icao: &str,
+        devices: Option<&[String]>,
+        from: DateTime<Utc>,
+        to: Option<DateTime<Utc>>,
+        bucket_secs: Option<i64>,
+    ) -> Result<Vec<JsonValue>> {
+        let icao = icao.to_string();
+        let devices = devices.map(|devices| serde_json::json!(devices).to_string());
+        self.with_conn(move |conn| {
+            // Without buckets every row is its own group; with them, the
+            // bare columns come from the row holding max(time)
+            let mut stmt = conn.prepare_cached(
+                "SELECT max(time), latitude, longitude, altitude_ft, device_id,
+                        CASE WHEN ?4 IS NULL THEN NULL ELSE count(*) END
+                FROM aircraft_positions
+                WHERE icao_address = ?1
+                  AND time >= ?2
+                  AND time < ?3
+                  AND (?5 IS NULL OR device_id IN (SELECT value FROM json_each(?5)))
+                  AND latitude IS NOT NULL
+                  AND longitude IS NOT NULL
+                GROUP BY CASE WHEN ?4 IS NULL THEN rowid ELSE time / ?4 END
+                ORDER BY 1 ASC",
+            )?;
+
+            let (from, to) = (from.timestamp_millis(), to.map_or(i64::MAX, |to| to.timestamp_millis()));
+            let bucket_ms = bucket_secs.map(|secs| secs * 1000);
+            let rows = stmt.query_map(params![icao, from, to, bucket_ms, devices], |row| {
+                Ok(serde_json::json!({
+                    "time": ms_to_rfc3339(row.get::<_, i64>(0)?),
+                    "lat": row.get::<_, f64>(1)?,
+                    "lon": row.get::<_, f64>(2)?,
+                    "altitude": row.get::<_, Option<i32>>(3)?,
+                    "device_id": row.get::<_, Option<String>>(4)?,
+                    "positions": row.get::<_, Option<i64>>(5)?,
+                }))
+            })?;
+
+            Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+        })
+        .await
+    }
+
+    async fn get_position_history(&self, query: &HistoryQuery) -> Result<Vec<PositionPoint>> {
+        let query = query.clone();
+        self.with_conn(move |conn| {
+            let mut stmt = conn.prepare_cached(
+                "SELECT time, icao_address, latitude, longitude, altitude_ft,
+                        ground_speed_kts, track_deg, vertical_rate_fpm, squawk, device_id,
+                        magnetic_heading_deg, baro_rate_fpm, geom_rate_fpm
+                FROM aircraft_positions
+                WHERE time >= ?1 AND time < ?2
+                  AND (?3 IS NULL OR icao_address = ?3)
+                  AND (?4 IS NULL OR (time, icao_address) > (?4, ?5))
+                  AND (?7 IS NULL OR device_id IN (SELECT value FROM json_each(?7)))
+                  AND latitude IS NOT NULL
+                  AND longitude IS NOT NULL
+                ORDER BY time, icao_address
+                LIMIT ?6",
+            )?;
+
+            let after = query.after.as_ref();
+            let devices = query.devices.as_ref().map(|devices| serde_json::json!(devices).to_string());
+            let params = params![
+                query.from.timestamp_millis(),
+                query.to.timestamp_millis(),
+                query.icao,
+                after.map(|c| c.time.timestamp_millis()),
+                after.map(|c| c.icao.as_str()),
+                query.fetch,
+                devices,
+            ];
+            let rows = stmt.query_map(params, |row| {
+                Ok(PositionPoint {
+                    time: chrono::DateTime::from_timestamp_millis(row.get(0)?).unwrap_or_default(),
+                    icao: row.get(1)?,
+                    lat: row.get(2)?,
+                    lon: row.get(3)?,
+                    altitude: row.get(4)?,
+                    speed: row.get(5)?,
+                    track: row.get(6)?,
+                    magnetic_heading: row.get(10)?,
+                    vrate: row.get(7)?,
+                    baro_rate: row.get(11)?,
+                    geom_rate: row.get(12)?,
+                    squawk: row.get(8)?,
+                    device_id: row.get(9)?,
+                })
+            })?;
+
+            Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+        })
+        .await
+    }
+
+    async fn get_coverage(&self, query: &CoverageQuery) -> Result<Vec<CoverageCell>> {
+        let query = query.clone();
+        self.with_conn(move |conn| {
+            // CAST truncates towards zero; the offset makes that a floor
+            let mut stmt = conn.prepare_cached(
+                "SELECT
+                    CAST(latitude / ?3 + 1000000 AS INTEGER) - 1000000 AS lat_idx,
+                    CAST(longitude / ?3 + 1000000 AS INTEGER) - 1000000 AS lon_idx,
+                    count(*) AS count,
+                    max(altitude_ft) AS max_altitude
+                FROM aircraft_positions
+                WHERE time >= ?1 AND time < ?2
+                  AND (?4 IS NULL OR device_id = ?4)
+                  AND latitude IS NOT NULL
+                  AND longitude IS NOT NULL
+                GROUP BY lat_idx, lon_idx
+                ORDER BY count DESC
+                LIMIT ?5",
+            )?;
+
+            let params = params![
+                query.from.timestamp_millis(),
+                query.to.timestamp_millis(),
+                query.cell_deg,
+                query.device_id,
+                MAX_CELLS,
+            ];
+            let rows = stmt.query_map(params, |row| {
+                Ok(CoverageCell {
+                    lat_idx: row.get(0)?,
+                    lon_idx: row.get(1)?,
+                    count: row.get(2)?,
+                    max_altitude: row.get(3)?,
+                })
+            })?;
+
+            Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+        })
+        .await
+    }
+
+    async fn get_sdr_status(&self) -> Result<Vec<JsonValue>> {
+        self.with_conn(|conn| {
+            let now = now_ms();
+            let mut stmt = conn.prepare_cached(
+                "SELECT device_id, connected, sample_rate, center_freq, gain_db,
+                        last_heartbeat, messages_per_second, software_version, uptime_secs,
+                        decoder, clock_offset_ms
+                 FROM sdr_status
+                 ORDER BY device_id",
+            )?;
+            let rows = stmt.query_map([], |row| {
+                let connected: bool = row.get::<_, Option<bool>>(1)?.unwrap_or(false);
+                let heartbeat: Option<i64> = row.get(5)?;
+                let age_ms = heartbeat.map(|t| now - t).unwrap_or(i64::MAX);
+                let status = if connected && age_ms < 30_000 {
+                    "active"
+                } else if age_ms < 5 * 60_000 {
+                    "stale"
+                } else {
+                    "disconnected"
+                };
+
+                Ok(serde_json::json!({
+                    "device_id": row.get::<_, String>(0)?,
+                    "connected": connected,
+                    "sample_rate": row.get::<_, Option<i32>>(2)?,
+                    "center_freq": row.get::<_, Option<i64>>(3)?,
+                    "gain_db": row.get::<_, Option<f32>>(4)?,
+                    "last_heartbeat": heartbeat.map(ms_to_rfc3339),
+                    "messages_per_second": row.get::<_, Option<f32>>(6)?,
+                    "software_version": row.get::<_, Option<String>>(7)?,
+                    "uptime_secs": row.get::<_, Option<i64>>(8)?,
+                    "decoder": row
+                        .get::<_, Option<String>>(9)?
+                        .and_then(|s| serde_json::from_str::<JsonValue>(&s).ok()),
+                    "clock_offset_ms": row.get::<_, Option<i64>>(10)?,
+                    "status": status,
+                }))
+            })?;
+
+            Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+        })
+        .await
+    }
+
+    async fn get_sdr_history(
+        &self,
+        device_id: &str,
+        since: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<Vec<JsonValue>> {
+        let device_id = device_id.to_string();
+        self.with_conn(move |conn| {
+            let mut stmt = conn.prepare_cached(
+                "SELECT time, connected, sample_rate, center_freq, gain_db, software_version,
+                        decoder
+                 FROM sdr_status_history
+                 WHERE device_id = ?1 AND time >= ?2
+                 ORDER BY time DESC
+                 LIMIT ?3",
+            )?;
+            let rows = stmt.query_map(params![device_id, since.timestamp_millis(), limit], |row| {
+                Ok(serde_json::json!({
+                    "time": ms_to_rfc3339(row.get(0)?),
+                    "connected": row.get::<_, Option<bool>>(1)?.unwrap_or(false),
+                    "sample_rate": row.get::<_, Option<i32>>(2)?,
+                    "center_freq": row.get::<_, Option<i64>>(3)?,
+                    "gain_db": row.get::<_, Option<f32>>(4)?,
+                    "software_version": row.get::<_, Option<String>>(5)?,
+                    "decoder": row
+                        .get::<_, Option<String>>(6)?
+                        .and_then(|s| serde_json::from_str::<JsonValue>(&s).ok()),
+                }))
+            })?;
+
+            Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+        })
+        .await
+    }
+
+    async fn upsert_flight(&self, flight: &FlightRecord) -> Result<()> {
+        let flight = flight.clone();
+        self.with_conn(move |conn| {
+            conn.execute(
+                "INSERT INTO flights (
+                    icao_address, callsign, first_seen, last_seen,
+                    max_altitude_ft, position_count, device_id
+                ) VALUES (?1, NULLIF(?2, ''), ?3, ?4, ?5, ?6, NULLIF(?7, ''))
+                ON CONFLICT (icao_address, first_seen) DO UPDATE SET
+                    callsign = COALESCE(excluded.callsign, flights.callsign),
+                    last_seen = excluded.last_seen,
+                    max_altitude_ft = excluded.max_altitude_ft,
+                    position_count = excluded.position_count,
+                    device_id = COALESCE(excluded.device_id, flights.device_id)",
+                params![
+                    flight.icao,
+                    flight.callsign,
+                    flight.first_seen.timestamp_millis(),
+                    flight.last_seen.timestamp_millis(),
+                    flight.max_altitude_ft,
+                    flight.position_count,
+                    flight.device_id,
+                ],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn get_flights(&self, icao: Option<&str>, devices: Option<&[String]>, limit: i64) -> Result<Vec<JsonValue>> {
+        let icao = icao.map(str::to_string);
+        let devices = devices.map(|devices| serde_json::json!(devices).to_string());
+        self.with_conn(move |conn| {
+            let mut stmt = conn.prepare_cached(
+                "SELECT icao_address, callsign, first_seen, last_seen,
+                        max_altitude_ft, position_count, device_id
+                FROM flights
+                WHERE (?1 IS NULL OR icao_address = ?1)
+                  AND (?3 IS NULL OR device_id IN (SELECT value FROM json_each(?3)))
+                ORDER BY first_seen DESC
+                LIMIT ?2",
+            )?;
+
+            let rows = stmt.query_map(params![icao, limit, devices], |row| {
+                let first_seen: i64 = row.get(2)?;
+                let last_seen: i64 = row.get(3)?;
+                Ok(serde_json::json!({
+                    "icao": row.get::<_, String>(0)?,
+                    "callsign": row.get::<_, Option<String>>(1)?,
+                    "first_seen": ms_to_rfc3339(first_seen),
+                    "last_seen": ms_to_rfc3339(last_seen),
+                    "duration_s": (last_seen - first_seen) / 1000,
+                    "max_altitude": row.get::<_, Option<i32>>(4)?,
+                    "positions": row.get::<_, Option<i32>>(5)?,
+                    "device_id": row.get::<_, Option<String>>(6)?,
+                }))
+            })?;
+
+            Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+        })
+        .await
+    }
+
+    async fn upsert_emergency(&self, record: &EmergencyRecord) -> Result<()> {
+        let record = record.clone();
+        self.with_conn(move |conn| {
+            let (lat, lon) = record.position.unzip();
+            conn.execute(
+                "INSERT INTO emergencies (
+                    icao_address, started_at, last_seen, ended_at, emergency, squawk,
+                    callsign, latitude, longitude, altitude_ft, device_id
+                ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, NULLIF(?11, ''))
+                ON CONFLICT (icao_address, started_at) DO UPDATE SET
+                    last_seen = excluded.last_seen,
+                    ended_at = excluded.ended_at,
+                    callsign = COALESCE(excluded.callsign, emergencies.callsign),
+                    latitude = COALESCE(excluded.latitude, emergencies.latitude),
+                    longitude = COALESCE(excluded.longitude, emergencies.longitude),
+                    altitude_ft = COALESCE(excluded.altitude_ft, emergencies.altitude_ft),
+                    device_id = COALESCE(excluded.device_id, emergencies.device_id)",
+                params![
+                    record.icao,
+                    record.started_at.timestamp_millis(),
+                    record.last_seen.timestamp_millis(),
+                    record.ended_at.map(|t| t.timestamp_millis()),
+                    emergency_name(record.emergency),
+                    record.squawk,
+                    record.callsign,
+                    lat,
+                    lon,
+                    record.altitude_ft,
+                    record.device_id,
+                ],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn upsert_stats(&self, rows: &[StatsRollup]) -> Result<()> {
+        let rows = rows.to_vec();
+        self.with_conn(move |conn| {
+            let tx = conn.transaction()?;
+            {
+                let mut stmt = tx.prepare_cached(
+                    "INSERT INTO stats_rollups (
+                        period, bucket, device_id, messages, positions, unique_aircraft,
+                        max_range_km, frames_decoded, crc_errors
+                    ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+                    ON CONFLICT (period, bucket, device_id) DO UPDATE SET
+                        messages = messages + excluded.messages,
+                        positions = positions + excluded.positions,
+                        unique_aircraft = max(unique_aircraft, excluded.unique_aircraft),
+                        max_range_km = max(max_range_km, excluded.max_range_km),
+                        frames_decoded = frames_decoded + excluded.frames_decoded,
+                        crc_errors = crc_errors + excluded.crc_errors",
+                )?;
+                for row in &rows {
+                    stmt.execute(params![
+                        row.period.name(),
+                        row.bucket.timestamp_millis(),
+                        row.device_id,
+                        row.messages,
+                        row.positions,
+                        row.unique_aircraft,
+                        row.max_range_km,
+                        row.frames_decoded,
+                        row.crc_errors,
+                    ])?;
+                }
+
+                let mut stmt = tx.prepare_cached(
+                    "INSERT INTO stats_df_rollups (period, bucket, device_id, downlink_format, frames)
+                    VALUES (?1, ?2, ?3, ?4, ?5)
+                    ON CONFLICT (period, bucket, device_id, downlink_format) DO UPDATE SET
+                        frames = frames + excluded.frames",
+                )?;
+                for row in &rows {
+                    for (df, frames) in &row.downlink_formats {
+                        stmt.execute(params![
+                            row.period.name(),
+                            row.bucket.timestamp_millis(),
+                            row.device_id,
+                            df,
+                            frames,
+                        ])?;
+                    }
+                }
+            }
+            tx.commit()?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn get_stats(&self, period: Period, since: DateTime<Utc>) -> Result<Vec<StatsRollup>> {
+        self.with_conn(move |conn| {
+            let mut stmt = conn.prepare_cached(
+                "SELECT bucket, device_id, messages, positions, unique_aircraft,
+                        max_range_km, frames_decoded, crc_errors
+                FROM stats_rollups
+                WHERE period = ?1 AND bucket >= ?2
+                ORDER BY bucket, device_id",
+            )?;
+
+            let rows = stmt.query_map(params![period.name(), since.timestamp_millis()], |row| {
+                Ok(StatsRollup {
+                    period,
+                    bucket: DateTime::from_timestamp_millis(row.get(0)?).unwrap_or_default(),
+                    device_id: row.get(1)?,
+                    messages: row.get(2)?,
+                    positions: row.get(3)?,
+                    unique_aircraft: row.get(4)?,
+                    max_range_km: row.get(5)?,
+                    frames_decoded: row.get(6)?,
+                    crc_errors: row.get(7)?,
+                    downlink_formats: Default::default(),
+                })
+            })?;
+            let mut rollups = rows.collect::<rusqlite::Result<Vec<_>>>()?;
+
+            let mut stmt = conn.prepare_cached(
+                "SELECT bucket, device_id, downlink_format, frames
+                FROM stats_df_rollups
+                WHERE period = ?1 AND bucket >= ?2",
+            )?;
+            let formats = stmt.query_map(params![period.name(), since.timestamp_millis()], |row| {
+                Ok((
+                    DateTime::from_timestamp_millis(row.get(0)?).unwrap_or_default(),
+                    row.get(1)?,
+                    row.get(2)?,
+                    row.get(3)?,
+                ))
+            })?;
+            stats::add_downlink_formats(&mut rollups, formats.collect::<rusqlite::Result<Vec<_>>>()?);
+            Ok(rollups)
+        })
+        .await
+    }
+
+    async fn delete_positions(&self, purge: &PositionPurge) -> Result<u64> {
+        let purge = purge.clone();
+        self.with_conn(move |conn| {
+            let deleted = conn.execute(
+                "DELETE FROM aircraft_positions
+                WHERE (?1 IS NULL OR icao_address = ?1)
+                  AND (?2 IS NULL OR time >= ?2)
+                  AND (?3 IS NULL OR time < ?3)",
+                params![
+                    purge.icao,
+                    purge.from.map(|t| t.timestamp_millis()),
+                    purge.to.map(|t| t.timestamp_millis()),
+                ],
+            )?;
+            Ok(deleted as u64)
+        })
+        .await
+    }
+
+    async fn rename_device(&self, from: &str, to: &str) -> Result<u64> {
+        let (from, to) = (from.to_string(), to.to_string());
+        self.with_conn(move |conn| {
+            let tx = conn.transaction()?;
+            let mut updated = 0;
+
+            for table in [
+                "aircraft_positions",
+                "sdr_status_history",
+                "raw_frames",
+                "flights",
+                "emergencies",
+                "receiver_sessions",
+            ] {
+                updated += tx.execute(
+                    &format!("UPDATE {} SET device_id = ?2 WHERE device_id = ?1", table),
+                    params![from, to],
+                )?;
+            }
+            updated += tx.execute(
+                "UPDATE aircraft_info SET last_device_id = ?2 WHERE last_device_id = ?1",
+                params![from, to],
+            )?;
+
+            // Same merge as the Postgres writer: the new ID's status wins and
+            // rollups are added to its buckets
+            tx.execute(
+                "DELETE FROM sdr_status
+                WHERE device_id = ?1 AND EXISTS (SELECT 1 FROM sdr_status WHERE device_id = ?2)",
+                params![from, to],
+            )?;
+            updated += tx.execute(
+                "UPDATE sdr_status SET device_id = ?2 WHERE device_id = ?1",
+                params![from, to],
+            )?;
+            updated += tx.execute(
+                "INSERT INTO stats_rollups (
+                    period, bucket, device_id, messages, positions, unique_aircraft,
+                    max_range_km, frames_decoded, crc_errors
+                )
+                SELECT period, bucket, ?2, messages, positions, unique_aircraft,
+                       max_range_km, frames_decoded, crc_errors
+                FROM stats_rollups WHERE device_id = ?1
+                ON CONFLICT (period, bucket, device_id) DO UPDATE SET
+                    messages = messages + excluded.messages,
+                    positions = positions + excluded.positions,
+                    unique_aircraft = max(unique_aircraft, excluded.unique_aircraft),
+                    max_range_km = max(max_range_km, excluded.max_range_km),
+                    frames_decoded = frames_decoded + excluded.frames_decoded,
+                    crc_errors = crc_errors + excluded.crc_errors",
+                params![from, to],
+            )?;
+            tx.execute("DELETE FROM stats_rollups WHERE device_id = ?1", params![from])?;
+            tx.execute(
+                "INSERT INTO stats_df_rollups (period, bucket, device_id, downlink_format, frames)
+                SELECT period, bucket, ?2, downlink_format, frames
+                FROM stats_df_rollups WHERE device_id = ?1
+                ON CONFLICT (period, bucket, device_id, downlink_format) DO UPDATE SET
+                    frames = frames + excluded.frames",
+                params![from, to],
+            )?;
+            tx.execute("DELETE FROM stats_df_rollups WHERE device_id = ?1", params![from])?;
+
+            tx.commit()?;
+            Ok(updated as u64)
+        })
+        .await
+    }
+
+    async fn get_emergencies(&self, active_only: bool, devices: Option<&[String]>, limit: i64) -> Result<Vec<JsonValue>> {
+        let devices = devices.map(|devices| serde_json::json!(devices).to_string());
+        self.with_conn(move |conn| {
+            let mut stmt = conn.prepare_cached(
+                "SELECT icao_address, started_at, last_seen, ended_at, emergency, squawk,
+                        callsign, latitude, longitude, altitude_ft, device_id
+                FROM emergencies
+                WHERE (NOT ?1 OR ended_at IS NULL)
+                  AND (?3 IS NULL OR device_id IN (SELECT value FROM json_each(?3)))
+                ORDER BY started_at DESC
+                LIMIT ?2",
+            )?;
+
+            let rows = stmt.query_map(params![active_only, limit, devices], |row| {
+                let ended_at: Option<i64> = row.get(3)?;
+                Ok(serde_json::json!({
+                    "icao": row.get::<_, String>(0)?,
+                    "emergency": row.get::<_, String>(4)?,
+                    "squawk": row.get::<_, String>(5)?,
+                    "callsign": row.get::<_, Option<String>>(6)?,
+                    "active": ended_at.is_none(),
+                    "started_at": ms_to_rfc3339(row.get(1)?),
+                    "last_seen": ms_to_rfc3339(row.get(2)?),
+                    "ended_at": ended_at.map(ms_to_rfc3339),
+                    "lat": row.get::<_, Option<f64>>(7)?,
+                    "lon": row.get::<_, Option<f64>>(8)?,
+                    "altitude": row.get::<_, Option<i32>>(9)?,
+                    "device_id": row.get::<_, Option<String>>(10)?,
+                }))
+            })?;
+
+            Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+        })
+        .await
+    }
+
+    async fn upsert_receiver_session(&self, session: &ReceiverSession) -> Result<()> {
+        let session = session.clone();
+        self.with_conn(move |conn| {
+            conn.execute(
+                "INSERT INTO receiver_sessions (device_id, started_at, last_seen, ended_at, end_reason)
+                VALUES (?1, ?2, ?3, ?4, ?5)
+                ON CONFLICT (device_id, started_at) DO UPDATE SET
+                    last_seen = excluded.last_seen,
+                    ended_at = excluded.ended_at,
+                    end_reason = excluded.end_reason",
+                params![
+                    session.device_id,
+                    session.started_at.timestamp_millis(),
+                    session.last_seen.timestamp_millis(),
+                    session.ended_at.map(|t| t.timestamp_millis()),
+                    session.end_reason,
+                ],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn get_receiver_sessions(&self, since: DateTime<Utc>) -> Result<Vec<ReceiverSession>> {
+        self.with_conn(move |conn| {
+            let mut stmt = conn.prepare_cached(
+                "SELECT device_id, started_at, last_seen, ended_at, end_reason
+                FROM receiver_sessions
+                WHERE COALESCE(ended_at, last_seen) >= ?1
+                ORDER BY started_at",
+            )?;
+
+            let rows = stmt.query_map(params![since.timestamp_millis()], |row| {
+                Ok(ReceiverSession {
+                    device_id: row.get(0)?,
+                    started_at: DateTime::from_timestamp_millis(row.get(1)?).unwrap_or_default(),
+                    last_seen: DateTime::from_timestamp_millis(row.get(2)?).unwrap_or_default(),
+                    ended_at: row.get::<_, Option<i64>>(3)?.and_then(DateTime::from_timestamp_millis),
+                    end_reason: row.get(4)?,
+                })
+            })?;
+
+            Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+        })
+        .await
+    }
+
+    async fn get_watchlist(&self) -> Result<Vec<WatchRule>> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare_cached(
+                "SELECT id, name, match_kind, pattern, geofence, webhook_url,
+                        notifiers, cooldown_seconds, enabled
+                FROM watchlist_rules
+                ORDER BY id",
+            )?;
+            let rows = stmt.query_map([], watch_rule_from_row)?;
+            let rules = rows.collect::<rusqlite::Result<Vec<_>>>()?;
+            Ok(rules.into_iter().flatten().collect())
+        })
+        .await
+    }
+
+    async fn insert_watchlist(&self, rule: &NewWatchRule) -> Result<WatchRule> {
+        let rule = rule.clone();
+        self.with_conn(move |conn| {
+            let geofence = rule
+                .geofence
+                .as_ref()
+                .map(serde_json::to_string)
+                .transpose()?;
+            conn.execute(
+                "INSERT INTO watchlist_rules (
+                    name, match_kind, pattern, geofence, webhook_url, notifiers,
+                    cooldown_seconds, created_at
+                ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                params![
+                    rule.name,
+                    rule.kind.as_str(),
+                    rule.pattern,
+                    geofence,
+                    rule.webhook_url,
+                    serde_json::to_string(&rule.notifiers)?,
+                    rule.cooldown_seconds.unwrap_or(DEFAULT_COOLDOWN_SECONDS),
+                    now_ms(),
+                ],
+            )?;
+            let id = conn.last_insert_rowid();
+            let stored = conn.query_row(
+                "SELECT id, name, match_kind, pattern, geofence, webhook_url,
+                        notifiers, cooldown_seconds, enabled
+                FROM watchlist_rules WHERE id = ?1",
+                params![id],
+                watch_rule_from_row,
+            )?;
+            stored.ok_or_else(|| anyhow!("Invalid watchlist rule stored"))
+        })
+        .await
+    }
+
+    async fn delete_watchlist(&self, id: i64) -> Result<bool> {
+        self.with_conn(move |conn| {
+            let deleted = conn.execute("DELETE FROM watchlist_rules WHERE id = ?1", params![id])?;
+            Ok(deleted > 0)
+        })
+        .await
+    }
+
+    async fn get_geofences(&self) -> Result<Vec<GeofenceDef>> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare_cached(
+                "SELECT id, name, shape, min_altitude_ft, max_altitude_ft, log_events
+                FROM geofences
+                ORDER BY id",
+            )?;
+            let rows = stmt.query_map([], geofence_from_row)?;
+            let fences = rows.collect::<rusqlite::Result<Vec<_>>>()?;
+            Ok(fences.into_iter().flatten().collect())
+        })
+        .await
+    }
+
+    async fn insert_geofence(&self, fence: &NewGeofence) -> Result<GeofenceDef> {
+        let fence = fence.clone();
+        self.with_conn(move |conn| {
+            conn.execute(
+                "INSERT INTO geofences (
+                    name, shape, min_altitude_ft, max_altitude_ft, log_events, created_at
+                ) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    fence.name,
+                    serde_json::to_string(&fence.shape)?,
+                    fence.min_altitude_ft,
+                    fence.max_altitude_ft,
+                    fence.log_events,
+                    now_ms(),
+                ],
+            )?;
+            let id = conn.last_insert_rowid();
+            let stored = conn.query_row(
+                "SELECT id, name, shape, min_altitude_ft, max_altitude_ft, log_events
+                FROM geofences WHERE id = ?1",
+                params![id],
+                geofence_from_row,
+            )?;
+            stored.ok_or_else(|| anyhow!("Invalid geofence stored"))
+        })
+        .await
+    }
+
+    async fn delete_geofence(&self, id: i64) -> Result<bool> {
+        self.with_conn(move |conn| {
+            // Foreign keys are off by default in SQLite, so cascade by hand
+            let tx = conn.transaction()?;
+            tx.execute("DELETE FROM geofence_events WHERE geofence_id = ?1", params![id])?;
+            let deleted = tx.execute("DELETE FROM geofences WHERE id = ?1", params![id])?;
+            tx.commit()?;
+            Ok(deleted > 0)
+        })
+        .await
+    }
+
+    async fn insert_geofence_event(&self, event: &GeofenceEvent) -> Result<()> {
+        let event = event.clone();
+        self.with_conn(move |conn| {
+            conn.execute(
+                "INSERT INTO geofence_events (
+                    time, geofence_id, icao_address, callsign, event, reason,
+                    latitude, longitude, altitude_ft
+                ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                params![
+                    event.time.timestamp_millis(),
+                    event.geofence_id,
+                    event.icao,
+                    event.callsign,
+                    event.event.as_str(),
+                    event.reason,
+                    event.lat,
+                    event.lon,
+                    event.altitude_ft,
+                ],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn get_geofence_events(&self, geofence_id: Option<i64>, limit: i64) -> Result<Vec<JsonValue>> {
+        self.with_conn(move |conn| {
+            let mut stmt = conn.prepare_cached(
+                "SELECT e.time, e.geofence_id, g.name, e.icao_address, e.callsign, e.event,
+                        e.reason, e.latitude, e.longitude, e.altitude_ft
+                FROM geofence_events e
+                JOIN geofences g ON g.id = e.geofence_id
+                WHERE ?1 IS NULL OR e.geofence_id = ?1
+                ORDER BY e.time DESC
+                LIMIT ?2",
+            )?;
+
+            let rows = stmt.query_map(params![geofence_id, limit], |row| {
+                Ok(serde_json::json!({
+                    "time": ms_to_rfc3339(row.get::<_, i64>(0)?),
+                    "geofence_id": row.get::<_, i64>(1)?,
+                    "geofence_name": row.get::<_, String>(2)?,
+                    "icao": row.get::<_, String>(3)?,
+                    "callsign": row.get::<_, Option<String>>(4)?,
+                    "event": row.get::<_, String>(5)?,
+                    "reason": row.get::<_, Option<String>>(6)?,
+                    "lat": row.get::<_, Option<f64>>(7)?,
+                    "lon": row.get::<_, Option<f64>>(8)?,
+                    "altitude": row.get::<_, Option<i32>>(9)?,
+                }))
+            })?;
+
+            Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+        })
+        .await
+    }
+
+    async fn get_squawk_changes(
+        &self,
+        icao: Option<&str>,
+        devices: Option<&[String]>,
+        limit: i64,
+    ) -> Result<Vec<JsonValue>> {
+        let icao = icao.map(str::to_string);
+        let devices = devices.map(|devices| serde_json::json!(devices).to_string());
+        self.with_conn(move |conn| {
+            let mut stmt = conn.prepare_cached(
+                "SELECT time, icao_address, previous_squawk, squawk, device_id
+                FROM squawk_changes
+                WHERE (?1 IS NULL OR icao_address = ?1)
+                  AND (?3 IS NULL OR device_id IN (SELECT value FROM json_each(?3)))
+                ORDER BY time DESC
+                LIMIT ?2",
+            )?;
+
+            let rows = stmt.query_map(params![icao, limit, devices], |row| {
+                Ok(serde_json::json!({
+                    "icao": row.get::<_, String>(1)?,
+                    "time": ms_to_rfc3339(row.get(0)?),
+                    "previous_squawk": row.get::<_, Option<String>>(2)?,
+                    "squawk": row.get::<_, String>(3)?,
+                    "device_id": row.get::<_, Option<String>>(4)?,
+                }))
+            })?;
+
+            Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+        })
+        .await
+    }
+
+    async fn db_health(&self) -> Result<DbHealth> {
+        let tables = self
+            .with_conn(|conn| {
+                let mut tables = Vec::new();
+                for &(name, retention_days) in HEALTH_TABLES {
+                    // Names are the constants above, never input
+                    let rows = conn
+                        .query_row(&format!("SELECT max(rowid) - min(rowid) + 1 FROM {}", name), [], |row| {
+                            row.get::<_, Option<i64>>(0)
+                        })?
+                        .or(Some(0));
+                    // dbstat is missing from SQLite builds without it
+                    let bytes = conn
+                        .query_row(
+                            "SELECT sum(pgsize) FROM dbstat
+                            WHERE name = ?1
+                               OR name IN (SELECT name FROM sqlite_master WHERE type = 'index' AND tbl_name = ?1)",
+                            params![name],
+                            |row| row.get::<_, Option<i64>>(0),
+                        )
+                        .ok()
+                        .flatten();
+                    let oldest = match retention_days {
+                        Some(_) => conn
+                            .query_row(&format!("SELECT min(time) FROM {}", name), [], |row| {
+                                row.get::<_, Option<i64>>(0)
+                            })?
+                            .and_then(DateTime::from_timestamp_millis),
+                        None => None,
+                    };
+                    tables.push(TableHealth {
+                        name: name.to_string(),
+                        rows,
+                        bytes,
+                        oldest,
+                        chunks: None,
+                        retention_days: retention_days.map(|days| days as f64),
+                    });
+                }
+                Ok(tables)
+            })
+            .await?;
+        Ok(DbHealth::new(self.backend_name(), None, tables, Utc::now()))
+    }
+}
+
+
+/// Build a watchlist rule from a `watchlist_rules` row (None for unknown kinds)
+fn watch_rule_from_row(row: &rusqlite::Row) -> rusqlite::Result<Option<WatchRule>> {
+    let kind = match MatchKind::parse(&row.get::<_, String>(2)?) {
+        Some(kind) => kind,
+        None => return Ok(None),
+    };
+    Ok(Some(WatchRule {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        kind,
+        pattern: row.get(3)?,
+        geofence: row
+            .get::<_, Option<String>>(4)?
+            .and_then(|s| serde_json::from_str(&s).ok()),
+        webhook_url: row.get(5)?,
+        notifiers: row
+            .get::<_, Option<String>>(6)?
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default(),
+        cooldown_seconds: row.get(7)?,
+        enabled: row.get(8)?,
+    }))
+}
+
+
+/// Build a geofence from a `geofences` row (None if the shape is invalid)
+fn geofence_from_row(row: &rusqlite::Row) -> rusqlite::Result<Option<GeofenceDef>> {
+    let shape = match serde_json::from_str(&row.get::<_, String>(2)?) {
+        Ok(shape) => shape,
+        Err(_) => return Ok(None),
+    };
+    Ok(Some(GeofenceDef {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        shape,
+        min_altitude_ft: row.get(3)?,
+        max_altitude_ft: row.get(4)?,
+        log_events: row.get(5)?,
+    }))
+}
+
+/// Tables renamed after they were first released, as (old, new)
+const TABLE_RENAMES: &[(&str, &str)] = &[("squawk_history", "squawk_changes")];
+
+/// Columns renamed after a table was first released, as (table, old, new)
+const COLUMN_RENAMES: &[(&str, &str, &str)] = &[
+    ("aircraft_positions", "heading_deg", "track_deg"),
+];
+
+/// Columns added after a table was first released, applied to existing files
+const COLUMN_MIGRATIONS: &[(&str, &str, &str)] = &[
+    ("watchlist_rules", "notifiers", "TEXT NOT NULL DEFAULT '[]'"),
+    ("aircraft_info", "squawk", "TEXT"),
+    ("aircraft_info", "last_device_id", "TEXT"),
+    ("aircraft_positions", "magnetic_heading_deg", "REAL"),
+    ("aircraft_positions", "baro_rate_fpm", "INTEGER"),
+    ("aircraft_positions", "geom_rate_fpm", "INTEGER"),
+    ("squawk_changes", "previous_squawk", "TEXT"),
+    ("squawk_changes", "device_id", "TEXT"),
+    ("sdr_status", "software_version", "TEXT"),
+    ("sdr_status", "uptime_secs", "INTEGER"),
+    ("sdr_status", "decoder", "TEXT"),
+    ("sdr_status", "clock_offset_ms", "INTEGER"),
+    ("sdr_status_history", "software_version", "TEXT"),
+    ("sdr_status_history", "decoder", "TEXT"),
+];
+
+fn has_table(conn: &Connection, table: &str) -> Result<bool> {
+    Ok(conn
+        .prepare("SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?1")?
+        .exists(params![table])?)
+}
+
+/// Rename tables in a file created by an older schema; runs before the
+/// schema so it doesn't create the new table first
+fn rename_tables(conn: &Connection) -> Result<()> {
+    for (old, new) in TABLE_RENAMES {
+        if has_table(conn, old)? && !has_table(conn, new)? {
+            debug!("Renaming table {} to {}", old, new);
+            conn.execute_batch(&format!("ALTER TABLE {} RENAME TO {}", old, new))?;
+        }
+    }
+    Ok(())
+}
+
+fn has_column(conn: &Connection, table: &str, column: &str) -> Result<bool> {
+    Ok(conn
+        .prepare(&format!("SELECT 1 FROM pragma_table_info('{}') WHERE name = ?1", table))?
+        .exists(params![column])?)
+}
+
+/// Rename and add columns in tables created by an older schema
+fn migrate(conn: &Connection) -> Result<()> {
+    for (table, old, new) in COLUMN_RENAMES {
+        if has_column(conn, table, old)? && !has_column(conn, table, new)? {
+            debug!("Renaming column {}.{} to {}", table, old, new);
+            conn.execute_batch(&format!("ALTER TABLE {} RENAME COLUMN {} TO {}", table, old, new))?;
+        }
+    }
+    for (table, column, decl) in COLUMN_MIGRATIONS {
+        if !has_column(conn, table, column)? {
+            debug!("Adding column {}.{}", table, column);
+            conn.execute_batch(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, decl))?;
+        }
+    }
+    Ok(())
+}
+
+/// Current time as unix milliseconds
+fn now_ms() -> i64 {
+    chrono::Utc::now().timestamp_millis()
+}
+
+/// Convert unix milliseconds to an RFC 3339 string (matches the Postgres output)
+fn ms_to_rfc3339(ms: i64) -> String {
+    chrono::DateTime::from_timestamp_millis(ms)
+        .unwrap_or_default()
+        .to_rfc3339()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_migrate_old_schema() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE aircraft_positions (time INTEGER, icao_address TEXT, heading_deg REAL);
+             INSERT INTO aircraft_positions VALUES (1, '71BE11', 90.0);
+             CREATE TABLE squawk_history (time INTEGER, icao_address TEXT, squawk TEXT);
+             INSERT INTO squawk_history VALUES (1, '71BE11', '7600');",
+        )
+        .unwrap();
+        // Running again on a migrated file changes nothing
+        for _ in 0..2 {
+            rename_tables(&conn).unwrap();
+            conn.execute_batch(SCHEMA).unwrap();
+            migrate(&conn).unwrap();
+        }
+
+        let (track, heading): (f32, Option<f32>) = conn
+            .query_row(
+                "SELECT track_deg, magnetic_heading_deg FROM aircraft_positions",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!((track, heading), (90.0, None));
+        assert!(!has_column(&conn, "aircraft_positions", "heading_deg").unwrap());
+
+        let (squawk, previous): (String, Option<String>) = conn
+            .query_row("SELECT squawk, previous_squawk FROM squawk_changes", [], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .unwrap();
+        assert_eq!((squawk.as_str(), previous), ("7600", None));
+        assert!(!has_table(&conn, "squawk_history").unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_trail_buckets() {
+        let db = SqliteWriter::open(Path::new(":memory:")).await.unwrap();
+        let start = now_ms() - 60_000;
+        db.with_conn(move |conn| {
+            for i in 0..6 {
+                conn.execute(
+                    "INSERT INTO aircraft_positions (time, icao_address, latitude, longitude, device_id)
+                     VALUES (?1, '71BE11', ?2, 126.4, ?3)",
+                    params![start + i * 10_000, 37.0 + i as f64, if i % 2 == 0 { "acme-rx1" } else { "globex-rx1" }],
+                )?;
+            }
+            Ok(())
+        })
+        .await
+        .unwrap();
+        let from = DateTime::from_timestamp_millis(start).unwrap();
+
+        let trail = db.get_aircraft_trail("71BE11", None, from, None, None).await.unwrap();
+        assert_eq!(trail.len(), 6);
+        assert!(trail[0]["positions"].is_null());
+
+        let trail = db.get_aircraft_trail("71BE11", None, from, None, Some(30)).await.unwrap();
+        let positions: i64 = trail.iter().map(|p| p["positions"].as_i64().unwrap()).sum();
+        assert!(trail.len() <= 3 && positions == 6);
+        assert_eq!(trail.last().unwrap()["lat"], 42.0);
+
+        // Other receivers' positions don't fill a tenant's buckets
+        let devices = ["acme-rx1".to_string()];
+        let trail = db.get_aircraft_trail("71BE11", Some(&devices), from, None, Some(30)).await.unwrap();
+        let positions: i64 = trail.iter().map(|p| p["positions"].as_i64().unwrap()).sum();
+        assert_eq!(positions, 3);
+        assert_eq!(trail.last().unwrap()["lat"], 41.0);
+    }
+
+    #[tokio::test]
+    async fn test_device_filter_before_limit() {
+        let db = SqliteWriter::open(Path::new(":memory:")).await.unwrap();
+        db.with_conn(|conn| {
+            conn.execute_batch(
+                "INSERT INTO squawk_changes (time, icao_address, squawk, device_id) VALUES
+                    (1000, '71BE11', '7600', 'acme-rx1'),
+                    (2000, '71BE11', '7000', 'globex-rx1')",
+            )?;
+            Ok(())
+        })
+        .await
+        .unwrap();
+
+        let devices = ["acme-rx1".to_string()];
+        let changes = db.get_squawk_changes(None, Some(&devices), 1).await.unwrap();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0]["device_id"], "acme-rx1");
+        assert!(db.get_squawk_changes(None, Some(&[]), 10).await.unwrap().is_empty());
+        assert_eq!(db.get_squawk_changes(None, None, 10).await.unwrap().len(), 2);
+    }
+}
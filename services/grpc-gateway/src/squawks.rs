@@ -0,0 +1,125 @@
+//! Squawk changes
+//!
+//! Controllers usually care about a change of squawk code more than the
+//! code itself. Each event's squawk is compared with the last one merged for
+//! the aircraft and a `squawk_change` message is broadcast when it differs.
+//! The storage backend logs every code an aircraft sets, with the code it
+//! replaced, in the `squawk_changes` table served by `/api/squawk-changes`.
+
+use crate::adsb::AircraftEvent;
+use crate::api::{self, ApiError};
+use crate::pubsub::LiveMessage;
+use crate::tenants::Scope;
+use crate::AppState;
+use axum::{
+    extract::{Query, State},
+    response::IntoResponse,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tracing::error;
+use utoipa::{IntoParams, ToSchema};
+
+/// A squawk code an aircraft set
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct SquawkChange {
+    pub icao: String,
+    /// RFC 3339 time the new code was first stored
+    pub time: String,
+    /// Code before the change; absent for the first code seen
+    pub previous_squawk: Option<String>,
+    pub squawk: String,
+    /// Device that received the new code
+    pub device_id: Option<String>,
+}
+
+/// Query parameters for the squawk change log
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SquawkChangeParams {
+    /// Only changes by this aircraft
+    pub icao: Option<String>,
+    /// 1-1000, default 100
+    pub limit: Option<i64>,
+}
+
+/// The `squawk_change` message for `event`, when its squawk differs from
+/// the aircraft's `previous` one
+pub fn change_message(previous: Option<&str>, event: &AircraftEvent) -> Option<LiveMessage> {
+    let previous = previous.filter(|s| !s.is_empty())?;
+    let squawk = event.squawk.as_deref().filter(|s| !s.is_empty())?;
+    if squawk == previous {
+        return None;
+    }
+    Some(LiveMessage::from_value(&serde_json::json!({
+        "type": "squawk_change",
+        "icao": event.icao,
+        "device_id": event.device_id,
+        "previous_squawk": previous,
+        "squawk": squawk,
+        "timestamp_ms": event.timestamp_ms,
+    })))
+}
+
+/// Get stored squawk changes, most recent first
+#[utoipa::path(
+    get,
+    path = "/api/squawk-changes",
+    tag = "alerts",
+    params(SquawkChangeParams),
+    responses(
+        (status = 200, description = "Squawk changes, most recent first", body = [SquawkChange]),
+        (status = 400, description = "Invalid ICAO address", body = ApiError),
+    ),
+    security((), ("api_key" = []), ("bearer" = []))
+)]
+pub async fn get_squawk_changes(
+    State(state): State<Arc<AppState>>,
+    scope: Scope,
+    Query(params): Query<SquawkChangeParams>,
+) -> impl IntoResponse {
+    let icao = match params.icao.as_deref().map(api::parse_icao).transpose() {
+        Ok(icao) => icao,
+        Err(e) => return ApiError::bad_request(e),
+    };
+    let limit = params.limit.unwrap_or(100).clamp(1, 1000);
+    let devices = scope.devices();
+    let changes = state.db_writer.get_squawk_changes(icao.as_deref(), devices.as_deref(), limit).await;
+    let changes = changes.and_then(|rows| Ok(api::from_rows::<SquawkChange>(rows)?));
+    match changes {
+        Ok(changes) => Json(changes).into_response(),
+        Err(e) => {
+            error!("Failed to get squawk changes: {}", e);
+            ApiError::internal(e)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(squawk: Option<&str>) -> AircraftEvent {
+        AircraftEvent {
+            icao: "71BE11".into(),
+            device_id: "rtlsdr-0".into(),
+            squawk: squawk.map(String::from),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_change_message() {
+        let msg = change_message(Some("1234"), &event(Some("7600"))).unwrap();
+        let json: serde_json::Value = serde_json::from_str(&msg.json).unwrap();
+        assert_eq!(json["type"], "squawk_change");
+        assert_eq!(json["previous_squawk"], "1234");
+        assert_eq!(json["squawk"], "7600");
+
+        // Same code, no code, or nothing known before
+        assert!(change_message(Some("1234"), &event(Some("1234"))).is_none());
+        assert!(change_message(Some("1234"), &event(None)).is_none());
+        assert!(change_message(None, &event(Some("7600"))).is_none());
+    }
+}
@@ -0,0 +1,374 @@
+//! Server-Sent Events stream
+//!
+//! `/api/stream` carries the same JSON messages as the WebSocket broadcast,
+//! for clients behind proxies that block WebSockets or that would rather
+//! read a plain HTTP stream. Every broadcast message is numbered and the
+//! most recent ones are kept in an [`EventLog`], so a client reconnecting
+//! with `Last-Event-ID` is sent what it missed instead of starting over.
+//! Event IDs are `<gateway start ms>-<sequence>`; an ID from an earlier
+//! gateway run, or one older than the log, gets a fresh `initial` snapshot.
+//! Clients connecting with `?api_key=` are limited to the credential's
+//! tenant like WebSocket clients. In public viewer mode, clients without
+//! the `WS_AUTH_TOKEN` token or an API key get the public view's delayed,
+//! redacted stream, which can't be resumed.
+
+use crate::connections::ConnectionGuard;
+use crate::public::PublicView;
+use crate::pubsub::{LiveMessage, PubSub};
+use crate::share::SharePolicies;
+use crate::tenants::Scope;
+use crate::ws_handler::initial_message;
+use crate::AppState;
+use axum::{
+    extract::{ConnectInfo, Query, State},
+    http::HeaderMap,
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
+};
+use futures_util::{Stream, StreamExt};
+use serde::Deserialize;
+use std::collections::VecDeque;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast;
+use tracing::{debug, info, warn};
+
+/// Messages kept for resuming clients
+pub const REPLAY_CAPACITY: usize = 10_000;
+
+/// A numbered broadcast message
+type Numbered = (u64, Arc<LiveMessage>);
+
+struct Log {
+    next_seq: u64,
+    events: VecDeque<Numbered>,
+}
+
+/// Numbers broadcast messages and keeps the most recent for replay
+pub struct EventLog {
+    epoch: i64,
+    capacity: usize,
+    log: Mutex<Log>,
+    tx: broadcast::Sender<Numbered>,
+}
+
+impl EventLog {
+    pub fn new(capacity: usize) -> Self {
+        let (tx, _) = broadcast::channel(1000);
+        Self {
+            epoch: chrono::Utc::now().timestamp_millis(),
+            capacity,
+            log: Mutex::new(Log {
+                next_seq: 1,
+                events: VecDeque::new(),
+            }),
+            tx,
+        }
+    }
+
+    /// Record a message and pass it on to SSE clients
+    pub fn push(&self, msg: Arc<LiveMessage>) {
+        let Ok(mut log) = self.log.lock() else {
+            return;
+        };
+        let event = (log.next_seq, msg);
+        log.next_seq += 1;
+        if log.events.len() == self.capacity {
+            log.events.pop_front();
+        }
+        log.events.push_back(event.clone());
+        // Sent under the lock so subscribers see sequence order
+        let _ = self.tx.send(event);
+    }
+
+    fn event_id(&self, seq: u64) -> String {
+        format!("{}-{}", self.epoch, seq)
+    }
+
+    /// Sequence number of an ID issued by this run
+    fn parse_id(&self, id: &str) -> Option<u64> {
+        let (epoch, seq) = id.trim().split_once('-')?;
+        (epoch.parse::<i64>().ok()? == self.epoch).then_some(())?;
+        seq.parse().ok()
+    }
+
+    /// Subscribe to new messages, with the ones after `after` when they're
+    /// all still in the log (`None` means the client needs a snapshot)
+    fn subscribe(
+        &self,
+        after: Option<u64>,
+    ) -> Option<(Option<Vec<Numbered>>, broadcast::Receiver<Numbered>)> {
+        let log = self.log.lock().ok()?;
+        let rx = self.tx.subscribe();
+        Some((after.and_then(|seq| replay(&log, seq)), rx))
+    }
+
+    /// Messages after `seq`, if the log still covers them
+    fn since(&self, seq: u64) -> Option<Vec<Numbered>> {
+        replay(&*self.log.lock().ok()?, seq)
+    }
+
+    /// Sequence number of the latest message
+    fn last_seq(&self) -> u64 {
+        self.log.lock().map(|log| log.next_seq - 1).unwrap_or(0)
+    }
+}
+
+fn replay(log: &Log, after: u64) -> Option<Vec<Numbered>> {
+    if after >= log.next_seq {
+        return None;
+    }
+    let oldest = log.events.front().map(|(seq, _)| *seq).unwrap_or(log.next_seq);
+    if after + 1 < oldest {
+        return None;
+    }
+    Some(log.events.iter().filter(|(seq, _)| *seq > after).cloned().collect())
+}
+
+/// Feed every WebSocket broadcast, less what `SHARE_SSE` withholds, into
+/// the log
+pub fn spawn_recorder(log: Arc<EventLog>, pubsub: Arc<dyn PubSub>, share: Arc<SharePolicies>) {
+    let mut rx = pubsub.subscribe();
+    tokio::spawn(async move {
+        loop {
+            match rx.recv().await {
+                Ok(msg) => {
+                    if let Some(msg) = share.sse.apply_message(msg) {
+                        log.push(msg);
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(n)) => {
+                    debug!("SSE event log lagged by {} messages", n);
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    });
+}
+
+/// Resume position, for clients that can't set the `Last-Event-ID` header
+#[derive(Debug, Default, Deserialize)]
+pub struct StreamParams {
+    pub last_event_id: Option<String>,
+    /// Access token, when `WS_AUTH_TOKEN` is set
+    pub token: Option<String>,
+    /// API key or JWT, in place of `token`
+    pub api_key: Option<String>,
+}
+
+/// Per-client stream state
+struct Client {
+    state: Arc<AppState>,
+    scope: Scope,
+    rx: broadcast::Receiver<Numbered>,
+    /// Last sequence number sent
+    last: u64,
+    pending: VecDeque<Event>,
+    _guard: ConnectionGuard,
+}
+
+impl Client {
+    fn queue(&mut self, events: Vec<Numbered>) {
+        for (seq, msg) in events {
+            if self.scope.allows_message(&msg.fields) {
+                self.pending
+                    .push_back(Event::default().id(self.state.events.event_id(seq)).data(&*msg.json));
+            }
+            self.last = seq;
+        }
+    }
+
+    /// Start over from a snapshot of the current aircraft
+    fn queue_snapshot(&mut self) {
+        // Messages up to here are superseded by the snapshot
+        self.last = self.state.events.last_seq();
+        if let Some(json) = initial_message(&self.state, &self.state.share.sse, &self.scope) {
+            self.pending.push_back(Event::default().data(json));
+        }
+    }
+
+    async fn next(mut self) -> Option<(Result<Event, Infallible>, Self)> {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return Some((Ok(event), self));
+            }
+            match self.rx.recv().await {
+                Ok((seq, msg)) if seq > self.last => {
+                    self.last = seq;
+                    if !self.scope.allows_message(&msg.fields) {
+                        continue;
+                    }
+                    let event = Event::default().id(self.state.events.event_id(seq)).data(&*msg.json);
+                    return Some((Ok(event), self));
+                }
+                Ok(_) => {}
+                Err(broadcast::error::RecvError::Lagged(n)) => {
+                    debug!("SSE client lagged by {} messages", n);
+                    match self.state.events.since(self.last) {
+                        Some(missed) => self.queue(missed),
+                        None => self.queue_snapshot(),
+                    }
+                }
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    }
+}
+
+/// Handle an SSE subscription
+pub async fn sse_handler(
+    State(state): State<Arc<AppState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Query(params): Query<StreamParams>,
+    headers: HeaderMap,
+) -> Response {
+    let (scope, admitted) = match params.api_key.as_deref() {
+        Some(key) => match state.auth.verify(key) {
+            Ok(principal) => (principal.scope, state.connections.admit_authenticated(addr.ip())),
+            Err(e) => {
+                warn!("Rejected SSE client {}: {:?}", addr, e);
+                return e.into_response();
+            }
+        },
+        None if state.public.enabled() && !state.connections.authenticates(params.token.as_deref(), &headers) => {
+            (Scope::Public, state.connections.admit_authenticated(addr.ip()))
+        }
+        None => (Scope::All, state.connections.admit(addr.ip(), params.token.as_deref(), &headers)),
+    };
+    let guard = match admitted {
+        Ok(guard) => guard,
+        Err(rejection) => {
+            warn!("Rejected SSE client {}: {:?}", addr, rejection);
+            return rejection.into_response();
+        }
+    };
+    if scope == Scope::Public {
+        info!("New public SSE client connected");
+        let stream = public_messages(&state.public).map(move |json| {
+            let _guard = &guard;
+            Ok(Event::default().data(json))
+        });
+        return sse_response(stream);
+    }
+    let last_event_id = headers
+        .get("last-event-id")
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string)
+        .or(params.last_event_id);
+    let after = last_event_id.as_deref().and_then(|id| state.events.parse_id(id));
+
+    let client = match state.events.subscribe(after) {
+        Some((replay, rx)) => {
+            let mut client = Client {
+                state: state.clone(),
+                scope,
+                rx,
+                last: 0,
+                pending: VecDeque::new(),
+                _guard: guard,
+            };
+            match replay {
+                Some(missed) => {
+                    info!("SSE client resumed with {} missed messages", missed.len());
+                    client.last = after.unwrap_or_default();
+                    client.queue(missed);
+                }
+                None => {
+                    info!("New SSE client connected");
+                    client.queue_snapshot();
+                }
+            }
+            Some(client)
+        }
+        None => None,
+    };
+
+    let stream = futures_util::stream::unfold(client, |client| async move {
+        client?.next().await.map(|(event, client)| (event, Some(client)))
+    });
+    sse_response(stream)
+}
+
+fn sse_response(stream: impl Stream<Item = Result<Event, Infallible>> + Send + 'static) -> Response {
+    (
+        // Stop nginx-style proxies from buffering the stream
+        [("x-accel-buffering", "no")],
+        Sse::new(stream).keep_alive(KeepAlive::default()),
+    )
+        .into_response()
+}
+
+/// The public view's `initial` message, then its live messages
+fn public_messages(view: &PublicView) -> impl Stream<Item = String> {
+    let initial = view.initial_message();
+    let queue = view.fanout.subscribe();
+    futures_util::stream::unfold((initial, queue), |(initial, mut queue)| async move {
+        let json = match initial {
+            Some(json) => json,
+            None => queue.recv().await?.json.to_string(),
+        };
+        Some((json, (None, queue)))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_replay_window() {
+        let log = EventLog::new(3);
+        for i in 1..=5 {
+            log.push(Arc::new(LiveMessage::parse(&format!("{{\"n\":{}}}", i))));
+        }
+        // Log holds 3, 4, 5
+        let seqs = |after| {
+            log.since(after)
+                .map(|events| events.iter().map(|(seq, _)| *seq).collect::<Vec<_>>())
+        };
+        assert_eq!(seqs(2), Some(vec![3, 4, 5]));
+        assert_eq!(seqs(4), Some(vec![5]));
+        assert_eq!(seqs(5), Some(vec![]));
+        assert_eq!(seqs(1), None);
+        assert_eq!(seqs(9), None);
+    }
+
+    #[test]
+    fn test_event_ids() {
+        let log = EventLog::new(3);
+        let id = log.event_id(42);
+        assert_eq!(log.parse_id(&id), Some(42));
+        assert_eq!(log.parse_id("1-42"), None);
+        assert_eq!(log.parse_id("garbage"), None);
+    }
+
+    #[tokio::test]
+    async fn test_public_stream() {
+        use crate::fanout::Fanout;
+        use crate::pubsub::LocalPubSub;
+        use std::time::Duration;
+
+        let delay = Duration::from_millis(200);
+        let view = Arc::new(PublicView::new(true, Some(0.01), delay, Arc::new(Fanout::new(10, 0))));
+        let pubsub: Arc<dyn PubSub> = Arc::new(LocalPubSub::new());
+        crate::public::spawn_dispatcher(view.clone(), pubsub.clone(), Arc::new(SharePolicies::default()));
+        let mut stream = Box::pin(public_messages(&view));
+        let initial: serde_json::Value = serde_json::from_str(&stream.next().await.unwrap()).unwrap();
+        assert_eq!(initial["type"], "initial");
+
+        let published = tokio::time::Instant::now();
+        pubsub.publish(LiveMessage::from_value(&serde_json::json!({
+            "type": "position_update", "icao": "71BE11", "device_id": "rooftop",
+            "lat": 37.46123, "lon": 126.43877, "provenance": {"raw": "8D71BE11"},
+        })));
+        let json = tokio::time::timeout(Duration::from_secs(5), stream.next()).await.unwrap().unwrap();
+        assert!(published.elapsed() >= delay);
+        let message: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(message["icao"], "71BE11");
+        assert!(message.get("device_id").is_none() && message.get("provenance").is_none());
+        assert!((message["lat"].as_f64().unwrap() - 37.46).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,678 @@
+//! Receiver statistics rollups
+//!
+//! Messages, positions, distinct aircraft, maximum range, decoder CRC
+//! errors and decoded frames per downlink format are counted per hour and
+//! per day, for each device and for all devices together (`device_id` "").
+//! Once a minute the counters are flushed to the `stats_rollups` and
+//! `stats_df_rollups` tables as deltas that the backends add to the stored
+//! rows, so the statistics API reads a few summary rows however much
+//! position history there is. Distinct-aircraft counts and range are written as maxima, so
+//! a gateway restart mid-period undercounts them rather than resetting them.
+
+use crate::adsb::{AircraftEvent, SignalMetrics};
+use crate::geo::haversine_km;
+use crate::storage::Storage;
+use async_graphql::SimpleObject;
+use chrono::{DateTime, DurationRound, Utc};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::warn;
+use utoipa::ToSchema;
+
+/// How often rollups are written
+const FLUSH_INTERVAL_SECS: u64 = 60;
+
+/// Signal reports older than this don't count towards the live rate
+const LIVE_RATE_MAX_AGE_SECS: i64 = 30;
+
+/// Rolling windows for the rate of received events
+pub const RATE_WINDOWS_SECS: [i64; 3] = [10, 60, 300];
+
+/// Window for the per-device rate stored in `sdr_status`
+const DEVICE_RATE_WINDOW_SECS: i64 = 60;
+
+/// Rollup granularity
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Period {
+    Hour,
+    Day,
+}
+
+impl Period {
+    pub const ALL: [Period; 2] = [Period::Hour, Period::Day];
+
+    /// Stable name used in the database
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Hour => "hour",
+            Self::Day => "day",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "hour" => Some(Self::Hour),
+            "day" => Some(Self::Day),
+            _ => None,
+        }
+    }
+
+    pub fn duration(self) -> chrono::Duration {
+        match self {
+            Self::Hour => chrono::Duration::hours(1),
+            Self::Day => chrono::Duration::days(1),
+        }
+    }
+
+    /// Start of the (UTC) period containing `time`
+    pub fn start(self, time: DateTime<Utc>) -> DateTime<Utc> {
+        time.duration_trunc(self.duration()).unwrap_or(time)
+    }
+}
+
+/// Which rollup buckets a statistics request covers
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StatsRange {
+    /// Earliest hourly and daily bucket starts
+    pub hourly_from: DateTime<Utc>,
+    pub daily_from: DateTime<Utc>,
+    /// Buckets starting at or after this are left out; `None` runs to now
+    pub to: Option<DateTime<Utc>>,
+}
+
+impl StatsRange {
+    /// The last `hours` hourly and `days` daily buckets, counting the
+    /// current ones
+    pub fn recent(hours: i64, days: i64, now: DateTime<Utc>) -> Self {
+        Self {
+            hourly_from: Period::Hour.start(now) - chrono::Duration::hours(hours - 1),
+            daily_from: Period::Day.start(now) - chrono::Duration::days(days - 1),
+            to: None,
+        }
+    }
+
+    /// Every bucket overlapping `from..to`
+    pub fn between(from: DateTime<Utc>, to: DateTime<Utc>) -> Self {
+        Self {
+            hourly_from: Period::Hour.start(from),
+            daily_from: Period::Day.start(from),
+            to: Some(to),
+        }
+    }
+
+    /// Whether a bucket fetched from `hourly_from`/`daily_from` is in range
+    pub fn includes(&self, bucket: DateTime<Utc>) -> bool {
+        self.to.is_none_or(|to| bucket < to)
+    }
+}
+
+/// One `stats_rollups` row (or, when flushing, the change since the last flush)
+#[derive(Debug, Clone, PartialEq)]
+pub struct StatsRollup {
+    pub period: Period,
+    pub bucket: DateTime<Utc>,
+    /// Empty for the all-devices total
+    pub device_id: String,
+    pub messages: i64,
+    pub positions: i64,
+    pub unique_aircraft: i64,
+    /// 0 when the receiver location isn't configured
+    pub max_range_km: f64,
+    pub frames_decoded: i64,
+    pub crc_errors: i64,
+    /// Decoded frames per downlink format
+    pub downlink_formats: BTreeMap<u32, i64>,
+}
+
+#[derive(Default)]
+struct Bucket {
+    messages: i64,
+    positions: i64,
+    frames_decoded: i64,
+    crc_errors: i64,
+    downlink_formats: BTreeMap<u32, i64>,
+    aircraft: HashSet<String>,
+    max_range_km: f64,
+    dirty: bool,
+}
+
+#[derive(Default)]
+struct Inner {
+    buckets: HashMap<(Period, DateTime<Utc>, String), Bucket>,
+    /// Last cumulative `(frames_decoded, crc_errors)` reported per device
+    counters: HashMap<String, (u64, u64)>,
+    /// Last cumulative frame count per downlink format reported per device
+    downlink_formats: HashMap<String, HashMap<u32, u64>>,
+    /// Latest reported message rate per device
+    rates: HashMap<String, (f32, DateTime<Utc>)>,
+    /// Messages since startup
+    messages_total: u64,
+    /// Events per second over the longest rate window, per device and total ("")
+    per_second: HashMap<String, VecDeque<(i64, u32)>>,
+    /// When the first event arrived, so rates aren't diluted after startup
+    first_event: Option<DateTime<Utc>>,
+}
+
+impl Inner {
+    /// Apply `f` to the hour and day buckets of the device and the total
+    fn update(&mut self, device_id: &str, now: DateTime<Utc>, mut f: impl FnMut(&mut Bucket)) {
+        let devices: &[&str] = if device_id.is_empty() { &[""] } else { &["", device_id] };
+        for period in Period::ALL {
+            for device in devices {
+                let bucket = self
+                    .buckets
+                    .entry((period, period.start(now), device.to_string()))
+                    .or_default();
+                f(bucket);
+                bucket.dirty = true;
+            }
+        }
+    }
+
+    /// Count an event in the current second of the device and the total
+    fn count_second(&mut self, device_id: &str, now: DateTime<Utc>) {
+        self.first_event.get_or_insert(now);
+        let second = now.timestamp();
+        let oldest = second - RATE_WINDOWS_SECS[RATE_WINDOWS_SECS.len() - 1];
+        let devices: &[&str] = if device_id.is_empty() { &[""] } else { &["", device_id] };
+        for device in devices {
+            let counts = self.per_second.entry(device.to_string()).or_default();
+            match counts.back_mut() {
+                Some((s, n)) if *s == second => *n += 1,
+                _ => counts.push_back((second, 1)),
+            }
+            while counts.front().is_some_and(|(s, _)| *s <= oldest) {
+                counts.pop_front();
+            }
+        }
+    }
+
+    /// Events per second from a device ("" = all) over the last `window` seconds
+    fn rate(&self, device_id: &str, window: i64, now: DateTime<Utc>) -> f32 {
+        let Some(counts) = self.per_second.get(device_id) else {
+            return 0.0;
+        };
+        let since = now.timestamp() - window;
+        let events: u32 = counts
+            .iter()
+            .rev()
+            .take_while(|(s, _)| *s > since)
+            .map(|(_, n)| n)
+            .sum();
+        let elapsed = self
+            .first_event
+            .map_or(window, |first| (now - first).num_seconds() + 1)
+            .clamp(1, window);
+        events as f32 / elapsed as f32
+    }
+}
+
+/// Collects receiver statistics from the gRPC streams
+pub struct StatsCollector {
+    receiver: Option<(f64, f64)>,
+    inner: Mutex<Inner>,
+}
+
+impl StatsCollector {
+    pub fn new(receiver: Option<(f64, f64)>) -> Self {
+        Self {
+            receiver,
+            inner: Mutex::new(Inner::default()),
+        }
+    }
+
+    /// Receiver location used for range
+    pub fn receiver(&self) -> Option<(f64, f64)> {
+        self.receiver
+    }
+
+    /// Count a decoded aircraft event
+    pub fn record_event(&self, event: &AircraftEvent, now: DateTime<Utc>) {
+        let has_position = event.position().is_some();
+        let range_km = match (self.receiver, event.position()) {
+            (Some((lat, lon)), Some((event_lat, event_lon))) => {
+                haversine_km(lat, lon, event_lat, event_lon)
+            }
+            _ => 0.0,
+        };
+
+        let Ok(mut inner) = self.inner.lock() else {
+            return;
+        };
+        inner.messages_total += 1;
+        inner.count_second(&event.device_id, now);
+        inner.update(&event.device_id, now, |bucket| {
+            bucket.messages += 1;
+            if has_position {
+                bucket.positions += 1;
+            }
+            if !bucket.aircraft.contains(&event.icao) {
+                bucket.aircraft.insert(event.icao.clone());
+            }
+            bucket.max_range_km = bucket.max_range_km.max(range_km);
+        });
+    }
+
+    /// Count decoder frames and CRC errors from a signal report
+    pub fn record_signal(&self, metrics: &SignalMetrics, now: DateTime<Utc>) {
+        let Ok(mut inner) = self.inner.lock() else {
+            return;
+        };
+        inner
+            .rates
+            .insert(metrics.device_id.clone(), (metrics.msg_rate, now));
+
+        // Counters are cumulative since the capture started; the first report
+        // is only a baseline, and a drop means the capture restarted
+        let delta = |now: u64, before: u64| now.checked_sub(before).unwrap_or(now) as i64;
+        let formats: HashMap<u32, u64> = metrics
+            .downlink_formats
+            .iter()
+            .map(|c| (c.downlink_format, c.frames))
+            .collect();
+        let format_deltas: BTreeMap<u32, i64> = match inner.downlink_formats.get(&metrics.device_id) {
+            Some(previous) => formats
+                .iter()
+                .map(|(df, &frames)| (*df, delta(frames, previous.get(df).copied().unwrap_or(0))))
+                .filter(|(_, frames)| *frames > 0)
+                .collect(),
+            None => BTreeMap::new(),
+        };
+        inner.downlink_formats.insert(metrics.device_id.clone(), formats);
+
+        let current = (metrics.frames_decoded, metrics.crc_errors);
+        let Some(previous) = inner.counters.insert(metrics.device_id.clone(), current) else {
+            return;
+        };
+        let frames = delta(current.0, previous.0);
+        let crc = delta(current.1, previous.1);
+        if frames == 0 && crc == 0 && format_deltas.is_empty() {
+            return;
+        }
+        inner.update(&metrics.device_id, now, |bucket| {
+            bucket.frames_decoded += frames;
+            bucket.crc_errors += crc;
+            for (df, frames) in &format_deltas {
+                *bucket.downlink_formats.entry(*df).or_default() += frames;
+            }
+        });
+    }
+
+    /// Changes since the last call; buckets whose period is over are dropped
+    pub fn take_rollups(&self, now: DateTime<Utc>) -> Vec<StatsRollup> {
+        let Ok(mut inner) = self.inner.lock() else {
+            return Vec::new();
+        };
+
+        let mut rows = Vec::new();
+        for ((period, start, device_id), bucket) in inner.buckets.iter_mut() {
+            if !bucket.dirty {
+                continue;
+            }
+            rows.push(StatsRollup {
+                period: *period,
+                bucket: *start,
+                device_id: device_id.clone(),
+                messages: std::mem::take(&mut bucket.messages),
+                positions: std::mem::take(&mut bucket.positions),
+                unique_aircraft: bucket.aircraft.len() as i64,
+                max_range_km: bucket.max_range_km,
+                frames_decoded: std::mem::take(&mut bucket.frames_decoded),
+                crc_errors: std::mem::take(&mut bucket.crc_errors),
+                downlink_formats: std::mem::take(&mut bucket.downlink_formats),
+            });
+            bucket.dirty = false;
+        }
+        inner
+            .buckets
+            .retain(|(period, start, _), _| *start + period.duration() > now);
+        rows
+    }
+
+    /// Messages counted since startup
+    pub fn messages_total(&self) -> u64 {
+        self.inner.lock().map(|inner| inner.messages_total).unwrap_or(0)
+    }
+
+    /// Events received from a device per second, over the last minute
+    pub fn device_rate(&self, device_id: &str, now: DateTime<Utc>) -> f32 {
+        self.inner
+            .lock()
+            .map(|inner| inner.rate(device_id, DEVICE_RATE_WINDOW_SECS, now))
+            .unwrap_or(0.0)
+    }
+
+    /// Current message rate per device and in total
+    pub fn live(&self, now: DateTime<Utc>) -> LiveRates {
+        let Ok(inner) = self.inner.lock() else {
+            return LiveRates::default();
+        };
+        let max_age = chrono::Duration::seconds(LIVE_RATE_MAX_AGE_SECS);
+        let devices: BTreeMap<String, f32> = inner
+            .rates
+            .iter()
+            .filter(|(_, (_, at))| now - *at <= max_age)
+            .map(|(device, (rate, _))| (device.clone(), *rate))
+            .collect();
+        let windows = RATE_WINDOWS_SECS
+            .iter()
+            .map(|&seconds| RateWindow {
+                seconds,
+                messages_per_second: inner.rate("", seconds, now),
+                devices: inner
+                    .per_second
+                    .keys()
+                    .filter(|device| !device.is_empty())
+                    .map(|device| (device.clone(), inner.rate(device, seconds, now)))
+                    .filter(|(_, rate)| *rate > 0.0)
+                    .collect(),
+            })
+            .collect();
+        LiveRates {
+            messages_per_second: devices.values().sum(),
+            devices,
+            windows,
+        }
+    }
+}
+
+/// Receiver location (`RECEIVER_LAT`/`RECEIVER_LON`)
+#[derive(Debug, Clone, Copy, Serialize, ToSchema, SimpleObject)]
+pub struct Receiver {
+    pub lat: f64,
+    pub lon: f64,
+}
+
+impl From<(f64, f64)> for Receiver {
+    fn from((lat, lon): (f64, f64)) -> Self {
+        Self { lat, lon }
+    }
+}
+
+/// Live message rates
+#[derive(Debug, Default, Serialize, ToSchema, SimpleObject)]
+pub struct LiveRates {
+    /// Decoder rate reported by the capture services
+    pub messages_per_second: f32,
+    /// Reported rate per device
+    pub devices: BTreeMap<String, f32>,
+    /// Events received by the gateway over rolling windows
+    pub windows: Vec<RateWindow>,
+}
+
+/// Events received per second over the last `seconds`
+#[derive(Debug, Serialize, ToSchema, SimpleObject)]
+pub struct RateWindow {
+    pub seconds: i64,
+    pub messages_per_second: f32,
+    /// Rate per device (devices without events in the window are left out)
+    pub devices: BTreeMap<String, f32>,
+}
+
+/// Counters for one period, for one device or for all of them
+#[derive(Debug, Serialize, ToSchema, SimpleObject)]
+pub struct RollupSummary {
+    pub messages: i64,
+    /// Averaged over the elapsed part of the period
+    pub messages_per_second: f64,
+    pub positions: i64,
+    pub unique_aircraft: i64,
+    pub max_range_km: Option<f64>,
+    pub frames_decoded: i64,
+    pub crc_errors: i64,
+    pub crc_error_ratio: Option<f64>,
+    /// Decoded frames per downlink format (24 counts all Comm-D formats)
+    pub downlink_formats: BTreeMap<u32, i64>,
+}
+
+/// One hourly or daily bucket with a per-device breakdown
+#[derive(Debug, Serialize, ToSchema, SimpleObject)]
+pub struct StatsBucket {
+    /// Start of the period
+    pub bucket: DateTime<Utc>,
+    /// All devices together; absent if only per-device rows were stored
+    #[serde(flatten)]
+    pub total: Option<RollupSummary>,
+    pub devices: BTreeMap<String, RollupSummary>,
+}
+
+/// Add stored per-format counts, `(bucket, device_id, downlink_format,
+/// frames)`, to the rollup rows they belong to
+pub fn add_downlink_formats(
+    rows: &mut [StatsRollup],
+    counts: impl IntoIterator<Item = (DateTime<Utc>, String, u32, i64)>,
+) {
+    let index: HashMap<(DateTime<Utc>, String), usize> = rows
+        .iter()
+        .enumerate()
+        .map(|(i, row)| ((row.bucket, row.device_id.clone()), i))
+        .collect();
+    for (bucket, device_id, df, frames) in counts {
+        if let Some(&i) = index.get(&(bucket, device_id)) {
+            *rows[i].downlink_formats.entry(df).or_default() += frames;
+        }
+    }
+}
+
+/// Group rollup rows into one entry per bucket with a per-device breakdown
+pub fn summarize(rows: &[StatsRollup], now: DateTime<Utc>) -> Vec<StatsBucket> {
+    let mut buckets: BTreeMap<DateTime<Utc>, StatsBucket> = BTreeMap::new();
+    for row in rows {
+        let entry = buckets.entry(row.bucket).or_insert_with(|| StatsBucket {
+            bucket: row.bucket,
+            total: None,
+            devices: BTreeMap::new(),
+        });
+        let summary = rollup_summary(row, now);
+        if row.device_id.is_empty() {
+            entry.total = Some(summary);
+        } else {
+            entry.devices.insert(row.device_id.clone(), summary);
+        }
+    }
+    buckets.into_values().collect()
+}
+
+fn rollup_summary(row: &StatsRollup, now: DateTime<Utc>) -> RollupSummary {
+    // The current period has only run for part of its length
+    let elapsed = (now - row.bucket).min(row.period.duration()).num_seconds().max(1);
+    let attempts = row.frames_decoded + row.crc_errors;
+    RollupSummary {
+        messages: row.messages,
+        messages_per_second: row.messages as f64 / elapsed as f64,
+        positions: row.positions,
+        unique_aircraft: row.unique_aircraft,
+        max_range_km: (row.max_range_km > 0.0).then_some(row.max_range_km),
+        frames_decoded: row.frames_decoded,
+        crc_errors: row.crc_errors,
+        crc_error_ratio: (attempts > 0).then(|| row.crc_errors as f64 / attempts as f64),
+        downlink_formats: row.downlink_formats.clone(),
+    }
+}
+
+/// Periodically write rollups
+pub fn spawn_flusher(collector: Arc<StatsCollector>, db_writer: Arc<dyn Storage>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(FLUSH_INTERVAL_SECS));
+        loop {
+            interval.tick().await;
+            let rows = collector.take_rollups(Utc::now());
+            if rows.is_empty() {
+                continue;
+            }
+            if let Err(e) = db_writer.upsert_stats(&rows).await {
+                warn!("Failed to store statistics rollups: {}", e);
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adsb::DownlinkFormatCount;
+
+    fn at(h: u32, m: u32) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(&format!("2024-01-15T{:02}:{:02}:00Z", h, m))
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    fn event(icao: &str, device: &str, lat: f64) -> AircraftEvent {
+        AircraftEvent {
+            icao: icao.into(),
+            device_id: device.into(),
+            latitude: Some(lat),
+            longitude: Some(126.0),
+            ..Default::default()
+        }
+    }
+
+    fn find<'a>(rows: &'a [StatsRollup], period: Period, device: &str) -> &'a StatsRollup {
+        rows.iter()
+            .find(|r| r.period == period && r.device_id == device)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_event_rollups() {
+        let stats = StatsCollector::new(Some((37.0, 126.0)));
+        stats.record_event(&event("71BE11", "sdr0", 37.5), at(10, 0));
+        stats.record_event(&event("71BE11", "sdr0", 37.6), at(10, 1));
+        // No position
+        let identification = AircraftEvent {
+            latitude: None,
+            longitude: None,
+            ..event("71BE12", "sdr1", 0.0)
+        };
+        stats.record_event(&identification, at(10, 2));
+
+        let rows = stats.take_rollups(at(10, 5));
+        // hour + day, for the total and two devices
+        assert_eq!(rows.len(), 6);
+        let total = find(&rows, Period::Hour, "");
+        assert_eq!(total.bucket, at(10, 0));
+        assert_eq!((total.messages, total.positions, total.unique_aircraft), (3, 2, 2));
+        assert!((total.max_range_km - 66.7).abs() < 0.5);
+        assert_eq!(find(&rows, Period::Day, "sdr0").unique_aircraft, 1);
+
+        // Flushed counters are deltas; distinct aircraft carry over
+        stats.record_event(&event("71BE11", "sdr0", 37.5), at(10, 6));
+        let rows = stats.take_rollups(at(10, 7));
+        let total = find(&rows, Period::Hour, "");
+        assert_eq!((total.messages, total.unique_aircraft), (1, 2));
+        assert_eq!(rows.len(), 4);
+
+        // Finished hours are dropped, the day continues
+        stats.record_event(&event("71BE13", "sdr0", 37.5), at(11, 0));
+        let rows = stats.take_rollups(at(11, 1));
+        assert_eq!(find(&rows, Period::Hour, "").unique_aircraft, 1);
+        assert_eq!(find(&rows, Period::Day, "").unique_aircraft, 3);
+    }
+
+    #[test]
+    fn test_signal_counters() {
+        let stats = StatsCollector::new(None);
+        let metrics = |frames: u64, crc: u64| SignalMetrics {
+            device_id: "sdr0".into(),
+            frames_decoded: frames,
+            crc_errors: crc,
+            msg_rate: 12.5,
+            downlink_formats: [(11, frames / 2), (17, frames - frames / 2)]
+                .into_iter()
+                .map(|(downlink_format, frames)| DownlinkFormatCount {
+                    downlink_format,
+                    frames,
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        };
+        stats.record_signal(&metrics(1000, 100), at(10, 0));
+        assert!(stats.take_rollups(at(10, 0)).is_empty());
+
+        stats.record_signal(&metrics(1090, 110), at(10, 1));
+        // Capture restarted
+        stats.record_signal(&metrics(5, 1), at(10, 2));
+        let rows = stats.take_rollups(at(10, 3));
+        let total = find(&rows, Period::Hour, "");
+        assert_eq!((total.frames_decoded, total.crc_errors), (95, 11));
+        assert_eq!(total.downlink_formats, BTreeMap::from([(11, 47), (17, 48)]));
+
+        let live = stats.live(at(10, 2));
+        assert_eq!(live.messages_per_second, 12.5);
+        assert!(stats.live(at(11, 0)).devices.is_empty());
+    }
+
+    #[test]
+    fn test_received_rates() {
+        let stats = StatsCollector::new(None);
+        let start = at(10, 0);
+        for second in 0..120 {
+            let now = start + chrono::Duration::seconds(second);
+            stats.record_event(&event("71BE11", "sdr0", 37.5), now);
+            if second % 2 == 0 {
+                stats.record_event(&event("71BE12", "sdr1", 37.5), now);
+            }
+        }
+
+        let now = start + chrono::Duration::seconds(119);
+        assert_eq!(stats.device_rate("sdr0", now), 1.0);
+        assert_eq!(stats.device_rate("sdr1", now), 0.5);
+        assert_eq!(stats.device_rate("sdr2", now), 0.0);
+
+        let live = stats.live(now);
+        let windows: Vec<i64> = live.windows.iter().map(|w| w.seconds).collect();
+        assert_eq!(windows, RATE_WINDOWS_SECS);
+        assert_eq!(live.windows[0].messages_per_second, 1.5);
+        // Only two minutes of events: the five-minute rate isn't diluted
+        assert_eq!(live.windows[2].devices["sdr0"], 1.0);
+
+        // Silent devices drop out of the windows
+        let later = stats.live(now + chrono::Duration::seconds(30));
+        assert!(later.windows[0].devices.is_empty());
+        assert_eq!(later.windows[1].devices["sdr1"], 15.0 / 60.0);
+    }
+
+    #[test]
+    fn test_summarize() {
+        let row = |device: &str, messages: i64| StatsRollup {
+            period: Period::Hour,
+            bucket: at(10, 0),
+            device_id: device.into(),
+            messages,
+            positions: 0,
+            unique_aircraft: 1,
+            max_range_km: 0.0,
+            frames_decoded: 90,
+            crc_errors: 10,
+            downlink_formats: BTreeMap::from([(11, 30), (17, 60)]),
+        };
+        let summary = summarize(&[row("", 3600), row("sdr0", 1800)], at(12, 0));
+        let summary = serde_json::to_value(summary).unwrap();
+        assert_eq!(summary.as_array().unwrap().len(), 1);
+        assert_eq!(summary[0]["messages_per_second"], 1.0);
+        assert_eq!(summary[0]["crc_error_ratio"], 0.1);
+        assert_eq!(summary[0]["max_range_km"], serde_json::Value::Null);
+        assert_eq!(summary[0]["devices"]["sdr0"]["messages"], 1800);
+        assert_eq!(summary[0]["downlink_formats"]["17"], 60);
+    }
+
+    #[test]
+    fn test_stats_range() {
+        // The last 3 hours and 2 days, counting the current ones
+        let recent = StatsRange::recent(3, 2, at(10, 30));
+        assert_eq!(recent.hourly_from, at(8, 0));
+        assert_eq!(recent.daily_from, at(0, 0) - chrono::Duration::days(1));
+        assert!(recent.includes(at(10, 0)));
+
+        // Buckets overlapping 08:30+09:00 to 11:15Z
+        let from = DateTime::parse_from_rfc3339("2024-01-15T08:30:00+09:00").unwrap();
+        let range = StatsRange::between(from.with_timezone(&Utc), at(11, 15));
+        assert_eq!(range.hourly_from, at(0, 0) - chrono::Duration::minutes(60));
+        assert!(range.includes(at(11, 0)));
+        assert!(!range.includes(at(12, 0)));
+    }
+}
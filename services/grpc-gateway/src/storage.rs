@@ -0,0 +1,212 @@
+//! Storage abstraction over the database backends
+//!
+//! The gateway talks to the database only through [`Storage`], so the same
+//! gRPC/REST/WebSocket code runs against TimescaleDB, a local SQLite file,
+//! or ClickHouse.
+
+use crate::adsb::{AircraftEvent, DeviceStatus, RawFrame};
+use crate::admin::PositionPurge;
+use crate::alerts::{NewWatchRule, WatchRule};
+use crate::clickhouse_writer::ClickHouseWriter;
+use crate::config::{Config, DbBackend};
+use crate::coverage::{CoverageCell, CoverageQuery};
+use crate::db_health::DbHealth;
+use crate::db_writer::DbWriter;
+use crate::emergencies::EmergencyRecord;
+use crate::flights::FlightRecord;
+use crate::geofences::{GeofenceDef, GeofenceEvent, NewGeofence};
+use crate::history::{HistoryQuery, PositionPoint};
+use crate::sqlite_writer::SqliteWriter;
+use crate::stats::{Period, StatsRollup};
+use crate::uptime::ReceiverSession;
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use serde_json::Value as JsonValue;
+use std::sync::Arc;
+use tracing::{error, info};
+
+/// Persistence operations used by the gateway
+#[tonic::async_trait]
+pub trait Storage: Send + Sync {
+    /// Short backend name for logs and health output
+    fn backend_name(&self) -> &'static str;
+
+    /// Insert aircraft position (when the event has one) and update the
+    /// aircraft's `aircraft_info` row, both at `time` and atomically; `messages` is the
+    /// number of events the write stands for, added to the aircraft's
+    /// message count
+    async fn insert_position(&self, event: &AircraftEvent, time: DateTime<Utc>, messages: u32)
+        -> Result<()>;
+
+    /// Insert a batch of raw frames into the archive
+    async fn insert_raw_frames(&self, frames: &[RawFrame]) -> Result<()>;
+
+    /// Update SDR device status, with the message rate the gateway measured
+    async fn update_sdr_status(&self, status: &DeviceStatus, messages_per_second: f32)
+        -> Result<()>;
+
+    /// Get one aircraft's latest state with first/last seen and message count
+    async fn get_aircraft(&self, icao: &str) -> Result<Option<JsonValue>>;
+
+    /// Get aircraft position trail from `from` (inclusive) to `to`
+    /// (exclusive; `None` for everything since `from`). With `bucket_secs`,
+    /// only the latest position of each time bucket is returned, with the
+    /// number of positions in the bucket as `positions`. `devices` limits
+    /// the positions to those receivers before bucketing
+    async fn get_aircraft_trail(
+        &self,
+        icao: &str,
+        devices: Option<&[String]>,
+        from: DateTime<Utc>,
+        to: Option<DateTime<Utc>>,
+        bucket_secs: Option<i64>,
+    ) -> Result<Vec<JsonValue>>;
+
+    /// Get stored positions in `(time, icao)` order, resuming after the query's cursor
+    async fn get_position_history(&self, query: &HistoryQuery) -> Result<Vec<PositionPoint>>;
+
+    /// Count positions per `cell_deg` grid cell (densest cells first, at most
+    /// [`crate::coverage::MAX_CELLS`])
+    async fn get_coverage(&self, query: &CoverageQuery) -> Result<Vec<CoverageCell>>;
+
+    /// Get every device's current status, ordered by device ID
+    async fn get_sdr_status(&self) -> Result<Vec<JsonValue>>;
+
+    /// Get a device's heartbeats since `since`, most recent first
+    async fn get_sdr_history(
+        &self,
+        device_id: &str,
+        since: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<Vec<JsonValue>>;
+
+    /// Insert or update a flight session (keyed by ICAO + first_seen)
+    async fn upsert_flight(&self, flight: &FlightRecord) -> Result<()>;
+
+    /// Get recent flight sessions, optionally for one aircraft
+    async fn get_flights(&self, icao: Option<&str>, devices: Option<&[String]>, limit: i64) -> Result<Vec<JsonValue>>;
+
+    /// Insert or update an emergency occurrence (keyed by ICAO + started_at)
+    async fn upsert_emergency(&self, record: &EmergencyRecord) -> Result<()>;
+
+    /// Get emergency occurrences, most recent first
+    async fn get_emergencies(&self, active_only: bool, devices: Option<&[String]>, limit: i64) -> Result<Vec<JsonValue>>;
+
+    /// Insert or update a receiver session (keyed by device ID + started_at)
+    async fn upsert_receiver_session(&self, session: &ReceiverSession) -> Result<()>;
+
+    /// Get receiver sessions that lasted until `since` or later, oldest first
+    async fn get_receiver_sessions(&self, since: DateTime<Utc>) -> Result<Vec<ReceiverSession>>;
+
+    /// Add flushed statistics to the stored rollups: counters are summed,
+    /// `unique_aircraft` and `max_range_km` keep the larger value
+    async fn upsert_stats(&self, rows: &[StatsRollup]) -> Result<()>;
+
+    /// Get rollups for buckets starting at or after `since`, oldest first
+    async fn get_stats(&self, period: Period, since: DateTime<Utc>) -> Result<Vec<StatsRollup>>;
+
+    /// Delete stored positions matching `purge`; returns the rows deleted
+    async fn delete_positions(&self, _purge: &PositionPurge) -> Result<u64> {
+        Err(unsupported(self.backend_name(), "position purges"))
+    }
+
+    /// Move every stored row of device `from` to `to`, merging with what `to`
+    /// already has; returns the rows changed
+    async fn rename_device(&self, _from: &str, _to: &str) -> Result<u64> {
+        Err(unsupported(self.backend_name(), "device renames"))
+    }
+
+    /// List watchlist rules
+    async fn get_watchlist(&self) -> Result<Vec<WatchRule>> {
+        Err(unsupported(self.backend_name(), "watchlists"))
+    }
+
+    /// Create a watchlist rule
+    async fn insert_watchlist(&self, _rule: &NewWatchRule) -> Result<WatchRule> {
+        Err(unsupported(self.backend_name(), "watchlists"))
+    }
+
+    /// Delete a watchlist rule; returns false if it didn't exist
+    async fn delete_watchlist(&self, _id: i64) -> Result<bool> {
+        Err(unsupported(self.backend_name(), "watchlists"))
+    }
+
+    /// List geofences
+    async fn get_geofences(&self) -> Result<Vec<GeofenceDef>> {
+        Err(unsupported(self.backend_name(), "geofences"))
+    }
+
+    /// Create a geofence
+    async fn insert_geofence(&self, _fence: &NewGeofence) -> Result<GeofenceDef> {
+        Err(unsupported(self.backend_name(), "geofences"))
+    }
+
+    /// Delete a geofence (and its logged events); returns false if it didn't exist
+    async fn delete_geofence(&self, _id: i64) -> Result<bool> {
+        Err(unsupported(self.backend_name(), "geofences"))
+    }
+
+    /// Log a geofence entry/exit event
+    async fn insert_geofence_event(&self, _event: &GeofenceEvent) -> Result<()> {
+        Err(unsupported(self.backend_name(), "geofences"))
+    }
+
+    /// Get logged geofence events, most recent first
+    async fn get_geofence_events(&self, _geofence_id: Option<i64>, _limit: i64) -> Result<Vec<JsonValue>> {
+        Err(unsupported(self.backend_name(), "geofences"))
+    }
+
+    /// Table sizes and ages, and connection pool use
+    async fn db_health(&self) -> Result<DbHealth>;
+
+    /// Get logged squawk changes, optionally for one aircraft, most recent first
+    async fn get_squawk_changes(
+        &self,
+        _icao: Option<&str>,
+        _devices: Option<&[String]>,
+        _limit: i64,
+    ) -> Result<Vec<JsonValue>> {
+        Err(unsupported(self.backend_name(), "squawk changes"))
+    }
+}
+
+/// Error for features a backend doesn't implement
+pub fn unsupported(backend: &str, feature: &str) -> anyhow::Error {
+    anyhow!("{} are not supported by the {} backend", feature, backend)
+}
+
+/// Connect to the configured backend, falling back to a no-op writer
+pub async fn connect(config: &Config) -> Arc<dyn Storage> {
+    match config.db_backend {
+        DbBackend::Postgres => match DbWriter::new(&config.db_url()).await {
+            Ok(db) => {
+                info!("Connected to database");
+                Arc::new(db)
+            }
+            Err(e) => {
+                error!("Failed to connect to database: {}. Continuing without DB.", e);
+                Arc::new(DbWriter::new_dummy())
+            }
+        },
+        DbBackend::Sqlite => match SqliteWriter::open(&config.sqlite_path).await {
+            Ok(db) => {
+                info!("Opened SQLite database: {}", config.sqlite_path.display());
+                Arc::new(db)
+            }
+            Err(e) => {
+                error!("Failed to open SQLite database: {}. Continuing without DB.", e);
+                Arc::new(DbWriter::new_dummy())
+            }
+        },
+        DbBackend::ClickHouse => match ClickHouseWriter::new(config).await {
+            Ok(db) => {
+                info!("Connected to ClickHouse: {}", config.clickhouse_url);
+                Arc::new(db)
+            }
+            Err(e) => {
+                error!("Failed to connect to ClickHouse: {}. Continuing without DB.", e);
+                Arc::new(DbWriter::new_dummy())
+            }
+        },
+    }
+}
@@ -0,0 +1,354 @@
+//! tar1090-compatible data files
+//!
+//! Serves `/data/aircraft.json`, `/data/receiver.json` and
+//! `/data/history_N.json` in the format readsb/dump1090-fa write, so the
+//! tar1090 (or dump1090 SkyAware) frontend can be dropped into the static
+//! directory unmodified. `aircraft.json` is built from the current aircraft
+//! on each request; a snapshot of it is kept every [`HISTORY_INTERVAL_SECS`]
+//! for the last hour, which the frontend loads on startup to draw trails.
+//! The files sit behind the API's auth middleware. `aircraft.json` follows
+//! the caller's [`Scope`]; snapshots hold every receiver's aircraft, so only
+//! unscoped callers get them, and others are told there is no history.
+
+use crate::adsb::Emergency;
+use crate::api::Aircraft;
+use crate::filters::AircraftFilter;
+use crate::tenants::Scope;
+use crate::AppState;
+use anyhow::Result;
+use axum::{
+    extract::{Path, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::warn;
+
+/// Seconds between history snapshots (dump1090's default)
+pub const HISTORY_INTERVAL_SECS: u64 = 30;
+
+/// Snapshots kept: one hour
+const HISTORY_CAPACITY: usize = 120;
+
+/// How often the frontend should poll `aircraft.json`, in milliseconds
+const REFRESH_MS: u32 = 1000;
+
+/// readsb `dbFlags` bit for military aircraft
+const DB_FLAG_MILITARY: u8 = 1;
+
+/// `aircraft.json`
+#[derive(Debug, Serialize)]
+pub struct AircraftFile {
+    /// Unix time in seconds
+    now: f64,
+    /// Messages received since the gateway started
+    messages: u64,
+    aircraft: Vec<ReadsbAircraft>,
+}
+
+/// One aircraft in readsb's field names and units
+#[derive(Debug, Serialize)]
+struct ReadsbAircraft {
+    hex: String,
+    #[serde(rename = "type")]
+    kind: &'static str,
+    /// Callsign padded to 8 characters
+    #[serde(skip_serializing_if = "Option::is_none")]
+    flight: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    alt_baro: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    gs: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    track: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mag_heading: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    baro_rate: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    geom_rate: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    squawk: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    emergency: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    lat: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    lon: Option<f64>,
+    /// Seconds since the last message
+    seen: f64,
+    /// Seconds since the last position
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seen_pos: Option<f64>,
+    messages: i64,
+    /// Registration
+    #[serde(skip_serializing_if = "Option::is_none")]
+    r: Option<String>,
+    /// ICAO type designator
+    #[serde(skip_serializing_if = "Option::is_none")]
+    t: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    desc: Option<String>,
+    #[serde(rename = "ownOp", skip_serializing_if = "Option::is_none")]
+    own_op: Option<String>,
+    #[serde(rename = "dbFlags", skip_serializing_if = "Option::is_none")]
+    db_flags: Option<u8>,
+}
+
+/// `receiver.json`
+#[derive(Debug, Serialize)]
+struct ReceiverFile {
+    version: String,
+    refresh: u32,
+    /// Number of `history_N.json` files
+    history: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    lat: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    lon: Option<f64>,
+}
+
+/// readsb's name for an emergency
+fn emergency_name(emergency: Emergency) -> Option<&'static str> {
+    match emergency {
+        Emergency::None => None,
+        Emergency::General => Some("general"),
+        Emergency::RadioFailure => Some("nordo"),
+        Emergency::Hijack => Some("unlawful"),
+    }
+}
+
+/// Convert the current aircraft list
+pub fn aircraft_file(
+    aircraft: Vec<Aircraft>,
+    messages: u64,
+    now: DateTime<Utc>,
+    emergency: impl Fn(&str) -> Option<Emergency>,
+) -> AircraftFile {
+    let age = |seen: &Option<String>| {
+        let seen = DateTime::parse_from_rfc3339(seen.as_deref()?).ok()?;
+        Some(((now - seen.with_timezone(&Utc)).num_milliseconds().max(0) as f64) / 1000.0)
+    };
+    let aircraft = aircraft
+        .into_iter()
+        .map(|a| {
+            let seen = age(&a.seen).unwrap_or(0.0);
+            let has_position = a.lat.is_some() && a.lon.is_some();
+            ReadsbAircraft {
+                emergency: emergency(&a.icao).and_then(emergency_name),
+                db_flags: a.enrichment.military.then_some(DB_FLAG_MILITARY),
+                hex: a.icao.to_ascii_lowercase(),
+                kind: "adsb_icao",
+                flight: a.callsign.filter(|c| !c.is_empty()).map(|c| format!("{:<8}", c)),
+                alt_baro: a.altitude,
+                gs: a.speed,
+                track: a.track,
+                mag_heading: a.magnetic_heading,
+                baro_rate: a.baro_rate,
+                geom_rate: a.geom_rate,
+                squawk: a.squawk,
+                lat: a.lat,
+                lon: a.lon,
+                seen,
+                seen_pos: has_position.then_some(seen),
+                messages: a.messages.unwrap_or(0),
+                r: a.enrichment.registration,
+                t: a.enrichment.aircraft_type,
+                desc: a.enrichment.model,
+                own_op: a.enrichment.operator,
+            }
+        })
+        .collect();
+    AircraftFile {
+        now: now.timestamp_millis() as f64 / 1000.0,
+        messages,
+        aircraft,
+    }
+}
+
+/// Recent `aircraft.json` snapshots, oldest first
+pub struct SnapshotHistory {
+    snapshots: Mutex<VecDeque<Arc<String>>>,
+}
+
+impl SnapshotHistory {
+    pub fn new() -> Self {
+        Self {
+            snapshots: Mutex::new(VecDeque::with_capacity(HISTORY_CAPACITY)),
+        }
+    }
+
+    fn push(&self, snapshot: String) {
+        if let Ok(mut snapshots) = self.snapshots.lock() {
+            if snapshots.len() == HISTORY_CAPACITY {
+                snapshots.pop_front();
+            }
+            snapshots.push_back(Arc::new(snapshot));
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.snapshots.lock().map(|s| s.len()).unwrap_or(0)
+    }
+
+    fn get(&self, index: usize) -> Option<Arc<String>> {
+        self.snapshots.lock().ok()?.get(index).cloned()
+    }
+}
+
+/// Build `aircraft.json` from the current state in `scope`
+async fn current(state: &AppState, scope: &Scope) -> Result<AircraftFile> {
+    let aircraft = state.current_aircraft(&AircraftFilter::default(), scope).await?;
+    Ok(aircraft_file(
+        aircraft,
+        state.stats.messages_total(),
+        Utc::now(),
+        |icao| state.emergencies.active(icao),
+    ))
+}
+
+/// Periodically record a history snapshot
+pub fn spawn_recorder(state: Arc<AppState>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(HISTORY_INTERVAL_SECS));
+        loop {
+            interval.tick().await;
+            match current(&state, &Scope::All).await {
+                Ok(file) => match serde_json::to_string(&file) {
+                    Ok(json) => state.tar1090.push(json),
+                    Err(e) => warn!("Failed to encode tar1090 history snapshot: {}", e),
+                },
+                Err(e) => warn!("Failed to build tar1090 history snapshot: {}", e),
+            }
+        }
+    });
+}
+
+fn json_response(body: String) -> Response {
+    (
+        [
+            (header::CONTENT_TYPE, "application/json"),
+            (header::CACHE_CONTROL, "no-cache"),
+        ],
+        body,
+    )
+        .into_response()
+}
+
+/// `GET /data/:file`
+pub async fn data_file(
+    State(state): State<Arc<AppState>>,
+    scope: Scope,
+    Path(file): Path<String>,
+) -> Response {
+    // Snapshots and the receiver location aren't split by receiver
+    let unscoped = scope == Scope::All;
+    match file.as_str() {
+        "aircraft.json" => match current(&state, &scope).await {
+            Ok(aircraft) => json_response(serde_json::to_string(&aircraft).unwrap_or_default()),
+            Err(e) => {
+                warn!("Failed to build aircraft.json: {}", e);
+                StatusCode::SERVICE_UNAVAILABLE.into_response()
+            }
+        },
+        "receiver.json" => {
+            let receiver = state.stats.receiver();
+            let file = ReceiverFile {
+                version: format!("grpc-gateway {}", env!("CARGO_PKG_VERSION")),
+                refresh: REFRESH_MS,
+                history: if unscoped { state.tar1090.len() } else { 0 },
+                lat: receiver.filter(|_| unscoped).map(|(lat, _)| lat),
+                lon: receiver.filter(|_| unscoped).map(|(_, lon)| lon),
+            };
+            json_response(serde_json::to_string(&file).unwrap_or_default())
+        }
+        _ => {
+            let snapshot = file
+                .strip_prefix("history_")
+                .and_then(|rest| rest.strip_suffix(".json"))
+                .and_then(|n| n.parse::<usize>().ok())
+                .filter(|_| unscoped)
+                .and_then(|n| state.tar1090.get(n));
+            match snapshot {
+                Some(snapshot) => json_response(snapshot.as_str().to_string()),
+                None => StatusCode::NOT_FOUND.into_response(),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::Enrichment;
+
+    fn aircraft(icao: &str, lat: Option<f64>) -> Aircraft {
+        Aircraft {
+            icao: icao.into(),
+            callsign: Some("KAL123".into()),
+            lat,
+            lon: lat.map(|_| 126.44),
+            altitude: Some(35000),
+            speed: Some(450.0),
+            track: Some(90.0),
+            magnetic_heading: None,
+            vrate: None,
+            baro_rate: None,
+            geom_rate: None,
+            squawk: Some("7600".into()),
+            seen: Some("2024-01-15T10:00:00Z".into()),
+            messages: Some(42),
+            enrichment: Enrichment {
+                registration: Some("HL7611".into()),
+                ..Default::default()
+            },
+        }
+    }
+
+    #[test]
+    fn test_aircraft_file() {
+        let now = DateTime::parse_from_rfc3339("2024-01-15T10:00:02.5Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let mut military = aircraft("AE1234", None);
+        military.enrichment.military = true;
+        let file = aircraft_file(
+            vec![aircraft("71BE11", Some(37.46)), military],
+            1000,
+            now,
+            |icao| (icao == "71BE11").then_some(Emergency::RadioFailure),
+        );
+        let json = serde_json::to_value(&file).unwrap();
+        assert_eq!(json["now"], 1705312802.5);
+        assert_eq!(json["messages"], 1000);
+
+        let first = &json["aircraft"][0];
+        assert_eq!(first["hex"], "71be11");
+        assert_eq!(first["flight"], "KAL123  ");
+        assert_eq!(first["alt_baro"], 35000);
+        assert_eq!(first["seen"], 2.5);
+        assert_eq!(first["seen_pos"], 2.5);
+        assert_eq!(first["emergency"], "nordo");
+        assert_eq!(first["r"], "HL7611");
+        assert!(first.get("baro_rate").is_none() && first.get("dbFlags").is_none());
+
+        let second = &json["aircraft"][1];
+        assert!(second.get("lat").is_none() && second.get("seen_pos").is_none());
+        assert_eq!(second["dbFlags"], 1);
+    }
+
+    #[test]
+    fn test_history_ring() {
+        let history = SnapshotHistory::new();
+        for i in 0..HISTORY_CAPACITY + 2 {
+            history.push(i.to_string());
+        }
+        assert_eq!(history.len(), HISTORY_CAPACITY);
+        assert_eq!(history.get(0).unwrap().as_str(), "2");
+        assert!(history.get(HISTORY_CAPACITY).is_none());
+    }
+}
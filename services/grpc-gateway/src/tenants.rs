@@ -0,0 +1,198 @@
+//! Tenant scoping for hosted gateways
+//!
+//! One gateway can serve several independent receiver owners. `TENANTS`
+//! is a JSON array naming each tenant and the device IDs of its receivers,
+//! e.g. `[{"name": "acme", "devices": ["acme-rx1", "acme-rx2"]}]`, and an
+//! `API_KEYS` entry or JWT with a `tenant` sees only what those receivers
+//! heard: REST rows, position history and WebSocket messages are limited to
+//! the tenant's device IDs, and receiver endpoints for other devices answer
+//! 404. The tar1090 `aircraft.json` is limited the same way; its history
+//! snapshots are not split by receiver and answer 404. WebSocket and
+//! `/api/stream` clients connecting with `?api_key=` get only messages from
+//! their receivers, plus aircraft removals. Endpoints that can't be split by
+//! receiver (admin, GraphQL, stats, coverage, recordings, watchlist and
+//! geofence management) are refused to tenant credentials. Credentials
+//! without a tenant see everything, as before.
+
+use axum::{async_trait, extract::FromRequestParts, http::request::Parts};
+use crate::pubsub::MessageFields;
+use serde::Deserialize;
+use serde_json::Value as JsonValue;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::convert::Infallible;
+use std::sync::Arc;
+
+/// An entry in `TENANTS`
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+pub struct Tenant {
+    pub name: String,
+    /// Device IDs of the tenant's receivers
+    pub devices: HashSet<String>,
+}
+
+/// Configured tenants by name
+#[derive(Debug, Default)]
+pub struct Tenants {
+    by_name: HashMap<String, Arc<Tenant>>,
+}
+
+impl Tenants {
+    pub fn new(tenants: Vec<Tenant>) -> Self {
+        Self {
+            by_name: tenants.into_iter().map(|t| (t.name.clone(), Arc::new(t))).collect(),
+        }
+    }
+
+    /// Scope of a credential naming `tenant`; `None` for a tenant that
+    /// isn't configured, which must not fall back to seeing everything
+    pub fn scope(&self, tenant: Option<&str>) -> Option<Scope> {
+        match tenant {
+            None => Some(Scope::All),
+            Some(name) => self.by_name.get(name).cloned().map(Scope::Tenant),
+        }
+    }
+}
+
+/// What a caller may see
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum Scope {
+    /// Every receiver
+    #[default]
+    All,
+    Tenant(Arc<Tenant>),
+    /// No receiver; a caller without credentials in public viewer mode,
+    /// served from [`crate::public`]
+    Public,
+}
+
+impl Scope {
+    pub fn is_tenant(&self) -> bool {
+        matches!(self, Self::Tenant(_))
+    }
+
+    pub fn allows_device(&self, device_id: &str) -> bool {
+        match self {
+            Self::All => true,
+            Self::Tenant(tenant) => tenant.devices.contains(device_id),
+            Self::Public => false,
+        }
+    }
+
+    /// Whether a row or message belongs to the scope, by its `device_id`;
+    /// tenants don't see rows without one
+    pub fn allows(&self, value: &JsonValue) -> bool {
+        match self {
+            Self::All => true,
+            Self::Tenant(_) => value
+                .get("device_id")
+                .and_then(JsonValue::as_str)
+                .is_some_and(|device_id| self.allows_device(device_id)),
+            Self::Public => false,
+        }
+    }
+
+    /// Whether a live message reaches the scope: those from its receivers,
+    /// and removals, which don't say who heard the aircraft
+    pub fn allows_message(&self, fields: &MessageFields) -> bool {
+        match (self, &fields.device_id) {
+            (Self::All, _) => true,
+            // Public clients read the public view's own queue
+            (Self::Public, _) => true,
+            (_, Some(device_id)) => self.allows_device(device_id),
+            (_, None) => fields.kind == "aircraft_removed",
+        }
+    }
+
+    /// Keep the rows the scope allows
+    pub fn retain(&self, rows: &mut Vec<JsonValue>) {
+        if *self != Self::All {
+            rows.retain(|row| self.allows(row));
+        }
+    }
+
+    /// Keeps cached responses of different scopes apart
+    pub fn cache_key(&self) -> Cow<'_, str> {
+        match self {
+            Self::All => Cow::Borrowed(""),
+            Self::Tenant(tenant) => Cow::Owned(format!("tenant:{}", tenant.name)),
+            Self::Public => Cow::Borrowed("public"),
+        }
+    }
+
+    /// Device IDs to limit a database query to, sorted; `None` for all
+    pub fn devices(&self) -> Option<Vec<String>> {
+        match self {
+            Self::All => None,
+            Self::Public => Some(Vec::new()),
+            Self::Tenant(tenant) => {
+                let mut devices: Vec<String> = tenant.devices.iter().cloned().collect();
+                devices.sort();
+                Some(devices)
+            }
+        }
+    }
+}
+
+/// The scope the auth middleware resolved, or everything when the API is open
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Scope {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(parts.extensions.get::<Scope>().cloned().unwrap_or_default())
+    }
+}
+
+/// Whether tenant credentials may use an API path
+pub fn allows_path(path: &str) -> bool {
+    const PREFIXES: &[&str] = &[
+        "/api/aircraft",
+        "/api/history/",
+        "/api/export/",
+        "/api/flights",
+        "/api/emergencies",
+        "/api/squawk-changes",
+        "/api/receivers/",
+        "/api/sdr/",
+        "/api/signal/",
+        "/api/ingest/",
+        "/data/",
+    ];
+    PREFIXES.iter().any(|prefix| path.starts_with(prefix))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tenant_scope() {
+        let tenants = Tenants::new(
+            serde_json::from_str(r#"[{"name": "acme", "devices": ["acme-rx1", "acme-rx2"]}]"#).unwrap(),
+        );
+        assert_eq!(tenants.scope(None), Some(Scope::All));
+        assert_eq!(tenants.scope(Some("globex")), None);
+
+        let scope = tenants.scope(Some("acme")).unwrap();
+        assert!(scope.allows_device("acme-rx1") && !scope.allows_device("globex-rx1"));
+        let mut rows = vec![
+            serde_json::json!({"icao": "71BE11", "device_id": "acme-rx2"}),
+            serde_json::json!({"icao": "71BE12", "device_id": "globex-rx1"}),
+            serde_json::json!({"icao": "71BE13"}),
+        ];
+        scope.retain(&mut rows);
+        assert_eq!(rows.len(), 1);
+        assert_eq!(scope.devices(), Some(vec!["acme-rx1".to_string(), "acme-rx2".to_string()]));
+        assert!(Scope::All.allows(&serde_json::json!({"icao": "71BE13"})));
+        let message = |json: &str| MessageFields::read(&serde_json::from_str(json).unwrap());
+        assert!(scope.allows_message(&message(r#"{"type": "signal", "device_id": "acme-rx1"}"#)));
+        assert!(!scope.allows_message(&message(r#"{"type": "signal", "device_id": "globex-rx1"}"#)));
+        assert!(scope.allows_message(&message(r#"{"type": "aircraft_removed", "icao": "71BE11"}"#)));
+        assert!(!scope.allows_message(&message(r#"{"type": "geofence", "icao": "71BE11"}"#)));
+
+        assert!(allows_path("/api/aircraft/71BE11/trail") && allows_path("/api/sdr/status"));
+        assert!(allows_path("/data/aircraft.json"));
+        assert!(!allows_path("/api/admin/positions") && !allows_path("/api/graphql") && !allows_path("/api/stats"));
+    }
+}
@@ -0,0 +1,298 @@
+//! Map tile proxy
+//!
+//! The web UI's base map normally comes straight from a public tile server,
+//! which a receiver on an isolated network can't reach, and whose usage
+//! policy every viewer's browser counts against. With `TILE_UPSTREAM_URL`
+//! and/or `TILE_CACHE_DIR` set, `/tiles/{z}/{x}/{y}.png` serves tiles from a
+//! directory, fetching missing and expired ones from the upstream server at
+//! most `TILE_RATE_PER_MINUTE` times a minute and keeping them. When the
+//! upstream is unreachable or the rate is used up, an expired copy is served
+//! rather than nothing; without an upstream the directory is all there is,
+//! e.g. tiles seeded for offline use. `/tiles/tiles.json` describes the
+//! layer as TileJSON, and the UI uses the proxy whenever it answers.
+
+use crate::AppState;
+use axum::{
+    body::Bytes,
+    extract::{Path, State},
+    http::{header, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+use tracing::{debug, warn};
+
+/// Deepest zoom level served
+const MAX_ZOOM: u32 = 19;
+
+/// Give up on an upstream tile after this long
+const FETCH_TIMEOUT_SECS: u64 = 10;
+
+/// How long browsers may keep a tile
+const BROWSER_CACHE_SECS: u64 = 86_400;
+
+/// Tile proxy settings
+#[derive(Debug, Clone)]
+pub struct TileConfig {
+    /// Upstream URL template with `{z}`, `{x}` and `{y}` (and optionally
+    /// `{s}`, filled with `a`, and `{r}`, left empty); tiles are only read
+    /// from disk without one
+    pub upstream: Option<String>,
+    pub cache_dir: PathBuf,
+    /// Upstream requests allowed per minute
+    pub rate_per_minute: u32,
+    /// Age after which a stored tile is fetched again
+    pub max_age: Duration,
+    pub attribution: String,
+}
+
+/// Token bucket for upstream requests
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// Disk-cached, rate-limited tile source
+pub struct TileProxy {
+    config: TileConfig,
+    client: reqwest::Client,
+    bucket: Mutex<Bucket>,
+}
+
+/// Why a tile can't be served
+#[derive(Debug, PartialEq)]
+enum TileError {
+    Invalid,
+    NotFound,
+    RateLimited,
+    Upstream,
+}
+
+impl TileProxy {
+    pub fn new(config: TileConfig) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(FETCH_TIMEOUT_SECS))
+            .user_agent(concat!("adsb-grpc-gateway/", env!("CARGO_PKG_VERSION")))
+            .build()
+            .unwrap_or_default();
+        Self {
+            bucket: Mutex::new(Bucket {
+                tokens: config.rate_per_minute as f64,
+                updated: Instant::now(),
+            }),
+            config,
+            client,
+        }
+    }
+
+    /// Image extension of the upstream's tiles, `png` when it doesn't say
+    fn extension(&self) -> &str {
+        self.config
+            .upstream
+            .as_deref()
+            .and_then(|url| url.rsplit_once('.'))
+            .map(|(_, ext)| ext)
+            .filter(|ext| matches!(*ext, "png" | "jpg" | "jpeg" | "webp"))
+            .unwrap_or("png")
+    }
+
+    /// TileJSON for the proxied layer
+    fn tile_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "tilejson": "3.0.0",
+            "tiles": [format!("/tiles/{{z}}/{{x}}/{{y}}.{}", self.extension())],
+            "attribution": self.config.attribution,
+            "minzoom": 0,
+            "maxzoom": MAX_ZOOM,
+        })
+    }
+
+    /// Take an upstream request from the bucket
+    fn take_token(&self, now: Instant) -> bool {
+        let rate = self.config.rate_per_minute as f64;
+        let Ok(mut bucket) = self.bucket.lock() else {
+            return false;
+        };
+        let refill = now.saturating_duration_since(bucket.updated).as_secs_f64() * rate / 60.0;
+        bucket.tokens = (bucket.tokens + refill).min(rate);
+        bucket.updated = now;
+        if bucket.tokens < 1.0 {
+            return false;
+        }
+        bucket.tokens -= 1.0;
+        true
+    }
+
+    /// The tile at `z`/`x`/`file` (`{y}.{ext}`), from disk or upstream
+    async fn tile(&self, z: u32, x: u32, file: &str) -> Result<Bytes, TileError> {
+        let y = parse_tile(z, x, file, self.extension()).ok_or(TileError::Invalid)?;
+        let path = self.config.cache_dir.join(z.to_string()).join(x.to_string()).join(file);
+
+        let stored = tokio::fs::read(&path).await.ok().map(Bytes::from);
+        let fresh = match tokio::fs::metadata(&path).await.and_then(|m| m.modified()) {
+            Ok(modified) => SystemTime::now()
+                .duration_since(modified)
+                .is_ok_and(|age| age < self.config.max_age),
+            Err(_) => false,
+        };
+        let Some(upstream) = &self.config.upstream else {
+            return stored.ok_or(TileError::NotFound);
+        };
+        if let Some(stored) = &stored {
+            if fresh {
+                return Ok(stored.clone());
+            }
+        }
+        if !self.take_token(Instant::now()) {
+            return stored.ok_or(TileError::RateLimited);
+        }
+
+        let url = upstream
+            .replace("{s}", "a")
+            .replace("{r}", "")
+            .replace("{z}", &z.to_string())
+            .replace("{x}", &x.to_string())
+            .replace("{y}", &y.to_string());
+        debug!("Fetching tile {}", url);
+        let fetched = match self.client.get(&url).send().await {
+            Ok(response) if response.status() == reqwest::StatusCode::NOT_FOUND => {
+                return stored.ok_or(TileError::NotFound);
+            }
+            Ok(response) => match response.error_for_status() {
+                Ok(response) => response.bytes().await,
+                Err(e) => Err(e),
+            },
+            Err(e) => Err(e),
+        };
+        match fetched {
+            Ok(tile) => {
+                if let Err(e) = store(&path, &tile).await {
+                    warn!("Failed to store tile {}: {}", path.display(), e);
+                }
+                Ok(tile)
+            }
+            Err(e) => {
+                warn!("Failed to fetch tile {}: {}", url, e);
+                stored.ok_or(TileError::Upstream)
+            }
+        }
+    }
+}
+
+/// The `y` of a tile file name, if `z`/`x`/`y` is a tile of the layer
+fn parse_tile(z: u32, x: u32, file: &str, extension: &str) -> Option<u32> {
+    let (y, ext) = file.rsplit_once('.')?;
+    let y: u32 = y.parse().ok()?;
+    let size = 1u32 << z.min(MAX_ZOOM);
+    (z <= MAX_ZOOM && x < size && y < size && ext == extension).then_some(y)
+}
+
+/// Write a tile through a temporary file, so readers never see half of one
+async fn store(path: &std::path::Path, tile: &[u8]) -> std::io::Result<()> {
+    if let Some(dir) = path.parent() {
+        tokio::fs::create_dir_all(dir).await?;
+    }
+    let partial = path.with_extension("part");
+    tokio::fs::write(&partial, tile).await?;
+    tokio::fs::rename(&partial, path).await
+}
+
+/// Describe the proxied tile layer (404 when the proxy is off)
+pub async fn tile_json(State(state): State<Arc<AppState>>) -> Response {
+    match &state.tiles {
+        Some(tiles) => Json(tiles.tile_json()).into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+/// Serve one map tile
+pub async fn tile(
+    State(state): State<Arc<AppState>>,
+    Path((z, x, file)): Path<(u32, u32, String)>,
+) -> Response {
+    let Some(tiles) = &state.tiles else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    match tiles.tile(z, x, &file).await {
+        Ok(tile) => {
+            let content_type = match tiles.extension() {
+                "jpg" | "jpeg" => "image/jpeg",
+                "webp" => "image/webp",
+                _ => "image/png",
+            };
+            (
+                [
+                    (header::CONTENT_TYPE, HeaderValue::from_static(content_type)),
+                    (
+                        header::CACHE_CONTROL,
+                        HeaderValue::from_str(&format!("max-age={}", BROWSER_CACHE_SECS))
+                            .unwrap_or(HeaderValue::from_static("no-cache")),
+                    ),
+                ],
+                tile,
+            )
+                .into_response()
+        }
+        Err(TileError::Invalid) => StatusCode::BAD_REQUEST.into_response(),
+        Err(TileError::NotFound) => StatusCode::NOT_FOUND.into_response(),
+        Err(TileError::RateLimited) => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            [(header::RETRY_AFTER, HeaderValue::from_static("1"))],
+        )
+            .into_response(),
+        Err(TileError::Upstream) => StatusCode::BAD_GATEWAY.into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn proxy(upstream: Option<&str>, cache_dir: PathBuf) -> TileProxy {
+        TileProxy::new(TileConfig {
+            upstream: upstream.map(str::to_string),
+            cache_dir,
+            rate_per_minute: 2,
+            max_age: Duration::from_secs(3600),
+            attribution: "© OpenStreetMap contributors".into(),
+        })
+    }
+
+    #[test]
+    fn test_parse_tile() {
+        assert_eq!(parse_tile(0, 0, "0.png", "png"), Some(0));
+        assert_eq!(parse_tile(3, 7, "5.png", "png"), Some(5));
+        assert_eq!(parse_tile(3, 8, "5.png", "png"), None);
+        assert_eq!(parse_tile(3, 7, "8.png", "png"), None);
+        assert_eq!(parse_tile(3, 7, "5.jpg", "png"), None);
+        assert_eq!(parse_tile(3, 7, "../5.png", "png"), None);
+        assert_eq!(parse_tile(20, 0, "0.png", "png"), None);
+    }
+
+    #[test]
+    fn test_rate_limit() {
+        let tiles = proxy(Some("https://tile.example.org/{z}/{x}/{y}.jpg"), std::env::temp_dir());
+        assert_eq!(tiles.extension(), "jpg");
+        assert_eq!(tiles.tile_json()["tiles"][0], "/tiles/{z}/{x}/{y}.jpg");
+
+        let t = Instant::now();
+        assert!(tiles.take_token(t));
+        assert!(tiles.take_token(t));
+        assert!(!tiles.take_token(t));
+        assert!(tiles.take_token(t + Duration::from_secs(30)));
+    }
+
+    #[tokio::test]
+    async fn test_offline_tiles() {
+        let dir = std::env::temp_dir().join(format!("tiles-test-{}", std::process::id()));
+        let tiles = proxy(None, dir.clone());
+        assert_eq!(tiles.tile(2, 1, "3.png").await, Err(TileError::NotFound));
+
+        store(&dir.join("2/1/3.png"), b"tile").await.unwrap();
+        assert_eq!(tiles.tile(2, 1, "3.png").await, Ok(Bytes::from_static(b"tile")));
+        assert_eq!(tiles.tile(2, 4, "3.png").await, Err(TileError::Invalid));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
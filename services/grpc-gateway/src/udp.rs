@@ -0,0 +1,158 @@
+//! UDP event output
+//!
+//! For LAN integrations that care more about latency than delivery, such
+//! as a radar-scope display, `UDP_OUTPUT` takes a comma-separated list of
+//! unicast or multicast `host:port` targets. Each aircraft event is sent to
+//! every target as one datagram as soon as it has been merged, before
+//! storage: the `position_update` JSON the WebSocket carries
+//! (`UDP_OUTPUT_FORMAT=json`, the default) or the merged `AircraftEvent`
+//! protobuf message from `adsb.proto` (`protobuf`). Sends never wait; a
+//! datagram the socket can't take at once is dropped and counted in
+//! `/metrics`. Multicast datagrams go `UDP_MULTICAST_TTL` hops (default 1,
+//! the local network). `SHARE_UDP` restricts what is sent like the other
+//! share settings.
+
+use crate::adsb::AircraftEvent;
+use crate::share::SharePolicy;
+use prost::Message;
+use std::net::{SocketAddr, UdpSocket};
+use std::sync::atomic::{AtomicU64, Ordering};
+use tracing::debug;
+
+/// Datagram encoding
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UdpFormat {
+    /// `position_update` JSON, as on the WebSocket
+    Json,
+    /// `AircraftEvent` protobuf
+    Protobuf,
+}
+
+impl UdpFormat {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "json" => Some(Self::Json),
+            "protobuf" | "proto" => Some(Self::Protobuf),
+            _ => None,
+        }
+    }
+}
+
+/// Socket and targets of the UDP output
+#[derive(Debug)]
+pub struct UdpOutput {
+    socket: Option<UdpSocket>,
+    targets: Vec<SocketAddr>,
+    format: UdpFormat,
+    share: SharePolicy,
+    pub sent: AtomicU64,
+    pub dropped: AtomicU64,
+}
+
+impl UdpOutput {
+    /// Output sending nothing
+    pub fn disabled() -> Self {
+        Self {
+            socket: None,
+            targets: Vec::new(),
+            format: UdpFormat::Json,
+            share: SharePolicy::default(),
+            sent: AtomicU64::new(0),
+            dropped: AtomicU64::new(0),
+        }
+    }
+
+    /// Send to `targets`; disabled when there are none
+    pub fn new(targets: Vec<SocketAddr>, format: UdpFormat, multicast_ttl: u32, share: SharePolicy) -> std::io::Result<Self> {
+        if targets.is_empty() {
+            return Ok(Self::disabled());
+        }
+        let bind: SocketAddr = if targets.iter().all(SocketAddr::is_ipv6) {
+            "[::]:0".parse().unwrap()
+        } else {
+            "0.0.0.0:0".parse().unwrap()
+        };
+        let socket = UdpSocket::bind(bind)?;
+        socket.set_nonblocking(true)?;
+        if bind.is_ipv4() && targets.iter().any(|t| t.ip().is_multicast()) {
+            socket.set_multicast_ttl_v4(multicast_ttl)?;
+        }
+        Ok(Self {
+            socket: Some(socket),
+            targets,
+            format,
+            share,
+            ..Self::disabled()
+        })
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.socket.is_some()
+    }
+
+    /// Datagram for an event; `json` gives its `position_update` message
+    pub fn encode(&self, event: &AircraftEvent, json: impl FnOnce() -> Option<String>) -> Option<Vec<u8>> {
+        match self.format {
+            UdpFormat::Json => Some(self.share.apply_str(&json()?)?.into_owned().into_bytes()),
+            UdpFormat::Protobuf => self.share.allows(&event.icao).then(|| event.encode_to_vec()),
+        }
+    }
+
+    /// Send an event to every target without waiting
+    pub fn send(&self, event: &AircraftEvent, json: impl FnOnce() -> Option<String>) {
+        let Some(socket) = &self.socket else {
+            return;
+        };
+        let Some(datagram) = self.encode(event, json) else {
+            return;
+        };
+        for target in &self.targets {
+            match socket.send_to(&datagram, target) {
+                Ok(_) => self.sent.fetch_add(1, Ordering::Relaxed),
+                Err(e) => {
+                    debug!("UDP output to {} dropped a datagram: {}", target, e);
+                    self.dropped.fetch_add(1, Ordering::Relaxed)
+                }
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::military::AddressClasses;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[test]
+    fn test_udp_output() {
+        let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+        receiver.set_read_timeout(Some(Duration::from_secs(2))).unwrap();
+        let share = SharePolicy::parse("no-military,no-registration", Arc::new(AddressClasses::new())).unwrap();
+        let event = AircraftEvent {
+            icao: "71BE11".into(),
+            altitude_ft: Some(35000),
+            ..Default::default()
+        };
+        let json = || Some(r#"{"type":"position_update","icao":"71BE11","registration":"HL7611"}"#.to_string());
+        let mut buf = [0u8; 1024];
+
+        let output = UdpOutput::new(vec![receiver.local_addr().unwrap()], UdpFormat::Json, 1, share.clone()).unwrap();
+        output.send(&event, json);
+        let n = receiver.recv(&mut buf).unwrap();
+        let sent: serde_json::Value = serde_json::from_slice(&buf[..n]).unwrap();
+        assert_eq!(sent, serde_json::json!({"type": "position_update", "icao": "71BE11"}));
+        assert_eq!(output.sent.load(Ordering::Relaxed), 1);
+
+        let output = UdpOutput::new(vec![receiver.local_addr().unwrap()], UdpFormat::Protobuf, 1, share).unwrap();
+        output.send(&event, json);
+        let n = receiver.recv(&mut buf).unwrap();
+        assert_eq!(AircraftEvent::decode(&buf[..n]).unwrap(), event);
+        // Withheld aircraft are not sent
+        assert!(output.encode(&AircraftEvent { icao: "AE1234".into(), ..Default::default() }, json).is_none());
+
+        assert!(!UdpOutput::new(Vec::new(), UdpFormat::Json, 1, SharePolicy::default()).unwrap().is_enabled());
+        assert_eq!(UdpFormat::parse("PROTO"), Some(UdpFormat::Protobuf));
+    }
+}
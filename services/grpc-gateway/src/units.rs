@@ -0,0 +1,214 @@
+//! Unit preferences
+//!
+//! Altitudes are stored and broadcast in feet, vertical rates in feet per
+//! minute and speeds in knots. REST endpoints returning aircraft data take
+//! `altitude_unit` (`ft` or `m`) and `speed_unit` (`kt`, `kph` or `mps`),
+//! and a WebSocket `subscribe` message takes the same two fields, so clients
+//! get the units they display instead of each carrying the conversions.
+//! Vertical rates follow the altitude unit, per minute. The altitude band
+//! filters (`min_alt`/`max_alt`) are read in the same unit as the response.
+//!
+//! Conversion works on the serialized JSON by field name, so it covers
+//! every response and message type that uses the standard names
+//! (`altitude`, `max_altitude`, `vrate`, `baro_rate`, `geom_rate`,
+//! `speed`); fields named for their unit (`altitude_ft`) keep it.
+
+use crate::api::ApiError;
+use axum::{
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use std::borrow::Cow;
+use utoipa::{IntoParams, ToSchema};
+
+/// Metres per foot
+const M_PER_FT: f64 = 0.3048;
+
+/// Kilometres per nautical mile
+const KM_PER_NM: f64 = 1.852;
+
+/// Fields holding an altitude in feet
+const ALTITUDE_FIELDS: [&str; 2] = ["altitude", "max_altitude"];
+
+/// Fields holding a vertical rate in feet per minute
+const RATE_FIELDS: [&str; 3] = ["vrate", "baro_rate", "geom_rate"];
+
+/// Fields holding a speed in knots
+const SPEED_FIELDS: [&str; 1] = ["speed"];
+
+/// Unit for altitudes and vertical rates
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub enum AltitudeUnit {
+    #[default]
+    #[serde(rename = "ft", alias = "feet")]
+    Feet,
+    #[serde(rename = "m", alias = "meters", alias = "metres")]
+    Meters,
+}
+
+impl AltitudeUnit {
+    fn convert_feet(self, feet: f64) -> f64 {
+        match self {
+            Self::Feet => feet,
+            Self::Meters => feet * M_PER_FT,
+        }
+    }
+
+    /// An altitude given in this unit, in feet
+    pub fn to_feet(self, value: i32) -> i32 {
+        match self {
+            Self::Feet => value,
+            Self::Meters => (value as f64 / M_PER_FT).round() as i32,
+        }
+    }
+}
+
+/// Unit for speeds
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub enum SpeedUnit {
+    #[default]
+    #[serde(rename = "kt", alias = "knots")]
+    Knots,
+    #[serde(rename = "kph", alias = "kmh")]
+    Kph,
+    #[serde(rename = "mps", alias = "m/s")]
+    Mps,
+}
+
+impl SpeedUnit {
+    fn convert_knots(self, knots: f64) -> f64 {
+        match self {
+            Self::Knots => knots,
+            Self::Kph => knots * KM_PER_NM,
+            Self::Mps => knots * KM_PER_NM / 3.6,
+        }
+    }
+}
+
+/// Units requested for a response or WebSocket subscription
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct Units {
+    /// `ft` (default) or `m`; vertical rates follow, per minute
+    #[serde(default)]
+    pub altitude_unit: AltitudeUnit,
+    /// `kt` (default), `kph` or `mps`
+    #[serde(default)]
+    pub speed_unit: SpeedUnit,
+}
+
+impl Units {
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Convert every altitude, vertical rate and speed in `value`, at any
+    /// depth, from feet and knots
+    pub fn convert(&self, value: &mut JsonValue) {
+        match value {
+            JsonValue::Object(fields) => {
+                for (name, field) in fields.iter_mut() {
+                    let name = name.as_str();
+                    if ALTITUDE_FIELDS.contains(&name) || RATE_FIELDS.contains(&name) {
+                        convert_number(field, |ft| self.altitude_unit.convert_feet(ft), 0);
+                    } else if SPEED_FIELDS.contains(&name) {
+                        convert_number(field, |kt| self.speed_unit.convert_knots(kt), 1);
+                    } else {
+                        self.convert(field);
+                    }
+                }
+            }
+            JsonValue::Array(items) => items.iter_mut().for_each(|item| self.convert(item)),
+            _ => {}
+        }
+    }
+
+    /// A serialized message in these units
+    pub fn convert_str<'a>(&self, json: &'a str) -> Cow<'a, str> {
+        if self.is_default() {
+            return Cow::Borrowed(json);
+        }
+        match serde_json::from_str::<JsonValue>(json) {
+            Ok(mut value) => {
+                self.convert(&mut value);
+                Cow::Owned(value.to_string())
+            }
+            Err(_) => Cow::Borrowed(json),
+        }
+    }
+
+    /// JSON response body in these units
+    pub fn json<T: Serialize>(&self, body: T) -> Response {
+        if self.is_default() {
+            return Json(body).into_response();
+        }
+        match serde_json::to_value(body) {
+            Ok(mut value) => {
+                self.convert(&mut value);
+                Json(value).into_response()
+            }
+            Err(e) => ApiError::internal(e),
+        }
+    }
+}
+
+/// Replace a number with `convert` of it, rounded to `decimals`; integers
+/// stay integers when rounded to none. Nulls are left alone
+fn convert_number(field: &mut JsonValue, convert: impl Fn(f64) -> f64, decimals: i32) {
+    let Some(value) = field.as_f64() else {
+        return;
+    };
+    let scale = 10f64.powi(decimals);
+    let converted = (convert(value) * scale).round() / scale;
+    *field = if decimals == 0 {
+        JsonValue::from(converted as i64)
+    } else {
+        JsonValue::from(converted)
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_convert() {
+        let units = Units {
+            altitude_unit: AltitudeUnit::Meters,
+            speed_unit: SpeedUnit::Kph,
+        };
+        let mut value = json!({
+            "aircraft": [{"icao": "71BE11", "altitude": 35000, "speed": 450.0, "vrate": -1000, "baro_rate": null}],
+            "flight": {"max_altitude": 10000},
+            "altitude_ft": 35000,
+        });
+        units.convert(&mut value);
+        assert_eq!(value["aircraft"][0]["altitude"], 10668);
+        assert_eq!(value["aircraft"][0]["speed"], 833.4);
+        assert_eq!(value["aircraft"][0]["vrate"], -305);
+        assert_eq!(value["aircraft"][0]["baro_rate"], JsonValue::Null);
+        assert_eq!(value["flight"]["max_altitude"], 3048);
+        assert_eq!(value["altitude_ft"], 35000);
+
+        let mps = Units {
+            speed_unit: SpeedUnit::Mps,
+            ..Default::default()
+        };
+        assert_eq!(mps.convert_str("{\"speed\":100}"), "{\"speed\":51.4}");
+        let json = "{\"altitude\":3000}";
+        assert!(matches!(Units::default().convert_str(json), Cow::Borrowed(_)));
+        assert_eq!(AltitudeUnit::Meters.to_feet(3048), 10000);
+    }
+
+    #[test]
+    fn test_query() {
+        let query = |uri: &str| axum::extract::Query::<Units>::try_from_uri(&uri.parse().unwrap()).map(|q| q.0);
+        let units = query("/api/aircraft?altitude_unit=m&speed_unit=mps&min_alt=300").unwrap();
+        assert_eq!((units.altitude_unit, units.speed_unit), (AltitudeUnit::Meters, SpeedUnit::Mps));
+        assert!(query("/api/aircraft").unwrap().is_default());
+        assert!(query("/api/aircraft?speed_unit=furlongs").is_err());
+    }
+}
@@ -0,0 +1,443 @@
+//! Receiver uptime
+//!
+//! A receiver counts as up while the gateway hears from it. A session opens
+//! with the first aircraft event, signal metric, device status or heartbeat
+//! a capture host streams for a device, and ends when every stream carrying the
+//! device has closed, when the host reports the SDR disconnected, or when
+//! nothing has arrived for `SILENCE_TIMEOUT_SECS` (hosts send a status
+//! heartbeat every 5 seconds, so a stalled connection is noticed too).
+//! Sessions are written to the `receiver_sessions` table, and `/api/stats`
+//! reports each device's share of every day it spent up. A session left
+//! open by a gateway restart counts until it was last written.
+
+use crate::storage::Storage;
+use async_graphql::SimpleObject;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::{info, warn};
+use utoipa::ToSchema;
+
+/// Receivers silent this long are treated as down
+const SILENCE_TIMEOUT_SECS: i64 = 60;
+
+/// How often open sessions are written and timed out
+const SWEEP_INTERVAL_SECS: u64 = 30;
+
+/// Every stream carrying the device ended
+const STREAM_CLOSED: &str = "stream_closed";
+
+/// The host reported the SDR disconnected
+const SDR_DISCONNECTED: &str = "sdr_disconnected";
+
+/// Nothing was heard for `SILENCE_TIMEOUT_SECS`
+const SILENT: &str = "silent";
+
+/// A connected episode as stored in the `receiver_sessions` table
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReceiverSession {
+    pub device_id: String,
+    pub started_at: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
+    pub ended_at: Option<DateTime<Utc>>,
+    /// `stream_closed`, `sdr_disconnected` or `silent` once ended
+    pub end_reason: Option<String>,
+}
+
+impl ReceiverSession {
+    fn new(device_id: &str, now: DateTime<Utc>) -> Self {
+        Self {
+            device_id: device_id.to_string(),
+            started_at: now,
+            last_seen: now,
+            ended_at: None,
+            end_reason: None,
+        }
+    }
+
+    fn end(mut self, at: DateTime<Utc>, reason: &str) -> Self {
+        info!(
+            "Receiver {} down ({}), up {}s",
+            self.device_id,
+            reason,
+            (at - self.started_at).num_seconds()
+        );
+        self.ended_at = Some(at);
+        self.end_reason = Some(reason.to_string());
+        self
+    }
+
+    /// When the receiver went down, or was last known up
+    fn until(&self) -> DateTime<Utc> {
+        self.ended_at.unwrap_or(self.last_seen)
+    }
+}
+
+/// A device some stream has carried
+#[derive(Default)]
+struct Device {
+    /// Streams currently carrying the device
+    streams: usize,
+    /// The host last reported the SDR disconnected
+    sdr_down: bool,
+    /// Round trip of the host's last heartbeat, milliseconds
+    rtt_ms: Option<u32>,
+    session: Option<ReceiverSession>,
+}
+
+#[derive(Default)]
+struct Sessions {
+    devices: HashMap<String, Device>,
+    /// Ended since the last write
+    ended: Vec<ReceiverSession>,
+}
+
+impl Sessions {
+    fn heard(&mut self, device_id: &str, now: DateTime<Utc>) {
+        let device = self.devices.entry(device_id.to_string()).or_default();
+        if device.sdr_down {
+            return;
+        }
+        match &mut device.session {
+            Some(session) => session.last_seen = now,
+            None => {
+                info!("Receiver {} up", device_id);
+                device.session = Some(ReceiverSession::new(device_id, now));
+            }
+        }
+    }
+
+    fn end(&mut self, device_id: &str, at: DateTime<Utc>, reason: &str) {
+        if let Some(session) = self.devices.get_mut(device_id).and_then(|d| d.session.take()) {
+            self.ended.push(session.end(at, reason));
+        }
+    }
+}
+
+/// Tracks which receivers are up
+#[derive(Default)]
+pub struct ReceiverTracker {
+    sessions: Mutex<Sessions>,
+}
+
+impl ReceiverTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A message from `device_id` arrived on a stream already carrying it
+    fn heard(&self, device_id: &str, now: DateTime<Utc>) {
+        if let Ok(mut sessions) = self.sessions.lock() {
+            sessions.heard(device_id, now);
+        }
+    }
+
+    /// A stream carried its first message from `device_id`
+    fn attach(&self, device_id: &str, now: DateTime<Utc>) {
+        if let Ok(mut sessions) = self.sessions.lock() {
+            sessions.devices.entry(device_id.to_string()).or_default().streams += 1;
+            sessions.heard(device_id, now);
+        }
+    }
+
+    /// A stream that carried `device_id` ended
+    fn detach(&self, device_id: &str, now: DateTime<Utc>) {
+        let Ok(mut sessions) = self.sessions.lock() else {
+            return;
+        };
+        let Some(device) = sessions.devices.get_mut(device_id) else {
+            return;
+        };
+        device.streams = device.streams.saturating_sub(1);
+        if device.streams == 0 {
+            sessions.end(device_id, now, STREAM_CLOSED);
+            sessions.devices.remove(device_id);
+        }
+    }
+
+    /// The host reported whether the device's SDR is connected
+    pub fn status(&self, device_id: &str, connected: bool, now: DateTime<Utc>) {
+        if device_id.is_empty() {
+            return;
+        }
+        let Ok(mut sessions) = self.sessions.lock() else {
+            return;
+        };
+        sessions.devices.entry(device_id.to_string()).or_default().sdr_down = !connected;
+        if connected {
+            sessions.heard(device_id, now);
+        } else {
+            sessions.end(device_id, now, SDR_DISCONNECTED);
+        }
+    }
+
+    /// The host measured a heartbeat round trip for a device some stream
+    /// carries
+    pub fn round_trip(&self, device_id: &str, rtt_ms: u32) {
+        if let Ok(mut sessions) = self.sessions.lock() {
+            if let Some(device) = sessions.devices.get_mut(device_id) {
+                device.rtt_ms = Some(rtt_ms);
+            }
+        }
+    }
+
+    /// Last heartbeat round trip of every connected device
+    pub fn round_trips(&self) -> Vec<(String, u32)> {
+        self.sessions
+            .lock()
+            .map(|s| {
+                s.devices
+                    .iter()
+                    .filter_map(|(id, d)| Some((id.clone(), d.rtt_ms?)))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Sessions currently open
+    pub fn open_sessions(&self) -> Vec<ReceiverSession> {
+        self.sessions
+            .lock()
+            .map(|s| s.devices.values().filter_map(|d| d.session.clone()).collect())
+            .unwrap_or_default()
+    }
+
+    /// End silent sessions, and collect every session to write: the ended
+    /// ones and the open ones
+    fn take_pending(&self, now: DateTime<Utc>) -> Vec<ReceiverSession> {
+        let Ok(mut sessions) = self.sessions.lock() else {
+            return Vec::new();
+        };
+        let timeout = chrono::Duration::seconds(SILENCE_TIMEOUT_SECS);
+        let silent: Vec<(String, DateTime<Utc>)> = sessions
+            .devices
+            .values()
+            .filter_map(|d| d.session.as_ref())
+            .filter(|s| now - s.last_seen > timeout)
+            .map(|s| (s.device_id.clone(), s.last_seen))
+            .collect();
+        for (device_id, last_seen) in silent {
+            sessions.end(&device_id, last_seen, SILENT);
+        }
+
+        let mut pending = std::mem::take(&mut sessions.ended);
+        pending.extend(sessions.devices.values().filter_map(|d| d.session.clone()));
+        pending
+    }
+}
+
+/// Devices heard on one gRPC stream; they are detached when the stream's
+/// handler returns or is dropped
+pub struct StreamDevices {
+    tracker: Arc<ReceiverTracker>,
+    devices: HashSet<String>,
+    attach: bool,
+}
+
+impl StreamDevices {
+    pub fn new(tracker: Arc<ReceiverTracker>) -> Self {
+        Self {
+            tracker,
+            devices: HashSet::new(),
+            attach: true,
+        }
+    }
+
+    /// For devices feeding through separate requests rather than a stream:
+    /// their sessions end after the silence timeout, not with the request
+    pub fn unattached(tracker: Arc<ReceiverTracker>) -> Self {
+        Self {
+            tracker,
+            devices: HashSet::new(),
+            attach: false,
+        }
+    }
+
+    /// A message from `device_id` arrived; hosts that don't send a device ID
+    /// aren't tracked
+    pub fn heard(&mut self, device_id: &str, now: DateTime<Utc>) {
+        if device_id.is_empty() {
+            return;
+        }
+        if !self.attach {
+            self.tracker.heard(device_id, now);
+        } else if self.devices.insert(device_id.to_string()) {
+            self.tracker.attach(device_id, now);
+        } else {
+            self.tracker.heard(device_id, now);
+        }
+    }
+}
+
+impl Drop for StreamDevices {
+    fn drop(&mut self) {
+        let now = Utc::now();
+        for device_id in &self.devices {
+            self.tracker.detach(device_id, now);
+        }
+    }
+}
+
+/// Periodically persist sessions and end silent ones
+pub fn spawn_sweeper(tracker: Arc<ReceiverTracker>, db_writer: Arc<dyn Storage>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(SWEEP_INTERVAL_SECS));
+        loop {
+            interval.tick().await;
+            for session in tracker.take_pending(Utc::now()) {
+                if let Err(e) = db_writer.upsert_receiver_session(&session).await {
+                    warn!("Failed to store receiver session for {}: {}", session.device_id, e);
+                }
+            }
+        }
+    });
+}
+
+/// One device's time up during a day
+#[derive(Debug, Serialize, ToSchema, SimpleObject)]
+pub struct DeviceUptime {
+    /// Share of the day, or of its elapsed part for today, spent up
+    pub uptime_pct: f64,
+    pub up_secs: i64,
+    /// Sessions overlapping the day
+    pub sessions: i64,
+}
+
+/// Every device's uptime during one UTC day
+#[derive(Debug, Serialize, ToSchema, SimpleObject)]
+pub struct UptimeDay {
+    /// Start of the day
+    pub day: DateTime<Utc>,
+    pub devices: BTreeMap<String, DeviceUptime>,
+}
+
+/// Per-day uptime of each device from the start of the day `from` falls
+/// in to `to`. Devices are reported from the day of their first session;
+/// overlapping sessions count once
+pub fn daily(sessions: &[ReceiverSession], from: DateTime<Utc>, to: DateTime<Utc>) -> Vec<UptimeDay> {
+    let mut by_device: BTreeMap<&str, Vec<&ReceiverSession>> = BTreeMap::new();
+    for session in sessions.iter().filter(|s| s.until() > s.started_at) {
+        by_device.entry(&session.device_id).or_default().push(session);
+    }
+    for spans in by_device.values_mut() {
+        spans.sort_by_key(|s| s.started_at);
+    }
+
+    let one_day = chrono::Duration::days(1);
+    let mut days = Vec::new();
+    let mut day = crate::stats::Period::Day.start(from);
+    while day < to {
+        let end = (day + one_day).min(to);
+        let length = (end - day).num_seconds().max(1);
+        let mut devices = BTreeMap::new();
+        for (device_id, spans) in &by_device {
+            let first_day = crate::stats::Period::Day.start(spans[0].started_at);
+            if first_day > day {
+                continue;
+            }
+            let (mut up_secs, mut count) = (0, 0);
+            let mut covered = day;
+            for session in spans {
+                let (start, until) = (session.started_at.max(covered), session.until().min(end));
+                if until <= start {
+                    continue;
+                }
+                up_secs += (until - start).num_seconds();
+                count += 1;
+                covered = until;
+            }
+            devices.insert(
+                device_id.to_string(),
+                DeviceUptime {
+                    uptime_pct: (up_secs as f64 * 1000.0 / length as f64).round() / 10.0,
+                    up_secs,
+                    sessions: count,
+                },
+            );
+        }
+        days.push(UptimeDay { day, devices });
+        day += one_day;
+    }
+    days
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc)
+    }
+
+    #[test]
+    fn test_stream_lifecycle() {
+        let tracker = Arc::new(ReceiverTracker::new());
+        let t = time("2024-01-15T10:30:00Z");
+        let at = |secs| t + chrono::Duration::seconds(secs);
+
+        // Two streams carry the device; it stays up until both end
+        let mut aircraft = StreamDevices::new(tracker.clone());
+        let mut status = StreamDevices::new(tracker.clone());
+        aircraft.heard("rtlsdr-0", t);
+        status.heard("rtlsdr-0", at(5));
+        aircraft.heard("", at(5));
+        drop(aircraft);
+        let open = tracker.open_sessions();
+        assert_eq!(open.len(), 1);
+        assert_eq!((open[0].started_at, open[0].last_seen), (t, at(5)));
+
+        // A disconnected SDR ends the session until it is back
+        tracker.status("rtlsdr-0", false, at(10));
+        status.heard("rtlsdr-0", at(15));
+        assert!(tracker.open_sessions().is_empty());
+        tracker.status("rtlsdr-0", true, at(20));
+        assert_eq!(tracker.open_sessions()[0].started_at, at(20));
+
+        // Silence ends it at the last message
+        let pending = tracker.take_pending(at(100));
+        assert_eq!(pending.len(), 2);
+        assert_eq!(pending[0].ended_at, Some(at(10)));
+        assert_eq!(pending[0].end_reason.as_deref(), Some(SDR_DISCONNECTED));
+        assert_eq!(pending[1].ended_at, Some(at(20)));
+        assert_eq!(pending[1].end_reason.as_deref(), Some(SILENT));
+
+        status.heard("rtlsdr-0", at(110));
+        drop(status);
+        let pending = tracker.take_pending(at(120));
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].end_reason.as_deref(), Some(STREAM_CLOSED));
+        assert!(tracker.take_pending(at(130)).is_empty());
+    }
+
+    #[test]
+    fn test_daily_uptime() {
+        let session = |device: &str, start: &str, until: &str, ended: bool| ReceiverSession {
+            device_id: device.into(),
+            started_at: time(start),
+            last_seen: time(until),
+            ended_at: ended.then(|| time(until)),
+            end_reason: None,
+        };
+        let sessions = [
+            // Down from 01:00 to 07:00 on the 15th
+            session("rtlsdr-0", "2024-01-14T12:00:00Z", "2024-01-15T01:00:00Z", true),
+            session("rtlsdr-0", "2024-01-15T07:00:00Z", "2024-01-16T06:00:00Z", false),
+            // Counted once
+            session("rtlsdr-0", "2024-01-15T08:00:00Z", "2024-01-15T09:00:00Z", true),
+            session("rtlsdr-1", "2024-01-16T00:00:00Z", "2024-01-16T03:00:00Z", false),
+        ];
+        let days = daily(&sessions, time("2024-01-15T10:00:00Z"), time("2024-01-16T12:00:00Z"));
+        assert_eq!(days.len(), 2);
+        assert_eq!(days[0].day, time("2024-01-15T00:00:00Z"));
+        let day = &days[0].devices["rtlsdr-0"];
+        assert_eq!((day.up_secs, day.sessions, day.uptime_pct), (18 * 3600, 2, 75.0));
+        assert!(!days[0].devices.contains_key("rtlsdr-1"));
+
+        // Today counts up to now
+        let today = &days[1].devices;
+        assert_eq!(today["rtlsdr-0"].uptime_pct, 50.0);
+        assert_eq!(today["rtlsdr-1"].uptime_pct, 25.0);
+    }
+}
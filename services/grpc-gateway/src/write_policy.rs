@@ -0,0 +1,96 @@
+//! Position write throttling
+//!
+//! Aircraft sending DF17 squitters several times a second would otherwise
+//! produce a database row per message. At most one position per aircraft is
+//! stored every `POSITION_WRITE_INTERVAL_MS`; every update is still
+//! broadcast to live clients and fed to the trackers. Skipped updates are
+//! counted and added to the aircraft's message count with the next write.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Prune the table beyond this many aircraft
+const MAX_ENTRIES: usize = 10_000;
+
+/// Last write and updates since, per aircraft
+struct Entry {
+    written: Instant,
+    skipped: u32,
+}
+
+/// Decides which position updates are stored
+pub struct PositionThrottle {
+    interval: Duration,
+    last_written: Mutex<HashMap<String, Entry>>,
+}
+
+impl PositionThrottle {
+    /// A zero interval stores every update
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            last_written: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Count an update for `icao`; when it should be stored, returns the
+    /// number of updates it stands for (itself plus those skipped since the
+    /// last write)
+    pub fn record(&self, icao: &str, now: Instant) -> Option<u32> {
+        if self.interval.is_zero() {
+            return Some(1);
+        }
+        let Ok(mut last_written) = self.last_written.lock() else {
+            return Some(1);
+        };
+        if let Some(entry) = last_written.get_mut(icao) {
+            if now.duration_since(entry.written) < self.interval {
+                entry.skipped += 1;
+                return None;
+            }
+            let messages = entry.skipped + 1;
+            *entry = Entry {
+                written: now,
+                skipped: 0,
+            };
+            return Some(messages);
+        }
+
+        if last_written.len() >= MAX_ENTRIES {
+            let interval = self.interval;
+            last_written.retain(|_, entry| now.duration_since(entry.written) < interval);
+        }
+        last_written.insert(
+            icao.to_string(),
+            Entry {
+                written: now,
+                skipped: 0,
+            },
+        );
+        Some(1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_one_write_per_interval() {
+        let throttle = PositionThrottle::new(Duration::from_secs(2));
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+
+        assert_eq!(throttle.record("71BE11", at(0)), Some(1));
+        assert_eq!(throttle.record("71BE11", at(500)), None);
+        assert_eq!(throttle.record("71BE12", at(500)), Some(1));
+        assert_eq!(throttle.record("71BE11", at(1999)), None);
+        // Stands for itself and the two skipped updates
+        assert_eq!(throttle.record("71BE11", at(2000)), Some(3));
+
+        let unthrottled = PositionThrottle::new(Duration::ZERO);
+        assert_eq!(unthrottled.record("71BE11", at(0)), Some(1));
+        assert_eq!(unthrottled.record("71BE11", at(0)), Some(1));
+    }
+}
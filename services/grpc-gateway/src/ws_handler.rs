@@ -1,67 +1,362 @@
 //! WebSocket handler for real-time updates to browser clients
+//!
+//! Clients receive every broadcast message until they send a `subscribe`
+//! message narrowing the stream to a bounding box, a list of ICAO
+//! addresses, some message types and/or an altitude band. Messages that
+//! carry a position, altitude or ICAO outside the subscription are dropped
+//! for that connection; messages without those fields (signal, device
+//! status) are only subject to the type filter. A subscription can also
+//! ask for altitudes in metres and speeds in km/h or m/s, as described in
+//! [`crate::units`]; its altitude band is then in the same unit.
+//!
+//! Connecting with `?delta=true` switches position updates to the
+//! delta-compressed form described in [`crate::delta`], and
+//! `?coalesce_ms=` batches them into periodic snapshots as described in
+//! [`crate::coalesce`].
+//!
+//! Connecting with `?api_key=` instead of `token` authenticates with an
+//! `API_KEYS` entry or JWT; a tenant's credential limits the stream to its
+//! receivers, as described in [`crate::tenants`]. In public viewer mode,
+//! clients presenting neither get the public stream of [`crate::public`].
+//!
+//! `?v=` picks the protocol version, as described in [`crate::schema`];
+//! every message carries it in a `v` field.
+//!
+//! `?compress=deflate` sends messages of [`DEFLATE_MIN_BYTES`] or more as
+//! binary frames holding the JSON as raw DEFLATE; shorter ones stay text
+//! frames. The bundled WebSocket library has no `permessage-deflate`, so
+//! this is the gateway's own, per message. With `WS_COMPRESSION=off` the
+//! option is accepted but everything is sent as text.
+//!
+//! Clients are admitted through [`crate::connections`] and pinged every
+//! `WS_PING_INTERVAL_SECS`; one that sends nothing, not even a pong, for
+//! `WS_IDLE_TIMEOUT_SECS` is disconnected. Broadcasts reach each client
+//! through its own queue in [`crate::fanout`], which drops the oldest
+//! messages for a client that can't keep up.
 
+use crate::api;
+use crate::coalesce::{self, Coalescer};
+use crate::connections::ConnectionGuard;
+use crate::delta::{DeltaEncoder, KEYFRAME_INTERVAL};
+use crate::filters::BoundingBox;
+use crate::pubsub::{LiveMessage, MessageFields};
+use crate::schema::Version;
+use crate::share::SharePolicy;
+use crate::tenants::Scope;
+use crate::units::Units;
 use crate::AppState;
 use axum::{
     extract::{
         ws::{Message, WebSocket},
-        State, WebSocketUpgrade,
+        ConnectInfo, Query, State, WebSocketUpgrade,
     },
+    http::{HeaderMap, StatusCode},
     response::IntoResponse,
 };
+use flate2::{write::DeflateEncoder, Compression};
 use futures_util::{SinkExt, StreamExt};
+use serde::Deserialize;
+use serde_json::Value as JsonValue;
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::io::Write;
+use std::net::SocketAddr;
 use std::sync::Arc;
-use tokio::sync::broadcast;
-use tracing::{debug, error, info};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use tracing::{debug, error, info, warn};
+
+/// Connection options
+#[derive(Debug, Default, Deserialize)]
+pub struct WsParams {
+    /// Send `position_delta` messages between keyframes
+    pub delta: Option<bool>,
+    /// Batch position updates into one `snapshot_diff` per interval
+    pub coalesce_ms: Option<u64>,
+    /// Access token, when `WS_AUTH_TOKEN` is set
+    pub token: Option<String>,
+    /// API key or JWT, in place of `token`
+    pub api_key: Option<String>,
+    /// Protocol version; unversioned clients get version 1
+    pub v: Option<u32>,
+    /// `deflate` to get larger messages as deflated binary frames
+    pub compress: Option<String>,
+}
+
+/// Messages shorter than this aren't worth deflating on their own
+pub const DEFLATE_MIN_BYTES: usize = 512;
+
+/// Frame for an outgoing JSON message: raw DEFLATE in a binary frame when
+/// `deflate` is on and the message is long enough, text otherwise
+fn frame(json: String, deflate: bool) -> Message {
+    if !deflate || json.len() < DEFLATE_MIN_BYTES {
+        return Message::Text(json);
+    }
+    let mut encoder = DeflateEncoder::new(Vec::with_capacity(json.len() / 4), Compression::fast());
+    match encoder.write_all(json.as_bytes()).and_then(|_| encoder.finish()) {
+        Ok(compressed) => Message::Binary(compressed),
+        Err(e) => {
+            error!("Failed to deflate WebSocket message: {}", e);
+            Message::Text(json)
+        }
+    }
+}
 
 /// Handle WebSocket upgrade request
 pub async fn ws_handler(
     ws: WebSocketUpgrade,
     State(state): State<Arc<AppState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Query(params): Query<WsParams>,
+    headers: HeaderMap,
 ) -> impl IntoResponse {
-    ws.on_upgrade(move |socket| handle_socket(socket, state))
+    let version = match Version::negotiate(params.v) {
+        Ok(version) => version,
+        Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+    };
+    let (scope, admitted) = match params.api_key.as_deref() {
+        Some(key) => match state.auth.verify(key) {
+            Ok(principal) => (principal.scope, state.connections.admit_authenticated(addr.ip())),
+            Err(e) => {
+                warn!("Rejected WebSocket client {}: {:?}", addr, e);
+                return e.into_response();
+            }
+        },
+        None if state.public.enabled() && !state.connections.authenticates(params.token.as_deref(), &headers) => {
+            (Scope::Public, state.connections.admit_authenticated(addr.ip()))
+        }
+        None => (Scope::All, state.connections.admit(addr.ip(), params.token.as_deref(), &headers)),
+    };
+    let guard = match admitted {
+        Ok(guard) => guard,
+        Err(rejection) => {
+            warn!("Rejected WebSocket client {}: {:?}", addr, rejection);
+            return rejection.into_response();
+        }
+    };
+    let deflate = match params.compress.as_deref() {
+        None | Some("none") => false,
+        Some("deflate") => state.ws_compression,
+        Some(other) => {
+            return (StatusCode::BAD_REQUEST, format!("unsupported compression: {}", other)).into_response();
+        }
+    };
+    let options = StreamOptions {
+        delta: params.delta.unwrap_or(false),
+        coalesce: params.coalesce_ms.map(coalesce::interval),
+        version,
+        deflate,
+    };
+    ws.on_upgrade(move |socket| handle_socket(socket, state, guard, scope, options))
 }
 
-/// Handle individual WebSocket connection
-async fn handle_socket(socket: WebSocket, state: Arc<AppState>) {
-    let (mut sender, mut receiver) = socket.split();
+/// `initial` message with the current aircraft list in `scope`, less what
+/// `share` withholds
+pub fn initial_message(state: &AppState, share: &SharePolicy, scope: &Scope) -> Option<String> {
+    if *scope == Scope::Public {
+        return state.public.initial_message();
+    }
+    let mut aircraft = state.aircraft_cache.rows(chrono::Utc::now());
+    for a in aircraft.iter_mut() {
+        state.aircraft_db.enrich(a);
+        state.routes.enrich(a);
+    }
+    aircraft.retain_mut(|a| scope.allows(a) && share.apply(a));
+    let initial_msg = serde_json::json!({
+        "type": "initial",
+        "aircraft": aircraft,
+    });
+    serde_json::to_string(&initial_msg).ok()
+}
 
-    // Subscribe to broadcast channel
-    let mut broadcast_rx = state.broadcast_tx.subscribe();
+/// Client `subscribe` message; omitted fields don't filter
+#[derive(Debug, Default, Deserialize)]
+struct SubscribeRequest {
+    /// `[min_lat, min_lon, max_lat, max_lon]`
+    bbox: Option<[f64; 4]>,
+    #[serde(default)]
+    icao: Vec<String>,
+    #[serde(default)]
+    types: Vec<String>,
+    min_alt: Option<i32>,
+    max_alt: Option<i32>,
+    #[serde(flatten)]
+    units: Units,
+}
 
-    info!("New WebSocket client connected");
+/// Per-connection message filter
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Subscription {
+    bbox: Option<BoundingBox>,
+    icao: HashSet<String>,
+    types: HashSet<String>,
+    /// In `units.altitude_unit`
+    min_alt: Option<i32>,
+    max_alt: Option<i32>,
+    units: Units,
+}
 
-    // Send initial aircraft list
-    match state.db_writer.get_current_aircraft().await {
-        Ok(aircraft) => {
-            let initial_msg = serde_json::json!({
-                "type": "initial",
-                "aircraft": aircraft,
-            });
-            if let Ok(json) = serde_json::to_string(&initial_msg) {
-                if sender.send(Message::Text(json)).await.is_err() {
-                    return;
+impl Subscription {
+    fn from_request(req: SubscribeRequest) -> Result<Self, String> {
+        let bbox = req
+            .bbox
+            .map(|[min_lat, min_lon, max_lat, max_lon]| BoundingBox::new(min_lat, min_lon, max_lat, max_lon))
+            .transpose()?;
+        if let (Some(min), Some(max)) = (req.min_alt, req.max_alt) {
+            if min > max {
+                return Err("min_alt must not exceed max_alt".into());
+            }
+        }
+        Ok(Self {
+            bbox,
+            icao: req.icao.iter().map(|i| api::normalize_icao(i)).collect(),
+            types: req.types.into_iter().collect(),
+            min_alt: req.min_alt,
+            max_alt: req.max_alt,
+            units: req.units,
+        })
+    }
+
+    fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    fn to_json(&self) -> JsonValue {
+        let mut icao: Vec<&String> = self.icao.iter().collect();
+        icao.sort();
+        let mut types: Vec<&String> = self.types.iter().collect();
+        types.sort();
+        serde_json::json!({
+            "type": "subscribed",
+            "bbox": self.bbox.map(|b| [b.min_lat, b.min_lon, b.max_lat, b.max_lon]),
+            "icao": icao,
+            "types": types,
+            "min_alt": self.min_alt,
+            "max_alt": self.max_alt,
+            "altitude_unit": self.units.altitude_unit,
+            "speed_unit": self.units.speed_unit,
+        })
+    }
+
+    /// Whether an aircraft-bearing message or list entry passes
+    fn allows_aircraft(&self, fields: &MessageFields) -> bool {
+        if !self.icao.is_empty() {
+            if let Some(icao) = &fields.icao {
+                if !self.icao.contains(&api::normalize_icao(icao)) {
+                    return false;
                 }
             }
         }
-        Err(e) => {
-            error!("Failed to get initial aircraft: {}", e);
+        if let Some(bbox) = &self.bbox {
+            if let Some((lat, lon)) = fields.position {
+                if !bbox.contains(lat, lon) {
+                    return false;
+                }
+            }
+        }
+        if let Some(alt) = fields.altitude {
+            let feet = |limit: i32| self.units.altitude_unit.to_feet(limit) as i64;
+            if self.min_alt.is_some_and(|min| alt < feet(min))
+                || self.max_alt.is_some_and(|max| alt > feet(max))
+            {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// The message as this client should see it, or `None` to drop it
+    pub fn apply<'a>(&self, msg: &'a LiveMessage) -> Option<Cow<'a, str>> {
+        if self.is_empty() {
+            return Some(Cow::Borrowed(&msg.json));
+        }
+        let kind = msg.fields.kind.as_str();
+        if !self.types.is_empty() && !self.types.contains(kind) {
+            return None;
+        }
+        if kind == "initial" {
+            // Only sent to one client at a time, so filtered on the JSON itself
+            let mut json = serde_json::from_str::<JsonValue>(&msg.json).ok()?;
+            if let Some(aircraft) = json.get_mut("aircraft").and_then(JsonValue::as_array_mut) {
+                aircraft.retain(|a| self.allows_aircraft(&MessageFields::read(a)));
+            }
+            self.units.convert(&mut json);
+            return Some(Cow::Owned(json.to_string()));
+        }
+        if !self.allows_aircraft(&msg.fields) {
+            return None;
         }
+        Some(self.units.convert_str(&msg.json))
     }
+}
+
+/// Requests from the receive task to the send task
+enum Control {
+    Subscribe(Subscription),
+    Error(String),
+}
 
-    // Send current SDR device status
+/// How messages are encoded for one connection
+struct StreamOptions {
+    delta: bool,
+    coalesce: Option<Duration>,
+    version: Version,
+    deflate: bool,
+}
+
+/// Handle individual WebSocket connection
+async fn handle_socket(
+    socket: WebSocket,
+    state: Arc<AppState>,
+    _guard: ConnectionGuard,
+    scope: Scope,
+    options: StreamOptions,
+) {
+    let StreamOptions { delta, coalesce, version, deflate } = options;
+    let (mut sender, mut receiver) = socket.split();
+
+    // Queue broadcasts for this client
+    let mut queue = match scope {
+        Scope::Public => state.public.fanout.subscribe(),
+        _ => state.fanout.subscribe(),
+    };
+
+    info!(
+        "New WebSocket client connected (delta: {}, coalesce: {:?}, v{}, deflate: {})",
+        delta,
+        coalesce,
+        version.number(),
+        deflate
+    );
+
+    // Send initial aircraft list
+    if let Some(mut json) = initial_message(&state, &state.share.ws, &scope) {
+        version.stamp(&mut json);
+        if sender.send(frame(json, deflate)).await.is_err() {
+            return;
+        }
+    }
+
+    // Send each SDR device's current status
     match state.db_writer.get_sdr_status().await {
-        Ok(status) => {
-            let status_msg = serde_json::json!({
-                "type": "device_status",
-                "device_id": status.get("device_id").and_then(|v| v.as_str()).unwrap_or("unknown"),
-                "connected": status.get("connected").and_then(|v| v.as_bool()).unwrap_or(false),
-                "sample_rate": status.get("sample_rate").and_then(|v| v.as_i64()).unwrap_or(0),
-                "center_freq": status.get("center_freq").and_then(|v| v.as_i64()).unwrap_or(0),
-                "gain_db": status.get("gain_db").and_then(|v| v.as_f64()).unwrap_or(0.0),
-            });
-            if let Ok(json) = serde_json::to_string(&status_msg) {
-                if sender.send(Message::Text(json)).await.is_err() {
-                    return;
+        Ok(devices) => {
+            for status in devices {
+                if !scope.allows(&status) {
+                    continue;
+                }
+                let status_msg = serde_json::json!({
+                    "type": "device_status",
+                    "device_id": status.get("device_id").and_then(|v| v.as_str()).unwrap_or("unknown"),
+                    "connected": status.get("connected").and_then(|v| v.as_bool()).unwrap_or(false),
+                    "sample_rate": status.get("sample_rate").and_then(|v| v.as_i64()).unwrap_or(0),
+                    "center_freq": status.get("center_freq").and_then(|v| v.as_i64()).unwrap_or(0),
+                    "gain_db": status.get("gain_db").and_then(|v| v.as_f64()).unwrap_or(0.0),
+                });
+                if let Ok(mut json) = serde_json::to_string(&status_msg) {
+                    version.stamp(&mut json);
+                    if sender.send(frame(json, deflate)).await.is_err() {
+                        return;
+                    }
                 }
             }
         }
@@ -70,37 +365,130 @@ async fn handle_socket(socket: WebSocket, state: Arc<AppState>) {
         }
     }
 
+    let (control_tx, mut control_rx) = mpsc::unbounded_channel::<Control>();
+
     // Spawn task to forward broadcasts to this client
+    let send_state = state.clone();
     let mut send_task = tokio::spawn(async move {
+        let mut subscription = Subscription::default();
+        let mut encoder = delta.then(|| DeltaEncoder::new(KEYFRAME_INTERVAL));
+        let mut coalescer = coalesce.map(|_| Coalescer::default());
+        let mut flush = tokio::time::interval(coalesce.unwrap_or(coalesce::MAX_INTERVAL));
+        flush.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        let mut ping = tokio::time::interval(send_state.connections.ping_interval);
+        ping.tick().await;
         loop {
-            match broadcast_rx.recv().await {
-                Ok(msg) => {
-                    if sender.send(Message::Text(msg)).await.is_err() {
+            let outgoing = tokio::select! {
+                msg = queue.recv() => match msg {
+                    Some(msg) if !scope.allows_message(&msg.fields) => continue,
+                    Some(msg) => match subscription.apply(&msg).map(|m| version.translate(&msg.fields.kind, m)) {
+                        Some(filtered) => match coalescer.as_mut() {
+                            Some(coalescer) => match coalescer.push(filtered.into_owned()) {
+                                Some(msg) => vec![msg],
+                                None => continue,
+                            },
+                            None => vec![filtered.into_owned()],
+                        },
+                        None => continue,
+                    },
+                    None => {
+                        warn!("Disconnecting WebSocket client that fell too far behind");
                         break;
                     }
+                },
+                _ = flush.tick(), if coalescer.is_some() => {
+                    let Some(coalescer) = coalescer.as_mut().filter(|c| !c.is_empty()) else {
+                        continue;
+                    };
+                    let (mut updates, removed) = coalescer.take();
+                    if let Some(encoder) = encoder.as_mut() {
+                        let now = Instant::now();
+                        for icao in &removed {
+                            encoder.forget(icao);
+                        }
+                        for update in updates.iter_mut() {
+                            if let Cow::Owned(encoded) = encoder.encode(update, now) {
+                                *update = encoded;
+                            }
+                        }
+                    }
+                    let time = chrono::Utc::now().to_rfc3339();
+                    let mut diff = coalesce::snapshot_diff(&updates, &removed, &time);
+                    version.stamp(&mut diff);
+                    if sender.send(frame(diff, deflate)).await.is_err() {
+                        return;
+                    }
+                    continue;
+                },
+                _ = ping.tick() => {
+                    if sender.send(Message::Ping(Vec::new())).await.is_err() {
+                        return;
+                    }
+                    continue;
+                },
+                control = control_rx.recv() => match control {
+                    Some(Control::Subscribe(new)) => {
+                        subscription = new;
+                        // Re-send the aircraft list as seen through the new filter
+                        let mut replies = vec![subscription.to_json().to_string()];
+                        if let Some(initial) = initial_message(&send_state, &send_state.share.ws, &scope) {
+                            if let Some(filtered) = subscription.apply(&LiveMessage::parse(&initial)) {
+                                replies.push(filtered.into_owned());
+                            }
+                        }
+                        replies
+                    }
+                    Some(Control::Error(e)) => {
+                        vec![serde_json::json!({"type": "error", "error": e}).to_string()]
+                    }
+                    None => break,
+                },
+            };
+            for mut msg in outgoing {
+                if let Some(encoder) = encoder.as_mut() {
+                    if let Cow::Owned(encoded) = encoder.encode(&msg, Instant::now()) {
+                        msg = encoded;
+                    }
                 }
-                Err(broadcast::error::RecvError::Lagged(n)) => {
-                    debug!("WebSocket client lagged by {} messages", n);
-                }
-                Err(broadcast::error::RecvError::Closed) => {
-                    break;
+                version.stamp(&mut msg);
+                if sender.send(frame(msg, deflate)).await.is_err() {
+                    return;
                 }
             }
         }
     });
 
     // Handle incoming messages from client
+    let idle_timeout = state.connections.idle_timeout;
     let mut recv_task = tokio::spawn(async move {
-        while let Some(result) = receiver.next().await {
+        loop {
+            let result = match tokio::time::timeout(idle_timeout, receiver.next()).await {
+                Ok(Some(result)) => result,
+                Ok(None) => break,
+                Err(_) => {
+                    info!("Disconnecting idle WebSocket client");
+                    break;
+                }
+            };
             match result {
                 Ok(Message::Text(text)) => {
-                    // Handle client messages (subscribe, ping, etc.)
                     debug!("Received from client: {}", text);
-                    if let Ok(msg) = serde_json::from_str::<serde_json::Value>(&text) {
+                    if let Ok(msg) = serde_json::from_str::<JsonValue>(&text) {
                         match msg.get("type").and_then(|t| t.as_str()) {
                             Some("subscribe") => {
-                                // Client wants to subscribe (we already send everything)
-                                debug!("Client subscribed");
+                                let control = serde_json::from_value::<SubscribeRequest>(msg)
+                                    .map_err(|e| e.to_string())
+                                    .and_then(Subscription::from_request);
+                                let control = match control {
+                                    Ok(subscription) => {
+                                        debug!("Client subscribed: {:?}", subscription);
+                                        Control::Subscribe(subscription)
+                                    }
+                                    Err(e) => Control::Error(format!("invalid subscribe: {}", e)),
+                                };
+                                if control_tx.send(control).is_err() {
+                                    break;
+                                }
                             }
                             Some("ping") => {
                                 debug!("Client ping");
@@ -136,3 +524,106 @@ async fn handle_socket(socket: WebSocket, state: Arc<AppState>) {
 
     info!("WebSocket client disconnected");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn subscription(json: JsonValue) -> Subscription {
+        Subscription::from_request(serde_json::from_value(json).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_empty_subscription_passes_everything() {
+        let sub = subscription(serde_json::json!({"type": "subscribe"}));
+        assert!(matches!(sub.apply(&LiveMessage::parse("{\"type\":\"signal\"}")), Some(Cow::Borrowed(_))));
+    }
+
+    #[test]
+    fn test_filters() {
+        let sub = subscription(serde_json::json!({
+            "type": "subscribe",
+            "bbox": [37.0, 126.0, 38.0, 127.0],
+            "types": ["position_update", "initial"],
+            "min_alt": 1000,
+        }));
+        let position = |lat: f64, lon: f64, altitude: i32| {
+            LiveMessage::from_value(
+                &serde_json::json!({"type": "position_update", "icao": "71BE11", "lat": lat, "lon": lon, "altitude": altitude}),
+            )
+        };
+        assert!(sub.apply(&position(37.5, 126.5, 5000)).is_some());
+        assert!(sub.apply(&position(35.0, 126.5, 5000)).is_none());
+        assert!(sub.apply(&position(37.5, 126.5, 500)).is_none());
+        // No position yet: can't be placed outside the box, while 0,0 is
+        // a position like any other
+        let unplaced = serde_json::json!({"type": "position_update", "icao": "71BE11", "lat": null, "lon": null, "altitude": 5000});
+        assert!(sub.apply(&LiveMessage::from_value(&unplaced)).is_some());
+        assert!(sub.apply(&position(0.0, 0.0, 5000)).is_none());
+        assert!(sub.apply(&LiveMessage::parse("{\"type\":\"signal\"}")).is_none());
+
+        let initial = serde_json::json!({"type": "initial", "aircraft": [
+            {"icao": "71BE11", "lat": 37.5, "lon": 126.5, "altitude": 5000},
+            {"icao": "71BE12", "lat": 35.0, "lon": 126.5, "altitude": 5000},
+        ]});
+        let initial = LiveMessage::from_value(&initial);
+        let filtered: JsonValue = serde_json::from_str(&sub.apply(&initial).unwrap()).unwrap();
+        assert_eq!(filtered["aircraft"].as_array().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_units() {
+        let sub = subscription(serde_json::json!({
+            "type": "subscribe",
+            "min_alt": 1000,
+            "altitude_unit": "m",
+            "speed_unit": "kph",
+        }));
+        let position = |altitude: i32| {
+            LiveMessage::from_value(&serde_json::json!({"type": "position_update", "icao": "71BE11", "altitude": altitude, "speed": 100.0}))
+        };
+        // 3000 ft is under 1000 m
+        assert!(sub.apply(&position(3000)).is_none());
+        let json: JsonValue = serde_json::from_str(&sub.apply(&position(5000)).unwrap()).unwrap();
+        assert_eq!((json["altitude"].as_i64(), json["speed"].as_f64()), (Some(1524), Some(185.2)));
+        assert_eq!(sub.to_json()["altitude_unit"], "m");
+
+        // Units alone don't filter anything out
+        let sub = subscription(serde_json::json!({"type": "subscribe", "speed_unit": "mps"}));
+        assert!(!sub.is_empty());
+        assert!(sub.apply(&LiveMessage::parse("{\"type\":\"signal\"}")).is_some());
+    }
+
+    #[test]
+    fn test_icao_filter() {
+        let sub = subscription(serde_json::json!({"icao": ["71be11"]}));
+        assert!(sub.apply(&LiveMessage::parse("{\"type\":\"alert\",\"icao\":\"71BE11\"}")).is_some());
+        assert!(sub.apply(&LiveMessage::parse("{\"type\":\"alert\",\"icao\":\"71BE12\"}")).is_none());
+        assert!(sub.apply(&LiveMessage::parse("{\"type\":\"signal\"}")).is_some());
+
+        let bad = SubscribeRequest {
+            bbox: Some([38.0, 126.0, 37.0, 127.0]),
+            ..Default::default()
+        };
+        assert!(Subscription::from_request(bad).is_err());
+    }
+
+    #[test]
+    fn test_deflate_frames() {
+        use flate2::read::DeflateDecoder;
+        use std::io::Read;
+
+        let short = "{\"type\":\"signal\"}".to_string();
+        assert!(matches!(frame(short.clone(), true), Message::Text(t) if t == short));
+
+        let long = format!("{{\"type\":\"initial\",\"aircraft\":[{}]}}", vec!["{\"icao\":\"4840D6\"}"; 64].join(","));
+        assert!(matches!(frame(long.clone(), false), Message::Text(t) if t == long));
+        let Message::Binary(compressed) = frame(long.clone(), true) else {
+            panic!("expected a binary frame");
+        };
+        assert!(compressed.len() < long.len());
+        let mut inflated = String::new();
+        DeflateDecoder::new(&compressed[..]).read_to_string(&mut inflated).unwrap();
+        assert_eq!(inflated, long);
+    }
+}
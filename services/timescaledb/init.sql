@@ -10,9 +10,23 @@ CREATE TABLE IF NOT EXISTS aircraft_info (
     aircraft_type VARCHAR(10),
     first_seen TIMESTAMPTZ DEFAULT NOW(),
     last_seen TIMESTAMPTZ DEFAULT NOW(),
-    message_count BIGINT DEFAULT 1
+    message_count BIGINT DEFAULT 1,
+    squawk VARCHAR(4),
+    last_device_id VARCHAR(64)
+);
+
+-- Squawk codes each aircraft has set, with the code each replaced
+CREATE TABLE IF NOT EXISTS squawk_changes (
+    time TIMESTAMPTZ NOT NULL,
+    icao_address VARCHAR(6) NOT NULL,
+    squawk VARCHAR(4) NOT NULL,
+    previous_squawk VARCHAR(4),  -- NULL for the first code seen
+    device_id VARCHAR(64)
 );
 
+CREATE INDEX IF NOT EXISTS idx_squawk_changes_icao ON squawk_changes (icao_address, time DESC);
+CREATE INDEX IF NOT EXISTS idx_squawk_changes_time ON squawk_changes (time DESC);
+
 -- Aircraft positions (time-series data)
 CREATE TABLE IF NOT EXISTS aircraft_positions (
     time TIMESTAMPTZ NOT NULL,
@@ -22,8 +36,11 @@ CREATE TABLE IF NOT EXISTS aircraft_positions (
     longitude DOUBLE PRECISION,
     altitude_ft INTEGER,
     ground_speed_kts REAL,
-    heading_deg REAL,
-    vertical_rate_fpm INTEGER,
+    track_deg REAL,             -- Ground track, degrees true
+    magnetic_heading_deg REAL,  -- Where the nose points, degrees magnetic
+    vertical_rate_fpm INTEGER,  -- Positive when climbing, from either source
+    baro_rate_fpm INTEGER,      -- Latest barometric vertical rate
+    geom_rate_fpm INTEGER,      -- Latest GNSS (geometric) vertical rate
     squawk VARCHAR(4),
     signal_strength_db REAL,
     raw_message BYTEA
@@ -51,25 +68,8 @@ SELECT add_compression_policy('aircraft_positions', INTERVAL '1 day', if_not_exi
 -- Create retention policy (keep 30 days of data)
 SELECT add_retention_policy('aircraft_positions', INTERVAL '30 days', if_not_exists => TRUE);
 
--- Function to update aircraft_info on new message
-CREATE OR REPLACE FUNCTION update_aircraft_info()
-RETURNS TRIGGER AS $$
-BEGIN
-    INSERT INTO aircraft_info (icao_address, last_seen, message_count)
-    VALUES (NEW.icao_address, NEW.time, 1)
-    ON CONFLICT (icao_address) DO UPDATE SET
-        last_seen = NEW.time,
-        message_count = aircraft_info.message_count + 1;
-    RETURN NEW;
-END;
-$$ LANGUAGE plpgsql;
-
--- Trigger to auto-update aircraft_info
-DROP TRIGGER IF EXISTS trg_update_aircraft_info ON aircraft_positions;
-CREATE TRIGGER trg_update_aircraft_info
-    AFTER INSERT ON aircraft_positions
-    FOR EACH ROW
-    EXECUTE FUNCTION update_aircraft_info();
+-- aircraft_info is maintained by the gateway, which counts received
+-- messages rather than stored rows
 
 -- View for current aircraft state (most recent position per aircraft)
 CREATE OR REPLACE VIEW current_aircraft AS
@@ -81,12 +81,15 @@ SELECT DISTINCT ON (p.icao_address)
     p.longitude,
     p.altitude_ft,
     p.ground_speed_kts,
-    p.heading_deg,
+    p.track_deg,
+    p.magnetic_heading_deg,
     p.vertical_rate_fpm,
     p.squawk,
     p.device_id,
     p.time as last_seen,
-    i.message_count
+    i.message_count,
+    p.baro_rate_fpm,
+    p.geom_rate_fpm
 FROM aircraft_positions p
 LEFT JOIN aircraft_info i ON p.icao_address = i.icao_address
 WHERE p.time > NOW() - INTERVAL '5 minutes'
@@ -103,7 +106,8 @@ BEGIN
         'longitude', NEW.longitude,
         'altitude_ft', NEW.altitude_ft,
         'ground_speed_kts', NEW.ground_speed_kts,
-        'heading_deg', NEW.heading_deg,
+        'track_deg', NEW.track_deg,
+        'magnetic_heading_deg', NEW.magnetic_heading_deg,
         'vertical_rate_fpm', NEW.vertical_rate_fpm,
         'time', NEW.time
     )::text);
@@ -128,7 +132,11 @@ CREATE TABLE IF NOT EXISTS sdr_status (
     ppm_error INTEGER,
     last_heartbeat TIMESTAMPTZ DEFAULT NOW(),
     messages_per_second REAL DEFAULT 0,
-    error_message TEXT
+    error_message TEXT,
+    software_version VARCHAR(32),
+    uptime_secs BIGINT,
+    decoder JSONB,
+    clock_offset_ms BIGINT
 );
 
 -- Signal metrics table (time-series)
@@ -154,6 +162,27 @@ CREATE INDEX IF NOT EXISTS idx_signal_metrics_device ON signal_metrics (device_i
 -- Retention policy for signal metrics (keep 7 days)
 SELECT add_retention_policy('signal_metrics', INTERVAL '7 days', if_not_exists => TRUE);
 
+-- SDR heartbeat history (one row per device status update)
+CREATE TABLE IF NOT EXISTS sdr_status_history (
+    time TIMESTAMPTZ NOT NULL,
+    device_id VARCHAR(64) NOT NULL,
+    connected BOOLEAN,
+    sample_rate INTEGER,
+    center_freq BIGINT,
+    gain_db REAL,
+    software_version VARCHAR(32),
+    decoder JSONB
+);
+
+SELECT create_hypertable('sdr_status_history', 'time',
+    chunk_time_interval => INTERVAL '1 day',
+    if_not_exists => TRUE
+);
+
+CREATE INDEX IF NOT EXISTS idx_sdr_status_history_device ON sdr_status_history (device_id, time DESC);
+
+SELECT add_retention_policy('sdr_status_history', INTERVAL '7 days', if_not_exists => TRUE);
+
 -- View for current SDR status
 CREATE OR REPLACE VIEW current_sdr_status AS
 SELECT
@@ -172,7 +201,11 @@ SELECT
     END as status,
     m.signal_power_db,
     m.noise_floor_db,
-    m.snr_db
+    m.snr_db,
+    s.software_version,
+    s.uptime_secs,
+    s.decoder,
+    s.clock_offset_ms
 FROM sdr_status s
 LEFT JOIN LATERAL (
     SELECT signal_power_db, noise_floor_db, snr_db
@@ -182,6 +215,137 @@ LEFT JOIN LATERAL (
     LIMIT 1
 ) m ON true;
 
+-- Raw frame archive (opt-in via RAW_ARCHIVE=db on the gateway)
+-- Keeps undecoded frames so history can be re-decoded when the parser improves
+CREATE TABLE IF NOT EXISTS raw_frames (
+    time TIMESTAMPTZ NOT NULL,
+    device_id VARCHAR(64) NOT NULL,
+    raw_message BYTEA NOT NULL,
+    signal_level INTEGER
+);
+
+SELECT create_hypertable('raw_frames', 'time',
+    chunk_time_interval => INTERVAL '1 hour',
+    if_not_exists => TRUE
+);
+
+-- Raw frames compress very well; compress after 1 hour
+ALTER TABLE raw_frames SET (
+    timescaledb.compress,
+    timescaledb.compress_segmentby = 'device_id'
+);
+
+SELECT add_compression_policy('raw_frames', INTERVAL '1 hour', if_not_exists => TRUE);
+SELECT add_retention_policy('raw_frames', INTERVAL '14 days', if_not_exists => TRUE);
+
+-- Flight sessions (maintained by the gateway's flight segmenter)
+CREATE TABLE IF NOT EXISTS flights (
+    icao_address VARCHAR(6) NOT NULL,
+    callsign VARCHAR(8),
+    first_seen TIMESTAMPTZ NOT NULL,
+    last_seen TIMESTAMPTZ NOT NULL,
+    max_altitude_ft INTEGER,
+    position_count INTEGER DEFAULT 0,
+    device_id VARCHAR(64),
+    PRIMARY KEY (icao_address, first_seen)
+);
+
+CREATE INDEX IF NOT EXISTS idx_flights_first_seen ON flights (first_seen DESC);
+
+-- Emergency squawk occurrences (7500/7600/7700)
+CREATE TABLE IF NOT EXISTS emergencies (
+    icao_address VARCHAR(6) NOT NULL,
+    started_at TIMESTAMPTZ NOT NULL,
+    last_seen TIMESTAMPTZ NOT NULL,
+    ended_at TIMESTAMPTZ,
+    emergency VARCHAR(16) NOT NULL,
+    squawk VARCHAR(4) NOT NULL,
+    callsign VARCHAR(8),
+    latitude DOUBLE PRECISION,
+    longitude DOUBLE PRECISION,
+    altitude_ft INTEGER,
+    device_id VARCHAR(64),
+    PRIMARY KEY (icao_address, started_at)
+);
+
+CREATE INDEX IF NOT EXISTS idx_emergencies_started_at ON emergencies (started_at DESC);
+CREATE INDEX IF NOT EXISTS idx_emergencies_active ON emergencies (started_at DESC) WHERE ended_at IS NULL;
+
+-- Connected episodes of each capture device, for daily uptime
+CREATE TABLE IF NOT EXISTS receiver_sessions (
+    device_id VARCHAR(64) NOT NULL,
+    started_at TIMESTAMPTZ NOT NULL,
+    last_seen TIMESTAMPTZ NOT NULL,
+    ended_at TIMESTAMPTZ,
+    end_reason VARCHAR(16),
+    PRIMARY KEY (device_id, started_at)
+);
+
+CREATE INDEX IF NOT EXISTS idx_receiver_sessions_last_seen ON receiver_sessions (last_seen DESC);
+
+-- Hourly/daily receiver statistics, rolled up by the gateway (device_id '' = all devices)
+CREATE TABLE IF NOT EXISTS stats_rollups (
+    period VARCHAR(8) NOT NULL,
+    bucket TIMESTAMPTZ NOT NULL,
+    device_id VARCHAR(64) NOT NULL DEFAULT '',
+    messages BIGINT NOT NULL DEFAULT 0,
+    positions BIGINT NOT NULL DEFAULT 0,
+    unique_aircraft BIGINT NOT NULL DEFAULT 0,
+    max_range_km DOUBLE PRECISION NOT NULL DEFAULT 0,
+    frames_decoded BIGINT NOT NULL DEFAULT 0,
+    crc_errors BIGINT NOT NULL DEFAULT 0,
+    PRIMARY KEY (period, bucket, device_id)
+);
+
+-- Decoded frames per downlink format, alongside stats_rollups
+CREATE TABLE IF NOT EXISTS stats_df_rollups (
+    period VARCHAR(8) NOT NULL,
+    bucket TIMESTAMPTZ NOT NULL,
+    device_id VARCHAR(64) NOT NULL DEFAULT '',
+    downlink_format INTEGER NOT NULL,
+    frames BIGINT NOT NULL DEFAULT 0,
+    PRIMARY KEY (period, bucket, device_id, downlink_format)
+);
+
+-- Watchlist rules for the gateway alert engine
+CREATE TABLE IF NOT EXISTS watchlist_rules (
+    id BIGSERIAL PRIMARY KEY,
+    name TEXT NOT NULL,
+    match_kind VARCHAR(16) NOT NULL,
+    pattern TEXT,
+    geofence JSONB,
+    webhook_url TEXT,
+    notifiers JSONB NOT NULL DEFAULT '[]',
+    cooldown_seconds INTEGER NOT NULL DEFAULT 600,
+    enabled BOOLEAN NOT NULL DEFAULT TRUE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Geofences and their logged entry/exit events
+CREATE TABLE IF NOT EXISTS geofences (
+    id BIGSERIAL PRIMARY KEY,
+    name TEXT NOT NULL,
+    shape JSONB NOT NULL,
+    min_altitude_ft INTEGER,
+    max_altitude_ft INTEGER,
+    log_events BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE IF NOT EXISTS geofence_events (
+    time TIMESTAMPTZ NOT NULL,
+    geofence_id BIGINT NOT NULL REFERENCES geofences(id) ON DELETE CASCADE,
+    icao_address VARCHAR(6) NOT NULL,
+    callsign VARCHAR(8),
+    event VARCHAR(8) NOT NULL,
+    reason VARCHAR(16),
+    latitude DOUBLE PRECISION,
+    longitude DOUBLE PRECISION,
+    altitude_ft INTEGER
+);
+
+CREATE INDEX IF NOT EXISTS idx_geofence_events ON geofence_events (geofence_id, time DESC);
+
 -- Grant permissions
 GRANT ALL PRIVILEGES ON ALL TABLES IN SCHEMA public TO adsb;
 GRANT ALL PRIVILEGES ON ALL SEQUENCES IN SCHEMA public TO adsb;
@@ -0,0 +1,23 @@
+-- Migration: Add raw frame archive
+-- Stores raw frames (opt-in via RAW_ARCHIVE=db) for later re-decoding
+
+CREATE TABLE IF NOT EXISTS raw_frames (
+    time TIMESTAMPTZ NOT NULL,
+    device_id VARCHAR(64) NOT NULL,
+    raw_message BYTEA NOT NULL,
+    signal_level INTEGER
+);
+
+SELECT create_hypertable('raw_frames', 'time',
+    chunk_time_interval => INTERVAL '1 hour',
+    if_not_exists => TRUE
+);
+
+-- Raw frames compress very well; compress after 1 hour
+ALTER TABLE raw_frames SET (
+    timescaledb.compress,
+    timescaledb.compress_segmentby = 'device_id'
+);
+
+SELECT add_compression_policy('raw_frames', INTERVAL '1 hour', if_not_exists => TRUE);
+SELECT add_retention_policy('raw_frames', INTERVAL '14 days', if_not_exists => TRUE);
@@ -0,0 +1,15 @@
+-- Migration: Add flight sessions
+-- One row per flight, segmented by the gateway on message gaps and landings
+
+CREATE TABLE IF NOT EXISTS flights (
+    icao_address VARCHAR(6) NOT NULL,
+    callsign VARCHAR(8),
+    first_seen TIMESTAMPTZ NOT NULL,
+    last_seen TIMESTAMPTZ NOT NULL,
+    max_altitude_ft INTEGER,
+    position_count INTEGER DEFAULT 0,
+    device_id VARCHAR(64),
+    PRIMARY KEY (icao_address, first_seen)
+);
+
+CREATE INDEX IF NOT EXISTS idx_flights_first_seen ON flights (first_seen DESC);
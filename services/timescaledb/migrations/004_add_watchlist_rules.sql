@@ -0,0 +1,14 @@
+-- Migration: Add watchlist rules
+-- Rules matched by the gateway alert engine (ICAO, registration, callsign, squawk, geofence)
+
+CREATE TABLE IF NOT EXISTS watchlist_rules (
+    id BIGSERIAL PRIMARY KEY,
+    name TEXT NOT NULL,
+    match_kind VARCHAR(16) NOT NULL,
+    pattern TEXT,
+    geofence JSONB,
+    webhook_url TEXT,
+    cooldown_seconds INTEGER NOT NULL DEFAULT 600,
+    enabled BOOLEAN NOT NULL DEFAULT TRUE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
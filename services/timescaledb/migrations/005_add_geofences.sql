@@ -0,0 +1,26 @@
+-- Migration: Add geofences
+-- User-defined areas with entry/exit detection; events are logged per fence (log_events)
+
+CREATE TABLE IF NOT EXISTS geofences (
+    id BIGSERIAL PRIMARY KEY,
+    name TEXT NOT NULL,
+    shape JSONB NOT NULL,
+    min_altitude_ft INTEGER,
+    max_altitude_ft INTEGER,
+    log_events BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE IF NOT EXISTS geofence_events (
+    time TIMESTAMPTZ NOT NULL,
+    geofence_id BIGINT NOT NULL REFERENCES geofences(id) ON DELETE CASCADE,
+    icao_address VARCHAR(6) NOT NULL,
+    callsign VARCHAR(8),
+    event VARCHAR(8) NOT NULL,
+    reason VARCHAR(16),
+    latitude DOUBLE PRECISION,
+    longitude DOUBLE PRECISION,
+    altitude_ft INTEGER
+);
+
+CREATE INDEX IF NOT EXISTS idx_geofence_events ON geofence_events (geofence_id, time DESC);
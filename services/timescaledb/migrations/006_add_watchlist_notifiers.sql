@@ -0,0 +1,4 @@
+-- Migration: Add notifier sinks to watchlist rules
+-- JSON array of webhook/discord/slack/telegram sink configs per rule
+
+ALTER TABLE watchlist_rules ADD COLUMN IF NOT EXISTS notifiers JSONB NOT NULL DEFAULT '[]';
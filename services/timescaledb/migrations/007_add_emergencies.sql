@@ -0,0 +1,20 @@
+-- Migration: Add emergency squawk occurrences
+-- One row per 7500/7600/7700 episode, opened and closed by the gateway
+
+CREATE TABLE IF NOT EXISTS emergencies (
+    icao_address VARCHAR(6) NOT NULL,
+    started_at TIMESTAMPTZ NOT NULL,
+    last_seen TIMESTAMPTZ NOT NULL,
+    ended_at TIMESTAMPTZ,
+    emergency VARCHAR(16) NOT NULL,
+    squawk VARCHAR(4) NOT NULL,
+    callsign VARCHAR(8),
+    latitude DOUBLE PRECISION,
+    longitude DOUBLE PRECISION,
+    altitude_ft INTEGER,
+    device_id VARCHAR(64),
+    PRIMARY KEY (icao_address, started_at)
+);
+
+CREATE INDEX IF NOT EXISTS idx_emergencies_started_at ON emergencies (started_at DESC);
+CREATE INDEX IF NOT EXISTS idx_emergencies_active ON emergencies (started_at DESC) WHERE ended_at IS NULL;
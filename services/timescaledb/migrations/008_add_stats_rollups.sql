@@ -0,0 +1,15 @@
+-- Migration: Add receiver statistics rollups
+-- Hourly and daily counters flushed by the gateway; device_id '' holds the all-devices total
+
+CREATE TABLE IF NOT EXISTS stats_rollups (
+    period VARCHAR(8) NOT NULL,
+    bucket TIMESTAMPTZ NOT NULL,
+    device_id VARCHAR(64) NOT NULL DEFAULT '',
+    messages BIGINT NOT NULL DEFAULT 0,
+    positions BIGINT NOT NULL DEFAULT 0,
+    unique_aircraft BIGINT NOT NULL DEFAULT 0,
+    max_range_km DOUBLE PRECISION NOT NULL DEFAULT 0,
+    frames_decoded BIGINT NOT NULL DEFAULT 0,
+    crc_errors BIGINT NOT NULL DEFAULT 0,
+    PRIMARY KEY (period, bucket, device_id)
+);
@@ -0,0 +1,20 @@
+-- Migration: Add SDR heartbeat history
+-- One row per device status update, kept for 7 days
+
+CREATE TABLE IF NOT EXISTS sdr_status_history (
+    time TIMESTAMPTZ NOT NULL,
+    device_id VARCHAR(64) NOT NULL,
+    connected BOOLEAN,
+    sample_rate INTEGER,
+    center_freq BIGINT,
+    gain_db REAL
+);
+
+SELECT create_hypertable('sdr_status_history', 'time',
+    chunk_time_interval => INTERVAL '1 day',
+    if_not_exists => TRUE
+);
+
+CREATE INDEX IF NOT EXISTS idx_sdr_status_history_device ON sdr_status_history (device_id, time DESC);
+
+SELECT add_retention_policy('sdr_status_history', INTERVAL '7 days', if_not_exists => TRUE);
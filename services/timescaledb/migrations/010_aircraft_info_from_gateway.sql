@@ -0,0 +1,17 @@
+-- Migration: Maintain aircraft_info from the gateway
+-- The gateway now upserts callsign, category, squawk, last device and message
+-- count on every write, replacing the per-row trigger, and logs squawk changes
+
+ALTER TABLE aircraft_info ADD COLUMN IF NOT EXISTS squawk VARCHAR(4);
+ALTER TABLE aircraft_info ADD COLUMN IF NOT EXISTS last_device_id VARCHAR(64);
+
+CREATE TABLE IF NOT EXISTS squawk_history (
+    time TIMESTAMPTZ NOT NULL,
+    icao_address VARCHAR(6) NOT NULL,
+    squawk VARCHAR(4) NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_squawk_history_icao ON squawk_history (icao_address, time DESC);
+
+DROP TRIGGER IF EXISTS trg_update_aircraft_info ON aircraft_positions;
+DROP FUNCTION IF EXISTS update_aircraft_info();
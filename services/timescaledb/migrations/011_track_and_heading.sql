@@ -0,0 +1,58 @@
+-- Migration: Store ground track and magnetic heading separately
+-- heading_deg held whichever the aircraft last sent: the ground track from
+-- ground-speed velocity messages or the magnetic heading from airspeed ones.
+-- Existing values are kept as track, which nearly all of them were.
+
+DO $$
+BEGIN
+    IF EXISTS (
+        SELECT 1 FROM information_schema.columns
+        WHERE table_name = 'aircraft_positions' AND column_name = 'heading_deg'
+    ) THEN
+        ALTER TABLE aircraft_positions RENAME COLUMN heading_deg TO track_deg;
+    END IF;
+END $$;
+
+ALTER TABLE aircraft_positions ADD COLUMN IF NOT EXISTS magnetic_heading_deg REAL;
+
+-- The view's column names change, so it can't be replaced in place
+DROP VIEW IF EXISTS current_aircraft;
+CREATE VIEW current_aircraft AS
+SELECT DISTINCT ON (p.icao_address)
+    p.icao_address,
+    i.callsign,
+    i.category,
+    p.latitude,
+    p.longitude,
+    p.altitude_ft,
+    p.ground_speed_kts,
+    p.track_deg,
+    p.magnetic_heading_deg,
+    p.vertical_rate_fpm,
+    p.squawk,
+    p.device_id,
+    p.time as last_seen,
+    i.message_count
+FROM aircraft_positions p
+LEFT JOIN aircraft_info i ON p.icao_address = i.icao_address
+WHERE p.time > NOW() - INTERVAL '5 minutes'
+ORDER BY p.icao_address, p.time DESC;
+
+CREATE OR REPLACE FUNCTION notify_new_position()
+RETURNS TRIGGER AS $$
+BEGIN
+    PERFORM pg_notify('new_position', json_build_object(
+        'icao_address', NEW.icao_address,
+        'device_id', NEW.device_id,
+        'latitude', NEW.latitude,
+        'longitude', NEW.longitude,
+        'altitude_ft', NEW.altitude_ft,
+        'ground_speed_kts', NEW.ground_speed_kts,
+        'track_deg', NEW.track_deg,
+        'magnetic_heading_deg', NEW.magnetic_heading_deg,
+        'vertical_rate_fpm', NEW.vertical_rate_fpm,
+        'time', NEW.time
+    )::text);
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
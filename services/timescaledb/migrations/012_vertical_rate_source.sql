@@ -0,0 +1,30 @@
+-- Migration: Store vertical rate by source
+-- Velocity messages say whether their vertical rate is barometric or GNSS
+-- (geometric). vertical_rate_fpm keeps whichever arrived last; the new
+-- columns hold the latest of each.
+
+ALTER TABLE aircraft_positions ADD COLUMN IF NOT EXISTS baro_rate_fpm INTEGER;
+ALTER TABLE aircraft_positions ADD COLUMN IF NOT EXISTS geom_rate_fpm INTEGER;
+
+CREATE OR REPLACE VIEW current_aircraft AS
+SELECT DISTINCT ON (p.icao_address)
+    p.icao_address,
+    i.callsign,
+    i.category,
+    p.latitude,
+    p.longitude,
+    p.altitude_ft,
+    p.ground_speed_kts,
+    p.track_deg,
+    p.magnetic_heading_deg,
+    p.vertical_rate_fpm,
+    p.squawk,
+    p.device_id,
+    p.time as last_seen,
+    i.message_count,
+    p.baro_rate_fpm,
+    p.geom_rate_fpm
+FROM aircraft_positions p
+LEFT JOIN aircraft_info i ON p.icao_address = i.icao_address
+WHERE p.time > NOW() - INTERVAL '5 minutes'
+ORDER BY p.icao_address, p.time DESC;
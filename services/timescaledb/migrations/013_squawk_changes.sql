@@ -0,0 +1,19 @@
+-- Migration: Log squawk changes with the code they replaced
+-- squawk_history becomes squawk_changes and gains the previous code and the
+-- device that received the new one. Rows logged before this migration have
+-- no previous code.
+
+ALTER TABLE IF EXISTS squawk_history RENAME TO squawk_changes;
+ALTER INDEX IF EXISTS idx_squawk_history_icao RENAME TO idx_squawk_changes_icao;
+
+CREATE TABLE IF NOT EXISTS squawk_changes (
+    time TIMESTAMPTZ NOT NULL,
+    icao_address VARCHAR(6) NOT NULL,
+    squawk VARCHAR(4) NOT NULL
+);
+
+ALTER TABLE squawk_changes ADD COLUMN IF NOT EXISTS previous_squawk VARCHAR(4);
+ALTER TABLE squawk_changes ADD COLUMN IF NOT EXISTS device_id VARCHAR(64);
+
+CREATE INDEX IF NOT EXISTS idx_squawk_changes_icao ON squawk_changes (icao_address, time DESC);
+CREATE INDEX IF NOT EXISTS idx_squawk_changes_time ON squawk_changes (time DESC);
@@ -0,0 +1,15 @@
+-- Migration: Add receiver sessions
+-- One row per connected episode of a capture device, opened and closed by
+-- the gateway from its gRPC streams and heartbeats; /api/stats derives daily
+-- uptime from them
+
+CREATE TABLE IF NOT EXISTS receiver_sessions (
+    device_id VARCHAR(64) NOT NULL,
+    started_at TIMESTAMPTZ NOT NULL,
+    last_seen TIMESTAMPTZ NOT NULL,
+    ended_at TIMESTAMPTZ,
+    end_reason VARCHAR(16),
+    PRIMARY KEY (device_id, started_at)
+);
+
+CREATE INDEX IF NOT EXISTS idx_receiver_sessions_last_seen ON receiver_sessions (last_seen DESC);
@@ -0,0 +1,12 @@
+-- Migration: Add per-downlink-format statistics rollups
+-- Hourly and daily decoded frame counts per DF, flushed by the gateway with
+-- stats_rollups; device_id '' holds the all-devices total
+
+CREATE TABLE IF NOT EXISTS stats_df_rollups (
+    period VARCHAR(8) NOT NULL,
+    bucket TIMESTAMPTZ NOT NULL,
+    device_id VARCHAR(64) NOT NULL DEFAULT '',
+    downlink_format INTEGER NOT NULL,
+    frames BIGINT NOT NULL DEFAULT 0,
+    PRIMARY KEY (period, bucket, device_id, downlink_format)
+);
@@ -0,0 +1,36 @@
+-- Migration: Keep the clock offset capture hosts measure to the gateway
+-- Hosts ping the gateway and report gateway time minus their own with every
+-- status heartbeat; sdr_status holds the latest.
+
+ALTER TABLE sdr_status ADD COLUMN IF NOT EXISTS clock_offset_ms BIGINT;
+
+CREATE OR REPLACE VIEW current_sdr_status AS
+SELECT
+    s.device_id,
+    s.connected,
+    s.sample_rate,
+    s.center_freq,
+    s.gain_db,
+    s.last_heartbeat,
+    s.messages_per_second,
+    s.error_message,
+    CASE
+        WHEN s.last_heartbeat > NOW() - INTERVAL '10 seconds' THEN 'active'
+        WHEN s.last_heartbeat > NOW() - INTERVAL '30 seconds' THEN 'stale'
+        ELSE 'disconnected'
+    END as status,
+    m.signal_power_db,
+    m.noise_floor_db,
+    m.snr_db,
+    s.software_version,
+    s.uptime_secs,
+    s.decoder,
+    s.clock_offset_ms
+FROM sdr_status s
+LEFT JOIN LATERAL (
+    SELECT signal_power_db, noise_floor_db, snr_db
+    FROM signal_metrics
+    WHERE device_id = s.device_id
+    ORDER BY time DESC
+    LIMIT 1
+) m ON true;